@@ -1,31 +1,61 @@
 mod http_handlers;
 
 use hyper::Method;
-use mmids_core::config::{parse as parse_config_file, MmidsConfig};
+use mmids_core::config::{
+    get_workflow_start_order, parse_file as parse_config_file, validate as validate_config,
+    watch as watch_config_file, MmidsConfig,
+};
 use mmids_core::endpoints::ffmpeg::{start_ffmpeg_endpoint, FfmpegEndpointRequest};
 use mmids_core::endpoints::rtmp_server::{start_rtmp_server_endpoint, RtmpEndpointRequest};
+use mmids_core::endpoints::srt_server::{start_srt_server_endpoint, SrtEndpointRequest};
 use mmids_core::event_hub::{start_event_hub, PublishEventRequest, SubscriptionRequest};
 use mmids_core::http_api::handlers;
 use mmids_core::http_api::routing::{PathPart, Route, RoutingTable};
-use mmids_core::http_api::HttpApiShutdownSignal;
+use mmids_core::http_api::{HttpApiShutdownSignal, HttpApiTlsOptions, DEFAULT_DRAIN_TIMEOUT};
 use mmids_core::net::tcp::{start_socket_manager, TlsOptions};
+use mmids_core::reactors::executors::file_executor::FileReactorExecutorGenerator;
+use mmids_core::reactors::executors::hashing_executor::HashingReactorExecutorGenerator;
 use mmids_core::reactors::executors::simple_http_executor::SimpleHttpExecutorGenerator;
 use mmids_core::reactors::executors::ReactorExecutorFactory;
 use mmids_core::reactors::manager::{
     start_reactor_manager, CreateReactorResult, ReactorManagerRequest,
 };
 use mmids_core::workflows::definitions::WorkflowStepType;
+use mmids_core::workflows::log_filter::WorkflowLogFilterLayer;
 use mmids_core::workflows::manager::{
-    start_workflow_manager, WorkflowManagerRequest, WorkflowManagerRequestOperation,
+    start_workflow_manager_with_options, WorkflowManagerOptions, WorkflowManagerRequest,
+    WorkflowManagerRequestOperation, DEFAULT_RESTORED_WORKFLOW_GRACE_PERIOD,
 };
+use mmids_core::workflows::steps::audio_only::AudioOnlyStepGenerator;
+use mmids_core::workflows::steps::bitrate_guard::BitrateGuardStepGenerator;
+use mmids_core::workflows::steps::cue_inject::CueInjectStepGenerator;
+use mmids_core::workflows::steps::demux::DemuxStepGenerator;
+use mmids_core::workflows::steps::drop_bframes::DropBFramesStepGenerator;
+use mmids_core::workflows::steps::exec_hook::ExecHookStepGenerator;
 use mmids_core::workflows::steps::factory::WorkflowStepFactory;
+use mmids_core::workflows::steps::failover::FailoverStepGenerator;
 use mmids_core::workflows::steps::ffmpeg_hls::FfmpegHlsStepGenerator;
 use mmids_core::workflows::steps::ffmpeg_pull::FfmpegPullStepGenerator;
 use mmids_core::workflows::steps::ffmpeg_rtmp_push::FfmpegRtmpPushStepGenerator;
 use mmids_core::workflows::steps::ffmpeg_transcode::FfmpegTranscoderStepGenerator;
+use mmids_core::workflows::steps::filter::FilterStepGenerator;
+use mmids_core::workflows::steps::gap_monitor::GapMonitorStepGenerator;
+use mmids_core::workflows::steps::interleave::InterleaveStepGenerator;
+use mmids_core::workflows::steps::keyframe_capture::KeyframeCaptureStepGenerator;
+use mmids_core::workflows::steps::max_duration::MaxDurationStepGenerator;
+use mmids_core::workflows::steps::mirror_to_workflow::MirrorToWorkflowStepGenerator;
+use mmids_core::workflows::steps::normalize_timestamps::NormalizeTimestampsStepGenerator;
+use mmids_core::workflows::steps::record::RecordStepGenerator;
+use mmids_core::workflows::steps::rename_stream::RenameStreamStepGenerator;
+use mmids_core::workflows::steps::rtmp_pull::RtmpPullStepGenerator;
+use mmids_core::workflows::steps::rtmp_push::RtmpPushStepGenerator;
 use mmids_core::workflows::steps::rtmp_receive::RtmpReceiverStepGenerator;
 use mmids_core::workflows::steps::rtmp_watch::RtmpWatchStepGenerator;
+use mmids_core::workflows::steps::srt_receive::SrtReceiverStepGenerator;
+use mmids_core::workflows::steps::stream_stats::StreamStatsStepGenerator;
+use mmids_core::workflows::steps::tag::TagStepGenerator;
 use mmids_core::workflows::steps::workflow_forwarder::WorkflowForwarderStepGenerator;
+use mmids_core::workflows::{DefinitionUpdateResult, StepRestartPolicy, WorkflowRunnerOptions};
 use mmids_gstreamer::encoders::{
     AudioCopyEncoderGenerator, AudioDropEncoderGenerator, AvencAacEncoderGenerator, EncoderFactory,
     VideoCopyEncoderGenerator, VideoDropEncoderGenerator, X264EncoderGenerator,
@@ -34,20 +64,47 @@ use mmids_gstreamer::endpoints::gst_transcoder::{start_gst_transcoder, GstTransc
 use mmids_gstreamer::steps::basic_transcoder::BasicTranscodeStepGenerator;
 use native_tls::Identity;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
 use tokio::sync::oneshot::{channel, Sender};
-use tracing::{info, warn, Level};
-use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing::{error, info, warn, Level};
 use tracing_subscriber::{fmt, layer::SubscriberExt};
 
+const CONFIG_FILE: &str = "mmids.config";
+const CHECK_CONFIG_FLAG: &str = "--check-config";
+
+/// How long to wait at startup for a workflow other workflows depend on to become active
+const DEPENDENCY_START_TIMEOUT: Duration = Duration::from_secs(30);
+
 const RTMP_RECEIVE: &str = "rtmp_receive";
 const RTMP_WATCH: &str = "rtmp_watch";
+const RTMP_PUSH: &str = "rtmp_push";
+const RTMP_PULL: &str = "rtmp_pull";
+const SRT_RECEIVE: &str = "srt_receive";
 const FORWARD_STEP: &str = "forward_to_workflow";
 const BASIC_TRANSCODE_STEP: &str = "basic_transcode";
+const AUDIO_ONLY_STEP: &str = "audio_only";
+const STREAM_STATS_STEP: &str = "stream_stats";
+const TAG_STEP: &str = "tag";
+const FILTER_STEP: &str = "filter";
+const RENAME_STREAM_STEP: &str = "rename_stream";
+const KEYFRAME_CAPTURE_STEP: &str = "keyframe_capture";
+const MAX_DURATION_STEP: &str = "max_duration";
+const RECORD_STEP: &str = "record";
+const NORMALIZE_TIMESTAMPS_STEP: &str = "normalize_timestamps";
+const FAILOVER_STEP: &str = "failover";
+const MIRROR_TO_WORKFLOW_STEP: &str = "mirror_to_workflow";
+const INTERLEAVE_STEP: &str = "interleave";
+const CUE_INJECT_STEP: &str = "cue_inject";
+const EXEC_HOOK_STEP: &str = "exec_hook";
+const DROP_BFRAMES_STEP: &str = "drop_bframes";
+const GAP_MONITOR_STEP: &str = "gap_monitor";
+const BITRATE_GUARD_STEP: &str = "bitrate_guard";
+const DEMUX_STEP: &str = "demux";
 
 // ffmpeg steps will be depreciated at some point
 const FFMPEG_TRANSCODE: &str = "ffmpeg_transcode";
@@ -57,12 +114,17 @@ const FFMPEG_PULL: &str = "ffmpeg_pull";
 
 struct Endpoints {
     rtmp: UnboundedSender<RtmpEndpointRequest>,
+    srt: UnboundedSender<SrtEndpointRequest>,
     ffmpeg: UnboundedSender<FfmpegEndpointRequest>,
     gst_transcoder: UnboundedSender<GstTranscoderRequest>,
 }
 
 #[tokio::main]
 pub async fn main() {
+    if env::args().skip(1).any(|arg| arg == CHECK_CONFIG_FLAG) {
+        std::process::exit(check_config());
+    }
+
     // Start logging
     let log_dir = get_log_directory();
     let mut app_log_path = PathBuf::from(log_dir.clone());
@@ -83,12 +145,13 @@ pub async fn main() {
 
     let appender = tracing_appender::rolling::hourly(app_log_path.clone(), "application.log");
     let (non_blocking, _guard) = tracing_appender::non_blocking(appender);
-    let stdout_writer = std::io::stdout.with_max_level(log_level);
-    let json_writer = non_blocking.with_max_level(log_level);
 
+    // Log levels are filtered by the workflow log filter instead of by each writer, so
+    // individual workflows can have their log level raised
     let subscriber = tracing_subscriber::registry()
-        .with(fmt::Layer::new().with_writer(stdout_writer).pretty())
-        .with(fmt::Layer::new().with_writer(json_writer).json());
+        .with(WorkflowLogFilterLayer::new(log_level))
+        .with(fmt::Layer::new().with_writer(std::io::stdout).pretty())
+        .with(fmt::Layer::new().with_writer(non_blocking).json());
 
     tracing::subscriber::set_global_default(subscriber).expect("Unable to set a global collector");
 
@@ -98,25 +161,97 @@ pub async fn main() {
     let config = read_config();
     let tls_options = load_tls_options(&config).await;
     let endpoints = start_endpoints(&config, tls_options, log_dir);
+    let rtmp_endpoint = endpoints.rtmp.clone();
     let (pub_sender, sub_sender) = start_event_hub();
     let reactor_manager = start_reactor(&config, sub_sender.clone()).await;
     let step_factory = register_steps(endpoints, sub_sender, reactor_manager);
-    let manager = start_workflows(&config, step_factory, pub_sender);
-    let http_api_shutdown = start_http_api(&config, manager);
+    if let Err(errors) = validate_config(&config, &step_factory) {
+        for error in &errors {
+            error!("{}", error);
+        }
+
+        panic!("Found {} problem(s) in the config file", errors.len());
+    }
+
+    let manager = start_workflows(&config, step_factory.clone(), pub_sender).await;
+    if let Err(error) = watch_config_file(Path::new(CONFIG_FILE), manager.clone(), step_factory) {
+        warn!("Config file changes will not be reloaded: {}", error);
+    }
+
+    let http_api_shutdown = start_http_api(&config, manager.clone(), rtmp_endpoint);
 
     tokio::signal::ctrl_c()
         .await
         .expect("Failed to install ctrl+c signal handler");
 
     if let Some(sender) = http_api_shutdown {
-        let _ = sender.send(HttpApiShutdownSignal {});
+        let (response_sender, response_receiver) = channel();
+        let _ = sender.send(HttpApiShutdownSignal {
+            drain_timeout: get_http_api_drain_timeout(&config),
+            response_channel: Some(response_sender),
+        });
+
+        let _ = response_receiver.await;
     }
+
+    info!("Stopping all workflows");
+    let (sender, receiver) = channel();
+    let _ = manager.send(WorkflowManagerRequest {
+        request_id: "mmids-app-shutdown".to_string(),
+        operation: WorkflowManagerRequestOperation::StopAllWorkflows {
+            response_channel: Some(sender),
+        },
+    });
+
+    let _ = receiver.await;
 }
 
 fn read_config() -> MmidsConfig {
-    let contents = std::fs::read_to_string("mmids.config").expect("Failed to read 'mmids.config'");
+    return parse_config_file(Path::new(CONFIG_FILE)).expect("Failed to parse config file");
+}
+
+/// Parses and validates the config file without starting any endpoints or workflows, printing
+/// every problem that was found.  Returns the exit code the process should end with.
+fn check_config() -> i32 {
+    let config = match parse_config_file(Path::new(CONFIG_FILE)) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Failed to parse config file '{}': {}", CONFIG_FILE, error);
+            return 1;
+        }
+    };
+
+    // Steps are only validated and never generated, so nothing needs to be listening on the
+    // other end of these channels
+    let endpoints = Endpoints {
+        rtmp: unbounded_channel().0,
+        srt: unbounded_channel().0,
+        ffmpeg: unbounded_channel().0,
+        gst_transcoder: unbounded_channel().0,
+    };
+
+    let step_factory = register_steps(endpoints, unbounded_channel().0, unbounded_channel().0);
+
+    match validate_config(&config, &step_factory) {
+        Ok(()) => {
+            println!("Config file '{}' is valid", CONFIG_FILE);
+            0
+        }
+
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("{}", error);
+            }
+
+            eprintln!(
+                "Found {} problem(s) in config file '{}'",
+                errors.len(),
+                CONFIG_FILE
+            );
 
-    return parse_config_file(contents.as_str()).expect("Failed to parse config file");
+            1
+        }
+    }
 }
 
 fn get_log_directory() -> String {
@@ -159,6 +294,27 @@ fn register_steps(
         )
         .expect("Failed to register rtmp_watch step");
 
+    step_factory
+        .register(
+            WorkflowStepType(RTMP_PUSH.to_string()),
+            Box::new(RtmpPushStepGenerator::new()),
+        )
+        .expect("Failed to register rtmp_push step");
+
+    step_factory
+        .register(
+            WorkflowStepType(RTMP_PULL.to_string()),
+            Box::new(RtmpPullStepGenerator::new()),
+        )
+        .expect("Failed to register rtmp_pull step");
+
+    step_factory
+        .register(
+            WorkflowStepType(SRT_RECEIVE.to_string()),
+            Box::new(SrtReceiverStepGenerator::new(endpoints.srt.clone())),
+        )
+        .expect("Failed to register srt_receive step");
+
     step_factory
         .register(
             WorkflowStepType(FFMPEG_TRANSCODE.to_string()),
@@ -203,12 +359,140 @@ fn register_steps(
         .register(
             WorkflowStepType(FORWARD_STEP.to_string()),
             Box::new(WorkflowForwarderStepGenerator::new(
-                subscription_sender,
+                subscription_sender.clone(),
                 reactor_manager,
             )),
         )
         .expect("Failed to register forward_to_workflow step");
 
+    step_factory
+        .register(
+            WorkflowStepType(AUDIO_ONLY_STEP.to_string()),
+            Box::new(AudioOnlyStepGenerator::new()),
+        )
+        .expect("Failed to register audio_only step");
+
+    step_factory
+        .register(
+            WorkflowStepType(STREAM_STATS_STEP.to_string()),
+            Box::new(StreamStatsStepGenerator::new()),
+        )
+        .expect("Failed to register stream_stats step");
+
+    step_factory
+        .register(
+            WorkflowStepType(TAG_STEP.to_string()),
+            Box::new(TagStepGenerator::new()),
+        )
+        .expect("Failed to register tag step");
+
+    step_factory
+        .register(
+            WorkflowStepType(FILTER_STEP.to_string()),
+            Box::new(FilterStepGenerator::new()),
+        )
+        .expect("Failed to register filter step");
+
+    step_factory
+        .register(
+            WorkflowStepType(RENAME_STREAM_STEP.to_string()),
+            Box::new(RenameStreamStepGenerator::new()),
+        )
+        .expect("Failed to register rename_stream step");
+
+    step_factory
+        .register(
+            WorkflowStepType(KEYFRAME_CAPTURE_STEP.to_string()),
+            Box::new(KeyframeCaptureStepGenerator::new()),
+        )
+        .expect("Failed to register keyframe_capture step");
+
+    step_factory
+        .register(
+            WorkflowStepType(MAX_DURATION_STEP.to_string()),
+            Box::new(MaxDurationStepGenerator::new()),
+        )
+        .expect("Failed to register max_duration step");
+
+    step_factory
+        .register(
+            WorkflowStepType(RECORD_STEP.to_string()),
+            Box::new(RecordStepGenerator::new()),
+        )
+        .expect("Failed to register record step");
+
+    step_factory
+        .register(
+            WorkflowStepType(NORMALIZE_TIMESTAMPS_STEP.to_string()),
+            Box::new(NormalizeTimestampsStepGenerator::new()),
+        )
+        .expect("Failed to register normalize_timestamps step");
+
+    step_factory
+        .register(
+            WorkflowStepType(FAILOVER_STEP.to_string()),
+            Box::new(FailoverStepGenerator::new()),
+        )
+        .expect("Failed to register failover step");
+
+    step_factory
+        .register(
+            WorkflowStepType(MIRROR_TO_WORKFLOW_STEP.to_string()),
+            Box::new(MirrorToWorkflowStepGenerator::new(
+                subscription_sender.clone(),
+            )),
+        )
+        .expect("Failed to register mirror_to_workflow step");
+
+    step_factory
+        .register(
+            WorkflowStepType(INTERLEAVE_STEP.to_string()),
+            Box::new(InterleaveStepGenerator::new()),
+        )
+        .expect("Failed to register interleave step");
+
+    step_factory
+        .register(
+            WorkflowStepType(CUE_INJECT_STEP.to_string()),
+            Box::new(CueInjectStepGenerator::new(subscription_sender)),
+        )
+        .expect("Failed to register cue_inject step");
+
+    step_factory
+        .register(
+            WorkflowStepType(EXEC_HOOK_STEP.to_string()),
+            Box::new(ExecHookStepGenerator::new()),
+        )
+        .expect("Failed to register exec_hook step");
+
+    step_factory
+        .register(
+            WorkflowStepType(DROP_BFRAMES_STEP.to_string()),
+            Box::new(DropBFramesStepGenerator::new()),
+        )
+        .expect("Failed to register drop_bframes step");
+
+    step_factory
+        .register(
+            WorkflowStepType(GAP_MONITOR_STEP.to_string()),
+            Box::new(GapMonitorStepGenerator::new()),
+        )
+        .expect("Failed to register gap_monitor step");
+
+    step_factory
+        .register(
+            WorkflowStepType(BITRATE_GUARD_STEP.to_string()),
+            Box::new(BitrateGuardStepGenerator::new()),
+        )
+        .expect("Failed to register bitrate_guard step");
+
+    step_factory
+        .register(
+            WorkflowStepType(DEMUX_STEP.to_string()),
+            Box::new(DemuxStepGenerator::new()),
+        )
+        .expect("Failed to register demux step");
+
     step_factory
         .register(
             WorkflowStepType(BASIC_TRANSCODE_STEP.to_string()),
@@ -266,6 +550,7 @@ fn start_endpoints(
 
     let socket_manager = start_socket_manager(tls_options);
     let rtmp_endpoint = start_rtmp_server_endpoint(socket_manager);
+    let srt_endpoint = start_srt_server_endpoint();
 
     let ffmpeg_path = config
         .settings
@@ -307,33 +592,178 @@ fn start_endpoints(
 
     Endpoints {
         rtmp: rtmp_endpoint,
+        srt: srt_endpoint,
         ffmpeg: ffmpeg_endpoint,
         gst_transcoder,
     }
 }
 
-fn start_workflows(
+async fn start_workflows(
     config: &MmidsConfig,
     step_factory: Arc<WorkflowStepFactory>,
     event_hub_publisher: UnboundedSender<PublishEventRequest>,
 ) -> UnboundedSender<WorkflowManagerRequest> {
     info!("Starting workflow manager");
-    let manager = start_workflow_manager(step_factory, event_hub_publisher);
-    for (_, workflow) in &config.workflows {
+    let state_file = match config.settings.get("workflow_state_file") {
+        Some(Some(path)) => Some(PathBuf::from(path)),
+        _ => None,
+    };
+
+    let manager = start_workflow_manager_with_options(
+        step_factory,
+        event_hub_publisher,
+        WorkflowManagerOptions {
+            state_file,
+            restored_workflow_grace_period: get_numeric_setting(
+                config,
+                "workflow_restore_grace_period_seconds",
+            )
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_RESTORED_WORKFLOW_GRACE_PERIOD),
+            runner_options: get_workflow_runner_options(config),
+            ..Default::default()
+        },
+    );
+
+    let workflows =
+        get_workflow_start_order(config).expect("Workflow dependencies should have been validated");
+
+    for workflow in workflows {
+        let is_dependency = config
+            .workflow_dependencies
+            .values()
+            .any(|dependencies| dependencies.contains(&workflow.name));
+
+        if !is_dependency {
+            let _ = manager.send(WorkflowManagerRequest {
+                request_id: "mmids-app-startup".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: workflow.clone(),
+                },
+            });
+
+            continue;
+        }
+
+        // Workflows that depend on this one aren't started until it's active
+        let (sender, receiver) = channel();
         let _ = manager.send(WorkflowManagerRequest {
             request_id: "mmids-app-startup".to_string(),
-            operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+            operation: WorkflowManagerRequestOperation::UpsertWorkflowAndWait {
                 definition: workflow.clone(),
+                timeout: DEPENDENCY_START_TIMEOUT,
+                response_channel: sender,
             },
         });
+
+        match receiver.await {
+            Ok(DefinitionUpdateResult::Applied) => (),
+            Ok(result) => warn!(
+                workflow_name = %workflow.name,
+                "Workflow '{}' did not become active before starting the workflows that depend \
+                on it: {:?}",
+                workflow.name,
+                result,
+            ),
+
+            Err(_) => warn!(
+                workflow_name = %workflow.name,
+                "No response was received when starting workflow '{}'", workflow.name,
+            ),
+        }
     }
 
     manager
 }
 
+fn get_workflow_runner_options(config: &MmidsConfig) -> WorkflowRunnerOptions {
+    let mut options = WorkflowRunnerOptions::default();
+    if let Some(millis) = get_numeric_setting(config, "workflow_step_drain_period_ms") {
+        options.step_drain_period = Duration::from_millis(millis);
+    }
+
+    options.cache_latest_gop = match config.settings.get("workflow_cache_latest_gop") {
+        Some(None) => true,
+        Some(Some(value)) => value.to_lowercase() == "true",
+        None => false,
+    };
+
+    // A value of zero turns off the warning or limit
+    if let Some(millis) = get_numeric_setting(config, "workflow_slow_step_threshold_ms") {
+        options.slow_step_threshold = match millis {
+            0 => None,
+            millis => Some(Duration::from_millis(millis)),
+        };
+    }
+
+    if let Some(count) = get_numeric_setting(config, "workflow_max_media_outputs_per_execution") {
+        options.max_media_outputs_per_execution = match count {
+            0 => None,
+            count => Some(count),
+        };
+    }
+
+    if let Some(max_restarts) = get_numeric_setting(config, "workflow_step_max_restarts") {
+        let window = get_numeric_setting(config, "workflow_step_restart_window_seconds");
+        let backoff = get_numeric_setting(config, "workflow_step_restart_backoff_ms");
+        options.step_restart_policy = Some(StepRestartPolicy {
+            max_restarts,
+            failure_window: Duration::from_secs(window.unwrap_or(60)),
+            initial_backoff: Duration::from_millis(backoff.unwrap_or(1000)),
+        });
+    }
+
+    options.max_cached_bytes_per_step =
+        get_numeric_setting(config, "workflow_max_cached_bytes_per_step");
+
+    options.max_cached_bytes_per_workflow =
+        get_numeric_setting(config, "workflow_max_cached_bytes_per_workflow");
+
+    options
+}
+
+/// Reads a numeric setting, logging a warning and ignoring the setting if its value isn't a
+/// valid number
+fn get_numeric_setting<T: std::str::FromStr>(config: &MmidsConfig, name: &str) -> Option<T> {
+    match config.settings.get(name) {
+        Some(Some(value)) => match value.parse::<T>() {
+            Ok(number) => Some(number),
+            Err(_) => {
+                warn!(
+                    "{} value of '{}' is not a valid number, using the default",
+                    name, value
+                );
+
+                None
+            }
+        },
+
+        _ => None,
+    }
+}
+
+fn get_http_api_drain_timeout(config: &MmidsConfig) -> Duration {
+    match config.settings.get("http_api_drain_timeout_seconds") {
+        Some(Some(value)) => match value.parse::<u64>() {
+            Ok(seconds) => Duration::from_secs(seconds),
+            Err(_) => {
+                warn!(
+                    "http_api_drain_timeout_seconds value of '{}' is not a valid number, using the default",
+                    value
+                );
+
+                DEFAULT_DRAIN_TIMEOUT
+            }
+        },
+
+        _ => DEFAULT_DRAIN_TIMEOUT,
+    }
+}
+
 fn start_http_api(
     config: &MmidsConfig,
     manager: UnboundedSender<WorkflowManagerRequest>,
+    rtmp_endpoint: UnboundedSender<RtmpEndpointRequest>,
 ) -> Option<Sender<HttpApiShutdownSignal>> {
     let port = match config.settings.get("http_api_port") {
         Some(Some(value)) => match value.parse::<u16>() {
@@ -408,6 +838,107 @@ fn start_http_api(
         })
         .expect("Failed to register start workflow route");
 
+    routes
+        .register(Route {
+            method: Method::PUT,
+            path: vec![
+                PathPart::Exact {
+                    value: "workflows".to_string(),
+                },
+                PathPart::Parameter {
+                    name: "workflow".to_string(),
+                },
+            ],
+            handler: Box::new(handlers::start_workflow::StartWorkflowHandler::new(
+                manager.clone(),
+            )),
+        })
+        .expect("Failed to register update workflow route");
+
+    routes
+        .register(Route {
+            method: Method::PUT,
+            path: vec![
+                PathPart::Exact {
+                    value: "workflows".to_string(),
+                },
+                PathPart::Parameter {
+                    name: "workflow".to_string(),
+                },
+                PathPart::Exact {
+                    value: "log_level".to_string(),
+                },
+            ],
+            handler: Box::new(
+                handlers::set_workflow_log_level::SetWorkflowLogLevelHandler::new(manager.clone()),
+            ),
+        })
+        .expect("Failed to register set workflow log level route");
+
+    routes
+        .register(Route {
+            method: Method::GET,
+            path: vec![
+                PathPart::Exact {
+                    value: "rtmp".to_string(),
+                },
+                PathPart::Exact {
+                    value: "statistics".to_string(),
+                },
+            ],
+            handler: Box::new(
+                handlers::get_rtmp_statistics::GetRtmpStatisticsHandler::new(rtmp_endpoint.clone()),
+            ),
+        })
+        .expect("Failed to register get rtmp statistics route");
+
+    routes
+        .register(Route {
+            method: Method::DELETE,
+            path: vec![
+                PathPart::Exact {
+                    value: "rtmp".to_string(),
+                },
+                PathPart::Exact {
+                    value: "connections".to_string(),
+                },
+                PathPart::Parameter {
+                    name: "connection".to_string(),
+                },
+            ],
+            handler: Box::new(
+                handlers::disconnect_rtmp_connection::DisconnectRtmpConnectionHandler::new(
+                    rtmp_endpoint.clone(),
+                ),
+            ),
+        })
+        .expect("Failed to register disconnect rtmp connection route");
+
+    routes
+        .register(Route {
+            method: Method::GET,
+            path: vec![PathPart::Exact {
+                value: "metrics".to_string(),
+            }],
+            handler: Box::new(handlers::get_metrics::GetMetricsHandler::new(
+                manager.clone(),
+                rtmp_endpoint,
+            )),
+        })
+        .expect("Failed to register metrics route");
+
+    routes
+        .register(Route {
+            method: Method::GET,
+            path: vec![PathPart::Exact {
+                value: "healthz".to_string(),
+            }],
+            handler: Box::new(handlers::health_check::HealthCheckHandler::new(
+                manager.clone(),
+            )),
+        })
+        .expect("Failed to register health check route");
+
     routes
         .register(Route {
             method: Method::GET,
@@ -416,8 +947,26 @@ fn start_http_api(
         })
         .expect("Failed to register version route");
 
+    let tls_options = match (
+        config.settings.get("http_api_tls_cert_path"),
+        config.settings.get("http_api_tls_key_path"),
+    ) {
+        (Some(Some(certificate_path)), Some(Some(private_key_path))) => Some(HttpApiTlsOptions {
+            certificate_path: certificate_path.clone(),
+            private_key_path: private_key_path.clone(),
+        }),
+
+        (None, None) => None,
+        _ => panic!(
+            "Both `http_api_tls_cert_path` and `http_api_tls_key_path` must be specified to enable HTTPS"
+        ),
+    };
+
     let addr = ([127, 0, 0, 1], port).into();
-    Some(mmids_core::http_api::start_http_api(addr, routes))
+    match mmids_core::http_api::start_http_api(addr, routes, tls_options) {
+        Ok(sender) => Some(sender),
+        Err(error) => panic!("Failed to start the HTTP api: {}", error),
+    }
 }
 
 async fn start_reactor(
@@ -432,6 +981,20 @@ async fn start_reactor(
         )
         .expect("Failed to add simple_http reactor executor");
 
+    factory
+        .register(
+            "file".to_string(),
+            Box::new(FileReactorExecutorGenerator {}),
+        )
+        .expect("Failed to add file reactor executor");
+
+    factory
+        .register(
+            "hashing".to_string(),
+            Box::new(HashingReactorExecutorGenerator {}),
+        )
+        .expect("Failed to add hashing reactor executor");
+
     let reactor_manager = start_reactor_manager(factory, event_hub_subscriber.clone());
     for (name, definition) in &config.reactors {
         let (sender, receiver) = channel();
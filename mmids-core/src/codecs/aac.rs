@@ -0,0 +1,374 @@
+//! Helpers for working with AAC audio.  Within mmids AAC audio is always passed around as raw
+//! AAC frames, with the stream's `AudioSpecificConfig` sent as a sequence header before any
+//! frames.  This is how AAC is carried in FLV/RTMP, while transports such as MPEG-TS instead
+//! prefix every frame with an ADTS header containing the audio configuration.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use thiserror::Error;
+
+/// Sample rates of each AAC sampling frequency index
+pub const AAC_SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+const EXPLICIT_FREQUENCY_INDEX: u8 = 15;
+const ESCAPE_OBJECT_TYPE: u8 = 31;
+const ADTS_HEADER_LENGTH: usize = 7;
+const ADTS_HEADER_WITH_CRC_LENGTH: usize = 9;
+const MAX_ADTS_FRAME_LENGTH: usize = (1 << 13) - 1;
+
+/// The decoder configuration of an AAC stream, as defined by ISO 14496-3
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioSpecificConfig {
+    /// The MPEG-4 audio object type (e.g. 2 for AAC-LC)
+    pub object_type: u8,
+
+    /// Index into the AAC sample rate table, or 15 if the sample rate was explicitly given
+    pub sampling_frequency_index: u8,
+
+    pub sample_rate: u32,
+    pub channel_configuration: u8,
+}
+
+/// A single ADTS framed AAC frame
+#[derive(Debug, PartialEq)]
+pub struct AdtsFrame<'a> {
+    /// The audio configuration the ADTS header describes
+    pub config: AudioSpecificConfig,
+
+    /// The length of the whole frame, including the ADTS header
+    pub frame_length: usize,
+
+    /// The raw AAC frame, without the ADTS header
+    pub data: &'a [u8],
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum AacError {
+    #[error("The audio specific config is too short")]
+    AudioSpecificConfigTooShort,
+
+    #[error("The audio specific config has an invalid object type of {0}")]
+    InvalidObjectType(u8),
+
+    #[error("The audio specific config has an invalid sampling frequency index of {0}")]
+    InvalidSamplingFrequencyIndex(u8),
+
+    #[error("Object type {0} can not be described by an ADTS header")]
+    ObjectTypeNotAdtsCompatible(u8),
+
+    #[error("Explicit sample rates can not be described by an ADTS header")]
+    ExplicitSampleRateNotAdtsCompatible,
+
+    #[error("Frame of {0} bytes is too large to fit in an ADTS frame")]
+    FrameTooLarge(usize),
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+struct BitWriter {
+    bits: u64,
+    bit_count: u32,
+}
+
+impl AudioSpecificConfig {
+    /// Parses an `AudioSpecificConfig`, such as from an AAC sequence header.  Only the fields
+    /// common to all object types are read, so any trailing object type specific data is
+    /// ignored.
+    pub fn parse(data: &[u8]) -> Result<Self, AacError> {
+        let mut reader = BitReader { data, position: 0 };
+        let mut object_type = reader.read(5)? as u8;
+        if object_type == ESCAPE_OBJECT_TYPE {
+            object_type = 32 + reader.read(6)? as u8;
+        }
+
+        if object_type == 0 {
+            return Err(AacError::InvalidObjectType(object_type));
+        }
+
+        let sampling_frequency_index = reader.read(4)? as u8;
+        let sample_rate = if sampling_frequency_index == EXPLICIT_FREQUENCY_INDEX {
+            reader.read(24)?
+        } else {
+            match AAC_SAMPLE_RATES.get(sampling_frequency_index as usize) {
+                Some(sample_rate) => *sample_rate,
+                None => {
+                    return Err(AacError::InvalidSamplingFrequencyIndex(
+                        sampling_frequency_index,
+                    ))
+                }
+            }
+        };
+
+        let channel_configuration = reader.read(4)? as u8;
+
+        Ok(AudioSpecificConfig {
+            object_type,
+            sampling_frequency_index,
+            sample_rate,
+            channel_configuration,
+        })
+    }
+
+    /// Creates the `AudioSpecificConfig` bytes for this configuration, suitable for use as an
+    /// AAC sequence header.
+    pub fn to_bytes(&self) -> Bytes {
+        let mut writer = BitWriter {
+            bits: 0,
+            bit_count: 0,
+        };
+
+        if self.object_type >= ESCAPE_OBJECT_TYPE {
+            writer.write(ESCAPE_OBJECT_TYPE as u32, 5);
+            writer.write((self.object_type - 32) as u32, 6);
+        } else {
+            writer.write(self.object_type as u32, 5);
+        }
+
+        writer.write(self.sampling_frequency_index as u32, 4);
+        if self.sampling_frequency_index == EXPLICIT_FREQUENCY_INDEX {
+            writer.write(self.sample_rate, 24);
+        }
+
+        writer.write(self.channel_configuration as u32, 4);
+        writer.into_bytes()
+    }
+
+    /// Creates the ADTS header for a raw AAC frame of the specified length
+    pub fn adts_header(&self, raw_frame_length: usize) -> Result<[u8; 7], AacError> {
+        if self.object_type == 0 || self.object_type > 4 {
+            return Err(AacError::ObjectTypeNotAdtsCompatible(self.object_type));
+        }
+
+        if self.sampling_frequency_index >= EXPLICIT_FREQUENCY_INDEX {
+            return Err(AacError::ExplicitSampleRateNotAdtsCompatible);
+        }
+
+        let frame_length = raw_frame_length + ADTS_HEADER_LENGTH;
+        if frame_length > MAX_ADTS_FRAME_LENGTH {
+            return Err(AacError::FrameTooLarge(raw_frame_length));
+        }
+
+        let profile = self.object_type - 1;
+        let channels = self.channel_configuration & 0x07;
+
+        Ok([
+            0xff,
+            0xf1, // MPEG-4, no CRC
+            (profile << 6) | (self.sampling_frequency_index << 2) | (channels >> 2),
+            ((channels & 0x03) << 6) | (frame_length >> 11) as u8,
+            (frame_length >> 3) as u8,
+            ((frame_length as u8) << 5) | 0x1f, // variable bitrate buffer fullness
+            0xfc,                               // with one raw data block
+        ])
+    }
+}
+
+impl<'a> AdtsFrame<'a> {
+    /// Parses the ADTS frame at the start of the specified bytes.  `None` is returned if the
+    /// bytes do not start with a complete ADTS frame.
+    pub fn parse(bytes: &'a [u8]) -> Option<Self> {
+        if bytes.len() < ADTS_HEADER_LENGTH || bytes[0] != 0xff || bytes[1] & 0xf0 != 0xf0 {
+            return None;
+        }
+
+        let protection_absent = bytes[1] & 0x01 == 0x01;
+        let header_length = if protection_absent {
+            ADTS_HEADER_LENGTH
+        } else {
+            ADTS_HEADER_WITH_CRC_LENGTH
+        };
+
+        let sampling_frequency_index = (bytes[2] >> 2) & 0x0f;
+        let frame_length = ((bytes[3] as usize & 0x03) << 11)
+            | ((bytes[4] as usize) << 3)
+            | (bytes[5] as usize >> 5);
+
+        if frame_length < header_length || bytes.len() < frame_length {
+            return None;
+        }
+
+        Some(AdtsFrame {
+            config: AudioSpecificConfig {
+                object_type: (bytes[2] >> 6) + 1,
+                sampling_frequency_index,
+                sample_rate: *AAC_SAMPLE_RATES.get(sampling_frequency_index as usize)?,
+                channel_configuration: ((bytes[2] & 0x01) << 2) | (bytes[3] >> 6),
+            },
+            frame_length,
+            data: &bytes[header_length..frame_length],
+        })
+    }
+}
+
+/// Wraps a raw AAC frame in an ADTS header describing the specified configuration
+pub fn raw_to_adts(config: &AudioSpecificConfig, raw_frame: &[u8]) -> Result<Bytes, AacError> {
+    let header = config.adts_header(raw_frame.len())?;
+    let mut frame = BytesMut::with_capacity(header.len() + raw_frame.len());
+    frame.put_slice(&header);
+    frame.put_slice(raw_frame);
+
+    Ok(frame.freeze())
+}
+
+/// Returns the raw AAC frame if the data consists of exactly one ADTS frame, or `None` if the
+/// data is not ADTS framed.
+pub fn adts_to_raw(data: &Bytes) -> Option<Bytes> {
+    let frame = AdtsFrame::parse(data)?;
+    if frame.frame_length != data.len() {
+        return None;
+    }
+
+    let header_length = frame.frame_length - frame.data.len();
+    Some(data.slice(header_length..))
+}
+
+impl<'a> BitReader<'a> {
+    fn read(&mut self, count: usize) -> Result<u32, AacError> {
+        if self.position + count > self.data.len() * 8 {
+            return Err(AacError::AudioSpecificConfigTooShort);
+        }
+
+        let mut value = 0;
+        for _ in 0..count {
+            let byte = self.data[self.position / 8];
+            let bit = (byte >> (7 - self.position % 8)) & 0x01;
+            value = (value << 1) | bit as u32;
+            self.position += 1;
+        }
+
+        Ok(value)
+    }
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        let mask = (1_u64 << count) - 1;
+        self.bits = (self.bits << count) | (value as u64 & mask);
+        self.bit_count += count;
+    }
+
+    /// Pads the written bits to a whole number of bytes and returns them
+    fn into_bytes(mut self) -> Bytes {
+        let padding = (8 - self.bit_count % 8) % 8;
+        self.write(0, padding);
+
+        let byte_count = (self.bit_count / 8) as usize;
+        let bytes = self.bits.to_be_bytes();
+        Bytes::copy_from_slice(&bytes[bytes.len() - byte_count..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // AAC-LC, 44.1khz, stereo
+    const AUDIO_SPECIFIC_CONFIG: [u8; 2] = [0x12, 0x10];
+
+    fn lc_44khz_stereo() -> AudioSpecificConfig {
+        AudioSpecificConfig {
+            object_type: 2,
+            sampling_frequency_index: 4,
+            sample_rate: 44100,
+            channel_configuration: 2,
+        }
+    }
+
+    #[test]
+    fn can_parse_two_byte_audio_specific_config() {
+        let config = AudioSpecificConfig::parse(&AUDIO_SPECIFIC_CONFIG);
+
+        assert_eq!(config, Ok(lc_44khz_stereo()), "Unexpected config");
+    }
+
+    #[test]
+    fn two_byte_audio_specific_config_round_trips() {
+        let bytes = lc_44khz_stereo().to_bytes();
+
+        assert_eq!(&bytes[..], &AUDIO_SPECIFIC_CONFIG[..], "Unexpected bytes");
+    }
+
+    #[test]
+    fn explicit_sample_rate_round_trips() {
+        let config = AudioSpecificConfig {
+            object_type: 2,
+            sampling_frequency_index: 15,
+            sample_rate: 44000,
+            channel_configuration: 1,
+        };
+
+        let bytes = config.to_bytes();
+
+        assert_eq!(bytes.len(), 5, "Unexpected number of bytes");
+        assert_eq!(AudioSpecificConfig::parse(&bytes), Ok(config));
+    }
+
+    #[test]
+    fn escaped_object_type_round_trips() {
+        let config = AudioSpecificConfig {
+            object_type: 42,
+            sampling_frequency_index: 3,
+            sample_rate: 48000,
+            channel_configuration: 2,
+        };
+
+        assert_eq!(AudioSpecificConfig::parse(&config.to_bytes()), Ok(config));
+    }
+
+    #[test]
+    fn short_audio_specific_config_is_invalid() {
+        let config = AudioSpecificConfig::parse(&AUDIO_SPECIFIC_CONFIG[..1]);
+
+        assert_eq!(config, Err(AacError::AudioSpecificConfigTooShort));
+    }
+
+    #[test]
+    fn zero_object_type_is_invalid() {
+        let config = AudioSpecificConfig::parse(&[0x03, 0x04]);
+
+        assert_eq!(config, Err(AacError::InvalidObjectType(0)));
+    }
+
+    #[test]
+    fn raw_frame_round_trips_through_adts() {
+        let adts = raw_to_adts(&lc_44khz_stereo(), &[1, 2, 3]).unwrap();
+
+        assert_eq!(
+            &adts[..],
+            &[0xff, 0xf1, 0x50, 0x80, 0x01, 0x5f, 0xfc, 1, 2, 3],
+            "Unexpected adts frame"
+        );
+
+        let frame = AdtsFrame::parse(&adts).unwrap();
+        assert_eq!(frame.config, lc_44khz_stereo(), "Unexpected config");
+        assert_eq!(frame.data, &[1, 2, 3], "Unexpected frame data");
+
+        let raw = adts_to_raw(&adts).unwrap();
+        assert_eq!(&raw[..], &[1, 2, 3], "Unexpected raw frame");
+    }
+
+    #[test]
+    fn raw_frame_is_not_adts() {
+        let data = Bytes::from(vec![0x21, 0x10, 0x05]);
+
+        assert_eq!(adts_to_raw(&data), None);
+    }
+
+    #[test]
+    fn adts_not_possible_for_explicit_sample_rate() {
+        let config = AudioSpecificConfig {
+            sampling_frequency_index: 15,
+            sample_rate: 44000,
+            ..lc_44khz_stereo()
+        };
+
+        assert_eq!(
+            raw_to_adts(&config, &[1]),
+            Err(AacError::ExplicitSampleRateNotAdtsCompatible)
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! Helpers for working with H264 video.  Within mmids H264 video is passed around in AVCC format,
+//! where each frame is a series of NAL units prefixed by their length, with the stream's
+//! `AVCDecoderConfigurationRecord` sent as a sequence header before any frames.  The size of each
+//! length prefix is defined by the decoder configuration record.
+
+/// The size of NAL unit length prefixes when the stream's decoder configuration isn't known.
+/// Almost all encoders use 4 byte prefixes.
+pub const DEFAULT_NALU_LENGTH_SIZE: usize = 4;
+
+pub const NAL_UNIT_TYPE_NON_IDR_SLICE: u8 = 1;
+pub const NAL_UNIT_TYPE_IDR_SLICE: u8 = 5;
+
+/// The type of a coded slice, as defined by ISO 14496-10
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SliceType {
+    P,
+    B,
+    I,
+    Sp,
+    Si,
+}
+
+/// Reads the size of NAL unit length prefixes from an AVC decoder configuration record
+pub fn get_nalu_length_size(decoder_configuration_record: &[u8]) -> Option<usize> {
+    if decoder_configuration_record.len() < 5 || decoder_configuration_record[0] != 1 {
+        return None;
+    }
+
+    Some(((decoder_configuration_record[4] & 0x03) + 1) as usize)
+}
+
+/// Splits AVCC formatted video data into its NAL units.  `None` is returned if the data isn't
+/// made up of complete length prefixed NAL units.
+pub fn split_nal_units(data: &[u8], nalu_length_size: usize) -> Option<Vec<&[u8]>> {
+    let mut nal_units = Vec::new();
+    let mut index = 0;
+    while index < data.len() {
+        let length_bytes = data.get(index..index + nalu_length_size)?;
+        let length = length_bytes
+            .iter()
+            .fold(0_usize, |length, byte| (length << 8) | *byte as usize);
+
+        let start = index + nalu_length_size;
+        nal_units.push(data.get(start..start + length)?);
+        index = start + length;
+    }
+
+    Some(nal_units)
+}
+
+pub fn get_nal_unit_type(nal_unit: &[u8]) -> Option<u8> {
+    nal_unit.first().map(|header| header & 0x1f)
+}
+
+/// Returns if other pictures may reference the NAL unit during decoding (`nal_ref_idc` is not
+/// zero).  NAL units that are not referenced can be dropped without affecting other pictures.
+pub fn is_referenced(nal_unit: &[u8]) -> bool {
+    match nal_unit.first() {
+        Some(header) => header & 0x60 != 0,
+        None => false,
+    }
+}
+
+/// Reads the slice type from the slice header of a coded slice NAL unit.  `None` is returned if
+/// the NAL unit is not a coded slice or its header could not be read.
+pub fn get_slice_type(nal_unit: &[u8]) -> Option<SliceType> {
+    match get_nal_unit_type(nal_unit)? {
+        NAL_UNIT_TYPE_NON_IDR_SLICE | NAL_UNIT_TYPE_IDR_SLICE => (),
+        _ => return None,
+    }
+
+    // The slice type is the second field of the slice header, so only the start of the NAL
+    // unit needs its emulation prevention bytes removed
+    let header = remove_emulation_prevention(&nal_unit[1..], 16);
+    let mut reader = ExpGolombReader {
+        data: &header,
+        position: 0,
+    };
+
+    let _first_mb_in_slice = reader.read_unsigned()?;
+    let slice_type = match reader.read_unsigned()? % 5 {
+        0 => SliceType::P,
+        1 => SliceType::B,
+        2 => SliceType::I,
+        3 => SliceType::Sp,
+        _ => SliceType::Si,
+    };
+
+    Some(slice_type)
+}
+
+/// Converts up to `max_length` bytes of a NAL unit's payload to its raw byte sequence, by
+/// removing the `0x03` bytes encoders insert to prevent start codes appearing in the payload.
+fn remove_emulation_prevention(payload: &[u8], max_length: usize) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(max_length);
+    let mut zero_count = 0;
+    for byte in payload {
+        if raw.len() >= max_length {
+            break;
+        }
+
+        if zero_count >= 2 && *byte == 0x03 {
+            zero_count = 0;
+            continue;
+        }
+
+        zero_count = if *byte == 0 { zero_count + 1 } else { 0 };
+        raw.push(*byte);
+    }
+
+    raw
+}
+
+struct ExpGolombReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ExpGolombReader<'a> {
+    fn read_bit(&mut self) -> Option<u32> {
+        let byte = self.data.get(self.position / 8)?;
+        let bit = (byte >> (7 - self.position % 8)) & 0x01;
+        self.position += 1;
+
+        Some(bit as u32)
+    }
+
+    /// Reads an unsigned exp-Golomb coded value (`ue(v)`)
+    fn read_unsigned(&mut self) -> Option<u32> {
+        let mut leading_zeros = 0;
+        while self.read_bit()? == 0 {
+            leading_zeros += 1;
+            if leading_zeros > 31 {
+                return None;
+            }
+        }
+
+        let mut info = 0_u64;
+        for _ in 0..leading_zeros {
+            info = (info << 1) | self.read_bit()? as u64;
+        }
+
+        let value = (1_u64 << leading_zeros) - 1 + info;
+        Some(value as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_read_nalu_length_size_from_decoder_configuration_record() {
+        let record = [0x01, 0x64, 0x00, 0x1f, 0xfd, 0xe1];
+
+        assert_eq!(get_nalu_length_size(&record), Some(2));
+    }
+
+    #[test]
+    fn invalid_decoder_configuration_record_has_no_nalu_length_size() {
+        let record = [0x00, 0x64, 0x00, 0x1f, 0xff, 0xe1];
+
+        assert_eq!(get_nalu_length_size(&record), None);
+    }
+
+    #[test]
+    fn can_split_nal_units() {
+        let data = [0, 0, 0, 2, 0x09, 0xf0, 0, 0, 0, 3, 0x41, 0x9a, 0x01];
+        let nal_units = split_nal_units(&data, 4);
+
+        assert_eq!(
+            nal_units,
+            Some(vec![&[0x09, 0xf0][..], &[0x41, 0x9a, 0x01][..]]),
+            "Unexpected NAL units"
+        );
+    }
+
+    #[test]
+    fn truncated_nal_unit_fails_to_split() {
+        let data = [0, 0, 0, 5, 0x41, 0x9a];
+
+        assert_eq!(split_nal_units(&data, 4), None);
+    }
+
+    #[test]
+    fn nal_ref_idc_determines_if_referenced() {
+        assert!(
+            is_referenced(&[0x41, 0xc0]),
+            "Expected P slice to be referenced"
+        );
+        assert!(
+            !is_referenced(&[0x01, 0xa0]),
+            "Expected B slice to not be referenced"
+        );
+    }
+
+    #[test]
+    fn can_read_slice_types() {
+        // first_mb_in_slice of 0 followed by each slice type
+        assert_eq!(get_slice_type(&[0x41, 0xc0]), Some(SliceType::P));
+        assert_eq!(get_slice_type(&[0x01, 0xa0]), Some(SliceType::B));
+        assert_eq!(get_slice_type(&[0x65, 0x88]), Some(SliceType::I));
+    }
+
+    #[test]
+    fn slice_type_read_through_emulation_prevention_bytes() {
+        // Raw header of 00 00 02 00 00 02 is a large first_mb_in_slice followed by a B slice type
+        let nal_unit = [0x01, 0x00, 0x00, 0x03, 0x02, 0x00, 0x00, 0x03, 0x02];
+
+        assert_eq!(get_slice_type(&nal_unit), Some(SliceType::B));
+    }
+
+    #[test]
+    fn non_slice_nal_unit_has_no_slice_type() {
+        // Access unit delimiter
+        assert_eq!(get_slice_type(&[0x09, 0xf0]), None);
+    }
+}
@@ -1,3 +1,6 @@
+pub mod aac;
+pub mod h264;
+
 /// Video codecs that can be identified
 #[derive(Debug, Clone, PartialEq, Copy)]
 pub enum VideoCodec {
@@ -1,17 +1,40 @@
 use crate::reactors::ReactorDefinition;
 use crate::workflows::definitions::{WorkflowDefinition, WorkflowStepDefinition, WorkflowStepType};
+use crate::workflows::manager::{WorkflowManagerRequest, WorkflowManagerRequestOperation};
+use crate::workflows::steps::factory::{StepKind, WorkflowStepFactory};
+use notify::{EventKind, RecursiveMode, Watcher};
 use pest::iterators::{Pair, Pairs};
 use pest::Parser;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
-use tracing::warn;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tracing::{error, info, warn};
+
+/// How long to wait after a change to the config file is detected before reloading it.  Editors
+/// commonly write a file in multiple steps, so this allows all the changes to settle first.
+const CONFIG_RELOAD_DELAY: Duration = Duration::from_millis(500);
 
 /// Configuration for a Mmids system.  Defines the settings and any workflows that should be active.
 pub struct MmidsConfig {
     pub settings: HashMap<String, Option<String>>,
     pub reactors: HashMap<String, ReactorDefinition>,
     pub workflows: HashMap<String, WorkflowDefinition>,
+
+    /// The line number each workflow's steps were defined on, in the same order as the steps
+    /// in the workflow definition.  Used for reporting errors found after parsing.
+    pub workflow_step_lines: HashMap<String, Vec<usize>>,
+
+    /// The names of the workflows each workflow depends on, as specified by its `depends_on`
+    /// argument.  Dependencies only affect the order workflows are started in.
+    pub workflow_dependencies: HashMap<String, Vec<String>>,
+
+    /// Workflows that were marked with the `disabled` argument, keyed by name.  Disabled workflows
+    /// are parsed and validated, but are not added to `workflows` so they are never started.
+    pub disabled_workflows: HashMap<String, WorkflowDefinition>,
 }
 
 /// Errors that can occur when parsing a configuration entry
@@ -43,6 +66,31 @@ pub enum ConfigParseError {
     )]
     InvalidRoutedByReactorArgument { line: usize },
 
+    #[error("The `disabled` argument on line {line} is invalid. Equal signs are not allowed")]
+    InvalidDisabledArgument { line: usize },
+
+    #[error("The `depends_on` argument on line {line} is invalid. At least one workflow name must be specified")]
+    InvalidDependsOnArgument { line: usize },
+
+    #[error(
+        "The workflow '{workflow}' depends on the workflow '{dependency}', which does not exist"
+    )]
+    UnknownDependency {
+        workflow: String,
+        dependency: String,
+    },
+
+    #[error("The workflow '{workflow}' depends on the workflow '{dependency}', which is disabled")]
+    DisabledDependency {
+        workflow: String,
+        dependency: String,
+    },
+
+    #[error(
+        "The workflow '{workflow}' depends on itself, either directly or through other workflows"
+    )]
+    DependencyCycle { workflow: String },
+
     #[error("The workflow on line {line} did not have a name specified")]
     NoNameOnWorkflow { line: usize },
 
@@ -58,6 +106,9 @@ pub enum ConfigParseError {
     #[error("The reactor on line {line} has an invalid update_interval value of '{argument}'. This value must be a number")]
     InvalidUpdateIntervalValue { line: usize, argument: String },
 
+    #[error("The reactor on line {line} has an invalid minimum_workflow_lifetime value of '{argument}'. This value must be a number")]
+    InvalidMinimumWorkflowLifetimeValue { line: usize, argument: String },
+
     #[error(
         "The reactor parameter's value on line {line} is invalid. Equal signs are not allowed"
     )]
@@ -71,6 +122,52 @@ pub enum ConfigParseError {
 
     #[error("The executor on line {line} did not have an executor specified")]
     NoExecutorForReactor { line: usize },
+
+    #[error("The environment variable '{name}' referenced on line {line} is not defined")]
+    UndefinedEnvironmentVariable { name: String, line: usize },
+
+    #[error("The step on line {line} of workflow '{workflow}' comes before any step that provides media streams")]
+    InvalidStepOrder { workflow: String, line: usize },
+
+    #[error("The step on line {line} of workflow '{workflow}' is invalid: {error}")]
+    InvalidStep {
+        workflow: String,
+        line: usize,
+        #[source]
+        error: Box<dyn std::error::Error + Sync + Send>,
+    },
+
+    #[error("The include on line {line} is not allowed, as includes are only supported when parsing a config file")]
+    IncludeNotAllowed { line: usize },
+
+    #[error("The file '{path}' includes itself, either directly or through other included files")]
+    IncludeCycle { path: String },
+
+    #[error("The file '{path}' included on line {line} is invalid: {error}")]
+    InvalidIncludedFile {
+        path: String,
+        line: usize,
+        #[source]
+        error: Box<ConfigParseError>,
+    },
+
+    #[error("The config file '{path}' could not be read")]
+    FileReadFailed {
+        path: String,
+        #[source]
+        error: std::io::Error,
+    },
+}
+
+/// Errors that can occur when starting to watch a configuration file
+#[derive(Error, Debug)]
+pub enum ConfigWatchError {
+    #[error("The config file '{path}' could not be watched for changes")]
+    WatchFailed {
+        path: String,
+        #[source]
+        error: notify::Error,
+    },
 }
 
 #[derive(Parser)]
@@ -82,19 +179,116 @@ struct ChildNode {
     arguments: HashMap<String, Option<String>>,
 }
 
-/// Parses configuration from a text block.
+/// Parses configuration from a text block.  Include directives are not allowed, since there's
+/// no file to resolve them relative to.
 pub fn parse(content: &str) -> Result<MmidsConfig, ConfigParseError> {
     let mut config = MmidsConfig {
         settings: HashMap::new(),
         reactors: HashMap::new(),
         workflows: HashMap::new(),
+        workflow_step_lines: HashMap::new(),
+        workflow_dependencies: HashMap::new(),
+        disabled_workflows: HashMap::new(),
+    };
+
+    parse_into(&mut config, content, None)?;
+    get_workflow_start_order(&config)?;
+
+    Ok(config)
+}
+
+/// Parses configuration from the specified file.  Any `include "<path>"` directives are resolved
+/// relative to the directory of the file containing them, and the included file's settings,
+/// reactors, and workflows are merged into the returned configuration.
+pub fn parse_file(path: &Path) -> Result<MmidsConfig, ConfigParseError> {
+    let mut config = MmidsConfig {
+        settings: HashMap::new(),
+        reactors: HashMap::new(),
+        workflows: HashMap::new(),
+        workflow_step_lines: HashMap::new(),
+        workflow_dependencies: HashMap::new(),
+        disabled_workflows: HashMap::new(),
+    };
+
+    let mut visited = HashSet::new();
+    parse_file_into(&mut config, path, None, &mut visited)?;
+    get_workflow_start_order(&config)?;
+
+    Ok(config)
+}
+
+/// Parses the file at the specified path into the configuration.  If the file was included by
+/// another file, `included_on_line` is the line of the include directive, and any error in the
+/// file's content is wrapped so it names the file its line numbers refer to.
+fn parse_file_into(
+    config: &mut MmidsConfig,
+    path: &Path,
+    included_on_line: Option<usize>,
+    visited: &mut HashSet<PathBuf>,
+) -> Result<(), ConfigParseError> {
+    let read_error = |error| ConfigParseError::FileReadFailed {
+        path: path.display().to_string(),
+        error,
+    };
+
+    let path = path.canonicalize().map_err(read_error)?;
+    if !visited.insert(path.clone()) {
+        return Err(ConfigParseError::IncludeCycle {
+            path: path.display().to_string(),
+        });
+    }
+
+    let content = std::fs::read_to_string(&path).map_err(read_error)?;
+    let mut includes = IncludeContext {
+        directory: path.parent().unwrap_or(Path::new("")),
+        visited,
     };
 
+    parse_into(config, &content, Some(&mut includes)).map_err(|error| match included_on_line {
+        Some(line) => ConfigParseError::InvalidIncludedFile {
+            path: path.display().to_string(),
+            line,
+            error: Box::new(error),
+        },
+
+        None => error,
+    })?;
+
+    // Only files currently being parsed are tracked, so a file can be included more than once
+    // as long as it doesn't end up including itself.
+    includes.visited.remove(&path);
+
+    Ok(())
+}
+
+struct IncludeContext<'a> {
+    directory: &'a Path,
+    visited: &'a mut HashSet<PathBuf>,
+}
+
+fn parse_into(
+    config: &mut MmidsConfig,
+    content: &str,
+    mut includes: Option<&mut IncludeContext>,
+) -> Result<(), ConfigParseError> {
     let pairs = RawConfigParser::parse(Rule::content, content)?;
     for pair in pairs {
         let rule = pair.as_rule();
         match &rule {
-            Rule::node_block => handle_node_block(&mut config, pair)?,
+            Rule::node_block => handle_node_block(config, pair)?,
+            Rule::include_node => {
+                let line = get_line_number(&pair);
+                let includes = match includes.as_mut() {
+                    Some(includes) => includes,
+                    None => return Err(ConfigParseError::IncludeNotAllowed { line }),
+                };
+
+                let value = pair.into_inner().next().unwrap(); // grammar requires a path
+                let value = expand_environment_variables(value.as_str(), line)?;
+                let path = includes.directory.join(value);
+                parse_file_into(config, &path, Some(line), includes.visited)?;
+            }
+
             Rule::EOI => (),
             x => {
                 return Err(ConfigParseError::UnexpectedRule {
@@ -105,7 +299,187 @@ pub fn parse(content: &str) -> Result<MmidsConfig, ConfigParseError> {
         }
     }
 
-    Ok(config)
+    Ok(())
+}
+
+/// Gets the workflows in the order they should be started in, so every workflow is started after
+/// all the workflows it depends on.  Workflows that don't depend on each other are ordered by
+/// name.  An error is returned if a workflow depends on a workflow that doesn't exist, or if
+/// workflows depend on each other in a cycle.
+pub fn get_workflow_start_order(
+    config: &MmidsConfig,
+) -> Result<Vec<&WorkflowDefinition>, ConfigParseError> {
+    let no_dependencies = Vec::new();
+    let dependencies_of = |name: &str| {
+        config
+            .workflow_dependencies
+            .get(name)
+            .unwrap_or(&no_dependencies)
+    };
+
+    let mut remaining = config.workflows.values().collect::<Vec<_>>();
+    remaining.sort_by(|first, second| first.name.cmp(&second.name));
+
+    for workflow in &remaining {
+        for dependency in dependencies_of(&workflow.name) {
+            if config.disabled_workflows.contains_key(dependency) {
+                return Err(ConfigParseError::DisabledDependency {
+                    workflow: workflow.name.clone(),
+                    dependency: dependency.clone(),
+                });
+            }
+
+            if !config.workflows.contains_key(dependency) {
+                return Err(ConfigParseError::UnknownDependency {
+                    workflow: workflow.name.clone(),
+                    dependency: dependency.clone(),
+                });
+            }
+        }
+    }
+
+    let mut started = HashSet::new();
+    let mut order = Vec::new();
+    while !remaining.is_empty() {
+        let next = remaining.iter().position(|workflow| {
+            dependencies_of(&workflow.name)
+                .iter()
+                .all(|dependency| started.contains(dependency.as_str()))
+        });
+
+        match next {
+            Some(index) => {
+                let workflow = remaining.remove(index);
+                started.insert(workflow.name.as_str());
+                order.push(workflow);
+            }
+
+            None => {
+                // Every remaining workflow is waiting on another remaining workflow, so following
+                // unstarted dependencies must eventually lead back to a workflow already seen.
+                let mut seen = HashSet::new();
+                let mut name = remaining[0].name.as_str();
+                while seen.insert(name) {
+                    name = dependencies_of(name)
+                        .iter()
+                        .find(|dependency| !started.contains(dependency.as_str()))
+                        .unwrap() // otherwise the workflow could have been started
+                        .as_str();
+                }
+
+                return Err(ConfigParseError::DependencyCycle {
+                    workflow: name.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(order)
+}
+
+/// Validates that the steps of each workflow are in an order that makes sense, based on the kind
+/// of each step registered with the step factory.  A workflow that contains source steps must not
+/// have any other steps before its first source step, as those steps would never receive media
+/// from it.  Workflows without any source steps are allowed, since they can receive media that's
+/// been forwarded to them from other workflows.  Step types that aren't registered are ignored.
+/// Disabled workflows are checked as well, so they are known to be valid when they get enabled.
+pub fn validate_step_order(
+    config: &MmidsConfig,
+    step_factory: &WorkflowStepFactory,
+) -> Result<(), ConfigParseError> {
+    for workflow in config
+        .workflows
+        .values()
+        .chain(config.disabled_workflows.values())
+    {
+        validate_workflow_step_order(config, workflow, step_factory)?;
+    }
+
+    Ok(())
+}
+
+/// Validates every workflow in the configuration against the registered step generators, without
+/// creating any steps.  On top of the step order checks done by `validate_step_order()`, each
+/// step's parameters are checked by its generator, so misconfigured steps are caught before any
+/// workflow is started.  All problems found are returned instead of only the first one, ordered by
+/// workflow name and then by the order of steps within the workflow.  Disabled workflows are
+/// validated the same way as enabled ones.
+pub fn validate(
+    config: &MmidsConfig,
+    step_factory: &WorkflowStepFactory,
+) -> Result<(), Vec<ConfigParseError>> {
+    let mut workflows = config
+        .workflows
+        .values()
+        .chain(config.disabled_workflows.values())
+        .collect::<Vec<_>>();
+    workflows.sort_by(|first, second| first.name.cmp(&second.name));
+
+    let mut errors = Vec::new();
+    for workflow in workflows {
+        if let Err(error) = validate_workflow_step_order(config, workflow, step_factory) {
+            errors.push(error);
+        }
+
+        for (index, step) in workflow.steps.iter().enumerate() {
+            if let Err(error) = step_factory.validate_step(step) {
+                errors.push(ConfigParseError::InvalidStep {
+                    workflow: workflow.name.clone(),
+                    line: get_step_line(config, &workflow.name, index),
+                    error,
+                });
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn validate_workflow_step_order(
+    config: &MmidsConfig,
+    workflow: &WorkflowDefinition,
+    step_factory: &WorkflowStepFactory,
+) -> Result<(), ConfigParseError> {
+    let kinds = workflow
+        .steps
+        .iter()
+        .map(|step| step_factory.get_step_kind(&step.step_type))
+        .collect::<Vec<_>>();
+
+    let first_source_index = match kinds
+        .iter()
+        .position(|kind| kind == &Some(StepKind::Source))
+    {
+        Some(index) => index,
+        None => return Ok(()),
+    };
+
+    if let Some(index) = kinds[..first_source_index]
+        .iter()
+        .position(|kind| kind.is_some())
+    {
+        return Err(ConfigParseError::InvalidStepOrder {
+            workflow: workflow.name.clone(),
+            line: get_step_line(config, &workflow.name, index),
+        });
+    }
+
+    Ok(())
+}
+
+/// Gets the line number the step at the specified index of a workflow was defined on.  Returns
+/// zero if the workflow wasn't read from config text (and thus has no known line numbers).
+fn get_step_line(config: &MmidsConfig, workflow_name: &str, step_index: usize) -> usize {
+    config
+        .workflow_step_lines
+        .get(workflow_name)
+        .and_then(|lines| lines.get(step_index))
+        .copied()
+        .unwrap_or(0)
 }
 
 fn handle_node_block(config: &mut MmidsConfig, pair: Pair<Rule>) -> Result<(), ConfigParseError> {
@@ -176,11 +550,15 @@ fn read_workflow(
     starting_line: usize,
 ) -> Result<(), ConfigParseError> {
     let mut steps = Vec::new();
+    let mut step_lines = Vec::new();
     let mut workflow_name = None;
     let mut routed_by_reactor = false;
+    let mut disabled = false;
+    let mut dependencies = Vec::new();
     for pair in pairs {
         match pair.as_rule() {
             Rule::child_node => {
+                step_lines.push(get_line_number(&pair));
                 let child_node = read_child_node(pair)?;
                 steps.push(WorkflowStepDefinition {
                     step_type: WorkflowStepType(child_node.name),
@@ -199,6 +577,30 @@ fn read_workflow(
                         }
 
                         routed_by_reactor = true;
+                    } else if &key == "disabled" {
+                        if value.is_some() {
+                            return Err(ConfigParseError::InvalidDisabledArgument {
+                                line: get_line_number(&pair),
+                            });
+                        }
+
+                        disabled = true;
+                    } else if &key == "depends_on" {
+                        let names = value
+                            .iter()
+                            .flat_map(|value| value.split(','))
+                            .map(|name| name.trim())
+                            .filter(|name| !name.is_empty())
+                            .map(|name| name.to_string())
+                            .collect::<Vec<_>>();
+
+                        if names.is_empty() {
+                            return Err(ConfigParseError::InvalidDependsOnArgument {
+                                line: get_line_number(&pair),
+                            });
+                        }
+
+                        dependencies.extend(names);
                     } else {
                         let line = get_line_number(&pair);
                         warn!(
@@ -231,18 +633,30 @@ fn read_workflow(
     }
 
     if let Some(name) = workflow_name {
-        if config.workflows.contains_key(&name) {
+        if config.workflows.contains_key(&name) || config.disabled_workflows.contains_key(&name) {
             return Err(ConfigParseError::DuplicateWorkflowName { name });
         }
 
-        config.workflows.insert(
-            name.to_string(),
-            WorkflowDefinition {
-                name,
-                steps,
-                routed_by_reactor,
-            },
-        );
+        config.workflow_step_lines.insert(name.clone(), step_lines);
+        let definition = WorkflowDefinition {
+            name: name.clone(),
+            steps,
+            routed_by_reactor,
+        };
+
+        if disabled {
+            info!("Workflow '{}' is disabled and will not be started", name);
+            config.disabled_workflows.insert(name, definition);
+            return Ok(());
+        }
+
+        if !dependencies.is_empty() {
+            config
+                .workflow_dependencies
+                .insert(name.clone(), dependencies);
+        }
+
+        config.workflows.insert(name, definition);
     } else {
         return Err(ConfigParseError::NoNameOnWorkflow {
             line: starting_line,
@@ -260,7 +674,10 @@ fn read_reactor(
     let mut name = None;
     let mut parameters = HashMap::new();
     let mut executor_name = None;
+    let mut fallback_executors = Vec::new();
     let mut update_interval = 0;
+    let mut workflow_name_template = None;
+    let mut minimum_workflow_lifetime = 0;
 
     for pair in pairs {
         match pair.as_rule() {
@@ -281,6 +698,15 @@ fn read_reactor(
                         if let Some(value) = value {
                             executor_name = Some(value);
                         }
+                    } else if key == "fallback_executors" {
+                        if let Some(value) = value {
+                            fallback_executors = value
+                                .split(',')
+                                .map(|name| name.trim())
+                                .filter(|name| !name.is_empty())
+                                .map(|name| name.to_string())
+                                .collect();
+                        }
                     } else if key == "update_interval" {
                         if let Some(value) = value {
                             if let Ok(num) = value.parse() {
@@ -297,6 +723,28 @@ fn read_reactor(
                                 argument: "".to_string(),
                             });
                         }
+                    } else if key == "minimum_workflow_lifetime" {
+                        if let Some(value) = value {
+                            if let Ok(num) = value.parse() {
+                                minimum_workflow_lifetime = num;
+                            } else {
+                                return Err(
+                                    ConfigParseError::InvalidMinimumWorkflowLifetimeValue {
+                                        line: get_line_number(&pair),
+                                        argument: value,
+                                    },
+                                );
+                            }
+                        } else {
+                            return Err(ConfigParseError::InvalidMinimumWorkflowLifetimeValue {
+                                line: get_line_number(&pair),
+                                argument: "".to_string(),
+                            });
+                        }
+                    } else if key == "workflow_name_template" {
+                        if let Some(value) = value {
+                            workflow_name_template = Some(value);
+                        }
                     } else {
                         let line = get_line_number(&pair);
                         warn!(
@@ -353,7 +801,10 @@ fn read_reactor(
                     name,
                     parameters,
                     executor,
+                    fallback_executors,
                     update_interval: Duration::from_secs(update_interval),
+                    workflow_name_template,
+                    minimum_workflow_lifetime: Duration::from_secs(minimum_workflow_lifetime),
                 },
             );
         } else {
@@ -372,15 +823,19 @@ fn read_reactor(
 
 fn read_argument(pair: Pair<Rule>) -> Result<(String, Option<String>), ConfigParseError> {
     let result;
+    let line = get_line_number(&pair);
+
     // Each argument should have a single child rule based on grammar
     let argument = pair.into_inner().nth(0).unwrap();
     match argument.as_rule() {
         Rule::argument_flag => {
-            result = (argument.as_str().to_string(), None);
+            let flag = expand_environment_variables(argument.as_str(), line)?;
+            result = (flag, None);
         }
 
         Rule::quoted_string_value => {
-            result = (argument.as_str().to_string(), None);
+            let value = expand_environment_variables(argument.as_str(), line)?;
+            result = (value, None);
         }
 
         Rule::key_value_pair => {
@@ -392,13 +847,15 @@ fn read_argument(pair: Pair<Rule>) -> Result<(String, Option<String>), ConfigPar
                     Rule::value => {
                         // If this is a quotes string value, we need to unquote it, otherwise
                         // use the value as-is
-                        value = inner
+                        let raw_value = inner
                             .clone()
                             .into_inner()
                             .filter(|p| p.as_rule() == Rule::quoted_string_value)
                             .map(|p| p.as_str().to_string())
                             .nth(0)
                             .unwrap_or(inner.as_str().to_string());
+
+                        value = expand_environment_variables(&raw_value, line)?;
                     }
 
                     rule => {
@@ -424,6 +881,50 @@ fn read_argument(pair: Pair<Rule>) -> Result<(String, Option<String>), ConfigPar
     Ok(result)
 }
 
+/// Replaces any `${NAME}` references with the value of the `NAME` environment variable.  A
+/// reference can be escaped by using `$${NAME}`, which results in a literal `${NAME}`.
+fn expand_environment_variables(value: &str, line: usize) -> Result<String, ConfigParseError> {
+    let mut result = String::new();
+    let mut remaining = value;
+    while let Some(index) = remaining.find("${") {
+        if index > 0 && remaining[..index].ends_with('$') {
+            // Escaped reference, so the extra dollar sign gets dropped and the rest is kept as-is
+            result.push_str(&remaining[..index - 1]);
+            result.push_str("${");
+            remaining = &remaining[index + 2..];
+            continue;
+        }
+
+        result.push_str(&remaining[..index]);
+        let reference = &remaining[index + 2..];
+        let end = match reference.find('}') {
+            Some(end) => end,
+            None => {
+                // Not a complete reference, so treat it literally
+                result.push_str(&remaining[index..]);
+                remaining = "";
+                break;
+            }
+        };
+
+        let name = &reference[..end];
+        match env::var(name) {
+            Ok(variable_value) => result.push_str(&variable_value),
+            Err(_) => {
+                return Err(ConfigParseError::UndefinedEnvironmentVariable {
+                    name: name.to_string(),
+                    line,
+                })
+            }
+        }
+
+        remaining = &reference[end + 1..];
+    }
+
+    result.push_str(remaining);
+    Ok(result)
+}
+
 fn read_child_node(child_node: Pair<Rule>) -> Result<ChildNode, ConfigParseError> {
     let mut pairs = child_node.into_inner();
     let name_node = pairs.next().unwrap(); // Grammar requires a node name first
@@ -436,6 +937,15 @@ fn read_child_node(child_node: Pair<Rule>) -> Result<ChildNode, ConfigParseError
         match pair.as_rule() {
             Rule::argument => {
                 let (key, value) = read_argument(pair)?;
+
+                // Repeated arguments are merged into a single comma delimited value, so they can
+                // be read the same way as list values
+                let value = match (parsed_node.arguments.remove(&key), value) {
+                    (Some(Some(existing)), Some(value)) => Some(format!("{},{}", existing, value)),
+                    (Some(existing), None) => existing,
+                    (_, value) => value,
+                };
+
                 parsed_node.arguments.insert(key, value);
             }
 
@@ -455,9 +965,175 @@ fn get_line_number(node: &Pair<Rule>) -> usize {
     node.as_span().start_pos().line_col().0
 }
 
+/// Watches the specified config file for changes.  Every time the file changes it's re-parsed,
+/// and the workflow manager is told to upsert any workflows that were added or changed and to
+/// stop any workflows that were removed, compared to the last successfully parsed version of the
+/// file.  If the file fails to parse, or fails the same checks `validate()` performs, the errors
+/// are logged and the running workflows are left alone until a valid config is saved.
+///
+/// Only workflows are reloaded.  Changes to settings and reactors require a restart.  Files
+/// brought in through `include` directives are re-read on reload, but changes to them alone do
+/// not trigger a reload.
+pub fn watch(
+    path: &Path,
+    manager: UnboundedSender<WorkflowManagerRequest>,
+    step_factory: Arc<WorkflowStepFactory>,
+) -> Result<(), ConfigWatchError> {
+    let watch_error = |error| ConfigWatchError::WatchFailed {
+        path: path.display().to_string(),
+        error,
+    };
+
+    let path = path
+        .canonicalize()
+        .map_err(|error| watch_error(notify::Error::io(error)))?;
+
+    // Editors often save by replacing the file, which would end a watch on the file itself, so
+    // watch the directory containing it instead.
+    let directory = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+    let (sender, receiver) = unbounded_channel();
+    let watched_path = path.clone();
+    let handle_event = move |result: notify::Result<notify::Event>| match result {
+        Ok(event) => {
+            let is_config_change = !matches!(event.kind, EventKind::Access(_))
+                && event.paths.iter().any(|path| *path == watched_path);
+
+            if is_config_change {
+                let _ = sender.send(());
+            }
+        }
+
+        Err(error) => error!("Error watching config file: {:?}", error),
+    };
+
+    let mut watcher = notify::recommended_watcher(handle_event).map_err(watch_error)?;
+
+    watcher
+        .watch(&directory, RecursiveMode::NonRecursive)
+        .map_err(watch_error)?;
+
+    let workflows = match parse_file(&path) {
+        Ok(config) => config.workflows,
+        Err(error) => {
+            warn!(
+                "Config file '{}' could not be parsed when starting to watch it: {}",
+                path.display(),
+                error
+            );
+
+            HashMap::new()
+        }
+    };
+
+    info!("Watching config file '{}' for changes", path.display());
+    let reload = reload_on_change(path, workflows, receiver, manager, step_factory, watcher);
+    tokio::spawn(reload);
+
+    Ok(())
+}
+
+async fn reload_on_change(
+    path: PathBuf,
+    mut workflows: HashMap<String, WorkflowDefinition>,
+    mut changes: UnboundedReceiver<()>,
+    manager: UnboundedSender<WorkflowManagerRequest>,
+    step_factory: Arc<WorkflowStepFactory>,
+    _watcher: notify::RecommendedWatcher,
+) {
+    while changes.recv().await.is_some() {
+        tokio::time::sleep(CONFIG_RELOAD_DELAY).await;
+        while changes.try_recv().is_ok() {}
+
+        info!("Config file '{}' changed, reloading it", path.display());
+        let config = match parse_file(&path) {
+            Ok(config) => config,
+            Err(error) => {
+                error!(
+                    "Config file '{}' could not be reloaded, keeping the previous config: {}",
+                    path.display(),
+                    error
+                );
+
+                continue;
+            }
+        };
+
+        let start_order = match get_workflow_start_order(&config) {
+            Ok(start_order) => start_order,
+            Err(error) => {
+                error!(
+                    "Config file '{}' could not be reloaded, keeping the previous config: {}",
+                    path.display(),
+                    error
+                );
+
+                continue;
+            }
+        };
+
+        if let Err(errors) = validate(&config, &step_factory) {
+            for error in &errors {
+                error!("{}", error);
+            }
+
+            error!(
+                "Config file '{}' has {} problem(s), keeping the previous config",
+                path.display(),
+                errors.len()
+            );
+
+            continue;
+        }
+
+        for operation in get_workflow_changes(&workflows, &start_order) {
+            let _ = manager.send(WorkflowManagerRequest {
+                request_id: "config-reload".to_string(),
+                operation,
+            });
+        }
+
+        workflows = config.workflows;
+    }
+
+    info!("Stopped watching config file '{}'", path.display());
+}
+
+/// Gets the workflow manager operations needed to go from the previous set of workflows to the
+/// current set of workflows.  Workflows are upserted in the order they're given in, which should
+/// be their start order.
+fn get_workflow_changes(
+    previous: &HashMap<String, WorkflowDefinition>,
+    current: &[&WorkflowDefinition],
+) -> Vec<WorkflowManagerRequestOperation> {
+    let mut operations = Vec::new();
+    for definition in current {
+        let name = &definition.name;
+        if previous.get(name) != Some(*definition) {
+            info!(workflow_name = %name, "Workflow '{}' was added or changed", name);
+            operations.push(WorkflowManagerRequestOperation::UpsertWorkflow {
+                definition: (*definition).clone(),
+            });
+        }
+    }
+
+    for name in previous.keys() {
+        if !current.iter().any(|definition| &definition.name == name) {
+            info!(workflow_name = %name, "Workflow '{}' was removed", name);
+            operations.push(WorkflowManagerRequestOperation::StopWorkflow {
+                name: name.clone(),
+                response_channel: None,
+            });
+        }
+    }
+
+    operations
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::workflows::steps::factory::StepGenerator;
+    use crate::workflows::steps::{StepCreationError, StepCreationResult, StepValidationResult};
 
     #[test]
     fn can_parse_settings() {
@@ -639,78 +1315,234 @@ reactor name executor=abc {
     }
 
     #[test]
-    fn duplicate_workflow_name_returns_error() {
+    fn can_read_reactor_fallback_executors() {
         let content = "
-workflow name {
-    rtmp_receive port=1935 app=receive stream_key=*
-    hls path=c:\\temp\\test.m3u8 segment_size=\"3\" size=640x480 flag
-}
-
-workflow name {
-    another a
+reactor name executor=abc fallback_executors=def,ghi {
 }
 ";
-        match parse(content) {
-            Err(ConfigParseError::DuplicateWorkflowName { name }) => {
-                if name != "name".to_string() {
-                    panic!("Unexpected name in workflow: '{}'", name);
-                }
-            }
-            Err(e) => panic!(
-                "Expected duplicate workflow name error, instead got: {:?}",
-                e
-            ),
-            Ok(_) => panic!("Received successful parse, but an error was expected"),
-        }
+        let config = parse(content).unwrap();
+        let reactor = &config.reactors["name"];
+        assert_eq!(
+            reactor.fallback_executors,
+            vec!["def".to_string(), "ghi".to_string()],
+            "Unexpected fallback executors"
+        );
     }
 
     #[test]
-    fn full_config_can_be_parsed() {
+    fn reactor_without_fallback_executors_has_none() {
         let content = "
-# comment
-settings {
-    first a # another comment
-    second \"C:\\program files\\ffmpeg\\bin\\ffmpeg.exe\"
-    flag
-
-}
-
-workflow name { #workflow comment
-    rtmp_receive port=1935 app=receive stream_key=* #step comment
-    hls path=c:\\temp\\test.m3u8 segment_size=\"3\" size=640x480 flag
-}
-
-workflow name2 {
-    another a
+reactor name executor=abc {
 }
 ";
-        parse(content).unwrap();
+        let config = parse(content).unwrap();
+        let reactor = &config.reactors["name"];
+        assert!(
+            reactor.fallback_executors.is_empty(),
+            "Expected no fallback executors"
+        );
     }
 
     #[test]
-    fn can_parse_routed_by_reactor_argument_on_workflow() {
+    fn can_read_reactor_workflow_name_template() {
         let content = "
-workflow name routed_by_reactor {
-    rtmp_receive port=1935 app=receive stream_key=*
+reactor name executor=abc workflow_name_template=\"ingest_{stream}\" {
 }
 ";
-
         let config = parse(content).unwrap();
-        let workflow = config.workflows.get("name").unwrap();
-        assert!(
-            workflow.routed_by_reactor,
-            "Expected routed by workflow to be true"
+        let reactor = &config.reactors["name"];
+        assert_eq!(
+            reactor.workflow_name_template,
+            Some("ingest_{stream}".to_string()),
+            "Unexpected workflow name template"
         );
     }
 
     #[test]
-    fn comments_can_have_greater_than_or_less_than_signs() {
+    fn reactor_without_workflow_name_template_has_none() {
         let content = "
-settings {
-  # <test>
+reactor name executor=abc {
 }
 ";
-        parse(content).unwrap();
+        let config = parse(content).unwrap();
+        let reactor = &config.reactors["name"];
+        assert_eq!(
+            reactor.workflow_name_template, None,
+            "Unexpected workflow name template"
+        );
+    }
+
+    #[test]
+    fn can_read_reactor_minimum_workflow_lifetime() {
+        let content = "
+reactor name executor=abc minimum_workflow_lifetime=30 {
+}
+";
+        let config = parse(content).unwrap();
+        let reactor = &config.reactors["name"];
+        assert_eq!(
+            reactor.minimum_workflow_lifetime,
+            Duration::from_secs(30),
+            "Unexpected minimum workflow lifetime"
+        );
+    }
+
+    #[test]
+    fn invalid_reactor_minimum_workflow_lifetime_returns_error() {
+        let content = "
+reactor name executor=abc minimum_workflow_lifetime=abc {
+}
+";
+        match parse(content) {
+            Err(ConfigParseError::InvalidMinimumWorkflowLifetimeValue { argument, .. }) => {
+                assert_eq!(argument, "abc", "Unexpected argument");
+            }
+
+            Err(e) => panic!("Expected invalid lifetime error, instead got: {:?}", e),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn duplicate_workflow_name_returns_error() {
+        let content = "
+workflow name {
+    rtmp_receive port=1935 app=receive stream_key=*
+    hls path=c:\\temp\\test.m3u8 segment_size=\"3\" size=640x480 flag
+}
+
+workflow name {
+    another a
+}
+";
+        match parse(content) {
+            Err(ConfigParseError::DuplicateWorkflowName { name }) => {
+                if name != "name".to_string() {
+                    panic!("Unexpected name in workflow: '{}'", name);
+                }
+            }
+            Err(e) => panic!(
+                "Expected duplicate workflow name error, instead got: {:?}",
+                e
+            ),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn full_config_can_be_parsed() {
+        let content = "
+# comment
+settings {
+    first a # another comment
+    second \"C:\\program files\\ffmpeg\\bin\\ffmpeg.exe\"
+    flag
+
+}
+
+workflow name { #workflow comment
+    rtmp_receive port=1935 app=receive stream_key=* #step comment
+    hls path=c:\\temp\\test.m3u8 segment_size=\"3\" size=640x480 flag
+}
+
+workflow name2 {
+    another a
+}
+";
+        parse(content).unwrap();
+    }
+
+    #[test]
+    fn can_parse_routed_by_reactor_argument_on_workflow() {
+        let content = "
+workflow name routed_by_reactor {
+    rtmp_receive port=1935 app=receive stream_key=*
+}
+";
+
+        let config = parse(content).unwrap();
+        let workflow = config.workflows.get("name").unwrap();
+        assert!(
+            workflow.routed_by_reactor,
+            "Expected routed by workflow to be true"
+        );
+    }
+
+    #[test]
+    fn disabled_workflow_parsed_but_not_included_in_workflows() {
+        let content = "
+workflow name disabled {
+    rtmp_receive port=1935 app=receive stream_key=*
+}
+
+workflow other {
+    rtmp_receive port=1935 app=other stream_key=*
+}
+";
+
+        let config = parse(content).unwrap();
+        assert!(
+            !config.workflows.contains_key("name"),
+            "Expected disabled workflow to not be in workflows"
+        );
+        assert!(
+            config.workflows.contains_key("other"),
+            "Expected enabled workflow to be in workflows"
+        );
+        assert!(
+            config.disabled_workflows.contains_key("name"),
+            "Expected workflow to be marked as disabled"
+        );
+        assert_eq!(
+            config.disabled_workflows["name"].steps.len(),
+            1,
+            "Unexpected number of steps in disabled workflow"
+        );
+    }
+
+    #[test]
+    fn disabled_workflow_contents_are_still_validated() {
+        let content = "
+workflow name disabled {
+    rtmp_receive port=1935 app=receive stream_key=${MMIDS_CONFIG_TEST_UNDEFINED}
+}
+";
+
+        match parse(content) {
+            Err(ConfigParseError::UndefinedEnvironmentVariable { .. }) => (),
+            Err(e) => panic!(
+                "Expected undefined environment variable error, instead got: {:?}",
+                e
+            ),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn disabled_argument_with_value_returns_error() {
+        let content = "
+workflow name disabled=true {
+}
+";
+
+        match parse(content) {
+            Err(ConfigParseError::InvalidDisabledArgument { .. }) => (),
+            Err(e) => panic!(
+                "Expected invalid disabled argument error, instead got: {:?}",
+                e
+            ),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn comments_can_have_greater_than_or_less_than_signs() {
+        let content = "
+settings {
+  # <test>
+}
+";
+        parse(content).unwrap();
     }
 
     #[test]
@@ -723,4 +1555,872 @@ settings {
 
         parse(content).unwrap();
     }
+
+    #[test]
+    fn environment_variables_are_expanded_in_settings_and_arguments() {
+        env::set_var("MMIDS_CONFIG_TEST_DEFINED", "abc");
+        let content = "
+settings {
+    first ${MMIDS_CONFIG_TEST_DEFINED}
+    second \"c:\\${MMIDS_CONFIG_TEST_DEFINED}\\test dir\"
+}
+
+workflow name {
+    rtmp_receive port=1935 app=${MMIDS_CONFIG_TEST_DEFINED}_app
+}
+";
+
+        let config = parse(content).unwrap();
+        assert_eq!(
+            config.settings.get("first"),
+            Some(&Some("abc".to_string())),
+            "Unexpected first value"
+        );
+
+        assert_eq!(
+            config.settings.get("second"),
+            Some(&Some("c:\\abc\\test dir".to_string())),
+            "Unexpected second value"
+        );
+
+        let workflow = config.workflows.get("name").unwrap();
+        assert_eq!(
+            workflow.steps[0].parameters.get("app"),
+            Some(&Some("abc_app".to_string())),
+            "Unexpected app value"
+        );
+    }
+
+    #[test]
+    fn undefined_environment_variable_returns_error() {
+        env::remove_var("MMIDS_CONFIG_TEST_UNDEFINED");
+        let content = "
+settings {
+    first a
+    second ${MMIDS_CONFIG_TEST_UNDEFINED}
+}
+";
+
+        match parse(content) {
+            Err(ConfigParseError::UndefinedEnvironmentVariable { name, line }) => {
+                assert_eq!(name, "MMIDS_CONFIG_TEST_UNDEFINED", "Unexpected name");
+                assert_eq!(line, 4, "Unexpected line");
+            }
+
+            Err(e) => panic!("Expected undefined variable error, instead got: {:?}", e),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn escaped_environment_variable_is_not_expanded() {
+        env::remove_var("MMIDS_CONFIG_TEST_ESCAPED");
+        let content = "
+settings {
+    first $${MMIDS_CONFIG_TEST_ESCAPED}
+}
+";
+
+        let config = parse(content).unwrap();
+        assert_eq!(
+            config.settings.get("first"),
+            Some(&Some("${MMIDS_CONFIG_TEST_ESCAPED}".to_string())),
+            "Unexpected first value"
+        );
+    }
+
+    #[test]
+    fn environment_variable_in_argument_key_returns_error() {
+        env::set_var("MMIDS_CONFIG_TEST_KEY", "app");
+        let content = "
+workflow name {
+    rtmp_receive port=1935 ${MMIDS_CONFIG_TEST_KEY}=abc
+}
+";
+
+        match parse(content) {
+            Err(ConfigParseError::InvalidConfig(_)) => (),
+            Err(e) => panic!("Expected invalid config error, instead got: {:?}", e),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn environment_variable_in_node_name_returns_error() {
+        env::set_var("MMIDS_CONFIG_TEST_NODE", "rtmp_receive");
+        let content = "
+workflow name {
+    ${MMIDS_CONFIG_TEST_NODE} port=1935
+}
+";
+
+        match parse(content) {
+            Err(ConfigParseError::InvalidConfig(_)) => (),
+            Err(e) => panic!("Expected invalid config error, instead got: {:?}", e),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn can_read_comma_delimited_list_argument() {
+        let content = "
+workflow name {
+    transcode codecs=\"h264, aac\"
+}
+";
+
+        let config = parse(content).unwrap();
+        let workflow = config.workflows.get("name").unwrap();
+        assert_eq!(
+            workflow.steps[0].get_list_parameter("codecs"),
+            Some(vec!["h264".to_string(), "aac".to_string()]),
+            "Unexpected codecs value"
+        );
+    }
+
+    #[test]
+    fn repeated_argument_keys_are_merged_into_list() {
+        let content = "
+workflow name {
+    rtmp_receive allow_ips=10.0.0.1 allow_ips=10.0.0.2,10.0.0.3
+}
+";
+
+        let config = parse(content).unwrap();
+        let workflow = config.workflows.get("name").unwrap();
+        assert_eq!(
+            workflow.steps[0].parameters.len(),
+            1,
+            "Unexpected number of parameters"
+        );
+        assert_eq!(
+            workflow.steps[0].get_list_parameter("allow_ips"),
+            Some(vec![
+                "10.0.0.1".to_string(),
+                "10.0.0.2".to_string(),
+                "10.0.0.3".to_string()
+            ]),
+            "Unexpected allow_ips value"
+        );
+    }
+
+    #[test]
+    fn repeated_stream_key_arguments_are_merged_into_list() {
+        let content = "
+workflow name {
+    rtmp_watch stream_key=a stream_key=b
+}
+";
+
+        let config = parse(content).unwrap();
+        let workflow = config.workflows.get("name").unwrap();
+        assert_eq!(
+            workflow.steps[0].get_list_parameter("stream_key"),
+            Some(vec!["a".to_string(), "b".to_string()]),
+            "Unexpected stream_key value"
+        );
+    }
+
+    #[test]
+    fn repeated_flag_argument_keeps_existing_value() {
+        let content = "
+workflow name {
+    rtmp_receive port=1935 port
+}
+";
+
+        let config = parse(content).unwrap();
+        let workflow = config.workflows.get("name").unwrap();
+        assert_eq!(
+            workflow.steps[0].parameters.get("port"),
+            Some(&Some("1935".to_string())),
+            "Unexpected port value"
+        );
+    }
+
+    #[test]
+    fn can_read_depends_on_argument_on_workflow() {
+        let content = "
+workflow name depends_on=first,second depends_on=third {
+}
+
+workflow first {
+}
+
+workflow second {
+}
+
+workflow third {
+}
+";
+
+        let config = parse(content).unwrap();
+        assert_eq!(
+            config.workflow_dependencies.get("name"),
+            Some(&vec![
+                "first".to_string(),
+                "second".to_string(),
+                "third".to_string()
+            ]),
+            "Unexpected dependencies"
+        );
+
+        assert!(
+            !config.workflow_dependencies.contains_key("first"),
+            "Expected no dependencies for workflow without depends_on"
+        );
+    }
+
+    #[test]
+    fn depends_on_argument_without_value_returns_error() {
+        let content = "
+workflow name depends_on {
+}
+";
+
+        match parse(content) {
+            Err(ConfigParseError::InvalidDependsOnArgument { line }) => {
+                assert_eq!(line, 2, "Unexpected line number");
+            }
+
+            Err(e) => panic!("Expected invalid depends on error, instead got: {:?}", e),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn workflows_started_after_their_dependencies() {
+        let content = "
+workflow a depends_on=c {
+}
+
+workflow b {
+}
+
+workflow c depends_on=d {
+}
+
+workflow d {
+}
+";
+
+        let config = parse(content).unwrap();
+        let order = get_workflow_start_order(&config)
+            .unwrap()
+            .into_iter()
+            .map(|workflow| workflow.name.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(order, vec!["b", "d", "c", "a"], "Unexpected start order");
+    }
+
+    #[test]
+    fn unknown_dependency_returns_error() {
+        let content = "
+workflow name depends_on=other {
+}
+";
+
+        match parse(content) {
+            Err(ConfigParseError::UnknownDependency {
+                workflow,
+                dependency,
+            }) => {
+                assert_eq!(workflow, "name", "Unexpected workflow");
+                assert_eq!(dependency, "other", "Unexpected dependency");
+            }
+
+            Err(e) => panic!("Expected unknown dependency error, instead got: {:?}", e),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn dependency_on_disabled_workflow_returns_error() {
+        let content = "
+workflow name depends_on=other {
+}
+
+workflow other disabled {
+}
+";
+
+        match parse(content) {
+            Err(ConfigParseError::DisabledDependency {
+                workflow,
+                dependency,
+            }) => {
+                assert_eq!(workflow, "name", "Unexpected workflow");
+                assert_eq!(dependency, "other", "Unexpected dependency");
+            }
+
+            Err(e) => panic!("Expected disabled dependency error, instead got: {:?}", e),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn dependency_cycle_returns_error() {
+        let content = "
+workflow a depends_on=b {
+}
+
+workflow b depends_on=c {
+}
+
+workflow c depends_on=b {
+}
+";
+
+        match parse(content) {
+            Err(ConfigParseError::DependencyCycle { workflow }) => {
+                assert!(
+                    workflow == "b" || workflow == "c",
+                    "Unexpected workflow in cycle: {}",
+                    workflow
+                );
+            }
+
+            Err(e) => panic!("Expected dependency cycle error, instead got: {:?}", e),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn workflow_depending_on_itself_returns_error() {
+        let content = "
+workflow name depends_on=name {
+}
+";
+
+        match parse(content) {
+            Err(ConfigParseError::DependencyCycle { workflow }) => {
+                assert_eq!(workflow, "name", "Unexpected workflow");
+            }
+
+            Err(e) => panic!("Expected dependency cycle error, instead got: {:?}", e),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    struct KindOnlyStepGenerator {
+        kind: StepKind,
+    }
+
+    /// Only considers a step valid if it has a `valid` parameter
+    struct ValidatingStepGenerator;
+
+    impl StepGenerator for ValidatingStepGenerator {
+        fn generate(&self, _definition: WorkflowStepDefinition) -> StepCreationResult {
+            Err(StepCreationError::Fatal("Not supported in tests".into()))
+        }
+
+        fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+            match definition.parameters.get("valid") {
+                Some(_) => Ok(()),
+                None => Err("No valid parameter".into()),
+            }
+        }
+    }
+
+    impl StepGenerator for KindOnlyStepGenerator {
+        fn generate(&self, _definition: WorkflowStepDefinition) -> StepCreationResult {
+            Err(StepCreationError::Fatal("Not supported in tests".into()))
+        }
+
+        fn kind(&self) -> StepKind {
+            self.kind
+        }
+    }
+
+    fn create_step_factory() -> WorkflowStepFactory {
+        let mut factory = WorkflowStepFactory::new();
+        for (name, kind) in [
+            ("receive", StepKind::Source),
+            ("transcode", StepKind::Transform),
+            ("watch", StepKind::Sink),
+        ] {
+            factory
+                .register(
+                    WorkflowStepType(name.to_string()),
+                    Box::new(KindOnlyStepGenerator { kind }),
+                )
+                .unwrap();
+        }
+
+        factory
+            .register(
+                WorkflowStepType("check".to_string()),
+                Box::new(ValidatingStepGenerator),
+            )
+            .unwrap();
+
+        factory
+    }
+
+    #[test]
+    fn step_order_valid_when_source_is_first() {
+        let content = "
+workflow name {
+    receive
+    transcode
+    watch
+}
+";
+
+        let config = parse(content).unwrap();
+        validate_step_order(&config, &create_step_factory()).unwrap();
+    }
+
+    #[test]
+    fn step_order_valid_when_workflow_has_no_source_steps() {
+        let content = "
+workflow name {
+    transcode
+    watch
+}
+";
+
+        let config = parse(content).unwrap();
+        validate_step_order(&config, &create_step_factory()).unwrap();
+    }
+
+    #[test]
+    fn step_order_invalid_when_sink_comes_before_source() {
+        let content = "
+workflow name {
+    watch
+    receive
+}
+";
+
+        let config = parse(content).unwrap();
+        match validate_step_order(&config, &create_step_factory()) {
+            Err(ConfigParseError::InvalidStepOrder { workflow, line }) => {
+                assert_eq!(workflow, "name", "Unexpected workflow name");
+                assert_eq!(line, 3, "Unexpected line number");
+            }
+
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn step_order_invalid_when_transform_comes_before_source() {
+        let content = "
+workflow name {
+    receive
+}
+
+workflow other {
+    watch
+    transcode
+    receive
+}
+";
+
+        let config = parse(content).unwrap();
+        match validate_step_order(&config, &create_step_factory()) {
+            Err(ConfigParseError::InvalidStepOrder { workflow, line }) => {
+                assert_eq!(workflow, "other", "Unexpected workflow name");
+                assert_eq!(line, 7, "Unexpected line number");
+            }
+
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn unregistered_step_types_ignored_for_step_order() {
+        let content = "
+workflow name {
+    unknown
+    receive
+    watch
+}
+";
+
+        let config = parse(content).unwrap();
+        validate_step_order(&config, &create_step_factory()).unwrap();
+    }
+
+    #[test]
+    fn validate_passes_when_all_steps_valid() {
+        let content = "
+workflow name {
+    receive
+    check valid
+    watch
+}
+";
+
+        let config = parse(content).unwrap();
+        validate(&config, &create_step_factory()).unwrap();
+    }
+
+    #[test]
+    fn validate_returns_all_invalid_steps_with_line_numbers() {
+        let content = "
+workflow second {
+    receive
+    check
+}
+
+workflow first {
+    check
+    check valid
+    unknown
+}
+";
+
+        let config = parse(content).unwrap();
+        let errors = match validate(&config, &create_step_factory()) {
+            Err(errors) => errors,
+            Ok(()) => panic!("Expected validation errors"),
+        };
+
+        let errors = errors
+            .into_iter()
+            .map(|error| match error {
+                ConfigParseError::InvalidStep { workflow, line, .. } => (workflow, line),
+                error => panic!("Unexpected error: {:?}", error),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            errors,
+            vec![
+                ("first".to_string(), 8),
+                ("first".to_string(), 10),
+                ("second".to_string(), 4),
+            ],
+            "Unexpected errors"
+        );
+    }
+
+    #[test]
+    fn validate_includes_step_order_errors() {
+        let content = "
+workflow name {
+    check
+    receive
+}
+";
+
+        let config = parse(content).unwrap();
+        let errors = match validate(&config, &create_step_factory()) {
+            Err(errors) => errors,
+            Ok(()) => panic!("Expected validation errors"),
+        };
+
+        assert_eq!(errors.len(), 2, "Unexpected number of errors");
+        match &errors[0] {
+            ConfigParseError::InvalidStepOrder { line, .. } => {
+                assert_eq!(*line, 3, "Unexpected step order line")
+            }
+
+            error => panic!("Unexpected error: {:?}", error),
+        }
+
+        match &errors[1] {
+            ConfigParseError::InvalidStep { line, .. } => {
+                assert_eq!(*line, 3, "Unexpected invalid step line")
+            }
+
+            error => panic!("Unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn validate_reports_invalid_steps_in_disabled_workflows() {
+        let content = "
+workflow name disabled {
+    receive
+    check
+}
+";
+
+        let config = parse(content).unwrap();
+        let errors = match validate(&config, &create_step_factory()) {
+            Err(errors) => errors,
+            Ok(()) => panic!("Expected validation errors"),
+        };
+
+        assert_eq!(errors.len(), 1, "Unexpected number of errors");
+        match &errors[0] {
+            ConfigParseError::InvalidStep { workflow, line, .. } => {
+                assert_eq!(workflow, "name", "Unexpected workflow");
+                assert_eq!(*line, 4, "Unexpected invalid step line");
+            }
+
+            error => panic!("Unexpected error: {:?}", error),
+        }
+    }
+
+    fn get_test_dir(name: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!("mmids-config-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+
+        path
+    }
+
+    #[test]
+    fn included_files_are_merged_relative_to_including_file() {
+        let dir = get_test_dir("include");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(
+            dir.join("mmids.config"),
+            "
+settings {
+    first a
+}
+
+include \"sub/other.mmids\"
+
+workflow name {
+    step a
+}
+",
+        )
+        .unwrap();
+
+        std::fs::write(
+            dir.join("sub").join("other.mmids"),
+            "
+settings {
+    second b
+}
+
+workflow name2 {
+    step b
+}
+",
+        )
+        .unwrap();
+
+        let config = parse_file(&dir.join("mmids.config")).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(config.workflows.len(), 2, "Unexpected number of workflows");
+        assert!(config.workflows.contains_key("name"), "Missing workflow 'name'");
+        assert!(config.workflows.contains_key("name2"), "Missing workflow 'name2'");
+        assert_eq!(
+            config.settings.get("first"),
+            Some(&Some("a".to_string())),
+            "Unexpected first value"
+        );
+        assert_eq!(
+            config.settings.get("second"),
+            Some(&Some("b".to_string())),
+            "Unexpected second value"
+        );
+    }
+
+    #[test]
+    fn duplicate_workflow_name_across_included_files_returns_error() {
+        let dir = get_test_dir("include-duplicate");
+        std::fs::write(
+            dir.join("mmids.config"),
+            "
+include \"other.mmids\"
+
+workflow name {
+    step a
+}
+",
+        )
+        .unwrap();
+
+        std::fs::write(dir.join("other.mmids"), "workflow name {\n    step b\n}\n").unwrap();
+
+        let result = parse_file(&dir.join("mmids.config"));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        match result {
+            Err(ConfigParseError::DuplicateWorkflowName { name }) => {
+                assert_eq!(name, "name", "Unexpected workflow name");
+            }
+
+            Err(e) => panic!("Expected duplicate workflow name error, instead got: {:?}", e),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn include_cycle_returns_error() {
+        let dir = get_test_dir("include-cycle");
+        std::fs::write(dir.join("mmids.config"), "include \"other.mmids\"\n").unwrap();
+        std::fs::write(dir.join("other.mmids"), "include \"mmids.config\"\n").unwrap();
+
+        let result = parse_file(&dir.join("mmids.config"));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        match result {
+            Err(ConfigParseError::InvalidIncludedFile { error, .. }) => match *error {
+                ConfigParseError::IncludeCycle { .. } => (),
+                e => panic!("Expected include cycle error, instead got: {:?}", e),
+            },
+
+            Err(e) => panic!("Expected include cycle error, instead got: {:?}", e),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn errors_in_included_file_name_the_file() {
+        let dir = get_test_dir("include-error");
+        std::fs::write(
+            dir.join("mmids.config"),
+            "
+settings {
+}
+
+include \"other.mmids\"
+",
+        )
+        .unwrap();
+
+        std::fs::write(dir.join("other.mmids"), "\nunknown {\n}\n").unwrap();
+
+        let result = parse_file(&dir.join("mmids.config"));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        match result {
+            Err(ConfigParseError::InvalidIncludedFile { path, line, error }) => {
+                assert!(path.ends_with("other.mmids"), "Unexpected path: {}", path);
+                assert_eq!(line, 5, "Unexpected include line");
+                match *error {
+                    ConfigParseError::InvalidNodeName { line, .. } => {
+                        assert_eq!(line, 2, "Unexpected line in included file")
+                    }
+
+                    e => panic!("Expected invalid node name error, instead got: {:?}", e),
+                }
+            }
+
+            Err(e) => panic!("Expected invalid included file error, instead got: {:?}", e),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn include_not_allowed_when_parsing_text() {
+        let content = "
+include \"other.mmids\"
+";
+
+        match parse(content) {
+            Err(ConfigParseError::IncludeNotAllowed { line }) => {
+                assert_eq!(line, 2, "Unexpected line number");
+            }
+
+            Err(e) => panic!("Expected include not allowed error, instead got: {:?}", e),
+            Ok(_) => panic!("Received successful parse, but an error was expected"),
+        }
+    }
+
+    #[test]
+    fn workflow_changes_include_added_changed_and_removed_workflows() {
+        let previous = parse(
+            "
+workflow unchanged {
+    rtmp_receive port=1935 rtmp_app=receive stream_key=*
+}
+
+workflow changed {
+    rtmp_receive port=1935 rtmp_app=receive2 stream_key=*
+}
+
+workflow removed {
+    rtmp_receive port=1935 rtmp_app=receive3 stream_key=*
+}
+",
+        )
+        .unwrap();
+
+        let current = parse(
+            "
+workflow unchanged {
+    rtmp_receive port=1935 rtmp_app=receive stream_key=*
+}
+
+workflow changed {
+    rtmp_receive port=1935 rtmp_app=receive2 stream_key=abc
+}
+
+workflow added {
+    rtmp_receive port=1935 rtmp_app=receive4 stream_key=*
+}
+",
+        )
+        .unwrap();
+
+        let start_order = get_workflow_start_order(&current).unwrap();
+        let operations = get_workflow_changes(&previous.workflows, &start_order);
+
+        let mut upserted = Vec::new();
+        let mut stopped = Vec::new();
+        for operation in operations {
+            match operation {
+                WorkflowManagerRequestOperation::UpsertWorkflow { definition } => {
+                    upserted.push(definition.name);
+                }
+
+                WorkflowManagerRequestOperation::StopWorkflow { name, .. } => stopped.push(name),
+                _ => panic!("Unexpected workflow manager operation"),
+            }
+        }
+
+        upserted.sort();
+        assert_eq!(upserted, vec!["added", "changed"], "Unexpected upserts");
+        assert_eq!(stopped, vec!["removed"], "Unexpected stops");
+    }
+
+    #[test]
+    fn workflow_changes_upsert_dependencies_first() {
+        let previous = parse("").unwrap();
+        let current = parse(
+            "
+workflow a depends_on=b {
+    rtmp_receive port=1935 rtmp_app=receive stream_key=*
+}
+
+workflow b {
+    rtmp_receive port=1935 rtmp_app=receive2 stream_key=*
+}
+",
+        )
+        .unwrap();
+
+        let start_order = get_workflow_start_order(&current).unwrap();
+        let operations = get_workflow_changes(&previous.workflows, &start_order);
+
+        let upserted = operations
+            .into_iter()
+            .map(|operation| match operation {
+                WorkflowManagerRequestOperation::UpsertWorkflow { definition } => definition.name,
+                _ => panic!("Unexpected workflow manager operation"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(upserted, vec!["b", "a"], "Unexpected upserts");
+    }
+
+    #[test]
+    fn no_workflow_changes_for_identical_configs() {
+        let content = "
+workflow name {
+    rtmp_receive port=1935 rtmp_app=receive stream_key=*
+}
+";
+
+        let previous = parse(content).unwrap();
+        let current = parse(content).unwrap();
+        let start_order = get_workflow_start_order(&current).unwrap();
+        let operations = get_workflow_changes(&previous.workflows, &start_order);
+
+        assert!(operations.is_empty(), "Expected no workflow changes");
+    }
 }
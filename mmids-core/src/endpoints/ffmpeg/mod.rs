@@ -121,6 +121,9 @@ pub enum TargetParams {
         /// The maximum number of segments that should be in the playlist.  If none is specified
         /// than ffmpeg's default will be used
         max_entries: Option<u16>,
+
+        /// If segments should be deleted from disk once they are no longer in the playlist
+        delete_old_segments: bool,
     },
 }
 
@@ -444,6 +447,7 @@ impl Actor {
                 path,
                 max_entries,
                 segment_length,
+                delete_old_segments,
             } => {
                 args.push("hls".to_string());
 
@@ -455,6 +459,11 @@ impl Actor {
                     args.push(entries.to_string());
                 }
 
+                if *delete_old_segments {
+                    args.push("-hls_flags".to_string());
+                    args.push("delete_segments".to_string());
+                }
+
                 args.push(path.clone());
             }
         }
@@ -5,3 +5,4 @@
 
 pub mod ffmpeg;
 pub mod rtmp_server;
+pub mod srt_server;
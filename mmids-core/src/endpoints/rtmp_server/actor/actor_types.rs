@@ -1,8 +1,9 @@
 use super::connection_handler::{ConnectionRequest, ConnectionResponse};
+use super::connection_rate_limiter::ConnectionRateLimiter;
 use super::{RtmpEndpointPublisherMessage, RtmpEndpointRequest, StreamKeyRegistration};
 use crate::codecs::{AudioCodec, VideoCodec};
 use crate::endpoints::rtmp_server::{
-    IpRestriction, RtmpEndpointMediaData, RtmpEndpointMediaMessage,
+    IpRestriction, RtmpAppMatching, RtmpEndpointMediaData, RtmpEndpointMediaMessage,
     RtmpEndpointWatcherNotification, ValidationResponse,
 };
 
@@ -12,9 +13,13 @@ use crate::StreamId;
 use bytes::Bytes;
 use futures::future::BoxFuture;
 use futures::stream::FuturesUnordered;
+use rml_rtmp::sessions::StreamMetadata;
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tracing::Span;
 
 pub enum FutureResult {
     EndpointRequestReceived {
@@ -59,6 +64,7 @@ pub enum FutureResult {
         stream_key: String,
         stream_key_registration: StreamKeyRegistration,
         receiver: UnboundedReceiver<RtmpEndpointMediaMessage>,
+        media_backlog: Option<Arc<AtomicUsize>>,
     },
 
     PortGone {
@@ -75,14 +81,20 @@ pub struct PublishingRegistrant {
     pub stream_id: Option<StreamId>,
     pub ip_restrictions: IpRestriction,
     pub requires_registrant_approval: bool,
+    pub connection_rate_limiter: Option<ConnectionRateLimiter>,
     pub cancellation_notifier: UnboundedReceiver<()>,
+
+    pub bytes_received: u64,
 }
 
 pub struct WatcherRegistrant {
     pub response_channel: UnboundedSender<RtmpEndpointWatcherNotification>,
     pub ip_restrictions: IpRestriction,
     pub requires_registrant_approval: bool,
+    pub max_watchers: Option<usize>,
+    pub start_on_keyframe: bool,
     pub cancellation_notifier: UnboundedReceiver<()>,
+    pub bytes_sent: u64,
 }
 
 pub struct VideoSequenceHeader {
@@ -97,16 +109,25 @@ pub struct AudioSequenceHeader {
 
 pub struct WatcherDetails {
     pub media_sender: UnboundedSender<RtmpEndpointMediaData>,
+
+    /// If true, audio and video (other than sequence headers) are withheld from this watcher
+    /// until a keyframe is received.
+    pub awaiting_keyframe: bool,
 }
 
 pub struct StreamKeyConnections {
+    /// The stream key the connections are for.  This may differ from the key the connections are
+    /// tracked under, as streams for registrations on any app are also keyed by the app name.
+    pub stream_key: String,
     pub publisher: Option<ConnectionId>,
     pub watchers: HashMap<ConnectionId, WatcherDetails>,
     pub latest_video_sequence_header: Option<VideoSequenceHeader>,
     pub latest_audio_sequence_header: Option<AudioSequenceHeader>,
+    pub latest_metadata: Option<StreamMetadata>,
 }
 
 pub struct RtmpAppMapping {
+    pub app_matching: RtmpAppMatching,
     pub publisher_registrants: HashMap<StreamKeyRegistration, PublishingRegistrant>,
     pub watcher_registrants: HashMap<StreamKeyRegistration, WatcherRegistrant>,
     pub active_stream_keys: HashMap<String, StreamKeyConnections>,
@@ -126,14 +147,19 @@ pub struct RtmpServerEndpointActor {
 pub enum ListenerRequest {
     Publisher {
         channel: UnboundedSender<RtmpEndpointPublisherMessage>,
+        app_matching: RtmpAppMatching,
         stream_id: Option<StreamId>,
         requires_registrant_approval: bool,
+        max_connects_per_minute: Option<u32>,
     },
 
     Watcher {
         notification_channel: UnboundedSender<RtmpEndpointWatcherNotification>,
         media_channel: UnboundedReceiver<RtmpEndpointMediaMessage>,
+        media_backlog: Option<Arc<AtomicUsize>>,
         requires_registrant_approval: bool,
+        max_watchers: Option<usize>,
+        start_on_keyframe: bool,
     },
 }
 
@@ -166,6 +192,10 @@ pub struct Connection {
     pub state: ConnectionState,
     pub socket_address: SocketAddr,
     pub received_registrant_approval: bool,
+
+    /// Span that all processing for this connection is done under, both in the endpoint and in
+    /// the connection's handler, so logs for a single connection can be correlated.
+    pub span: Span,
 }
 
 pub struct PortMapping {
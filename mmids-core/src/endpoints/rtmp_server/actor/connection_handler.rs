@@ -6,6 +6,7 @@ use rml_rtmp::sessions::{
 use std::io::Cursor;
 
 use super::RtmpEndpointPublisherMessage;
+use crate::codecs::aac::{adts_to_raw, AudioSpecificConfig};
 use crate::codecs::{AudioCodec, VideoCodec};
 use crate::endpoints::rtmp_server::RtmpEndpointMediaData;
 use crate::net::tcp::OutboundPacket;
@@ -17,7 +18,7 @@ use futures::{FutureExt, StreamExt};
 use rml_rtmp::handshake::{Handshake, HandshakeProcessResult, PeerType};
 use rml_rtmp::time::RtmpTimestamp;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-use tracing::{debug, error, info, instrument};
+use tracing::{debug, error, info, instrument, warn};
 
 pub struct RtmpServerConnectionHandler {
     id: ConnectionId,
@@ -49,6 +50,10 @@ pub enum ConnectionRequest {
         stream_key: String,
     },
 
+    PublishedBytesReceived {
+        byte_count: usize,
+    },
+
     PublishFinished,
     PlaybackFinished,
 }
@@ -113,18 +118,18 @@ enum FutureResult {
     RtmpServerEndpointGone,
 }
 
-struct UnwrappedVideo {
-    codec: VideoCodec,
-    is_keyframe: bool,
-    is_sequence_header: bool,
-    data: Bytes,
-    composition_time_in_ms: i32,
+pub(crate) struct UnwrappedVideo {
+    pub(crate) codec: VideoCodec,
+    pub(crate) is_keyframe: bool,
+    pub(crate) is_sequence_header: bool,
+    pub(crate) data: Bytes,
+    pub(crate) composition_time_in_ms: i32,
 }
 
-struct UnwrappedAudio {
-    codec: AudioCodec,
-    is_sequence_header: bool,
-    data: Bytes,
+pub(crate) struct UnwrappedAudio {
+    pub(crate) codec: AudioCodec,
+    pub(crate) is_sequence_header: bool,
+    pub(crate) data: Bytes,
 }
 
 impl RtmpServerConnectionHandler {
@@ -570,6 +575,8 @@ impl RtmpServerConnectionHandler {
                     codec,
                 } = unwrap_audio_from_flv(data);
 
+                self.record_published_bytes(data.len());
+
                 let _ = self.published_event_channel.as_ref().unwrap().send(
                     RtmpEndpointPublisherMessage::NewAudioData {
                         publisher: self.id.clone(),
@@ -623,6 +630,8 @@ impl RtmpServerConnectionHandler {
                     composition_time_in_ms,
                 } = unwrap_video_from_flv(data);
 
+                self.record_published_bytes(data.len());
+
                 let _ = self.published_event_channel.as_ref().unwrap().send(
                     RtmpEndpointPublisherMessage::NewVideoData {
                         publisher: self.id.clone(),
@@ -847,6 +856,12 @@ impl RtmpServerConnectionHandler {
         }
     }
 
+    fn record_published_bytes(&self, byte_count: usize) {
+        let _ = self
+            .request_sender
+            .send(ConnectionRequest::PublishedBytesReceived { byte_count });
+    }
+
     fn handle_endpoint_publish_request_accepted(
         &mut self,
         channel: UnboundedSender<RtmpEndpointPublisherMessage>,
@@ -1021,7 +1036,7 @@ impl RtmpServerConnectionHandler {
     }
 }
 
-fn unwrap_video_from_flv(mut data: Bytes) -> UnwrappedVideo {
+pub(crate) fn unwrap_video_from_flv(mut data: Bytes) -> UnwrappedVideo {
     if data.len() < 2 {
         return UnwrappedVideo {
             codec: VideoCodec::Unknown,
@@ -1063,7 +1078,7 @@ fn unwrap_video_from_flv(mut data: Bytes) -> UnwrappedVideo {
     }
 }
 
-fn wrap_video_into_flv(
+pub(crate) fn wrap_video_into_flv(
     data: Bytes,
     codec: VideoCodec,
     is_keyframe: bool,
@@ -1095,7 +1110,7 @@ fn wrap_video_into_flv(
     }
 }
 
-fn unwrap_audio_from_flv(mut data: Bytes) -> UnwrappedAudio {
+pub(crate) fn unwrap_audio_from_flv(mut data: Bytes) -> UnwrappedAudio {
     if data.len() < 2 {
         return UnwrappedAudio {
             codec: AudioCodec::Unknown,
@@ -1106,21 +1121,43 @@ fn unwrap_audio_from_flv(mut data: Bytes) -> UnwrappedAudio {
 
     let flv_tag = data.split_to(1);
     let packet_type = data.split_to(1);
-    let is_sequence_header = packet_type[0] == 0;
-    let codec = if flv_tag[0] & 0xa0 == 0xa0 {
-        AudioCodec::Aac
+    if flv_tag[0] & 0xa0 != 0xa0 {
+        return UnwrappedAudio {
+            codec: AudioCodec::Unknown,
+            is_sequence_header: packet_type[0] == 0,
+            data,
+        };
+    }
+
+    // Only a valid audio specific config is treated as an AAC sequence header, otherwise
+    // downstream consumers would end up trying to configure decoders with garbage.
+    let is_sequence_header = if packet_type[0] == 0 {
+        match AudioSpecificConfig::parse(&data) {
+            Ok(_) => true,
+            Err(error) => {
+                warn!("Received AAC sequence header with invalid audio specific config: {error}");
+                false
+            }
+        }
     } else {
-        AudioCodec::Unknown
+        false
+    };
+
+    // Some encoders send ADTS framed AAC over RTMP, while everything downstream expects raw AAC
+    let data = if is_sequence_header {
+        data
+    } else {
+        adts_to_raw(&data).unwrap_or(data)
     };
 
     UnwrappedAudio {
-        codec,
+        codec: AudioCodec::Aac,
         is_sequence_header,
         data,
     }
 }
 
-fn wrap_audio_into_flv(
+pub(crate) fn wrap_audio_into_flv(
     data: Bytes,
     codec: AudioCodec,
     is_sequence_header: bool,
@@ -1129,6 +1166,13 @@ fn wrap_audio_into_flv(
         AudioCodec::Aac => {
             let flv_tag = 0xaf;
             let packet_type = if is_sequence_header { 0 } else { 1 };
+            let data = if is_sequence_header {
+                data
+            } else {
+                // FLV only allows raw AAC frames
+                adts_to_raw(&data).unwrap_or(data)
+            };
+
             let mut wrapped = BytesMut::new();
             wrapped.put_u8(flv_tag);
             wrapped.put_u8(packet_type);
@@ -0,0 +1,159 @@
+//! Token bucket rate limiting of new connections, keyed on the remote IP address of the client.
+//! Each IP address gets a bucket holding up to the maximum number of connections allowed per
+//! minute, which is refilled continuously over the course of a minute.
+//!
+//! Buckets are only tracked while they are partially drained.  Once a bucket would have been
+//! refilled it's indistinguishable from a brand new bucket, so it's removed to keep IP addresses
+//! that have gone idle from growing the map forever.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+const REFILL_PERIOD: Duration = Duration::from_secs(60);
+
+pub struct ConnectionRateLimiter {
+    max_connects_per_minute: u32,
+    buckets: HashMap<IpAddr, TokenBucket>,
+    last_pruned_at: Instant,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refilled_at: Instant,
+}
+
+impl ConnectionRateLimiter {
+    pub fn new(max_connects_per_minute: u32) -> Self {
+        ConnectionRateLimiter {
+            max_connects_per_minute,
+            buckets: HashMap::new(),
+            last_pruned_at: Instant::now(),
+        }
+    }
+
+    /// Attempts to take a connection token for the specified IP address.  Returns false if the IP
+    /// address has exceeded its allowed connection rate, and the connection should be rejected.
+    pub fn try_acquire(&mut self, ip_address: IpAddr, now: Instant) -> bool {
+        if now.saturating_duration_since(self.last_pruned_at) >= REFILL_PERIOD {
+            self.prune_idle_buckets(now);
+        }
+
+        let capacity = self.max_connects_per_minute as f64;
+        let bucket = self.buckets.entry(ip_address).or_insert(TokenBucket {
+            tokens: capacity,
+            last_refilled_at: now,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.last_refilled_at);
+        let refilled = elapsed.as_secs_f64() / REFILL_PERIOD.as_secs_f64() * capacity;
+        bucket.tokens = (bucket.tokens + refilled).min(capacity);
+        bucket.last_refilled_at = now;
+
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    fn prune_idle_buckets(&mut self, now: Instant) {
+        // Every bucket refills completely within a single refill period, so any bucket that
+        // hasn't been touched in that long is full and no longer needs to be tracked.
+        self.buckets.retain(|_, bucket| {
+            now.saturating_duration_since(bucket.last_refilled_at) < REFILL_PERIOD
+        });
+
+        self.last_pruned_at = now;
+    }
+
+    #[cfg(test)]
+    fn tracked_ip_count(&self) -> usize {
+        self.buckets.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn ip(last_octet: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, last_octet))
+    }
+
+    #[test]
+    fn connections_allowed_up_to_limit() {
+        let mut limiter = ConnectionRateLimiter::new(3);
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire(ip(1), now), "First connection rejected");
+        assert!(
+            limiter.try_acquire(ip(1), now),
+            "Second connection rejected"
+        );
+        assert!(limiter.try_acquire(ip(1), now), "Third connection rejected");
+        assert!(
+            !limiter.try_acquire(ip(1), now),
+            "Fourth connection allowed"
+        );
+    }
+
+    #[test]
+    fn limits_are_tracked_per_ip_address() {
+        let mut limiter = ConnectionRateLimiter::new(1);
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire(ip(1), now), "First ip rejected");
+        assert!(limiter.try_acquire(ip(2), now), "Second ip rejected");
+        assert!(
+            !limiter.try_acquire(ip(1), now),
+            "First ip allowed a second time"
+        );
+    }
+
+    #[test]
+    fn tokens_refill_over_time() {
+        let mut limiter = ConnectionRateLimiter::new(2);
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire(ip(1), now), "First connection rejected");
+        assert!(
+            limiter.try_acquire(ip(1), now),
+            "Second connection rejected"
+        );
+        assert!(!limiter.try_acquire(ip(1), now), "Third connection allowed");
+
+        let later = now + Duration::from_secs(30);
+        assert!(
+            limiter.try_acquire(ip(1), later),
+            "Connection rejected after refill"
+        );
+        assert!(
+            !limiter.try_acquire(ip(1), later),
+            "Second connection allowed after refilling a single token"
+        );
+    }
+
+    #[test]
+    fn zero_limit_rejects_all_connections() {
+        let mut limiter = ConnectionRateLimiter::new(0);
+        let now = Instant::now();
+
+        assert!(!limiter.try_acquire(ip(1), now), "Connection allowed");
+    }
+
+    #[test]
+    fn idle_ip_addresses_are_no_longer_tracked() {
+        let mut limiter = ConnectionRateLimiter::new(5);
+        let now = Instant::now();
+        limiter.try_acquire(ip(1), now);
+        limiter.try_acquire(ip(2), now);
+        assert_eq!(limiter.tracked_ip_count(), 2, "Unexpected tracked ip count");
+
+        let later = now + REFILL_PERIOD + Duration::from_secs(1);
+        limiter.try_acquire(ip(3), later);
+        assert_eq!(limiter.tracked_ip_count(), 1, "Unexpected tracked ip count");
+    }
+}
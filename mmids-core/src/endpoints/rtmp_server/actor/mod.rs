@@ -1,16 +1,24 @@
 pub mod actor_types;
 mod connection_handler;
+mod connection_rate_limiter;
+
+pub(crate) use connection_handler::{
+    unwrap_audio_from_flv, unwrap_video_from_flv, wrap_audio_into_flv, wrap_video_into_flv,
+    UnwrappedAudio, UnwrappedVideo,
+};
 
 #[cfg(test)]
 mod tests;
 
 use super::{
-    RtmpEndpointMediaData, RtmpEndpointPublisherMessage, RtmpEndpointRequest, StreamKeyRegistration,
+    RtmpEndpointMediaData, RtmpEndpointPublisherMessage, RtmpEndpointRequest,
+    RtmpEndpointStatistics, RtmpRegistrationStatistics, StreamKeyRegistration,
 };
 use crate::endpoints::rtmp_server::actor::connection_handler::ConnectionResponse;
 use crate::endpoints::rtmp_server::actor::internal_futures::wait_for_validation;
 use crate::endpoints::rtmp_server::{
-    IpRestriction, RegistrationType, RtmpEndpointWatcherNotification, ValidationResponse,
+    IpRestriction, RegistrationType, RtmpAppMatching, RtmpEndpointWatcherNotification,
+    ValidationResponse,
 };
 use crate::net::tcp::{TcpSocketRequest, TcpSocketResponse};
 use crate::net::ConnectionId;
@@ -18,14 +26,16 @@ use crate::reactors::ReactorWorkflowUpdate;
 use crate::StreamId;
 use actor_types::*;
 use connection_handler::{ConnectionRequest, RtmpServerConnectionHandler};
+use connection_rate_limiter::ConnectionRateLimiter;
 use futures::future::{BoxFuture, FutureExt};
 use futures::StreamExt;
 use rml_rtmp::time::RtmpTimestamp;
 use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::time::Instant;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::oneshot::channel;
-use tracing::{error, info, instrument, warn};
+use tracing::field::Empty;
+use tracing::{error, info, info_span, instrument, warn, Instrument, Span};
 use uuid::Uuid;
 
 impl RtmpServerEndpointActor {
@@ -129,10 +139,12 @@ impl RtmpServerEndpointActor {
                     stream_key_registration,
                     data,
                     receiver,
+                    media_backlog,
                 } => {
                     self.futures.push(
                         internal_futures::wait_for_watcher_media(
                             receiver,
+                            media_backlog,
                             port,
                             app.clone(),
                             stream_key_registration,
@@ -140,7 +152,13 @@ impl RtmpServerEndpointActor {
                         .boxed(),
                     );
 
-                    self.handle_watcher_media_received(port, app, stream_key, data);
+                    self.handle_watcher_media_received(
+                        port,
+                        app,
+                        stream_key,
+                        stream_key_registration,
+                        data,
+                    );
                 }
 
                 FutureResult::ValidationApprovalResponseReceived(port, connection_id, response) => {
@@ -179,6 +197,9 @@ impl RtmpServerEndpointActor {
             } // Disconnected before this response came in
         };
 
+        let span = connection.span.clone();
+        let _enter = span.enter();
+
         match response {
             ValidationResponse::Approve {
                 reactor_update_channel,
@@ -217,7 +238,7 @@ impl RtmpServerEndpointActor {
                             port,
                             rtmp_app,
                             &stream_key,
-                            Some(reactor_update_channel),
+                            reactor_update_channel,
                         );
 
                         if let Some(future) = future {
@@ -246,7 +267,7 @@ impl RtmpServerEndpointActor {
                             port,
                             rtmp_app,
                             &stream_key,
-                            Some(reactor_update_channel),
+                            reactor_update_channel,
                         );
 
                         if let Some(future) = future {
@@ -305,6 +326,7 @@ impl RtmpServerEndpointActor {
         port: u16,
         app: String,
         stream_key: String,
+        stream_key_registration: StreamKeyRegistration,
         data: RtmpEndpointMediaData,
     ) {
         let port_map = match self.ports.get_mut(&port) {
@@ -321,10 +343,12 @@ impl RtmpServerEndpointActor {
             .active_stream_keys
             .entry(stream_key.clone())
             .or_insert(StreamKeyConnections {
+                stream_key: stream_key.clone(),
                 watchers: HashMap::new(),
                 publisher: None,
                 latest_video_sequence_header: None,
                 latest_audio_sequence_header: None,
+                latest_metadata: None,
             });
 
         match &data {
@@ -356,12 +380,91 @@ impl RtmpServerEndpointActor {
                 }
             }
 
-            _ => (),
+            RtmpEndpointMediaData::NewStreamMetaData { metadata } => {
+                key_details.latest_metadata = Some(metadata.clone());
+            }
         };
 
-        for (_, watcher_details) in &key_details.watchers {
+        for (_, watcher_details) in key_details.watchers.iter_mut() {
+            if watcher_details.awaiting_keyframe {
+                match &data {
+                    RtmpEndpointMediaData::NewVideoData {
+                        is_keyframe: true,
+                        is_sequence_header: false,
+                        ..
+                    } => watcher_details.awaiting_keyframe = false,
+
+                    RtmpEndpointMediaData::NewVideoData {
+                        is_sequence_header: true,
+                        ..
+                    } => (),
+
+                    RtmpEndpointMediaData::NewAudioData {
+                        is_sequence_header: true,
+                        ..
+                    } => (),
+
+                    RtmpEndpointMediaData::NewStreamMetaData { .. } => (),
+
+                    // Hold off on all other media so the watcher's first frame is a keyframe
+                    _ => continue,
+                }
+            }
+
             let _ = watcher_details.media_sender.send(data.clone());
         }
+
+        let byte_count = match &data {
+            RtmpEndpointMediaData::NewVideoData { data, .. } => data.len(),
+            RtmpEndpointMediaData::NewAudioData { data, .. } => data.len(),
+            RtmpEndpointMediaData::NewStreamMetaData { .. } => 0,
+        };
+
+        if let Some(registrant) = app_map
+            .watcher_registrants
+            .get_mut(&stream_key_registration)
+        {
+            registrant.bytes_sent += (byte_count * key_details.watchers.len()) as u64;
+        }
+    }
+
+    fn get_statistics(&self) -> RtmpEndpointStatistics {
+        let mut registrations = Vec::new();
+        for (port, port_map) in &self.ports {
+            for (app, app_map) in &port_map.rtmp_applications {
+                for (stream_key, registrant) in &app_map.publisher_registrants {
+                    let active_connections = get_active_stream_keys(app_map, stream_key)
+                        .filter(|connections| connections.publisher.is_some())
+                        .count();
+
+                    registrations.push(RtmpRegistrationStatistics {
+                        registration_type: RegistrationType::Publisher,
+                        port: *port,
+                        rtmp_app: app.clone(),
+                        stream_key: stream_key.clone(),
+                        active_connections,
+                        bytes_transferred: registrant.bytes_received,
+                    });
+                }
+
+                for (stream_key, registrant) in &app_map.watcher_registrants {
+                    let active_connections = get_active_stream_keys(app_map, stream_key)
+                        .map(|connections| connections.watchers.len())
+                        .sum();
+
+                    registrations.push(RtmpRegistrationStatistics {
+                        registration_type: RegistrationType::Watcher,
+                        port: *port,
+                        rtmp_app: app.clone(),
+                        stream_key: stream_key.clone(),
+                        active_connections,
+                        bytes_transferred: registrant.bytes_sent,
+                    });
+                }
+            }
+        }
+
+        RtmpEndpointStatistics { registrations }
     }
 
     fn handle_endpoint_request(
@@ -373,12 +476,14 @@ impl RtmpServerEndpointActor {
             RtmpEndpointRequest::ListenForPublishers {
                 port,
                 rtmp_app,
+                rtmp_app_matching,
                 rtmp_stream_key,
                 message_channel,
                 stream_id,
                 ip_restrictions: ip_restriction,
                 use_tls,
                 requires_registrant_approval,
+                max_connects_per_minute,
             } => {
                 self.register_listener(
                     port,
@@ -387,8 +492,10 @@ impl RtmpServerEndpointActor {
                     socket_request_sender,
                     ListenerRequest::Publisher {
                         channel: message_channel,
+                        app_matching: rtmp_app_matching,
                         stream_id,
                         requires_registrant_approval,
+                        max_connects_per_minute,
                     },
                     ip_restriction,
                     use_tls,
@@ -400,10 +507,13 @@ impl RtmpServerEndpointActor {
                 rtmp_app,
                 rtmp_stream_key,
                 media_channel,
+                media_backlog,
                 notification_channel,
                 ip_restrictions,
                 use_tls,
                 requires_registrant_approval,
+                max_watchers,
+                start_on_keyframe,
             } => {
                 self.register_listener(
                     port,
@@ -413,7 +523,10 @@ impl RtmpServerEndpointActor {
                     ListenerRequest::Watcher {
                         notification_channel,
                         media_channel,
+                        media_backlog,
                         requires_registrant_approval,
+                        max_watchers,
+                        start_on_keyframe,
                     },
                     ip_restrictions,
                     use_tls,
@@ -444,6 +557,32 @@ impl RtmpServerEndpointActor {
                     }
                 }
             }
+
+            RtmpEndpointRequest::GetStatistics { response_channel } => {
+                let _ = response_channel.send(self.get_statistics());
+            }
+
+            RtmpEndpointRequest::ReloadTlsCertificate {
+                cert_path,
+                key_path,
+                response_channel,
+            } => {
+                info!(
+                    "TLS certificate reload requested with certificate '{}' and key '{}'",
+                    cert_path, key_path
+                );
+
+                // The socket manager owns the TLS sessions, so it handles the reload
+                let _ = socket_request_sender.send(TcpSocketRequest::ReloadTlsCertificate {
+                    cert_path,
+                    key_path,
+                    response_channel,
+                });
+            }
+
+            RtmpEndpointRequest::DisconnectConnection { connection_id } => {
+                self.disconnect_connection(connection_id);
+            }
         }
     }
 
@@ -479,20 +618,25 @@ impl RtmpServerEndpointActor {
                 port, use_tls, port_map.tls
             );
 
-            match listener {
-                ListenerRequest::Publisher { channel, .. } => {
-                    let _ = channel.send(RtmpEndpointPublisherMessage::PublisherRegistrationFailed);
-                }
+            notify_registration_failed(listener);
+            return;
+        }
 
-                ListenerRequest::Watcher {
-                    notification_channel,
-                    ..
-                } => {
-                    let _ = notification_channel
-                        .send(RtmpEndpointWatcherNotification::WatcherRegistrationFailed);
-                }
-            }
+        let app_matching = match &listener {
+            ListenerRequest::Publisher { app_matching, .. } => *app_matching,
+            ListenerRequest::Watcher { .. } => RtmpAppMatching::Exact,
+        };
+
+        if let Some(existing_app) =
+            find_conflicting_app(&port_map.rtmp_applications, &rtmp_app, app_matching)
+        {
+            warn!(
+                "Rtmp server registration failed for port {}, app '{}' ({:?} matching): \
+                Another system is registered for the app '{}' with overlapping app matching",
+                port, rtmp_app, app_matching, existing_app
+            );
 
+            notify_registration_failed(listener);
             return;
         }
 
@@ -513,6 +657,7 @@ impl RtmpServerEndpointActor {
             .rtmp_applications
             .entry(rtmp_app.clone())
             .or_insert(RtmpAppMapping {
+                app_matching,
                 publisher_registrants: HashMap::new(),
                 watcher_registrants: HashMap::new(),
                 active_stream_keys: HashMap::new(),
@@ -523,6 +668,8 @@ impl RtmpServerEndpointActor {
                 channel,
                 stream_id,
                 requires_registrant_approval,
+                max_connects_per_minute,
+                ..
             } => {
                 let can_be_added = match &stream_key {
                     StreamKeyRegistration::Any => {
@@ -536,7 +683,7 @@ impl RtmpServerEndpointActor {
                         }
                     }
 
-                    StreamKeyRegistration::Exact(key) => {
+                    StreamKeyRegistration::Exact(key) | StreamKeyRegistration::Pattern(key) => {
                         if app_map
                             .publisher_registrants
                             .contains_key(&StreamKeyRegistration::Any)
@@ -545,10 +692,7 @@ impl RtmpServerEndpointActor {
                                     Another system is registered for all stream keys on this port/app", port, rtmp_app, key);
 
                             false
-                        } else if app_map
-                            .publisher_registrants
-                            .contains_key(&StreamKeyRegistration::Exact(key.clone()))
-                        {
+                        } else if app_map.publisher_registrants.contains_key(&stream_key) {
                             warn!("Rtmp server publish request registration failed for port {}, app '{}', stream key '{}': \
                                     Another system is registered for this port/app/stream key combo", port, rtmp_app, key);
 
@@ -574,7 +718,10 @@ impl RtmpServerEndpointActor {
                         stream_id,
                         ip_restrictions,
                         requires_registrant_approval,
+                        connection_rate_limiter: max_connects_per_minute
+                            .map(ConnectionRateLimiter::new),
                         cancellation_notifier: cancel_receiver,
+                        bytes_received: 0,
                     },
                 );
 
@@ -599,8 +746,11 @@ impl RtmpServerEndpointActor {
 
             ListenerRequest::Watcher {
                 media_channel,
+                media_backlog,
                 notification_channel,
                 requires_registrant_approval,
+                max_watchers,
+                start_on_keyframe,
             } => {
                 let can_be_added = match &stream_key {
                     StreamKeyRegistration::Any => {
@@ -614,7 +764,7 @@ impl RtmpServerEndpointActor {
                         }
                     }
 
-                    StreamKeyRegistration::Exact(key) => {
+                    StreamKeyRegistration::Exact(key) | StreamKeyRegistration::Pattern(key) => {
                         if app_map
                             .watcher_registrants
                             .contains_key(&StreamKeyRegistration::Any)
@@ -623,10 +773,7 @@ impl RtmpServerEndpointActor {
                                     Another system is registered for all stream keys on this port/app", port, rtmp_app, key);
 
                             false
-                        } else if app_map
-                            .watcher_registrants
-                            .contains_key(&StreamKeyRegistration::Exact(key.clone()))
-                        {
+                        } else if app_map.watcher_registrants.contains_key(&stream_key) {
                             warn!("Rtmp server watcher registration failed for port {}, app '{}', stream key '{}': \
                                     Another system is registered for this port/app/stream key combo", port, rtmp_app, key);
 
@@ -651,7 +798,10 @@ impl RtmpServerEndpointActor {
                         response_channel: notification_channel.clone(),
                         ip_restrictions,
                         requires_registrant_approval,
+                        max_watchers,
+                        start_on_keyframe,
                         cancellation_notifier: cancel_receiver,
+                        bytes_sent: 0,
                     },
                 );
 
@@ -669,6 +819,7 @@ impl RtmpServerEndpointActor {
                 self.futures.push(
                     internal_futures::wait_for_watcher_media(
                         media_channel,
+                        media_backlog,
                         port,
                         rtmp_app,
                         stream_key,
@@ -754,6 +905,17 @@ impl RtmpServerEndpointActor {
                     incoming_bytes,
                     socket_address,
                 } => {
+                    // The app and stream key are recorded once the connection requests to publish
+                    // or watch
+                    let span = info_span!(
+                        parent: None,
+                        "Rtmp Connection",
+                        connection_id = %connection_id,
+                        port = port,
+                        rtmp_app = Empty,
+                        stream_key = Empty,
+                    );
+
                     let (request_sender, request_receiver) = unbounded_channel();
                     let (response_sender, response_receiver) = unbounded_channel();
                     let handler = RtmpServerConnectionHandler::new(
@@ -761,7 +923,11 @@ impl RtmpServerEndpointActor {
                         outgoing_bytes,
                         request_sender,
                     );
-                    tokio::spawn(handler.run_async(response_receiver, incoming_bytes));
+                    tokio::spawn(
+                        handler
+                            .run_async(response_receiver, incoming_bytes)
+                            .instrument(span.clone()),
+                    );
 
                     port_map.connections.insert(
                         connection_id.clone(),
@@ -770,6 +936,7 @@ impl RtmpServerEndpointActor {
                             state: ConnectionState::None,
                             socket_address,
                             received_registrant_approval: false,
+                            span,
                         },
                     );
 
@@ -816,6 +983,15 @@ impl RtmpServerEndpointActor {
             }
         };
 
+        // Process the request under the connection's span, so it's correlated with everything
+        // else that happens for the connection
+        let span = match port_map.connections.get(&connection_id) {
+            Some(connection) => connection.span.clone(),
+            None => Span::none(),
+        };
+
+        let _enter = span.enter();
+
         match request {
             ConnectionRequest::RequestConnectToApp { rtmp_app } => {
                 handle_connection_request_connect_to_app(&connection_id, port_map, port, rtmp_app);
@@ -825,6 +1001,9 @@ impl RtmpServerEndpointActor {
                 rtmp_app,
                 stream_key,
             } => {
+                span.record("rtmp_app", &rtmp_app.as_str());
+                span.record("stream_key", &stream_key.as_str());
+
                 let future = handle_connection_request_publish(
                     &connection_id,
                     port_map,
@@ -843,6 +1022,9 @@ impl RtmpServerEndpointActor {
                 rtmp_app,
                 stream_key,
             } => {
+                span.record("rtmp_app", &rtmp_app.as_str());
+                span.record("stream_key", &stream_key.as_str());
+
                 let future = handle_connection_request_watch(
                     connection_id,
                     port_map,
@@ -857,6 +1039,10 @@ impl RtmpServerEndpointActor {
                 }
             }
 
+            ConnectionRequest::PublishedBytesReceived { byte_count } => {
+                handle_connection_published_bytes(connection_id, port_map, byte_count);
+            }
+
             ConnectionRequest::PublishFinished => {
                 handle_connection_stop_publish(connection_id, port_map);
             }
@@ -883,17 +1069,28 @@ impl RtmpServerEndpointActor {
             None => return,
         };
 
-        if let None = app_map.publisher_registrants.remove(&stream_key) {
+        if !app_map.publisher_registrants.contains_key(&stream_key) {
             return;
         }
 
-        // Remove all publishers tied to this registrant
-        let mut keys_to_remove = Vec::new();
-        if let StreamKeyRegistration::Exact(key) = stream_key {
-            keys_to_remove.push(key);
-        } else {
-            keys_to_remove.extend(app_map.active_stream_keys.keys().map(|x| x.clone()));
-        }
+        // Remove all publishers tied to this registrant.  Other registrations may also match the
+        // same stream keys (e.g. an exact key and a pattern), so only stream keys that resolve to
+        // this registration are tied to it.
+        let keys_to_remove = app_map
+            .active_stream_keys
+            .iter()
+            .filter(|(_, connections)| {
+                find_stream_key_registration(
+                    &app_map.publisher_registrants,
+                    &connections.stream_key,
+                )
+                .as_ref()
+                    == Some(&stream_key)
+            })
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+
+        app_map.publisher_registrants.remove(&stream_key);
 
         for key in keys_to_remove {
             if let Some(connection) = app_map.active_stream_keys.get_mut(&key) {
@@ -914,6 +1111,41 @@ impl RtmpServerEndpointActor {
         }
     }
 
+    fn disconnect_connection(&mut self, connection_id: ConnectionId) {
+        let port_map = self
+            .ports
+            .values_mut()
+            .find(|port_map| port_map.connections.contains_key(&connection_id));
+
+        let port_map = match port_map {
+            Some(x) => x,
+            None => {
+                info!(
+                    connection_id = %connection_id,
+                    "Disconnect requested for connection {}, but no such connection exists",
+                    connection_id
+                );
+
+                return;
+            }
+        };
+
+        info!(
+            connection_id = %connection_id,
+            "Disconnect requested for connection {}", connection_id
+        );
+
+        if let Some(connection) = port_map.connections.get(&connection_id) {
+            let _ = connection
+                .response_channel
+                .send(ConnectionResponse::Disconnect);
+        }
+
+        // Clean up right away instead of waiting for the socket to close, so registrants don't
+        // keep sending media to a connection that's going away
+        clean_disconnected_connection(connection_id, port_map);
+    }
+
     fn remove_watcher_registration(
         &mut self,
         port: u16,
@@ -930,17 +1162,24 @@ impl RtmpServerEndpointActor {
             None => return,
         };
 
-        if let None = app_map.watcher_registrants.remove(&stream_key) {
+        if !app_map.watcher_registrants.contains_key(&stream_key) {
             return;
         }
 
-        // Remove all watchers tied to this registrant
-        let mut keys_to_remove = Vec::new();
-        if let StreamKeyRegistration::Exact(key) = stream_key {
-            keys_to_remove.push(key);
-        } else {
-            keys_to_remove.extend(app_map.active_stream_keys.keys().map(|x| x.clone()));
-        }
+        // Remove all watchers tied to this registrant, leaving watchers of stream keys that
+        // resolve to a different, overlapping registration connected
+        let keys_to_remove = app_map
+            .active_stream_keys
+            .iter()
+            .filter(|(_, connections)| {
+                find_stream_key_registration(&app_map.watcher_registrants, &connections.stream_key)
+                    .as_ref()
+                    == Some(&stream_key)
+            })
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+
+        app_map.watcher_registrants.remove(&stream_key);
 
         for key in keys_to_remove {
             if let Some(connection) = app_map.active_stream_keys.get_mut(&key) {
@@ -981,23 +1220,25 @@ fn handle_connection_stop_watch(connection_id: ConnectionId, port_map: &mut Port
             let rtmp_app = rtmp_app.clone();
             let stream_key = stream_key.clone();
             connection.state = ConnectionState::None;
-            match port_map.rtmp_applications.get_mut(rtmp_app.as_str()) {
+            match get_registered_app_mut(&mut port_map.rtmp_applications, &rtmp_app) {
                 None => (),
-                Some(app_map) => match app_map.active_stream_keys.get_mut(stream_key.as_str()) {
+                Some(app_map) => match app_map.active_stream_keys.get_mut(&get_active_stream_key(
+                    app_map.app_matching,
+                    &rtmp_app,
+                    &stream_key,
+                )) {
                     None => (),
                     Some(active_key) => {
                         active_key.watchers.remove(&connection_id);
 
                         if active_key.watchers.is_empty() {
-                            let registrant = match app_map
-                                .watcher_registrants
-                                .get(&StreamKeyRegistration::Any)
-                            {
-                                Some(x) => Some(x),
-                                None => app_map
-                                    .watcher_registrants
-                                    .get(&StreamKeyRegistration::Exact(stream_key.clone())),
-                            };
+                            let registrant = find_stream_key_registration(
+                                &app_map.watcher_registrants,
+                                &stream_key,
+                            )
+                            .and_then(|registration| {
+                                app_map.watcher_registrants.get(&registration)
+                            });
 
                             if let Some(registrant) = registrant {
                                 let _ = registrant.response_channel.send(
@@ -1016,6 +1257,39 @@ fn handle_connection_stop_watch(connection_id: ConnectionId, port_map: &mut Port
     }
 }
 
+fn handle_connection_published_bytes(
+    connection_id: ConnectionId,
+    port_map: &mut PortMapping,
+    byte_count: usize,
+) {
+    let (rtmp_app, stream_key) = match port_map.connections.get(&connection_id) {
+        Some(Connection {
+            state:
+                ConnectionState::Publishing {
+                    rtmp_app,
+                    stream_key,
+                },
+            ..
+        }) => (rtmp_app, stream_key),
+
+        // Counts can arrive after the connection stopped publishing, and those bytes no longer
+        // belong to a registration
+        _ => return,
+    };
+
+    let app_map = match get_registered_app_mut(&mut port_map.rtmp_applications, rtmp_app) {
+        Some(app_map) => app_map,
+        None => return,
+    };
+
+    let registration = find_stream_key_registration(&app_map.publisher_registrants, stream_key);
+    if let Some(registrant) =
+        registration.and_then(|registration| app_map.publisher_registrants.get_mut(&registration))
+    {
+        registrant.bytes_received += byte_count as u64;
+    }
+}
+
 fn handle_connection_stop_publish(connection_id: ConnectionId, port_map: &mut PortMapping) {
     let connection = match port_map.connections.get_mut(&connection_id) {
         Some(connection) => connection,
@@ -1039,9 +1313,13 @@ fn handle_connection_stop_publish(connection_id: ConnectionId, port_map: &mut Po
             let stream_key = stream_key.clone();
             connection.state = ConnectionState::None;
 
-            match port_map.rtmp_applications.get_mut(rtmp_app.as_str()) {
+            match get_registered_app_mut(&mut port_map.rtmp_applications, &rtmp_app) {
                 None => (),
-                Some(app_map) => match app_map.active_stream_keys.get_mut(stream_key.as_str()) {
+                Some(app_map) => match app_map.active_stream_keys.get_mut(&get_active_stream_key(
+                    app_map.app_matching,
+                    &rtmp_app,
+                    &stream_key,
+                )) {
                     None => (),
                     Some(active_key) => {
                         match &active_key.publisher {
@@ -1051,16 +1329,15 @@ fn handle_connection_stop_publish(connection_id: ConnectionId, port_map: &mut Po
                                     active_key.publisher = None;
                                     active_key.latest_video_sequence_header = None;
                                     active_key.latest_audio_sequence_header = None;
+                                    active_key.latest_metadata = None;
 
-                                    let registrant = match app_map
-                                        .publisher_registrants
-                                        .get(&StreamKeyRegistration::Any)
-                                    {
-                                        Some(x) => Some(x),
-                                        None => app_map
-                                            .publisher_registrants
-                                            .get(&StreamKeyRegistration::Exact(stream_key.clone())),
-                                    };
+                                    let registrant = find_stream_key_registration(
+                                        &app_map.publisher_registrants,
+                                        &stream_key,
+                                    )
+                                    .and_then(|registration| {
+                                        app_map.publisher_registrants.get(&registration)
+                                    });
 
                                     if let Some(registrant) = registrant {
                                         let _ = registrant.response_channel.send(
@@ -1101,7 +1378,7 @@ fn handle_connection_request_watch(
     };
 
     // Has this app been registered yet?
-    let application = match port_map.rtmp_applications.get_mut(rtmp_app.as_str()) {
+    let application = match get_registered_app_mut(&mut port_map.rtmp_applications, &rtmp_app) {
         Some(x) => x,
         None => {
             info!(
@@ -1118,36 +1395,34 @@ fn handle_connection_request_watch(
         }
     };
 
+    let active_stream_key = get_active_stream_key(application.app_matching, &rtmp_app, stream_key);
+
     // Is this stream key registered for watching
-    let registrant = match application
-        .watcher_registrants
-        .get(&StreamKeyRegistration::Any)
-    {
+    let registration = find_stream_key_registration(&application.watcher_registrants, stream_key);
+    let registrant =
+        registration.and_then(|registration| application.watcher_registrants.get(&registration));
+
+    let registrant = match registrant {
         Some(x) => x,
         None => {
-            match application
-                .watcher_registrants
-                .get(&StreamKeyRegistration::Exact(stream_key.clone()))
-            {
-                Some(x) => x,
-                None => {
-                    info!(
-                        "Connection {} requested watching '{}/{}' but that stream key is \
-                                not registered to accept watchers",
-                        connection_id, rtmp_app, stream_key
-                    );
+            info!(
+                "Connection {} requested watching '{}/{}' but that stream key is \
+                        not registered to accept watchers",
+                connection_id, rtmp_app, stream_key
+            );
 
-                    let _ = connection
-                        .response_channel
-                        .send(ConnectionResponse::RequestRejected);
+            let _ = connection
+                .response_channel
+                .send(ConnectionResponse::RequestRejected);
 
-                    return None;
-                }
-            }
+            return None;
         }
     };
 
-    if !is_ip_allowed(&connection.socket_address, &registrant.ip_restrictions) {
+    if !registrant
+        .ip_restrictions
+        .is_allowed(&connection.socket_address)
+    {
         error!(
             "Connection {} requested watching to '{}/{}', but the client's ip address of '{}' \
         is not allowed",
@@ -1164,6 +1439,30 @@ fn handle_connection_request_watch(
         return None;
     }
 
+    // Checked both before and after registrant approval, since other watchers may have connected
+    // while waiting for the approval response
+    if let Some(max_watchers) = registrant.max_watchers {
+        let watcher_count = application
+            .active_stream_keys
+            .get(&active_stream_key)
+            .map(|connections| connections.watchers.len())
+            .unwrap_or(0);
+
+        if watcher_count >= max_watchers {
+            warn!(
+                "Connection {} requested watching '{}/{}', but the maximum of {} watchers are \
+                already connected",
+                connection_id, rtmp_app, stream_key, max_watchers
+            );
+
+            let _ = connection
+                .response_channel
+                .send(ConnectionResponse::RequestRejected);
+
+            return None;
+        }
+    }
+
     if registrant.requires_registrant_approval && !connection.received_registrant_approval {
         info!(
             "Connection {} requested watching to '{}/{}' but requires approval from the \
@@ -1192,12 +1491,14 @@ fn handle_connection_request_watch(
 
     let active_stream_key = application
         .active_stream_keys
-        .entry(stream_key.clone())
+        .entry(active_stream_key)
         .or_insert(StreamKeyConnections {
+            stream_key: stream_key.clone(),
             watchers: HashMap::new(),
             publisher: None,
             latest_video_sequence_header: None,
             latest_audio_sequence_header: None,
+            latest_metadata: None,
         });
 
     connection.state = ConnectionState::Watching {
@@ -1216,6 +1517,14 @@ fn handle_connection_request_watch(
 
     let (media_sender, media_receiver) = unbounded_channel();
 
+    // Metadata is only sent by publishers when they start or change it, so send the latest
+    // metadata we've seen to make sure watchers that join mid-stream still receive it
+    if let Some(metadata) = &active_stream_key.latest_metadata {
+        let _ = media_sender.send(RtmpEndpointMediaData::NewStreamMetaData {
+            metadata: metadata.clone(),
+        });
+    }
+
     // If we have a sequence headers available, send it to the client so they can immediately
     // start decoding video
     if let Some(sequence_header) = &active_stream_key.latest_video_sequence_header {
@@ -1238,9 +1547,19 @@ fn handle_connection_request_watch(
         });
     }
 
-    active_stream_key
-        .watchers
-        .insert(connection_id, WatcherDetails { media_sender });
+    active_stream_key.watchers.insert(
+        connection_id,
+        WatcherDetails {
+            media_sender,
+            awaiting_keyframe: registrant.start_on_keyframe,
+        },
+    );
+
+    let _ = registrant
+        .response_channel
+        .send(RtmpEndpointWatcherNotification::WatcherJoined {
+            stream_key: stream_key.clone(),
+        });
 
     let _ = connection
         .response_channel
@@ -1271,7 +1590,7 @@ fn handle_connection_request_publish(
     };
 
     // Has this RTMP application been registered yet?
-    let application = match port_map.rtmp_applications.get_mut(rtmp_app.as_str()) {
+    let application = match get_registered_app_mut(&mut port_map.rtmp_applications, &rtmp_app) {
         Some(x) => x,
         None => {
             info!("Connection {} requested publishing to '{}/{}', but the RTMP app '{}' isn't registered yet",
@@ -1285,44 +1604,41 @@ fn handle_connection_request_publish(
         }
     };
 
+    let active_stream_key = get_active_stream_key(application.app_matching, &rtmp_app, stream_key);
+
     // Has this stream key been registered yet?
-    let registrant = match application
-        .publisher_registrants
-        .get(&StreamKeyRegistration::Any)
-    {
+    let registration = find_stream_key_registration(&application.publisher_registrants, stream_key);
+    let registrant = registration
+        .and_then(|registration| application.publisher_registrants.get_mut(&registration));
+
+    let registrant = match registrant {
         Some(x) => x,
         None => {
-            match application
-                .publisher_registrants
-                .get(&StreamKeyRegistration::Exact(stream_key.clone()))
-            {
-                Some(x) => x,
-                None => {
-                    error!(
-                        "Connection {} requested publishing to '{}/{}', but no one has registered \
-                            to support publishers on that stream key",
-                        connection_id, rtmp_app, stream_key
-                    );
+            error!(
+                "Connection {} requested publishing to '{}/{}', but no one has registered \
+                    to support publishers on that stream key",
+                connection_id, rtmp_app, stream_key
+            );
 
-                    let _ = connection
-                        .response_channel
-                        .send(ConnectionResponse::RequestRejected);
+            let _ = connection
+                .response_channel
+                .send(ConnectionResponse::RequestRejected);
 
-                    return None;
-                }
-            }
+            return None;
         }
     };
 
     // app/stream key combination is valid and we have a registrant for it
     let stream_key_connections = application
         .active_stream_keys
-        .entry(stream_key.clone())
+        .entry(active_stream_key)
         .or_insert(StreamKeyConnections {
+            stream_key: stream_key.clone(),
             publisher: None,
             watchers: HashMap::new(),
             latest_video_sequence_header: None,
             latest_audio_sequence_header: None,
+            latest_metadata: None,
         });
 
     // Is someone already publishing on this stream key?
@@ -1340,7 +1656,10 @@ fn handle_connection_request_publish(
         return None;
     }
 
-    if !is_ip_allowed(&connection.socket_address, &registrant.ip_restrictions) {
+    if !registrant
+        .ip_restrictions
+        .is_allowed(&connection.socket_address)
+    {
         error!(
             "Connection {} requested publishing to '{}/{}', but the client's ip address of '{}' \
         is not allowed",
@@ -1357,6 +1676,29 @@ fn handle_connection_request_publish(
         return None;
     }
 
+    // Connections that were sent for approval already counted against the rate limit when
+    // they first requested to publish
+    if let Some(limiter) = &mut registrant.connection_rate_limiter {
+        if !connection.received_registrant_approval
+            && !limiter.try_acquire(connection.socket_address.ip(), Instant::now())
+        {
+            warn!(
+                "Connection {} requested publishing to '{}/{}', but the client's ip address of \
+            '{}' has exceeded the allowed number of new connections per minute",
+                connection_id,
+                rtmp_app,
+                stream_key,
+                connection.socket_address.ip()
+            );
+
+            let _ = connection
+                .response_channel
+                .send(ConnectionResponse::RequestRejected);
+
+            return None;
+        }
+    }
+
     if registrant.requires_registrant_approval && !connection.received_registrant_approval {
         info!(
             "Connection {} requested publishing to '{}/{}' but requires approval from the \
@@ -1365,13 +1707,14 @@ fn handle_connection_request_publish(
         );
 
         connection.state = ConnectionState::WaitingForPublishValidation {
-            rtmp_app,
+            rtmp_app: rtmp_app.clone(),
             stream_key: stream_key.clone(),
         };
 
         let (sender, receiver) = channel();
         let _ = registrant.response_channel.send(
             RtmpEndpointPublisherMessage::PublisherRequiringApproval {
+                rtmp_app,
                 stream_key: stream_key.clone(),
                 connection_id: connection_id.clone(),
                 response_channel: sender,
@@ -1406,8 +1749,10 @@ fn handle_connection_request_publish(
         .response_channel
         .send(RtmpEndpointPublisherMessage::NewPublisherConnected {
             connection_id: connection_id.clone(),
+            rtmp_app,
             stream_key: stream_key.clone(),
             stream_id,
+            remote_address: connection.socket_address,
             reactor_update_channel: reactor_response_channel,
         });
 
@@ -1430,7 +1775,7 @@ fn handle_connection_request_connect_to_app(
             return;
         }
     };
-    let response = if !port_map.rtmp_applications.contains_key(rtmp_app.as_str()) {
+    let response = if find_registered_app(&port_map.rtmp_applications, &rtmp_app).is_none() {
         info!(
             "Connection {} requested connection to RTMP app '{}' which isn't registered yet",
             connection_id, rtmp_app
@@ -1456,6 +1801,9 @@ fn clean_disconnected_connection(connection_id: ConnectionId, port_map: &mut Por
         None => return,
     };
 
+    let span = connection.span.clone();
+    let _enter = span.enter();
+
     info!("Connection {} disconnected.  Cleaning it up", connection_id);
     match connection.state {
         ConnectionState::None => (),
@@ -1464,9 +1812,13 @@ fn clean_disconnected_connection(connection_id: ConnectionId, port_map: &mut Por
         ConnectionState::Publishing {
             rtmp_app,
             stream_key,
-        } => match port_map.rtmp_applications.get_mut(rtmp_app.as_str()) {
+        } => match get_registered_app_mut(&mut port_map.rtmp_applications, &rtmp_app) {
             None => (),
-            Some(app_map) => match app_map.active_stream_keys.get_mut(stream_key.as_str()) {
+            Some(app_map) => match app_map.active_stream_keys.get_mut(&get_active_stream_key(
+                app_map.app_matching,
+                &rtmp_app,
+                &stream_key,
+            )) {
                 None => (),
                 Some(active_key) => {
                     match &active_key.publisher {
@@ -1476,16 +1828,15 @@ fn clean_disconnected_connection(connection_id: ConnectionId, port_map: &mut Por
                                 active_key.publisher = None;
                                 active_key.latest_video_sequence_header = None;
                                 active_key.latest_audio_sequence_header = None;
+                                active_key.latest_metadata = None;
 
-                                let registrant = match app_map
-                                    .publisher_registrants
-                                    .get(&StreamKeyRegistration::Any)
-                                {
-                                    Some(x) => Some(x),
-                                    None => app_map
-                                        .publisher_registrants
-                                        .get(&StreamKeyRegistration::Exact(stream_key.clone())),
-                                };
+                                let registrant = find_stream_key_registration(
+                                    &app_map.publisher_registrants,
+                                    &stream_key,
+                                )
+                                .and_then(|registration| {
+                                    app_map.publisher_registrants.get(&registration)
+                                });
 
                                 if let Some(registrant) = registrant {
                                     let _ = registrant.response_channel.send(
@@ -1504,21 +1855,23 @@ fn clean_disconnected_connection(connection_id: ConnectionId, port_map: &mut Por
         ConnectionState::Watching {
             rtmp_app,
             stream_key,
-        } => match port_map.rtmp_applications.get_mut(rtmp_app.as_str()) {
+        } => match get_registered_app_mut(&mut port_map.rtmp_applications, &rtmp_app) {
             None => (),
-            Some(app_map) => match app_map.active_stream_keys.get_mut(stream_key.as_str()) {
+            Some(app_map) => match app_map.active_stream_keys.get_mut(&get_active_stream_key(
+                app_map.app_matching,
+                &rtmp_app,
+                &stream_key,
+            )) {
                 None => (),
                 Some(active_key) => {
                     active_key.watchers.remove(&connection_id);
 
                     if active_key.watchers.is_empty() {
                         let registrant =
-                            match app_map.watcher_registrants.get(&StreamKeyRegistration::Any) {
-                                Some(x) => Some(x),
-                                None => app_map
-                                    .watcher_registrants
-                                    .get(&StreamKeyRegistration::Exact(stream_key.clone())),
-                            };
+                            find_stream_key_registration(&app_map.watcher_registrants, &stream_key)
+                                .and_then(|registration| {
+                                    app_map.watcher_registrants.get(&registration)
+                                });
 
                         if let Some(registrant) = registrant {
                             let _ = registrant.response_channel.send(
@@ -1544,6 +1897,8 @@ mod internal_futures {
     };
     use crate::net::tcp::{TcpSocketRequest, TcpSocketResponse};
     use crate::net::ConnectionId;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
     use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
     use tokio::sync::oneshot::Receiver;
 
@@ -1633,6 +1988,7 @@ mod internal_futures {
 
     pub(super) async fn wait_for_watcher_media(
         mut receiver: UnboundedReceiver<RtmpEndpointMediaMessage>,
+        media_backlog: Option<Arc<AtomicUsize>>,
         port: u16,
         app_name: String,
         stream_key_registration: StreamKeyRegistration,
@@ -1643,14 +1999,21 @@ mod internal_futures {
                 app: app_name,
                 stream_key: stream_key_registration,
             },
-            Some(message) => FutureResult::WatcherMediaDataReceived {
-                port,
-                app: app_name,
-                stream_key: message.stream_key,
-                stream_key_registration,
-                data: message.data,
-                receiver,
-            },
+            Some(message) => {
+                if let Some(backlog) = &media_backlog {
+                    backlog.fetch_sub(1, Ordering::Relaxed);
+                }
+
+                FutureResult::WatcherMediaDataReceived {
+                    port,
+                    app: app_name,
+                    stream_key: message.stream_key,
+                    stream_key_registration,
+                    data: message.data,
+                    receiver,
+                    media_backlog,
+                }
+            }
         }
     }
 
@@ -1680,23 +2043,133 @@ mod internal_futures {
     }
 }
 
-fn is_ip_allowed(client_socket: &SocketAddr, ip_restrictions: &IpRestriction) -> bool {
-    match ip_restrictions {
-        IpRestriction::None => return true,
-        IpRestriction::Allow(allowed_ips) => {
-            if let SocketAddr::V4(client_ip) = client_socket {
-                return allowed_ips.into_iter().any(|ip| ip.matches(client_ip.ip()));
-            }
+/// Sends a registration failure notification to the registrant of the specified listener request
+fn notify_registration_failed(listener: ListenerRequest) {
+    match listener {
+        ListenerRequest::Publisher { channel, .. } => {
+            let _ = channel.send(RtmpEndpointPublisherMessage::PublisherRegistrationFailed);
+        }
 
-            return false; // ipv6 clients not supported atm
+        ListenerRequest::Watcher {
+            notification_channel,
+            ..
+        } => {
+            let _ = notification_channel
+                .send(RtmpEndpointWatcherNotification::WatcherRegistrationFailed);
         }
+    }
+}
 
-        IpRestriction::Deny(denied_ips) => {
-            if let SocketAddr::V4(client_ip) = client_socket {
-                return denied_ips.into_iter().all(|ip| !ip.matches(client_ip.ip()));
+/// Finds the name of an already registered RTMP application that a new registration for the
+/// specified app can't coexist with.  Registrations for the same app must use the same matching,
+/// and clients must not be able to match more than one registration with the same priority.
+fn find_conflicting_app<'a>(
+    applications: &'a HashMap<String, RtmpAppMapping>,
+    rtmp_app: &str,
+    app_matching: RtmpAppMatching,
+) -> Option<&'a String> {
+    applications
+        .iter()
+        .find(|(name, app_map)| {
+            if name.as_str() == rtmp_app {
+                return app_map.app_matching != app_matching;
             }
 
-            return false; // ipv6
-        }
+            match (app_matching, app_map.app_matching) {
+                (RtmpAppMatching::Any, RtmpAppMatching::Any) => true,
+                (RtmpAppMatching::CaseInsensitive, RtmpAppMatching::CaseInsensitive) => {
+                    name.eq_ignore_ascii_case(rtmp_app)
+                }
+
+                _ => false,
+            }
+        })
+        .map(|(name, _)| name)
+}
+
+/// Finds the name of the registered RTMP application that clients connecting to the specified
+/// app should be handled by.  Exact matches take priority over case-insensitive matches, which
+/// take priority over registrations for any app.
+fn find_registered_app(
+    applications: &HashMap<String, RtmpAppMapping>,
+    rtmp_app: &str,
+) -> Option<String> {
+    if applications.contains_key(rtmp_app) {
+        return Some(rtmp_app.to_string());
+    }
+
+    let find_with_matching = |matching: RtmpAppMatching| {
+        applications
+            .iter()
+            .filter(|(_, app_map)| app_map.app_matching == matching)
+            .find(|(name, _)| {
+                matching == RtmpAppMatching::Any || name.eq_ignore_ascii_case(rtmp_app)
+            })
+            .map(|(name, _)| name.clone())
     };
+
+    find_with_matching(RtmpAppMatching::CaseInsensitive)
+        .or_else(|| find_with_matching(RtmpAppMatching::Any))
+}
+
+fn get_registered_app_mut<'a>(
+    applications: &'a mut HashMap<String, RtmpAppMapping>,
+    rtmp_app: &str,
+) -> Option<&'a mut RtmpAppMapping> {
+    let name = find_registered_app(applications, rtmp_app)?;
+    applications.get_mut(&name)
+}
+
+/// Gets the key that streams on the specified app and stream key are tracked under.  Clients of
+/// a registration for any app may use the same stream key on different apps, so the app is made
+/// part of the key to keep those streams separate.
+fn get_active_stream_key(
+    app_matching: RtmpAppMatching,
+    rtmp_app: &str,
+    stream_key: &str,
+) -> String {
+    match app_matching {
+        RtmpAppMatching::Any => format!("{}/{}", rtmp_app, stream_key),
+        RtmpAppMatching::Exact | RtmpAppMatching::CaseInsensitive => stream_key.to_string(),
+    }
+}
+
+/// Finds the registration that the specified stream key falls under.  A registration for all
+/// stream keys takes priority, followed by a registration for the exact stream key, and then the
+/// longest (most specific) pattern that matches the stream key.
+fn find_stream_key_registration<T>(
+    registrants: &HashMap<StreamKeyRegistration, T>,
+    stream_key: &str,
+) -> Option<StreamKeyRegistration> {
+    if registrants.contains_key(&StreamKeyRegistration::Any) {
+        return Some(StreamKeyRegistration::Any);
+    }
+
+    let exact = StreamKeyRegistration::Exact(stream_key.to_string());
+    if registrants.contains_key(&exact) {
+        return Some(exact);
+    }
+
+    registrants
+        .keys()
+        .filter_map(|registration| match registration {
+            StreamKeyRegistration::Pattern(pattern) if registration.matches(stream_key) => {
+                Some((pattern.len(), pattern))
+            }
+
+            _ => None,
+        })
+        .max()
+        .map(|(_, pattern)| StreamKeyRegistration::Pattern(pattern.clone()))
+}
+
+/// Gets the connections for all active stream keys that fall under the specified registration
+fn get_active_stream_keys<'a>(
+    app_map: &'a RtmpAppMapping,
+    registration: &'a StreamKeyRegistration,
+) -> impl Iterator<Item = &'a StreamKeyConnections> + 'a {
+    app_map
+        .active_stream_keys
+        .values()
+        .filter(move |connections| registration.matches(&connections.stream_key))
 }
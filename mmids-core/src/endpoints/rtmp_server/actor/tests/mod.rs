@@ -3,15 +3,18 @@ use crate::codecs::{AudioCodec, VideoCodec};
 use crate::endpoints::rtmp_server::actor::tests::rtmp_client::RtmpTestClient;
 use crate::endpoints::rtmp_server::actor::tests::test_context::TestContextBuilder;
 use crate::endpoints::rtmp_server::{
-    start_rtmp_server_endpoint, IpRestriction, RtmpEndpointMediaData, RtmpEndpointMediaMessage,
-    RtmpEndpointPublisherMessage, RtmpEndpointRequest, RtmpEndpointWatcherNotification,
+    start_rtmp_server_endpoint, IpRestriction, RegistrationType, RtmpAppMatching,
+    RtmpEndpointMediaData, RtmpEndpointMediaMessage, RtmpEndpointPublisherMessage,
+    RtmpEndpointRequest, RtmpEndpointStatistics, RtmpEndpointWatcherNotification,
     StreamKeyRegistration, ValidationResponse,
 };
+use crate::net::ConnectionId;
 use crate::test_utils;
 use bytes::Bytes;
 use rml_rtmp::sessions::{ClientSessionEvent, StreamMetadata};
 use rml_rtmp::time::RtmpTimestamp;
-use tokio::sync::mpsc::unbounded_channel;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::oneshot::channel;
 
 mod rtmp_client;
 mod test_context;
@@ -27,9 +30,11 @@ async fn can_register_for_specific_port_for_publishers() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Any,
             message_channel: sender,
         })
@@ -55,9 +60,11 @@ async fn can_register_with_tls_enabled() {
             port: 9999,
             use_tls: true,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Any,
             message_channel: sender,
         })
@@ -83,9 +90,11 @@ async fn endpoint_publisher_receives_failed_when_port_rejected() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Any,
             message_channel: sender,
         })
@@ -111,9 +120,11 @@ async fn multiple_requests_for_same_port_only_sends_one_request_to_socket_manage
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Any,
             message_channel: sender,
         })
@@ -133,9 +144,11 @@ async fn multiple_requests_for_same_port_only_sends_one_request_to_socket_manage
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app2".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Any,
             message_channel: sender2,
         })
@@ -161,9 +174,11 @@ async fn second_publisher_rejected_on_same_app_when_both_any_stream_key() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Any,
             message_channel: sender,
         })
@@ -183,9 +198,11 @@ async fn second_publisher_rejected_on_same_app_when_both_any_stream_key() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Any,
             message_channel: sender2,
         })
@@ -209,9 +226,11 @@ async fn second_publisher_rejected_on_same_app_and_same_exact_key() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Exact("abc".to_string()),
             message_channel: sender,
         })
@@ -231,9 +250,11 @@ async fn second_publisher_rejected_on_same_app_and_same_exact_key() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Exact("abc".to_string()),
             message_channel: sender2,
         })
@@ -257,9 +278,11 @@ async fn second_publisher_rejected_on_same_app_when_first_request_is_for_any_key
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Any,
             message_channel: sender,
         })
@@ -279,9 +302,11 @@ async fn second_publisher_rejected_on_same_app_when_first_request_is_for_any_key
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Exact("abc".to_string()),
             message_channel: sender2,
         })
@@ -305,9 +330,11 @@ async fn second_publisher_rejected_on_same_app_when_first_request_is_for_specifi
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Exact("abc".to_string()),
             message_channel: sender,
         })
@@ -327,9 +354,11 @@ async fn second_publisher_rejected_on_same_app_when_first_request_is_for_specifi
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Any,
             message_channel: sender2,
         })
@@ -353,9 +382,11 @@ async fn second_publisher_accepted_on_same_app_on_different_exact_keys() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Exact("abc".to_string()),
             message_channel: sender,
         })
@@ -375,9 +406,11 @@ async fn second_publisher_accepted_on_same_app_on_different_exact_keys() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Exact("def".to_string()),
             message_channel: sender2,
         })
@@ -402,10 +435,13 @@ async fn can_register_for_specific_port_for_watcher() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_watchers: None,
+            start_on_keyframe: false,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
             rtmp_stream_key: StreamKeyRegistration::Any,
             media_channel: media_receiver,
+            media_backlog: None,
             notification_channel: sender,
         })
         .expect("Endpoint request failed to send");
@@ -431,10 +467,13 @@ async fn endpoint_watcher_receives_failed_when_port_rejected() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_watchers: None,
+            start_on_keyframe: false,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
             rtmp_stream_key: StreamKeyRegistration::Any,
             media_channel: media_receiver,
+            media_backlog: None,
             notification_channel: sender,
         })
         .expect("Endpoint request failed to send");
@@ -460,10 +499,13 @@ async fn second_watcher_rejected_on_same_app_when_both_any_stream_key() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_watchers: None,
+            start_on_keyframe: false,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
             rtmp_stream_key: StreamKeyRegistration::Any,
             media_channel: media_receiver,
+            media_backlog: None,
             notification_channel: sender,
         })
         .expect("Endpoint request failed to send");
@@ -483,10 +525,13 @@ async fn second_watcher_rejected_on_same_app_when_both_any_stream_key() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_watchers: None,
+            start_on_keyframe: false,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
             rtmp_stream_key: StreamKeyRegistration::Any,
             media_channel: media_receiver,
+            media_backlog: None,
             notification_channel: sender,
         })
         .expect("Endpoint request failed to send");
@@ -510,10 +555,13 @@ async fn second_watcher_rejected_on_same_app_and_same_exact_key() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_watchers: None,
+            start_on_keyframe: false,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
             rtmp_stream_key: StreamKeyRegistration::Exact("abc".to_string()),
             media_channel: media_receiver,
+            media_backlog: None,
             notification_channel: sender,
         })
         .expect("Endpoint request failed to send");
@@ -533,10 +581,13 @@ async fn second_watcher_rejected_on_same_app_and_same_exact_key() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_watchers: None,
+            start_on_keyframe: false,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
             rtmp_stream_key: StreamKeyRegistration::Exact("abc".to_string()),
             media_channel: media_receiver,
+            media_backlog: None,
             notification_channel: sender,
         })
         .expect("Endpoint request failed to send");
@@ -561,10 +612,13 @@ async fn second_watcher_rejected_on_same_app_when_first_request_is_for_any_key()
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_watchers: None,
+            start_on_keyframe: false,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
             rtmp_stream_key: StreamKeyRegistration::Any,
             media_channel: media_receiver,
+            media_backlog: None,
             notification_channel: sender,
         })
         .expect("Endpoint request failed to send");
@@ -584,10 +638,13 @@ async fn second_watcher_rejected_on_same_app_when_first_request_is_for_any_key()
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_watchers: None,
+            start_on_keyframe: false,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
             rtmp_stream_key: StreamKeyRegistration::Exact("abc".to_string()),
             media_channel: media_receiver,
+            media_backlog: None,
             notification_channel: sender,
         })
         .expect("Endpoint request failed to send");
@@ -611,10 +668,13 @@ async fn second_watcher_rejected_on_same_app_when_first_request_is_for_specific_
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_watchers: None,
+            start_on_keyframe: false,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
             rtmp_stream_key: StreamKeyRegistration::Exact("abc".to_string()),
             media_channel: media_receiver,
+            media_backlog: None,
             notification_channel: sender,
         })
         .expect("Endpoint request failed to send");
@@ -635,10 +695,13 @@ async fn second_watcher_rejected_on_same_app_when_first_request_is_for_specific_
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_watchers: None,
+            start_on_keyframe: false,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
             rtmp_stream_key: StreamKeyRegistration::Any,
             media_channel: media_receiver,
+            media_backlog: None,
             notification_channel: sender,
         })
         .expect("Endpoint request failed to send");
@@ -663,10 +726,13 @@ async fn second_watcher_accepted_on_same_app_with_different_exact_keys() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_watchers: None,
+            start_on_keyframe: false,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
             rtmp_stream_key: StreamKeyRegistration::Exact("abc".to_string()),
             media_channel: media_receiver,
+            media_backlog: None,
             notification_channel: sender,
         })
         .expect("Endpoint request failed to send");
@@ -687,10 +753,13 @@ async fn second_watcher_accepted_on_same_app_with_different_exact_keys() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_watchers: None,
+            start_on_keyframe: false,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
             rtmp_stream_key: StreamKeyRegistration::Exact("def".to_string()),
             media_channel: media_receiver,
+            media_backlog: None,
             notification_channel: sender,
         })
         .expect("Endpoint request failed to send");
@@ -714,9 +783,11 @@ async fn second_request_fails_if_tls_option_differs() {
             port: 9999,
             use_tls: false,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Any,
             message_channel: sender,
         })
@@ -736,9 +807,11 @@ async fn second_request_fails_if_tls_option_differs() {
             port: 9999,
             use_tls: true,
             requires_registrant_approval: false,
+            max_connects_per_minute: None,
             stream_id: None,
             ip_restrictions: IpRestriction::None,
             rtmp_app: "app2".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
             rtmp_stream_key: StreamKeyRegistration::Any,
             message_channel: sender2,
         })
@@ -806,11 +879,19 @@ async fn publisher_can_connect_on_registered_app_and_stream_key() {
     let response = test_utils::expect_mpsc_response(receiver).await;
     match response {
         RtmpEndpointPublisherMessage::NewPublisherConnected {
+            rtmp_app,
             stream_key,
             connection_id,
             stream_id: _,
+            remote_address,
             reactor_update_channel: _,
         } => {
+            assert_eq!(&rtmp_app, "app", "Unexpected rtmp app");
+            assert_eq!(
+                remote_address,
+                "127.0.0.1:1234".parse().unwrap(),
+                "Unexpected remote address"
+            );
             assert_eq!(
                 stream_key,
                 "key".to_string(),
@@ -828,6 +909,197 @@ async fn publisher_can_connect_on_registered_app_and_stream_key() {
     };
 }
 
+#[tokio::test]
+async fn publisher_disconnected_if_app_case_differs_with_exact_app_matching() {
+    let mut context = TestContextBuilder::new().into_publisher().await;
+    context.client.perform_handshake().await;
+    context
+        .client
+        .connect_to_app(context.rtmp_app.to_uppercase(), false)
+        .await;
+
+    context.client.assert_connection_sender_closed().await;
+}
+
+#[tokio::test]
+async fn publisher_can_connect_with_different_app_case_with_case_insensitive_app_matching() {
+    let mut context = TestContextBuilder::new()
+        .set_rtmp_app_matching(RtmpAppMatching::CaseInsensitive)
+        .into_publisher()
+        .await;
+
+    context.client.perform_handshake().await;
+    context
+        .client
+        .connect_to_app(context.rtmp_app.to_uppercase(), true)
+        .await;
+
+    context
+        .client
+        .publish_to_stream_key("key".to_string(), true)
+        .await;
+
+    let receiver = context.publish_receiver.as_mut().unwrap();
+    let response = test_utils::expect_mpsc_response(receiver).await;
+    match response {
+        RtmpEndpointPublisherMessage::NewPublisherConnected { rtmp_app, .. } => {
+            assert_eq!(&rtmp_app, "APP", "Unexpected rtmp app");
+        }
+
+        message => panic!("Unexpected publisher message: {:?}", message),
+    };
+}
+
+#[tokio::test]
+async fn publisher_can_connect_to_any_app_with_any_app_matching() {
+    let mut context = TestContextBuilder::new()
+        .set_rtmp_app_matching(RtmpAppMatching::Any)
+        .into_publisher()
+        .await;
+
+    context.client.perform_handshake().await;
+    context
+        .client
+        .connect_to_app("tenant1".to_string(), true)
+        .await;
+
+    context
+        .client
+        .publish_to_stream_key("key".to_string(), true)
+        .await;
+
+    let receiver = context.publish_receiver.as_mut().unwrap();
+    let response = test_utils::expect_mpsc_response(receiver).await;
+    match response {
+        RtmpEndpointPublisherMessage::NewPublisherConnected {
+            rtmp_app,
+            stream_key,
+            ..
+        } => {
+            assert_eq!(&rtmp_app, "tenant1", "Unexpected rtmp app");
+            assert_eq!(&stream_key, "key", "Unexpected stream key");
+        }
+
+        message => panic!("Unexpected publisher message: {:?}", message),
+    };
+}
+
+#[tokio::test]
+async fn second_any_app_registration_rejected_on_same_port() {
+    let context = TestContextBuilder::new()
+        .set_rtmp_app_matching(RtmpAppMatching::Any)
+        .into_publisher()
+        .await;
+
+    let (sender, mut receiver) = unbounded_channel();
+    context
+        .endpoint
+        .send(RtmpEndpointRequest::ListenForPublishers {
+            port: 9999,
+            use_tls: false,
+            requires_registrant_approval: false,
+            max_connects_per_minute: None,
+            stream_id: None,
+            ip_restrictions: IpRestriction::None,
+            rtmp_app: "other".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Any,
+            rtmp_stream_key: StreamKeyRegistration::Any,
+            message_channel: sender,
+        })
+        .expect("2nd endpoint request failed to send");
+
+    let response = test_utils::expect_mpsc_response(&mut receiver).await;
+    match response {
+        RtmpEndpointPublisherMessage::PublisherRegistrationFailed => (),
+        x => panic!("Unexpected endpoint response: {:?}", x),
+    }
+}
+
+#[tokio::test]
+async fn exact_app_registration_takes_priority_over_any_app_registration() {
+    let mut context = TestContextBuilder::new()
+        .set_rtmp_app_matching(RtmpAppMatching::Any)
+        .into_publisher()
+        .await;
+
+    let (sender, mut receiver) = unbounded_channel();
+    context
+        .endpoint
+        .send(RtmpEndpointRequest::ListenForPublishers {
+            port: 9999,
+            use_tls: false,
+            requires_registrant_approval: false,
+            max_connects_per_minute: None,
+            stream_id: None,
+            ip_restrictions: IpRestriction::None,
+            rtmp_app: "exact".to_string(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
+            rtmp_stream_key: StreamKeyRegistration::Any,
+            message_channel: sender,
+        })
+        .expect("2nd endpoint request failed to send");
+
+    let response = test_utils::expect_mpsc_response(&mut receiver).await;
+    match response {
+        RtmpEndpointPublisherMessage::PublisherRegistrationSuccessful => (),
+        x => panic!("Unexpected endpoint response: {:?}", x),
+    }
+
+    context.client.perform_handshake().await;
+    context
+        .client
+        .connect_to_app("exact".to_string(), true)
+        .await;
+
+    context
+        .client
+        .publish_to_stream_key("key".to_string(), true)
+        .await;
+
+    let response = test_utils::expect_mpsc_response(&mut receiver).await;
+    match response {
+        RtmpEndpointPublisherMessage::NewPublisherConnected { rtmp_app, .. } => {
+            assert_eq!(&rtmp_app, "exact", "Unexpected rtmp app");
+        }
+
+        message => panic!("Unexpected publisher message: {:?}", message),
+    };
+
+    let any_receiver = context.publish_receiver.as_mut().unwrap();
+    test_utils::expect_mpsc_timeout(any_receiver).await;
+}
+
+#[tokio::test]
+async fn publisher_disconnected_if_ip_exceeds_max_connects_per_minute() {
+    let mut context = TestContextBuilder::new()
+        .set_max_connects_per_minute(1)
+        .into_publisher()
+        .await;
+
+    context.set_as_active_publisher().await;
+    context.client.disconnect();
+
+    let receiver = context.publish_receiver.as_mut().unwrap();
+    let response = test_utils::expect_mpsc_response(receiver).await;
+    match response {
+        RtmpEndpointPublisherMessage::PublishingStopped { .. } => (),
+        message => panic!("Unexpected publisher message: {:?}", message),
+    };
+
+    context.client.perform_handshake().await;
+    context
+        .client
+        .connect_to_app(context.rtmp_app.clone(), true)
+        .await;
+
+    context
+        .client
+        .publish_to_stream_key("key".to_string(), false)
+        .await;
+
+    context.client.assert_connection_sender_closed().await;
+}
+
 #[tokio::test]
 async fn publish_stopped_notification_raised_on_disconnection() {
     let mut context = TestContextBuilder::new().into_publisher().await;
@@ -1222,7 +1494,8 @@ async fn published_audio_aac_sequence_header_if_second_byte_is_zero() {
     let mut context = TestContextBuilder::new().into_publisher().await;
     context.set_as_active_publisher().await;
 
-    let data = Bytes::from(vec![0xa0, 0, 3, 4]);
+    // AAC LC, 44.1khz, stereo
+    let data = Bytes::from(vec![0xa0, 0, 0x12, 0x10]);
     let timestamp = RtmpTimestamp::new(5);
     context
         .client
@@ -1272,13 +1545,212 @@ async fn published_audio_aac_not_sequence_header_if_second_byte_is_not_zero() {
             );
         }
 
-        message => panic!("Unexpected publisher message: {:?}", message),
-    };
-}
+        message => panic!("Unexpected publisher message: {:?}", message),
+    };
+}
+
+#[tokio::test]
+async fn published_audio_aac_not_sequence_header_if_audio_specific_config_invalid() {
+    let mut context = TestContextBuilder::new().into_publisher().await;
+    context.set_as_active_publisher().await;
+
+    // Object type of zero is not valid
+    let data = Bytes::from(vec![0xa0, 0, 0x02, 0x10]);
+    let timestamp = RtmpTimestamp::new(5);
+    context
+        .client
+        .publish_audio(data.clone(), timestamp.clone());
+
+    let receiver = context.publish_receiver.as_mut().unwrap();
+    let response = test_utils::expect_mpsc_response(receiver).await;
+    match response {
+        RtmpEndpointPublisherMessage::NewAudioData {
+            publisher: _,
+            timestamp: _,
+            data: _,
+            is_sequence_header,
+            codec: _,
+        } => {
+            assert!(
+                !is_sequence_header,
+                "Expected is sequence header to be false"
+            );
+        }
+
+        message => panic!("Unexpected publisher message: {:?}", message),
+    };
+}
+
+#[tokio::test]
+async fn published_aac_audio_has_adts_header_removed() {
+    let mut context = TestContextBuilder::new().into_publisher().await;
+    context.set_as_active_publisher().await;
+
+    let data = Bytes::from(vec![
+        0xa0, 1, 0xff, 0xf1, 0x50, 0x80, 0x01, 0x5f, 0xfc, 10, 11, 12,
+    ]);
+
+    let timestamp = RtmpTimestamp::new(5);
+    context
+        .client
+        .publish_audio(data.clone(), timestamp.clone());
+
+    let receiver = context.publish_receiver.as_mut().unwrap();
+    let response = test_utils::expect_mpsc_response(receiver).await;
+    match response {
+        RtmpEndpointPublisherMessage::NewAudioData {
+            publisher: _,
+            timestamp: _,
+            data: event_data,
+            is_sequence_header,
+            codec: _,
+        } => {
+            assert!(
+                !is_sequence_header,
+                "Expected is sequence header to be false"
+            );
+
+            assert_eq!(&event_data[..], &[10, 11, 12], "Unexpected audio data");
+        }
+
+        message => panic!("Unexpected publisher message: {:?}", message),
+    };
+}
+
+#[tokio::test]
+async fn stream_becoming_active_notification_when_watcher_connects() {
+    let mut context = TestContextBuilder::new().into_watcher().await;
+    context.client.perform_handshake().await;
+    context
+        .client
+        .connect_to_app(context.rtmp_app.clone(), true)
+        .await;
+
+    context
+        .client
+        .watch_stream_key("key".to_string(), true)
+        .await;
+
+    let receiver = context.watch_receiver.as_mut().unwrap();
+    let response = test_utils::expect_mpsc_response(receiver).await;
+    match response {
+        RtmpEndpointWatcherNotification::StreamKeyBecameActive {
+            stream_key,
+            reactor_update_channel: _,
+        } => {
+            assert_eq!(stream_key, "key".to_string());
+        }
+
+        message => panic!("Unexpected publisher message received: {:?}", message),
+    };
+}
+
+#[tokio::test]
+async fn watcher_joined_notification_sent_after_stream_becomes_active() {
+    let mut context = TestContextBuilder::new().into_watcher().await;
+    context.client.perform_handshake().await;
+    context
+        .client
+        .connect_to_app(context.rtmp_app.clone(), true)
+        .await;
+
+    context
+        .client
+        .watch_stream_key("key".to_string(), true)
+        .await;
+
+    let receiver = context.watch_receiver.as_mut().unwrap();
+    let _ = test_utils::expect_mpsc_response(receiver).await; // stream key became active
+    let response = test_utils::expect_mpsc_response(receiver).await;
+    match response {
+        RtmpEndpointWatcherNotification::WatcherJoined { stream_key } => {
+            assert_eq!(stream_key, "key".to_string());
+        }
+
+        message => panic!("Unexpected watcher message received: {:?}", message),
+    };
+}
+
+#[tokio::test]
+async fn watcher_can_watch_stream_key_matching_registered_pattern() {
+    let mut context = TestContextBuilder::new()
+        .set_stream_key(StreamKeyRegistration::Pattern("live_*".to_string()))
+        .into_watcher()
+        .await;
+
+    context.client.perform_handshake().await;
+    context
+        .client
+        .connect_to_app(context.rtmp_app.clone(), true)
+        .await;
+
+    context
+        .client
+        .watch_stream_key("live_abc".to_string(), true)
+        .await;
+
+    let receiver = context.watch_receiver.as_mut().unwrap();
+    let response = test_utils::expect_mpsc_response(receiver).await;
+    match response {
+        RtmpEndpointWatcherNotification::StreamKeyBecameActive { stream_key, .. } => {
+            assert_eq!(stream_key, "live_abc".to_string());
+        }
+
+        message => panic!("Unexpected watcher message received: {:?}", message),
+    };
+}
+
+#[tokio::test]
+async fn watcher_disconnected_if_stream_key_does_not_match_registered_pattern() {
+    let mut context = TestContextBuilder::new()
+        .set_stream_key(StreamKeyRegistration::Pattern("live_*".to_string()))
+        .into_watcher()
+        .await;
+
+    context.client.perform_handshake().await;
+    context
+        .client
+        .connect_to_app(context.rtmp_app.clone(), true)
+        .await;
+
+    context
+        .client
+        .watch_stream_key("test_abc".to_string(), false)
+        .await;
+
+    context.client.assert_connection_sender_closed().await;
+}
+
+#[tokio::test]
+async fn removing_pattern_registration_does_not_disconnect_publisher_of_overlapping_exact_key() {
+    let mut context = TestContextBuilder::new()
+        .set_stream_key(StreamKeyRegistration::Exact("live_abc".to_string()))
+        .into_publisher()
+        .await;
+
+    let (sender, mut receiver) = unbounded_channel();
+    context
+        .endpoint
+        .send(RtmpEndpointRequest::ListenForPublishers {
+            port: 9999,
+            use_tls: false,
+            requires_registrant_approval: false,
+            stream_id: None,
+            ip_restrictions: IpRestriction::None,
+            rtmp_app: context.rtmp_app.clone(),
+            rtmp_app_matching: RtmpAppMatching::Exact,
+            rtmp_stream_key: StreamKeyRegistration::Pattern("live_*".to_string()),
+            message_channel: sender,
+            max_connects_per_minute: None,
+        })
+        .expect("Endpoint request failed to send");
+
+    let response = test_utils::expect_mpsc_response(&mut receiver).await;
+    match response {
+        RtmpEndpointPublisherMessage::PublisherRegistrationSuccessful => (),
+        message => panic!("Unexpected publisher message received: {:?}", message),
+    }
 
-#[tokio::test]
-async fn stream_becoming_active_notification_when_watcher_connects() {
-    let mut context = TestContextBuilder::new().into_watcher().await;
     context.client.perform_handshake().await;
     context
         .client
@@ -1287,21 +1759,66 @@ async fn stream_becoming_active_notification_when_watcher_connects() {
 
     context
         .client
-        .watch_stream_key("key".to_string(), true)
+        .publish_to_stream_key("live_abc".to_string(), true)
         .await;
 
-    let receiver = context.watch_receiver.as_mut().unwrap();
-    let response = test_utils::expect_mpsc_response(receiver).await;
+    let publish_receiver = context.publish_receiver.as_mut().unwrap();
+    let response = test_utils::expect_mpsc_response(publish_receiver).await;
     match response {
-        RtmpEndpointWatcherNotification::StreamKeyBecameActive {
-            stream_key,
-            reactor_update_channel: _,
-        } => {
-            assert_eq!(stream_key, "key".to_string());
-        }
-
+        RtmpEndpointPublisherMessage::NewPublisherConnected { .. } => (),
         message => panic!("Unexpected publisher message received: {:?}", message),
-    };
+    }
+
+    context
+        .endpoint
+        .send(RtmpEndpointRequest::RemoveRegistration {
+            registration_type: RegistrationType::Publisher,
+            port: 9999,
+            rtmp_app: context.rtmp_app.clone(),
+            rtmp_stream_key: StreamKeyRegistration::Pattern("live_*".to_string()),
+        })
+        .expect("Endpoint request failed to send");
+
+    context.client.assert_connection_sender_open().await;
+}
+
+#[test]
+fn stream_key_patterns_match_glob_wildcards() {
+    let pattern = StreamKeyRegistration::Pattern("live_*".to_string());
+    assert!(pattern.matches("live_"), "Expected 'live_' to match");
+    assert!(pattern.matches("live_abc"), "Expected 'live_abc' to match");
+    assert!(!pattern.matches("live"), "Expected 'live' to not match");
+    assert!(
+        !pattern.matches("test_live_abc"),
+        "Expected 'test_live_abc' to not match"
+    );
+
+    let pattern = StreamKeyRegistration::Pattern("*_hd?".to_string());
+    assert!(pattern.matches("abc_hd1"), "Expected 'abc_hd1' to match");
+    assert!(pattern.matches("a_b_hd2"), "Expected 'a_b_hd2' to match");
+    assert!(!pattern.matches("abc_hd"), "Expected 'abc_hd' to not match");
+    assert!(
+        !pattern.matches("abc_hd12"),
+        "Expected 'abc_hd12' to not match"
+    );
+}
+
+#[test]
+fn configured_stream_keys_create_expected_registrations() {
+    assert_eq!(
+        StreamKeyRegistration::from_configured_key("*"),
+        StreamKeyRegistration::Any
+    );
+
+    assert_eq!(
+        StreamKeyRegistration::from_configured_key("abc"),
+        StreamKeyRegistration::Exact("abc".to_string())
+    );
+
+    assert_eq!(
+        StreamKeyRegistration::from_configured_key("live_*"),
+        StreamKeyRegistration::Pattern("live_*".to_string())
+    );
 }
 
 #[tokio::test]
@@ -1338,6 +1855,40 @@ async fn stream_becomes_inactive_when_only_watcher_disconnects() {
     }
 }
 
+#[tokio::test]
+async fn watcher_accepted_when_under_max_watchers() {
+    let mut context = TestContextBuilder::new()
+        .set_max_watchers(1)
+        .into_watcher()
+        .await;
+
+    context.set_as_active_watcher().await;
+}
+
+#[tokio::test]
+async fn watcher_disconnected_when_max_watchers_reached() {
+    let mut context = TestContextBuilder::new()
+        .set_max_watchers(0)
+        .into_watcher()
+        .await;
+
+    context.client.perform_handshake().await;
+    context
+        .client
+        .connect_to_app(context.rtmp_app.clone(), true)
+        .await;
+
+    context
+        .client
+        .watch_stream_key("key".to_string(), false)
+        .await;
+
+    context.client.assert_connection_sender_closed().await;
+
+    let receiver = context.watch_receiver.as_mut().unwrap();
+    test_utils::expect_mpsc_timeout(receiver).await;
+}
+
 #[tokio::test]
 async fn watcher_receives_metadata() {
     let mut context = TestContextBuilder::new().into_watcher().await;
@@ -1380,6 +1931,84 @@ async fn watcher_receives_metadata() {
     }
 }
 
+#[tokio::test]
+async fn watcher_receives_metadata_updates_mid_stream() {
+    let mut context = TestContextBuilder::new().into_watcher().await;
+    context.set_as_active_watcher().await;
+
+    for width in [1920, 1280].iter() {
+        let mut metadata = StreamMetadata::new();
+        metadata.video_width = Some(*width);
+
+        context
+            .media_sender
+            .as_ref()
+            .unwrap()
+            .send(RtmpEndpointMediaMessage {
+                stream_key: "key".to_string(),
+                data: RtmpEndpointMediaData::NewStreamMetaData { metadata },
+            })
+            .expect("Failed to send media message");
+    }
+
+    for width in [1920, 1280].iter() {
+        let event = context
+            .client
+            .get_next_event()
+            .await
+            .expect("Expected an event returned");
+
+        match event {
+            ClientSessionEvent::StreamMetadataReceived { metadata } => {
+                assert_eq!(metadata.video_width, Some(*width), "Unexpected video width");
+            }
+
+            event => panic!("Unexpected event raised: {:?}", event),
+        }
+    }
+}
+
+#[tokio::test]
+async fn watcher_joining_after_metadata_sent_receives_latest_metadata() {
+    let mut context = TestContextBuilder::new().into_watcher().await;
+
+    for width in [1920, 1280].iter() {
+        let mut metadata = StreamMetadata::new();
+        metadata.video_width = Some(*width);
+
+        context
+            .media_sender
+            .as_ref()
+            .unwrap()
+            .send(RtmpEndpointMediaMessage {
+                stream_key: "key".to_string(),
+                data: RtmpEndpointMediaData::NewStreamMetaData { metadata },
+            })
+            .expect("Failed to send media message");
+    }
+
+    context.set_as_active_watcher().await;
+
+    let event = context
+        .client
+        .get_next_event()
+        .await
+        .expect("Expected an event returned");
+
+    match event {
+        ClientSessionEvent::StreamMetadataReceived { metadata } => {
+            assert_eq!(metadata.video_width, Some(1280), "Unexpected video width");
+        }
+
+        event => panic!("Unexpected event raised: {:?}", event),
+    }
+
+    let event = context.client.get_next_event().await;
+    if let Some(event) = event {
+        panic!("Expected no events, but got {:?}", event);
+    }
+}
+
 #[tokio::test]
 async fn watcher_receives_video_wrapped_in_flv_tag_denoting_non_keyframe() {
     let mut context = TestContextBuilder::new().into_watcher().await;
@@ -1474,6 +2103,52 @@ async fn watcher_receives_video_wrapped_in_flv_tag_denoting_keyframe() {
     }
 }
 
+#[tokio::test]
+async fn watcher_not_sent_video_until_keyframe_when_starting_on_keyframe() {
+    let mut context = TestContextBuilder::new()
+        .set_start_on_keyframe(true)
+        .into_watcher()
+        .await;
+
+    context.set_as_active_watcher().await;
+
+    for is_keyframe in [false, true].iter() {
+        context
+            .media_sender
+            .as_ref()
+            .unwrap()
+            .send(RtmpEndpointMediaMessage {
+                stream_key: "key".to_string(),
+                data: RtmpEndpointMediaData::NewVideoData {
+                    codec: H264,
+                    data: Bytes::from(vec![1, 2, 3, 4]),
+                    is_sequence_header: false,
+                    is_keyframe: *is_keyframe,
+                    timestamp: RtmpTimestamp::new(5),
+                    composition_time_offset: 0,
+                },
+            })
+            .expect("Failed to send media message");
+    }
+
+    let event = context
+        .client
+        .get_next_event()
+        .await
+        .expect("Expected an event returned");
+
+    match event {
+        ClientSessionEvent::VideoDataReceived { data, .. } => {
+            assert_eq!(
+                data[0], 0x17,
+                "Expected first video received to be a keyframe"
+            );
+        }
+
+        event => panic!("Unexpected event raised: {:?}", event),
+    }
+}
+
 #[tokio::test]
 async fn watcher_does_not_receive_non_h264_video() {
     let mut context = TestContextBuilder::new().into_watcher().await;
@@ -1587,6 +2262,46 @@ async fn aac_audio_has_flv_headers_added_for_non_sequence_header() {
     }
 }
 
+#[tokio::test]
+async fn adts_framed_aac_audio_has_adts_header_replaced_with_flv_headers() {
+    let mut context = TestContextBuilder::new().into_watcher().await;
+    context.set_as_active_watcher().await;
+
+    let sent_data = Bytes::from(vec![0xff, 0xf1, 0x50, 0x80, 0x01, 0x5f, 0xfc, 10, 11, 12]);
+    let sent_timestamp = RtmpTimestamp::new(5);
+
+    match context
+        .media_sender
+        .as_ref()
+        .unwrap()
+        .send(RtmpEndpointMediaMessage {
+            stream_key: "key".to_string(),
+            data: RtmpEndpointMediaData::NewAudioData {
+                codec: AudioCodec::Aac,
+                data: sent_data.clone(),
+                is_sequence_header: false,
+                timestamp: sent_timestamp.clone(),
+            },
+        }) {
+        Ok(_) => (),
+        Err(_) => panic!("Failed to send media message"),
+    };
+
+    let event = context
+        .client
+        .get_next_event()
+        .await
+        .expect("Expected event returned");
+    match event {
+        ClientSessionEvent::AudioDataReceived { data, timestamp } => {
+            assert_eq!(&data, &vec![0xaf, 1, 10, 11, 12], "Unexpected audio data");
+            assert_eq!(timestamp, sent_timestamp, "Unexpected timestamp");
+        }
+
+        event => panic!("Unexpected event: {:?}", event),
+    }
+}
+
 #[tokio::test]
 async fn watcher_does_not_receives_unknown_audio_codec() {
     let mut context = TestContextBuilder::new().into_watcher().await;
@@ -1640,6 +2355,7 @@ async fn consumer_accepts_publisher() {
     let response = test_utils::expect_mpsc_response(receiver).await;
     match response {
         RtmpEndpointPublisherMessage::PublisherRequiringApproval {
+            rtmp_app: _,
             stream_key,
             connection_id,
             response_channel,
@@ -1654,7 +2370,7 @@ async fn consumer_accepts_publisher() {
             let (_sender, receiver) = unbounded_channel();
             response_channel
                 .send(ValidationResponse::Approve {
-                    reactor_update_channel: receiver,
+                    reactor_update_channel: Some(receiver),
                 })
                 .expect("Failed to send approval")
         }
@@ -1668,7 +2384,9 @@ async fn consumer_accepts_publisher() {
             reactor_update_channel,
             connection_id,
             stream_id: _,
+            rtmp_app: _,
             stream_key,
+            remote_address: _,
         } => {
             assert_eq!(
                 connection_id.0,
@@ -1708,6 +2426,7 @@ async fn consumer_rejectin_publisher_disconnects_client() {
     let response = test_utils::expect_mpsc_response(receiver).await;
     match response {
         RtmpEndpointPublisherMessage::PublisherRequiringApproval {
+            rtmp_app: _,
             stream_key,
             connection_id,
             response_channel,
@@ -1766,7 +2485,7 @@ async fn consumer_accepts_watcher() {
             let (_sender, receiver) = unbounded_channel();
             response_channel
                 .send(ValidationResponse::Approve {
-                    reactor_update_channel: receiver,
+                    reactor_update_channel: Some(receiver),
                 })
                 .expect("Failed to send approval")
         }
@@ -1834,3 +2553,175 @@ async fn consumer_rejecting_watcher_disconnects_client() {
 
     context.client.assert_connection_sender_closed().await;
 }
+
+#[tokio::test]
+async fn statistics_contain_publisher_connection_and_bytes_received() {
+    let mut context = TestContextBuilder::new().into_publisher().await;
+    context.set_as_active_publisher().await;
+
+    context.client.publish_video(
+        Bytes::from(vec![1, 2, 3, 4, 5, 6, 7]),
+        RtmpTimestamp::new(5),
+    );
+
+    let receiver = context.publish_receiver.as_mut().unwrap();
+    let _ = test_utils::expect_mpsc_response(receiver).await;
+
+    let statistics = get_statistics(&context.endpoint).await;
+    assert_eq!(
+        statistics.registrations.len(),
+        1,
+        "Unexpected number of registrations"
+    );
+
+    let registration = &statistics.registrations[0];
+    assert_eq!(
+        registration.registration_type,
+        RegistrationType::Publisher,
+        "Unexpected registration type"
+    );
+    assert_eq!(registration.port, 9999, "Unexpected port");
+    assert_eq!(registration.rtmp_app, context.rtmp_app, "Unexpected app");
+    assert_eq!(
+        registration.active_connections, 1,
+        "Unexpected active connections"
+    );
+
+    // Flv tag and avc video packet header are stripped out
+    assert_eq!(
+        registration.bytes_transferred, 2,
+        "Unexpected bytes transferred"
+    );
+}
+
+#[tokio::test]
+async fn statistics_contain_watcher_connection_and_bytes_sent() {
+    let mut context = TestContextBuilder::new().into_watcher().await;
+    context.set_as_active_watcher().await;
+
+    context
+        .media_sender
+        .as_ref()
+        .unwrap()
+        .send(RtmpEndpointMediaMessage {
+            stream_key: "key".to_string(),
+            data: RtmpEndpointMediaData::NewVideoData {
+                codec: H264,
+                data: Bytes::from(vec![1, 2, 3, 4]),
+                is_sequence_header: false,
+                is_keyframe: false,
+                timestamp: RtmpTimestamp::new(5),
+                composition_time_offset: 0,
+            },
+        })
+        .expect("Failed to send media message");
+
+    let _ = context
+        .client
+        .get_next_event()
+        .await
+        .expect("Expected an event returned");
+
+    let statistics = get_statistics(&context.endpoint).await;
+    assert_eq!(
+        statistics.registrations.len(),
+        1,
+        "Unexpected number of registrations"
+    );
+
+    let registration = &statistics.registrations[0];
+    assert_eq!(
+        registration.registration_type,
+        RegistrationType::Watcher,
+        "Unexpected registration type"
+    );
+    assert_eq!(
+        registration.active_connections, 1,
+        "Unexpected active connections"
+    );
+    assert_eq!(
+        registration.bytes_transferred, 4,
+        "Unexpected bytes transferred"
+    );
+}
+
+#[tokio::test]
+async fn tls_certificate_reload_passed_to_socket_manager() {
+    let (mut client, sender) = RtmpTestClient::new();
+    let endpoint = start_rtmp_server_endpoint(sender);
+
+    let (sender, receiver) = channel();
+    endpoint
+        .send(RtmpEndpointRequest::ReloadTlsCertificate {
+            cert_path: "cert.pem".to_string(),
+            key_path: "key.pem".to_string(),
+            response_channel: sender,
+        })
+        .expect("Endpoint request failed to send");
+
+    client
+        .accept_tls_reload_request("cert.pem", "key.pem")
+        .await;
+
+    let response = test_utils::expect_oneshot_response(receiver).await;
+    assert!(response.is_ok(), "Expected reload to succeed");
+}
+
+async fn get_statistics(endpoint: &UnboundedSender<RtmpEndpointRequest>) -> RtmpEndpointStatistics {
+    let (sender, receiver) = channel();
+    endpoint
+        .send(RtmpEndpointRequest::GetStatistics {
+            response_channel: sender,
+        })
+        .expect("Failed to send statistics request");
+
+    test_utils::expect_oneshot_response(receiver).await
+}
+
+#[tokio::test]
+async fn watcher_disconnected_when_disconnect_requested_for_its_connection() {
+    let mut context = TestContextBuilder::new().into_watcher().await;
+    context.set_as_active_watcher().await;
+
+    context
+        .endpoint
+        .send(RtmpEndpointRequest::DisconnectConnection {
+            connection_id: ConnectionId(rtmp_client::CONNECTION_ID.to_string()),
+        })
+        .expect("Failed to send disconnect request");
+
+    let receiver = context.watch_receiver.as_mut().unwrap();
+    let response = test_utils::expect_mpsc_response(receiver).await;
+    match response {
+        RtmpEndpointWatcherNotification::StreamKeyBecameInactive { stream_key } => {
+            assert_eq!(stream_key, "key".to_string());
+        }
+
+        message => panic!("Unexpected watcher notification received: {:?}", message),
+    }
+
+    context.client.assert_connection_sender_closed().await;
+}
+
+#[tokio::test]
+async fn disconnect_request_for_unknown_connection_ignored() {
+    let mut context = TestContextBuilder::new().into_watcher().await;
+    context.set_as_active_watcher().await;
+
+    context
+        .endpoint
+        .send(RtmpEndpointRequest::DisconnectConnection {
+            connection_id: ConnectionId("unknown".to_string()),
+        })
+        .expect("Failed to send disconnect request");
+
+    let receiver = context.watch_receiver.as_mut().unwrap();
+    test_utils::expect_mpsc_timeout(receiver).await;
+
+    context.client.stop_watching().await;
+    let response = test_utils::expect_mpsc_response(receiver).await;
+    match response {
+        RtmpEndpointWatcherNotification::StreamKeyBecameInactive { .. } => (),
+        message => panic!("Unexpected watcher notification received: {:?}", message),
+    }
+}
@@ -70,6 +70,8 @@ impl RtmpTestClient {
                 self.socket_manager_response_sender = Some(response_channel);
                 self.port = Some(port);
             }
+
+            request => panic!("Unexpected socket manager request: {:?}", request),
         }
     }
 
@@ -102,6 +104,29 @@ impl RtmpTestClient {
                     reason: RequestFailureReason::PortInUse,
                 });
             }
+
+            request => panic!("Unexpected socket manager request: {:?}", request),
+        }
+    }
+
+    pub async fn accept_tls_reload_request(&mut self, cert_path: &str, key_path: &str) {
+        let request = test_utils::expect_mpsc_response(&mut self.socket_manager_receiver).await;
+        match request {
+            TcpSocketRequest::ReloadTlsCertificate {
+                cert_path: requested_cert_path,
+                key_path: requested_key_path,
+                response_channel,
+            } => {
+                assert_eq!(
+                    requested_cert_path, cert_path,
+                    "Unexpected certificate path"
+                );
+                assert_eq!(requested_key_path, key_path, "Unexpected key path");
+
+                let _ = response_channel.send(Ok(()));
+            }
+
+            request => panic!("Unexpected socket manager request: {:?}", request),
         }
     }
 
@@ -126,6 +151,22 @@ impl RtmpTestClient {
         }
     }
 
+    pub async fn assert_connection_sender_open(&mut self) {
+        let connection = self
+            .connection
+            .as_mut()
+            .expect("Connection not established yet");
+
+        if let Ok(()) = timeout(
+            Duration::from_millis(10),
+            connection.incoming_bytes.closed(),
+        )
+        .await
+        {
+            panic!("Response sender unexpectedly closed (disconnected)");
+        }
+    }
+
     pub async fn perform_handshake(&mut self) {
         if self.connection.is_some() {
             panic!("Only one connection is supported at a time");
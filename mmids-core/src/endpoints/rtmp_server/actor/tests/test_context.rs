@@ -1,6 +1,6 @@
 use crate::endpoints::rtmp_server::actor::tests::rtmp_client::RtmpTestClient;
 use crate::endpoints::rtmp_server::{
-    start_rtmp_server_endpoint, IpRestriction, RtmpEndpointMediaMessage,
+    start_rtmp_server_endpoint, IpRestriction, RtmpAppMatching, RtmpEndpointMediaMessage,
     RtmpEndpointPublisherMessage, RtmpEndpointRequest, RtmpEndpointWatcherNotification,
     StreamKeyRegistration,
 };
@@ -16,7 +16,11 @@ pub struct TestContextBuilder {
     stream_id: Option<Option<StreamId>>,
     ip_restriction: Option<IpRestriction>,
     rtmp_app: Option<String>,
+    rtmp_app_matching: Option<RtmpAppMatching>,
     rtmp_stream_key: Option<StreamKeyRegistration>,
+    max_watchers: Option<usize>,
+    start_on_keyframe: Option<bool>,
+    max_connects_per_minute: Option<u32>,
 }
 
 pub struct TestContext {
@@ -37,7 +41,11 @@ impl TestContextBuilder {
             stream_id: None,
             ip_restriction: None,
             rtmp_app: None,
+            rtmp_app_matching: None,
             rtmp_stream_key: None,
+            max_watchers: None,
+            start_on_keyframe: None,
+            max_connects_per_minute: None,
         }
     }
 
@@ -46,11 +54,31 @@ impl TestContextBuilder {
         self
     }
 
+    pub fn set_rtmp_app_matching(mut self, app_matching: RtmpAppMatching) -> Self {
+        self.rtmp_app_matching = Some(app_matching);
+        self
+    }
+
     pub fn set_requires_registrant_approval(mut self, requires_approval: bool) -> Self {
         self.requires_registrant_approval = Some(requires_approval);
         self
     }
 
+    pub fn set_max_watchers(mut self, max_watchers: usize) -> Self {
+        self.max_watchers = Some(max_watchers);
+        self
+    }
+
+    pub fn set_start_on_keyframe(mut self, start_on_keyframe: bool) -> Self {
+        self.start_on_keyframe = Some(start_on_keyframe);
+        self
+    }
+
+    pub fn set_max_connects_per_minute(mut self, max_connects_per_minute: u32) -> Self {
+        self.max_connects_per_minute = Some(max_connects_per_minute);
+        self
+    }
+
     pub async fn into_publisher(self) -> TestContext {
         let (sender, receiver) = unbounded_channel();
         let request = RtmpEndpointRequest::ListenForPublishers {
@@ -60,8 +88,10 @@ impl TestContextBuilder {
             stream_id: self.stream_id.unwrap_or(None),
             ip_restrictions: self.ip_restriction.unwrap_or(IpRestriction::None),
             rtmp_app: self.rtmp_app.unwrap_or(RTMP_APP.to_string()),
+            rtmp_app_matching: self.rtmp_app_matching.unwrap_or(RtmpAppMatching::Exact),
             rtmp_stream_key: self.rtmp_stream_key.unwrap_or(StreamKeyRegistration::Any),
             message_channel: sender,
+            max_connects_per_minute: self.max_connects_per_minute,
         };
 
         TestContext::new_publisher(request, receiver).await
@@ -79,6 +109,9 @@ impl TestContextBuilder {
             rtmp_stream_key: self.rtmp_stream_key.unwrap_or(StreamKeyRegistration::Any),
             notification_channel: notification_sender,
             media_channel: media_receiver,
+            media_backlog: None,
+            max_watchers: self.max_watchers,
+            start_on_keyframe: self.start_on_keyframe.unwrap_or(false),
         };
 
         TestContext::new_watcher(request, notification_receiver, media_sender).await
@@ -118,6 +151,12 @@ impl TestContext {
             RtmpEndpointWatcherNotification::StreamKeyBecameActive { .. } => (),
             message => panic!("Unexpected publisher message received: {:?}", message),
         };
+
+        let response = test_utils::expect_mpsc_response(receiver).await;
+        match response {
+            RtmpEndpointWatcherNotification::WatcherJoined { .. } => (),
+            message => panic!("Unexpected publisher message received: {:?}", message),
+        };
     }
 
     async fn new_publisher(
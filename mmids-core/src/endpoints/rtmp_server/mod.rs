@@ -15,8 +15,13 @@
 
 mod actor;
 
+pub(crate) use actor::{
+    unwrap_audio_from_flv, unwrap_video_from_flv, wrap_audio_into_flv, wrap_video_into_flv,
+    UnwrappedAudio, UnwrappedVideo,
+};
+
 use crate::codecs::{AudioCodec, VideoCodec};
-use crate::net::tcp::TcpSocketRequest;
+use crate::net::tcp::{RequestFailureReason, TcpSocketRequest};
 use crate::net::{ConnectionId, IpAddress};
 use crate::reactors::ReactorWorkflowUpdate;
 use crate::StreamId;
@@ -26,6 +31,9 @@ use futures::stream::FuturesUnordered;
 use rml_rtmp::sessions::StreamMetadata;
 use rml_rtmp::time::RtmpTimestamp;
 use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::oneshot::Sender;
 
@@ -54,10 +62,94 @@ pub enum StreamKeyRegistration {
 
     /// Only set up registration for the exact stream key
     Exact(String),
+
+    /// Set up registration for all stream keys matching a glob pattern, where `*` matches any
+    /// number of characters and `?` matches a single character (e.g. `live_*`).  Exact stream key
+    /// registrations take priority over patterns that also match the stream key.
+    Pattern(String),
+}
+
+impl StreamKeyRegistration {
+    /// Creates the registration for a stream key specified in a step's configuration.  A stream
+    /// key of `*` registers all stream keys, and any other stream key containing `*` or `?` is
+    /// treated as a glob pattern.
+    pub fn from_configured_key(stream_key: &str) -> Self {
+        if stream_key == "*" {
+            StreamKeyRegistration::Any
+        } else if stream_key.contains(|c| c == '*' || c == '?') {
+            StreamKeyRegistration::Pattern(stream_key.to_string())
+        } else {
+            StreamKeyRegistration::Exact(stream_key.to_string())
+        }
+    }
+
+    /// Checks if the specified stream key falls under this registration
+    pub(crate) fn matches(&self, stream_key: &str) -> bool {
+        match self {
+            StreamKeyRegistration::Any => true,
+            StreamKeyRegistration::Exact(key) => key == stream_key,
+            StreamKeyRegistration::Pattern(pattern) => matches_pattern(pattern, stream_key),
+        }
+    }
+}
+
+/// Checks if the stream key matches the glob pattern.  When a `*` fails to lead to a match, the
+/// characters it consumes are extended one at a time until the pattern matches or the stream key
+/// runs out.
+fn matches_pattern(pattern: &str, stream_key: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let stream_key = stream_key.chars().collect::<Vec<_>>();
+    let mut pattern_index = 0;
+    let mut key_index = 0;
+    let mut last_wildcard = None;
+
+    while key_index < stream_key.len() {
+        match pattern.get(pattern_index) {
+            Some('*') => {
+                last_wildcard = Some((pattern_index, key_index));
+                pattern_index += 1;
+            }
+
+            Some(character) if *character == '?' || *character == stream_key[key_index] => {
+                pattern_index += 1;
+                key_index += 1;
+            }
+
+            _ => match last_wildcard {
+                Some((wildcard_index, wildcard_key_index)) => {
+                    last_wildcard = Some((wildcard_index, wildcard_key_index + 1));
+                    pattern_index = wildcard_index + 1;
+                    key_index = wildcard_key_index + 1;
+                }
+
+                None => return false,
+            },
+        }
+    }
+
+    pattern[pattern_index..]
+        .iter()
+        .all(|character| *character == '*')
+}
+
+/// Specifies how the RTMP application clients connect to is matched against a registration's
+/// RTMP application
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RtmpAppMatching {
+    /// The application name must match the registered application exactly
+    Exact,
+
+    /// The application name must match the registered application, ignoring differences in case
+    CaseInsensitive,
+
+    /// Clients connecting to any application name are accepted.  The registered application name
+    /// is only used to identify the registration (e.g. for removing it).  Applications that have
+    /// their own registrations take priority.
+    Any,
 }
 
 /// Specifies if there are any IP address restrictions as part of an RTMP server registration
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum IpRestriction {
     /// All IP addresses are allowed
     None,
@@ -67,10 +159,50 @@ pub enum IpRestriction {
 
     /// All IP addresses are allowed except for the ones specified.
     Deny(Vec<IpAddress>),
+
+    /// Only IP addresses in the allow list are allowed, unless they are also in the deny list.
+    /// This allows denying specific addresses within an allowed subnet.
+    AllowWithExceptions {
+        allow: Vec<IpAddress>,
+        deny: Vec<IpAddress>,
+    },
+}
+
+impl IpRestriction {
+    /// Checks if a client at the specified socket address passes the restriction rules
+    pub(crate) fn is_allowed(&self, client_socket: &SocketAddr) -> bool {
+        match self {
+            IpRestriction::None => true,
+            IpRestriction::Allow(allowed_ips) => {
+                if let SocketAddr::V4(client_ip) = client_socket {
+                    return allowed_ips.iter().any(|ip| ip.matches(client_ip.ip()));
+                }
+
+                false // ipv6 clients not supported atm
+            }
+
+            IpRestriction::Deny(denied_ips) => {
+                if let SocketAddr::V4(client_ip) = client_socket {
+                    return denied_ips.iter().all(|ip| !ip.matches(client_ip.ip()));
+                }
+
+                false // ipv6
+            }
+
+            IpRestriction::AllowWithExceptions { allow, deny } => {
+                if let SocketAddr::V4(client_ip) = client_socket {
+                    return allow.iter().any(|ip| ip.matches(client_ip.ip()))
+                        && deny.iter().all(|ip| !ip.matches(client_ip.ip()));
+                }
+
+                false // ipv6
+            }
+        }
+    }
 }
 
 /// Type of registration the request is related to
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum RegistrationType {
     Publisher,
     Watcher,
@@ -88,6 +220,9 @@ pub enum RtmpEndpointRequest {
         /// Name of the RTMP application publishers will connect to
         rtmp_app: String,
 
+        /// How the RTMP application publishers connect to is matched against `rtmp_app`
+        rtmp_app_matching: RtmpAppMatching,
+
         /// What stream key publishers should be using
         rtmp_stream_key: StreamKeyRegistration,
 
@@ -110,6 +245,11 @@ pub enum RtmpEndpointRequest {
         /// the correct app/stream key combination and pass ip restrictions. Instead the registrant
         /// should be asked for final verification if the publisher should be allowed or not.
         requires_registrant_approval: bool,
+
+        /// The maximum number of publish attempts a single IP address may make through this
+        /// registration each minute.  Attempts beyond this rate are disconnected.  `None` means
+        /// there is no limit.
+        max_connects_per_minute: Option<u32>,
     },
 
     /// Requests the RTMP server to allow clients to receive video on the given port, app,
@@ -130,6 +270,11 @@ pub enum RtmpEndpointRequest {
         /// The channel that the registrant will send updated media data to the rtmp endpoint on
         media_channel: UnboundedReceiver<RtmpEndpointMediaMessage>,
 
+        /// An optional count of media messages the registrant has sent on the media channel that
+        /// have not been read yet.  The endpoint decrements it as it reads each message, which
+        /// allows the registrant to detect when the endpoint is falling behind.
+        media_backlog: Option<Arc<AtomicUsize>>,
+
         /// What IP restriction rules should be in place for this registration
         ip_restrictions: IpRestriction,
 
@@ -140,6 +285,16 @@ pub enum RtmpEndpointRequest {
         /// the correct app/stream key combination and pass ip restrictions. Instead the registrant
         /// should be asked for final verification if the watcher should be allowed or not.
         requires_registrant_approval: bool,
+
+        /// The maximum number of watchers that may be connected to a single stream key through
+        /// this registration at the same time.  Watchers connecting after this limit has been
+        /// reached are disconnected.  `None` means there is no limit.
+        max_watchers: Option<usize>,
+
+        /// If true, watchers are not sent any audio or video until the next keyframe, so the
+        /// first frame each watcher receives is a keyframe (preceded by the latest sequence
+        /// headers).  If false, watchers are sent media as soon as they start watching.
+        start_on_keyframe: bool,
     },
 
     /// Requests the specified registration should be removed
@@ -156,13 +311,63 @@ pub enum RtmpEndpointRequest {
         /// The stream key the registrant had registered for
         rtmp_stream_key: StreamKeyRegistration,
     },
+
+    /// Requests a snapshot of the connections and traffic for all active registrations
+    GetStatistics {
+        response_channel: Sender<RtmpEndpointStatistics>,
+    },
+
+    /// Requests the certificate used for RTMPS connections be replaced.  Only connections made
+    /// after the reload use the new certificate, so existing RTMPS sessions are not interrupted.
+    /// The new certificate is validated before it's used, and if it cannot be loaded then the
+    /// current certificate remains in use.
+    ReloadTlsCertificate {
+        /// Path to a PEM encoded certificate chain, with the leaf certificate first
+        cert_path: String,
+
+        /// Path to the PEM encoded PKCS #8 private key for the leaf certificate
+        key_path: String,
+
+        /// The channel the result of the reload will be sent on
+        response_channel: Sender<Result<(), RequestFailureReason>>,
+    },
+
+    /// Requests that a specific publisher or watcher connection be forcibly disconnected.  The
+    /// connection is cleaned up as if the client had disconnected itself, so registrants are
+    /// notified if it was the last connection for its stream key.  Requests for connection ids
+    /// that aren't connected are ignored.
+    DisconnectConnection { connection_id: ConnectionId },
+}
+
+/// A point in time snapshot of the RTMP endpoint's registrations and their activity
+#[derive(Debug)]
+pub struct RtmpEndpointStatistics {
+    pub registrations: Vec<RtmpRegistrationStatistics>,
+}
+
+/// Activity for a single publisher or watcher registration
+#[derive(Debug)]
+pub struct RtmpRegistrationStatistics {
+    pub registration_type: RegistrationType,
+    pub port: u16,
+    pub rtmp_app: String,
+    pub stream_key: StreamKeyRegistration,
+
+    /// How many publishers or watchers are currently connected through this registration
+    pub active_connections: usize,
+
+    /// Total number of media bytes received from publishers, or sent to watchers, for this
+    /// registration
+    pub bytes_transferred: u64,
 }
 
 /// Response to approval/validation requests
 #[derive(Debug)]
 pub enum ValidationResponse {
     Approve {
-        reactor_update_channel: UnboundedReceiver<ReactorWorkflowUpdate>,
+        /// If the approval came from a reactor, this is the channel that will receive updates
+        /// about the workflow tied to the stream
+        reactor_update_channel: Option<UnboundedReceiver<ReactorWorkflowUpdate>>,
     },
 
     Reject,
@@ -184,6 +389,9 @@ pub enum RtmpEndpointPublisherMessage {
         /// Unique identifier for the TCP connection that's requesting to be a publisher
         connection_id: ConnectionId,
 
+        /// The RTMP application the connection is requesting to publish on
+        rtmp_app: String,
+
         /// The stream key that the connection is requesting to be a publisher to
         stream_key: String,
 
@@ -199,10 +407,17 @@ pub enum RtmpEndpointPublisherMessage {
         /// Unique identifier for the stream.
         stream_id: StreamId,
 
+        /// Actual RTMP application that this stream is coming in from.  Mostly used if the
+        /// registrant matches more than one application name.
+        rtmp_app: String,
+
         /// Actual stream key that this stream is coming in from.  Mostly used if the registrant
         /// specified that Any stream key would be allowed.
         stream_key: String,
 
+        /// The IP address and port the publisher connected from
+        remote_address: SocketAddr,
+
         /// If provided, this is a channel which will receive workflow updates from a reactor
         /// tied to this publisher
         reactor_update_channel: Option<UnboundedReceiver<ReactorWorkflowUpdate>>,
@@ -275,6 +490,10 @@ pub enum RtmpEndpointWatcherNotification {
     /// Notifies the registrant that the last watcher has disconnected on the stream key, and
     /// there are no longer anyone watching
     StreamKeyBecameInactive { stream_key: String },
+
+    /// Notifies the registrant that a watcher has started watching the stream key.  This is sent
+    /// for every watcher, after `StreamKeyBecameActive` for the first one.
+    WatcherJoined { stream_key: String },
 }
 
 /// Message watcher registrants send to announce new media data that should be sent to watchers
@@ -0,0 +1,716 @@
+use super::media_converter::{ConvertedMedia, MediaConverter};
+use super::mpeg_ts::TsDemuxer;
+use super::{SrtEndpointPublisherMessage, SrtEndpointRequest};
+use crate::codecs::{AudioCodec, VideoCodec};
+use crate::endpoints::rtmp_server::{IpRestriction, StreamKeyRegistration};
+use crate::net::ConnectionId;
+use crate::StreamId;
+use bytes::Bytes;
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use srt_tokio::{ConnectionRequest, SrtIncoming, SrtListener, SrtSocket};
+use std::collections::HashMap;
+use std::io;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
+
+pub enum FutureResult {
+    EndpointRequestReceived {
+        request: SrtEndpointRequest,
+        receiver: UnboundedReceiver<SrtEndpointRequest>,
+    },
+
+    NoMoreEndpointRequesters,
+
+    ListenerBound {
+        port: u16,
+        listener: SrtListener,
+        incoming: SrtIncoming,
+    },
+
+    ListenerBindFailed {
+        port: u16,
+        error: io::Error,
+    },
+
+    ListenerClosed {
+        port: u16,
+    },
+
+    ListenerCancelled,
+
+    ConnectionRequestReceived {
+        port: u16,
+        request: ConnectionRequest,
+        incoming: SrtIncoming,
+        cancellation: UnboundedReceiver<()>,
+    },
+
+    ConnectionAccepted {
+        port: u16,
+        connection_id: ConnectionId,
+        stream_key: String,
+        socket: SrtSocket,
+    },
+
+    DataReceived {
+        port: u16,
+        connection_id: ConnectionId,
+        data: Bytes,
+        socket: SrtSocket,
+        cancellation: UnboundedReceiver<()>,
+    },
+
+    ConnectionClosed {
+        port: u16,
+        connection_id: ConnectionId,
+    },
+
+    PublishingRegistrantGone {
+        port: u16,
+        stream_key: StreamKeyRegistration,
+    },
+}
+
+pub struct SrtServerEndpointActor {
+    pub futures: FuturesUnordered<BoxFuture<'static, FutureResult>>,
+    pub ports: HashMap<u16, PortHandling>,
+}
+
+pub struct PortHandling {
+    status: PortStatus,
+    registrants: HashMap<StreamKeyRegistration, PublishingRegistrant>,
+    connections: HashMap<ConnectionId, Connection>,
+}
+
+enum PortStatus {
+    Binding,
+    Listening {
+        _listener: SrtListener,
+
+        // Dropped when the port is no longer needed, which stops waiting for new callers
+        _cancellation_channel: UnboundedSender<()>,
+    },
+}
+
+struct PublishingRegistrant {
+    message_channel: UnboundedSender<SrtEndpointPublisherMessage>,
+    stream_id: Option<StreamId>,
+    ip_restrictions: IpRestriction,
+}
+
+struct Connection {
+    registration: StreamKeyRegistration,
+    demuxer: TsDemuxer,
+    converter: MediaConverter,
+
+    // Dropped when the connection should be closed, which will drop the SRT socket
+    _cancellation_channel: UnboundedSender<()>,
+}
+
+impl SrtServerEndpointActor {
+    #[instrument(name = "SrtServer Endpoint Execution", skip(self, endpoint_receiver))]
+    pub async fn run(mut self, endpoint_receiver: UnboundedReceiver<SrtEndpointRequest>) {
+        info!("Starting SRT server endpoint");
+
+        self.futures
+            .push(wait_for_endpoint_request(endpoint_receiver).boxed());
+
+        while let Some(result) = self.futures.next().await {
+            match result {
+                FutureResult::NoMoreEndpointRequesters => {
+                    info!("No endpoint requesters exist");
+                    break;
+                }
+
+                FutureResult::EndpointRequestReceived { request, receiver } => {
+                    self.futures
+                        .push(wait_for_endpoint_request(receiver).boxed());
+
+                    self.handle_endpoint_request(request);
+                }
+
+                FutureResult::ListenerBound {
+                    port,
+                    listener,
+                    incoming,
+                } => {
+                    self.handle_listener_bound(port, listener, incoming);
+                }
+
+                FutureResult::ListenerBindFailed { port, error } => {
+                    error!(
+                        port = port,
+                        "Failed to bind SRT listener on port {}: {:?}", port, error
+                    );
+                    if let Some(port_handling) = self.ports.remove(&port) {
+                        for registrant in port_handling.registrants.values() {
+                            let _ = registrant
+                                .message_channel
+                                .send(SrtEndpointPublisherMessage::PublisherRegistrationFailed);
+                        }
+                    }
+                }
+
+                FutureResult::ListenerClosed { port } => {
+                    // Removing the port drops all registrant channels, letting them know their
+                    // registrations are gone.
+                    error!(
+                        port = port,
+                        "SRT listener on port {} unexpectedly closed", port
+                    );
+                    self.ports.remove(&port);
+                }
+
+                FutureResult::ListenerCancelled => (),
+
+                FutureResult::ConnectionRequestReceived {
+                    port,
+                    request,
+                    incoming,
+                    cancellation,
+                } => {
+                    self.futures
+                        .push(wait_for_connection_request(port, incoming, cancellation).boxed());
+
+                    self.handle_connection_request(port, request);
+                }
+
+                FutureResult::ConnectionAccepted {
+                    port,
+                    connection_id,
+                    stream_key,
+                    socket,
+                } => {
+                    self.handle_connection_accepted(port, connection_id, stream_key, socket);
+                }
+
+                FutureResult::DataReceived {
+                    port,
+                    connection_id,
+                    data,
+                    socket,
+                    cancellation,
+                } => {
+                    self.futures.push(
+                        wait_for_srt_data(port, connection_id.clone(), socket, cancellation)
+                            .boxed(),
+                    );
+
+                    self.handle_data_received(port, connection_id, data);
+                }
+
+                FutureResult::ConnectionClosed {
+                    port,
+                    connection_id,
+                } => {
+                    self.handle_connection_closed(port, connection_id);
+                }
+
+                FutureResult::PublishingRegistrantGone { port, stream_key } => {
+                    self.remove_publish_registration(port, stream_key);
+                }
+            }
+        }
+
+        info!("SRT server endpoint closing");
+    }
+
+    fn handle_endpoint_request(&mut self, request: SrtEndpointRequest) {
+        match request {
+            SrtEndpointRequest::ListenForPublishers {
+                port,
+                stream_key,
+                message_channel,
+                stream_id,
+                ip_restrictions,
+            } => {
+                let futures = &mut self.futures;
+                let port_handling = self.ports.entry(port).or_insert_with(|| {
+                    futures.push(bind_listener(port).boxed());
+
+                    PortHandling {
+                        status: PortStatus::Binding,
+                        registrants: HashMap::new(),
+                        connections: HashMap::new(),
+                    }
+                });
+
+                if port_handling.registrants.contains_key(&stream_key) {
+                    warn!(
+                        port = port,
+                        stream_key = ?stream_key,
+                        "SRT publisher registration failed: port {} already has a registrant for stream key {:?}",
+                        port, stream_key
+                    );
+
+                    let _ = message_channel
+                        .send(SrtEndpointPublisherMessage::PublisherRegistrationFailed);
+
+                    return;
+                }
+
+                if let PortStatus::Listening { .. } = port_handling.status {
+                    let _ = message_channel
+                        .send(SrtEndpointPublisherMessage::PublisherRegistrationSuccessful);
+                }
+
+                self.futures.push(
+                    wait_for_registrant_gone(port, stream_key.clone(), message_channel.clone())
+                        .boxed(),
+                );
+
+                port_handling.registrants.insert(
+                    stream_key,
+                    PublishingRegistrant {
+                        message_channel,
+                        stream_id,
+                        ip_restrictions,
+                    },
+                );
+            }
+
+            SrtEndpointRequest::RemoveRegistration { port, stream_key } => {
+                self.remove_publish_registration(port, stream_key);
+            }
+        }
+    }
+
+    fn handle_listener_bound(&mut self, port: u16, listener: SrtListener, incoming: SrtIncoming) {
+        let port_handling = match self.ports.get_mut(&port) {
+            Some(port_handling) => port_handling,
+            None => return, // All registrants left while binding
+        };
+
+        info!(port = port, "SRT listener bound to port {}", port);
+
+        let (cancellation_sender, cancellation_receiver) = unbounded_channel();
+        port_handling.status = PortStatus::Listening {
+            _listener: listener,
+            _cancellation_channel: cancellation_sender,
+        };
+
+        for registrant in port_handling.registrants.values() {
+            let _ = registrant
+                .message_channel
+                .send(SrtEndpointPublisherMessage::PublisherRegistrationSuccessful);
+        }
+
+        self.futures
+            .push(wait_for_connection_request(port, incoming, cancellation_receiver).boxed());
+    }
+
+    fn handle_connection_request(&mut self, port: u16, request: ConnectionRequest) {
+        let port_handling = match self.ports.get(&port) {
+            Some(port_handling) => port_handling,
+            None => return,
+        };
+
+        let remote = request.remote();
+        let stream_key = match request
+            .stream_id()
+            .and_then(|stream_id| get_stream_key(&stream_id.to_string()))
+        {
+            Some(stream_key) => stream_key,
+            None => {
+                info!(
+                    port = port,
+                    remote = %remote,
+                    "Rejecting SRT caller {} as it did not provide a stream id to publish on",
+                    remote
+                );
+
+                return; // Dropping the request rejects it
+            }
+        };
+
+        let registrant = match get_registrant(port_handling, &stream_key) {
+            Some((_, registrant)) => registrant,
+            None => {
+                info!(
+                    port = port,
+                    remote = %remote,
+                    stream_key = %stream_key,
+                    "Rejecting SRT caller {} as stream key {} is not registered",
+                    remote, stream_key
+                );
+
+                return;
+            }
+        };
+
+        if !registrant.ip_restrictions.is_allowed(&remote) {
+            info!(
+                port = port,
+                remote = %remote,
+                stream_key = %stream_key,
+                "Rejecting SRT caller {} as its ip address is restricted",
+                remote
+            );
+
+            return;
+        }
+
+        let connection_id = ConnectionId(Uuid::new_v4().to_string());
+        self.futures
+            .push(accept_connection(port, connection_id, stream_key, request).boxed());
+    }
+
+    fn handle_connection_accepted(
+        &mut self,
+        port: u16,
+        connection_id: ConnectionId,
+        stream_key: String,
+        socket: SrtSocket,
+    ) {
+        let port_handling = match self.ports.get_mut(&port) {
+            Some(port_handling) => port_handling,
+            None => return,
+        };
+
+        // The registration may have been removed while the connection was being accepted
+        let (registration, registrant) = match get_registrant(port_handling, &stream_key) {
+            Some((registration, registrant)) => (registration.clone(), registrant),
+            None => return,
+        };
+
+        let stream_id = registrant
+            .stream_id
+            .clone()
+            .unwrap_or_else(|| StreamId(Uuid::new_v4().to_string()));
+
+        info!(
+            port = port,
+            connection_id = %connection_id,
+            stream_key = %stream_key,
+            "SRT connection {} now publishing on stream key {}",
+            connection_id, stream_key
+        );
+
+        let _ =
+            registrant
+                .message_channel
+                .send(SrtEndpointPublisherMessage::NewPublisherConnected {
+                    connection_id: connection_id.clone(),
+                    stream_id,
+                    stream_key,
+                });
+
+        let (cancellation_sender, cancellation_receiver) = unbounded_channel();
+        port_handling.connections.insert(
+            connection_id.clone(),
+            Connection {
+                registration,
+                demuxer: TsDemuxer::new(),
+                converter: MediaConverter::new(),
+                _cancellation_channel: cancellation_sender,
+            },
+        );
+
+        self.futures
+            .push(wait_for_srt_data(port, connection_id, socket, cancellation_receiver).boxed());
+    }
+
+    fn handle_data_received(&mut self, port: u16, connection_id: ConnectionId, data: Bytes) {
+        let port_handling = match self.ports.get_mut(&port) {
+            Some(port_handling) => port_handling,
+            None => return,
+        };
+
+        let connection = match port_handling.connections.get_mut(&connection_id) {
+            Some(connection) => connection,
+            None => return,
+        };
+
+        let registrant = match port_handling.registrants.get(&connection.registration) {
+            Some(registrant) => registrant,
+            None => return,
+        };
+
+        for pes in connection.demuxer.push(&data) {
+            for media in connection.converter.convert(pes) {
+                let message = match media {
+                    ConvertedMedia::H264 {
+                        is_keyframe,
+                        is_sequence_header,
+                        data,
+                        timestamp,
+                    } => SrtEndpointPublisherMessage::NewVideoData {
+                        publisher: connection_id.clone(),
+                        codec: VideoCodec::H264,
+                        is_keyframe,
+                        is_sequence_header,
+                        data,
+                        timestamp,
+                    },
+
+                    ConvertedMedia::Aac {
+                        is_sequence_header,
+                        data,
+                        timestamp,
+                    } => SrtEndpointPublisherMessage::NewAudioData {
+                        publisher: connection_id.clone(),
+                        codec: AudioCodec::Aac,
+                        is_sequence_header,
+                        data,
+                        timestamp,
+                    },
+                };
+
+                let _ = registrant.message_channel.send(message);
+            }
+        }
+    }
+
+    fn handle_connection_closed(&mut self, port: u16, connection_id: ConnectionId) {
+        let port_handling = match self.ports.get_mut(&port) {
+            Some(port_handling) => port_handling,
+            None => return,
+        };
+
+        let connection = match port_handling.connections.remove(&connection_id) {
+            Some(connection) => connection,
+            None => return,
+        };
+
+        info!(
+            port = port,
+            connection_id = %connection_id,
+            "SRT connection {} disconnected",
+            connection_id
+        );
+
+        if let Some(registrant) = port_handling.registrants.get(&connection.registration) {
+            let _ = registrant
+                .message_channel
+                .send(SrtEndpointPublisherMessage::PublishingStopped { connection_id });
+        }
+    }
+
+    fn remove_publish_registration(&mut self, port: u16, stream_key: StreamKeyRegistration) {
+        let port_handling = match self.ports.get_mut(&port) {
+            Some(port_handling) => port_handling,
+            None => return,
+        };
+
+        if port_handling.registrants.remove(&stream_key).is_none() {
+            return;
+        }
+
+        info!(
+            port = port,
+            stream_key = ?stream_key,
+            "Removing SRT publisher registration for stream key {:?} on port {}",
+            stream_key, port
+        );
+
+        // Dropping the connections closes their sockets
+        port_handling
+            .connections
+            .retain(|_, connection| connection.registration != stream_key);
+
+        if port_handling.registrants.is_empty() {
+            info!(
+                port = port,
+                "No SRT registrants left on port {}, closing listener", port
+            );
+            self.ports.remove(&port);
+        }
+    }
+}
+
+fn get_registrant<'a>(
+    port_handling: &'a PortHandling,
+    stream_key: &str,
+) -> Option<(&'a StreamKeyRegistration, &'a PublishingRegistrant)> {
+    let exact_key = StreamKeyRegistration::Exact(stream_key.to_string());
+    port_handling
+        .registrants
+        .get_key_value(&exact_key)
+        .or_else(|| {
+            port_handling
+                .registrants
+                .get_key_value(&StreamKeyRegistration::Any)
+        })
+}
+
+/// Gets the stream key from an SRT stream id.  Stream ids using the SRT access control syntax
+/// take the key from the resource name (`r`), and are only allowed if they are publishing.
+fn get_stream_key(stream_id: &str) -> Option<String> {
+    let stream_id = stream_id.trim();
+    let access_control = match stream_id.strip_prefix("#!::") {
+        Some(access_control) => access_control,
+        None if stream_id.is_empty() => return None,
+        None => return Some(stream_id.to_string()),
+    };
+
+    let mut stream_key = None;
+    for pair in access_control.split(',') {
+        match pair.split_once('=') {
+            Some(("r", value)) if !value.is_empty() => stream_key = Some(value.to_string()),
+            Some(("m", mode)) if mode != "publish" => return None,
+            _ => (),
+        }
+    }
+
+    stream_key
+}
+
+async fn wait_for_endpoint_request(
+    mut receiver: UnboundedReceiver<SrtEndpointRequest>,
+) -> FutureResult {
+    match receiver.recv().await {
+        None => FutureResult::NoMoreEndpointRequesters,
+        Some(request) => FutureResult::EndpointRequestReceived { request, receiver },
+    }
+}
+
+async fn wait_for_registrant_gone(
+    port: u16,
+    stream_key: StreamKeyRegistration,
+    message_channel: UnboundedSender<SrtEndpointPublisherMessage>,
+) -> FutureResult {
+    message_channel.closed().await;
+
+    FutureResult::PublishingRegistrantGone { port, stream_key }
+}
+
+async fn bind_listener(port: u16) -> FutureResult {
+    match SrtListener::builder().bind(port).await {
+        Ok((listener, incoming)) => FutureResult::ListenerBound {
+            port,
+            listener,
+            incoming,
+        },
+
+        Err(error) => FutureResult::ListenerBindFailed { port, error },
+    }
+}
+
+async fn wait_for_connection_request(
+    port: u16,
+    mut incoming: SrtIncoming,
+    mut cancellation: UnboundedReceiver<()>,
+) -> FutureResult {
+    let request = tokio::select! {
+        request = incoming.incoming().next() => request,
+        _ = cancellation.recv() => return FutureResult::ListenerCancelled,
+    };
+
+    match request {
+        Some(request) => FutureResult::ConnectionRequestReceived {
+            port,
+            request,
+            incoming,
+            cancellation,
+        },
+
+        None => FutureResult::ListenerClosed { port },
+    }
+}
+
+async fn accept_connection(
+    port: u16,
+    connection_id: ConnectionId,
+    stream_key: String,
+    request: ConnectionRequest,
+) -> FutureResult {
+    match request.accept(None).await {
+        Ok(socket) => FutureResult::ConnectionAccepted {
+            port,
+            connection_id,
+            stream_key,
+            socket,
+        },
+
+        Err(error) => {
+            warn!(
+                port = port,
+                connection_id = %connection_id,
+                "Failed to accept SRT connection: {:?}",
+                error
+            );
+
+            FutureResult::ConnectionClosed {
+                port,
+                connection_id,
+            }
+        }
+    }
+}
+
+async fn wait_for_srt_data(
+    port: u16,
+    connection_id: ConnectionId,
+    mut socket: SrtSocket,
+    mut cancellation: UnboundedReceiver<()>,
+) -> FutureResult {
+    let result = tokio::select! {
+        result = socket.next() => result,
+        _ = cancellation.recv() => None,
+    };
+
+    match result {
+        Some(Ok((_, data))) => FutureResult::DataReceived {
+            port,
+            connection_id,
+            data,
+            socket,
+            cancellation,
+        },
+
+        Some(Err(error)) => {
+            warn!(
+                port = port,
+                connection_id = %connection_id,
+                "SRT connection read failed: {:?}",
+                error
+            );
+
+            FutureResult::ConnectionClosed {
+                port,
+                connection_id,
+            }
+        }
+
+        None => FutureResult::ConnectionClosed {
+            port,
+            connection_id,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_stream_id_used_as_stream_key() {
+        assert_eq!(get_stream_key("abc"), Some("abc".to_string()));
+    }
+
+    #[test]
+    fn empty_stream_id_has_no_stream_key() {
+        assert_eq!(get_stream_key(""), None);
+    }
+
+    #[test]
+    fn access_control_resource_name_used_as_stream_key() {
+        assert_eq!(
+            get_stream_key("#!::r=abc,m=publish"),
+            Some("abc".to_string())
+        );
+    }
+
+    #[test]
+    fn access_control_without_mode_used_as_stream_key() {
+        assert_eq!(get_stream_key("#!::u=user,r=abc"), Some("abc".to_string()));
+    }
+
+    #[test]
+    fn access_control_requesting_playback_has_no_stream_key() {
+        assert_eq!(get_stream_key("#!::r=abc,m=request"), None);
+    }
+}
@@ -0,0 +1,352 @@
+//! Converts demuxed MPEG-TS elementary stream data into the packet format used by the rest of
+//! mmids.  H264 video is converted from annex B to length prefixed (AVCC) NAL units with an
+//! `AVCDecoderConfigurationRecord` sequence header, while AAC audio has its ADTS headers stripped
+//! with an `AudioSpecificConfig` sequence header raised when the configuration is first seen.
+
+use super::mpeg_ts::{ElementaryStreamType, PesPacket};
+use crate::codecs::aac::AdtsFrame;
+use crate::VideoTimestamp;
+use bytes::{BufMut, Bytes, BytesMut};
+use std::time::Duration;
+
+const NAL_TYPE_IDR: u8 = 5;
+const NAL_TYPE_SPS: u8 = 7;
+const NAL_TYPE_PPS: u8 = 8;
+const NAL_TYPE_AUD: u8 = 9;
+
+// MPEG-TS timestamps are 33 bits
+const TIMESTAMP_ROLLOVER: u64 = 1 << 33;
+
+#[derive(Debug, PartialEq)]
+pub enum ConvertedMedia {
+    H264 {
+        is_keyframe: bool,
+        is_sequence_header: bool,
+        data: Bytes,
+        timestamp: VideoTimestamp,
+    },
+
+    Aac {
+        is_sequence_header: bool,
+        data: Bytes,
+        timestamp: Duration,
+    },
+}
+
+pub struct MediaConverter {
+    first_timestamp: Option<u64>,
+    avc_sequence_header: Option<Bytes>,
+    aac_sequence_header: Option<Bytes>,
+}
+
+impl MediaConverter {
+    pub fn new() -> Self {
+        MediaConverter {
+            first_timestamp: None,
+            avc_sequence_header: None,
+            aac_sequence_header: None,
+        }
+    }
+
+    pub fn convert(&mut self, pes: PesPacket) -> Vec<ConvertedMedia> {
+        let pts = match pes.pts {
+            Some(pts) => pts,
+            None => return Vec::new(), // Can't place media without a timestamp
+        };
+
+        let dts = pes.dts.unwrap_or(pts);
+        let first_timestamp = *self.first_timestamp.get_or_insert(dts);
+        let pts = to_duration(pts, first_timestamp);
+        let dts = to_duration(dts, first_timestamp);
+
+        match pes.stream_type {
+            ElementaryStreamType::H264 => self.convert_h264(&pes.payload, dts, pts),
+            ElementaryStreamType::AacAdts => self.convert_aac(&pes.payload, pts),
+        }
+    }
+
+    fn convert_h264(
+        &mut self,
+        payload: &[u8],
+        dts: Duration,
+        pts: Duration,
+    ) -> Vec<ConvertedMedia> {
+        let mut results = Vec::new();
+        let mut sps = None;
+        let mut pps = None;
+        let mut is_keyframe = false;
+        let mut frame = BytesMut::new();
+
+        for nal in split_annex_b(payload) {
+            match nal[0] & 0x1f {
+                NAL_TYPE_SPS => sps = Some(nal),
+                NAL_TYPE_PPS => pps = Some(nal),
+                NAL_TYPE_AUD => (),
+                nal_type => {
+                    if nal_type == NAL_TYPE_IDR {
+                        is_keyframe = true;
+                    }
+
+                    frame.put_u32(nal.len() as u32);
+                    frame.extend_from_slice(nal);
+                }
+            }
+        }
+
+        if let (Some(sps), Some(pps)) = (sps, pps) {
+            if let Some(sequence_header) = create_avc_sequence_header(sps, pps) {
+                if self.avc_sequence_header.as_ref() != Some(&sequence_header) {
+                    self.avc_sequence_header = Some(sequence_header.clone());
+                    results.push(ConvertedMedia::H264 {
+                        is_keyframe: true,
+                        is_sequence_header: true,
+                        data: sequence_header,
+                        timestamp: VideoTimestamp::from_durations(dts, pts),
+                    });
+                }
+            }
+        }
+
+        // Frames are not decodable until the parameter sets have been seen
+        if self.avc_sequence_header.is_some() && !frame.is_empty() {
+            results.push(ConvertedMedia::H264 {
+                is_keyframe,
+                is_sequence_header: false,
+                data: frame.freeze(),
+                timestamp: VideoTimestamp::from_durations(dts, pts),
+            });
+        }
+
+        results
+    }
+
+    fn convert_aac(&mut self, mut payload: &[u8], pts: Duration) -> Vec<ConvertedMedia> {
+        let mut results = Vec::new();
+        let mut frame_index = 0;
+        while let Some(frame) = AdtsFrame::parse(payload) {
+            let sequence_header = frame.config.to_bytes();
+            if self.aac_sequence_header.as_ref() != Some(&sequence_header) {
+                self.aac_sequence_header = Some(sequence_header.clone());
+                results.push(ConvertedMedia::Aac {
+                    is_sequence_header: true,
+                    data: sequence_header,
+                    timestamp: pts,
+                });
+            }
+
+            // Each AAC frame contains 1024 samples
+            let offset = frame_index as u64 * 1024 * 1_000_000 / frame.config.sample_rate as u64;
+            results.push(ConvertedMedia::Aac {
+                is_sequence_header: false,
+                data: Bytes::copy_from_slice(frame.data),
+                timestamp: pts + Duration::from_micros(offset),
+            });
+
+            frame_index += 1;
+            payload = &payload[frame.frame_length..];
+        }
+
+        results
+    }
+}
+
+fn to_duration(timestamp: u64, first_timestamp: u64) -> Duration {
+    let ticks = (timestamp + TIMESTAMP_ROLLOVER - first_timestamp) % TIMESTAMP_ROLLOVER;
+    Duration::from_micros(ticks * 100 / 9)
+}
+
+fn split_annex_b(data: &[u8]) -> Vec<&[u8]> {
+    let mut nal_units = Vec::new();
+    let mut nal_start = None;
+    let mut index = 0;
+    while index + 3 <= data.len() {
+        if data[index..index + 3] == [0, 0, 1] {
+            if let Some(start) = nal_start {
+                nal_units.push(trim_trailing_zeros(&data[start..index]));
+            }
+
+            index += 3;
+            nal_start = Some(index);
+        } else {
+            index += 1;
+        }
+    }
+
+    if let Some(start) = nal_start {
+        nal_units.push(&data[start..]);
+    }
+
+    nal_units.retain(|nal| !nal.is_empty());
+    nal_units
+}
+
+// Four byte start codes leave a leading zero at the end of the previous NAL unit
+fn trim_trailing_zeros(nal: &[u8]) -> &[u8] {
+    let end = nal.iter().rposition(|x| *x != 0).map_or(0, |x| x + 1);
+    &nal[..end]
+}
+
+fn create_avc_sequence_header(sps: &[u8], pps: &[u8]) -> Option<Bytes> {
+    if sps.len() < 4 {
+        return None;
+    }
+
+    let mut record = BytesMut::new();
+    record.put_u8(1); // configuration version
+    record.put_u8(sps[1]); // profile
+    record.put_u8(sps[2]); // profile compatibility
+    record.put_u8(sps[3]); // level
+    record.put_u8(0xff); // 4 byte NAL lengths
+    record.put_u8(0xe1); // 1 SPS
+    record.put_u16(sps.len() as u16);
+    record.extend_from_slice(sps);
+    record.put_u8(1); // 1 PPS
+    record.put_u16(pps.len() as u16);
+    record.extend_from_slice(pps);
+
+    Some(record.freeze())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPS: [u8; 6] = [0x67, 0x64, 0x00, 0x1f, 0xac, 0xd9];
+    const PPS: [u8; 4] = [0x68, 0xeb, 0xe3, 0xcb];
+
+    fn h264_pes(nal_units: &[&[u8]], pts: u64) -> PesPacket {
+        let mut payload = Vec::new();
+        for nal in nal_units {
+            payload.extend([0, 0, 0, 1]);
+            payload.extend_from_slice(nal);
+        }
+
+        PesPacket {
+            stream_type: ElementaryStreamType::H264,
+            pts: Some(pts),
+            dts: None,
+            payload: Bytes::from(payload),
+        }
+    }
+
+    fn adts_frame(payload: &[u8]) -> Vec<u8> {
+        // AAC LC, 48khz, stereo
+        let frame_length = payload.len() + 7;
+        let mut frame = vec![
+            0xff,
+            0xf1,
+            0x4c,
+            0x80 | (frame_length >> 11) as u8,
+            (frame_length >> 3) as u8,
+            ((frame_length as u8) << 5) | 0x1f,
+            0xfc,
+        ];
+
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn h264_frames_dropped_until_parameter_sets_seen() {
+        let mut converter = MediaConverter::new();
+        let results = converter.convert(h264_pes(&[&[0x41, 1, 2]], 0));
+
+        assert!(results.is_empty(), "Expected no converted media");
+    }
+
+    #[test]
+    fn h264_keyframe_raises_sequence_header_then_avcc_frame() {
+        let mut converter = MediaConverter::new();
+        let results = converter.convert(h264_pes(&[&[0x09, 0xf0], &SPS, &PPS, &[0x65, 1, 2]], 0));
+
+        let mut expected_header = vec![1, 0x64, 0x00, 0x1f, 0xff, 0xe1, 0, 6];
+        expected_header.extend(SPS);
+        expected_header.extend([1, 0, 4]);
+        expected_header.extend(PPS);
+
+        assert_eq!(
+            results,
+            vec![
+                ConvertedMedia::H264 {
+                    is_keyframe: true,
+                    is_sequence_header: true,
+                    data: Bytes::from(expected_header),
+                    timestamp: VideoTimestamp::from_zero(),
+                },
+                ConvertedMedia::H264 {
+                    is_keyframe: true,
+                    is_sequence_header: false,
+                    data: Bytes::from(vec![0, 0, 0, 3, 0x65, 1, 2]),
+                    timestamp: VideoTimestamp::from_zero(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn h264_sequence_header_not_repeated_when_unchanged() {
+        let mut converter = MediaConverter::new();
+        converter.convert(h264_pes(&[&SPS, &PPS, &[0x65, 1]], 0));
+        let results = converter.convert(h264_pes(&[&SPS, &PPS, &[0x65, 2]], 9000));
+
+        assert_eq!(results.len(), 1, "Unexpected number of results");
+        match &results[0] {
+            ConvertedMedia::H264 {
+                is_sequence_header,
+                timestamp,
+                ..
+            } => {
+                assert!(!is_sequence_header, "Expected non-sequence header");
+                assert_eq!(
+                    timestamp.dts(),
+                    Duration::from_millis(100),
+                    "Unexpected dts"
+                );
+            }
+
+            media => panic!("Unexpected media: {:?}", media),
+        }
+    }
+
+    #[test]
+    fn timestamps_relative_to_first_timestamp_and_handle_rollover() {
+        let first = TIMESTAMP_ROLLOVER - 9000;
+        assert_eq!(to_duration(first, first), Duration::from_secs(0));
+        assert_eq!(to_duration(9000, first), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn adts_frames_converted_to_raw_aac() {
+        let mut payload = adts_frame(&[1, 2, 3]);
+        payload.extend(adts_frame(&[4, 5]));
+
+        let mut converter = MediaConverter::new();
+        let results = converter.convert(PesPacket {
+            stream_type: ElementaryStreamType::AacAdts,
+            pts: Some(90000),
+            dts: None,
+            payload: Bytes::from(payload),
+        });
+
+        assert_eq!(
+            results,
+            vec![
+                ConvertedMedia::Aac {
+                    is_sequence_header: true,
+                    data: Bytes::from(vec![0x11, 0x90]),
+                    timestamp: Duration::from_secs(0),
+                },
+                ConvertedMedia::Aac {
+                    is_sequence_header: false,
+                    data: Bytes::from(vec![1, 2, 3]),
+                    timestamp: Duration::from_secs(0),
+                },
+                ConvertedMedia::Aac {
+                    is_sequence_header: false,
+                    data: Bytes::from(vec![4, 5]),
+                    timestamp: Duration::from_micros(21333),
+                },
+            ]
+        );
+    }
+}
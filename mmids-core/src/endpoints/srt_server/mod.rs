@@ -0,0 +1,125 @@
+//! This endpoint acts as a server for SRT clients that want to publish live streams.  Workflow
+//! steps send a message requesting to allow SRT publishers on a specific port and stream key
+//! combination.  The SRT server endpoint will bind a listener on the requested port and accept
+//! callers whose stream id maps to an active registration.
+//!
+//! The stream key is taken from the SRT stream id the caller connects with.  Both a plain stream
+//! id (e.g. `my_stream`) and the SRT access control syntax (e.g. `#!::r=my_stream,m=publish`) are
+//! supported.  Callers requesting to play (`m=request`) are rejected, as only publishing is
+//! supported at this time.
+//!
+//! Publishers are expected to send MPEG-TS over SRT.  The endpoint demuxes the transport stream
+//! and converts H264 and AAC elementary streams into the same packet format the RTMP endpoint
+//! produces (AVCC formatted video with an `AVCDecoderConfigurationRecord` sequence header, and
+//! raw AAC audio with an `AudioSpecificConfig` sequence header), so later workflow steps can treat
+//! SRT and RTMP media identically.  Other codecs are ignored.
+
+mod actor;
+mod media_converter;
+mod mpeg_ts;
+
+use crate::codecs::{AudioCodec, VideoCodec};
+use crate::endpoints::rtmp_server::{IpRestriction, StreamKeyRegistration};
+use crate::net::ConnectionId;
+use crate::{StreamId, VideoTimestamp};
+use actor::SrtServerEndpointActor;
+use bytes::Bytes;
+use futures::stream::FuturesUnordered;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+/// Starts a new SRT server endpoint, returning a channel that can be used to send requests to it.
+pub fn start_srt_server_endpoint() -> UnboundedSender<SrtEndpointRequest> {
+    let (endpoint_sender, endpoint_receiver) = unbounded_channel();
+
+    let endpoint = SrtServerEndpointActor {
+        futures: FuturesUnordered::new(),
+        ports: HashMap::new(),
+    };
+
+    tokio::spawn(endpoint.run(endpoint_receiver));
+
+    endpoint_sender
+}
+
+/// Operations the SRT server endpoint is being requested to make
+#[derive(Debug)]
+pub enum SrtEndpointRequest {
+    /// Requests the SRT server to allow publishers on the given port and stream key combination
+    ListenForPublishers {
+        /// Port to listen for SRT publisher connections on
+        port: u16,
+
+        /// What stream key (taken from the SRT stream id) publishers should be using
+        stream_key: StreamKeyRegistration,
+
+        /// Channel that the SRT server endpoint should respond with
+        message_channel: UnboundedSender<SrtEndpointPublisherMessage>,
+
+        /// If specified, new media streams being published from this registration will be given
+        /// the stream id specified.  If no id is given than one will be generated.
+        stream_id: Option<StreamId>,
+
+        /// What IP restriction rules should be in place for this registration
+        ip_restrictions: IpRestriction,
+    },
+
+    /// Requests the specified registration should be removed
+    RemoveRegistration {
+        /// Port the removed registrant was listening on
+        port: u16,
+
+        /// The stream key the registrant had registered for
+        stream_key: StreamKeyRegistration,
+    },
+}
+
+/// Messages the SRT server endpoint will send to publisher registrants.
+#[derive(Debug)]
+pub enum SrtEndpointPublisherMessage {
+    /// Notification that the publisher registration failed.  No further messages will be sent
+    /// if this is sent.
+    PublisherRegistrationFailed,
+
+    /// Notification that the publisher registration succeeded.
+    PublisherRegistrationSuccessful,
+
+    /// Notification that a new SRT connection has been made and is publishing media
+    NewPublisherConnected {
+        /// Unique identifier for the SRT connection that's publishing
+        connection_id: ConnectionId,
+
+        /// Unique identifier for the stream.
+        stream_id: StreamId,
+
+        /// Actual stream key that this stream is coming in from.  Mostly used if the registrant
+        /// specified that Any stream key would be allowed.
+        stream_key: String,
+    },
+
+    /// Notification that a publisher has disconnected
+    PublishingStopped {
+        /// Unique identifier for the SRT connection that stopped publishing
+        connection_id: ConnectionId,
+    },
+
+    /// An SRT publisher has sent in new video data
+    NewVideoData {
+        publisher: ConnectionId,
+        codec: VideoCodec,
+        is_keyframe: bool,
+        is_sequence_header: bool,
+        data: Bytes,
+        timestamp: VideoTimestamp,
+    },
+
+    /// An SRT publisher has sent in new audio data
+    NewAudioData {
+        publisher: ConnectionId,
+        codec: AudioCodec,
+        is_sequence_header: bool,
+        data: Bytes,
+        timestamp: Duration,
+    },
+}
@@ -0,0 +1,456 @@
+//! Minimal MPEG-TS demuxer that extracts PES packets for H264 and AAC elementary streams.
+//!
+//! Only the first program of the transport stream is used, and PAT/PMT sections are expected to
+//! fit within a single transport stream packet (which is the case for all common muxers).
+
+use bytes::{Bytes, BytesMut};
+use std::collections::HashMap;
+use tracing::warn;
+
+pub const TS_PACKET_SIZE: usize = 188;
+const SYNC_BYTE: u8 = 0x47;
+const PAT_PID: u16 = 0x0000;
+const STREAM_TYPE_H264: u8 = 0x1b;
+const STREAM_TYPE_AAC_ADTS: u8 = 0x0f;
+
+/// The type of media contained in an elementary stream
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ElementaryStreamType {
+    H264,
+    AacAdts,
+}
+
+/// A fully reassembled PES packet.  Timestamps are in the 90khz MPEG-TS clock.
+#[derive(Debug, PartialEq)]
+pub struct PesPacket {
+    pub stream_type: ElementaryStreamType,
+    pub pts: Option<u64>,
+    pub dts: Option<u64>,
+    pub payload: Bytes,
+}
+
+struct PesBuffer {
+    stream_type: ElementaryStreamType,
+    data: BytesMut,
+}
+
+pub struct TsDemuxer {
+    pmt_pid: Option<u16>,
+    elementary_streams: HashMap<u16, PesBuffer>,
+    pending_bytes: BytesMut,
+}
+
+impl TsDemuxer {
+    pub fn new() -> Self {
+        TsDemuxer {
+            pmt_pid: None,
+            elementary_streams: HashMap::new(),
+            pending_bytes: BytesMut::new(),
+        }
+    }
+
+    /// Feeds raw bytes into the demuxer, returning any PES packets that were completed.  Bytes do
+    /// not need to be aligned to transport stream packet boundaries.
+    pub fn push(&mut self, data: &[u8]) -> Vec<PesPacket> {
+        self.pending_bytes.extend_from_slice(data);
+
+        let mut results = Vec::new();
+        loop {
+            // Resync if we are not at the start of a packet
+            match self.pending_bytes.iter().position(|x| *x == SYNC_BYTE) {
+                Some(0) => (),
+                Some(index) => {
+                    warn!("Skipping {} bytes of non-MPEG-TS data", index);
+                    let _ = self.pending_bytes.split_to(index);
+                }
+
+                None => {
+                    self.pending_bytes.clear();
+                    break;
+                }
+            }
+
+            if self.pending_bytes.len() < TS_PACKET_SIZE {
+                break;
+            }
+
+            let packet = self.pending_bytes.split_to(TS_PACKET_SIZE).freeze();
+            self.handle_packet(&packet, &mut results);
+        }
+
+        results
+    }
+
+    fn handle_packet(&mut self, packet: &[u8], results: &mut Vec<PesPacket>) {
+        let payload_unit_start = packet[1] & 0x40 == 0x40;
+        let pid = ((packet[1] as u16 & 0x1f) << 8) | packet[2] as u16;
+        let adaptation_field_control = (packet[3] >> 4) & 0x03;
+
+        let mut payload_start = 4;
+        if adaptation_field_control & 0x02 == 0x02 {
+            payload_start += 1 + packet[4] as usize;
+        }
+
+        if adaptation_field_control & 0x01 == 0 || payload_start >= packet.len() {
+            return; // No payload
+        }
+
+        let payload = &packet[payload_start..];
+        if pid == PAT_PID {
+            if payload_unit_start {
+                self.handle_pat(payload);
+            }
+
+            return;
+        }
+
+        if Some(pid) == self.pmt_pid {
+            if payload_unit_start {
+                self.handle_pmt(payload);
+            }
+
+            return;
+        }
+
+        let buffer = match self.elementary_streams.get_mut(&pid) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+
+        if payload_unit_start {
+            if let Some(pes) = parse_pes(buffer.stream_type, &buffer.data) {
+                results.push(pes);
+            }
+
+            buffer.data.clear();
+        } else if buffer.data.is_empty() {
+            return; // Haven't seen the start of a PES packet yet
+        }
+
+        buffer.data.extend_from_slice(payload);
+
+        // PES packets with an explicit length can be completed without waiting for the next one
+        if buffer.data.len() >= 6 {
+            let pes_length = ((buffer.data[4] as usize) << 8) | buffer.data[5] as usize;
+            if pes_length > 0 && buffer.data.len() >= pes_length + 6 {
+                if let Some(pes) = parse_pes(buffer.stream_type, &buffer.data) {
+                    results.push(pes);
+                }
+
+                buffer.data.clear();
+            }
+        }
+    }
+
+    fn handle_pat(&mut self, payload: &[u8]) {
+        let section = match get_psi_section(payload) {
+            Some(section) => section,
+            None => return,
+        };
+
+        // Skip transport stream id, version, and section numbers
+        let mut programs = match section.get(5..) {
+            Some(programs) => programs,
+            None => return,
+        };
+
+        while programs.len() >= 4 {
+            let program_number = ((programs[0] as u16) << 8) | programs[1] as u16;
+            let pid = ((programs[2] as u16 & 0x1f) << 8) | programs[3] as u16;
+            if program_number != 0 {
+                self.pmt_pid = Some(pid);
+                return;
+            }
+
+            programs = &programs[4..];
+        }
+    }
+
+    fn handle_pmt(&mut self, payload: &[u8]) {
+        let section = match get_psi_section(payload) {
+            Some(section) => section,
+            None => return,
+        };
+
+        if section.len() < 9 {
+            return;
+        }
+
+        let program_info_length = ((section[7] as usize & 0x0f) << 8) | section[8] as usize;
+        let mut streams = match section.get(9 + program_info_length..) {
+            Some(streams) => streams,
+            None => return,
+        };
+
+        while streams.len() >= 5 {
+            let stream_type = streams[0];
+            let pid = ((streams[1] as u16 & 0x1f) << 8) | streams[2] as u16;
+            let info_length = ((streams[3] as usize & 0x0f) << 8) | streams[4] as usize;
+
+            let stream_type = match stream_type {
+                STREAM_TYPE_H264 => Some(ElementaryStreamType::H264),
+                STREAM_TYPE_AAC_ADTS => Some(ElementaryStreamType::AacAdts),
+                _ => None,
+            };
+
+            if let Some(stream_type) = stream_type {
+                self.elementary_streams
+                    .entry(pid)
+                    .or_insert_with(|| PesBuffer {
+                        stream_type,
+                        data: BytesMut::new(),
+                    });
+            }
+
+            streams = match streams.get(5 + info_length..) {
+                Some(streams) => streams,
+                None => return,
+            };
+        }
+    }
+}
+
+/// Returns the section content after the section length field, minus the trailing CRC
+fn get_psi_section(payload: &[u8]) -> Option<&[u8]> {
+    let pointer = *payload.first()? as usize;
+    let table = payload.get(1 + pointer..)?;
+    if table.len() < 3 {
+        return None;
+    }
+
+    let section_length = ((table[1] as usize & 0x0f) << 8) | table[2] as usize;
+    if section_length < 4 {
+        return None;
+    }
+
+    table.get(3..3 + section_length - 4)
+}
+
+fn parse_pes(stream_type: ElementaryStreamType, data: &[u8]) -> Option<PesPacket> {
+    if data.len() < 9 || data[0..3] != [0x00, 0x00, 0x01] {
+        return None;
+    }
+
+    let pts_dts_flags = data[7] >> 6;
+    let header_length = data[8] as usize;
+    let payload_start = 9 + header_length;
+    if data.len() < payload_start {
+        return None;
+    }
+
+    let pes_length = ((data[4] as usize) << 8) | data[5] as usize;
+    let payload_end = if pes_length > 0 {
+        (pes_length + 6).min(data.len())
+    } else {
+        data.len()
+    };
+
+    // The packet length is remotely provided, so it can claim to end before the header does
+    if payload_end < payload_start {
+        return None;
+    }
+
+    let pts = if pts_dts_flags & 0x02 == 0x02 {
+        data.get(9..14).map(read_timestamp)
+    } else {
+        None
+    };
+
+    let dts = if pts_dts_flags == 0x03 {
+        data.get(14..19).map(read_timestamp)
+    } else {
+        None
+    };
+
+    Some(PesPacket {
+        stream_type,
+        pts,
+        dts,
+        payload: Bytes::copy_from_slice(&data[payload_start..payload_end]),
+    })
+}
+
+fn read_timestamp(bytes: &[u8]) -> u64 {
+    (((bytes[0] as u64 >> 1) & 0x07) << 30)
+        | ((bytes[1] as u64) << 22)
+        | ((bytes[2] as u64 >> 1) << 15)
+        | ((bytes[3] as u64) << 7)
+        | (bytes[4] as u64 >> 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PMT_PID: u16 = 0x1000;
+    const VIDEO_PID: u16 = 0x0100;
+
+    fn create_ts_packet(pid: u16, payload_unit_start: bool, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![
+            SYNC_BYTE,
+            ((pid >> 8) as u8 & 0x1f) | if payload_unit_start { 0x40 } else { 0 },
+            pid as u8,
+        ];
+
+        let stuffing = TS_PACKET_SIZE - 4 - payload.len();
+        if stuffing > 0 {
+            // Adaptation field followed by the payload
+            packet.push(0x30);
+            packet.push((stuffing - 1) as u8);
+            if stuffing > 1 {
+                packet.push(0x00);
+                packet.resize(packet.len() + stuffing - 2, 0xff);
+            }
+        } else {
+            packet.push(0x10);
+        }
+
+        packet.extend_from_slice(payload);
+        assert_eq!(packet.len(), TS_PACKET_SIZE, "Invalid test packet size");
+        packet
+    }
+
+    fn create_pat() -> Vec<u8> {
+        let mut section = vec![0x00, 0x00, 0xb0, 0x0d, 0x00, 0x01, 0xc1, 0x00, 0x00];
+        section.extend([0x00, 0x01, 0xe0 | (PMT_PID >> 8) as u8, PMT_PID as u8]);
+        section.extend([0, 0, 0, 0]); // crc
+        create_ts_packet(PAT_PID, true, &section)
+    }
+
+    fn create_pmt() -> Vec<u8> {
+        let mut section = vec![0x00, 0x02, 0xb0, 0x12, 0x00, 0x01, 0xc1, 0x00, 0x00];
+        section.extend([0xe0 | (VIDEO_PID >> 8) as u8, VIDEO_PID as u8, 0xf0, 0x00]);
+        section.extend([
+            STREAM_TYPE_H264,
+            0xe0 | (VIDEO_PID >> 8) as u8,
+            VIDEO_PID as u8,
+            0xf0,
+            0x00,
+        ]);
+        section.extend([0, 0, 0, 0]); // crc
+        create_ts_packet(PMT_PID, true, &section)
+    }
+
+    fn encode_timestamp(marker: u8, timestamp: u64) -> [u8; 5] {
+        [
+            (marker << 4) | (((timestamp >> 30) as u8 & 0x07) << 1) | 1,
+            (timestamp >> 22) as u8,
+            (((timestamp >> 15) as u8) << 1) | 1,
+            (timestamp >> 7) as u8,
+            ((timestamp as u8) << 1) | 1,
+        ]
+    }
+
+    fn create_pes(pts: u64, dts: u64, payload: &[u8]) -> Vec<u8> {
+        let mut pes = vec![0x00, 0x00, 0x01, 0xe0, 0x00, 0x00, 0x80, 0xc0, 10];
+        pes.extend(encode_timestamp(0x03, pts));
+        pes.extend(encode_timestamp(0x01, dts));
+        pes.extend_from_slice(payload);
+        pes
+    }
+
+    #[test]
+    fn can_read_pes_timestamps() {
+        let timestamp = 0x1_2345_6789;
+        let encoded = encode_timestamp(0x02, timestamp);
+
+        assert_eq!(read_timestamp(&encoded), timestamp);
+    }
+
+    #[test]
+    fn pes_with_length_shorter_than_header_is_rejected() {
+        let mut pes = create_pes(1000, 900, &[1, 2, 3]);
+        pes[5] = 4; // Claims the packet ends in the middle of the header
+
+        assert!(parse_pes(ElementaryStreamType::H264, &pes).is_none());
+    }
+
+    #[test]
+    fn pes_truncated_before_end_of_header_is_rejected() {
+        let pes = create_pes(1000, 900, &[1, 2, 3]);
+
+        assert!(parse_pes(ElementaryStreamType::H264, &pes[..12]).is_none());
+    }
+
+    #[test]
+    fn video_pes_returned_when_next_pes_starts() {
+        let mut demuxer = TsDemuxer::new();
+        assert!(demuxer.push(&create_pat()).is_empty());
+        assert!(demuxer.push(&create_pmt()).is_empty());
+
+        let pes = create_pes(9000, 6000, &[1, 2, 3, 4]);
+        let results = demuxer.push(&create_ts_packet(VIDEO_PID, true, &pes));
+        assert!(
+            results.is_empty(),
+            "Expected no pes packet until the next one starts"
+        );
+
+        let pes = create_pes(12000, 9000, &[5, 6, 7, 8]);
+        let results = demuxer.push(&create_ts_packet(VIDEO_PID, true, &pes));
+        assert_eq!(
+            results,
+            vec![PesPacket {
+                stream_type: ElementaryStreamType::H264,
+                pts: Some(9000),
+                dts: Some(6000),
+                payload: Bytes::from(vec![1, 2, 3, 4]),
+            }]
+        );
+    }
+
+    #[test]
+    fn pes_split_across_ts_packets_is_reassembled() {
+        let mut demuxer = TsDemuxer::new();
+        demuxer.push(&create_pat());
+        demuxer.push(&create_pmt());
+
+        let payload = (0..300).map(|x| x as u8).collect::<Vec<_>>();
+        let pes = create_pes(9000, 9000, &payload);
+        let (first, second) = pes.split_at(TS_PACKET_SIZE - 4);
+
+        demuxer.push(&create_ts_packet(VIDEO_PID, true, first));
+        demuxer.push(&create_ts_packet(VIDEO_PID, false, second));
+        let results = demuxer.push(&create_ts_packet(VIDEO_PID, true, &create_pes(0, 0, &[])));
+
+        assert_eq!(results.len(), 1, "Unexpected number of pes packets");
+        assert_eq!(results[0].payload, Bytes::from(payload));
+    }
+
+    #[test]
+    fn packets_not_aligned_to_boundaries_are_handled() {
+        let mut stream = Vec::new();
+        stream.extend(create_pat());
+        stream.extend(create_pmt());
+        stream.extend(create_ts_packet(
+            VIDEO_PID,
+            true,
+            &create_pes(9000, 9000, &[1, 2, 3]),
+        ));
+        stream.extend(create_ts_packet(
+            VIDEO_PID,
+            true,
+            &create_pes(12000, 12000, &[4]),
+        ));
+
+        let mut demuxer = TsDemuxer::new();
+        let mut results = Vec::new();
+        for chunk in stream.chunks(100) {
+            results.extend(demuxer.push(chunk));
+        }
+
+        assert_eq!(results.len(), 1, "Unexpected number of pes packets");
+        assert_eq!(results[0].payload, Bytes::from(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn packets_for_unknown_pids_are_ignored() {
+        let mut demuxer = TsDemuxer::new();
+        demuxer.push(&create_pat());
+        demuxer.push(&create_pmt());
+
+        demuxer.push(&create_ts_packet(0x200, true, &create_pes(0, 0, &[1])));
+        let results = demuxer.push(&create_ts_packet(0x200, true, &create_pes(0, 0, &[2])));
+
+        assert!(results.is_empty(), "Expected no pes packets");
+    }
+}
@@ -1,7 +1,9 @@
 //! The event hub is a central actor that receives events from all type of mmids subsystems and
 //! allows them to be published to interested subscribers.
 
+use crate::workflows::definitions::WorkflowStepType;
 use crate::workflows::manager::WorkflowManagerRequest;
+use crate::workflows::steps::StepStatus;
 use crate::workflows::WorkflowRequest;
 use futures::future::BoxFuture;
 use futures::stream::FuturesUnordered;
@@ -16,6 +18,7 @@ use tracing::{info, instrument, warn};
 pub enum PublishEventRequest {
     WorkflowStartedOrStopped(WorkflowStartedOrStoppedEvent),
     WorkflowManagerEvent(WorkflowManagerEvent),
+    WorkflowStepEvent(WorkflowStepEvent),
 }
 
 /// A request to subscribe to a category of events
@@ -28,6 +31,10 @@ pub enum SubscriptionRequest {
     WorkflowManagerEvents {
         channel: UnboundedSender<WorkflowManagerEvent>,
     },
+
+    WorkflowStepEvents {
+        channel: UnboundedSender<WorkflowStepEvent>,
+    },
 }
 
 /// Events relating to workflows being started or stopped
@@ -51,6 +58,27 @@ pub enum WorkflowManagerEvent {
     },
 }
 
+/// Event raised when an individual step of a workflow changes its lifecycle state
+#[derive(Clone, Debug)]
+pub struct WorkflowStepEvent {
+    pub workflow_name: String,
+    pub step_id: u64,
+    pub step_type: WorkflowStepType,
+    pub transition: WorkflowStepTransition,
+}
+
+/// The lifecycle change a workflow step went through
+#[derive(Clone, Debug, PartialEq)]
+pub enum WorkflowStepTransition {
+    /// The step's status has changed to the specified status.  The first event for a step is
+    /// raised with the status the step had right after it was created.
+    StatusChanged(StepStatus),
+
+    /// The step is no longer part of the workflow, either due to a definition update removing
+    /// it or the workflow being stopped.
+    Removed,
+}
+
 pub fn start_event_hub() -> (
     UnboundedSender<PublishEventRequest>,
     UnboundedSender<SubscriptionRequest>,
@@ -70,6 +98,7 @@ enum FutureResult {
     NewSubscriptionRequest(SubscriptionRequest, UnboundedReceiver<SubscriptionRequest>),
     WorkflowStartStopSubscriberGone(usize),
     WorkflowManagerSubscriberGone(usize),
+    WorkflowStepSubscriberGone(usize),
 }
 
 struct Actor {
@@ -78,6 +107,7 @@ struct Actor {
     active_subscriber_ids: HashSet<usize>,
     workflow_start_stop_subscribers: HashMap<usize, UnboundedSender<WorkflowStartedOrStoppedEvent>>,
     workflow_manager_subscribers: HashMap<usize, UnboundedSender<WorkflowManagerEvent>>,
+    workflow_step_subscribers: HashMap<usize, UnboundedSender<WorkflowStepEvent>>,
     new_subscribers_can_join: bool,
     active_workflows: HashMap<String, UnboundedSender<WorkflowRequest>>,
     active_workflow_manager: Option<UnboundedSender<WorkflowManagerRequest>>,
@@ -98,6 +128,7 @@ impl Actor {
             active_subscriber_ids: HashSet::new(),
             workflow_start_stop_subscribers: HashMap::new(),
             workflow_manager_subscribers: HashMap::new(),
+            workflow_step_subscribers: HashMap::new(),
             new_subscribers_can_join: true,
             active_workflows: HashMap::new(),
             active_workflow_manager: None,
@@ -134,6 +165,11 @@ impl Actor {
                     self.workflow_manager_subscribers.remove(&id);
                 }
 
+                FutureResult::WorkflowStepSubscriberGone(id) => {
+                    self.active_subscriber_ids.remove(&id);
+                    self.workflow_step_subscribers.remove(&id);
+                }
+
                 FutureResult::NewPublishRequest(request, receiver) => {
                     self.futures
                         .push(wait_for_publish_request(receiver).boxed());
@@ -187,6 +223,12 @@ impl Actor {
                     }
                 }
             }
+
+            PublishEventRequest::WorkflowStepEvent(event) => {
+                for subscriber in self.workflow_step_subscribers.values() {
+                    let _ = subscriber.send(event.clone());
+                }
+            }
         }
     }
 
@@ -231,6 +273,12 @@ impl Actor {
                 self.futures
                     .push(notify_workflow_manager_subscriber_gone(id.0, channel).boxed());
             }
+
+            SubscriptionRequest::WorkflowStepEvents { channel } => {
+                self.workflow_step_subscribers.insert(id.0, channel.clone());
+                self.futures
+                    .push(notify_workflow_step_subscriber_gone(id.0, channel).boxed());
+            }
         }
     }
 
@@ -273,6 +321,14 @@ async fn notify_workflow_manager_subscriber_gone(
     FutureResult::WorkflowManagerSubscriberGone(id)
 }
 
+async fn notify_workflow_step_subscriber_gone(
+    id: usize,
+    sender: UnboundedSender<WorkflowStepEvent>,
+) -> FutureResult {
+    sender.closed().await;
+    FutureResult::WorkflowStepSubscriberGone(id)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -437,4 +493,66 @@ mod tests {
             WorkflowManagerEvent::WorkflowManagerRegistered { channel: _ } => (),
         }
     }
+
+    #[tokio::test]
+    async fn can_receive_workflow_step_events() {
+        let (publish_channel, subscribe_channel) = start_event_hub();
+        let (subscriber_sender, mut subscriber_receiver) = unbounded_channel();
+
+        subscribe_channel
+            .send(SubscriptionRequest::WorkflowStepEvents {
+                channel: subscriber_sender,
+            })
+            .expect("Failed to send subscription request");
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        publish_channel
+            .send(PublishEventRequest::WorkflowStepEvent(WorkflowStepEvent {
+                workflow_name: "test".to_string(),
+                step_id: 5,
+                step_type: WorkflowStepType("step".to_string()),
+                transition: WorkflowStepTransition::StatusChanged(StepStatus::Active),
+            }))
+            .expect("Failed to send publish request");
+
+        let response = test_utils::expect_mpsc_response(&mut subscriber_receiver).await;
+        assert_eq!(&response.workflow_name, "test", "Unexpected workflow name");
+        assert_eq!(response.step_id, 5, "Unexpected step id");
+        assert_eq!(
+            response.step_type,
+            WorkflowStepType("step".to_string()),
+            "Unexpected step type"
+        );
+        assert_eq!(
+            response.transition,
+            WorkflowStepTransition::StatusChanged(StepStatus::Active),
+            "Unexpected transition"
+        );
+    }
+
+    #[tokio::test]
+    async fn no_step_events_received_when_published_prior_to_subscription() {
+        let (publish_channel, subscribe_channel) = start_event_hub();
+        let (subscriber_sender, mut subscriber_receiver) = unbounded_channel();
+
+        publish_channel
+            .send(PublishEventRequest::WorkflowStepEvent(WorkflowStepEvent {
+                workflow_name: "test".to_string(),
+                step_id: 5,
+                step_type: WorkflowStepType("step".to_string()),
+                transition: WorkflowStepTransition::Removed,
+            }))
+            .expect("Failed to send publish request");
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        subscribe_channel
+            .send(SubscriptionRequest::WorkflowStepEvents {
+                channel: subscriber_sender,
+            })
+            .expect("Failed to send subscription request");
+
+        test_utils::expect_mpsc_timeout(&mut subscriber_receiver).await;
+    }
 }
@@ -0,0 +1,65 @@
+//! Contains the handler for forcibly disconnecting a connection from the RTMP endpoint
+
+use crate::endpoints::rtmp_server::RtmpEndpointRequest;
+use crate::http_api::routing::RouteHandler;
+use crate::net::ConnectionId;
+use async_trait::async_trait;
+use hyper::{Body, Error, Request, Response, StatusCode};
+use std::collections::HashMap;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{error, info};
+
+/// Handles HTTP requests to disconnect a single RTMP publisher or watcher (e.g. for moderation).
+/// It requires a single path parameter named `connection` that contains the id of the connection
+/// to disconnect.  A 202 Accepted is returned once the request has been passed to the RTMP
+/// endpoint, even if no connection exists with that id.
+pub struct DisconnectRtmpConnectionHandler {
+    rtmp_endpoint: UnboundedSender<RtmpEndpointRequest>,
+}
+
+impl DisconnectRtmpConnectionHandler {
+    pub fn new(rtmp_endpoint: UnboundedSender<RtmpEndpointRequest>) -> Self {
+        DisconnectRtmpConnectionHandler { rtmp_endpoint }
+    }
+}
+
+#[async_trait]
+impl RouteHandler for DisconnectRtmpConnectionHandler {
+    async fn execute(
+        &self,
+        _request: &mut Request<Body>,
+        path_parameters: HashMap<String, String>,
+        request_id: String,
+    ) -> Result<Response<Body>, Error> {
+        let connection_id = match path_parameters.get("connection") {
+            Some(value) => ConnectionId(value.to_string()),
+            None => {
+                error!("Disconnect rtmp connection endpoint called without a 'connection' path parameter");
+                let mut response = Response::default();
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+
+                return Ok(response);
+            }
+        };
+
+        info!(
+            request_id = %request_id,
+            connection_id = %connection_id,
+            "Disconnect requested for rtmp connection {}", connection_id
+        );
+
+        let message = RtmpEndpointRequest::DisconnectConnection { connection_id };
+        if self.rtmp_endpoint.send(message).is_err() {
+            error!("Rtmp endpoint is no longer operational");
+            let mut response = Response::default();
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+
+            return Ok(response);
+        }
+
+        let mut response = Response::default();
+        *response.status_mut() = StatusCode::ACCEPTED;
+
+        Ok(response)
+    }
+}
@@ -0,0 +1,450 @@
+//! Contains the handler for exposing metrics in the Prometheus text exposition format
+
+use crate::endpoints::rtmp_server::{
+    RegistrationType, RtmpEndpointRequest, RtmpRegistrationStatistics, StreamKeyRegistration,
+};
+use crate::http_api::handlers::query_workflow_manager;
+use crate::http_api::routing::RouteHandler;
+use crate::workflows::manager::{WorkflowManagerRequest, WorkflowManagerRequestOperation};
+use crate::workflows::steps::stream_stats::StreamStatistics;
+use crate::workflows::{WorkflowState, WorkflowStatus};
+use async_trait::async_trait;
+use hyper::header::HeaderValue;
+use hyper::{Body, Error, Request, Response, StatusCode};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot::{channel, Receiver, Sender};
+use tokio::time::timeout;
+use tracing::error;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// HTTP handler which renders workflow, stream, and RTMP metrics in the Prometheus text
+/// exposition format, so it can be used as a Prometheus scrape target.
+pub struct GetMetricsHandler {
+    manager: UnboundedSender<WorkflowManagerRequest>,
+    rtmp_endpoint: UnboundedSender<RtmpEndpointRequest>,
+}
+
+impl GetMetricsHandler {
+    pub fn new(
+        manager: UnboundedSender<WorkflowManagerRequest>,
+        rtmp_endpoint: UnboundedSender<RtmpEndpointRequest>,
+    ) -> Self {
+        GetMetricsHandler {
+            manager,
+            rtmp_endpoint,
+        }
+    }
+
+    async fn query_manager<T>(
+        &self,
+        request_id: &str,
+        operation: impl FnOnce(Sender<T>) -> WorkflowManagerRequestOperation,
+    ) -> Result<T, Response<Body>> {
+        query_workflow_manager(
+            &self.manager,
+            request_id.to_string(),
+            REQUEST_TIMEOUT,
+            operation,
+        )
+        .await
+    }
+
+    async fn get_workflows(&self, request_id: &str) -> Result<Vec<WorkflowState>, Response<Body>> {
+        let running_workflows = self
+            .query_manager(request_id, |response_channel| {
+                WorkflowManagerRequestOperation::GetRunningWorkflows { response_channel }
+            })
+            .await?;
+
+        let mut workflows = Vec::new();
+        for workflow in running_workflows {
+            let details = self
+                .query_manager(request_id, |response_channel| {
+                    WorkflowManagerRequestOperation::GetWorkflowDetails {
+                        name: workflow.name,
+                        response_channel,
+                    }
+                })
+                .await?;
+
+            // Workflows may have stopped since they were listed
+            if let Some(details) = details {
+                workflows.push(details);
+            }
+        }
+
+        Ok(workflows)
+    }
+
+    async fn get_rtmp_statistics(&self) -> Option<Vec<RtmpRegistrationStatistics>> {
+        let (sender, receiver) = channel();
+        let request = RtmpEndpointRequest::GetStatistics {
+            response_channel: sender,
+        };
+
+        if self.rtmp_endpoint.send(request).is_err() {
+            error!("Rtmp endpoint is no longer operational");
+            return None;
+        }
+
+        wait_for_response(receiver, "Rtmp endpoint")
+            .await
+            .map(|statistics| statistics.registrations)
+    }
+}
+
+#[async_trait]
+impl RouteHandler for GetMetricsHandler {
+    async fn execute(
+        &self,
+        _request: &mut Request<Body>,
+        _path_parameters: HashMap<String, String>,
+        request_id: String,
+    ) -> Result<Response<Body>, Error> {
+        let workflows = match self.get_workflows(&request_id).await {
+            Ok(workflows) => workflows,
+            Err(response) => return Ok(response),
+        };
+
+        let streams = self
+            .query_manager(&request_id, |response_channel| {
+                WorkflowManagerRequestOperation::GetStreamStatistics { response_channel }
+            })
+            .await;
+
+        let streams = match streams {
+            Ok(streams) => streams,
+            Err(response) => return Ok(response),
+        };
+
+        let rtmp_registrations = match self.get_rtmp_statistics().await {
+            Some(registrations) => registrations,
+            None => {
+                let mut response = Response::default();
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+
+                return Ok(response);
+            }
+        };
+
+        let metrics = render_metrics(&workflows, &streams, &rtmp_registrations);
+        let mut response = Response::new(Body::from(metrics));
+        let headers = response.headers_mut();
+        headers.insert(
+            hyper::http::header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; version=0.0.4"),
+        );
+
+        Ok(response)
+    }
+}
+
+async fn wait_for_response<T>(receiver: Receiver<T>, source: &str) -> Option<T> {
+    match timeout(REQUEST_TIMEOUT, receiver).await {
+        Ok(Ok(response)) => Some(response),
+        Ok(Err(_)) => {
+            error!("{} is no longer operational", source);
+            None
+        }
+
+        Err(_) => {
+            error!("{} metrics request timed out", source);
+            None
+        }
+    }
+}
+
+fn render_metrics(
+    workflows: &[WorkflowState],
+    streams: &[StreamStatistics],
+    rtmp_registrations: &[RtmpRegistrationStatistics],
+) -> String {
+    let mut output = String::new();
+
+    write_header(
+        &mut output,
+        "mmids_workflows_active",
+        "gauge",
+        "Number of workflows currently running",
+    );
+    write_metric(
+        &mut output,
+        "mmids_workflows_active",
+        &[],
+        workflows.len() as f64,
+    );
+
+    write_header(
+        &mut output,
+        "mmids_workflow_errored",
+        "gauge",
+        "Whether the workflow is in an errored state",
+    );
+    for workflow in workflows {
+        let is_errored = match workflow.status {
+            WorkflowStatus::Running => 0.0,
+            WorkflowStatus::Error { .. } => 1.0,
+        };
+
+        let labels = [("workflow", workflow.name.as_str())];
+        write_metric(&mut output, "mmids_workflow_errored", &labels, is_errored);
+    }
+
+    write_header(
+        &mut output,
+        "mmids_workflow_active_streams",
+        "gauge",
+        "Number of streams currently flowing through the workflow",
+    );
+    for workflow in workflows {
+        let labels = [("workflow", workflow.name.as_str())];
+        write_metric(
+            &mut output,
+            "mmids_workflow_active_streams",
+            &labels,
+            workflow.active_stream_count as f64,
+        );
+    }
+
+    write_header(
+        &mut output,
+        "mmids_workflow_steps",
+        "gauge",
+        "Number of active and pending steps in the workflow",
+    );
+    for workflow in workflows {
+        for (state, steps) in [
+            ("active", &workflow.active_steps),
+            ("pending", &workflow.pending_steps),
+        ] {
+            let labels = [("workflow", workflow.name.as_str()), ("state", state)];
+            write_metric(
+                &mut output,
+                "mmids_workflow_steps",
+                &labels,
+                steps.len() as f64,
+            );
+        }
+    }
+
+    write_header(
+        &mut output,
+        "mmids_workflow_media_bytes_total",
+        "counter",
+        "Total bytes of media that have originated within the workflow",
+    );
+    for workflow in workflows {
+        for (media, bytes) in [
+            ("video", workflow.video_bytes),
+            ("audio", workflow.audio_bytes),
+        ] {
+            let labels = [("workflow", workflow.name.as_str()), ("media", media)];
+            write_metric(
+                &mut output,
+                "mmids_workflow_media_bytes_total",
+                &labels,
+                bytes as f64,
+            );
+        }
+    }
+
+    write_header(
+        &mut output,
+        "mmids_stream_bitrate_kbps",
+        "gauge",
+        "Bitrate of a stream as measured by a stream_stats step",
+    );
+    for stream in streams {
+        let step_id = stream.step_id.to_string();
+        let stream_name = stream.stream_name.clone().unwrap_or_default();
+        for (media, bitrate) in [
+            ("video", stream.video_bitrate_kbps),
+            ("audio", stream.audio_bitrate_kbps),
+        ] {
+            let labels = [
+                ("step_id", step_id.as_str()),
+                ("stream_id", stream.stream_id.0.as_str()),
+                ("stream_name", stream_name.as_str()),
+                ("media", media),
+            ];
+
+            write_metric(
+                &mut output,
+                "mmids_stream_bitrate_kbps",
+                &labels,
+                bitrate as f64,
+            );
+        }
+    }
+
+    write_header(
+        &mut output,
+        "mmids_rtmp_connections",
+        "gauge",
+        "Number of RTMP publishers or watchers connected to a registration",
+    );
+    for registration in rtmp_registrations {
+        let labels = RtmpLabels::new(registration);
+        write_metric(
+            &mut output,
+            "mmids_rtmp_connections",
+            &labels.as_labels(),
+            registration.active_connections as f64,
+        );
+    }
+
+    write_header(
+        &mut output,
+        "mmids_rtmp_media_bytes_total",
+        "counter",
+        "Total bytes of media received from publishers, or sent to watchers, of a registration",
+    );
+    for registration in rtmp_registrations {
+        let labels = RtmpLabels::new(registration);
+        write_metric(
+            &mut output,
+            "mmids_rtmp_media_bytes_total",
+            &labels.as_labels(),
+            registration.bytes_transferred as f64,
+        );
+    }
+
+    output
+}
+
+struct RtmpLabels<'a> {
+    registration_type: &'static str,
+    port: String,
+    rtmp_app: &'a str,
+    stream_key: &'a str,
+}
+
+impl<'a> RtmpLabels<'a> {
+    fn new(registration: &'a RtmpRegistrationStatistics) -> Self {
+        RtmpLabels {
+            registration_type: match registration.registration_type {
+                RegistrationType::Publisher => "publisher",
+                RegistrationType::Watcher => "watcher",
+            },
+            port: registration.port.to_string(),
+            rtmp_app: &registration.rtmp_app,
+            stream_key: match &registration.stream_key {
+                StreamKeyRegistration::Any => "*",
+                StreamKeyRegistration::Exact(key) => key,
+                StreamKeyRegistration::Pattern(pattern) => pattern,
+            },
+        }
+    }
+
+    fn as_labels(&self) -> [(&str, &str); 4] {
+        [
+            ("type", self.registration_type),
+            ("port", &self.port),
+            ("rtmp_app", self.rtmp_app),
+            ("stream_key", self.stream_key),
+        ]
+    }
+}
+
+fn write_header(output: &mut String, name: &str, metric_type: &str, help: &str) {
+    let _ = writeln!(output, "# HELP {} {}", name, help);
+    let _ = writeln!(output, "# TYPE {} {}", name, metric_type);
+}
+
+fn write_metric(output: &mut String, name: &str, labels: &[(&str, &str)], value: f64) {
+    output.push_str(name);
+    if !labels.is_empty() {
+        let labels = labels
+            .iter()
+            .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+            .collect::<Vec<_>>();
+
+        let _ = write!(output, "{{{}}}", labels.join(","));
+    }
+
+    let _ = writeln!(output, " {}", value);
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflows::definitions::WorkflowDefinition;
+
+    #[test]
+    fn metric_without_labels_rendered() {
+        let mut output = String::new();
+        write_metric(&mut output, "test_metric", &[], 5.0);
+
+        assert_eq!(output, "test_metric 5\n");
+    }
+
+    #[test]
+    fn metric_with_labels_rendered() {
+        let mut output = String::new();
+        write_metric(&mut output, "test_metric", &[("a", "b"), ("c", "d")], 1.5);
+
+        assert_eq!(output, "test_metric{a=\"b\",c=\"d\"} 1.5\n");
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(escape_label_value("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[test]
+    fn workflow_metrics_rendered() {
+        let workflow = WorkflowState {
+            name: "abc".to_string(),
+            status: WorkflowStatus::Running,
+            active_steps: Vec::new(),
+            pending_steps: Vec::new(),
+            video_bytes: 10,
+            audio_bytes: 20,
+            active_stream_count: 2,
+            active_streams: Vec::new(),
+            definition: WorkflowDefinition {
+                name: "abc".to_string(),
+                routed_by_reactor: false,
+                steps: Vec::new(),
+            },
+        };
+
+        let output = render_metrics(&[workflow], &[], &[]);
+
+        assert!(output.contains("mmids_workflows_active 1\n"));
+        assert!(output.contains("mmids_workflow_active_streams{workflow=\"abc\"} 2\n"));
+        assert!(output
+            .contains("mmids_workflow_media_bytes_total{workflow=\"abc\",media=\"video\"} 10\n"));
+        assert!(output
+            .contains("mmids_workflow_media_bytes_total{workflow=\"abc\",media=\"audio\"} 20\n"));
+    }
+
+    #[test]
+    fn rtmp_metrics_rendered() {
+        let registration = RtmpRegistrationStatistics {
+            registration_type: RegistrationType::Watcher,
+            port: 1935,
+            rtmp_app: "app".to_string(),
+            stream_key: StreamKeyRegistration::Any,
+            active_connections: 3,
+            bytes_transferred: 100,
+        };
+
+        let output = render_metrics(&[], &[], &[registration]);
+
+        assert!(output.contains(
+            "mmids_rtmp_connections{type=\"watcher\",port=\"1935\",rtmp_app=\"app\",stream_key=\"*\"} 3\n"
+        ));
+    }
+}
@@ -0,0 +1,131 @@
+//! Contains the handler for getting connection statistics from the RTMP endpoint
+
+use crate::endpoints::rtmp_server::{
+    RegistrationType, RtmpEndpointRequest, RtmpRegistrationStatistics, StreamKeyRegistration,
+};
+use crate::http_api::routing::RouteHandler;
+use async_trait::async_trait;
+use hyper::header::HeaderValue;
+use hyper::{Body, Error, Request, Response, StatusCode};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot::channel;
+use tokio::time::timeout;
+use tracing::error;
+
+/// HTTP handler which provides how many publishers and watchers are connected to each RTMP
+/// registration, and how much media has been transferred through them.
+pub struct GetRtmpStatisticsHandler {
+    rtmp_endpoint: UnboundedSender<RtmpEndpointRequest>,
+}
+
+/// Defines what data the API will return for each RTMP registration
+#[derive(Serialize)]
+pub struct RtmpRegistrationStatisticsResponse {
+    registration_type: String,
+    port: u16,
+    rtmp_app: String,
+    stream_key: String,
+    active_connections: usize,
+    bytes_transferred: u64,
+}
+
+impl GetRtmpStatisticsHandler {
+    pub fn new(rtmp_endpoint: UnboundedSender<RtmpEndpointRequest>) -> Self {
+        GetRtmpStatisticsHandler { rtmp_endpoint }
+    }
+}
+
+#[async_trait]
+impl RouteHandler for GetRtmpStatisticsHandler {
+    async fn execute(
+        &self,
+        _request: &mut Request<Body>,
+        _path_parameters: HashMap<String, String>,
+        _request_id: String,
+    ) -> Result<Response<Body>, Error> {
+        let (response_sender, response_receiver) = channel();
+        let message = RtmpEndpointRequest::GetStatistics {
+            response_channel: response_sender,
+        };
+
+        match self.rtmp_endpoint.send(message) {
+            Ok(_) => (),
+            Err(_) => {
+                error!("Rtmp endpoint is no longer operational");
+                let mut response = Response::default();
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+
+                return Ok(response);
+            }
+        };
+
+        let statistics = match timeout(Duration::from_secs(10), response_receiver).await {
+            Ok(Ok(statistics)) => statistics,
+
+            Ok(Err(_)) => {
+                error!("Rtmp endpoint is no longer operational");
+                let mut response = Response::default();
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+
+                return Ok(response);
+            }
+
+            Err(_) => {
+                error!("Get rtmp statistics request timed out");
+                let mut response = Response::default();
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+
+                return Ok(response);
+            }
+        };
+
+        let response = statistics
+            .registrations
+            .into_iter()
+            .map(|x| RtmpRegistrationStatisticsResponse::from(x))
+            .collect::<Vec<_>>();
+
+        let json = match serde_json::to_string_pretty(&response) {
+            Ok(json) => json,
+            Err(error) => {
+                error!("Failed to serialize rtmp statistics to json: {:?}", error);
+                let mut response = Response::default();
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+
+                return Ok(response);
+            }
+        };
+
+        let mut response = Response::new(Body::from(json));
+        let headers = response.headers_mut();
+        headers.insert(
+            hyper::http::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+
+        Ok(response)
+    }
+}
+
+impl From<RtmpRegistrationStatistics> for RtmpRegistrationStatisticsResponse {
+    fn from(statistics: RtmpRegistrationStatistics) -> Self {
+        RtmpRegistrationStatisticsResponse {
+            registration_type: match statistics.registration_type {
+                RegistrationType::Publisher => "Publisher".to_string(),
+                RegistrationType::Watcher => "Watcher".to_string(),
+            },
+            port: statistics.port,
+            rtmp_app: statistics.rtmp_app,
+            stream_key: match statistics.stream_key {
+                StreamKeyRegistration::Any => "*".to_string(),
+                StreamKeyRegistration::Exact(key) => key,
+                StreamKeyRegistration::Pattern(pattern) => pattern,
+            },
+            active_connections: statistics.active_connections,
+            bytes_transferred: statistics.bytes_transferred,
+        }
+    }
+}
@@ -1,9 +1,13 @@
 //! Contains the handler for getting details about a running workflow
 
+use crate::http_api::handlers::query_workflow_manager;
 use crate::http_api::routing::RouteHandler;
+use crate::workflows::definitions::WorkflowDefinition;
 use crate::workflows::manager::{WorkflowManagerRequest, WorkflowManagerRequestOperation};
 use crate::workflows::steps::StepStatus;
-use crate::workflows::{WorkflowState, WorkflowStatus, WorkflowStepState};
+use crate::workflows::{
+    StepExecutionTiming, WorkflowState, WorkflowStatus, WorkflowStepState, WorkflowStreamState,
+};
 use async_trait::async_trait;
 use hyper::http::HeaderValue;
 use hyper::{Body, Error, Request, Response, StatusCode};
@@ -11,8 +15,6 @@ use serde::Serialize;
 use std::collections::HashMap;
 use std::time::Duration;
 use tokio::sync::mpsc::UnboundedSender;
-use tokio::sync::oneshot::channel;
-use tokio::time::timeout;
 use tracing::error;
 
 /// Handles HTTP requests to get details for a specific workflow.  It requires a single path
@@ -28,6 +30,19 @@ pub struct WorkflowStateResponse {
     status: String,
     active_steps: Vec<WorkflowStepStateResponse>,
     pending_steps: Vec<WorkflowStepStateResponse>,
+    video_bytes: u64,
+    audio_bytes: u64,
+    active_stream_count: usize,
+    active_streams: Vec<WorkflowStreamResponse>,
+    definition: WorkflowDefinition,
+}
+
+/// API's response for a stream that is active within the workflow
+#[derive(Serialize)]
+pub struct WorkflowStreamResponse {
+    stream_id: String,
+    stream_name: String,
+    originating_step_id: String,
 }
 
 /// API's response for the details of an individual workflow step
@@ -37,6 +52,17 @@ pub struct WorkflowStepStateResponse {
     step_type: String,
     parameters: HashMap<String, Option<String>>,
     status: String,
+    execution_timing: Option<StepExecutionTimingResponse>,
+
+    /// Values the step reports for each of its streams, keyed by stream id
+    stream_details: HashMap<String, HashMap<String, String>>,
+}
+
+/// API's response for how long a workflow step's recent executions have taken
+#[derive(Serialize)]
+pub struct StepExecutionTimingResponse {
+    average_microseconds: u64,
+    max_microseconds: u64,
 }
 
 impl GetWorkflowDetailsHandler {
@@ -64,32 +90,20 @@ impl RouteHandler for GetWorkflowDetailsHandler {
             }
         };
 
-        let (sender, receiver) = channel();
-        let _ = self.manager.send(WorkflowManagerRequest {
+        let details = query_workflow_manager(
+            &self.manager,
             request_id,
-            operation: WorkflowManagerRequestOperation::GetWorkflowDetails {
+            Duration::from_secs(1),
+            |response_channel| WorkflowManagerRequestOperation::GetWorkflowDetails {
                 name: workflow_name,
-                response_channel: sender,
+                response_channel,
             },
-        });
+        )
+        .await;
 
-        let details = match timeout(Duration::from_secs(1), receiver).await {
-            Ok(Ok(details)) => details,
-            Ok(Err(_)) => {
-                error!("Receiver was dropped prior to sending a response");
-                let mut response = Response::default();
-                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
-
-                return Ok(response);
-            }
-
-            Err(_) => {
-                error!("Request timed out");
-                let mut response = Response::default();
-                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
-
-                return Ok(response);
-            }
+        let details = match details {
+            Ok(details) => details,
+            Err(response) => return Ok(response),
         };
 
         let response = if let Some(details) = details {
@@ -146,6 +160,17 @@ impl From<WorkflowState> for WorkflowStateResponse {
                 .into_iter()
                 .map(|x| WorkflowStepStateResponse::from(x))
                 .collect(),
+
+            video_bytes: workflow.video_bytes,
+            audio_bytes: workflow.audio_bytes,
+            active_stream_count: workflow.active_stream_count,
+            active_streams: workflow
+                .active_streams
+                .into_iter()
+                .map(WorkflowStreamResponse::from)
+                .collect(),
+
+            definition: workflow.definition,
         }
     }
 }
@@ -160,8 +185,39 @@ impl From<WorkflowStepState> for WorkflowStepStateResponse {
                 StepStatus::Created => "Created".to_string(),
                 StepStatus::Active => "Active".to_string(),
                 StepStatus::Error { message } => format!("Error: {}", message),
+                StepStatus::Reconnecting { attempt } => {
+                    format!("Reconnecting (attempt {})", attempt)
+                }
+                StepStatus::ShuttingDown => "Shutting Down".to_string(),
                 StepStatus::Shutdown => "Shut Down".to_string(),
             },
+            execution_timing: step_state
+                .execution_timing
+                .map(StepExecutionTimingResponse::from),
+            stream_details: step_state
+                .stream_details
+                .into_iter()
+                .map(|(stream_id, details)| (stream_id.0, details))
+                .collect(),
+        }
+    }
+}
+
+impl From<WorkflowStreamState> for WorkflowStreamResponse {
+    fn from(stream: WorkflowStreamState) -> Self {
+        WorkflowStreamResponse {
+            stream_id: stream.stream_id.0,
+            stream_name: stream.stream_name,
+            originating_step_id: stream.originating_step_id.to_string(),
+        }
+    }
+}
+
+impl From<StepExecutionTiming> for StepExecutionTimingResponse {
+    fn from(timing: StepExecutionTiming) -> Self {
+        StepExecutionTimingResponse {
+            average_microseconds: timing.average.as_micros() as u64,
+            max_microseconds: timing.max.as_micros() as u64,
         }
     }
 }
@@ -0,0 +1,56 @@
+//! Contains the handler for checking if mmids is healthy
+
+use crate::http_api::handlers::query_workflow_manager;
+use crate::http_api::routing::RouteHandler;
+use crate::workflows::manager::{WorkflowManagerRequest, WorkflowManagerRequestOperation};
+use async_trait::async_trait;
+use hyper::{Body, Error, Request, Response, StatusCode};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+
+const PING_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// HTTP handler meant for load balancers and orchestrators to check if mmids is healthy.  It
+/// returns a 200 OK if the workflow manager responds to a ping, or a 503 Service Unavailable if
+/// the workflow manager is gone or doesn't respond quickly.  Unlike listing workflows, no
+/// workflow information is gathered, so the check is cheap enough to be called frequently.
+pub struct HealthCheckHandler {
+    manager: UnboundedSender<WorkflowManagerRequest>,
+}
+
+impl HealthCheckHandler {
+    pub fn new(manager: UnboundedSender<WorkflowManagerRequest>) -> Self {
+        HealthCheckHandler { manager }
+    }
+}
+
+#[async_trait]
+impl RouteHandler for HealthCheckHandler {
+    async fn execute(
+        &self,
+        _request: &mut Request<Body>,
+        _path_parameters: HashMap<String, String>,
+        request_id: String,
+    ) -> Result<Response<Body>, Error> {
+        let result = query_workflow_manager(
+            &self.manager,
+            request_id,
+            PING_TIMEOUT,
+            |response_channel| WorkflowManagerRequestOperation::Ping { response_channel },
+        )
+        .await;
+
+        let response = match result {
+            Ok(()) => Response::new(Body::from("OK")),
+            Err(_) => {
+                let mut response = Response::new(Body::from("Unhealthy"));
+                *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+
+                response
+            }
+        };
+
+        Ok(response)
+    }
+}
@@ -1,7 +1,10 @@
 //! Contains the handler for getting a list of workflows
 
+use crate::http_api::handlers::query_workflow_manager;
 use crate::http_api::routing::RouteHandler;
-use crate::workflows::manager::{WorkflowManagerRequest, WorkflowManagerRequestOperation};
+use crate::workflows::manager::{
+    WorkflowManagerRequest, WorkflowManagerRequestOperation, WorkflowSummary, WorkflowSummaryStatus,
+};
 use async_trait::async_trait;
 use hyper::header::HeaderValue;
 use hyper::{Body, Error, Request, Response, StatusCode};
@@ -9,8 +12,6 @@ use serde::Serialize;
 use std::collections::HashMap;
 use std::time::Duration;
 use tokio::sync::mpsc::UnboundedSender;
-use tokio::sync::oneshot::channel;
-use tokio::time::timeout;
 use tracing::error;
 
 /// HTTP handler which provides a list of workflows that are actively running
@@ -22,6 +23,9 @@ pub struct ListWorkflowsHandler {
 #[derive(Serialize)]
 pub struct WorkflowListItemResponse {
     name: String,
+    status: String,
+    step_count: Option<usize>,
+    active_stream_count: Option<usize>,
 }
 
 impl ListWorkflowsHandler {
@@ -38,48 +42,24 @@ impl RouteHandler for ListWorkflowsHandler {
         _path_parameters: HashMap<String, String>,
         request_id: String,
     ) -> Result<Response<Body>, Error> {
-        let (response_sender, response_receiver) = channel();
-        let message = WorkflowManagerRequest {
+        let response = query_workflow_manager(
+            &self.manager,
             request_id,
-            operation: WorkflowManagerRequestOperation::GetRunningWorkflows {
-                response_channel: response_sender,
+            Duration::from_secs(10),
+            |response_channel| WorkflowManagerRequestOperation::GetAllWorkflowDetails {
+                response_channel,
             },
-        };
-
-        match self.manager.send(message) {
-            Ok(_) => (),
-            Err(_) => {
-                error!("Workflow manager is no longer operational");
-                let mut response = Response::default();
-                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
-
-                return Ok(response);
-            }
-        };
-
-        let response = match timeout(Duration::from_secs(10), response_receiver).await {
-            Ok(Ok(response)) => response,
-
-            Ok(Err(_)) => {
-                error!("Workflow manager is no longer operational");
-                let mut response = Response::default();
-                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+        )
+        .await;
 
-                return Ok(response);
-            }
-
-            Err(_) => {
-                error!("Get workflow request timed out");
-                let mut response = Response::default();
-                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
-
-                return Ok(response);
-            }
+        let response = match response {
+            Ok(response) => response,
+            Err(response) => return Ok(response),
         };
 
         let response = response
             .into_iter()
-            .map(|x| WorkflowListItemResponse { name: x.name })
+            .map(WorkflowListItemResponse::from)
             .collect::<Vec<_>>();
         let json = match serde_json::to_string_pretty(&response) {
             Ok(json) => json,
@@ -102,3 +82,21 @@ impl RouteHandler for ListWorkflowsHandler {
         Ok(response)
     }
 }
+
+impl From<WorkflowSummary> for WorkflowListItemResponse {
+    fn from(summary: WorkflowSummary) -> Self {
+        WorkflowListItemResponse {
+            name: summary.name,
+            status: match summary.status {
+                WorkflowSummaryStatus::Running => "Running".to_string(),
+                WorkflowSummaryStatus::Error {
+                    failed_step_id,
+                    message,
+                } => format!("Step id {} failed: {}", failed_step_id, message),
+                WorkflowSummaryStatus::Unresponsive => "Unresponsive".to_string(),
+            },
+            step_count: summary.step_count,
+            active_stream_count: summary.active_stream_count,
+        }
+    }
+}
@@ -1,6 +1,82 @@
 //! Contains pre-defined implementations of the `RouteHandler` traits for various functionality
 
+pub mod disconnect_rtmp_connection;
+pub mod get_metrics;
+pub mod get_rtmp_statistics;
 pub mod get_workflow_details;
+pub mod health_check;
 pub mod list_workflows;
+pub mod set_workflow_log_level;
 pub mod start_workflow;
 pub mod stop_workflow;
+
+#[cfg(test)]
+mod tests;
+
+use crate::workflows::manager::{WorkflowManagerRequest, WorkflowManagerRequestOperation};
+use hyper::{Body, Response, StatusCode};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot::{channel, Sender};
+use tokio::time::timeout;
+use tracing::error;
+
+/// Sends a request to the workflow manager without waiting for a response.  If the workflow
+/// manager has shut down, the `503 Service Unavailable` response the handler should return is
+/// provided as the error.
+pub(crate) fn send_to_workflow_manager(
+    manager: &UnboundedSender<WorkflowManagerRequest>,
+    request: WorkflowManagerRequest,
+) -> Result<(), Response<Body>> {
+    if manager.send(request).is_err() {
+        error!("Workflow manager is no longer operational");
+        return Err(workflow_manager_unavailable_response());
+    }
+
+    Ok(())
+}
+
+/// Sends a request to the workflow manager and waits for its response.  If the workflow manager
+/// has shut down then a `503 Service Unavailable` response is provided as the error, so the
+/// handler doesn't have to wait for a response that will never come.  If the workflow manager
+/// doesn't respond in time a `500 Internal Server Error` response is provided instead.
+pub(crate) async fn query_workflow_manager<T>(
+    manager: &UnboundedSender<WorkflowManagerRequest>,
+    request_id: String,
+    wait_duration: Duration,
+    operation: impl FnOnce(Sender<T>) -> WorkflowManagerRequestOperation,
+) -> Result<T, Response<Body>> {
+    let (sender, receiver) = channel();
+    send_to_workflow_manager(
+        manager,
+        WorkflowManagerRequest {
+            request_id,
+            operation: operation(sender),
+        },
+    )?;
+
+    match timeout(wait_duration, receiver).await {
+        Ok(Ok(response)) => Ok(response),
+
+        // The workflow manager drops pending requests when it shuts down
+        Ok(Err(_)) => {
+            error!("Workflow manager is no longer operational");
+            Err(workflow_manager_unavailable_response())
+        }
+
+        Err(_) => {
+            error!("Workflow manager request timed out");
+            let mut response = Response::default();
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+
+            Err(response)
+        }
+    }
+}
+
+fn workflow_manager_unavailable_response() -> Response<Body> {
+    let mut response = Response::new(Body::from("The workflow manager is not available"));
+    *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+
+    response
+}
@@ -0,0 +1,121 @@
+//! Handler that allows the log level of a single workflow to be changed
+
+use crate::http_api::handlers::query_workflow_manager;
+use crate::http_api::routing::RouteHandler;
+use crate::workflows::manager::{WorkflowManagerRequest, WorkflowManagerRequestOperation};
+use async_trait::async_trait;
+use bytes::BytesMut;
+use hyper::body::HttpBody;
+use hyper::{Body, Error, Request, Response, StatusCode};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{error, Level};
+
+const MAX_BODY_SIZE: usize = 64;
+const DEFAULT_LEVEL: &str = "default";
+
+/// Handles HTTP requests to change the level a running workflow logs at, without affecting any
+/// other workflow.  It requires a single path parameter named `workflow` that contains the name
+/// of the workflow to change.  The request body must contain the log level to use (`error`,
+/// `warn`, `info`, `debug`, or `trace`), or `default` to return the workflow to the global log
+/// level.
+///
+/// It will return a 200 OK if the log level was changed, a 400 Bad Request if the log level
+/// wasn't valid, or a 404 Not Found if the workflow isn't running.
+pub struct SetWorkflowLogLevelHandler {
+    manager: UnboundedSender<WorkflowManagerRequest>,
+}
+
+impl SetWorkflowLogLevelHandler {
+    pub fn new(manager: UnboundedSender<WorkflowManagerRequest>) -> Self {
+        SetWorkflowLogLevelHandler { manager }
+    }
+}
+
+#[async_trait]
+impl RouteHandler for SetWorkflowLogLevelHandler {
+    async fn execute(
+        &self,
+        request: &mut Request<Body>,
+        path_parameters: HashMap<String, String>,
+        request_id: String,
+    ) -> Result<Response<Body>, Error> {
+        let workflow_name = match path_parameters.get("workflow") {
+            Some(value) => value.to_string(),
+            None => {
+                error!("Set log level endpoint called without a 'workflow' path parameter");
+                let mut response = Response::default();
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+
+                return Ok(response);
+            }
+        };
+
+        let level = match read_level(request.body_mut()).await? {
+            Some(level) => level,
+            None => {
+                let mut response = Response::new(Body::from(format!(
+                    "The body must contain a log level of error, warn, info, debug, trace, or {}",
+                    DEFAULT_LEVEL
+                )));
+
+                *response.status_mut() = StatusCode::BAD_REQUEST;
+
+                return Ok(response);
+            }
+        };
+
+        let was_set = query_workflow_manager(
+            &self.manager,
+            request_id,
+            Duration::from_secs(10),
+            |sender| WorkflowManagerRequestOperation::SetLogLevel {
+                name: workflow_name,
+                level,
+                response_channel: Some(sender),
+            },
+        )
+        .await;
+
+        let was_set = match was_set {
+            Ok(was_set) => was_set,
+            Err(response) => return Ok(response),
+        };
+
+        let mut response = Response::default();
+        *response.status_mut() = if was_set {
+            StatusCode::OK
+        } else {
+            StatusCode::NOT_FOUND
+        };
+
+        Ok(response)
+    }
+}
+
+/// Reads the log level from the request body.  `None` is returned if the body doesn't contain a
+/// valid log level, while `Some(None)` means the global log level should be used.
+async fn read_level(body: &mut Body) -> Result<Option<Option<Level>>, Error> {
+    let mut bytes = BytesMut::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if bytes.len() + chunk.len() > MAX_BODY_SIZE {
+            return Ok(None);
+        }
+
+        bytes.extend_from_slice(&chunk);
+    }
+
+    let content = match std::str::from_utf8(&bytes) {
+        Ok(content) => content.trim(),
+        Err(_) => return Ok(None),
+    };
+
+    if content.eq_ignore_ascii_case(DEFAULT_LEVEL) {
+        return Ok(Some(None));
+    }
+
+    Ok(Level::from_str(content).ok().map(Some))
+}
@@ -1,10 +1,12 @@
 //! Contains the handler that creates and updates workflows
 
+use crate::http_api::handlers::send_to_workflow_manager;
 use crate::http_api::routing::RouteHandler;
 use crate::workflows::definitions::WorkflowDefinition;
 use crate::workflows::manager::{WorkflowManagerRequest, WorkflowManagerRequestOperation};
 use async_trait::async_trait;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use hyper::body::HttpBody;
 use hyper::http::HeaderValue;
 use hyper::{Body, Error, Request, Response, StatusCode};
 use serde::Serialize;
@@ -13,6 +15,7 @@ use tokio::sync::mpsc::UnboundedSender;
 use tracing::{error, warn};
 
 const MMIDS_MIME_TYPE: &'static str = "application/vnd.mmids.workflow";
+const MAX_BODY_SIZE: usize = 1024 * 1024;
 
 /// Handles requests to start a workflow. Every workflow must have a name, and if a workflow is
 /// specified with a name that matches an already running workflow then the existing workflow
@@ -29,6 +32,9 @@ const MMIDS_MIME_TYPE: &'static str = "application/vnd.mmids.workflow";
 /// the mmids configuration files.
 ///
 /// If no `Content-Type` is specified than `application/vnd.mmids.workflow` is assumed.
+///
+/// If the route contains a `workflow` path parameter, then the workflow in the request body must
+/// have the same name as the one specified in the path.
 pub struct StartWorkflowHandler {
     manager: UnboundedSender<WorkflowManagerRequest>,
 }
@@ -50,10 +56,23 @@ impl RouteHandler for StartWorkflowHandler {
     async fn execute(
         &self,
         request: &mut Request<Body>,
-        _path_parameters: HashMap<String, String>,
+        path_parameters: HashMap<String, String>,
         request_id: String,
     ) -> Result<Response<Body>, Error> {
-        let body = hyper::body::to_bytes(request.body_mut()).await?;
+        let body = match read_body(request.body_mut()).await? {
+            Some(body) => body,
+            None => {
+                warn!(
+                    "Request body exceeded the maximum of {} bytes",
+                    MAX_BODY_SIZE
+                );
+                let mut response = Response::default();
+                *response.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+
+                return Ok(response);
+            }
+        };
+
         let content_type = match request.headers().get(hyper::http::header::CONTENT_TYPE) {
             Some(content_type) => content_type.to_str().unwrap_or(MMIDS_MIME_TYPE),
             None => {
@@ -81,23 +100,32 @@ impl RouteHandler for StartWorkflowHandler {
             }
         };
 
-        let result = self.manager.send(WorkflowManagerRequest {
-            request_id,
-            operation: WorkflowManagerRequestOperation::UpsertWorkflow {
-                definition: workflow,
+        if let Some(name) = path_parameters.get("workflow") {
+            if *name != workflow.name {
+                let error = ErrorResponse {
+                    error: format!(
+                        "The workflow name '{}' does not match the name in the path '{}'",
+                        workflow.name, name
+                    ),
+                };
+
+                return Ok(error.to_json_bad_request());
+            }
+        }
+
+        let result = send_to_workflow_manager(
+            &self.manager,
+            WorkflowManagerRequest {
+                request_id,
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: workflow,
+                },
             },
-        });
+        );
 
         match result {
             Ok(_) => Ok(Response::default()),
-
-            Err(_) => {
-                error!("Workflow manager no longer exists");
-                let mut response = Response::default();
-                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
-
-                Ok(response)
-            }
+            Err(response) => Ok(response),
         }
     }
 }
@@ -128,6 +156,21 @@ impl ErrorResponse {
     }
 }
 
+/// Reads the full request body, returning `None` if it's larger than the maximum allowed size
+async fn read_body(body: &mut Body) -> Result<Option<Bytes>, Error> {
+    let mut bytes = BytesMut::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if bytes.len() + chunk.len() > MAX_BODY_SIZE {
+            return Ok(None);
+        }
+
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(Some(bytes.freeze()))
+}
+
 fn parse_mmids_mime_type(body: Bytes) -> Result<Result<WorkflowDefinition, ErrorResponse>, Error> {
     let content = match String::from_utf8(body.to_vec()) {
         Ok(content) => content,
@@ -142,7 +185,7 @@ fn parse_mmids_mime_type(body: Bytes) -> Result<Result<WorkflowDefinition, Error
         Ok(config) => config,
         Err(parse_error) => {
             return Ok(Err(ErrorResponse {
-                error: format!("Failed to parse input: {:?}", parse_error),
+                error: format!("Failed to parse input: {}", parse_error),
             }));
         }
     };
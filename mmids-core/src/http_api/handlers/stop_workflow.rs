@@ -1,16 +1,18 @@
 //! Handler that allows a workflow to be stopped
 
+use crate::http_api::handlers::query_workflow_manager;
 use crate::http_api::routing::RouteHandler;
 use crate::workflows::manager::{WorkflowManagerRequest, WorkflowManagerRequestOperation};
 use async_trait::async_trait;
 use hyper::{Body, Error, Request, Response, StatusCode};
 use std::collections::HashMap;
+use std::time::Duration;
 use tokio::sync::mpsc::UnboundedSender;
 use tracing::error;
 
 /// Handles HTTP requests to stop a running workflow.  It requires a single path parameter
-/// named `workflow` that contains the name of the workflow to be stopped.  It will always return
-/// a 200 OK, even if the workflow isn't running.
+/// named `workflow` that contains the name of the workflow to be stopped.  It will return a
+/// 202 Accepted if the workflow was stopped, or a 404 Not Found if the workflow isn't running.
 pub struct StopWorkflowHandler {
     manager: UnboundedSender<WorkflowManagerRequest>,
 }
@@ -40,22 +42,29 @@ impl RouteHandler for StopWorkflowHandler {
             }
         };
 
-        match self.manager.send(WorkflowManagerRequest {
+        let was_stopped = query_workflow_manager(
+            &self.manager,
             request_id,
-            operation: WorkflowManagerRequestOperation::StopWorkflow {
+            Duration::from_secs(10),
+            |sender| WorkflowManagerRequestOperation::StopWorkflow {
                 name: workflow_name,
+                response_channel: Some(sender),
             },
-        }) {
-            Ok(_) => (),
-            Err(_) => {
-                error!("Workflow manager endpoint gone");
-                let mut response = Response::default();
-                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+        )
+        .await;
 
-                return Ok(response);
-            }
+        let was_stopped = match was_stopped {
+            Ok(was_stopped) => was_stopped,
+            Err(response) => return Ok(response),
+        };
+
+        let mut response = Response::default();
+        *response.status_mut() = if was_stopped {
+            StatusCode::ACCEPTED
+        } else {
+            StatusCode::NOT_FOUND
         };
 
-        Ok(Response::default())
+        Ok(response)
     }
 }
@@ -0,0 +1,258 @@
+use super::disconnect_rtmp_connection::DisconnectRtmpConnectionHandler;
+use super::get_workflow_details::GetWorkflowDetailsHandler;
+use super::health_check::HealthCheckHandler;
+use super::list_workflows::ListWorkflowsHandler;
+use super::set_workflow_log_level::SetWorkflowLogLevelHandler;
+use super::start_workflow::StartWorkflowHandler;
+use super::stop_workflow::StopWorkflowHandler;
+use super::*;
+use crate::endpoints::rtmp_server::RtmpEndpointRequest;
+use crate::http_api::routing::RouteHandler;
+use std::collections::HashMap;
+use tokio::sync::mpsc::unbounded_channel;
+
+/// Creates a workflow manager channel whose receiver has already been dropped, as if the
+/// workflow manager had shut down
+fn closed_manager_channel() -> UnboundedSender<WorkflowManagerRequest> {
+    let (sender, _) = unbounded_channel();
+    sender
+}
+
+fn workflow_path_parameters() -> HashMap<String, String> {
+    let mut parameters = HashMap::new();
+    parameters.insert("workflow".to_string(), "abc".to_string());
+
+    parameters
+}
+
+async fn assert_service_unavailable(response: Response<Body>) {
+    assert_eq!(
+        response.status(),
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Unexpected status code"
+    );
+
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .expect("Failed to read response body");
+
+    assert!(!body.is_empty(), "Expected a response body");
+}
+
+#[tokio::test]
+async fn list_workflows_returns_503_when_manager_gone() {
+    let handler = ListWorkflowsHandler::new(closed_manager_channel());
+    let response = handler
+        .execute(&mut Request::default(), HashMap::new(), "id".to_string())
+        .await
+        .unwrap();
+
+    assert_service_unavailable(response).await;
+}
+
+#[tokio::test]
+async fn get_workflow_details_returns_503_when_manager_gone() {
+    let handler = GetWorkflowDetailsHandler::new(closed_manager_channel());
+    let response = handler
+        .execute(
+            &mut Request::default(),
+            workflow_path_parameters(),
+            "id".to_string(),
+        )
+        .await
+        .unwrap();
+
+    assert_service_unavailable(response).await;
+}
+
+#[tokio::test]
+async fn stop_workflow_returns_503_when_manager_gone() {
+    let handler = StopWorkflowHandler::new(closed_manager_channel());
+    let response = handler
+        .execute(
+            &mut Request::default(),
+            workflow_path_parameters(),
+            "id".to_string(),
+        )
+        .await
+        .unwrap();
+
+    assert_service_unavailable(response).await;
+}
+
+#[tokio::test]
+async fn start_workflow_returns_503_when_manager_gone() {
+    let handler = StartWorkflowHandler::new(closed_manager_channel());
+    let mut request = Request::new(Body::from("workflow abc {\n    tag name=test\n}\n"));
+    let response = handler
+        .execute(&mut request, HashMap::new(), "id".to_string())
+        .await
+        .unwrap();
+
+    assert_service_unavailable(response).await;
+}
+
+#[tokio::test]
+async fn query_returns_503_when_manager_drops_request_without_responding() {
+    let (sender, mut receiver) = unbounded_channel();
+    let query = tokio::spawn(async move {
+        query_workflow_manager(
+            &sender,
+            "id".to_string(),
+            Duration::from_secs(10),
+            |response_channel| WorkflowManagerRequestOperation::GetRunningWorkflows {
+                response_channel,
+            },
+        )
+        .await
+    });
+
+    // Receive the request then shut down, dropping the request's response channel with it
+    let request = receiver.recv().await.expect("Expected a manager request");
+    drop(request);
+    drop(receiver);
+
+    let response = query
+        .await
+        .unwrap()
+        .expect_err("Expected an error response");
+
+    assert_service_unavailable(response).await;
+}
+
+#[tokio::test]
+async fn health_check_returns_200_when_manager_responds() {
+    let (sender, mut receiver) = unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(request) = receiver.recv().await {
+            if let WorkflowManagerRequestOperation::Ping { response_channel } = request.operation {
+                let _ = response_channel.send(());
+            }
+        }
+    });
+
+    let handler = HealthCheckHandler::new(sender);
+    let response = handler
+        .execute(&mut Request::default(), HashMap::new(), "id".to_string())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK, "Unexpected status code");
+}
+
+#[tokio::test]
+async fn health_check_returns_503_when_manager_gone() {
+    let handler = HealthCheckHandler::new(closed_manager_channel());
+    let response = handler
+        .execute(&mut Request::default(), HashMap::new(), "id".to_string())
+        .await
+        .unwrap();
+
+    assert_service_unavailable(response).await;
+}
+
+#[tokio::test]
+async fn set_log_level_sends_level_to_manager() {
+    let (sender, mut receiver) = unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(request) = receiver.recv().await {
+            if let WorkflowManagerRequestOperation::SetLogLevel {
+                name,
+                level,
+                response_channel: Some(response_channel),
+            } = request.operation
+            {
+                assert_eq!(name, "abc", "Unexpected workflow name");
+                assert_eq!(level, Some(tracing::Level::DEBUG), "Unexpected log level");
+                let _ = response_channel.send(true);
+            }
+        }
+    });
+
+    let handler = SetWorkflowLogLevelHandler::new(sender);
+    let response = handler
+        .execute(
+            &mut Request::new(Body::from("debug")),
+            workflow_path_parameters(),
+            "id".to_string(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK, "Unexpected status code");
+}
+
+#[tokio::test]
+async fn set_log_level_returns_400_for_invalid_level() {
+    let (sender, _receiver) = unbounded_channel();
+    let handler = SetWorkflowLogLevelHandler::new(sender);
+    let response = handler
+        .execute(
+            &mut Request::new(Body::from("loud")),
+            workflow_path_parameters(),
+            "id".to_string(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        response.status(),
+        StatusCode::BAD_REQUEST,
+        "Unexpected status code"
+    );
+}
+
+fn connection_path_parameters() -> HashMap<String, String> {
+    let mut parameters = HashMap::new();
+    parameters.insert("connection".to_string(), "abc".to_string());
+
+    parameters
+}
+
+#[tokio::test]
+async fn disconnect_rtmp_connection_sends_request_to_endpoint() {
+    let (sender, mut receiver) = unbounded_channel();
+    let handler = DisconnectRtmpConnectionHandler::new(sender);
+    let response = handler
+        .execute(
+            &mut Request::new(Body::empty()),
+            connection_path_parameters(),
+            "id".to_string(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        response.status(),
+        StatusCode::ACCEPTED,
+        "Unexpected status code"
+    );
+
+    match receiver.try_recv() {
+        Ok(RtmpEndpointRequest::DisconnectConnection { connection_id }) => {
+            assert_eq!(connection_id.0, "abc", "Unexpected connection id");
+        }
+
+        request => panic!("Unexpected endpoint request: {:?}", request),
+    }
+}
+
+#[tokio::test]
+async fn disconnect_rtmp_connection_returns_500_when_endpoint_gone() {
+    let (sender, _) = unbounded_channel();
+    let handler = DisconnectRtmpConnectionHandler::new(sender);
+    let response = handler
+        .execute(
+            &mut Request::new(Body::empty()),
+            connection_path_parameters(),
+            "id".to_string(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        response.status(),
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Unexpected status code"
+    );
+}
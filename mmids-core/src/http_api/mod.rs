@@ -3,54 +3,236 @@
 
 pub mod handlers;
 pub mod routing;
+mod tls;
+
+pub use tls::HttpApiTlsOptions;
 
 use crate::http_api::routing::RoutingTable;
 use hyper::header::HeaderName;
+use hyper::rt::Executor;
+use hyper::server::accept;
 use hyper::server::conn::AddrStream;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Request, Response, Server, StatusCode};
+use std::future::Future;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tls::TlsConnection;
+use tokio::net::TcpListener;
 use tokio::sync::oneshot::{channel, Receiver, Sender};
-use tracing::{error, info, instrument};
+use tokio::sync::watch;
+use tracing::{error, info, instrument, warn};
 use uuid::Uuid;
 
-pub struct HttpApiShutdownSignal {}
+/// How long in-flight requests are given to complete if the shutdown signal's sender is dropped
+/// without a signal being sent
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tells the HTTP api to stop accepting new connections and shut down once all in-flight requests
+/// have completed.
+pub struct HttpApiShutdownSignal {
+    /// How long in-flight requests are given to complete before their connections are forcibly
+    /// closed
+    pub drain_timeout: Duration,
+
+    /// Notified once the server has fully shut down and all connections have been closed
+    pub response_channel: Option<Sender<()>>,
+}
+
+/// Errors that can occur when starting the HTTP api
+#[derive(Error, Debug)]
+pub enum HttpApiStartError {
+    #[error("Failed to bind the HTTP api to {address}: {error}")]
+    BindError {
+        address: SocketAddr,
+        error: std::io::Error,
+    },
 
+    #[error("Failed to read file '{path}': {error}")]
+    FileReadError { path: String, error: std::io::Error },
+
+    #[error("Invalid TLS certificate or private key: {0}")]
+    InvalidCertificate(native_tls::Error),
+}
+
+/// Starts the HTTP api on the specified address.  If TLS options are provided then the api will
+/// only be served over HTTPS, and an error is returned if the certificate can't be loaded.
 pub fn start_http_api(
     bind_address: SocketAddr,
     routes: RoutingTable,
-) -> Sender<HttpApiShutdownSignal> {
+    tls_options: Option<HttpApiTlsOptions>,
+) -> Result<Sender<HttpApiShutdownSignal>, HttpApiStartError> {
     let routes = Arc::new(routes);
-    let service = make_service_fn(move |socket: &AddrStream| {
-        let remote_address = socket.remote_addr();
-        let routes_clone = routes.clone();
-        async move {
-            Ok::<_, hyper::Error>(service_fn(move |request: Request<Body>| {
-                execute_request(
-                    request,
-                    remote_address,
-                    routes_clone.clone(),
-                    Uuid::new_v4().to_string(),
-                )
-            }))
+    let (sender, receiver) = channel();
+    let (drain_sender, drain_receiver) = channel();
+    let (force_close_sender, force_close_receiver) = watch::channel(false);
+    let executor = ConnectionExecutor {
+        force_close: force_close_receiver,
+    };
+
+    match tls_options {
+        None => {
+            let service = make_service_fn(move |socket: &AddrStream| {
+                let remote_address = socket.remote_addr();
+                let routes_clone = routes.clone();
+                async move {
+                    Ok::<_, hyper::Error>(service_fn(move |request: Request<Body>| {
+                        execute_request(
+                            request,
+                            remote_address,
+                            routes_clone.clone(),
+                            Uuid::new_v4().to_string(),
+                        )
+                    }))
+                }
+            });
+
+            let server = Server::try_bind(&bind_address)
+                .map_err(|error| HttpApiStartError::BindError {
+                    address: bind_address,
+                    error: std::io::Error::new(std::io::ErrorKind::Other, error),
+                })?
+                .executor(executor)
+                .serve(service)
+                .with_graceful_shutdown(graceful_shutdown(drain_receiver));
+
+            info!("Starting HTTP api on {}", bind_address);
+            tokio::spawn(run_server(
+                server,
+                receiver,
+                drain_sender,
+                force_close_sender,
+            ));
         }
-    });
 
-    let (sender, receiver) = channel();
-    let server = Server::bind(&bind_address)
-        .serve(service)
-        .with_graceful_shutdown(graceful_shutdown(receiver));
+        Some(tls_options) => {
+            let acceptor = tls::create_tls_acceptor(&tls_options)?;
+            let listener =
+                bind_tcp_listener(bind_address).map_err(|error| HttpApiStartError::BindError {
+                    address: bind_address,
+                    error,
+                })?;
+
+            let service = make_service_fn(move |connection: &TlsConnection| {
+                let remote_address = connection.remote_address;
+                let routes_clone = routes.clone();
+                async move {
+                    Ok::<_, hyper::Error>(service_fn(move |request: Request<Body>| {
+                        execute_request(
+                            request,
+                            remote_address,
+                            routes_clone.clone(),
+                            Uuid::new_v4().to_string(),
+                        )
+                    }))
+                }
+            });
+
+            let connections = tls::accept_tls_connections(listener, acceptor);
+            let server = Server::builder(accept::from_stream(connections))
+                .executor(executor)
+                .serve(service)
+                .with_graceful_shutdown(graceful_shutdown(drain_receiver));
+
+            info!("Starting HTTPS api on {}", bind_address);
+            tokio::spawn(run_server(
+                server,
+                receiver,
+                drain_sender,
+                force_close_sender,
+            ));
+        }
+    }
+
+    Ok(sender)
+}
+
+fn bind_tcp_listener(bind_address: SocketAddr) -> std::io::Result<TcpListener> {
+    let listener = std::net::TcpListener::bind(bind_address)?;
+    listener.set_nonblocking(true)?;
+
+    TcpListener::from_std(listener)
+}
+
+async fn graceful_shutdown(drain_signal: Receiver<()>) {
+    let _ = drain_signal.await;
+}
+
+/// Runs the server until a shutdown signal is received, then gives in-flight requests until the
+/// signal's drain timeout to complete before forcibly closing any remaining connections.
+async fn run_server(
+    server: impl Future<Output = Result<(), hyper::Error>>,
+    shutdown_signal: Receiver<HttpApiShutdownSignal>,
+    drain_sender: Sender<()>,
+    force_close_sender: watch::Sender<bool>,
+) {
+    tokio::pin!(server);
+
+    let signal = tokio::select! {
+        result = &mut server => {
+            if let Err(error) = result {
+                error!("HTTP api stopped unexpectedly: {:?}", error);
+            }
+
+            return;
+        }
+
+        signal = shutdown_signal => signal.ok(),
+    };
+
+    let (drain_timeout, response_channel) = match signal {
+        Some(signal) => (signal.drain_timeout, signal.response_channel),
+        None => (DEFAULT_DRAIN_TIMEOUT, None),
+    };
+
+    info!("Shutting down HTTP api");
+    let _ = drain_sender.send(());
+    let result = match tokio::time::timeout(drain_timeout, &mut server).await {
+        Ok(result) => result,
+        Err(_) => {
+            warn!(
+                "In-flight HTTP api requests did not complete within {:?}, closing their connections",
+                drain_timeout
+            );
+
+            let _ = force_close_sender.send(true);
+            server.await
+        }
+    };
 
-    info!("Starting HTTP api on {}", bind_address);
-    tokio::spawn(async { server.await });
+    if let Err(error) = result {
+        error!("HTTP api failed while shutting down: {:?}", error);
+    }
 
-    sender
+    info!("HTTP api shut down");
+    if let Some(response_channel) = response_channel {
+        let _ = response_channel.send(());
+    }
 }
 
-async fn graceful_shutdown(shutdown_signal: Receiver<HttpApiShutdownSignal>) {
-    let _ = shutdown_signal.await;
+/// Spawns the tasks hyper creates for each connection, and drops them (closing the connection)
+/// once connections are forcibly closed.
+#[derive(Clone)]
+struct ConnectionExecutor {
+    force_close: watch::Receiver<bool>,
+}
+
+impl<F> Executor<F> for ConnectionExecutor
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, future: F) {
+        let mut force_close = self.force_close.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = future => (),
+                _ = force_close.changed() => (),
+            }
+        });
+    }
 }
 
 #[instrument(
@@ -128,3 +310,126 @@ async fn execute_request(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_api::routing::{PathPart, Route, RouteHandler};
+    use async_trait::async_trait;
+    use hyper::{Client, Method};
+    use std::collections::HashMap;
+    use tokio::time::timeout;
+
+    /// Responds to requests after a delay, or never if no delay is given
+    struct SlowHandler {
+        delay: Option<Duration>,
+    }
+
+    #[async_trait]
+    impl RouteHandler for SlowHandler {
+        async fn execute(
+            &self,
+            _request: &mut Request<Body>,
+            _path_parameters: HashMap<String, String>,
+            _request_id: String,
+        ) -> Result<Response<Body>, hyper::Error> {
+            match self.delay {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => futures::future::pending::<()>().await,
+            }
+
+            Ok(Response::new(Body::from("done")))
+        }
+    }
+
+    fn start_api(delay: Option<Duration>) -> (SocketAddr, Sender<HttpApiShutdownSignal>) {
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("Failed to find a free port");
+
+        let mut routes = RoutingTable::new();
+        routes
+            .register(Route {
+                method: Method::GET,
+                path: vec![PathPart::Exact {
+                    value: "slow".to_string(),
+                }],
+                handler: Box::new(SlowHandler { delay }),
+            })
+            .expect("Failed to register route");
+
+        let shutdown = start_http_api(address, routes, None).expect("Failed to start http api");
+
+        (address, shutdown)
+    }
+
+    fn shutdown(sender: Sender<HttpApiShutdownSignal>, drain_timeout: Duration) -> Receiver<()> {
+        let (response_sender, response_receiver) = channel();
+        let _ = sender.send(HttpApiShutdownSignal {
+            drain_timeout,
+            response_channel: Some(response_sender),
+        });
+
+        response_receiver
+    }
+
+    #[tokio::test]
+    async fn shutdown_acknowledged_when_no_requests_in_flight() {
+        let (_, sender) = start_api(None);
+        let response = shutdown(sender, Duration::from_secs(5));
+
+        timeout(Duration::from_secs(1), response)
+            .await
+            .expect("Shutdown was not acknowledged")
+            .expect("Response channel closed");
+    }
+
+    #[tokio::test]
+    async fn in_flight_request_completes_before_shutdown_acknowledged() {
+        let (address, sender) = start_api(Some(Duration::from_millis(100)));
+        let uri = format!("http://{}/slow", address).parse().unwrap();
+        let request = tokio::spawn(async move { Client::new().get(uri).await });
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let mut response = shutdown(sender, Duration::from_secs(5));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(
+            response.try_recv().is_err(),
+            "Shutdown acknowledged while request was in flight"
+        );
+
+        let result = timeout(Duration::from_secs(1), request)
+            .await
+            .expect("Request did not complete")
+            .unwrap();
+
+        assert!(result.is_ok(), "Expected request to succeed");
+
+        timeout(Duration::from_secs(1), response)
+            .await
+            .expect("Shutdown was not acknowledged")
+            .expect("Response channel closed");
+    }
+
+    #[tokio::test]
+    async fn in_flight_request_closed_after_drain_timeout() {
+        let (address, sender) = start_api(None);
+        let uri = format!("http://{}/slow", address).parse().unwrap();
+        let request = tokio::spawn(async move { Client::new().get(uri).await });
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let response = shutdown(sender, Duration::from_millis(50));
+
+        timeout(Duration::from_secs(1), response)
+            .await
+            .expect("Shutdown was not acknowledged")
+            .expect("Response channel closed");
+
+        let result = timeout(Duration::from_secs(1), request)
+            .await
+            .expect("Request did not complete")
+            .unwrap();
+
+        assert!(result.is_err(), "Expected request connection to be closed");
+    }
+}
@@ -0,0 +1,138 @@
+//! Support for serving the HTTP api over TLS.  Incoming TCP connections go through the TLS
+//! handshake before being handed off to hyper, so route handlers are unaware of whether the
+//! request came in over a secure connection or not.
+
+use super::HttpApiStartError;
+use futures::stream::{self, Stream, StreamExt};
+use native_tls::Identity;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_native_tls::{TlsAcceptor, TlsStream};
+use tracing::warn;
+
+const MAX_CONCURRENT_HANDSHAKES: usize = 100;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Options for serving the HTTP api over TLS
+#[derive(Clone, Debug)]
+pub struct HttpApiTlsOptions {
+    /// Path to the PEM encoded certificate (chain) file
+    pub certificate_path: String,
+
+    /// Path to the PEM encoded PKCS #8 private key file for the certificate
+    pub private_key_path: String,
+}
+
+/// A client connection that has successfully completed the TLS handshake
+pub struct TlsConnection {
+    stream: TlsStream<TcpStream>,
+    pub remote_address: SocketAddr,
+}
+
+pub fn create_tls_acceptor(options: &HttpApiTlsOptions) -> Result<TlsAcceptor, HttpApiStartError> {
+    let certificate = std::fs::read(&options.certificate_path).map_err(|error| {
+        HttpApiStartError::FileReadError {
+            path: options.certificate_path.clone(),
+            error,
+        }
+    })?;
+
+    let private_key = std::fs::read(&options.private_key_path).map_err(|error| {
+        HttpApiStartError::FileReadError {
+            path: options.private_key_path.clone(),
+            error,
+        }
+    })?;
+
+    let identity = Identity::from_pkcs8(&certificate, &private_key)
+        .map_err(HttpApiStartError::InvalidCertificate)?;
+
+    let acceptor = native_tls::TlsAcceptor::builder(identity)
+        .build()
+        .map_err(HttpApiStartError::InvalidCertificate)?;
+
+    Ok(TlsAcceptor::from(acceptor))
+}
+
+/// Creates a stream of connections that have completed the TLS handshake.  Handshakes are
+/// performed concurrently so a slow client can't hold up other connections, and any connection
+/// that fails the handshake is dropped.
+pub fn accept_tls_connections(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+) -> impl Stream<Item = Result<TlsConnection, io::Error>> {
+    stream::unfold(listener, |listener| async move {
+        let result = listener.accept().await;
+        Some((result, listener))
+    })
+    .map(move |result| {
+        let acceptor = acceptor.clone();
+        async move {
+            let (socket, remote_address) = match result {
+                Ok(x) => x,
+                Err(error) => {
+                    warn!("Failed to accept HTTP api connection: {:?}", error);
+                    return None;
+                }
+            };
+
+            match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(socket)).await {
+                Ok(Ok(stream)) => Some(Ok(TlsConnection {
+                    stream,
+                    remote_address,
+                })),
+
+                Ok(Err(error)) => {
+                    warn!(
+                        "TLS handshake with {} failed: {:?}",
+                        remote_address.ip(),
+                        error
+                    );
+
+                    None
+                }
+
+                Err(_) => {
+                    warn!("TLS handshake with {} timed out", remote_address.ip());
+
+                    None
+                }
+            }
+        }
+    })
+    .buffer_unordered(MAX_CONCURRENT_HANDSHAKES)
+    .filter_map(|connection| async move { connection })
+}
+
+impl AsyncRead for TlsConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
@@ -17,6 +17,8 @@ pub mod endpoints;
 pub mod event_hub;
 pub mod http_api;
 pub mod net;
+#[cfg(feature = "json-logging")]
+pub mod observability;
 pub mod reactors;
 #[cfg(test)]
 mod test_utils;
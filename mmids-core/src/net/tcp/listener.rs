@@ -1,14 +1,13 @@
 use super::TcpSocketResponse;
-use crate::net::tcp::TlsOptions;
 use crate::net::ConnectionId;
 use bytes::{Bytes, BytesMut};
 use futures::future::FutureExt;
 use std::collections::VecDeque;
 use std::net::SocketAddr;
-use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
 use tokio_native_tls::TlsAcceptor;
 use tracing::{error, info, instrument, warn};
 use uuid::Uuid;
@@ -31,8 +30,9 @@ pub struct ListenerParams {
     /// Should this port accept TLS connections
     pub use_tls: bool,
 
-    /// Options for TLS. Required if use_tls is true
-    pub tls_options: Arc<Option<TlsOptions>>,
+    /// The acceptor to use for new TLS sessions.  Required if use_tls is true.  The acceptor is
+    /// read for each new connection, so replacing it only affects sessions started afterwards.
+    pub tls_acceptor: watch::Receiver<Option<TlsAcceptor>>,
 
     /// The channel in which to send notifications of port activity to
     pub response_channel: UnboundedSender<TcpSocketResponse>,
@@ -65,27 +65,9 @@ async fn listen(params: ListenerParams, _self_disconnection_signal: UnboundedRec
         port,
         response_channel,
         use_tls,
-        tls_options,
+        tls_acceptor,
     } = params;
 
-    let tls = if let Some(tls) = tls_options.as_ref() {
-        let identity = tls.certificate.clone();
-        let tls_acceptor = match native_tls::TlsAcceptor::builder(identity).build() {
-            Ok(x) => x,
-            Err(e) => {
-                error!("Failed to build tls acceptor: {:?}", e);
-                return;
-            }
-        };
-
-        Some(tokio_native_tls::TlsAcceptor::from(tls_acceptor))
-    } else {
-        None
-    };
-
-    let tls = if use_tls { tls } else { None };
-    let tls = Arc::new(tls);
-
     let bind_address = "0.0.0.0:".to_string() + &port.to_string();
     let listener = match TcpListener::bind(bind_address.clone()).await {
         Ok(x) => x,
@@ -107,8 +89,9 @@ async fn listen(params: ListenerParams, _self_disconnection_signal: UnboundedRec
                     }
                 };
 
+                let tls = if use_tls { tls_acceptor.borrow().clone() } else { None };
                 let connection_id = ConnectionId(Uuid::new_v4().to_string());
-                tokio::spawn(handle_new_connection(socket, client_info, response_channel.clone(), port, connection_id, tls.clone()));
+                tokio::spawn(handle_new_connection(socket, client_info, response_channel.clone(), port, connection_id, tls));
             },
 
             _ = disconnect.closed() => {
@@ -126,7 +109,7 @@ async fn handle_new_connection(
     response_channel: UnboundedSender<TcpSocketResponse>,
     port: u16,
     connection_id: ConnectionId,
-    tls_acceptor: Arc<Option<TlsAcceptor>>,
+    tls_acceptor: Option<TlsAcceptor>,
 ) {
     info!(
         ip = %client_info.ip(),
@@ -283,9 +266,9 @@ async fn socket_writer(
 
 async fn split_socket(
     socket: TcpStream,
-    tls_acceptor: Arc<Option<TlsAcceptor>>,
+    tls_acceptor: Option<TlsAcceptor>,
 ) -> Result<(ReadSocket, WriteSocket), Box<dyn std::error::Error + Sync + Send>> {
-    match tls_acceptor.as_ref() {
+    match tls_acceptor {
         None => {
             let (reader, writer) = tokio::io::split(socket);
             Ok((ReadSocket::Bare(reader), WriteSocket::Bare(writer)))
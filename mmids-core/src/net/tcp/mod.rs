@@ -8,7 +8,7 @@ use super::ConnectionId;
 use bytes::Bytes;
 use native_tls::Identity;
 use std::net::SocketAddr;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 
 pub use listener::OutboundPacket;
 pub use socket_manager::start as start_socket_manager;
@@ -19,7 +19,8 @@ pub enum RequestFailureReason {
     /// The port being requested has already been opened for another requester
     PortInUse,
 
-    /// A TLS port was requested to be opened, but the certificate could not be opened
+    /// A TLS port was requested to be opened (or a certificate reload was requested), but the
+    /// certificate could not be opened
     InvalidCertificate(String),
 
     /// A TLS port was requested to be opened, but the password provided did not unlock the
@@ -52,6 +53,21 @@ pub enum TcpSocketRequest {
         /// for notifications
         response_channel: mpsc::UnboundedSender<TcpSocketResponse>,
     },
+
+    /// Request for the server to replace the certificate used for TLS sessions.  The new
+    /// certificate is only used for connections accepted after the reload, and sessions that
+    /// were already established continue to use the certificate they started with.  If the new
+    /// certificate cannot be loaded then the existing certificate remains in use.
+    ReloadTlsCertificate {
+        /// Path to a PEM encoded certificate chain, with the leaf certificate first
+        cert_path: String,
+
+        /// Path to the PEM encoded PKCS #8 private key for the leaf certificate
+        key_path: String,
+
+        /// The channel the result of the reload will be sent on
+        response_channel: oneshot::Sender<Result<(), RequestFailureReason>>,
+    },
 }
 
 #[derive(Debug)]
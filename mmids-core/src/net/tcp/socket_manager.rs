@@ -4,10 +4,12 @@ use crate::net::tcp::{RequestFailureReason, TlsOptions};
 use futures::future::BoxFuture;
 use futures::stream::{FuturesUnordered, StreamExt};
 use futures::FutureExt;
+use native_tls::Identity;
 use std::collections::HashMap;
-use std::sync::Arc;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
-use tracing::{debug, error, info};
+use tokio::sync::{oneshot, watch};
+use tokio_native_tls::TlsAcceptor;
+use tracing::{debug, error, info, warn};
 
 /// Starts a new instance of a socket manager task.  A socket manager can be requested to open
 /// ports on behalf of another system.  If the port is successfully opened it will begin listening
@@ -16,8 +18,8 @@ use tracing::{debug, error, info};
 pub fn start(tls_options: Option<TlsOptions>) -> UnboundedSender<TcpSocketRequest> {
     let (request_sender, request_receiver) = unbounded_channel();
 
-    let manager = SocketManager::new();
-    tokio::spawn(manager.run(request_receiver, tls_options));
+    let manager = SocketManager::new(tls_options);
+    tokio::spawn(manager.run(request_receiver));
 
     request_sender
 }
@@ -30,6 +32,10 @@ enum SocketManagerFutureResult {
     ListenerShutdown {
         port: u16,
     },
+    TlsCertificateLoaded {
+        result: Result<TlsAcceptor, RequestFailureReason>,
+        response_channel: oneshot::Sender<Result<(), RequestFailureReason>>,
+    },
 }
 
 struct OpenPort {
@@ -39,23 +45,36 @@ struct OpenPort {
 struct SocketManager {
     open_ports: HashMap<u16, OpenPort>,
     futures: FuturesUnordered<BoxFuture<'static, SocketManagerFutureResult>>,
+    tls_acceptor_sender: watch::Sender<Option<TlsAcceptor>>,
+    tls_acceptor_receiver: watch::Receiver<Option<TlsAcceptor>>,
 }
 
 impl SocketManager {
-    fn new() -> Self {
+    fn new(tls_options: Option<TlsOptions>) -> Self {
+        let tls_acceptor = match tls_options {
+            Some(options) => match build_tls_acceptor(options.certificate) {
+                Ok(acceptor) => Some(acceptor),
+                Err(error) => {
+                    error!("Failed to build tls acceptor: {:?}", error);
+                    None
+                }
+            },
+
+            None => None,
+        };
+
+        let (tls_acceptor_sender, tls_acceptor_receiver) = watch::channel(tls_acceptor);
+
         SocketManager {
             open_ports: HashMap::new(),
             futures: FuturesUnordered::new(),
+            tls_acceptor_sender,
+            tls_acceptor_receiver,
         }
     }
 
-    async fn run(
-        mut self,
-        request_receiver: UnboundedReceiver<TcpSocketRequest>,
-        tls_options: Option<TlsOptions>,
-    ) {
+    async fn run(mut self, request_receiver: UnboundedReceiver<TcpSocketRequest>) {
         info!("Starting TCP socket manager");
-        let tls_options = Arc::new(tls_options);
 
         self.futures
             .push(request_receiver_future(request_receiver).boxed());
@@ -66,7 +85,7 @@ impl SocketManager {
                     self.futures.push(request_receiver_future(receiver).boxed());
 
                     match request {
-                        Some(request) => self.handle_request(request, tls_options.clone()),
+                        Some(request) => self.handle_request(request),
                         None => break, // no more senders of requests
                     }
                 }
@@ -81,20 +100,40 @@ impl SocketManager {
                         }
                     }
                 }
+
+                SocketManagerFutureResult::TlsCertificateLoaded {
+                    result,
+                    response_channel,
+                } => match result {
+                    Ok(acceptor) => {
+                        info!("New TLS certificate loaded, and will be used for new connections");
+                        self.tls_acceptor_sender.send_replace(Some(acceptor));
+                        let _ = response_channel.send(Ok(()));
+                    }
+
+                    Err(reason) => {
+                        warn!(
+                            "TLS certificate reload failed, existing certificate still in use: {:?}",
+                            reason
+                        );
+
+                        let _ = response_channel.send(Err(reason));
+                    }
+                },
             }
         }
 
         info!("Socket manager closing");
     }
 
-    fn handle_request(&mut self, request: TcpSocketRequest, tls_options: Arc<Option<TlsOptions>>) {
+    fn handle_request(&mut self, request: TcpSocketRequest) {
         match request {
             TcpSocketRequest::OpenPort {
                 port,
                 response_channel,
                 use_tls,
             } => {
-                if use_tls && tls_options.as_ref().is_none() {
+                if use_tls && self.tls_acceptor_receiver.borrow().is_none() {
                     error!(
                         port = port,
                         "Request to open port with tls, but we have no tls options"
@@ -125,7 +164,7 @@ impl SocketManager {
                         port,
                         response_channel: response_channel.clone(),
                         use_tls,
-                        tls_options: tls_options.clone(),
+                        tls_acceptor: self.tls_acceptor_receiver.clone(),
                     });
 
                     self.futures
@@ -134,10 +173,58 @@ impl SocketManager {
                     let _ = response_channel.send(TcpSocketResponse::RequestAccepted {});
                 }
             }
+
+            TcpSocketRequest::ReloadTlsCertificate {
+                cert_path,
+                key_path,
+                response_channel,
+            } => {
+                info!(
+                    cert_path = %cert_path,
+                    key_path = %key_path,
+                    "Reloading TLS certificate from '{}' and '{}'",
+                    cert_path, key_path
+                );
+
+                self.futures.push(
+                    load_tls_certificate_future(cert_path, key_path, response_channel).boxed(),
+                );
+            }
         }
     }
 }
 
+fn build_tls_acceptor(identity: Identity) -> Result<TlsAcceptor, native_tls::Error> {
+    let acceptor = native_tls::TlsAcceptor::builder(identity).build()?;
+
+    Ok(TlsAcceptor::from(acceptor))
+}
+
+async fn load_tls_certificate(
+    cert_path: &str,
+    key_path: &str,
+) -> Result<TlsAcceptor, RequestFailureReason> {
+    let certificate = tokio::fs::read(cert_path).await.map_err(|error| {
+        RequestFailureReason::InvalidCertificate(format!(
+            "Failed to read certificate '{}': {}",
+            cert_path, error
+        ))
+    })?;
+
+    let key = tokio::fs::read(key_path).await.map_err(|error| {
+        RequestFailureReason::InvalidCertificate(format!(
+            "Failed to read private key '{}': {}",
+            key_path, error
+        ))
+    })?;
+
+    // Building the acceptor ensures the certificate and key are usable before they replace the
+    // certificate currently in use.
+    Identity::from_pkcs8(&certificate, &key)
+        .and_then(build_tls_acceptor)
+        .map_err(|error| RequestFailureReason::InvalidCertificate(error.to_string()))
+}
+
 async fn request_receiver_future(
     mut receiver: UnboundedReceiver<TcpSocketRequest>,
 ) -> SocketManagerFutureResult {
@@ -157,3 +244,16 @@ async fn listener_shutdown_future(
 
     SocketManagerFutureResult::ListenerShutdown { port }
 }
+
+async fn load_tls_certificate_future(
+    cert_path: String,
+    key_path: String,
+    response_channel: oneshot::Sender<Result<(), RequestFailureReason>>,
+) -> SocketManagerFutureResult {
+    let result = load_tls_certificate(&cert_path, &key_path).await;
+
+    SocketManagerFutureResult::TlsCertificateLoaded {
+        result,
+        response_channel,
+    }
+}
@@ -0,0 +1,36 @@
+//! Helpers for applications built on mmids to set up consistent structured logging.
+//!
+//! mmids components log through `tracing`, with context (such as `workflow_name`, `step_id`,
+//! `connection_id` and `request_id`) recorded as fields on the spans they run within.  Nothing in
+//! this crate installs a subscriber on its own, so applications remain free to set up their own
+//! logging.  These helpers are only available when the `json-logging` feature is enabled.
+
+use tracing::level_filters::LevelFilter;
+use tracing::Level;
+use tracing_subscriber::util::{SubscriberInitExt, TryInitError};
+use tracing_subscriber::{fmt, layer::SubscriberExt, EnvFilter};
+
+/// Sets up a global subscriber that writes each log event to stdout as a single line of JSON.
+///
+/// Every event includes the fields of the span it was raised in and of all that span's parents,
+/// so workflow, step, connection and request identifiers are present on every log line they
+/// apply to.  Events are filtered by the `RUST_LOG` environment variable if it's set, otherwise
+/// only events at the specified level or more severe are written.
+///
+/// An error is returned if a global subscriber has already been set.
+pub fn init_json_logging(level: Level) -> Result<(), TryInitError> {
+    let filter = match EnvFilter::try_from_default_env() {
+        Ok(filter) => filter,
+        Err(_) => EnvFilter::default().add_directive(LevelFilter::from_level(level).into()),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_span_list(true),
+        )
+        .try_init()
+}
@@ -0,0 +1,102 @@
+use crate::reactors::executors::{
+    ReactorExecutionResult, ReactorExecutor, ReactorExecutorGenerator,
+};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use thiserror::Error;
+use tracing::{error, info, instrument};
+
+const FILE_EXTENSION: &str = "config";
+
+/// Looks up workflow definitions from files in a local directory.  When queried for a stream name
+/// it will look for a file named `<stream_name>.config` in the configured directory, and expects
+/// the file to contain one or more workflows in the standard mmids configuration format.
+///
+/// If no file exists for the stream name then the stream is considered not valid.  The file is
+/// read on every request, so any changes made to it will be picked up the next time the reactor
+/// re-executes for the stream.
+pub struct FileReactorExecutor {
+    directory: PathBuf,
+}
+
+impl ReactorExecutor for FileReactorExecutor {
+    fn get_workflow(&self, stream_name: String) -> BoxFuture<'static, ReactorExecutionResult> {
+        execute_file_executor(self.directory.clone(), stream_name).boxed()
+    }
+}
+
+pub struct FileReactorExecutorGenerator {}
+
+#[derive(Error, Debug)]
+pub enum FileReactorExecutorError {
+    #[error("The required parameter 'path' was not provided")]
+    PathParameterNotProvided,
+}
+
+impl ReactorExecutorGenerator for FileReactorExecutorGenerator {
+    fn generate(
+        &self,
+        parameters: &HashMap<String, Option<String>>,
+    ) -> Result<Box<dyn ReactorExecutor>, Box<dyn Error + Sync + Send>> {
+        let directory = match parameters.get("path") {
+            Some(Some(path)) => PathBuf::from(path.trim()),
+            _ => return Err(Box::new(FileReactorExecutorError::PathParameterNotProvided)),
+        };
+
+        Ok(Box::new(FileReactorExecutor { directory }))
+    }
+}
+
+#[instrument]
+async fn execute_file_executor(directory: PathBuf, stream_name: String) -> ReactorExecutionResult {
+    if stream_name.is_empty()
+        || stream_name.contains('/')
+        || stream_name.contains('\\')
+        || stream_name.starts_with('.')
+    {
+        info!("Stream name '{}' is not a valid file name", stream_name);
+        return ReactorExecutionResult::invalid();
+    }
+
+    let mut path = directory;
+    path.push(format!("{}.{}", stream_name, FILE_EXTENSION));
+
+    info!(
+        "Reading {} for workflows for stream '{}'",
+        path.display(),
+        stream_name
+    );
+
+    let content = match tokio::fs::read_to_string(&path).await {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            info!("No workflow file exists for the stream");
+            return ReactorExecutionResult::invalid();
+        }
+
+        Err(error) => {
+            error!("Failed to read {}: {}", path.display(), error);
+            return ReactorExecutionResult::invalid();
+        }
+    };
+
+    let mut config = match crate::config::parse(content.as_str()) {
+        Ok(config) => config,
+        Err(parse_error) => {
+            error!(
+                "The file {} was not a valid mmids config format: {:?}",
+                path.display(),
+                parse_error
+            );
+
+            return ReactorExecutionResult::invalid();
+        }
+    };
+
+    let workflows = config.workflows.drain().map(|kvp| kvp.1).collect();
+    ReactorExecutionResult::valid(workflows)
+}
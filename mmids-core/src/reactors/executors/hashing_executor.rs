@@ -0,0 +1,289 @@
+use crate::reactors::executors::{
+    ReactorExecutionResult, ReactorExecutor, ReactorExecutorGenerator,
+};
+use crate::reactors::reactor::STREAM_NAME_PLACEHOLDER;
+use crate::workflows::definitions::WorkflowDefinition;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::error::Error;
+use thiserror::Error;
+use tracing::info;
+
+/// The number of points each template is given on the hash ring.  More points give a more even
+/// spread of streams across templates.
+const VIRTUAL_NODES_PER_TEMPLATE: usize = 100;
+
+/// Spreads streams across a fixed set of template workflows using consistent hashing.  When
+/// queried for a stream name the executor picks a template based on a hash of the stream name,
+/// and returns a copy of it named `<template_name>_<stream_name>`, with any `{stream}`
+/// placeholders in its step parameters replaced with the stream name (the same placeholder
+/// workflow name templates use).
+///
+/// Every stream name is considered valid.  Since the hash does not depend on anything that
+/// changes between runs, a stream name will always be placed in the same template across
+/// restarts of mmids, and adding or removing a template only moves the streams placed in it.
+pub struct HashingReactorExecutor {
+    templates: Vec<WorkflowDefinition>,
+
+    /// Points on the hash ring, sorted by hash, with the index of the template that owns each
+    ring: Vec<(u64, usize)>,
+}
+
+impl ReactorExecutor for HashingReactorExecutor {
+    fn get_workflow(&self, stream_name: String) -> BoxFuture<'static, ReactorExecutionResult> {
+        let template = self.get_template(&stream_name);
+        info!(
+            "Stream '{}' placed in template workflow '{}'",
+            stream_name, template.name
+        );
+
+        let workflow = create_workflow(template, &stream_name);
+        futures::future::ready(ReactorExecutionResult::valid(vec![workflow])).boxed()
+    }
+}
+
+impl HashingReactorExecutor {
+    fn new(mut templates: Vec<WorkflowDefinition>) -> Self {
+        // Sorted so ring collisions resolve the same way regardless of the order templates were read
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut ring = Vec::with_capacity(templates.len() * VIRTUAL_NODES_PER_TEMPLATE);
+        for (index, template) in templates.iter().enumerate() {
+            for node in 0..VIRTUAL_NODES_PER_TEMPLATE {
+                ring.push((hash(&format!("{}-{}", template.name, node)), index));
+            }
+        }
+
+        ring.sort_unstable();
+
+        HashingReactorExecutor { templates, ring }
+    }
+
+    /// Finds the template owning the first point on the ring at or after the stream name's hash
+    fn get_template(&self, stream_name: &str) -> &WorkflowDefinition {
+        let stream_hash = hash(stream_name);
+        let position = self.ring.partition_point(|(point, _)| *point < stream_hash);
+        let (_, index) = self.ring[position % self.ring.len()];
+
+        &self.templates[index]
+    }
+}
+
+pub struct HashingReactorExecutorGenerator {}
+
+#[derive(Error, Debug)]
+pub enum HashingReactorExecutorError {
+    #[error("The required parameter 'path' was not provided")]
+    PathParameterNotProvided,
+
+    #[error("Failed to read the template file '{0}'")]
+    TemplateFileReadFailed(String, #[source] std::io::Error),
+
+    #[error("The template file '{0}' was not a valid mmids config format")]
+    InvalidTemplateFile(String, #[source] crate::config::ConfigParseError),
+
+    #[error("The template file '{0}' does not contain any workflows")]
+    NoTemplates(String),
+}
+
+impl ReactorExecutorGenerator for HashingReactorExecutorGenerator {
+    fn generate(
+        &self,
+        parameters: &HashMap<String, Option<String>>,
+    ) -> Result<Box<dyn ReactorExecutor>, Box<dyn Error + Sync + Send>> {
+        let path = match parameters.get("path") {
+            Some(Some(path)) => path.trim().to_string(),
+            _ => {
+                return Err(Box::new(
+                    HashingReactorExecutorError::PathParameterNotProvided,
+                ))
+            }
+        };
+
+        let content = std::fs::read_to_string(&path).map_err(|error| {
+            HashingReactorExecutorError::TemplateFileReadFailed(path.clone(), error)
+        })?;
+
+        let mut config = crate::config::parse(content.as_str()).map_err(|error| {
+            HashingReactorExecutorError::InvalidTemplateFile(path.clone(), error)
+        })?;
+
+        let templates = config
+            .workflows
+            .drain()
+            .map(|kvp| kvp.1)
+            .collect::<Vec<_>>();
+
+        if templates.is_empty() {
+            return Err(Box::new(HashingReactorExecutorError::NoTemplates(path)));
+        }
+
+        Ok(Box::new(HashingReactorExecutor::new(templates)))
+    }
+}
+
+fn hash(value: &str) -> u64 {
+    let digest = Sha256::digest(value.as_bytes());
+    let mut bytes = [0_u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+
+    u64::from_be_bytes(bytes)
+}
+
+fn create_workflow(template: &WorkflowDefinition, stream_name: &str) -> WorkflowDefinition {
+    let mut workflow = template.clone();
+    workflow.name = format!("{}_{}", template.name, stream_name);
+    for step in &mut workflow.steps {
+        for value in step.parameters.values_mut().flatten() {
+            *value = value.replace(STREAM_NAME_PLACEHOLDER, stream_name);
+        }
+    }
+
+    workflow
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflows::definitions::{WorkflowStepDefinition, WorkflowStepType};
+
+    fn create_template(name: &str) -> WorkflowDefinition {
+        let mut parameters = HashMap::new();
+        parameters.insert(
+            "stream_key".to_string(),
+            Some(format!("{}-{{stream}}", name)),
+        );
+
+        WorkflowDefinition {
+            name: name.to_string(),
+            routed_by_reactor: true,
+            steps: vec![WorkflowStepDefinition {
+                step_type: WorkflowStepType("rtmp_push".to_string()),
+                parameters,
+            }],
+        }
+    }
+
+    fn create_executor(names: &[&str]) -> HashingReactorExecutor {
+        HashingReactorExecutor::new(names.iter().map(|name| create_template(name)).collect())
+    }
+
+    #[test]
+    fn same_stream_always_placed_in_same_template() {
+        let executor = create_executor(&["a", "b", "c"]);
+        let first = executor.get_template("abc").name.clone();
+
+        for _ in 0..10 {
+            assert_eq!(executor.get_template("abc").name, first);
+        }
+    }
+
+    #[test]
+    fn template_order_does_not_change_placement() {
+        let executor1 = create_executor(&["a", "b", "c"]);
+        let executor2 = create_executor(&["c", "a", "b"]);
+
+        for x in 0..100 {
+            let stream_name = format!("stream{}", x);
+            assert_eq!(
+                executor1.get_template(&stream_name).name,
+                executor2.get_template(&stream_name).name,
+                "Stream {} placed in different templates",
+                stream_name
+            );
+        }
+    }
+
+    #[test]
+    fn streams_spread_across_all_templates() {
+        let executor = create_executor(&["a", "b", "c"]);
+        let mut counts = HashMap::new();
+        for x in 0..300 {
+            let template = executor.get_template(&format!("stream{}", x));
+            *counts.entry(template.name.clone()).or_insert(0) += 1;
+        }
+
+        assert_eq!(counts.len(), 3, "Expected all templates to be used");
+    }
+
+    #[test]
+    fn adding_template_only_moves_streams_into_new_template() {
+        let executor1 = create_executor(&["a", "b", "c"]);
+        let executor2 = create_executor(&["a", "b", "c", "d"]);
+
+        for x in 0..100 {
+            let stream_name = format!("stream{}", x);
+            let before = &executor1.get_template(&stream_name).name;
+            let after = &executor2.get_template(&stream_name).name;
+            if before != after {
+                assert_eq!(
+                    after, "d",
+                    "Stream {} moved between old templates",
+                    stream_name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn workflow_named_after_template_and_stream() {
+        let template = create_template("a");
+        let workflow = create_workflow(&template, "abc");
+
+        assert_eq!(workflow.name, "a_abc");
+        assert!(
+            workflow.routed_by_reactor,
+            "Expected routed_by_reactor to be kept"
+        );
+    }
+
+    #[test]
+    fn stream_name_placeholder_replaced_in_parameters() {
+        let template = create_template("a");
+        let workflow = create_workflow(&template, "abc");
+
+        assert_eq!(
+            workflow.steps[0].parameters.get("stream_key"),
+            Some(&Some("a-abc".to_string()))
+        );
+    }
+
+    #[test]
+    fn quoted_placeholders_in_template_file_replaced_with_stream_name() {
+        let content = "
+workflow server1 routed_by_reactor {
+    rtmp_push target_url=rtmp://server1 app=live stream_key=\"{stream}\"
+}
+
+workflow server2 routed_by_reactor {
+    rtmp_push target_url=rtmp://server2 app=live stream_key=\"{stream}\"
+}
+";
+
+        let mut config = crate::config::parse(content).expect("Failed to parse template file");
+        let templates = config.workflows.drain().map(|kvp| kvp.1).collect();
+        let executor = HashingReactorExecutor::new(templates);
+        let template = executor.get_template("abc");
+        let workflow = create_workflow(template, "abc");
+
+        assert_eq!(
+            workflow.name,
+            format!("{}_abc", template.name),
+            "Unexpected workflow name"
+        );
+
+        assert_eq!(
+            workflow.steps[0].parameters.get("stream_key"),
+            Some(&Some("abc".to_string())),
+            "Unexpected stream key"
+        );
+    }
+
+    #[test]
+    fn generation_fails_without_path() {
+        let generator = HashingReactorExecutorGenerator {};
+        assert!(generator.generate(&HashMap::new()).is_err());
+    }
+}
@@ -1,8 +1,12 @@
+pub mod file_executor;
+pub mod hashing_executor;
 pub mod simple_http_executor;
 
+use crate::reactors::StreamMetadata;
 use crate::workflows::definitions::WorkflowDefinition;
 use futures::future::BoxFuture;
 use std::collections::HashMap;
+use std::time::Duration;
 use thiserror::Error;
 
 /// Contains the result from a reactor execution request about a stream
@@ -13,12 +17,28 @@ pub struct ReactorExecutionResult {
     /// If the stream was valid, what workflows were defined. it's valid for a stream to be valid
     /// without any workflows.
     pub workflows_returned: Vec<WorkflowDefinition>,
+
+    /// How long until the stream should be checked again.  If `None` the reactor's update
+    /// interval is used, while a zero duration disables periodic re-checks of the stream.
+    pub update_interval: Option<Duration>,
 }
 
 /// Performs a request for workflow information on behalf of a reactor
 pub trait ReactorExecutor {
     /// Requests the definition of a workflow based on a stream name
     fn get_workflow(&self, stream_name: String) -> BoxFuture<'static, ReactorExecutionResult>;
+
+    /// Requests the definition of a workflow based on a stream name, along with the stream's
+    /// metadata if it was known when the reactor was queried.  Executors that don't route based
+    /// on the format of the stream can rely on the default implementation, which ignores the
+    /// metadata.
+    fn get_workflow_with_metadata(
+        &self,
+        stream_name: String,
+        _metadata: Option<StreamMetadata>,
+    ) -> BoxFuture<'static, ReactorExecutionResult> {
+        self.get_workflow(stream_name)
+    }
 }
 
 /// Allows generating a reactor executor using parameters from a reactor definition
@@ -50,6 +70,7 @@ impl ReactorExecutionResult {
         ReactorExecutionResult {
             stream_is_valid: false,
             workflows_returned: Vec::new(),
+            update_interval: None,
         }
     }
 
@@ -57,8 +78,16 @@ impl ReactorExecutionResult {
         ReactorExecutionResult {
             stream_is_valid: true,
             workflows_returned: workflows,
+            update_interval: None,
         }
     }
+
+    /// Suggests how long until the stream should be checked again, instead of the reactor's
+    /// update interval.  A zero duration disables periodic re-checks of the stream.
+    pub fn with_update_interval(mut self, update_interval: Duration) -> Self {
+        self.update_interval = Some(update_interval);
+        self
+    }
 }
 
 impl ReactorExecutorFactory {
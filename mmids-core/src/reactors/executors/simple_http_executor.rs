@@ -2,6 +2,7 @@ use crate::config::MmidsConfig;
 use crate::reactors::executors::{
     ReactorExecutionResult, ReactorExecutor, ReactorExecutorGenerator,
 };
+use crate::reactors::StreamMetadata;
 use async_recursion::async_recursion;
 use futures::future::BoxFuture;
 use futures::FutureExt;
@@ -12,11 +13,15 @@ use std::collections::HashMap;
 use std::error::Error;
 use std::time::Duration;
 use thiserror::Error;
-use tracing::{error, info, instrument};
+use tracing::{error, info, instrument, warn};
 
 const MAX_RETRIES: u64 = 3;
 const RETRY_DELAY: u64 = 5;
 
+/// The setting a response can contain to override the reactor's update interval for the stream,
+/// in seconds
+const UPDATE_INTERVAL_SETTING: &str = "update_interval";
+
 /// Attempts to query for a workflow definition by performing a simple HTTP POST request to the
 /// configured URL. The request will contain a body with a json object containing the stream name to look
 /// up the workflow for. It's expecting a response of either 404 (denoting that no workflow exists
@@ -25,13 +30,28 @@ const RETRY_DELAY: u64 = 5;
 ///
 /// Zero workflows are allowed in a 200 status code.  This represents that the stream name is valid
 /// (and should be allowed) but it does not have an specific workflows tied to it.
+///
+/// If the stream's metadata was known when the reactor was queried, it's included in the request
+/// body so the server can pick workflows based on the format of the stream.
+///
+/// The response may also contain a settings node with an `update_interval` setting, which is the
+/// number of seconds until the stream should be checked again.  This overrides the reactor's
+/// update interval for that stream, and a value of zero stops the stream from being re-checked.
 pub struct SimpleHttpExecutor {
     url: String,
 }
 
 impl ReactorExecutor for SimpleHttpExecutor {
     fn get_workflow(&self, stream_name: String) -> BoxFuture<'static, ReactorExecutionResult> {
-        execute_simple_http_executor(self.url.clone(), stream_name).boxed()
+        self.get_workflow_with_metadata(stream_name, None)
+    }
+
+    fn get_workflow_with_metadata(
+        &self,
+        stream_name: String,
+        metadata: Option<StreamMetadata>,
+    ) -> BoxFuture<'static, ReactorExecutionResult> {
+        execute_simple_http_executor(self.url.clone(), stream_name, metadata).boxed()
     }
 }
 
@@ -44,8 +64,11 @@ pub enum SimpleHttpExecutorError {
 }
 
 #[derive(Serialize)]
-struct RequestContent {
-    stream_name: String,
+struct RequestContent<'a> {
+    stream_name: &'a str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<&'a StreamMetadata>,
 }
 
 impl ReactorExecutorGenerator for SimpleHttpExecutorGenerator {
@@ -63,20 +86,53 @@ impl ReactorExecutorGenerator for SimpleHttpExecutorGenerator {
 }
 
 #[instrument]
-async fn execute_simple_http_executor(url: String, stream_name: String) -> ReactorExecutionResult {
+async fn execute_simple_http_executor(
+    url: String,
+    stream_name: String,
+    metadata: Option<StreamMetadata>,
+) -> ReactorExecutionResult {
     info!("Querying {} for workflow for stream '{}'", url, stream_name);
-    let mut config = match execute_with_retry(&url, &stream_name, 0).await {
+    let mut config = match execute_with_retry(&url, &stream_name, &metadata, 0).await {
         Ok(config) => config,
         Err(_) => return ReactorExecutionResult::invalid(),
     };
 
+    let update_interval = get_update_interval(&config);
     let workflows = config.workflows.drain().map(|kvp| kvp.1).collect();
-    ReactorExecutionResult::valid(workflows)
+    let result = ReactorExecutionResult::valid(workflows);
+    match update_interval {
+        Some(update_interval) => result.with_update_interval(update_interval),
+        None => result,
+    }
 }
 
-fn build_request(url: &String, stream_name: &String) -> Result<Request<Body>, ()> {
+fn get_update_interval(config: &MmidsConfig) -> Option<Duration> {
+    match config.settings.get(UPDATE_INTERVAL_SETTING) {
+        Some(Some(value)) => match value.parse::<u64>() {
+            Ok(seconds) => Some(Duration::from_secs(seconds)),
+            Err(_) => {
+                warn!(
+                    "The response had an invalid {} value of '{}', so the reactor's update \
+                        interval will be used",
+                    UPDATE_INTERVAL_SETTING, value
+                );
+
+                None
+            }
+        },
+
+        _ => None,
+    }
+}
+
+fn build_request(
+    url: &String,
+    stream_name: &String,
+    metadata: &Option<StreamMetadata>,
+) -> Result<Request<Body>, ()> {
     let content = match serde_json::to_string_pretty(&RequestContent {
-        stream_name: stream_name.clone(),
+        stream_name,
+        metadata: metadata.as_ref(),
     }) {
         Ok(json) => json,
         Err(error) => {
@@ -107,6 +163,7 @@ fn build_request(url: &String, stream_name: &String) -> Result<Request<Body>, ()
 async fn execute_with_retry(
     url: &String,
     stream_name: &String,
+    metadata: &Option<StreamMetadata>,
     times_retried: u64,
 ) -> Result<MmidsConfig, ()> {
     if times_retried >= MAX_RETRIES {
@@ -120,7 +177,7 @@ async fn execute_with_retry(
         info!("Attempting retry #{}", times_retried);
     }
 
-    let request = match build_request(&url, &stream_name) {
+    let request = match build_request(&url, &stream_name, metadata) {
         Ok(request) => request,
         Err(_) => return Err(()), // retry wont' help building the request
     };
@@ -132,7 +189,7 @@ async fn execute_with_retry(
             Err(()) // Since we got a valid not found result, don't bother retrying
         }
     } else {
-        execute_with_retry(url, stream_name, times_retried + 1).await
+        execute_with_retry(url, stream_name, metadata, times_retried + 1).await
     }
 }
 
@@ -188,3 +245,58 @@ async fn execute_http_call(request: Request<Body>) -> Result<Option<MmidsConfig>
 
     return Ok(Some(config));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_interval_read_from_response_settings() {
+        let content = "
+settings {
+    update_interval 30
+}
+
+workflow abc {
+    step a
+}
+";
+
+        let config = crate::config::parse(content).unwrap();
+        let update_interval = get_update_interval(&config);
+
+        assert_eq!(
+            update_interval,
+            Some(Duration::from_secs(30)),
+            "Unexpected update interval"
+        );
+    }
+
+    #[test]
+    fn no_update_interval_when_response_does_not_specify_one() {
+        let content = "
+workflow abc {
+    step a
+}
+";
+
+        let config = crate::config::parse(content).unwrap();
+        let update_interval = get_update_interval(&config);
+
+        assert_eq!(update_interval, None, "Expected no update interval");
+    }
+
+    #[test]
+    fn invalid_update_interval_ignored() {
+        let content = "
+settings {
+    update_interval abc
+}
+";
+
+        let config = crate::config::parse(content).unwrap();
+        let update_interval = get_update_interval(&config);
+
+        assert_eq!(update_interval, None, "Expected no update interval");
+    }
+}
@@ -4,7 +4,7 @@
 use crate::event_hub::SubscriptionRequest;
 use crate::reactors::executors::{GenerationError, ReactorExecutorFactory};
 use crate::reactors::reactor::ReactorWorkflowUpdate;
-use crate::reactors::{start_reactor, ReactorDefinition, ReactorRequest};
+use crate::reactors::{start_reactor, ReactorDefinition, ReactorRequest, StreamMetadata};
 use futures::future::BoxFuture;
 use futures::stream::FuturesUnordered;
 use futures::{FutureExt, StreamExt};
@@ -30,6 +30,10 @@ pub enum ReactorManagerRequest {
         /// The name of the stream to look up a workflow for
         stream_name: String,
 
+        /// Details about the stream's format, if they are known, which the reactor's executor
+        /// can use to pick workflows
+        metadata: Option<StreamMetadata>,
+
         /// Channel that will be used to keep the created workflow alive. When the sender end of
         /// the channel is closed, that will be a signal to the reactor to remove the created
         /// workflow.
@@ -122,43 +126,56 @@ impl Actor {
                     return;
                 }
 
-                let generator = match self.executor_factory.get_generator(&definition.executor) {
-                    Ok(generator) => generator,
-                    Err(error) => {
-                        warn!(
-                            reactor_name = %definition.name,
-                            executor_name = %definition.executor,
-                            "Reactor {} is configured to use executor {}, but the factory \
-                            returned an error when trying to get it: {:?}",
-                            definition.name, definition.executor, error
-                        );
-
-                        let _ = response_channel
-                            .send(CreateReactorResult::ExecutorGeneratorError(error));
-                        return;
-                    }
-                };
-
-                let executor = match generator.generate(&definition.parameters) {
-                    Ok(executor) => executor,
-                    Err(error) => {
-                        warn!(
-                            reactor_name = %definition.name,
-                            "Executor failed to be generated for reactor {}: {:?}",
-                            definition.name, error
-                        );
-
-                        let _ = response_channel
-                            .send(CreateReactorResult::ExecutorReturnedError(error));
-                        return;
-                    }
-                };
+                // The primary executor is queried first, followed by the fallbacks in the order
+                // they were specified
+                let executor_names =
+                    std::iter::once(&definition.executor).chain(&definition.fallback_executors);
+
+                let mut executors = Vec::new();
+                for executor_name in executor_names {
+                    let generator = match self.executor_factory.get_generator(executor_name) {
+                        Ok(generator) => generator,
+                        Err(error) => {
+                            warn!(
+                                reactor_name = %definition.name,
+                                executor_name = %executor_name,
+                                "Reactor {} is configured to use executor {}, but the factory \
+                                returned an error when trying to get it: {:?}",
+                                definition.name, executor_name, error
+                            );
+
+                            let _ = response_channel
+                                .send(CreateReactorResult::ExecutorGeneratorError(error));
+                            return;
+                        }
+                    };
+
+                    let executor = match generator.generate(&definition.parameters) {
+                        Ok(executor) => executor,
+                        Err(error) => {
+                            warn!(
+                                reactor_name = %definition.name,
+                                executor_name = %executor_name,
+                                "Executor {} failed to be generated for reactor {}: {:?}",
+                                executor_name, definition.name, error
+                            );
+
+                            let _ = response_channel
+                                .send(CreateReactorResult::ExecutorReturnedError(error));
+                            return;
+                        }
+                    };
+
+                    executors.push(executor);
+                }
 
                 let reactor = start_reactor(
                     definition.name.clone(),
-                    executor,
+                    executors,
                     self.event_hub_subscriber.clone(),
                     definition.update_interval,
+                    definition.workflow_name_template.clone(),
+                    definition.minimum_workflow_lifetime,
                 );
 
                 self.reactors.insert(definition.name, reactor);
@@ -169,6 +186,7 @@ impl Actor {
             ReactorManagerRequest::CreateWorkflowForStreamName {
                 reactor_name,
                 stream_name,
+                metadata,
                 response_channel,
             } => {
                 let reactor = match self.reactors.get(&reactor_name) {
@@ -183,6 +201,7 @@ impl Actor {
                         let _ = response_channel.send(ReactorWorkflowUpdate {
                             is_valid: false,
                             routable_workflow_names: HashSet::new(),
+                            definition_hash: None,
                         });
 
                         return;
@@ -191,6 +210,7 @@ impl Actor {
 
                 let _ = reactor.send(ReactorRequest::CreateWorkflowNameForStream {
                     stream_name,
+                    metadata,
                     response_channel,
                 });
             }
@@ -231,8 +251,11 @@ mod tests {
                 definition: ReactorDefinition {
                     name: "reactor".to_string(),
                     update_interval: Duration::new(0, 0),
+                    workflow_name_template: None,
+                    minimum_workflow_lifetime: Duration::new(0, 0),
                     parameters,
                     executor: "exe".to_string(),
+                    fallback_executors: Vec::new(),
                 },
                 response_channel: sender,
             })
@@ -259,8 +282,11 @@ mod tests {
                 definition: ReactorDefinition {
                     name: "reactor".to_string(),
                     update_interval: Duration::new(0, 0),
+                    workflow_name_template: None,
+                    minimum_workflow_lifetime: Duration::new(0, 0),
                     parameters: parameters.clone(),
                     executor: "exe".to_string(),
+                    fallback_executors: Vec::new(),
                 },
                 response_channel: sender,
             })
@@ -275,8 +301,11 @@ mod tests {
                 definition: ReactorDefinition {
                     name: "reactor".to_string(),
                     update_interval: Duration::new(0, 0),
+                    workflow_name_template: None,
+                    minimum_workflow_lifetime: Duration::new(0, 0),
                     parameters: parameters.clone(),
                     executor: "exe".to_string(),
+                    fallback_executors: Vec::new(),
                 },
                 response_channel: sender,
             })
@@ -303,8 +332,11 @@ mod tests {
                 definition: ReactorDefinition {
                     name: "reactor".to_string(),
                     update_interval: Duration::new(0, 0),
+                    workflow_name_template: None,
+                    minimum_workflow_lifetime: Duration::new(0, 0),
                     parameters,
                     executor: "exe".to_string(),
+                    fallback_executors: Vec::new(),
                 },
                 response_channel: sender,
             })
@@ -331,8 +363,11 @@ mod tests {
                 definition: ReactorDefinition {
                     name: "reactor".to_string(),
                     update_interval: Duration::new(0, 0),
+                    workflow_name_template: None,
+                    minimum_workflow_lifetime: Duration::new(0, 0),
                     parameters,
                     executor: "exe2".to_string(),
+                    fallback_executors: Vec::new(),
                 },
                 response_channel: sender,
             })
@@ -349,6 +384,41 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn error_when_fallback_executor_generator_not_found() {
+        let context = TestContext::new();
+
+        let mut parameters = HashMap::new();
+        parameters.insert("abc".to_string(), None);
+
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(ReactorManagerRequest::CreateReactor {
+                definition: ReactorDefinition {
+                    name: "reactor".to_string(),
+                    update_interval: Duration::new(0, 0),
+                    workflow_name_template: None,
+                    minimum_workflow_lifetime: Duration::new(0, 0),
+                    parameters,
+                    executor: "exe".to_string(),
+                    fallback_executors: vec!["exe".to_string(), "exe2".to_string()],
+                },
+                response_channel: sender,
+            })
+            .expect("Failed to send create request");
+
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        match response {
+            CreateReactorResult::ExecutorGeneratorError(
+                GenerationError::NoRegisteredGenerator(name),
+            ) => {
+                assert_eq!(&name, "exe2", "Error contained an unexpected name");
+            }
+            response => panic!("Expected a generator error, instead got {:?}", response),
+        }
+    }
+
     #[tokio::test]
     async fn create_workflow_request_sends_to_correct_reactor() {
         let context = TestContext::new();
@@ -363,8 +433,11 @@ mod tests {
                 definition: ReactorDefinition {
                     name: "reactor".to_string(),
                     update_interval: Duration::new(0, 0),
+                    workflow_name_template: None,
+                    minimum_workflow_lifetime: Duration::new(0, 0),
                     parameters,
                     executor: "exe".to_string(),
+                    fallback_executors: Vec::new(),
                 },
                 response_channel: sender,
             })
@@ -382,6 +455,7 @@ mod tests {
             .send(ReactorManagerRequest::CreateWorkflowForStreamName {
                 reactor_name: "reactor".to_string(),
                 stream_name: "def".to_string(),
+                metadata: None,
                 response_channel: sender,
             })
             .expect("Failed to send create workflow request");
@@ -407,8 +481,11 @@ mod tests {
                 definition: ReactorDefinition {
                     name: "reactor".to_string(),
                     update_interval: Duration::new(0, 0),
+                    workflow_name_template: None,
+                    minimum_workflow_lifetime: Duration::new(0, 0),
                     parameters,
                     executor: "exe".to_string(),
+                    fallback_executors: Vec::new(),
                 },
                 response_channel: sender,
             })
@@ -426,6 +503,7 @@ mod tests {
             .send(ReactorManagerRequest::CreateWorkflowForStreamName {
                 reactor_name: "reactor2".to_string(),
                 stream_name: "def".to_string(),
+                metadata: None,
                 response_channel: sender,
             })
             .expect("Failed to send create workflow request");
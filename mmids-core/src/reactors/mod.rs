@@ -10,6 +10,7 @@ pub mod executors;
 pub mod manager;
 mod reactor;
 
+use serde::Serialize;
 use std::collections::HashMap;
 use std::time::Duration;
 
@@ -25,12 +26,52 @@ pub struct ReactorDefinition {
     /// The name of the query executor this reactor should use to perform queries
     pub executor: String,
 
+    /// Names of executors to query, in order, when the primary executor does not consider a
+    /// stream name valid.  Each fallback executor is created with the reactor's parameters.
+    pub fallback_executors: Vec<String>,
+
     /// How many seconds the reactor should wait before it re-runs the executor and gets the latest
     /// version of the corresponding workflow definition. An update interval of 0 (or a value not
     /// specified) means it will never update.
     pub update_interval: Duration,
 
+    /// An optional template for the names of workflows the reactor manages, with `{stream}`
+    /// replaced by the stream name and `{workflow}` by the name the executor returned.  When not
+    /// specified the executor's workflow names are used as is.
+    pub workflow_name_template: Option<String>,
+
+    /// How long workflows the reactor creates are kept alive for at a minimum, even if the
+    /// executor stops considering the stream valid.  A duration of 0 means workflows are torn
+    /// down as soon as the executor no longer considers the stream valid.
+    pub minimum_workflow_lifetime: Duration,
+
     /// Key value pairs used to instruct the reactor's executor. Valid values here are specific
     /// to the executor that was picked.
     pub parameters: HashMap<String, Option<String>>,
 }
+
+/// Details about the format of a stream, taken from the first metadata notification seen for it.
+/// Reactor executors can use this to pick workflows based on the incoming media (e.g. a transcode
+/// profile based on the source resolution).  Values the stream's metadata did not contain are
+/// `None`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct StreamMetadata {
+    pub video_codec: Option<String>,
+    pub video_width: Option<u32>,
+    pub video_height: Option<u32>,
+    pub video_frame_rate: Option<f32>,
+    pub audio_codec: Option<String>,
+}
+
+impl StreamMetadata {
+    /// Creates stream metadata from the values of a metadata media notification
+    pub fn from_metadata(values: &HashMap<String, String>) -> Self {
+        StreamMetadata {
+            video_codec: values.get("videocodecid").cloned(),
+            video_width: values.get("width").and_then(|x| x.parse().ok()),
+            video_height: values.get("height").and_then(|x| x.parse().ok()),
+            video_frame_rate: values.get("framerate").and_then(|x| x.parse().ok()),
+            audio_codec: values.get("audiocodecid").cloned(),
+        }
+    }
+}
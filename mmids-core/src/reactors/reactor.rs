@@ -1,12 +1,14 @@
 use crate::event_hub::{SubscriptionRequest, WorkflowManagerEvent};
 use crate::reactors::executors::{ReactorExecutionResult, ReactorExecutor};
+use crate::reactors::StreamMetadata;
 use crate::workflows::definitions::WorkflowDefinition;
 use crate::workflows::manager::{WorkflowManagerRequest, WorkflowManagerRequestOperation};
 use futures::future::BoxFuture;
 use futures::stream::FuturesUnordered;
 use futures::{FutureExt, StreamExt};
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tracing::{info, instrument, warn};
 
@@ -18,6 +20,11 @@ pub enum ReactorRequest {
         /// Name of the stream to get a workflow for
         stream_name: String,
 
+        /// Details about the stream's format, if they were known when the request was made.
+        /// These are passed to the executors so they can pick workflows based on the incoming
+        /// media.
+        metadata: Option<StreamMetadata>,
+
         /// The channel to send a response for. This channel will not only be used for the
         /// initial response, but updates will be sent any time the reactor detects changes.
         response_channel: UnboundedSender<ReactorWorkflowUpdate>,
@@ -32,21 +39,48 @@ pub struct ReactorWorkflowUpdate {
 
     /// The names of workflows that the reactor expects streams to be routed to.
     pub routable_workflow_names: HashSet<String>,
+
+    /// A hash of all the workflow definitions the reactor is managing for the stream, which
+    /// changes whenever any of those definitions change.  `None` if the stream is not valid.
+    pub definition_hash: Option<String>,
 }
 
+/// The placeholder in a workflow name template that's replaced with the stream name
+pub const STREAM_NAME_PLACEHOLDER: &str = "{stream}";
+
+/// The placeholder in a workflow name template that's replaced with the name of the workflow
+/// returned by the executor
+pub const WORKFLOW_NAME_PLACEHOLDER: &str = "{workflow}";
+
+/// Starts a new reactor.  Executors are queried in the order given, with later executors only
+/// being queried for a stream name if all earlier executors considered the stream name invalid.
+///
+/// If a name template is given, every workflow returned by the executors is renamed by replacing
+/// `{stream}` in the template with the stream name and `{workflow}` with the name the executor
+/// returned (e.g. `ingest_{stream}`).  The templated name is what the reactor upserts, stops and
+/// reports as routable.
+///
+/// Once workflows are created for a stream they are kept for at least the minimum workflow
+/// lifetime, even if the executors stop considering the stream valid.  Teardown is deferred until
+/// the stream is re-queried after the lifetime has passed, so executors that flap between valid
+/// and invalid results don't cause workflows to be rapidly started and stopped.
 pub fn start_reactor(
     name: String,
-    executor: Box<dyn ReactorExecutor>,
+    executors: Vec<Box<dyn ReactorExecutor>>,
     event_hub_subscriber: UnboundedSender<SubscriptionRequest>,
     update_interval: Duration,
+    name_template: Option<String>,
+    minimum_workflow_lifetime: Duration,
 ) -> UnboundedSender<ReactorRequest> {
     let (sender, receiver) = unbounded_channel();
     let actor = Actor::new(
         name,
         receiver,
-        executor,
+        executors,
         event_hub_subscriber,
         update_interval,
+        name_template,
+        minimum_workflow_lifetime,
     );
     tokio::spawn(actor.run());
 
@@ -60,6 +94,7 @@ enum FutureResult {
     RequestReceived(ReactorRequest, UnboundedReceiver<ReactorRequest>),
     ExecutorResponseReceived {
         stream_name: String,
+        executor_index: usize,
         result: ReactorExecutionResult,
     },
 
@@ -79,16 +114,23 @@ enum FutureResult {
 
 struct CachedWorkflows {
     definitions: Vec<WorkflowDefinition>,
+    definition_hash: String,
+
+    /// When workflows were first created for the stream
+    created_at: Instant,
 }
 
 struct Actor {
     name: String,
-    executor: Box<dyn ReactorExecutor>,
+    executors: Vec<Box<dyn ReactorExecutor>>,
     futures: FuturesUnordered<BoxFuture<'static, FutureResult>>,
     workflow_manager: Option<UnboundedSender<WorkflowManagerRequest>>,
     cached_workflows_for_stream_name: HashMap<String, CachedWorkflows>,
     update_interval: Duration,
+    name_template: Option<String>,
+    minimum_workflow_lifetime: Duration,
     stream_response_channels: HashMap<String, Vec<UnboundedSender<ReactorWorkflowUpdate>>>,
+    metadata_for_stream_name: HashMap<String, StreamMetadata>,
 }
 
 unsafe impl Send for Actor {}
@@ -97,9 +139,11 @@ impl Actor {
     fn new(
         name: String,
         receiver: UnboundedReceiver<ReactorRequest>,
-        executor: Box<dyn ReactorExecutor>,
+        executors: Vec<Box<dyn ReactorExecutor>>,
         event_hub_subscriber: UnboundedSender<SubscriptionRequest>,
         update_interval: Duration,
+        name_template: Option<String>,
+        minimum_workflow_lifetime: Duration,
     ) -> Self {
         let futures = FuturesUnordered::new();
         futures.push(wait_for_request(receiver).boxed());
@@ -113,12 +157,15 @@ impl Actor {
 
         Actor {
             name,
-            executor,
+            executors,
             futures,
             workflow_manager: None,
             cached_workflows_for_stream_name: HashMap::new(),
             update_interval,
+            name_template,
+            minimum_workflow_lifetime,
             stream_response_channels: HashMap::new(),
+            metadata_for_stream_name: HashMap::new(),
         }
     }
 
@@ -154,9 +201,30 @@ impl Actor {
 
                 FutureResult::ExecutorResponseReceived {
                     stream_name,
-                    result: workflow,
+                    executor_index,
+                    result,
                 } => {
-                    self.handle_executor_response(stream_name, workflow);
+                    if !result.stream_is_valid && executor_index + 1 < self.executors.len() {
+                        info!(
+                            stream_name = %stream_name,
+                            executor_index = %executor_index,
+                            "Executor {} did not consider stream '{}' valid, falling back to the next executor",
+                            executor_index, stream_name
+                        );
+
+                        self.query_executors(stream_name, executor_index + 1);
+                    } else {
+                        if result.stream_is_valid {
+                            info!(
+                                stream_name = %stream_name,
+                                executor_index = %executor_index,
+                                "Executor {} satisfied the workflow lookup for stream '{}'",
+                                executor_index, stream_name
+                            );
+                        }
+
+                        self.handle_executor_response(stream_name, result);
+                    }
                 }
 
                 FutureResult::UpdateStreamNameRequested { stream_name } => {
@@ -164,9 +232,7 @@ impl Actor {
                         .cached_workflows_for_stream_name
                         .contains_key(&stream_name)
                     {
-                        let future = self.executor.get_workflow(stream_name.clone());
-                        self.futures
-                            .push(wait_for_executor_response(stream_name, future).boxed());
+                        self.query_executors(stream_name, 0);
                     }
                 }
 
@@ -186,6 +252,7 @@ impl Actor {
         match request {
             ReactorRequest::CreateWorkflowNameForStream {
                 stream_name,
+                metadata,
                 response_channel,
             } => {
                 info!(
@@ -193,6 +260,14 @@ impl Actor {
                     "Received request to get workflow for stream '{}'", stream_name
                 );
 
+                // Only the earliest metadata is kept, so re-executions for the stream stay
+                // consistent with the format the workflows were originally picked for
+                if let Some(metadata) = metadata {
+                    self.metadata_for_stream_name
+                        .entry(stream_name.clone())
+                        .or_insert(metadata);
+                }
+
                 let channels = self
                     .stream_response_channels
                     .entry(stream_name.clone())
@@ -209,11 +284,10 @@ impl Actor {
                             .filter(|w| w.routed_by_reactor)
                             .map(|w| w.name.clone())
                             .collect::<HashSet<_>>(),
+                        definition_hash: Some(cache.definition_hash.clone()),
                     });
                 } else {
-                    let future = self.executor.get_workflow(stream_name.clone());
-                    self.futures
-                        .push(wait_for_executor_response(stream_name.clone(), future).boxed());
+                    self.query_executors(stream_name.clone(), 0);
                 }
 
                 self.futures.push(
@@ -223,7 +297,35 @@ impl Actor {
         }
     }
 
-    fn handle_executor_response(&mut self, stream_name: String, result: ReactorExecutionResult) {
+    fn query_executors(&mut self, stream_name: String, executor_index: usize) {
+        match self.executors.get(executor_index) {
+            Some(executor) => {
+                let metadata = self.metadata_for_stream_name.get(&stream_name).cloned();
+                let future = executor.get_workflow_with_metadata(stream_name.clone(), metadata);
+                self.futures
+                    .push(wait_for_executor_response(stream_name, executor_index, future).boxed());
+            }
+
+            None => {
+                // No executors left to ask
+                self.handle_executor_response(stream_name, ReactorExecutionResult::invalid());
+            }
+        }
+    }
+
+    fn handle_executor_response(
+        &mut self,
+        stream_name: String,
+        mut result: ReactorExecutionResult,
+    ) {
+        // Templating happens before anything else looks at the workflows, so the same name is
+        // used for upserts, the cache (and therefore stops), and the routable workflow names.
+        if let Some(template) = &self.name_template {
+            apply_name_template(template, &stream_name, &mut result.workflows_returned);
+        }
+
+        // The executor can suggest a different interval for volatile or stable streams
+        let update_interval = result.update_interval.unwrap_or(self.update_interval);
         if let Some(channels) = self.stream_response_channels.get(&stream_name) {
             let routed_workflow_names = result
                 .workflows_returned
@@ -240,7 +342,31 @@ impl Actor {
                 result.workflows_returned.len(), routed_workflow_names.len(), stream_name,
             );
 
+            let mut has_changes = true;
+            let mut definition_hash = None;
             if !result.stream_is_valid {
+                if let Some(remaining) = self.get_remaining_minimum_lifetime(&stream_name) {
+                    info!(
+                        stream_name = %stream_name,
+                        "Stream '{}' is no longer valid, but its workflows have not been alive for \
+                            the minimum lifetime of {:?}.  Teardown deferred for {:?}",
+                        stream_name, self.minimum_workflow_lifetime, remaining,
+                    );
+
+                    // Re-check once the lifetime has passed, or sooner if the stream would
+                    // normally be updated before then, in case the stream becomes valid again
+                    let wait_time = if update_interval.is_zero() {
+                        remaining
+                    } else {
+                        remaining.min(update_interval)
+                    };
+
+                    self.futures
+                        .push(wait_for_update_interval(stream_name, wait_time).boxed());
+
+                    return;
+                }
+
                 if let Some(cache) = self.cached_workflows_for_stream_name.remove(&stream_name) {
                     // Since we had some workflows cached, and now the external service isn't giving us
                     // any workflows, that means this stream name is no longer valid.
@@ -253,6 +379,7 @@ impl Actor {
                                 ),
                                 operation: WorkflowManagerRequestOperation::StopWorkflow {
                                     name: workflow.name,
+                                    response_channel: None,
                                 },
                             });
                         }
@@ -267,9 +394,47 @@ impl Actor {
                     );
                 }
 
-                // Upsert all returned workflows
+                let cached_definitions = self
+                    .cached_workflows_for_stream_name
+                    .get(&stream_name)
+                    .map(|cache| {
+                        cache
+                            .definitions
+                            .iter()
+                            .map(|w| (w.name.clone(), w))
+                            .collect::<HashMap<_, _>>()
+                    });
+
+                // Only workflows that are new or have changed since the last execution need to
+                // be upserted, otherwise we are just causing needless churn
+                let changed_workflows = result
+                    .workflows_returned
+                    .iter()
+                    .filter(|w| match &cached_definitions {
+                        Some(cached) => cached.get(&w.name) != Some(w),
+                        None => true,
+                    })
+                    .collect::<Vec<_>>();
+
+                has_changes = match &cached_definitions {
+                    Some(cached) => {
+                        !changed_workflows.is_empty()
+                            || cached.len() != result.workflows_returned.len()
+                    }
+
+                    None => true,
+                };
+
+                if !has_changes {
+                    info!(
+                        stream_name = %stream_name,
+                        "No workflow changes for stream '{}'", stream_name
+                    );
+                }
+
+                // Upsert all new or changed workflows
                 if let Some(manager) = &self.workflow_manager {
-                    for workflow in &result.workflows_returned {
+                    for workflow in changed_workflows {
                         let _ = manager.send(WorkflowManagerRequest {
                             request_id: format!(
                                 "reactor_{}_stream_{}_update",
@@ -288,8 +453,19 @@ impl Actor {
                     .map(|w| w.name.clone())
                     .collect::<HashSet<_>>();
 
+                let hash = get_definitions_hash(&result.workflows_returned);
+                definition_hash = Some(hash.clone());
+
+                let created_at = self
+                    .cached_workflows_for_stream_name
+                    .get(&stream_name)
+                    .map(|cache| cache.created_at)
+                    .unwrap_or_else(Instant::now);
+
                 let new_cache = CachedWorkflows {
                     definitions: result.workflows_returned,
+                    definition_hash: hash,
+                    created_at,
                 };
 
                 if let Some(old_cache) = self
@@ -307,6 +483,7 @@ impl Actor {
                                     ),
                                     operation: WorkflowManagerRequestOperation::StopWorkflow {
                                         name: workflow.name,
+                                        response_channel: None,
                                     },
                                 });
                             }
@@ -315,20 +492,38 @@ impl Actor {
                 }
             }
 
-            for channel in channels {
-                let _ = channel.send(ReactorWorkflowUpdate {
-                    is_valid: result.stream_is_valid,
-                    routable_workflow_names: routed_workflow_names.clone(),
-                });
+            if has_changes {
+                for channel in channels {
+                    let _ = channel.send(ReactorWorkflowUpdate {
+                        is_valid: result.stream_is_valid,
+                        routable_workflow_names: routed_workflow_names.clone(),
+                        definition_hash: definition_hash.clone(),
+                    });
+                }
             }
 
-            if !self.update_interval.is_zero() {
+            if !update_interval.is_zero() {
                 self.futures
-                    .push(wait_for_update_interval(stream_name, self.update_interval).boxed());
+                    .push(wait_for_update_interval(stream_name, update_interval).boxed());
             }
         }
     }
 
+    /// Gets how much longer the stream's workflows need to be kept alive before they can be torn
+    /// down.  `None` if the stream has no workflows or they have lived past the minimum lifetime.
+    fn get_remaining_minimum_lifetime(&self, stream_name: &str) -> Option<Duration> {
+        let cache = self.cached_workflows_for_stream_name.get(stream_name)?;
+        let remaining = self
+            .minimum_workflow_lifetime
+            .checked_sub(cache.created_at.elapsed())?;
+
+        if remaining.is_zero() {
+            None
+        } else {
+            Some(remaining)
+        }
+    }
+
     fn handle_workflow_manager_event(&mut self, event: WorkflowManagerEvent) {
         match event {
             WorkflowManagerEvent::WorkflowManagerRegistered { channel } => {
@@ -368,6 +563,7 @@ impl Actor {
                 );
 
                 self.stream_response_channels.remove(&stream_name);
+                self.metadata_for_stream_name.remove(&stream_name);
 
                 if let Some(channel) = &self.workflow_manager {
                     if let Some(cache) = self.cached_workflows_for_stream_name.remove(&stream_name)
@@ -380,6 +576,7 @@ impl Actor {
                                 ),
                                 operation: WorkflowManagerRequestOperation::StopWorkflow {
                                     name: workflow.name,
+                                    response_channel: None,
                                 },
                             });
                         }
@@ -396,6 +593,45 @@ impl Actor {
     }
 }
 
+/// Renames the workflows returned for a stream based on the reactor's workflow name template
+fn apply_name_template(template: &str, stream_name: &str, workflows: &mut [WorkflowDefinition]) {
+    for workflow in workflows.iter_mut() {
+        workflow.name = template
+            .replace(WORKFLOW_NAME_PLACEHOLDER, &workflow.name)
+            .replace(STREAM_NAME_PLACEHOLDER, stream_name);
+    }
+
+    let unique_names = workflows.iter().map(|w| &w.name).collect::<HashSet<_>>();
+    if unique_names.len() < workflows.len() {
+        warn!(
+            stream_name = %stream_name,
+            template = %template,
+            "Workflow name template '{}' produced duplicate workflow names for stream '{}'. \
+                Include '{}' in the template when executors return multiple workflows",
+            template, stream_name, WORKFLOW_NAME_PLACEHOLDER
+        );
+    }
+}
+
+/// Combines the content hashes of all workflows returned for a stream into a single hash.  The
+/// hashes are sorted first, so executors returning the same workflows in a different order does
+/// not change the result.
+fn get_definitions_hash(definitions: &[WorkflowDefinition]) -> String {
+    let mut hashes = definitions
+        .iter()
+        .map(|definition| definition.get_content_hash())
+        .collect::<Vec<_>>();
+
+    hashes.sort();
+
+    let mut hasher = Sha256::new();
+    for hash in hashes {
+        hasher.update(hash.as_bytes());
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
 async fn wait_for_request(mut receiver: UnboundedReceiver<ReactorRequest>) -> FutureResult {
     match receiver.recv().await {
         Some(request) => FutureResult::RequestReceived(request, receiver),
@@ -405,12 +641,14 @@ async fn wait_for_request(mut receiver: UnboundedReceiver<ReactorRequest>) -> Fu
 
 async fn wait_for_executor_response(
     stream_name: String,
+    executor_index: usize,
     future: BoxFuture<'static, ReactorExecutionResult>,
 ) -> FutureResult {
     let result = future.await;
     FutureResult::ExecutorResponseReceived {
         stream_name,
-        result: result,
+        executor_index,
+        result,
     }
 }
 
@@ -448,6 +686,7 @@ mod tests {
     use super::*;
     use crate::test_utils;
     use crate::workflows::definitions::{WorkflowStepDefinition, WorkflowStepType};
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use tokio::time::timeout;
 
     struct TestContext {
@@ -462,10 +701,90 @@ mod tests {
         workflows: Vec<WorkflowDefinition>,
     }
 
+    /// Executor that returns the test workflows on the first call, and a modified "first"
+    /// workflow on all subsequent calls
+    struct ChangingTestExecutor {
+        expected_name: String,
+        call_count: AtomicUsize,
+        update_interval: Option<Duration>,
+    }
+
+    /// Executor that returns the test workflows on the first call, and considers the stream
+    /// invalid on all subsequent calls
+    struct ValidOnceTestExecutor {
+        call_count: AtomicUsize,
+    }
+
+    /// Executor that alternates between returning the test workflows and considering the stream
+    /// invalid, starting with the test workflows
+    struct FlappingTestExecutor {
+        call_count: AtomicUsize,
+    }
+
+    /// Executor that considers all streams valid, and reports the metadata of each query
+    struct MetadataTestExecutor {
+        metadata_sender: UnboundedSender<Option<StreamMetadata>>,
+    }
+
     impl TestContext {
-        async fn new(name: String, duration: Duration, executor: TestExecutor) -> Self {
+        async fn new(
+            name: String,
+            duration: Duration,
+            executor: impl ReactorExecutor + 'static,
+        ) -> Self {
+            Self::with_executors(name, duration, vec![Box::new(executor)]).await
+        }
+
+        async fn with_executors(
+            name: String,
+            duration: Duration,
+            executors: Vec<Box<dyn ReactorExecutor>>,
+        ) -> Self {
+            Self::with_name_template(name, duration, executors, None).await
+        }
+
+        async fn with_name_template(
+            name: String,
+            duration: Duration,
+            executors: Vec<Box<dyn ReactorExecutor>>,
+            name_template: Option<String>,
+        ) -> Self {
+            Self::with_options(
+                name,
+                duration,
+                executors,
+                name_template,
+                Duration::new(0, 0),
+            )
+            .await
+        }
+
+        async fn with_minimum_workflow_lifetime(
+            name: String,
+            duration: Duration,
+            executor: impl ReactorExecutor + 'static,
+            minimum_workflow_lifetime: Duration,
+        ) -> Self {
+            let executors: Vec<Box<dyn ReactorExecutor>> = vec![Box::new(executor)];
+            Self::with_options(name, duration, executors, None, minimum_workflow_lifetime).await
+        }
+
+        async fn with_options(
+            name: String,
+            duration: Duration,
+            executors: Vec<Box<dyn ReactorExecutor>>,
+            name_template: Option<String>,
+            minimum_workflow_lifetime: Duration,
+        ) -> Self {
             let (sender, mut sub_receiver) = unbounded_channel();
-            let reactor = start_reactor(name, Box::new(executor), sender, duration);
+            let reactor = start_reactor(
+                name,
+                executors,
+                sender,
+                duration,
+                name_template,
+                minimum_workflow_lifetime,
+            );
 
             let response = test_utils::expect_mpsc_response(&mut sub_receiver).await;
             let response_channel = match response {
@@ -506,6 +825,80 @@ mod tests {
         }
     }
 
+    impl ReactorExecutor for ChangingTestExecutor {
+        fn get_workflow(&self, stream_name: String) -> BoxFuture<'static, ReactorExecutionResult> {
+            if self.expected_name != stream_name {
+                return async { ReactorExecutionResult::invalid() }.boxed();
+            }
+
+            let mut workflows = get_test_workflows();
+            if self.call_count.fetch_add(1, Ordering::SeqCst) > 0 {
+                for workflow in &mut workflows {
+                    if &workflow.name == "first" {
+                        workflow.steps.push(WorkflowStepDefinition {
+                            step_type: WorkflowStepType("g".to_string()),
+                            parameters: HashMap::new(),
+                        });
+                    }
+                }
+            }
+
+            let mut result = ReactorExecutionResult::valid(workflows);
+            if let Some(update_interval) = self.update_interval {
+                result = result.with_update_interval(update_interval);
+            }
+
+            async { result }.boxed()
+        }
+    }
+
+    impl ReactorExecutor for ValidOnceTestExecutor {
+        fn get_workflow(&self, _stream_name: String) -> BoxFuture<'static, ReactorExecutionResult> {
+            if self.call_count.fetch_add(1, Ordering::SeqCst) > 0 {
+                return async { ReactorExecutionResult::invalid() }.boxed();
+            }
+
+            let workflows = get_test_workflows();
+            async { ReactorExecutionResult::valid(workflows) }.boxed()
+        }
+    }
+
+    impl ReactorExecutor for FlappingTestExecutor {
+        fn get_workflow(&self, _stream_name: String) -> BoxFuture<'static, ReactorExecutionResult> {
+            if self.call_count.fetch_add(1, Ordering::SeqCst) % 2 == 1 {
+                return async { ReactorExecutionResult::invalid() }.boxed();
+            }
+
+            let workflows = get_test_workflows();
+            async { ReactorExecutionResult::valid(workflows) }.boxed()
+        }
+    }
+
+    impl ReactorExecutor for MetadataTestExecutor {
+        fn get_workflow(&self, stream_name: String) -> BoxFuture<'static, ReactorExecutionResult> {
+            self.get_workflow_with_metadata(stream_name, None)
+        }
+
+        fn get_workflow_with_metadata(
+            &self,
+            _stream_name: String,
+            metadata: Option<StreamMetadata>,
+        ) -> BoxFuture<'static, ReactorExecutionResult> {
+            let _ = self.metadata_sender.send(metadata);
+            async { ReactorExecutionResult::valid(Vec::new()) }.boxed()
+        }
+    }
+
+    fn get_test_metadata() -> StreamMetadata {
+        StreamMetadata {
+            video_codec: Some("avc1".to_string()),
+            video_width: Some(1920),
+            video_height: Some(1080),
+            video_frame_rate: Some(30.0),
+            audio_codec: Some("mp4a".to_string()),
+        }
+    }
+
     #[tokio::test]
     async fn can_get_routable_workflows_from_executor() {
         let executor = TestExecutor {
@@ -520,6 +913,7 @@ mod tests {
             .reactor
             .send(ReactorRequest::CreateWorkflowNameForStream {
                 stream_name: "stream".to_string(),
+                metadata: None,
                 response_channel: sender,
             })
             .expect("Channel closed");
@@ -556,12 +950,214 @@ mod tests {
             .reactor
             .send(ReactorRequest::CreateWorkflowNameForStream {
                 stream_name: "invalid".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let update = test_utils::expect_mpsc_response(&mut receiver).await;
+
+        assert!(!update.is_valid, "Expected is valid to be false");
+        assert_eq!(
+            update.routable_workflow_names.len(),
+            0,
+            "Expected no routable workflow names"
+        );
+        assert_eq!(update.definition_hash, None, "Expected no definition hash");
+    }
+
+    #[tokio::test]
+    async fn definition_hash_included_for_valid_stream() {
+        let executor = TestExecutor {
+            expected_name: "stream".to_string(),
+            workflows: get_test_workflows(),
+        };
+
+        let context =
+            TestContext::new("reactor".to_string(), Duration::from_millis(0), executor).await;
+        let (sender, mut receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let update = test_utils::expect_mpsc_response(&mut receiver).await;
+        assert_eq!(
+            update.definition_hash,
+            Some(get_definitions_hash(&get_test_workflows())),
+            "Unexpected definition hash"
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_workflows_sent_with_same_definition_hash() {
+        let executor = TestExecutor {
+            expected_name: "stream".to_string(),
+            workflows: get_test_workflows(),
+        };
+
+        let context =
+            TestContext::new("reactor".to_string(), Duration::from_millis(0), executor).await;
+        let (sender, mut receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let first_update = test_utils::expect_mpsc_response(&mut receiver).await;
+
+        let (sender, mut receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let second_update = test_utils::expect_mpsc_response(&mut receiver).await;
+        assert!(
+            first_update.definition_hash.is_some(),
+            "Expected a definition hash"
+        );
+        assert_eq!(
+            first_update.definition_hash, second_update.definition_hash,
+            "Expected the same definition hash"
+        );
+    }
+
+    #[test]
+    fn definitions_hash_not_affected_by_workflow_order() {
+        let workflows = get_test_workflows();
+        let mut reversed = get_test_workflows();
+        reversed.reverse();
+
+        assert_eq!(
+            get_definitions_hash(&workflows),
+            get_definitions_hash(&reversed),
+            "Expected the same hash"
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_next_executor_when_first_considers_stream_invalid() {
+        let first = TestExecutor {
+            expected_name: "other".to_string(),
+            workflows: Vec::new(),
+        };
+
+        let second = TestExecutor {
+            expected_name: "stream".to_string(),
+            workflows: get_test_workflows(),
+        };
+
+        let context = TestContext::with_executors(
+            "reactor".to_string(),
+            Duration::from_millis(0),
+            vec![Box::new(first), Box::new(second)],
+        )
+        .await;
+
+        let (sender, mut receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let update = test_utils::expect_mpsc_response(&mut receiver).await;
+        assert!(update.is_valid, "Expected is valid to be true");
+        assert_eq!(
+            update.routable_workflow_names.len(),
+            2,
+            "Expected 2 routable workflows"
+        );
+    }
+
+    #[tokio::test]
+    async fn first_valid_executor_result_is_used() {
+        let first = TestExecutor {
+            expected_name: "stream".to_string(),
+            workflows: vec![get_test_workflows().remove(0)],
+        };
+
+        let second = TestExecutor {
+            expected_name: "stream".to_string(),
+            workflows: get_test_workflows(),
+        };
+
+        let context = TestContext::with_executors(
+            "reactor".to_string(),
+            Duration::from_millis(0),
+            vec![Box::new(first), Box::new(second)],
+        )
+        .await;
+
+        let (sender, mut receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
                 response_channel: sender,
             })
             .expect("Channel closed");
 
         let update = test_utils::expect_mpsc_response(&mut receiver).await;
+        assert!(update.is_valid, "Expected is valid to be true");
+        assert_eq!(
+            update.routable_workflow_names.len(),
+            1,
+            "Expected 1 routable workflow"
+        );
+        assert!(
+            update.routable_workflow_names.contains("first"),
+            "Did not find 'first' workflow in routable results"
+        );
+    }
+
+    #[tokio::test]
+    async fn not_valid_if_no_executor_considers_stream_valid() {
+        let first = TestExecutor {
+            expected_name: "other".to_string(),
+            workflows: get_test_workflows(),
+        };
+
+        let second = TestExecutor {
+            expected_name: "another".to_string(),
+            workflows: get_test_workflows(),
+        };
+
+        let context = TestContext::with_executors(
+            "reactor".to_string(),
+            Duration::from_millis(0),
+            vec![Box::new(first), Box::new(second)],
+        )
+        .await;
+
+        let (sender, mut receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
 
+        let update = test_utils::expect_mpsc_response(&mut receiver).await;
         assert!(!update.is_valid, "Expected is valid to be false");
         assert_eq!(
             update.routable_workflow_names.len(),
@@ -584,6 +1180,7 @@ mod tests {
             .reactor
             .send(ReactorRequest::CreateWorkflowNameForStream {
                 stream_name: "stream".to_string(),
+                metadata: None,
                 response_channel: sender,
             })
             .expect("Channel closed");
@@ -644,6 +1241,7 @@ mod tests {
             .reactor
             .send(ReactorRequest::CreateWorkflowNameForStream {
                 stream_name: "stream".to_string(),
+                metadata: None,
                 response_channel: sender,
             })
             .expect("Channel closed");
@@ -656,7 +1254,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn routable_workflows_updated_when_duration_set() {
+    async fn routable_workflows_not_resent_when_unchanged_after_duration() {
         let executor = TestExecutor {
             expected_name: "stream".to_string(),
             workflows: get_test_workflows(),
@@ -669,6 +1267,7 @@ mod tests {
             .reactor
             .send(ReactorRequest::CreateWorkflowNameForStream {
                 stream_name: "stream".to_string(),
+                metadata: None,
                 response_channel: sender,
             })
             .expect("Channel closed");
@@ -677,8 +1276,39 @@ mod tests {
         test_utils::expect_mpsc_timeout(&mut receiver).await;
         tokio::time::sleep(Duration::from_millis(500)).await;
 
+        test_utils::expect_mpsc_timeout(&mut receiver).await;
+    }
+
+    #[tokio::test]
+    async fn routable_workflows_updated_when_changed_after_duration() {
+        let executor = ChangingTestExecutor {
+            expected_name: "stream".to_string(),
+            call_count: AtomicUsize::new(0),
+            update_interval: None,
+        };
+
+        let context =
+            TestContext::new("reactor".to_string(), Duration::from_millis(500), executor).await;
+        let (sender, mut receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let first_update = test_utils::expect_mpsc_response(&mut receiver).await;
+        test_utils::expect_mpsc_timeout(&mut receiver).await;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
         let update = test_utils::expect_mpsc_response(&mut receiver).await;
         assert!(update.is_valid, "Expected is valid to be true");
+        assert_ne!(
+            update.definition_hash, first_update.definition_hash,
+            "Expected the definition hash to change"
+        );
         assert_eq!(
             update.routable_workflow_names.len(),
             2,
@@ -696,7 +1326,63 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn all_workflows_upserted_to_workflow_manager_again_after_duration() {
+    async fn routable_workflows_updated_after_interval_returned_by_executor() {
+        let executor = ChangingTestExecutor {
+            expected_name: "stream".to_string(),
+            call_count: AtomicUsize::new(0),
+            update_interval: Some(Duration::from_millis(500)),
+        };
+
+        let context =
+            TestContext::new("reactor".to_string(), Duration::from_millis(0), executor).await;
+        let (sender, mut receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let first_update = test_utils::expect_mpsc_response(&mut receiver).await;
+        test_utils::expect_mpsc_timeout(&mut receiver).await;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let update = test_utils::expect_mpsc_response(&mut receiver).await;
+        assert_ne!(
+            update.definition_hash, first_update.definition_hash,
+            "Expected the definition hash to change"
+        );
+    }
+
+    #[tokio::test]
+    async fn routable_workflows_not_updated_when_executor_returns_zero_interval() {
+        let executor = ChangingTestExecutor {
+            expected_name: "stream".to_string(),
+            call_count: AtomicUsize::new(0),
+            update_interval: Some(Duration::from_millis(0)),
+        };
+
+        let context =
+            TestContext::new("reactor".to_string(), Duration::from_millis(500), executor).await;
+        let (sender, mut receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let _ = test_utils::expect_mpsc_response(&mut receiver).await;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        test_utils::expect_mpsc_timeout(&mut receiver).await;
+    }
+
+    #[tokio::test]
+    async fn workflows_not_upserted_again_when_unchanged_after_duration() {
         let executor = TestExecutor {
             expected_name: "stream".to_string(),
             workflows: get_test_workflows(),
@@ -709,6 +1395,7 @@ mod tests {
             .reactor
             .send(ReactorRequest::CreateWorkflowNameForStream {
                 stream_name: "stream".to_string(),
+                metadata: None,
                 response_channel: sender,
             })
             .expect("Channel closed");
@@ -721,44 +1408,46 @@ mod tests {
         }
 
         tokio::time::sleep(Duration::from_millis(500)).await;
+        test_utils::expect_mpsc_timeout(&mut context.workflow_manager).await;
+    }
 
-        let mut workflows_found = [false, false, false];
-        loop {
-            let request = test_utils::expect_mpsc_response(&mut context.workflow_manager).await;
-            match request.operation {
-                WorkflowManagerRequestOperation::UpsertWorkflow { definition } => {
-                    if &definition.name == "first" {
-                        if workflows_found[0] {
-                            panic!("Received duplicate upsert request for workflow 'first'");
-                        }
+    #[tokio::test]
+    async fn only_changed_workflows_upserted_again_after_duration() {
+        let executor = ChangingTestExecutor {
+            expected_name: "stream".to_string(),
+            call_count: AtomicUsize::new(0),
+            update_interval: None,
+        };
 
-                        assert_eq!(definition.steps.len(), 1, "Expected 1 workflows");
-                        workflows_found[0] = true;
-                    } else if &definition.name == "second" {
-                        if workflows_found[1] {
-                            panic!("Received duplicate upsert request for workflow 'second'");
-                        }
+        let mut context =
+            TestContext::new("reactor".to_string(), Duration::from_millis(500), executor).await;
+        let (sender, _receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
 
-                        assert_eq!(definition.steps.len(), 2, "Expected 2 workflow steps");
-                        workflows_found[1] = true;
-                    } else if &definition.name == "third" {
-                        if workflows_found[2] {
-                            panic!("Received duplicate upsert request for workflow 'third'");
-                        }
+        loop {
+            match timeout(Duration::from_millis(10), context.workflow_manager.recv()).await {
+                Ok(_) => (),
+                Err(_) => break,
+            }
+        }
 
-                        assert_eq!(definition.steps.len(), 3, "Expected 3 workflow steps");
-                        workflows_found[2] = true;
-                    } else {
-                        panic!("Unexpected workflow: {}", definition.name);
-                    }
-                }
+        tokio::time::sleep(Duration::from_millis(500)).await;
 
-                operation => panic!("Expected upsert request, instead got {:?}", operation),
+        let request = test_utils::expect_mpsc_response(&mut context.workflow_manager).await;
+        match request.operation {
+            WorkflowManagerRequestOperation::UpsertWorkflow { definition } => {
+                assert_eq!(&definition.name, "first", "Unexpected workflow upserted");
+                assert_eq!(definition.steps.len(), 2, "Expected 2 workflow steps");
             }
 
-            if workflows_found[0] && workflows_found[1] && workflows_found[2] {
-                break;
-            }
+            operation => panic!("Expected upsert request, instead got {:?}", operation),
         }
 
         test_utils::expect_mpsc_timeout(&mut context.workflow_manager).await;
@@ -778,6 +1467,7 @@ mod tests {
             .reactor
             .send(ReactorRequest::CreateWorkflowNameForStream {
                 stream_name: "stream".to_string(),
+                metadata: None,
                 response_channel: sender,
             })
             .expect("Channel closed");
@@ -793,6 +1483,347 @@ mod tests {
         test_utils::expect_mpsc_timeout(&mut context.workflow_manager).await;
     }
 
+    #[tokio::test]
+    async fn request_metadata_passed_to_executor() {
+        let (metadata_sender, mut metadata_receiver) = unbounded_channel();
+        let executor = MetadataTestExecutor { metadata_sender };
+
+        let context =
+            TestContext::new("reactor".to_string(), Duration::from_millis(0), executor).await;
+        let (sender, _receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: Some(get_test_metadata()),
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let metadata = test_utils::expect_mpsc_response(&mut metadata_receiver).await;
+        assert_eq!(
+            metadata,
+            Some(get_test_metadata()),
+            "Unexpected metadata passed to executor"
+        );
+    }
+
+    #[tokio::test]
+    async fn request_metadata_passed_to_executor_on_update() {
+        let (metadata_sender, mut metadata_receiver) = unbounded_channel();
+        let executor = MetadataTestExecutor { metadata_sender };
+
+        let context =
+            TestContext::new("reactor".to_string(), Duration::from_millis(50), executor).await;
+        let (sender, _receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: Some(get_test_metadata()),
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let _ = test_utils::expect_mpsc_response(&mut metadata_receiver).await;
+
+        let metadata = timeout(Duration::from_millis(100), metadata_receiver.recv())
+            .await
+            .expect("Timed out waiting for executor to be re-executed")
+            .expect("Channel closed");
+
+        assert_eq!(
+            metadata,
+            Some(get_test_metadata()),
+            "Unexpected metadata passed to executor"
+        );
+    }
+
+    #[tokio::test]
+    async fn executor_without_metadata_support_used_when_metadata_provided() {
+        let executor = TestExecutor {
+            expected_name: "stream".to_string(),
+            workflows: get_test_workflows(),
+        };
+
+        let context =
+            TestContext::new("reactor".to_string(), Duration::from_millis(0), executor).await;
+        let (sender, mut receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: Some(get_test_metadata()),
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let update = test_utils::expect_mpsc_response(&mut receiver).await;
+        assert!(update.is_valid, "Expected is valid to be true");
+        assert_eq!(
+            update.routable_workflow_names.len(),
+            2,
+            "Expected 2 routable workflows"
+        );
+    }
+
+    #[tokio::test]
+    async fn routable_workflow_names_use_name_template() {
+        let executor = TestExecutor {
+            expected_name: "stream".to_string(),
+            workflows: get_test_workflows(),
+        };
+
+        let context = TestContext::with_name_template(
+            "reactor".to_string(),
+            Duration::from_millis(0),
+            vec![Box::new(executor)],
+            Some("ingest_{stream}_{workflow}".to_string()),
+        )
+        .await;
+
+        let (sender, mut receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let update = test_utils::expect_mpsc_response(&mut receiver).await;
+        let expected = ["ingest_stream_first", "ingest_stream_third"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<HashSet<_>>();
+
+        assert_eq!(
+            update.routable_workflow_names, expected,
+            "Unexpected routable workflow names"
+        );
+    }
+
+    #[tokio::test]
+    async fn upserted_workflows_use_name_template() {
+        let executor = TestExecutor {
+            expected_name: "stream".to_string(),
+            workflows: get_test_workflows(),
+        };
+
+        let mut context = TestContext::with_name_template(
+            "reactor".to_string(),
+            Duration::from_millis(0),
+            vec![Box::new(executor)],
+            Some("ingest_{stream}_{workflow}".to_string()),
+        )
+        .await;
+
+        let (sender, _receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let mut names = HashSet::new();
+        for _ in 0..3 {
+            let request = test_utils::expect_mpsc_response(&mut context.workflow_manager).await;
+            match request.operation {
+                WorkflowManagerRequestOperation::UpsertWorkflow { definition } => {
+                    names.insert(definition.name);
+                }
+
+                operation => panic!("Expected upsert request, instead got {:?}", operation),
+            }
+        }
+
+        let expected = [
+            "ingest_stream_first",
+            "ingest_stream_second",
+            "ingest_stream_third",
+        ]
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<HashSet<_>>();
+
+        assert_eq!(names, expected, "Unexpected upserted workflow names");
+    }
+
+    #[tokio::test]
+    async fn stopped_workflows_use_same_templated_names_as_upserts() {
+        let executor = ValidOnceTestExecutor {
+            call_count: AtomicUsize::new(0),
+        };
+
+        let mut context = TestContext::with_name_template(
+            "reactor".to_string(),
+            Duration::from_millis(100),
+            vec![Box::new(executor)],
+            Some("ingest_{stream}_{workflow}".to_string()),
+        )
+        .await;
+
+        let (sender, _receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let mut upserted_names = HashSet::new();
+        for _ in 0..3 {
+            let request = test_utils::expect_mpsc_response(&mut context.workflow_manager).await;
+            match request.operation {
+                WorkflowManagerRequestOperation::UpsertWorkflow { definition } => {
+                    upserted_names.insert(definition.name);
+                }
+
+                operation => panic!("Expected upsert request, instead got {:?}", operation),
+            }
+        }
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+
+        let mut stopped_names = HashSet::new();
+        for _ in 0..3 {
+            let request = test_utils::expect_mpsc_response(&mut context.workflow_manager).await;
+            match request.operation {
+                WorkflowManagerRequestOperation::StopWorkflow { name, .. } => {
+                    stopped_names.insert(name);
+                }
+
+                operation => panic!("Expected stop request, instead got {:?}", operation),
+            }
+        }
+
+        assert_eq!(
+            stopped_names, upserted_names,
+            "Stopped workflows did not match upserted workflows"
+        );
+    }
+
+    #[tokio::test]
+    async fn workflows_not_stopped_before_minimum_lifetime_passes() {
+        let executor = ValidOnceTestExecutor {
+            call_count: AtomicUsize::new(0),
+        };
+
+        let mut context = TestContext::with_minimum_workflow_lifetime(
+            "reactor".to_string(),
+            Duration::from_millis(50),
+            executor,
+            Duration::from_millis(500),
+        )
+        .await;
+
+        let (sender, mut receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let update = test_utils::expect_mpsc_response(&mut receiver).await;
+        assert!(update.is_valid, "Expected stream to be valid");
+
+        for _ in 0..3 {
+            let _ = test_utils::expect_mpsc_response(&mut context.workflow_manager).await;
+        }
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        test_utils::expect_mpsc_timeout(&mut context.workflow_manager).await;
+        test_utils::expect_mpsc_timeout(&mut receiver).await;
+    }
+
+    #[tokio::test]
+    async fn workflows_stopped_after_minimum_lifetime_passes() {
+        let executor = ValidOnceTestExecutor {
+            call_count: AtomicUsize::new(0),
+        };
+
+        let mut context = TestContext::with_minimum_workflow_lifetime(
+            "reactor".to_string(),
+            Duration::from_millis(50),
+            executor,
+            Duration::from_millis(300),
+        )
+        .await;
+
+        let (sender, mut receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let _ = test_utils::expect_mpsc_response(&mut receiver).await;
+        for _ in 0..3 {
+            let _ = test_utils::expect_mpsc_response(&mut context.workflow_manager).await;
+        }
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+
+        for _ in 0..3 {
+            let request = test_utils::expect_mpsc_response(&mut context.workflow_manager).await;
+            match request.operation {
+                WorkflowManagerRequestOperation::StopWorkflow { .. } => (),
+                operation => panic!("Expected stop request, instead got {:?}", operation),
+            }
+        }
+
+        let update = test_utils::expect_mpsc_response(&mut receiver).await;
+        assert!(!update.is_valid, "Expected stream to no longer be valid");
+    }
+
+    #[tokio::test]
+    async fn flapping_executor_does_not_stop_workflows_within_minimum_lifetime() {
+        let executor = FlappingTestExecutor {
+            call_count: AtomicUsize::new(0),
+        };
+
+        let mut context = TestContext::with_minimum_workflow_lifetime(
+            "reactor".to_string(),
+            Duration::from_millis(50),
+            executor,
+            Duration::from_secs(10),
+        )
+        .await;
+
+        let (sender, mut receiver) = unbounded_channel();
+        context
+            .reactor
+            .send(ReactorRequest::CreateWorkflowNameForStream {
+                stream_name: "stream".to_string(),
+                metadata: None,
+                response_channel: sender,
+            })
+            .expect("Channel closed");
+
+        let _ = test_utils::expect_mpsc_response(&mut receiver).await;
+        for _ in 0..3 {
+            let _ = test_utils::expect_mpsc_response(&mut context.workflow_manager).await;
+        }
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        test_utils::expect_mpsc_timeout(&mut context.workflow_manager).await;
+        test_utils::expect_mpsc_timeout(&mut receiver).await;
+    }
+
     fn get_test_workflows() -> Vec<WorkflowDefinition> {
         vec![
             WorkflowDefinition {
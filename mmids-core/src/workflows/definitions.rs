@@ -1,23 +1,30 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::fmt::Formatter;
 use std::hash::{Hash, Hasher};
 
 /// Identifier representing the type of the workflow step being defined
-#[derive(Clone, Hash, Debug, Eq, PartialEq)]
+#[derive(Clone, Hash, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct WorkflowStepType(pub String);
 
-/// The definition of a workflow step and any parameters it may be using
-#[derive(Clone, Debug)]
+/// The definition of a workflow step and any parameters it may be using.  When serialized,
+/// parameters that were specified without a value (flags) have a `null` value.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct WorkflowStepDefinition {
     pub step_type: WorkflowStepType,
+
+    #[serde(default)]
     pub parameters: HashMap<String, Option<String>>,
 }
 
 /// The definition of a workflow and the steps (in order) it contains
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct WorkflowDefinition {
     pub name: String,
+
+    #[serde(default)]
     pub routed_by_reactor: bool,
     pub steps: Vec<WorkflowStepDefinition>,
 }
@@ -38,6 +45,66 @@ impl WorkflowStepDefinition {
         self.hash(&mut hasher);
         hasher.finish()
     }
+
+    /// Gets the values of a parameter that can contain multiple values.  Values can either be
+    /// comma delimited (e.g. `codecs=h264,aac`) or come from the same argument being specified
+    /// multiple times (e.g. `map=a map=b`).  Returns `None` if the parameter wasn't specified or
+    /// was specified without a value.
+    pub fn get_list_parameter(&self, name: &str) -> Option<Vec<String>> {
+        match self.parameters.get(name) {
+            Some(Some(value)) => Some(
+                value
+                    .split(',')
+                    .map(|x| x.trim())
+                    .filter(|x| !x.is_empty())
+                    .map(|x| x.to_string())
+                    .collect(),
+            ),
+
+            _ => None,
+        }
+    }
+}
+
+impl WorkflowDefinition {
+    /// Gets a SHA-256 hash (as a hex string) of the workflow's full definition.  Unlike step ids,
+    /// the hash only depends on the content of the definition, and is the same across application
+    /// runs regardless of what order parameters were specified in.  This allows external tooling
+    /// to cheaply detect if a workflow's definition has changed.
+    pub fn get_content_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        update_with_str(&mut hasher, &self.name);
+        hasher.update([self.routed_by_reactor as u8]);
+        hasher.update((self.steps.len() as u64).to_be_bytes());
+
+        for step in &self.steps {
+            let mut parameters = step.parameters.iter().collect::<Vec<_>>();
+            parameters.sort();
+
+            update_with_str(&mut hasher, &step.step_type.0);
+            hasher.update((parameters.len() as u64).to_be_bytes());
+            for (key, value) in parameters {
+                update_with_str(&mut hasher, key);
+                match value {
+                    Some(value) => {
+                        hasher.update([1u8]);
+                        update_with_str(&mut hasher, value);
+                    }
+
+                    None => hasher.update([0u8]),
+                }
+            }
+        }
+
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// Adds a string to the hash prefixed by its length, so adjacent values can't run into each
+/// other (e.g. `ab` + `c` hashing the same as `a` + `bc`)
+fn update_with_str(hasher: &mut Sha256, value: &str) {
+    hasher.update((value.len() as u64).to_be_bytes());
+    hasher.update(value.as_bytes());
 }
 
 impl Hash for WorkflowStepDefinition {
@@ -56,6 +123,7 @@ impl Hash for WorkflowStepDefinition {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::parse;
 
     #[test]
     fn two_steps_with_identical_setups_have_same_id() {
@@ -143,4 +211,109 @@ mod tests {
 
         assert_ne!(step1.get_id(), step2.get_id());
     }
+
+    #[test]
+    fn parsed_workflow_can_round_trip_through_json() {
+        let content = "
+workflow name routed_by_reactor {
+    rtmp_receive port=1935 rtmp_app=receive stream_key=*
+    record output_dir=/tmp/recordings format=flv
+    rtmp_watch port=1935 rtmp_app=watch stream_key=* rtmps
+}
+";
+
+        let config = parse(content).unwrap();
+        let workflow = config.workflows.get("name").unwrap();
+
+        let json = serde_json::to_string(workflow).expect("Failed to serialize workflow");
+        let deserialized: WorkflowDefinition =
+            serde_json::from_str(&json).expect("Failed to deserialize workflow");
+
+        assert_eq!(&deserialized, workflow, "Unexpected deserialized workflow");
+    }
+
+    #[test]
+    fn step_flags_serialize_as_null_parameters() {
+        let mut step = WorkflowStepDefinition {
+            step_type: WorkflowStepType("test".to_string()),
+            parameters: HashMap::new(),
+        };
+
+        step.parameters.insert("flag".to_string(), None);
+
+        let json = serde_json::to_value(&step).expect("Failed to serialize step");
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "step_type": "test",
+                "parameters": { "flag": null },
+            }),
+            "Unexpected serialized step"
+        );
+    }
+
+    fn create_workflow(parameters: &[(&str, Option<&str>)]) -> WorkflowDefinition {
+        let mut step = WorkflowStepDefinition {
+            step_type: WorkflowStepType("test".to_string()),
+            parameters: HashMap::new(),
+        };
+
+        for (key, value) in parameters {
+            step.parameters
+                .insert(key.to_string(), value.map(|x| x.to_string()));
+        }
+
+        WorkflowDefinition {
+            name: "workflow".to_string(),
+            routed_by_reactor: false,
+            steps: vec![step],
+        }
+    }
+
+    #[test]
+    fn content_hash_is_same_regardless_of_parameter_order() {
+        let workflow1 = create_workflow(&[("a", Some("b")), ("c", None), ("e", Some("f"))]);
+        let workflow2 = create_workflow(&[("e", Some("f")), ("a", Some("b")), ("c", None)]);
+
+        assert_eq!(
+            workflow1.get_content_hash(),
+            workflow2.get_content_hash(),
+            "Expected the same content hash"
+        );
+    }
+
+    #[test]
+    fn content_hash_is_stable_across_runs() {
+        let workflow = create_workflow(&[("a", Some("b"))]);
+
+        assert_eq!(
+            workflow.get_content_hash(),
+            "2ab39d7a7338a22f80b0d1b7a6b6cc4fa212a7e021ba8ad70b64ebd54c490c3a",
+            "Unexpected content hash"
+        );
+    }
+
+    #[test]
+    fn content_hash_changes_when_parameter_value_changes() {
+        let workflow1 = create_workflow(&[("a", Some("b"))]);
+        let workflow2 = create_workflow(&[("a", Some("c"))]);
+
+        assert_ne!(
+            workflow1.get_content_hash(),
+            workflow2.get_content_hash(),
+            "Expected different content hashes"
+        );
+    }
+
+    #[test]
+    fn content_hash_differs_between_flag_and_empty_value() {
+        let workflow1 = create_workflow(&[("a", None)]);
+        let workflow2 = create_workflow(&[("a", Some(""))]);
+
+        assert_ne!(
+            workflow1.get_content_hash(),
+            workflow2.get_content_hash(),
+            "Expected different content hashes"
+        );
+    }
 }
@@ -0,0 +1,240 @@
+//! Contains a tracing layer that allows individual workflows to log at a different level than
+//! the rest of the application.  Each workflow runner records its log level override on its
+//! `Workflow Execution` span, and any event raised within that span (or any of its child spans)
+//! is filtered against the override instead of the global log level.  Events outside of a
+//! workflow with an override are always filtered against the global log level.
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Level, Metadata, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// The name of the span field workflow runners record their log level override in
+pub(crate) const LOG_LEVEL_FIELD: &str = "log_level";
+
+/// Tracing layer that filters events against the global log level, unless the event was raised
+/// within a workflow that has had its log level overridden.  Since it filters events for the
+/// whole subscriber, writers should not apply their own maximum level.
+pub struct WorkflowLogFilterLayer {
+    global_level: LevelFilter,
+    active_overrides: AtomicUsize,
+}
+
+/// Span extension containing the log level override of a workflow
+struct WorkflowLogLevel(Level);
+
+#[derive(Default)]
+struct LogLevelVisitor {
+    level: Option<Option<Level>>,
+}
+
+impl WorkflowLogFilterLayer {
+    pub fn new(global_level: Level) -> Self {
+        WorkflowLogFilterLayer {
+            global_level: LevelFilter::from_level(global_level),
+            active_overrides: AtomicUsize::new(0),
+        }
+    }
+
+    fn is_globally_enabled(&self, metadata: &Metadata<'_>) -> bool {
+        // Spans are always enabled, otherwise events couldn't be matched to their workflow
+        metadata.is_span() || metadata.level() <= &self.global_level
+    }
+
+    fn update_override<S>(&self, id: &Id, level: Option<Level>, context: &Context<'_, S>)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let span = match context.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+
+        let mut extensions = span.extensions_mut();
+        let had_override = extensions.remove::<WorkflowLogLevel>().is_some();
+        match level {
+            Some(level) => {
+                extensions.insert(WorkflowLogLevel(level));
+                if !had_override {
+                    self.active_overrides.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+
+            None => {
+                if had_override {
+                    self.active_overrides.fetch_sub(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+impl<S> Layer<S> for WorkflowLogFilterLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if self.is_globally_enabled(metadata) {
+            Interest::always()
+        } else {
+            // Whether the event is enabled depends on the workflow it's raised in
+            Interest::sometimes()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, context: Context<'_, S>) -> bool {
+        if self.is_globally_enabled(metadata) {
+            return true;
+        }
+
+        if self.active_overrides.load(Ordering::Relaxed) == 0 {
+            return false;
+        }
+
+        let current_span = match context.lookup_current() {
+            Some(span) => span,
+            None => return false,
+        };
+
+        // The closest workflow override wins
+        for span in current_span.scope() {
+            if let Some(level) = span.extensions().get::<WorkflowLogLevel>() {
+                return metadata.level() <= &level.0;
+            }
+        }
+
+        false
+    }
+
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, context: Context<'_, S>) {
+        let mut visitor = LogLevelVisitor::default();
+        attributes.record(&mut visitor);
+
+        if let Some(level) = visitor.level {
+            self.update_override(id, level, &context);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, context: Context<'_, S>) {
+        let mut visitor = LogLevelVisitor::default();
+        values.record(&mut visitor);
+
+        if let Some(level) = visitor.level {
+            self.update_override(id, level, &context);
+        }
+    }
+
+    fn on_close(&self, id: Id, context: Context<'_, S>) {
+        self.update_override(&id, None, &context);
+    }
+}
+
+impl Visit for LogLevelVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == LOG_LEVEL_FIELD {
+            // Anything that isn't a valid level (such as an empty string) clears the override
+            self.level = Some(Level::from_str(value).ok());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tracing::{debug, info, span, trace};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Layer that counts the events that make it through the filter
+    struct CountingLayer {
+        count: Arc<AtomicUsize>,
+    }
+
+    impl<S: Subscriber> Layer<S> for CountingLayer {
+        fn on_event(&self, _event: &tracing::Event<'_>, _context: Context<'_, S>) {
+            self.count.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn count_events(test: impl FnOnce()) -> usize {
+        let count = Arc::new(AtomicUsize::new(0));
+        let subscriber = tracing_subscriber::registry()
+            .with(WorkflowLogFilterLayer::new(Level::INFO))
+            .with(CountingLayer {
+                count: count.clone(),
+            });
+
+        tracing::subscriber::with_default(subscriber, test);
+
+        count.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn events_above_global_level_filtered_without_override() {
+        let count = count_events(|| {
+            let span = span!(Level::INFO, "workflow", log_level = tracing::field::Empty);
+            let _enter = span.enter();
+
+            info!("info");
+            debug!("debug");
+        });
+
+        assert_eq!(count, 1, "Unexpected number of events");
+    }
+
+    #[test]
+    fn events_within_overridden_workflow_use_its_level() {
+        let count = count_events(|| {
+            let span = span!(Level::INFO, "workflow", log_level = tracing::field::Empty);
+            span.record(LOG_LEVEL_FIELD, "debug");
+
+            let child = span!(parent: &span, Level::INFO, "step");
+            let _enter = child.enter();
+
+            info!("info");
+            debug!("debug");
+            trace!("trace");
+        });
+
+        assert_eq!(count, 2, "Unexpected number of events");
+    }
+
+    #[test]
+    fn events_outside_overridden_workflow_use_global_level() {
+        let count = count_events(|| {
+            let overridden = span!(Level::INFO, "workflow", log_level = tracing::field::Empty);
+            overridden.record(LOG_LEVEL_FIELD, "trace");
+
+            let other = span!(Level::INFO, "workflow", log_level = tracing::field::Empty);
+            let _enter = other.enter();
+
+            debug!("debug");
+        });
+
+        assert_eq!(count, 0, "Unexpected number of events");
+    }
+
+    #[test]
+    fn clearing_override_returns_to_global_level() {
+        let count = count_events(|| {
+            let span = span!(Level::INFO, "workflow", log_level = tracing::field::Empty);
+            span.record(LOG_LEVEL_FIELD, "debug");
+            span.record(LOG_LEVEL_FIELD, "");
+
+            let _enter = span.enter();
+            debug!("debug");
+        });
+
+        assert_eq!(count, 0, "Unexpected number of events");
+    }
+}
@@ -4,17 +4,26 @@
 
 use crate::event_hub::{PublishEventRequest, WorkflowManagerEvent, WorkflowStartedOrStoppedEvent};
 use crate::workflows::definitions::WorkflowDefinition;
-use crate::workflows::runner::{WorkflowRequestOperation, WorkflowState};
+use crate::workflows::runner::{
+    DefinitionUpdateResult, WorkflowRequestOperation, WorkflowState, WorkflowStatus,
+};
+use crate::workflows::steps::cue_inject::CueInjectionRequest;
 use crate::workflows::steps::factory::WorkflowStepFactory;
-use crate::workflows::{start_workflow, WorkflowRequest};
-use futures::future::BoxFuture;
+use crate::workflows::steps::stream_stats::StreamStatistics;
+use crate::workflows::{
+    start_workflow_with_options, MediaNotification, WorkflowRequest, WorkflowRunnerOptions,
+};
+use futures::future::{join_all, BoxFuture};
 use futures::stream::FuturesUnordered;
 use futures::{FutureExt, StreamExt};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
-use tokio::sync::oneshot::Sender;
-use tracing::{info, instrument, warn};
+use tokio::sync::oneshot::{channel, Sender};
+use tracing::{debug, error, info, instrument, warn, Level};
 
 /// Requests an action be taken by the workflow manager
 #[derive(Debug)]
@@ -32,19 +41,119 @@ pub enum WorkflowManagerRequestOperation {
     /// Starts or updates a specified workflow based on the passed in definition
     UpsertWorkflow { definition: WorkflowDefinition },
 
-    /// Stops the specified workflow, if it is running
-    StopWorkflow { name: String },
+    /// Starts or updates a specified workflow the same as `UpsertWorkflow`, but responds once all
+    /// steps of the definition are active, the workflow enters an error state, or the timeout
+    /// elapses (whichever comes first).
+    UpsertWorkflowAndWait {
+        definition: WorkflowDefinition,
+        timeout: Duration,
+        response_channel: Sender<DefinitionUpdateResult>,
+    },
+
+    /// Stops the specified workflow, if it is running.  If a response channel is provided, it
+    /// will be sent `true` if the workflow was running and has been stopped, or `false` if no
+    /// workflow with the specified name was running.
+    StopWorkflow {
+        name: String,
+        response_channel: Option<Sender<bool>>,
+    },
 
     /// Requests information about all workflows currently running
     GetRunningWorkflows {
         response_channel: Sender<Vec<GetWorkflowResponse>>,
     },
 
+    /// Requests a summary of the current state of every running workflow.  Each workflow is
+    /// queried for its state, and any workflow that doesn't respond in time is included in the
+    /// response with an `Unresponsive` status instead of holding up the whole response.
+    GetAllWorkflowDetails {
+        response_channel: Sender<Vec<WorkflowSummary>>,
+    },
+
     /// Requests details about a specific workflow
     GetWorkflowDetails {
         name: String,
         response_channel: Sender<Option<WorkflowState>>,
     },
+
+    /// Sends a media notification into the specified workflow, the same as if it was sent to the
+    /// workflow's channel directly.  The media is dropped if no workflow with the specified name
+    /// is running.  If a response channel is provided, it will be sent `true` if the workflow
+    /// was running and the media was passed to it, or `false` if the media was dropped.
+    SendMediaToWorkflow {
+        name: String,
+        media: MediaNotification,
+        response_channel: Option<Sender<bool>>,
+    },
+
+    /// Injects a media notification into the specified workflow from outside of mmids, such as
+    /// for debugging or end to end tests.  The media is fed into the workflow's first active step
+    /// the same way as `SendMediaToWorkflow`, but it is only accepted if media injection was
+    /// enabled in the manager's options.  If a response channel is provided, it will be sent
+    /// `true` if the media was passed to the workflow, or `false` if injection is disabled or no
+    /// workflow with the specified name is running.
+    InjectMedia {
+        workflow_name: String,
+        notification: MediaNotification,
+        response_channel: Option<Sender<bool>>,
+    },
+
+    /// Overrides the level the specified workflow logs at, without affecting the level of any
+    /// other workflow.  A level of `None` returns the workflow to the global log level.  If a
+    /// response channel is provided, it will be sent `true` if the workflow was running, or
+    /// `false` if no workflow with the specified name was running.
+    SetLogLevel {
+        name: String,
+        level: Option<Level>,
+        response_channel: Option<Sender<bool>>,
+    },
+
+    /// Registers the channel cue injection requests for the specified cue channel should be sent
+    /// to.  Used by cue inject steps.  Registration is rejected if another step's channel that is
+    /// still open is registered with the same name.  If a response channel is provided it will be
+    /// sent `true` if the channel was registered, or `false` if it was rejected.
+    RegisterCueChannel {
+        name: String,
+        channel: UnboundedSender<CueInjectionRequest>,
+        response_channel: Option<Sender<bool>>,
+    },
+
+    /// Removes the registration of a cue channel.  Only removed if the registered channel is the
+    /// specified channel, so a step can't remove a registration it doesn't own.
+    UnregisterCueChannel {
+        name: String,
+        channel: UnboundedSender<CueInjectionRequest>,
+    },
+
+    /// Requests a cue be injected into the media of the specified stream, by the cue inject step
+    /// registered for the specified cue channel.  If a response channel is provided it will be
+    /// sent `true` if the cue was injected, or `false` if no step is registered for the cue
+    /// channel or no stream with that name is passing through it.
+    InjectCue {
+        cue_channel: String,
+        stream_name: String,
+        id: String,
+        duration: Duration,
+        response_channel: Option<Sender<bool>>,
+    },
+
+    /// Requests the latest statistics for all streams passing through stream stats steps.  Each
+    /// workflow is queried for its statistics, and workflows that don't respond in time are left
+    /// out of the response.
+    GetStreamStatistics {
+        response_channel: Sender<Vec<StreamStatistics>>,
+    },
+
+    /// Shuts down all running workflows, giving each step a chance to clean up, and then stops
+    /// the workflow manager.  If a response channel is provided it will be notified once every
+    /// workflow has exited.  Upserts received while waiting for workflows to exit are ignored.
+    StopAllWorkflows {
+        response_channel: Option<Sender<()>>,
+    },
+
+    /// Responds as soon as the request is received, without doing any other work.  Used to
+    /// check that the workflow manager is still alive and processing requests.
+    Ping { response_channel: Sender<()> },
 }
 
 #[derive(Debug)]
@@ -52,12 +161,106 @@ pub struct GetWorkflowResponse {
     pub name: String,
 }
 
+/// A summary of a single workflow's state, as returned when details of all workflows are requested
+#[derive(Debug, PartialEq)]
+pub struct WorkflowSummary {
+    pub name: String,
+    pub status: WorkflowSummaryStatus,
+
+    /// The number of steps in the workflow's most recently applied definition.  `None` if the
+    /// workflow did not respond.
+    pub step_count: Option<usize>,
+
+    /// `None` if the workflow did not respond
+    pub active_stream_count: Option<usize>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WorkflowSummaryStatus {
+    Running,
+    Error {
+        failed_step_id: u64,
+        message: String,
+    },
+
+    /// The workflow did not respond with its state before the timeout
+    Unresponsive,
+}
+
+/// How long each workflow has to respond with its state when details of all workflows are
+/// requested
+const WORKFLOW_SUMMARY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The default amount of time changes to the running workflows are collected for before the
+/// state file is written
+pub const DEFAULT_STATE_SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// The default amount of time workflows restored from the state file have to be upserted again
+/// before they are stopped
+pub const DEFAULT_RESTORED_WORKFLOW_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// Options that change how the workflow manager operates
+#[derive(Clone, Debug)]
+pub struct WorkflowManagerOptions {
+    /// If specified, the definitions of all running workflows are saved to this file as json
+    /// whenever workflows are started, updated, or stopped.  Workflows in the file are started
+    /// when the manager starts, so workflows created by reactors or the HTTP API are running
+    /// again right away after a restart, instead of waiting for them to be re-requested.
+    pub state_file: Option<PathBuf>,
+
+    /// How long changes are collected for before the state file is written, so a burst of
+    /// changes results in a single write.
+    pub state_save_delay: Duration,
+
+    /// How long workflows restored from the state file have to be upserted again (e.g. by the
+    /// config or a reactor) before they are stopped.  This keeps workflows that no longer exist
+    /// anywhere from running forever just because they were once saved.
+    pub restored_workflow_grace_period: Duration,
+
+    /// The options every workflow the manager starts is run with.  The event hub publisher is
+    /// always replaced with the manager's own, so workflows publish their step events.
+    pub runner_options: WorkflowRunnerOptions,
+
+    /// If `InjectMedia` requests are accepted.  Off by default, so synthetic media can't be
+    /// pushed into live workflows unless it's been explicitly allowed.
+    pub allow_media_injection: bool,
+}
+
+impl Default for WorkflowManagerOptions {
+    fn default() -> Self {
+        WorkflowManagerOptions {
+            state_file: None,
+            state_save_delay: DEFAULT_STATE_SAVE_DELAY,
+            restored_workflow_grace_period: DEFAULT_RESTORED_WORKFLOW_GRACE_PERIOD,
+            runner_options: WorkflowRunnerOptions::default(),
+            allow_media_injection: false,
+        }
+    }
+}
+
 pub fn start_workflow_manager(
     step_factory: Arc<WorkflowStepFactory>,
     event_hub_publisher: UnboundedSender<PublishEventRequest>,
+) -> UnboundedSender<WorkflowManagerRequest> {
+    start_workflow_manager_with_options(
+        step_factory,
+        event_hub_publisher,
+        WorkflowManagerOptions::default(),
+    )
+}
+
+pub fn start_workflow_manager_with_options(
+    step_factory: Arc<WorkflowStepFactory>,
+    event_hub_publisher: UnboundedSender<PublishEventRequest>,
+    options: WorkflowManagerOptions,
 ) -> UnboundedSender<WorkflowManagerRequest> {
     let (sender, receiver) = unbounded_channel();
-    let actor = Actor::new(step_factory, event_hub_publisher);
+    let mut actor = Actor::new(step_factory, event_hub_publisher);
+    actor.state_file = options.state_file;
+    actor.state_save_delay = options.state_save_delay;
+    actor.restored_workflow_grace_period = options.restored_workflow_grace_period;
+    actor.runner_options = options.runner_options;
+    actor.allow_media_injection = options.allow_media_injection;
     tokio::spawn(actor.run(receiver, sender.clone()));
 
     sender
@@ -71,13 +274,48 @@ enum FutureResult {
         UnboundedReceiver<WorkflowManagerRequest>,
     ),
     WorkflowGone(String),
+    UnchangedWorkflowStatusReceived {
+        request_id: String,
+        definition: WorkflowDefinition,
+        is_errored: bool,
+    },
+    StateSaveDelayElapsed,
+    StateFileWritten,
+    RestoredWorkflowGracePeriodElapsed,
+    AllWorkflowsExited {
+        response_channel: Option<Sender<()>>,
+    },
 }
 
 struct Actor {
     futures: FuturesUnordered<BoxFuture<'static, FutureResult>>,
     workflows: HashMap<String, UnboundedSender<WorkflowRequest>>,
+
+    /// The definition most recently sent to each running workflow, so upserts that wouldn't
+    /// change anything aren't forwarded to healthy workflows
+    definitions: HashMap<String, WorkflowDefinition>,
+    cue_channels: HashMap<String, UnboundedSender<CueInjectionRequest>>,
     step_factory: Arc<WorkflowStepFactory>,
     event_hub_publisher: UnboundedSender<PublishEventRequest>,
+    state_file: Option<PathBuf>,
+    state_save_delay: Duration,
+    runner_options: WorkflowRunnerOptions,
+    allow_media_injection: bool,
+
+    /// Workflows started from the state file that have not been upserted again since
+    restored_workflows: HashSet<String>,
+    restored_workflow_grace_period: Duration,
+
+    /// Set from when a state save is scheduled until the state file has been written
+    state_save_pending: bool,
+
+    /// Set when workflows change while a state save is pending, so another save is scheduled
+    /// once it completes
+    state_changed_during_save: bool,
+
+    /// Set once all workflows have been told to stop, so no new workflows are started while
+    /// waiting for them to exit
+    stopping_all_workflows: bool,
 }
 
 impl Actor {
@@ -88,8 +326,19 @@ impl Actor {
         Actor {
             futures: FuturesUnordered::new(),
             workflows: HashMap::new(),
+            definitions: HashMap::new(),
+            cue_channels: HashMap::new(),
             step_factory,
             event_hub_publisher,
+            state_file: None,
+            state_save_delay: DEFAULT_STATE_SAVE_DELAY,
+            runner_options: WorkflowRunnerOptions::default(),
+            allow_media_injection: false,
+            restored_workflows: HashSet::new(),
+            restored_workflow_grace_period: DEFAULT_RESTORED_WORKFLOW_GRACE_PERIOD,
+            state_save_pending: false,
+            state_changed_during_save: false,
+            stopping_all_workflows: false,
         }
     }
 
@@ -117,25 +366,48 @@ impl Actor {
                 },
             ));
 
+        if let Some(state_file) = self.state_file.clone() {
+            self.restore_state(state_file).await;
+        }
+
         while let Some(result) = self.futures.next().await {
             match result {
                 FutureResult::AllConsumersGone => {
                     info!("All consumers gone");
+                    self.shutdown_all_workflows("workflow-manager-shutdown");
                     break;
                 }
 
                 FutureResult::EventHubGone => {
                     warn!("Event hub is gone");
+                    self.shutdown_all_workflows("workflow-manager-shutdown");
                     break;
                 }
 
                 FutureResult::WorkflowManagerRequestReceived(request, receiver) => {
                     self.futures.push(wait_for_request(receiver).boxed());
-                    self.handle_request(request);
+
+                    let mut stop_manager = false;
+                    self.handle_request(request, &mut stop_manager);
+
+                    if stop_manager {
+                        break;
+                    }
                 }
 
                 FutureResult::WorkflowGone(name) => {
-                    if let Some(_) = self.workflows.remove(&name) {
+                    // Only forget the definition if the workflow was still being managed, as
+                    // definitions are kept for the state file while all workflows shut down
+                    let is_managed = self
+                        .workflows
+                        .get(&name)
+                        .map(|sender| sender.is_closed())
+                        .unwrap_or(false);
+
+                    if is_managed {
+                        self.workflows.remove(&name);
+                        self.definitions.remove(&name);
+                        self.state_changed();
                         let event =
                             WorkflowStartedOrStoppedEvent::WorkflowEnded { name: name.clone() };
                         let _ = self
@@ -148,22 +420,99 @@ impl Actor {
                         );
                     }
                 }
+
+                FutureResult::UnchangedWorkflowStatusReceived {
+                    request_id,
+                    definition,
+                    is_errored,
+                } => self.handle_unchanged_workflow_status(request_id, definition, is_errored),
+
+                FutureResult::StateSaveDelayElapsed => self.save_state(),
+
+                FutureResult::StateFileWritten => {
+                    self.state_save_pending = false;
+                    if self.state_changed_during_save {
+                        self.state_changed_during_save = false;
+                        self.state_changed();
+                    }
+                }
+
+                FutureResult::RestoredWorkflowGracePeriodElapsed => {
+                    self.stop_unclaimed_restored_workflows();
+                }
+
+                FutureResult::AllWorkflowsExited { response_channel } => {
+                    info!("All workflows have exited");
+                    if let Some(response_channel) = response_channel {
+                        let _ = response_channel.send(());
+                    }
+
+                    break;
+                }
             }
         }
 
+        self.write_pending_state().await;
+
         info!("Workflow manager closing")
     }
 
-    #[instrument(skip(self, request), fields(request_id = %request.request_id))]
-    fn handle_request(&mut self, request: WorkflowManagerRequest) {
+    #[instrument(skip(self, request, stop_manager), fields(request_id = %request.request_id))]
+    fn handle_request(&mut self, request: WorkflowManagerRequest, stop_manager: &mut bool) {
+        if self.stopping_all_workflows {
+            match &request.operation {
+                WorkflowManagerRequestOperation::UpsertWorkflow { definition }
+                | WorkflowManagerRequestOperation::UpsertWorkflowAndWait { definition, .. } => {
+                    // Anything started now would outlive the stop all request, so the upsert is
+                    // dropped (which closes any response channel)
+                    warn!(
+                        workflow_name = %definition.name,
+                        "Ignoring upsert of workflow '{}' as all workflows are stopping",
+                        definition.name,
+                    );
+
+                    return;
+                }
+
+                _ => (),
+            }
+        }
+
         match request.operation {
             WorkflowManagerRequestOperation::UpsertWorkflow { definition } => {
+                self.restored_workflows.remove(&definition.name);
                 if let Some(sender) = self.workflows.get_mut(&definition.name) {
+                    if self.definitions.get(&definition.name) == Some(&definition) {
+                        // An errored workflow only recovers when it's sent a definition, so the
+                        // upsert can only be skipped once we know the workflow is healthy
+                        debug!(
+                            workflow_name = %definition.name,
+                            "Workflow '{}' is already running with the requested definition, so \
+                            the upsert is only forwarded if the workflow is in an error state",
+                            definition.name,
+                        );
+
+                        self.futures.push(
+                            check_if_workflow_errored(
+                                sender.clone(),
+                                request.request_id,
+                                definition,
+                            )
+                            .boxed(),
+                        );
+
+                        return;
+                    }
+
                     info!(
                         workflow_name = %definition.name,
                         "Updating existing workflow '{}' with new definition", definition.name,
                     );
 
+                    self.definitions
+                        .insert(definition.name.clone(), definition.clone());
+
+                    self.state_changed();
                     let _ = sender.send(WorkflowRequest {
                         request_id: request.request_id,
                         operation: WorkflowRequestOperation::UpdateDefinition {
@@ -177,11 +526,20 @@ impl Actor {
                     );
 
                     let name = definition.name.clone();
-                    let sender = start_workflow(definition, self.step_factory.clone());
+                    self.definitions.insert(name.clone(), definition.clone());
+
+                    let options = WorkflowRunnerOptions {
+                        event_hub_publisher: Some(self.event_hub_publisher.clone()),
+                        ..self.runner_options.clone()
+                    };
+
+                    let sender =
+                        start_workflow_with_options(definition, self.step_factory.clone(), options);
                     self.futures
                         .push(wait_for_workflow_gone(sender.clone(), name.clone()).boxed());
 
                     self.workflows.insert(name.clone(), sender.clone());
+                    self.state_changed();
 
                     let event = WorkflowStartedOrStoppedEvent::WorkflowStarted {
                         name: name.clone(),
@@ -194,13 +552,66 @@ impl Actor {
                 }
             }
 
-            WorkflowManagerRequestOperation::StopWorkflow { name } => {
+            WorkflowManagerRequestOperation::UpsertWorkflowAndWait {
+                definition,
+                timeout,
+                response_channel,
+            } => {
+                self.restored_workflows.remove(&definition.name);
+                if !self.workflows.contains_key(&definition.name) {
+                    // Start the workflow the same way a regular upsert does, then wait on it
+                    self.handle_request(
+                        WorkflowManagerRequest {
+                            request_id: request.request_id.clone(),
+                            operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                                definition: definition.clone(),
+                            },
+                        },
+                        stop_manager,
+                    );
+                } else if self.definitions.get(&definition.name) != Some(&definition) {
+                    info!(
+                        workflow_name = %definition.name,
+                        "Updating existing workflow '{}' with new definition", definition.name,
+                    );
+
+                    self.definitions
+                        .insert(definition.name.clone(), definition.clone());
+
+                    self.state_changed();
+                }
+
+                // Unchanged definitions are still sent, since only the workflow knows when its
+                // steps are active (and an errored workflow recovers when it's sent a definition)
+                if let Some(sender) = self.workflows.get(&definition.name) {
+                    let _ = sender.send(WorkflowRequest {
+                        request_id: request.request_id,
+                        operation: WorkflowRequestOperation::UpdateDefinitionAndWait {
+                            new_definition: definition,
+                            timeout,
+                            response_channel,
+                        },
+                    });
+                }
+            }
+
+            WorkflowManagerRequestOperation::StopWorkflow {
+                name,
+                response_channel,
+            } => {
                 info!(
                     workflow_name = %name,
                     "Stopping workflow '{}'", name,
                 );
 
-                if let Some(sender) = self.workflows.remove(&name) {
+                self.definitions.remove(&name);
+                let sender = self.workflows.remove(&name);
+                if let Some(response_channel) = response_channel {
+                    let _ = response_channel.send(sender.is_some());
+                }
+
+                if let Some(sender) = sender {
+                    self.state_changed();
                     let _ = sender.send(WorkflowRequest {
                         request_id: request.request_id,
                         operation: WorkflowRequestOperation::StopWorkflow,
@@ -226,6 +637,26 @@ impl Actor {
                 let _ = response_channel.send(response);
             }
 
+            WorkflowManagerRequestOperation::GetAllWorkflowDetails { response_channel } => {
+                let workflows = self
+                    .workflows
+                    .iter()
+                    .map(|(name, sender)| (name.clone(), sender.clone()))
+                    .collect::<Vec<_>>();
+
+                // Gathered in its own task so slow workflows don't block other manager requests
+                tokio::spawn(async move {
+                    let summaries = get_workflow_summaries(
+                        workflows,
+                        request.request_id,
+                        WORKFLOW_SUMMARY_TIMEOUT,
+                    )
+                    .await;
+
+                    let _ = response_channel.send(summaries);
+                });
+            }
+
             WorkflowManagerRequestOperation::GetWorkflowDetails {
                 name,
                 response_channel,
@@ -241,140 +672,1709 @@ impl Actor {
                     });
                 }
             },
-        }
-    }
-}
 
-async fn wait_for_request(mut receiver: UnboundedReceiver<WorkflowManagerRequest>) -> FutureResult {
-    match receiver.recv().await {
-        Some(request) => FutureResult::WorkflowManagerRequestReceived(request, receiver),
-        None => FutureResult::AllConsumersGone,
-    }
-}
+            WorkflowManagerRequestOperation::SendMediaToWorkflow {
+                name,
+                media,
+                response_channel,
+            } => {
+                let sender = self.workflows.get(&name);
+                if let Some(sender) = sender {
+                    let _ = sender.send(WorkflowRequest {
+                        request_id: request.request_id,
+                        operation: WorkflowRequestOperation::MediaNotification { media },
+                    });
+                }
 
-async fn notify_when_event_hub_is_gone(
-    sender: UnboundedSender<PublishEventRequest>,
-) -> FutureResult {
-    sender.closed().await;
-    FutureResult::EventHubGone
-}
+                if let Some(response_channel) = response_channel {
+                    let _ = response_channel.send(sender.is_some());
+                }
+            }
 
-async fn wait_for_workflow_gone(
-    sender: UnboundedSender<WorkflowRequest>,
-    name: String,
-) -> FutureResult {
-    sender.closed().await;
-    FutureResult::WorkflowGone(name)
-}
+            WorkflowManagerRequestOperation::InjectMedia {
+                workflow_name,
+                notification,
+                response_channel,
+            } => {
+                if !self.allow_media_injection {
+                    warn!(
+                        workflow_name = %workflow_name,
+                        "Request to inject media into workflow '{}' rejected, as media injection \
+                            is not enabled",
+                        workflow_name
+                    );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::test_utils;
-    use tokio::sync::oneshot::channel;
+                    if let Some(response_channel) = response_channel {
+                        let _ = response_channel.send(false);
+                    }
 
-    struct TestContext {
-        event_hub: UnboundedReceiver<PublishEventRequest>,
-        manager: UnboundedSender<WorkflowManagerRequest>,
-    }
+                    return;
+                }
 
-    impl TestContext {
-        fn new() -> Self {
-            let (sender, receiver) = unbounded_channel();
-            let factory = Arc::new(WorkflowStepFactory::new());
-            let manager = start_workflow_manager(factory, sender);
+                let sender = self.workflows.get(&workflow_name);
+                if let Some(sender) = sender {
+                    let _ = sender.send(WorkflowRequest {
+                        request_id: request.request_id,
+                        operation: WorkflowRequestOperation::MediaNotification {
+                            media: notification,
+                        },
+                    });
+                }
 
-            TestContext {
-                event_hub: receiver,
-                manager,
+                if let Some(response_channel) = response_channel {
+                    let _ = response_channel.send(sender.is_some());
+                }
             }
-        }
-    }
 
-    #[tokio::test]
-    async fn new_workflow_manager_registers_with_event_hub() {
-        let mut context = TestContext::new();
+            WorkflowManagerRequestOperation::SetLogLevel {
+                name,
+                level,
+                response_channel,
+            } => {
+                let sender = self.workflows.get(&name);
+                if let Some(sender) = sender {
+                    let _ = sender.send(WorkflowRequest {
+                        request_id: request.request_id,
+                        operation: WorkflowRequestOperation::SetLogLevel { level },
+                    });
+                }
 
-        let event = test_utils::expect_mpsc_response(&mut context.event_hub).await;
-        match event {
-            PublishEventRequest::WorkflowManagerEvent(event) => match event {
-                WorkflowManagerEvent::WorkflowManagerRegistered { channel: _ } => (),
-            },
+                if let Some(response_channel) = response_channel {
+                    let _ = response_channel.send(sender.is_some());
+                }
+            }
 
-            event => panic!("Expected workflow manager event, instead got {:?}", event),
-        }
-    }
+            WorkflowManagerRequestOperation::RegisterCueChannel {
+                name,
+                channel,
+                response_channel,
+            } => {
+                let is_taken = self
+                    .cue_channels
+                    .get(&name)
+                    .map(|existing| !existing.is_closed() && !existing.same_channel(&channel))
+                    .unwrap_or(false);
+
+                if is_taken {
+                    warn!(
+                        cue_channel = %name,
+                        "Cue channel '{}' not registered, as another step already registered it",
+                        name
+                    );
+                } else {
+                    info!(cue_channel = %name, "Registering cue channel '{}'", name);
+                    self.cue_channels.insert(name, channel);
+                }
 
-    #[tokio::test]
-    async fn created_workflow_has_event_published() {
-        let mut context = TestContext::new();
-        test_utils::expect_mpsc_response(&mut context.event_hub).await; // manager registered event
+                if let Some(response_channel) = response_channel {
+                    let _ = response_channel.send(!is_taken);
+                }
+            }
 
-        context
-            .manager
-            .send(WorkflowManagerRequest {
-                request_id: "".to_string(),
-                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
-                    definition: WorkflowDefinition {
-                        name: "workflow".to_string(),
-                        routed_by_reactor: false,
-                        steps: Vec::new(),
+            WorkflowManagerRequestOperation::UnregisterCueChannel { name, channel } => {
+                let is_registered = self
+                    .cue_channels
+                    .get(&name)
+                    .map(|existing| existing.same_channel(&channel))
+                    .unwrap_or(false);
+
+                if is_registered {
+                    info!(cue_channel = %name, "Unregistering cue channel '{}'", name);
+                    self.cue_channels.remove(&name);
+                }
+            }
+
+            WorkflowManagerRequestOperation::InjectCue {
+                cue_channel,
+                stream_name,
+                id,
+                duration,
+                response_channel,
+            } => {
+                let request = CueInjectionRequest {
+                    stream_name,
+                    id,
+                    duration,
+                    response_channel,
+                };
+
+                let unsent_request = match self.cue_channels.get(&cue_channel) {
+                    Some(channel) if channel.is_closed() => {
+                        self.cue_channels.remove(&cue_channel);
+                        Some(request)
+                    }
+
+                    Some(channel) => match channel.send(request) {
+                        Ok(_) => None,
+                        Err(error) => {
+                            // The step that registered the channel is gone
+                            self.cue_channels.remove(&cue_channel);
+                            Some(error.0)
+                        }
                     },
-                },
-            })
-            .expect("Failed to send upsert request");
 
-        let event = test_utils::expect_mpsc_response(&mut context.event_hub).await;
-        match event {
-            PublishEventRequest::WorkflowStartedOrStopped(event) => match event {
-                WorkflowStartedOrStoppedEvent::WorkflowStarted { name, channel: _ } => {
-                    assert_eq!(&name, "workflow", "Unexpected workflow name");
+                    None => Some(request),
+                };
+
+                if let Some(request) = unsent_request {
+                    warn!(
+                        cue_channel = %cue_channel,
+                        "Cue '{}' not injected, as no step is registered for cue channel '{}'",
+                        request.id, cue_channel
+                    );
+
+                    if let Some(response_channel) = request.response_channel {
+                        let _ = response_channel.send(false);
+                    }
                 }
+            }
 
-                event => panic!("Unexpected workflow event received: {:?}", event),
-            },
+            WorkflowManagerRequestOperation::GetStreamStatistics { response_channel } => {
+                let workflows = self
+                    .workflows
+                    .iter()
+                    .map(|(name, sender)| (name.clone(), sender.clone()))
+                    .collect::<Vec<_>>();
 
-            event => panic!("Unexpected publish event received; {:?}", event),
-        }
+                // Gathered in its own task so slow workflows don't block other manager requests
+                tokio::spawn(async move {
+                    let statistics = get_stream_statistics(
+                        workflows,
+                        request.request_id,
+                        WORKFLOW_SUMMARY_TIMEOUT,
+                    )
+                    .await;
+
+                    let _ = response_channel.send(statistics);
+                });
+            }
 
-        test_utils::expect_mpsc_timeout(&mut context.event_hub).await;
+            WorkflowManagerRequestOperation::StopAllWorkflows { response_channel } => {
+                info!("Stopping all workflows");
+                let workflows = self.shutdown_all_workflows(&request.request_id);
+                if workflows.is_empty() {
+                    *stop_manager = true;
+                    if let Some(response_channel) = response_channel {
+                        let _ = response_channel.send(());
+                    }
+
+                    return;
+                }
+
+                // The manager keeps running until the workflows exit, so callers know their
+                // steps are done cleaning up once they get a response
+                self.stopping_all_workflows = true;
+                self.futures
+                    .push(wait_for_workflows_to_exit(workflows, response_channel).boxed());
+            }
+
+            WorkflowManagerRequestOperation::Ping { response_channel } => {
+                let _ = response_channel.send(());
+            }
+        }
     }
 
-    #[tokio::test]
-    async fn created_workflow_shows_in_workflow_list() {
-        let context = TestContext::new();
-        context
-            .manager
+    /// Forwards an upserted definition that matched the workflow's current definition if the
+    /// workflow turned out to be in an error state, so the workflow can recover.
+    fn handle_unchanged_workflow_status(
+        &mut self,
+        request_id: String,
+        definition: WorkflowDefinition,
+        is_errored: bool,
+    ) {
+        if !is_errored {
+            return;
+        }
+
+        // The workflow may have been stopped or updated while its status was being retrieved
+        if self.definitions.get(&definition.name) != Some(&definition) {
+            return;
+        }
+
+        let sender = match self.workflows.get(&definition.name) {
+            Some(sender) => sender,
+            None => return,
+        };
+
+        info!(
+            workflow_name = %definition.name,
+            "Workflow '{}' is in an error state, so its definition is being reapplied",
+            definition.name,
+        );
+
+        let _ = sender.send(WorkflowRequest {
+            request_id,
+            operation: WorkflowRequestOperation::UpdateDefinition {
+                new_definition: definition,
+            },
+        });
+    }
+
+    /// Starts the workflows saved in the state file.  Workflows that are later upserted with the
+    /// same definition (e.g. by the config or a reactor) are left running untouched.
+    async fn restore_state(&mut self, state_file: PathBuf) {
+        let content = match tokio::fs::read_to_string(&state_file).await {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                info!(
+                    "No workflow state file exists at {}, so no workflows were restored",
+                    state_file.display()
+                );
+
+                return;
+            }
+
+            Err(error) => {
+                error!(
+                    "Failed to read workflow state file {}: {}",
+                    state_file.display(),
+                    error
+                );
+
+                return;
+            }
+        };
+
+        let mut definitions = match serde_json::from_str::<Vec<WorkflowDefinition>>(&content) {
+            Ok(definitions) => definitions,
+            Err(error) => {
+                error!(
+                    "Workflow state file {} is not valid: {}",
+                    state_file.display(),
+                    error
+                );
+
+                return;
+            }
+        };
+
+        info!(
+            "Restoring {} workflows from {}",
+            definitions.len(),
+            state_file.display()
+        );
+
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+        for definition in definitions {
+            let name = definition.name.clone();
+            let mut stop_manager = false;
+            self.handle_request(
+                WorkflowManagerRequest {
+                    request_id: "workflow-manager-restore".to_string(),
+                    operation: WorkflowManagerRequestOperation::UpsertWorkflow { definition },
+                },
+                &mut stop_manager,
+            );
+
+            self.restored_workflows.insert(name);
+        }
+
+        if !self.restored_workflows.is_empty() {
+            self.futures.push(
+                wait_for_restored_workflow_grace_period(self.restored_workflow_grace_period)
+                    .boxed(),
+            );
+        }
+    }
+
+    /// Stops restored workflows that were not upserted again within the grace period, as nothing
+    /// wants them running anymore
+    fn stop_unclaimed_restored_workflows(&mut self) {
+        let names = self.restored_workflows.drain().collect::<Vec<_>>();
+        for name in names {
+            if !self.workflows.contains_key(&name) {
+                continue;
+            }
+
+            info!(
+                workflow_name = %name,
+                "Restored workflow '{}' was not upserted again within {} seconds, stopping it",
+                name,
+                self.restored_workflow_grace_period.as_secs(),
+            );
+
+            let mut stop_manager = false;
+            self.handle_request(
+                WorkflowManagerRequest {
+                    request_id: "workflow-manager-restore".to_string(),
+                    operation: WorkflowManagerRequestOperation::StopWorkflow {
+                        name,
+                        response_channel: None,
+                    },
+                },
+                &mut stop_manager,
+            );
+        }
+    }
+
+    /// Schedules the state file to be written, if one is configured
+    fn state_changed(&mut self) {
+        if self.state_file.is_none() {
+            return;
+        }
+
+        if self.state_save_pending {
+            self.state_changed_during_save = true;
+            return;
+        }
+
+        self.state_save_pending = true;
+        self.futures
+            .push(wait_for_state_save_delay(self.state_save_delay).boxed());
+    }
+
+    fn save_state(&mut self) {
+        let state_file = match &self.state_file {
+            Some(state_file) => state_file.clone(),
+            None => return,
+        };
+
+        let content = match self.serialize_state() {
+            Some(content) => content,
+            None => {
+                self.state_save_pending = false;
+                return;
+            }
+        };
+
+        self.futures
+            .push(write_state_file(state_file, content).boxed());
+    }
+
+    /// Writes the state file right away if a save is still pending, since the manager is stopping
+    /// and will no longer wait for the save delay to elapse.
+    async fn write_pending_state(&mut self) {
+        if !self.state_save_pending {
+            return;
+        }
+
+        let state_file = match &self.state_file {
+            Some(state_file) => state_file.clone(),
+            None => return,
+        };
+
+        if let Some(content) = self.serialize_state() {
+            write_state_file(state_file, content).await;
+        }
+
+        self.state_save_pending = false;
+    }
+
+    fn serialize_state(&self) -> Option<String> {
+        let mut definitions = self.definitions.values().collect::<Vec<_>>();
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+
+        match serde_json::to_string_pretty(&definitions) {
+            Ok(content) => Some(content),
+            Err(error) => {
+                error!("Failed to serialize workflow state: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Requests every managed workflow shut down all of its steps.  Workflows are not guaranteed
+    /// to exit just because the manager stopped tracking them (other actors may hold onto their
+    /// channels), so this is the only way steps get a chance to clean up when the manager stops.
+    ///
+    /// Definitions are kept, so a pending state save still contains the workflows that were
+    /// running and they are restored the next time the manager starts.  The channels of the
+    /// workflows that were shut down are returned.
+    fn shutdown_all_workflows(
+        &mut self,
+        request_id: &str,
+    ) -> Vec<UnboundedSender<WorkflowRequest>> {
+        let mut workflows = Vec::new();
+        for (name, sender) in self.workflows.drain() {
+            info!(
+                workflow_name = %name,
+                "Shutting down workflow '{}'", name,
+            );
+
+            let _ = sender.send(WorkflowRequest {
+                request_id: request_id.to_string(),
+                operation: WorkflowRequestOperation::Shutdown,
+            });
+
+            let event = WorkflowStartedOrStoppedEvent::WorkflowEnded { name };
+            let _ = self
+                .event_hub_publisher
+                .send(PublishEventRequest::WorkflowStartedOrStopped(event));
+
+            workflows.push(sender);
+        }
+
+        workflows
+    }
+}
+
+async fn wait_for_request(mut receiver: UnboundedReceiver<WorkflowManagerRequest>) -> FutureResult {
+    match receiver.recv().await {
+        Some(request) => FutureResult::WorkflowManagerRequestReceived(request, receiver),
+        None => FutureResult::AllConsumersGone,
+    }
+}
+
+async fn notify_when_event_hub_is_gone(
+    sender: UnboundedSender<PublishEventRequest>,
+) -> FutureResult {
+    sender.closed().await;
+    FutureResult::EventHubGone
+}
+
+/// Workflows drop their request channel once they exit, so it closing means the workflow is
+/// done shutting down
+async fn wait_for_workflows_to_exit(
+    workflows: Vec<UnboundedSender<WorkflowRequest>>,
+    response_channel: Option<Sender<()>>,
+) -> FutureResult {
+    join_all(workflows.iter().map(|sender| sender.closed())).await;
+    FutureResult::AllWorkflowsExited { response_channel }
+}
+
+async fn wait_for_restored_workflow_grace_period(grace_period: Duration) -> FutureResult {
+    tokio::time::sleep(grace_period).await;
+    FutureResult::RestoredWorkflowGracePeriodElapsed
+}
+
+async fn wait_for_state_save_delay(delay: Duration) -> FutureResult {
+    tokio::time::sleep(delay).await;
+    FutureResult::StateSaveDelayElapsed
+}
+
+/// Writes the state to a temporary file first and then moves it into place, so a crash part way
+/// through writing doesn't leave a truncated state file behind
+async fn write_state_file(state_file: PathBuf, content: String) -> FutureResult {
+    let mut temp_file = state_file.clone().into_os_string();
+    temp_file.push(".tmp");
+    let temp_file = PathBuf::from(temp_file);
+
+    let result = match tokio::fs::write(&temp_file, content).await {
+        Ok(()) => tokio::fs::rename(&temp_file, &state_file).await,
+        Err(error) => Err(error),
+    };
+
+    if let Err(error) = result {
+        error!(
+            "Failed to write workflow state file {}: {}",
+            state_file.display(),
+            error
+        );
+    }
+
+    FutureResult::StateFileWritten
+}
+
+async fn wait_for_workflow_gone(
+    sender: UnboundedSender<WorkflowRequest>,
+    name: String,
+) -> FutureResult {
+    sender.closed().await;
+    FutureResult::WorkflowGone(name)
+}
+
+/// Queries a workflow for its state to find out if it's in an error state.  Workflows that don't
+/// respond in time are not considered errored.
+async fn check_if_workflow_errored(
+    sender: UnboundedSender<WorkflowRequest>,
+    request_id: String,
+    definition: WorkflowDefinition,
+) -> FutureResult {
+    let (response_sender, response_receiver) = channel();
+    let _ = sender.send(WorkflowRequest {
+        request_id: request_id.clone(),
+        operation: WorkflowRequestOperation::GetState {
+            response_channel: response_sender,
+        },
+    });
+
+    let is_errored = match tokio::time::timeout(WORKFLOW_SUMMARY_TIMEOUT, response_receiver).await {
+        Ok(Ok(Some(state))) => matches!(state.status, WorkflowStatus::Error { .. }),
+        _ => false,
+    };
+
+    FutureResult::UnchangedWorkflowStatusReceived {
+        request_id,
+        definition,
+        is_errored,
+    }
+}
+
+/// Queries each workflow for its state at the same time, and summarizes the responses.  Workflows
+/// that don't respond within the timeout, or that close without responding, are marked as
+/// unresponsive.  Summaries are returned in the same order as the workflow list api.
+async fn get_workflow_summaries(
+    workflows: Vec<(String, UnboundedSender<WorkflowRequest>)>,
+    request_id: String,
+    timeout: Duration,
+) -> Vec<WorkflowSummary> {
+    let queries = workflows.into_iter().map(|(name, sender)| {
+        let (response_sender, response_receiver) = channel();
+        let _ = sender.send(WorkflowRequest {
+            request_id: request_id.clone(),
+            operation: WorkflowRequestOperation::GetState {
+                response_channel: response_sender,
+            },
+        });
+
+        async move {
+            match tokio::time::timeout(timeout, response_receiver).await {
+                Ok(Ok(Some(state))) => WorkflowSummary {
+                    name,
+                    status: match state.status {
+                        WorkflowStatus::Running => WorkflowSummaryStatus::Running,
+                        WorkflowStatus::Error {
+                            failed_step_id,
+                            message,
+                        } => WorkflowSummaryStatus::Error {
+                            failed_step_id,
+                            message,
+                        },
+                    },
+                    step_count: Some(state.definition.steps.len()),
+                    active_stream_count: Some(state.active_stream_count),
+                },
+
+                _ => {
+                    warn!(
+                        workflow_name = %name,
+                        "Workflow '{}' did not respond with its state", name
+                    );
+
+                    WorkflowSummary {
+                        name,
+                        status: WorkflowSummaryStatus::Unresponsive,
+                        step_count: None,
+                        active_stream_count: None,
+                    }
+                }
+            }
+        }
+    });
+
+    let mut summaries = join_all(queries).await;
+    summaries.sort_by(|a, b| b.name.cmp(&a.name));
+
+    summaries
+}
+
+async fn get_stream_statistics(
+    workflows: Vec<(String, UnboundedSender<WorkflowRequest>)>,
+    request_id: String,
+    timeout: Duration,
+) -> Vec<StreamStatistics> {
+    let queries = workflows.into_iter().map(|(name, sender)| {
+        let (response_sender, response_receiver) = channel();
+        let _ = sender.send(WorkflowRequest {
+            request_id: request_id.clone(),
+            operation: WorkflowRequestOperation::GetStreamStatistics {
+                response_channel: response_sender,
+            },
+        });
+
+        async move {
+            match tokio::time::timeout(timeout, response_receiver).await {
+                Ok(Ok(statistics)) => statistics,
+                _ => {
+                    warn!(
+                        workflow_name = %name,
+                        "Workflow '{}' did not respond with its stream statistics", name
+                    );
+
+                    Vec::new()
+                }
+            }
+        }
+    });
+
+    join_all(queries).await.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflows::definitions::{WorkflowStepDefinition, WorkflowStepType};
+    use crate::workflows::steps::stream_stats::StreamStatsStepGenerator;
+    use crate::workflows::MediaNotificationContent;
+    use crate::{test_utils, StreamId};
+    use std::time::Duration;
+    use tokio::sync::oneshot::channel;
+
+    struct TestContext {
+        event_hub: UnboundedReceiver<PublishEventRequest>,
+        manager: UnboundedSender<WorkflowManagerRequest>,
+    }
+
+    impl TestContext {
+        fn new() -> Self {
+            let (sender, receiver) = unbounded_channel();
+            let factory = Arc::new(WorkflowStepFactory::new());
+            let manager = start_workflow_manager(factory, sender);
+
+            TestContext {
+                event_hub: receiver,
+                manager,
+            }
+        }
+
+        fn with_state_file(state_file: PathBuf) -> Self {
+            TestContext::with_options(WorkflowManagerOptions {
+                state_file: Some(state_file),
+                state_save_delay: Duration::from_millis(1),
+                ..Default::default()
+            })
+        }
+
+        fn with_options(options: WorkflowManagerOptions) -> Self {
+            let (sender, receiver) = unbounded_channel();
+            let factory = Arc::new(WorkflowStepFactory::new());
+            let manager = start_workflow_manager_with_options(factory, sender, options);
+
+            TestContext {
+                event_hub: receiver,
+                manager,
+            }
+        }
+
+        async fn get_running_workflow_names(&self) -> Vec<String> {
+            let (sender, receiver) = channel();
+            self.manager
+                .send(WorkflowManagerRequest {
+                    request_id: "".to_string(),
+                    operation: WorkflowManagerRequestOperation::GetRunningWorkflows {
+                        response_channel: sender,
+                    },
+                })
+                .expect("Failed to send list workflow request");
+
+            let response = test_utils::expect_oneshot_response(receiver).await;
+            response.into_iter().map(|x| x.name).collect()
+        }
+    }
+
+    fn get_state_file(test_name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "mmids_workflow_state_{}_{}.json",
+            test_name,
+            std::process::id()
+        ));
+
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn read_state_file(path: &PathBuf) -> Vec<WorkflowDefinition> {
+        let content = std::fs::read_to_string(path).expect("Failed to read state file");
+        serde_json::from_str(&content).expect("Failed to parse state file")
+    }
+
+    #[tokio::test]
+    async fn new_workflow_manager_registers_with_event_hub() {
+        let mut context = TestContext::new();
+
+        let event = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+        match event {
+            PublishEventRequest::WorkflowManagerEvent(event) => match event {
+                WorkflowManagerEvent::WorkflowManagerRegistered { channel: _ } => (),
+            },
+
+            event => panic!("Expected workflow manager event, instead got {:?}", event),
+        }
+    }
+
+    #[tokio::test]
+    async fn ping_is_responded_to() {
+        let context = TestContext::new();
+
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::Ping {
+                    response_channel: sender,
+                },
+            })
+            .expect("Failed to send ping request");
+
+        test_utils::expect_oneshot_response(receiver).await;
+    }
+
+    #[tokio::test]
+    async fn created_workflow_has_event_published() {
+        let mut context = TestContext::new();
+        test_utils::expect_mpsc_response(&mut context.event_hub).await; // manager registered event
+
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: WorkflowDefinition {
+                        name: "workflow".to_string(),
+                        routed_by_reactor: false,
+                        steps: Vec::new(),
+                    },
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        let event = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+        match event {
+            PublishEventRequest::WorkflowStartedOrStopped(event) => match event {
+                WorkflowStartedOrStoppedEvent::WorkflowStarted { name, channel: _ } => {
+                    assert_eq!(&name, "workflow", "Unexpected workflow name");
+                }
+
+                event => panic!("Unexpected workflow event received: {:?}", event),
+            },
+
+            event => panic!("Unexpected publish event received; {:?}", event),
+        }
+
+        test_utils::expect_mpsc_timeout(&mut context.event_hub).await;
+    }
+
+    #[tokio::test]
+    async fn created_workflow_shows_in_workflow_list() {
+        let context = TestContext::new();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: WorkflowDefinition {
+                        name: "workflow".to_string(),
+                        routed_by_reactor: false,
+                        steps: Vec::new(),
+                    },
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::GetRunningWorkflows {
+                    response_channel: sender,
+                },
+            })
+            .expect("failed to send list workflow request");
+
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        assert_eq!(response.len(), 1, "Unexpected number of workflows");
+        assert_eq!(response[0].name, "workflow", "Unexpected workflow name");
+    }
+
+    #[tokio::test]
+    async fn can_get_details_of_created_workflow() {
+        let context = TestContext::new();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: WorkflowDefinition {
+                        name: "workflow".to_string(),
+                        routed_by_reactor: false,
+                        steps: Vec::new(),
+                    },
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::GetWorkflowDetails {
+                    name: "workflow".to_string(),
+                    response_channel: sender,
+                },
+            })
+            .expect("failed to send list workflow request");
+
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        assert!(
+            response.is_some(),
+            "Expected workflow details to be returned"
+        );
+    }
+
+    #[tokio::test]
+    async fn all_workflow_details_contains_summary_of_created_workflow() {
+        let context = TestContext::new();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: WorkflowDefinition {
+                        name: "workflow".to_string(),
+                        routed_by_reactor: false,
+                        steps: Vec::new(),
+                    },
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::GetAllWorkflowDetails {
+                    response_channel: sender,
+                },
+            })
+            .expect("Failed to send all workflow details request");
+
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        assert_eq!(
+            response,
+            vec![WorkflowSummary {
+                name: "workflow".to_string(),
+                status: WorkflowSummaryStatus::Running,
+                step_count: Some(0),
+                active_stream_count: Some(0),
+            }],
+            "Unexpected workflow summaries"
+        );
+    }
+
+    #[tokio::test]
+    async fn all_workflow_details_empty_when_no_workflows_running() {
+        let context = TestContext::new();
+
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::GetAllWorkflowDetails {
+                    response_channel: sender,
+                },
+            })
+            .expect("Failed to send all workflow details request");
+
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        assert!(response.is_empty(), "Expected no workflow summaries");
+    }
+
+    #[tokio::test]
+    async fn workflow_not_responding_with_state_marked_unresponsive() {
+        let (sender, mut receiver) = unbounded_channel();
+        let summaries = get_workflow_summaries(
+            vec![("workflow".to_string(), sender)],
+            "".to_string(),
+            Duration::from_millis(5),
+        )
+        .await;
+
+        let request = test_utils::expect_mpsc_response(&mut receiver).await;
+        match request.operation {
+            WorkflowRequestOperation::GetState { .. } => (),
+            operation => panic!("Expected GetState request, instead got {:?}", operation),
+        }
+
+        assert_eq!(
+            summaries,
+            vec![WorkflowSummary {
+                name: "workflow".to_string(),
+                status: WorkflowSummaryStatus::Unresponsive,
+                step_count: None,
+                active_stream_count: None,
+            }],
+            "Unexpected workflow summaries"
+        );
+    }
+
+    #[tokio::test]
+    async fn second_upsert_request_does_not_send_second_stated_event() {
+        let mut context = TestContext::new();
+        test_utils::expect_mpsc_response(&mut context.event_hub).await; // manager registered event
+
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: WorkflowDefinition {
+                        name: "workflow".to_string(),
+                        routed_by_reactor: false,
+                        steps: Vec::new(),
+                    },
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        let _ = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: WorkflowDefinition {
+                        name: "workflow".to_string(),
+                        routed_by_reactor: false,
+                        steps: Vec::new(),
+                    },
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        test_utils::expect_mpsc_timeout(&mut context.event_hub).await;
+    }
+
+    /// Creates an actor managing a workflow whose requests are sent to the returned receiver
+    fn create_actor_with_workflow(
+        definition: WorkflowDefinition,
+    ) -> (Actor, UnboundedReceiver<WorkflowRequest>) {
+        let (event_hub_sender, _event_hub_receiver) = unbounded_channel();
+        let mut actor = Actor::new(Arc::new(WorkflowStepFactory::new()), event_hub_sender);
+
+        let (sender, receiver) = unbounded_channel();
+        actor.workflows.insert(definition.name.clone(), sender);
+        actor
+            .definitions
+            .insert(definition.name.clone(), definition);
+
+        (actor, receiver)
+    }
+
+    fn create_definition(step_type: &str) -> WorkflowDefinition {
+        WorkflowDefinition {
+            name: "workflow".to_string(),
+            routed_by_reactor: false,
+            steps: vec![WorkflowStepDefinition {
+                step_type: WorkflowStepType(step_type.to_string()),
+                parameters: HashMap::new(),
+            }],
+        }
+    }
+
+    /// Responds to the state request an unchanged upsert raises, and handles the resulting status
+    /// check the same way the running manager would
+    async fn respond_to_unchanged_upsert_status_check(
+        actor: &mut Actor,
+        workflow: &mut UnboundedReceiver<WorkflowRequest>,
+        status: WorkflowStatus,
+    ) {
+        let request = test_utils::expect_mpsc_response(workflow).await;
+        match request.operation {
+            WorkflowRequestOperation::GetState { response_channel } => {
+                let _ = response_channel.send(Some(WorkflowState {
+                    name: "workflow".to_string(),
+                    status,
+                    active_steps: Vec::new(),
+                    pending_steps: Vec::new(),
+                    video_bytes: 0,
+                    audio_bytes: 0,
+                    active_stream_count: 0,
+                    active_streams: Vec::new(),
+                    definition: create_definition("a"),
+                }));
+            }
+
+            operation => panic!("Expected GetState request, instead got {:?}", operation),
+        }
+
+        match test_utils::expect_future_resolved(&mut actor.futures).await {
+            FutureResult::UnchangedWorkflowStatusReceived {
+                request_id,
+                definition,
+                is_errored,
+            } => actor.handle_unchanged_workflow_status(request_id, definition, is_errored),
+
+            _ => panic!("Expected the unchanged workflow's status to be received"),
+        }
+    }
+
+    #[tokio::test]
+    async fn upsert_with_unchanged_definition_not_sent_to_healthy_workflow() {
+        let (mut actor, mut workflow) = create_actor_with_workflow(create_definition("a"));
+
+        let mut stop_manager = false;
+        actor.handle_request(
+            WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: create_definition("a"),
+                },
+            },
+            &mut stop_manager,
+        );
+
+        respond_to_unchanged_upsert_status_check(
+            &mut actor,
+            &mut workflow,
+            WorkflowStatus::Running,
+        )
+        .await;
+
+        test_utils::expect_mpsc_timeout(&mut workflow).await;
+    }
+
+    #[tokio::test]
+    async fn upsert_with_unchanged_definition_sent_to_errored_workflow() {
+        let (mut actor, mut workflow) = create_actor_with_workflow(create_definition("a"));
+
+        let mut stop_manager = false;
+        actor.handle_request(
+            WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: create_definition("a"),
+                },
+            },
+            &mut stop_manager,
+        );
+
+        respond_to_unchanged_upsert_status_check(
+            &mut actor,
+            &mut workflow,
+            WorkflowStatus::Error {
+                failed_step_id: 1,
+                message: "failed".to_string(),
+            },
+        )
+        .await;
+
+        let request = test_utils::expect_mpsc_response(&mut workflow).await;
+        match request.operation {
+            WorkflowRequestOperation::UpdateDefinition { new_definition } => {
+                assert_eq!(
+                    new_definition,
+                    create_definition("a"),
+                    "Unexpected definition"
+                );
+            }
+
+            operation => panic!("Expected UpdateDefinition, instead got {:?}", operation),
+        }
+    }
+
+    #[tokio::test]
+    async fn upsert_and_wait_sends_definition_update_and_wait_to_running_workflow() {
+        let (mut actor, mut workflow) = create_actor_with_workflow(create_definition("a"));
+
+        let (sender, _receiver) = channel();
+        let mut stop_manager = false;
+        actor.handle_request(
+            WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflowAndWait {
+                    definition: create_definition("b"),
+                    timeout: Duration::from_secs(5),
+                    response_channel: sender,
+                },
+            },
+            &mut stop_manager,
+        );
+
+        let request = test_utils::expect_mpsc_response(&mut workflow).await;
+        match request.operation {
+            WorkflowRequestOperation::UpdateDefinitionAndWait {
+                new_definition,
+                timeout,
+                ..
+            } => {
+                assert_eq!(
+                    new_definition,
+                    create_definition("b"),
+                    "Unexpected definition"
+                );
+                assert_eq!(timeout, Duration::from_secs(5), "Unexpected timeout");
+            }
+
+            operation => panic!(
+                "Expected UpdateDefinitionAndWait, instead got {:?}",
+                operation
+            ),
+        }
+
+        test_utils::expect_mpsc_timeout(&mut workflow).await;
+    }
+
+    #[tokio::test]
+    async fn upsert_and_wait_responds_once_new_workflow_applied() {
+        let context = TestContext::new();
+
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflowAndWait {
+                    definition: WorkflowDefinition {
+                        name: "workflow".to_string(),
+                        routed_by_reactor: false,
+                        steps: Vec::new(),
+                    },
+                    timeout: Duration::from_secs(5),
+                    response_channel: sender,
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        assert_eq!(
+            response,
+            DefinitionUpdateResult::Applied,
+            "Unexpected update result"
+        );
+
+        let names = context.get_running_workflow_names().await;
+        assert_eq!(names, vec!["workflow".to_string()], "Unexpected workflows");
+    }
+
+    #[tokio::test]
+    async fn upsert_with_changed_definition_sent_to_workflow() {
+        let (mut actor, mut workflow) = create_actor_with_workflow(create_definition("a"));
+
+        let mut stop_manager = false;
+        actor.handle_request(
+            WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: create_definition("b"),
+                },
+            },
+            &mut stop_manager,
+        );
+
+        let request = test_utils::expect_mpsc_response(&mut workflow).await;
+        match request.operation {
+            WorkflowRequestOperation::UpdateDefinition { new_definition } => {
+                assert_eq!(
+                    new_definition,
+                    create_definition("b"),
+                    "Unexpected definition"
+                );
+            }
+
+            operation => panic!("Expected UpdateDefinition, instead got {:?}", operation),
+        }
+
+        // Upserting the same changed definition again should only check the workflow's status
+        actor.handle_request(
+            WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: create_definition("b"),
+                },
+            },
+            &mut stop_manager,
+        );
+
+        let request = test_utils::expect_mpsc_response(&mut workflow).await;
+        match request.operation {
+            WorkflowRequestOperation::GetState { .. } => (),
+            operation => panic!("Expected GetState request, instead got {:?}", operation),
+        }
+
+        test_utils::expect_mpsc_timeout(&mut workflow).await;
+    }
+
+    #[tokio::test]
+    async fn second_created_workflow_does_not_duplicate_in_workflow_list() {
+        let context = TestContext::new();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: WorkflowDefinition {
+                        name: "workflow".to_string(),
+                        routed_by_reactor: false,
+                        steps: Vec::new(),
+                    },
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: WorkflowDefinition {
+                        name: "workflow".to_string(),
+                        routed_by_reactor: false,
+                        steps: Vec::new(),
+                    },
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::GetRunningWorkflows {
+                    response_channel: sender,
+                },
+            })
+            .expect("failed to send list workflow request");
+
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        assert_eq!(response.len(), 1, "Unexpected number of workflows");
+        assert_eq!(response[0].name, "workflow", "Unexpected workflow name");
+    }
+
+    #[tokio::test]
+    async fn stopping_workflow_sends_stopped_event() {
+        let mut context = TestContext::new();
+        test_utils::expect_mpsc_response(&mut context.event_hub).await; // manager registered event
+
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: WorkflowDefinition {
+                        name: "workflow".to_string(),
+                        routed_by_reactor: false,
+                        steps: Vec::new(),
+                    },
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        let _ = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::StopWorkflow {
+                    name: "workflow".to_string(),
+                    response_channel: None,
+                },
+            })
+            .expect("Failed to send stop command");
+
+        let event = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+        match event {
+            PublishEventRequest::WorkflowStartedOrStopped(event) => match event {
+                WorkflowStartedOrStoppedEvent::WorkflowEnded { name } => {
+                    assert_eq!(&name, "workflow", "Unexpected workflow name");
+                }
+
+                event => panic!("Unexpected workflow event received: {:?}", event),
+            },
+
+            event => panic!("Unexpected publish event received; {:?}", event),
+        }
+
+        test_utils::expect_mpsc_timeout(&mut context.event_hub).await;
+    }
+
+    #[tokio::test]
+    async fn stopped_workflow_does_not_show_in_workflow_list() {
+        let mut context = TestContext::new();
+        test_utils::expect_mpsc_response(&mut context.event_hub).await; // manager registered event
+
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: WorkflowDefinition {
+                        name: "workflow".to_string(),
+                        routed_by_reactor: false,
+                        steps: Vec::new(),
+                    },
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        let _ = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::StopWorkflow {
+                    name: "workflow".to_string(),
+                    response_channel: None,
+                },
+            })
+            .expect("Failed to send stop command");
+
+        let _ = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::GetRunningWorkflows {
+                    response_channel: sender,
+                },
+            })
+            .expect("Failed to send get running workflow request");
+
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        assert!(response.is_empty(), "Expected empty workflow list");
+    }
+
+    #[tokio::test]
+    async fn no_details_returned_for_stopped_workflow() {
+        let mut context = TestContext::new();
+        test_utils::expect_mpsc_response(&mut context.event_hub).await; // manager registered event
+
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: WorkflowDefinition {
+                        name: "workflow".to_string(),
+                        routed_by_reactor: false,
+                        steps: Vec::new(),
+                    },
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        let _ = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::StopWorkflow {
+                    name: "workflow".to_string(),
+                    response_channel: None,
+                },
+            })
+            .expect("Failed to send stop command");
+
+        let _ = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::GetWorkflowDetails {
+                    name: "workflow".to_string(),
+                    response_channel: sender,
+                },
+            })
+            .expect("Failed to send get running workflow request");
+
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        assert!(response.is_none(), "Expected no workflow details returned");
+    }
+
+    #[tokio::test]
+    async fn stop_workflow_responds_with_true_when_workflow_was_running() {
+        let mut context = TestContext::new();
+        test_utils::expect_mpsc_response(&mut context.event_hub).await; // manager registered event
+
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: WorkflowDefinition {
+                        name: "workflow".to_string(),
+                        routed_by_reactor: false,
+                        steps: Vec::new(),
+                    },
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        let _ = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::StopWorkflow {
+                    name: "workflow".to_string(),
+                    response_channel: Some(sender),
+                },
+            })
+            .expect("Failed to send stop command");
+
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        assert!(response, "Expected the workflow to have been stopped");
+    }
+
+    #[tokio::test]
+    async fn stop_workflow_responds_with_false_when_workflow_not_running() {
+        let mut context = TestContext::new();
+        test_utils::expect_mpsc_response(&mut context.event_hub).await; // manager registered event
+
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::StopWorkflow {
+                    name: "workflow".to_string(),
+                    response_channel: Some(sender),
+                },
+            })
+            .expect("Failed to send stop command");
+
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        assert!(!response, "Expected the workflow to not have been running");
+        test_utils::expect_mpsc_timeout(&mut context.event_hub).await;
+    }
+
+    #[tokio::test]
+    async fn stop_all_workflows_shuts_down_workflows_and_manager() {
+        let mut context = TestContext::new();
+        test_utils::expect_mpsc_response(&mut context.event_hub).await; // manager registered event
+
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: WorkflowDefinition {
+                        name: "workflow".to_string(),
+                        routed_by_reactor: false,
+                        steps: Vec::new(),
+                    },
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        let workflow = match test_utils::expect_mpsc_response(&mut context.event_hub).await {
+            PublishEventRequest::WorkflowStartedOrStopped(
+                WorkflowStartedOrStoppedEvent::WorkflowStarted { channel, .. },
+            ) => channel,
+
+            event => panic!("Unexpected publish event received: {:?}", event),
+        };
+
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::StopAllWorkflows {
+                    response_channel: Some(sender),
+                },
+            })
+            .expect("Failed to send stop all command");
+
+        test_utils::expect_oneshot_response(receiver).await;
+
+        match test_utils::expect_mpsc_response(&mut context.event_hub).await {
+            PublishEventRequest::WorkflowStartedOrStopped(
+                WorkflowStartedOrStoppedEvent::WorkflowEnded { name },
+            ) => assert_eq!(&name, "workflow", "Unexpected workflow name"),
+
+            event => panic!("Unexpected publish event received: {:?}", event),
+        }
+
+        tokio::time::timeout(Duration::from_millis(10), workflow.closed())
+            .await
+            .expect("Workflow channel didn't close");
+
+        tokio::time::timeout(Duration::from_millis(10), context.manager.closed())
+            .await
+            .expect("Workflow manager channel didn't close");
+    }
+
+    #[tokio::test]
+    async fn stop_all_workflows_not_completed_until_workflows_exit() {
+        let (mut actor, mut workflow) = create_actor_with_workflow(create_definition("a"));
+
+        let (sender, _receiver) = channel();
+        let mut stop_manager = false;
+        actor.handle_request(
+            WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::StopAllWorkflows {
+                    response_channel: Some(sender),
+                },
+            },
+            &mut stop_manager,
+        );
+
+        let request = test_utils::expect_mpsc_response(&mut workflow).await;
+        match request.operation {
+            WorkflowRequestOperation::Shutdown => (),
+            operation => panic!("Expected Shutdown request, instead got {:?}", operation),
+        }
+
+        assert!(
+            !stop_manager,
+            "Expected manager to wait for the workflow to exit"
+        );
+        let resolved = tokio::time::timeout(Duration::from_millis(10), actor.futures.next()).await;
+        assert!(
+            resolved.is_err(),
+            "Expected no future to resolve while the workflow is still running"
+        );
+
+        drop(workflow);
+        match test_utils::expect_future_resolved(&mut actor.futures).await {
+            FutureResult::AllWorkflowsExited {
+                response_channel: Some(_),
+            } => (),
+
+            _ => panic!("Expected all workflows to have exited"),
+        }
+    }
+
+    #[tokio::test]
+    async fn upserts_ignored_while_waiting_for_workflows_to_stop() {
+        let (mut actor, _workflow) = create_actor_with_workflow(create_definition("a"));
+
+        let mut stop_manager = false;
+        actor.handle_request(
+            WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::StopAllWorkflows {
+                    response_channel: None,
+                },
+            },
+            &mut stop_manager,
+        );
+
+        let mut definition = create_definition("a");
+        definition.name = "other".to_string();
+        actor.handle_request(
+            WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: definition.clone(),
+                },
+            },
+            &mut stop_manager,
+        );
+
+        let (sender, receiver) = channel();
+        actor.handle_request(
+            WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflowAndWait {
+                    definition,
+                    timeout: Duration::from_secs(1),
+                    response_channel: sender,
+                },
+            },
+            &mut stop_manager,
+        );
+
+        assert!(
+            actor.workflows.is_empty(),
+            "Expected no workflows to be started"
+        );
+        assert!(
+            !actor.definitions.contains_key("other"),
+            "Expected the upserted definition to not be stored"
+        );
+        assert!(
+            receiver.await.is_err(),
+            "Expected the upsert and wait response channel to be dropped"
+        );
+    }
+
+    fn test_media() -> MediaNotification {
+        MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: MediaNotificationContent::NewIncomingStream {
+                stream_name: "def".to_string(),
+            },
+            tags: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_statistics_gathered_from_running_workflows() {
+        let (sender, _event_hub) = unbounded_channel();
+        let mut factory = WorkflowStepFactory::new();
+        factory
+            .register(
+                WorkflowStepType("stream_stats".to_string()),
+                Box::new(StreamStatsStepGenerator::new()),
+            )
+            .expect("Failed to register stream_stats step");
+
+        let manager = start_workflow_manager(Arc::new(factory), sender);
+        manager
             .send(WorkflowManagerRequest {
                 request_id: "".to_string(),
                 operation: WorkflowManagerRequestOperation::UpsertWorkflow {
                     definition: WorkflowDefinition {
                         name: "workflow".to_string(),
                         routed_by_reactor: false,
-                        steps: Vec::new(),
+                        steps: vec![WorkflowStepDefinition {
+                            step_type: WorkflowStepType("stream_stats".to_string()),
+                            parameters: HashMap::new(),
+                        }],
                     },
                 },
             })
             .expect("Failed to send upsert request");
 
+        manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::SendMediaToWorkflow {
+                    name: "workflow".to_string(),
+                    media: test_media(),
+                    response_channel: None,
+                },
+            })
+            .expect("Failed to send media request");
+
         let (sender, receiver) = channel();
-        context
-            .manager
+        manager
             .send(WorkflowManagerRequest {
                 request_id: "".to_string(),
-                operation: WorkflowManagerRequestOperation::GetRunningWorkflows {
+                operation: WorkflowManagerRequestOperation::GetStreamStatistics {
                     response_channel: sender,
                 },
             })
-            .expect("failed to send list workflow request");
+            .expect("Failed to send stream statistics request");
 
         let response = test_utils::expect_oneshot_response(receiver).await;
-        assert_eq!(response.len(), 1, "Unexpected number of workflows");
-        assert_eq!(response[0].name, "workflow", "Unexpected workflow name");
+        assert_eq!(response.len(), 1, "Unexpected number of stream statistics");
+        assert_eq!(
+            response[0].stream_id,
+            test_media().stream_id,
+            "Unexpected stream id"
+        );
     }
 
     #[tokio::test]
-    async fn can_get_details_of_created_workflow() {
+    async fn workflow_not_responding_with_stream_statistics_left_out() {
+        let (sender, mut receiver) = unbounded_channel();
+        let statistics = get_stream_statistics(
+            vec![("workflow".to_string(), sender)],
+            "".to_string(),
+            Duration::from_millis(5),
+        )
+        .await;
+
+        let request = test_utils::expect_mpsc_response(&mut receiver).await;
+        match request.operation {
+            WorkflowRequestOperation::GetStreamStatistics { .. } => (),
+            operation => panic!(
+                "Expected GetStreamStatistics request, instead got {:?}",
+                operation
+            ),
+        }
+
+        assert!(statistics.is_empty(), "Expected no stream statistics");
+    }
+
+    #[tokio::test]
+    async fn media_sent_to_running_workflow_responds_with_true() {
         let context = TestContext::new();
         context
             .manager
@@ -395,41 +2395,40 @@ mod tests {
             .manager
             .send(WorkflowManagerRequest {
                 request_id: "".to_string(),
-                operation: WorkflowManagerRequestOperation::GetWorkflowDetails {
+                operation: WorkflowManagerRequestOperation::SendMediaToWorkflow {
                     name: "workflow".to_string(),
-                    response_channel: sender,
+                    media: test_media(),
+                    response_channel: Some(sender),
                 },
             })
-            .expect("failed to send list workflow request");
+            .expect("Failed to send media request");
 
         let response = test_utils::expect_oneshot_response(receiver).await;
-        assert!(
-            response.is_some(),
-            "Expected workflow details to be returned"
-        );
+        assert!(response, "Expected media to be sent to the workflow");
     }
 
     #[tokio::test]
-    async fn second_upsert_request_does_not_send_second_stated_event() {
-        let mut context = TestContext::new();
-        test_utils::expect_mpsc_response(&mut context.event_hub).await; // manager registered event
+    async fn media_sent_to_unknown_workflow_responds_with_false() {
+        let context = TestContext::new();
 
+        let (sender, receiver) = channel();
         context
             .manager
             .send(WorkflowManagerRequest {
                 request_id: "".to_string(),
-                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
-                    definition: WorkflowDefinition {
-                        name: "workflow".to_string(),
-                        routed_by_reactor: false,
-                        steps: Vec::new(),
-                    },
+                operation: WorkflowManagerRequestOperation::SendMediaToWorkflow {
+                    name: "workflow".to_string(),
+                    media: test_media(),
+                    response_channel: Some(sender),
                 },
             })
-            .expect("Failed to send upsert request");
+            .expect("Failed to send media request");
 
-        let _ = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        assert!(!response, "Expected media to be dropped");
+    }
 
+    async fn inject_media_into_running_workflow(context: &TestContext) -> bool {
         context
             .manager
             .send(WorkflowManagerRequest {
@@ -444,11 +2443,47 @@ mod tests {
             })
             .expect("Failed to send upsert request");
 
-        test_utils::expect_mpsc_timeout(&mut context.event_hub).await;
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::InjectMedia {
+                    workflow_name: "workflow".to_string(),
+                    notification: test_media(),
+                    response_channel: Some(sender),
+                },
+            })
+            .expect("Failed to send inject media request");
+
+        test_utils::expect_oneshot_response(receiver).await
     }
 
     #[tokio::test]
-    async fn second_created_workflow_does_not_duplicate_in_workflow_list() {
+    async fn injected_media_rejected_when_injection_not_enabled() {
+        let context = TestContext::new();
+        let response = inject_media_into_running_workflow(&context).await;
+
+        assert!(!response, "Expected injected media to be rejected");
+    }
+
+    #[tokio::test]
+    async fn injected_media_sent_to_workflow_when_injection_enabled() {
+        let context = TestContext::with_options(WorkflowManagerOptions {
+            allow_media_injection: true,
+            ..Default::default()
+        });
+
+        let response = inject_media_into_running_workflow(&context).await;
+
+        assert!(
+            response,
+            "Expected injected media to be sent to the workflow"
+        );
+    }
+
+    #[tokio::test]
+    async fn log_level_set_for_running_workflow_responds_with_true() {
         let context = TestContext::new();
         context
             .manager
@@ -464,174 +2499,406 @@ mod tests {
             })
             .expect("Failed to send upsert request");
 
+        let (sender, receiver) = channel();
         context
             .manager
             .send(WorkflowManagerRequest {
                 request_id: "".to_string(),
-                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
-                    definition: WorkflowDefinition {
-                        name: "workflow".to_string(),
-                        routed_by_reactor: false,
-                        steps: Vec::new(),
-                    },
+                operation: WorkflowManagerRequestOperation::SetLogLevel {
+                    name: "workflow".to_string(),
+                    level: Some(Level::DEBUG),
+                    response_channel: Some(sender),
                 },
             })
-            .expect("Failed to send upsert request");
+            .expect("Failed to send log level request");
+
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        assert!(response, "Expected log level to be set");
+    }
+
+    #[tokio::test]
+    async fn log_level_set_for_unknown_workflow_responds_with_false() {
+        let context = TestContext::new();
 
         let (sender, receiver) = channel();
         context
             .manager
             .send(WorkflowManagerRequest {
                 request_id: "".to_string(),
-                operation: WorkflowManagerRequestOperation::GetRunningWorkflows {
-                    response_channel: sender,
+                operation: WorkflowManagerRequestOperation::SetLogLevel {
+                    name: "workflow".to_string(),
+                    level: Some(Level::DEBUG),
+                    response_channel: Some(sender),
                 },
             })
-            .expect("failed to send list workflow request");
+            .expect("Failed to send log level request");
 
         let response = test_utils::expect_oneshot_response(receiver).await;
-        assert_eq!(response.len(), 1, "Unexpected number of workflows");
-        assert_eq!(response[0].name, "workflow", "Unexpected workflow name");
+        assert!(!response, "Expected no workflow to be found");
     }
 
     #[tokio::test]
-    async fn stopping_workflow_sends_stopped_event() {
-        let mut context = TestContext::new();
-        test_utils::expect_mpsc_response(&mut context.event_hub).await; // manager registered event
+    async fn cue_request_sent_to_registered_cue_channel() {
+        let context = TestContext::new();
+        let (cue_sender, mut cue_receiver) = unbounded_channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::RegisterCueChannel {
+                    name: "cues".to_string(),
+                    channel: cue_sender,
+                    response_channel: None,
+                },
+            })
+            .expect("Failed to send register request");
 
         context
             .manager
             .send(WorkflowManagerRequest {
                 request_id: "".to_string(),
-                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
-                    definition: WorkflowDefinition {
-                        name: "workflow".to_string(),
-                        routed_by_reactor: false,
-                        steps: Vec::new(),
+                operation: WorkflowManagerRequestOperation::InjectCue {
+                    cue_channel: "cues".to_string(),
+                    stream_name: "def".to_string(),
+                    id: "cue1".to_string(),
+                    duration: Duration::from_secs(30),
+                    response_channel: None,
+                },
+            })
+            .expect("Failed to send cue request");
+
+        let request = test_utils::expect_mpsc_response(&mut cue_receiver).await;
+        assert_eq!(&request.stream_name, "def", "Unexpected stream name");
+        assert_eq!(&request.id, "cue1", "Unexpected cue id");
+        assert_eq!(
+            request.duration,
+            Duration::from_secs(30),
+            "Unexpected cue duration"
+        );
+    }
+
+    #[tokio::test]
+    async fn cue_channel_registration_rejected_when_name_already_registered() {
+        let context = TestContext::new();
+        let (first_sender, _first_receiver) = unbounded_channel();
+        let (second_sender, _second_receiver) = unbounded_channel();
+
+        let mut responses = Vec::new();
+        for cue_channel in [first_sender, second_sender] {
+            let (sender, receiver) = channel();
+            context
+                .manager
+                .send(WorkflowManagerRequest {
+                    request_id: "".to_string(),
+                    operation: WorkflowManagerRequestOperation::RegisterCueChannel {
+                        name: "cues".to_string(),
+                        channel: cue_channel,
+                        response_channel: Some(sender),
                     },
+                })
+                .expect("Failed to send register request");
+
+            responses.push(test_utils::expect_oneshot_response(receiver).await);
+        }
+
+        assert_eq!(
+            responses,
+            vec![true, false],
+            "Unexpected registration responses"
+        );
+    }
+
+    #[tokio::test]
+    async fn cue_channel_registration_allowed_after_previous_channel_unregistered() {
+        let context = TestContext::new();
+        let (first_sender, _first_receiver) = unbounded_channel();
+        let (second_sender, mut second_receiver) = unbounded_channel();
+
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::RegisterCueChannel {
+                    name: "cues".to_string(),
+                    channel: first_sender.clone(),
+                    response_channel: None,
                 },
             })
-            .expect("Failed to send upsert request");
+            .expect("Failed to send register request");
 
-        let _ = test_utils::expect_mpsc_response(&mut context.event_hub).await;
         context
             .manager
             .send(WorkflowManagerRequest {
                 request_id: "".to_string(),
-                operation: WorkflowManagerRequestOperation::StopWorkflow {
-                    name: "workflow".to_string(),
+                operation: WorkflowManagerRequestOperation::UnregisterCueChannel {
+                    name: "cues".to_string(),
+                    channel: first_sender,
                 },
             })
-            .expect("Failed to send stop command");
+            .expect("Failed to send unregister request");
 
-        let event = test_utils::expect_mpsc_response(&mut context.event_hub).await;
-        match event {
-            PublishEventRequest::WorkflowStartedOrStopped(event) => match event {
-                WorkflowStartedOrStoppedEvent::WorkflowEnded { name } => {
-                    assert_eq!(&name, "workflow", "Unexpected workflow name");
-                }
+        let (sender, receiver) = channel();
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::RegisterCueChannel {
+                    name: "cues".to_string(),
+                    channel: second_sender,
+                    response_channel: Some(sender),
+                },
+            })
+            .expect("Failed to send register request");
 
-                event => panic!("Unexpected workflow event received: {:?}", event),
-            },
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        assert!(response, "Expected channel to be registered");
 
-            event => panic!("Unexpected publish event received; {:?}", event),
-        }
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::InjectCue {
+                    cue_channel: "cues".to_string(),
+                    stream_name: "def".to_string(),
+                    id: "cue1".to_string(),
+                    duration: Duration::from_secs(30),
+                    response_channel: None,
+                },
+            })
+            .expect("Failed to send cue request");
 
-        test_utils::expect_mpsc_timeout(&mut context.event_hub).await;
+        test_utils::expect_mpsc_response(&mut second_receiver).await;
     }
 
     #[tokio::test]
-    async fn stopped_workflow_does_not_show_in_workflow_list() {
-        let mut context = TestContext::new();
-        test_utils::expect_mpsc_response(&mut context.event_hub).await; // manager registered event
-
+    async fn cue_request_for_closed_cue_channel_responds_with_false() {
+        let context = TestContext::new();
+        let (cue_sender, cue_receiver) = unbounded_channel();
         context
             .manager
             .send(WorkflowManagerRequest {
                 request_id: "".to_string(),
-                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
-                    definition: WorkflowDefinition {
-                        name: "workflow".to_string(),
-                        routed_by_reactor: false,
-                        steps: Vec::new(),
-                    },
+                operation: WorkflowManagerRequestOperation::RegisterCueChannel {
+                    name: "cues".to_string(),
+                    channel: cue_sender,
+                    response_channel: None,
                 },
             })
-            .expect("Failed to send upsert request");
+            .expect("Failed to send register request");
 
-        let _ = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+        drop(cue_receiver);
+
+        let (sender, receiver) = channel();
         context
             .manager
             .send(WorkflowManagerRequest {
                 request_id: "".to_string(),
-                operation: WorkflowManagerRequestOperation::StopWorkflow {
-                    name: "workflow".to_string(),
+                operation: WorkflowManagerRequestOperation::InjectCue {
+                    cue_channel: "cues".to_string(),
+                    stream_name: "def".to_string(),
+                    id: "cue1".to_string(),
+                    duration: Duration::from_secs(30),
+                    response_channel: Some(sender),
                 },
             })
-            .expect("Failed to send stop command");
+            .expect("Failed to send cue request");
 
-        let _ = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+        let response = test_utils::expect_oneshot_response(receiver).await;
+        assert!(!response, "Expected cue to not be injected");
+    }
+
+    #[tokio::test]
+    async fn cue_request_for_unknown_cue_channel_responds_with_false() {
+        let context = TestContext::new();
 
         let (sender, receiver) = channel();
         context
             .manager
             .send(WorkflowManagerRequest {
                 request_id: "".to_string(),
-                operation: WorkflowManagerRequestOperation::GetRunningWorkflows {
-                    response_channel: sender,
+                operation: WorkflowManagerRequestOperation::InjectCue {
+                    cue_channel: "cues".to_string(),
+                    stream_name: "def".to_string(),
+                    id: "cue1".to_string(),
+                    duration: Duration::from_secs(30),
+                    response_channel: Some(sender),
                 },
             })
-            .expect("Failed to send get running workflow request");
+            .expect("Failed to send cue request");
 
         let response = test_utils::expect_oneshot_response(receiver).await;
-        assert!(response.is_empty(), "Expected empty workflow list");
+        assert!(!response, "Expected cue to not be injected");
     }
 
     #[tokio::test]
-    async fn no_details_returned_for_stopped_workflow() {
-        let mut context = TestContext::new();
-        test_utils::expect_mpsc_response(&mut context.event_hub).await; // manager registered event
+    async fn workflows_in_state_file_started_on_boot() {
+        let state_file = get_state_file("restore");
+        let definitions = vec![create_definition("a")];
+        std::fs::write(&state_file, serde_json::to_string(&definitions).unwrap())
+            .expect("Failed to write state file");
+
+        let context = TestContext::with_state_file(state_file.clone());
+        let names = context.get_running_workflow_names().await;
+        let _ = std::fs::remove_file(&state_file);
+
+        assert_eq!(names, vec!["workflow".to_string()], "Unexpected workflows");
+    }
+
+    #[tokio::test]
+    async fn restored_workflow_stopped_when_not_upserted_within_grace_period() {
+        let state_file = get_state_file("restore_unclaimed");
+        let definitions = vec![create_definition("a")];
+        std::fs::write(&state_file, serde_json::to_string(&definitions).unwrap())
+            .expect("Failed to write state file");
+
+        let context = TestContext::with_options(WorkflowManagerOptions {
+            state_file: Some(state_file.clone()),
+            state_save_delay: Duration::from_millis(1),
+            restored_workflow_grace_period: Duration::from_millis(20),
+            ..Default::default()
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let names = context.get_running_workflow_names().await;
+        let _ = std::fs::remove_file(&state_file);
+
+        assert!(names.is_empty(), "Expected restored workflow to be stopped");
+    }
+
+    #[tokio::test]
+    async fn restored_workflow_kept_when_upserted_within_grace_period() {
+        let state_file = get_state_file("restore_claimed");
+        let definitions = vec![create_definition("a")];
+        std::fs::write(&state_file, serde_json::to_string(&definitions).unwrap())
+            .expect("Failed to write state file");
+
+        let context = TestContext::with_options(WorkflowManagerOptions {
+            state_file: Some(state_file.clone()),
+            state_save_delay: Duration::from_millis(1),
+            restored_workflow_grace_period: Duration::from_millis(20),
+            ..Default::default()
+        });
 
         context
             .manager
             .send(WorkflowManagerRequest {
                 request_id: "".to_string(),
                 operation: WorkflowManagerRequestOperation::UpsertWorkflow {
-                    definition: WorkflowDefinition {
-                        name: "workflow".to_string(),
-                        routed_by_reactor: false,
-                        steps: Vec::new(),
-                    },
+                    definition: create_definition("a"),
                 },
             })
             .expect("Failed to send upsert request");
 
-        let _ = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let names = context.get_running_workflow_names().await;
+        let _ = std::fs::remove_file(&state_file);
+
+        assert_eq!(names, vec!["workflow".to_string()], "Unexpected workflows");
+    }
+
+    #[tokio::test]
+    async fn pending_state_save_written_when_manager_stops() {
+        let state_file = get_state_file("shutdown");
+        let context = TestContext::with_options(WorkflowManagerOptions {
+            state_file: Some(state_file.clone()),
+            state_save_delay: Duration::from_secs(60),
+            ..Default::default()
+        });
+
         context
             .manager
             .send(WorkflowManagerRequest {
                 request_id: "".to_string(),
-                operation: WorkflowManagerRequestOperation::StopWorkflow {
-                    name: "workflow".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: create_definition("a"),
                 },
             })
-            .expect("Failed to send stop command");
+            .expect("Failed to send upsert request");
 
-        let _ = test_utils::expect_mpsc_response(&mut context.event_hub).await;
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::StopAllWorkflows {
+                    response_channel: None,
+                },
+            })
+            .expect("Failed to send stop all command");
 
-        let (sender, receiver) = channel();
+        tokio::time::timeout(Duration::from_millis(50), context.manager.closed())
+            .await
+            .expect("Workflow manager channel didn't close");
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let definitions = read_state_file(&state_file);
+        let _ = std::fs::remove_file(&state_file);
+
+        assert_eq!(definitions, vec![create_definition("a")]);
+    }
+
+    #[tokio::test]
+    async fn missing_state_file_starts_with_no_workflows() {
+        let state_file = get_state_file("missing");
+        let context = TestContext::with_state_file(state_file);
+        let names = context.get_running_workflow_names().await;
+
+        assert!(names.is_empty(), "Expected no workflows to be running");
+    }
+
+    #[tokio::test]
+    async fn upserted_workflow_written_to_state_file() {
+        let state_file = get_state_file("upsert");
+        let context = TestContext::with_state_file(state_file.clone());
         context
             .manager
             .send(WorkflowManagerRequest {
                 request_id: "".to_string(),
-                operation: WorkflowManagerRequestOperation::GetWorkflowDetails {
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: create_definition("a"),
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let definitions = read_state_file(&state_file);
+        let _ = std::fs::remove_file(&state_file);
+
+        assert_eq!(definitions, vec![create_definition("a")]);
+    }
+
+    #[tokio::test]
+    async fn stopped_workflow_removed_from_state_file() {
+        let state_file = get_state_file("stop");
+        let context = TestContext::with_state_file(state_file.clone());
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::UpsertWorkflow {
+                    definition: create_definition("a"),
+                },
+            })
+            .expect("Failed to send upsert request");
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        context
+            .manager
+            .send(WorkflowManagerRequest {
+                request_id: "".to_string(),
+                operation: WorkflowManagerRequestOperation::StopWorkflow {
                     name: "workflow".to_string(),
-                    response_channel: sender,
+                    response_channel: None,
                 },
             })
-            .expect("Failed to send get running workflow request");
+            .expect("Failed to send stop request");
 
-        let response = test_utils::expect_oneshot_response(receiver).await;
-        assert!(response.is_none(), "Expected no workflow details returned");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let definitions = read_state_file(&state_file);
+        let _ = std::fs::remove_file(&state_file);
+
+        assert!(
+            definitions.is_empty(),
+            "Expected no workflows in state file"
+        );
     }
 }
@@ -4,11 +4,17 @@
 //! were defined.
 
 pub mod definitions;
+pub mod log_filter;
 pub mod manager;
 mod runner;
 pub mod steps;
 
-pub use runner::{start_workflow, WorkflowRequest, WorkflowRequestOperation, WorkflowStatus};
+pub use runner::{
+    start_workflow, start_workflow_with_drain_period, start_workflow_with_options,
+    DefinitionUpdateResult, StepExecutionTiming, StepRestartPolicy, WorkflowRequest,
+    WorkflowRequestOperation, WorkflowRunnerOptions, WorkflowStatus,
+    DEFAULT_MAX_MEDIA_OUTPUTS_PER_EXECUTION, DEFAULT_SLOW_STEP_THRESHOLD,
+};
 
 use crate::codecs::{AudioCodec, VideoCodec};
 use crate::endpoints::rtmp_server::RtmpEndpointMediaData;
@@ -19,16 +25,22 @@ use rml_rtmp::time::RtmpTimestamp;
 use std::collections::HashMap;
 use std::time::Duration;
 
-pub use runner::{WorkflowState, WorkflowStepState};
+pub use runner::{WorkflowState, WorkflowStepState, WorkflowStreamState};
 
-/// Notification about media coming across a specific stream
+/// Notification about media coming across a specific stream.  Code outside of `mmids-core` must
+/// create notifications with `MediaNotification::new()`, so adding fields isn't a breaking change.
 #[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
 pub struct MediaNotification {
     /// The identifier for the stream that this notification pertains to
     pub stream_id: StreamId,
 
     /// The content of the notification message
     pub content: MediaNotificationContent,
+
+    /// Labels attached to the notification by `tag` workflow steps, allowing later steps to
+    /// filter media by the branch it was tagged with.
+    pub tags: Vec<String>,
 }
 
 /// The detailed information contained within a media notification
@@ -66,6 +78,36 @@ pub enum MediaNotificationContent {
 
     /// New stream metadata
     Metadata { data: HashMap<String, String> },
+
+    /// Announces that the media following this notification may not be continuous with the media
+    /// that came before it for this stream, such as when the chain of steps the stream flows
+    /// through has changed or the stream's sequence headers have changed.  Steps that mux media
+    /// into segmented formats (e.g. HLS) should mark a discontinuity at this point, while all
+    /// other steps should pass it through.  More than one discontinuity may be raised for the
+    /// same point in a stream, so consecutive discontinuities should be treated as one.
+    Discontinuity,
+
+    /// A cue marker at this point in the stream, such as for SCTE-35 style ad insertion
+    /// signaling.  Steps that package media into formats supporting markers (e.g. HLS or DASH)
+    /// can translate it into their own marker, while all other steps should pass it through.
+    Cue {
+        /// Identifies the cue, so it can be correlated with the event that triggered it
+        id: String,
+
+        /// How long the signaled event (e.g. an ad break) lasts
+        duration: Duration,
+    },
+}
+
+impl MediaNotification {
+    /// Creates a new media notification for the specified stream, without any tags
+    pub fn new(stream_id: StreamId, content: MediaNotificationContent) -> Self {
+        MediaNotification {
+            stream_id,
+            content,
+            tags: Vec::new(),
+        }
+    }
 }
 
 impl MediaNotificationContent {
@@ -74,6 +116,8 @@ impl MediaNotificationContent {
         match self {
             MediaNotificationContent::StreamDisconnected => return None,
             MediaNotificationContent::NewIncomingStream { stream_name: _ } => return None,
+            MediaNotificationContent::Discontinuity => return None,
+            MediaNotificationContent::Cue { .. } => return None,
             MediaNotificationContent::Metadata { data } => {
                 Some(RtmpEndpointMediaData::NewStreamMetaData {
                     metadata: hash_map_to_stream_metadata(&data),
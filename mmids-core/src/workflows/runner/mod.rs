@@ -5,21 +5,26 @@ mod test_steps;
 #[cfg(test)]
 mod tests;
 
-use crate::workflows::definitions::{WorkflowDefinition, WorkflowStepDefinition};
+use crate::event_hub::{PublishEventRequest, WorkflowStepEvent, WorkflowStepTransition};
+use crate::workflows::definitions::{WorkflowDefinition, WorkflowStepDefinition, WorkflowStepType};
+use crate::workflows::log_filter::LOG_LEVEL_FIELD;
 use crate::workflows::steps::factory::WorkflowStepFactory;
+use crate::workflows::steps::stream_stats::StreamStatistics;
 use crate::workflows::steps::{
-    StepFutureResult, StepInputs, StepOutputs, StepStatus, WorkflowStep,
+    StepCreationError, StepFutureResult, StepInputs, StepOutputs, StepStatus, SupportedCodecs,
+    WorkflowStep,
 };
 use crate::workflows::{MediaNotification, MediaNotificationContent};
 use crate::StreamId;
 use futures::future::BoxFuture;
 use futures::stream::FuturesUnordered;
 use futures::{FutureExt, StreamExt};
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::oneshot::Sender;
-use tracing::{error, info, instrument, span, warn, Level};
+use tracing::{error, info, instrument, span, warn, Level, Span};
 
 /// A request to the workflow to perform an action
 #[derive(Debug)]
@@ -38,23 +43,75 @@ pub enum WorkflowRequestOperation {
     /// specified.
     UpdateDefinition { new_definition: WorkflowDefinition },
 
+    /// Requests the workflow update with a new definition, the same as `UpdateDefinition`, but
+    /// responds once all steps of the new definition are active, the workflow enters an error
+    /// state, or the timeout elapses (whichever comes first).
+    UpdateDefinitionAndWait {
+        new_definition: WorkflowDefinition,
+        timeout: Duration,
+        response_channel: Sender<DefinitionUpdateResult>,
+    },
+
     /// Requests the workflow to return a snapshot of its current state
     GetState {
         response_channel: Sender<Option<WorkflowState>>,
     },
 
-    /// Requests the workflow stop operating
+    /// Requests the latest statistics of every stream measured by the workflow's active steps
+    GetStreamStatistics {
+        response_channel: Sender<Vec<StreamStatistics>>,
+    },
+
+    /// Requests the workflow shut down every step it owns and stop operating
     StopWorkflow,
 
+    /// Handled the same as `StopWorkflow`.  Used when the owner of the workflow is going away, so
+    /// steps can clean up any resources they hold with other actors (such as endpoint
+    /// registrations) before the workflow exits.
+    Shutdown,
+
     /// Sends a media notification to this stream
     MediaNotification { media: MediaNotification },
+
+    /// Overrides the level this workflow logs at, or returns it to the global log level if
+    /// `None`.  Only takes effect when the `WorkflowLogFilterLayer` is part of the global
+    /// tracing subscriber.
+    SetLogLevel { level: Option<Level> },
 }
 
 #[derive(Debug)]
 pub struct WorkflowState {
+    pub name: String,
     pub status: WorkflowStatus,
     pub active_steps: Vec<WorkflowStepState>,
     pub pending_steps: Vec<WorkflowStepState>,
+
+    /// Total bytes of video that have originated within this workflow
+    pub video_bytes: u64,
+
+    /// Total bytes of audio that have originated within this workflow
+    pub audio_bytes: u64,
+
+    pub active_stream_count: usize,
+
+    /// Streams that are currently active within the workflow, ordered by stream name
+    pub active_streams: Vec<WorkflowStreamState>,
+
+    /// The most recently applied definition for the workflow.  Some of its steps may still be
+    /// pending.
+    pub definition: WorkflowDefinition,
+}
+
+/// A stream that is currently active within a workflow
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkflowStreamState {
+    pub stream_id: StreamId,
+
+    /// The name the stream was announced with by the step it originated from
+    pub stream_name: String,
+
+    /// The step the stream originated from
+    pub originating_step_id: u64,
 }
 
 #[derive(Debug)]
@@ -62,6 +119,42 @@ pub struct WorkflowStepState {
     pub step_id: u64,
     pub definition: WorkflowStepDefinition,
     pub status: StepStatus,
+
+    /// How long the step's recent executions have taken.  `None` if the step has not been
+    /// executed yet.
+    pub execution_timing: Option<StepExecutionTiming>,
+
+    /// Values the step reports for each of its streams, keyed by stream id and then by the
+    /// name of the value
+    pub stream_details: HashMap<StreamId, HashMap<String, String>>,
+}
+
+/// Statistics on how long a step took for its most recent executions
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepExecutionTiming {
+    pub average: Duration,
+    pub max: Duration,
+}
+
+/// The outcome of an `UpdateDefinitionAndWait` request
+#[derive(PartialEq, Clone, Debug)]
+pub enum DefinitionUpdateResult {
+    /// All steps of the new definition are active
+    Applied,
+
+    /// The workflow entered an error state before the new definition became active
+    Failed { failed_step_id: u64, message: String },
+
+    /// A step in the new definition had an invalid configuration, so the new definition was
+    /// discarded and the workflow kept running its previous definition
+    Rejected { failed_step_id: u64, message: String },
+
+    /// The new definition did not become active before the timeout elapsed.  The workflow will
+    /// still continue to wait for the pending steps to become active.
+    TimedOut,
+
+    /// Another definition update was requested before this one became active
+    Superseded,
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -73,13 +166,141 @@ pub enum WorkflowStatus {
     },
 }
 
+/// The default amount of time a single step execution can take before it is logged as slow
+pub const DEFAULT_SLOW_STEP_THRESHOLD: Duration = Duration::from_millis(10);
+
+/// The default maximum number of media notifications a single step execution can output
+pub const DEFAULT_MAX_MEDIA_OUTPUTS_PER_EXECUTION: usize = 10_000;
+
+/// The number of most recent executions of each step that execution timings are calculated from
+const EXECUTION_TIMING_WINDOW: usize = 100;
+
+/// The minimum amount of time between slow execution warnings for the same step.  Slow executions
+/// in between are counted and included in the next warning, so a steadily slow step doesn't
+/// flood the logs.
+const SLOW_STEP_WARNING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Options that change how a workflow runner operates
+#[derive(Clone, Debug)]
+pub struct WorkflowRunnerOptions {
+    /// How long a step removed by a definition update is kept around to pass along any media it
+    /// still produces.  A drain period of zero removes steps immediately.
+    pub step_drain_period: Duration,
+
+    /// When true, the most recent keyframe and all media following it (up to the next keyframe)
+    /// is cached for each stream.  Steps added to the workflow are then sent these frames right
+    /// after the stream's sequence headers, so they can start decoding without waiting for the
+    /// next keyframe.  This is off by default as it keeps a full GOP in memory per stream.
+    pub cache_latest_gop: bool,
+
+    /// If a single execution of a step takes longer than this threshold a warning is logged.
+    /// Steps are expected to never block, so a slow step usually indicates a bug in the step.
+    /// No warnings are logged when `None`.
+    pub slow_step_threshold: Option<Duration>,
+
+    /// If a single execution of a step outputs more video and audio notifications than this, a
+    /// warning is logged and the extra video and audio is dropped instead of being passed to the
+    /// next step.  This keeps a runaway step from consuming unbounded memory.  Other notifications
+    /// (such as new stream and disconnection notifications) are never dropped, as later steps
+    /// rely on them to track streams.  No limit is applied when `None`.
+    pub max_media_outputs_per_execution: Option<usize>,
+
+    /// If specified, an event is published to the event hub each time a step of the workflow
+    /// changes status or is removed from the workflow.
+    pub event_hub_publisher: Option<UnboundedSender<PublishEventRequest>>,
+
+    /// If specified, active steps that enter an error state are recreated instead of putting the
+    /// whole workflow into an error state.  When `None` any step error stops the workflow.
+    pub step_restart_policy: Option<StepRestartPolicy>,
+
+    /// If specified, the media cached from each step's outputs (sequence headers, and the latest
+    /// GOPs when enabled) is kept at or under this many bytes.  Media sent into the workflow is
+    /// cached separately and is held to the same limit.  When the limit is exceeded, cached media
+    /// is evicted in the following order until the cache fits:
+    ///
+    /// * All cached media of streams that are no longer active, as they are likely leftovers
+    /// * Latest GOPs of active streams
+    /// * Sequence headers of active streams
+    ///
+    /// Within each group the streams that received media least recently are evicted first.
+    pub max_cached_bytes_per_step: Option<usize>,
+
+    /// If specified, the media cached for all steps of the workflow combined is kept at or under
+    /// this many bytes.  Media is evicted in the same order as `max_cached_bytes_per_step`.
+    pub max_cached_bytes_per_workflow: Option<usize>,
+}
+
+/// Controls how errored steps are restarted
+#[derive(Clone, Debug)]
+pub struct StepRestartPolicy {
+    /// The maximum number of times a single step will be restarted within the failure window.
+    /// Once a step fails more times than this, the workflow is put into an error state.
+    pub max_restarts: u32,
+
+    /// How far back a step's failures are counted against its maximum number of restarts
+    pub failure_window: Duration,
+
+    /// How long to wait before recreating a step after its first failure within the failure
+    /// window.  The wait is doubled for each subsequent failure within the window.
+    pub initial_backoff: Duration,
+}
+
+impl Default for WorkflowRunnerOptions {
+    fn default() -> Self {
+        WorkflowRunnerOptions {
+            step_drain_period: Duration::from_secs(0),
+            cache_latest_gop: false,
+            slow_step_threshold: Some(DEFAULT_SLOW_STEP_THRESHOLD),
+            max_media_outputs_per_execution: Some(DEFAULT_MAX_MEDIA_OUTPUTS_PER_EXECUTION),
+            event_hub_publisher: None,
+            step_restart_policy: None,
+            max_cached_bytes_per_step: None,
+            max_cached_bytes_per_workflow: None,
+        }
+    }
+}
+
 /// Starts the execution of a workflow with the specified definition
 pub fn start_workflow(
     definition: WorkflowDefinition,
     step_factory: Arc<WorkflowStepFactory>,
+) -> UnboundedSender<WorkflowRequest> {
+    start_workflow_with_options(definition, step_factory, WorkflowRunnerOptions::default())
+}
+
+/// Starts the execution of a workflow with the specified definition.  When a definition update
+/// removes a step, the step will be shut down but kept around for the specified drain period, so
+/// any media it still produces (e.g. from an external process) will be passed on to the steps
+/// that followed it.  A drain period of zero removes steps immediately.
+pub fn start_workflow_with_drain_period(
+    definition: WorkflowDefinition,
+    step_factory: Arc<WorkflowStepFactory>,
+    drain_period: Duration,
+) -> UnboundedSender<WorkflowRequest> {
+    let options = WorkflowRunnerOptions {
+        step_drain_period: drain_period,
+        ..Default::default()
+    };
+
+    start_workflow_with_options(definition, step_factory, options)
+}
+
+/// Starts the execution of a workflow with the specified definition and runner options
+pub fn start_workflow_with_options(
+    definition: WorkflowDefinition,
+    step_factory: Arc<WorkflowStepFactory>,
+    options: WorkflowRunnerOptions,
 ) -> UnboundedSender<WorkflowRequest> {
     let (sender, receiver) = unbounded_channel();
-    let actor = Actor::new(&definition, step_factory, receiver);
+    let mut actor = Actor::new(&definition, step_factory, receiver);
+    actor.step_drain_period = options.step_drain_period;
+    actor.cache_latest_gop = options.cache_latest_gop;
+    actor.slow_step_threshold = options.slow_step_threshold;
+    actor.max_media_outputs_per_execution = options.max_media_outputs_per_execution;
+    actor.event_hub_publisher = options.event_hub_publisher;
+    actor.step_restart_policy = options.step_restart_policy;
+    actor.max_cached_bytes_per_step = options.max_cached_bytes_per_step;
+    actor.max_cached_bytes_per_workflow = options.max_cached_bytes_per_workflow;
     tokio::spawn(actor.run(definition));
 
     sender
@@ -91,19 +312,147 @@ enum FutureResult {
 
     StepFutureResolved {
         step_id: u64,
+        generation: u64,
         result: Box<dyn StepFutureResult>,
     },
+
+    StepDrainPeriodElapsed {
+        step_id: u64,
+    },
+
+    DefinitionUpdateTimeoutElapsed {
+        update_id: u64,
+    },
+
+    StepRestartBackoffElapsed {
+        step_id: u64,
+    },
 }
 
 struct StreamDetails {
     /// The step that first sent a new stream media notification.  We know that if this step is
     /// removed, the stream no longer has a source of video and should be considered disconnected
     originating_step_id: u64,
+
+    /// The stream name the originating step announced the stream with
+    stream_name: String,
+}
+
+/// A step that has been removed from the workflow and shut down, but is still allowed to pass
+/// along any media it produces until its drain period has elapsed and it has finished shutting
+/// down.
+struct DrainingStep {
+    step: Box<dyn WorkflowStep>,
+
+    /// The generation of the step instance that's draining
+    generation: u64,
+
+    /// The first surviving step that came after the draining step at the time it was removed.
+    /// Any media the draining step produces will be passed to this step.
+    next_step_id: Option<u64>,
+
+    cached_media: HashMap<StreamId, Vec<MediaNotification>>,
+
+    /// If the drain period is over, meaning the step can be dropped as soon as it's no longer
+    /// shutting down
+    drain_period_elapsed: bool,
+}
+
+/// The cached media of a single stream in one of the workflow's media caches
+struct CachedMediaEntry {
+    /// The step whose outputs the media was cached from, or `None` for media sent into the
+    /// workflow
+    step_id: Option<u64>,
+    stream_id: StreamId,
+    is_gop: bool,
+    bytes: usize,
+
+    /// Entries with a lower priority are evicted first
+    retention_priority: (u8, u64),
+}
+
+/// The amount of audio and video held in a media cache
+#[derive(Clone, Copy, Debug, Default)]
+struct CacheUsage {
+    bytes: usize,
+    packets: usize,
+}
+
+impl CacheUsage {
+    fn of(media: &[MediaNotification]) -> Self {
+        let mut usage = CacheUsage::default();
+        for media in media {
+            usage.add(media);
+        }
+
+        usage
+    }
+
+    fn add(&mut self, media: &MediaNotification) {
+        if is_audio_or_video(media) {
+            self.bytes += get_media_size(media);
+            self.packets += 1;
+        }
+    }
+
+    fn remove(&mut self, media: &[MediaNotification]) {
+        self.subtract(CacheUsage::of(media));
+    }
+
+    fn subtract(&mut self, other: CacheUsage) {
+        self.bytes = self.bytes.saturating_sub(other.bytes);
+        self.packets = self.packets.saturating_sub(other.packets);
+    }
+}
+
+/// Tracks how long the most recent executions of a step took
+#[derive(Default)]
+struct StepExecutionTimings {
+    recent: VecDeque<Duration>,
+    total: Duration,
+    last_slow_warning: Option<Instant>,
+    unreported_slow_executions: u32,
+}
+
+/// Restart bookkeeping for a step that has failed while a restart policy is in place
+#[derive(Default)]
+struct StepRestarts {
+    /// When the step failed, for failures that are still within the failure window
+    recent_failures: VecDeque<Instant>,
+
+    /// If the step has been shut down and is waiting for its backoff period to elapse before it's
+    /// recreated
+    is_restarting: bool,
+}
+
+/// The last step details that were published to the event hub
+struct PublishedStepState {
+    step_type: WorkflowStepType,
+    status: StepStatus,
+}
+
+/// A new definition that was discarded because one of its steps could not be created
+struct RejectedDefinition {
+    failed_step_id: u64,
+    message: String,
+}
+
+/// A caller waiting for a definition update to become active
+struct DefinitionUpdateWaiter {
+    update_id: u64,
+    response_channel: Sender<DefinitionUpdateResult>,
 }
 
 struct Actor {
     name: String,
+    definition: WorkflowDefinition,
     steps_by_definition_id: HashMap<u64, Box<dyn WorkflowStep>>,
+
+    /// The generation of each step's current instance.  A step id is reused when its step is
+    /// restarted or re-added, so futures are tagged with the generation of the instance that
+    /// created them to keep results meant for an old instance from reaching its replacement.
+    step_generations: HashMap<u64, u64>,
+    next_step_generation: u64,
     active_steps: Vec<u64>,
     pending_steps: Vec<u64>,
     futures: FuturesUnordered<BoxFuture<'static, FutureResult>>,
@@ -111,10 +460,43 @@ struct Actor {
     step_outputs: StepOutputs,
     cached_step_media: HashMap<u64, HashMap<StreamId, Vec<MediaNotification>>>,
     cached_inbound_media: HashMap<StreamId, Vec<MediaNotification>>,
+    cache_latest_gop: bool,
+    cached_step_gops: HashMap<u64, HashMap<StreamId, Vec<MediaNotification>>>,
+    cached_inbound_gops: HashMap<StreamId, Vec<MediaNotification>>,
     active_streams: HashMap<StreamId, StreamDetails>,
     step_factory: Arc<WorkflowStepFactory>,
     step_definitions: HashMap<u64, WorkflowStepDefinition>,
     status: WorkflowStatus,
+    draining_steps: HashMap<u64, DrainingStep>,
+    step_drain_period: Duration,
+    video_bytes: u64,
+    audio_bytes: u64,
+    definition_update_waiter: Option<DefinitionUpdateWaiter>,
+    next_definition_update_id: u64,
+    slow_step_threshold: Option<Duration>,
+    max_media_outputs_per_execution: Option<usize>,
+    step_execution_timings: HashMap<u64, StepExecutionTimings>,
+    event_hub_publisher: Option<UnboundedSender<PublishEventRequest>>,
+    published_step_states: HashMap<u64, PublishedStepState>,
+    step_restart_policy: Option<StepRestartPolicy>,
+    step_restarts: HashMap<u64, StepRestarts>,
+    max_cached_bytes_per_step: Option<usize>,
+    max_cached_bytes_per_workflow: Option<usize>,
+
+    /// Steps and the streams they have already been warned about receiving an unsupported codec
+    unsupported_codec_warnings: HashSet<(u64, StreamId)>,
+
+    /// When media for each stream was last seen, only tracked when media cache limits are set
+    stream_cache_updates: HashMap<StreamId, u64>,
+    next_cache_update: u64,
+
+    /// Running totals of the audio and video held in each step's media caches, with `None` for
+    /// media sent into the workflow.  Kept up to date as media is cached and evicted, so cache
+    /// limits can be checked without adding up every cached packet.
+    cache_usage: HashMap<Option<u64>, CacheUsage>,
+
+    /// The span the workflow executes in, which log level overrides are recorded on
+    execution_span: Span,
 }
 
 impl Actor {
@@ -131,26 +513,54 @@ impl Actor {
 
         Actor {
             name: definition.name.clone(),
+            definition: definition.clone(),
             futures,
             steps_by_definition_id: HashMap::new(),
+            step_generations: HashMap::new(),
+            next_step_generation: 0,
             active_steps: Vec::new(),
             pending_steps: Vec::new(),
             step_inputs: StepInputs::new(),
             step_outputs: StepOutputs::new(),
             cached_step_media: HashMap::new(),
             cached_inbound_media: HashMap::new(),
+            cache_latest_gop: false,
+            cached_step_gops: HashMap::new(),
+            cached_inbound_gops: HashMap::new(),
             active_streams: HashMap::new(),
             step_factory,
             step_definitions: HashMap::new(),
             status: WorkflowStatus::Running,
+            draining_steps: HashMap::new(),
+            step_drain_period: Duration::from_secs(0),
+            video_bytes: 0,
+            audio_bytes: 0,
+            definition_update_waiter: None,
+            next_definition_update_id: 0,
+            slow_step_threshold: None,
+            max_media_outputs_per_execution: None,
+            step_execution_timings: HashMap::new(),
+            event_hub_publisher: None,
+            published_step_states: HashMap::new(),
+            step_restart_policy: None,
+            step_restarts: HashMap::new(),
+            max_cached_bytes_per_step: None,
+            max_cached_bytes_per_workflow: None,
+            unsupported_codec_warnings: HashSet::new(),
+            stream_cache_updates: HashMap::new(),
+            next_cache_update: 0,
+            cache_usage: HashMap::new(),
+            execution_span: Span::none(),
         }
     }
 
-    #[instrument(name = "Workflow Execution", skip(self, initial_definition), fields(workflow_name = %self.name))]
+    #[instrument(name = "Workflow Execution", skip(self, initial_definition), fields(workflow_name = %self.name, log_level = tracing::field::Empty))]
     async fn run(mut self, initial_definition: WorkflowDefinition) {
         info!("Starting workflow");
+        self.execution_span = Span::current();
 
-        self.apply_new_definition(initial_definition);
+        let _ = self.apply_new_definition(initial_definition);
+        self.publish_step_events();
 
         while let Some(future) = self.futures.next().await {
             match future {
@@ -171,91 +581,173 @@ impl Actor {
                     }
                 }
 
-                FutureResult::StepFutureResolved { step_id, result } => {
-                    self.execute_steps(step_id, Some(result), false, true);
+                FutureResult::StepFutureResolved {
+                    step_id,
+                    generation,
+                    result,
+                } => {
+                    let is_draining = self
+                        .draining_steps
+                        .get(&step_id)
+                        .map(|draining| draining.generation == generation)
+                        .unwrap_or(false);
+
+                    if is_draining {
+                        self.execute_draining_step(step_id, result);
+                    } else if self.step_generations.get(&step_id) == Some(&generation) {
+                        self.execute_steps(step_id, Some(result), false, true);
+                    } else {
+                        info!(
+                            step_id = step_id,
+                            "Ignoring future result for a previous instance of step id {}", step_id
+                        );
+                    }
+                }
+
+                FutureResult::StepDrainPeriodElapsed { step_id } => {
+                    self.handle_drain_period_elapsed(step_id);
+                }
+
+                FutureResult::StepRestartBackoffElapsed { step_id } => {
+                    self.restart_step(step_id);
+                }
+
+                FutureResult::DefinitionUpdateTimeoutElapsed { update_id } => {
+                    let is_current_update = self
+                        .definition_update_waiter
+                        .as_ref()
+                        .map(|waiter| waiter.update_id == update_id)
+                        .unwrap_or(false);
+
+                    if is_current_update {
+                        if let Some(waiter) = self.definition_update_waiter.take() {
+                            warn!("Definition update did not become active before the timeout");
+                            let _ = waiter
+                                .response_channel
+                                .send(DefinitionUpdateResult::TimedOut);
+                        }
+                    }
                 }
             }
+
+            self.resolve_definition_update_waiter();
+            self.publish_step_events();
         }
 
         info!("Workflow closing");
+        let removed_steps = self.published_step_states.drain().collect::<Vec<_>>();
+        for (step_id, state) in removed_steps {
+            self.send_step_event(step_id, state.step_type, WorkflowStepTransition::Removed);
+        }
     }
 
     #[instrument(skip(self, request, stop_workflow), fields(request_id = %request.request_id))]
     fn handle_workflow_request(&mut self, request: WorkflowRequest, stop_workflow: &mut bool) {
         match request.operation {
             WorkflowRequestOperation::UpdateDefinition { new_definition } => {
-                self.apply_new_definition(new_definition);
+                if let Some(waiter) = self.definition_update_waiter.take() {
+                    let _ = waiter
+                        .response_channel
+                        .send(DefinitionUpdateResult::Superseded);
+                }
+
+                let _ = self.apply_new_definition(new_definition);
+            }
+
+            WorkflowRequestOperation::UpdateDefinitionAndWait {
+                new_definition,
+                timeout,
+                response_channel,
+            } => {
+                if let Some(waiter) = self.definition_update_waiter.take() {
+                    let _ = waiter
+                        .response_channel
+                        .send(DefinitionUpdateResult::Superseded);
+                }
+
+                if let Err(rejected) = self.apply_new_definition(new_definition) {
+                    let _ = response_channel.send(DefinitionUpdateResult::Rejected {
+                        failed_step_id: rejected.failed_step_id,
+                        message: rejected.message,
+                    });
+
+                    return;
+                }
+
+                let update_id = self.next_definition_update_id;
+                self.next_definition_update_id += 1;
+                self.definition_update_waiter = Some(DefinitionUpdateWaiter {
+                    update_id,
+                    response_channel,
+                });
+
+                self.futures
+                    .push(wait_for_definition_update_timeout(update_id, timeout).boxed());
             }
 
             WorkflowRequestOperation::GetState { response_channel } => {
                 info!("Workflow state requested by external caller");
                 let mut state = WorkflowState {
+                    name: self.name.clone(),
                     status: self.status.clone(),
                     pending_steps: Vec::new(),
                     active_steps: Vec::new(),
+                    video_bytes: self.video_bytes,
+                    audio_bytes: self.audio_bytes,
+                    active_stream_count: self.active_streams.len(),
+                    active_streams: Vec::new(),
+                    definition: self.definition.clone(),
                 };
 
+                state.active_streams = self
+                    .active_streams
+                    .iter()
+                    .map(|(stream_id, details)| WorkflowStreamState {
+                        stream_id: stream_id.clone(),
+                        stream_name: details.stream_name.clone(),
+                        originating_step_id: details.originating_step_id,
+                    })
+                    .collect();
+
+                state.active_streams.sort_by(|first, second| {
+                    (&first.stream_name, &first.stream_id.0)
+                        .cmp(&(&second.stream_name, &second.stream_id.0))
+                });
+
                 for id in &self.pending_steps {
-                    if let Some(definition) = self.step_definitions.get(&id) {
-                        if let Some(step) = self.steps_by_definition_id.get(&id) {
-                            state.pending_steps.push(WorkflowStepState {
-                                step_id: *id,
-                                definition: definition.clone(),
-                                status: step.get_status().clone(),
-                            });
-                        } else {
-                            state.pending_steps.push(WorkflowStepState {
-                                step_id: *id,
-                                definition: definition.clone(),
-                                status: StepStatus::Error {
-                                    message: "Step not instantiated".to_string(),
-                                },
-                            });
-                        }
-                    } else {
-                        error!(step_id = %id, "No definition was found for step id {}", id);
+                    if let Some(step_state) = self.get_step_state(*id) {
+                        state.pending_steps.push(step_state);
                     }
                 }
 
                 for id in &self.active_steps {
-                    if let Some(definition) = self.step_definitions.get(&id) {
-                        if let Some(step) = self.steps_by_definition_id.get(&id) {
-                            state.active_steps.push(WorkflowStepState {
-                                step_id: *id,
-                                definition: definition.clone(),
-                                status: step.get_status().clone(),
-                            });
-                        } else {
-                            state.active_steps.push(WorkflowStepState {
-                                step_id: *id,
-                                definition: definition.clone(),
-                                status: StepStatus::Error {
-                                    message: "Step not instantiated".to_string(),
-                                },
-                            });
-                        }
-                    } else {
-                        error!(step_id = %id, "No definition was found for step id {}", id);
+                    if let Some(step_state) = self.get_step_state(*id) {
+                        state.active_steps.push(step_state);
                     }
                 }
 
                 let _ = response_channel.send(Some(state));
             }
 
-            WorkflowRequestOperation::StopWorkflow => {
+            WorkflowRequestOperation::GetStreamStatistics { response_channel } => {
+                let statistics = self
+                    .active_steps
+                    .iter()
+                    .filter_map(|id| self.steps_by_definition_id.get(id))
+                    .flat_map(|step| step.get_stream_statistics())
+                    .collect();
+
+                let _ = response_channel.send(statistics);
+            }
+
+            WorkflowRequestOperation::StopWorkflow | WorkflowRequestOperation::Shutdown => {
                 info!("Closing workflow as requested");
                 *stop_workflow = true;
 
-                for id in &self.active_steps {
-                    if let Some(step) = self.steps_by_definition_id.get_mut(id) {
-                        step.shutdown();
-                    }
-                }
-
-                for id in &self.pending_steps {
-                    if let Some(step) = self.steps_by_definition_id.get_mut(id) {
-                        step.shutdown();
-                    }
+                for (id, step) in self.steps_by_definition_id.iter_mut() {
+                    let span = span!(Level::INFO, "Step Shutdown", step_id = %id);
+                    let _enter = span.enter();
+                    step.shutdown();
                 }
             }
 
@@ -268,10 +760,35 @@ impl Actor {
                     self.execute_steps(id, None, true, true);
                 }
             }
+
+            WorkflowRequestOperation::SetLogLevel { level } => {
+                match level {
+                    Some(level) => info!("Workflow log level set to {}", level),
+                    None => info!("Workflow log level returned to the global log level"),
+                }
+
+                let level = level.map(|level| level.as_str()).unwrap_or_default();
+                self.execution_span.record(LOG_LEVEL_FIELD, level);
+            }
         }
     }
 
-    fn apply_new_definition(&mut self, definition: WorkflowDefinition) {
+    fn apply_new_definition(
+        &mut self,
+        definition: WorkflowDefinition,
+    ) -> Result<(), RejectedDefinition> {
+        // A workflow that's fully running its current definition keeps it if the new definition
+        // turns out to be invalid, instead of tearing down working steps
+        let previous_definition = if self.status == WorkflowStatus::Running
+            && self.pending_steps.is_empty()
+            && !self.active_steps.is_empty()
+        {
+            Some(self.definition.clone())
+        } else {
+            None
+        };
+
+        self.definition = definition.clone();
         let new_step_ids = definition
             .steps
             .iter()
@@ -284,7 +801,7 @@ impl Actor {
             && self.active_steps.iter().all(|x| new_step_ids.contains(x))
         {
             // No actual changes to this workflow
-            return;
+            return Ok(());
         }
 
         info!(
@@ -299,8 +816,16 @@ impl Actor {
             failed_step_id: _,
         } = &self.status
         {
+            info!("Recovering workflow from error state");
             self.active_steps.clear();
             self.steps_by_definition_id.clear();
+            self.step_generations.clear();
+            self.draining_steps.clear();
+            self.cached_step_media.clear();
+            self.cached_step_gops.clear();
+            self.cache_usage.retain(|step_id, _| step_id.is_none());
+            self.active_streams.clear();
+            self.step_restarts.clear();
             self.status = WorkflowStatus::Running;
         }
 
@@ -313,7 +838,14 @@ impl Actor {
 
             self.pending_steps.push(id);
 
-            if !self.steps_by_definition_id.contains_key(&id) {
+            if self.draining_steps.contains_key(&id) {
+                // The step is being re-added before it finished draining, so the old instance
+                // needs to be fully removed before its replacement can be created.
+                self.finish_draining_step(id);
+            }
+
+            // Steps waiting to be restarted will be recreated once their backoff period elapses
+            if !self.steps_by_definition_id.contains_key(&id) && !self.is_restarting(id) {
                 let span = span!(Level::INFO, "Step Creation", step_id = id);
                 let _enter = span.enter();
 
@@ -327,39 +859,20 @@ impl Actor {
 
                 info!("Creating step {}", details);
 
-                let step_result = match self.step_factory.create_step(step_definition) {
-                    Ok(step_result) => step_result,
-                    Err(error) => {
-                        error!("Step factory failed to generate step instance: {:?}", error);
-                        self.set_status_to_error(
-                            id,
-                            format!("Failed to generate step instance: {:?}", error),
-                        );
-
-                        return;
-                    }
-                };
-
-                let (step, futures) = match step_result {
-                    Ok((step, futures)) => (step, futures),
-                    Err(error) => {
-                        error!("Step could not be generated: {}", error);
-                        self.set_status_to_error(id, format!("Failed to generate step: {}", error));
-
-                        return;
-                    }
-                };
-
-                for future in futures {
-                    self.futures.push(wait_for_step_future(id, future).boxed());
+                if let Err(error) = self.create_step_instance(id, step_definition) {
+                    return self.handle_step_creation_error(id, error, previous_definition);
                 }
 
-                self.steps_by_definition_id.insert(id, step);
                 info!("Step type '{}' created", step_type);
+
+                // Publish right away, otherwise steps that become active before the workflow
+                // is done applying the definition would never be seen in the created state
+                self.publish_step_events();
             }
         }
 
         self.check_if_all_pending_steps_are_active(true);
+        Ok(())
     }
 
     fn execute_steps(
@@ -412,9 +925,17 @@ impl Actor {
             return;
         }
 
+        if self.is_restarting(step_id) {
+            // Media can't flow past a step that's waiting to be recreated
+            self.step_inputs.clear();
+            return;
+        }
+
         let span = span!(Level::INFO, "Step Execution", step_id = step_id);
         let _enter = span.enter();
 
+        self.warn_on_unsupported_codecs(step_id);
+
         let step = match self.steps_by_definition_id.get_mut(&step_id) {
             Some(x) => x,
             None => {
@@ -428,20 +949,53 @@ impl Actor {
             }
         };
 
+        let started_at = Instant::now();
         step.execute(&mut self.step_inputs, &mut self.step_outputs);
+        let elapsed = started_at.elapsed();
+
+        let timings = self.step_execution_timings.entry(step_id).or_default();
+        timings.record(elapsed);
+
+        if let Some(threshold) = self.slow_step_threshold {
+            if elapsed > threshold {
+                if let Some(count) = timings.record_slow_execution(Instant::now()) {
+                    warn!(
+                        step_id = step_id,
+                        slow_executions = count,
+                        "Step id {} took {}ms to execute, which exceeds the slow step threshold \
+                        of {}ms ({} slow executions since the last warning)",
+                        step_id,
+                        elapsed.as_millis(),
+                        threshold.as_millis(),
+                        count,
+                    );
+                }
+            }
+        }
+
         if let StepStatus::Error { message } = step.get_status() {
             let message = message.clone();
-            self.set_status_to_error(step_id, message);
+            self.handle_step_error(step_id, message);
 
             return;
         }
 
+        let generation = self
+            .step_generations
+            .get(&step_id)
+            .copied()
+            .unwrap_or_default();
+
         for future in self.step_outputs.futures.drain(..) {
             self.futures
-                .push(wait_for_step_future(step.get_definition().get_id(), future).boxed());
+                .push(wait_for_step_future(step_id, generation, future).boxed());
         }
 
+        self.forward_keyframe_requests(step_id);
+        self.limit_media_outputs(step_id);
+        self.insert_sequence_header_discontinuities(step_id);
         self.update_stream_details(step_id);
+        self.update_byte_counts(step_id);
         self.update_media_cache_from_outputs(step_id);
         self.step_inputs.clear();
         self.step_inputs
@@ -451,9 +1005,140 @@ impl Actor {
         self.step_outputs.clear();
     }
 
+    /// Passes any keyframe requests raised by the step to the active steps before it, starting
+    /// with the closest, until one of them acts on the request
+    fn forward_keyframe_requests(&mut self, step_id: u64) {
+        if self.step_outputs.keyframe_requests.is_empty() {
+            return;
+        }
+
+        let step_index = match self.active_steps.iter().position(|id| *id == step_id) {
+            Some(index) => index,
+            None => {
+                self.step_outputs.keyframe_requests.clear();
+                return;
+            }
+        };
+
+        for stream_id in self.step_outputs.keyframe_requests.drain(..) {
+            let mut handled_by = None;
+            for upstream_step_id in self.active_steps[..step_index].iter().rev() {
+                if let Some(step) = self.steps_by_definition_id.get_mut(upstream_step_id) {
+                    if step.request_keyframe(&stream_id) {
+                        handled_by = Some(*upstream_step_id);
+                        break;
+                    }
+                }
+            }
+
+            match handled_by {
+                Some(upstream_step_id) => info!(
+                    stream_id = ?stream_id,
+                    "Keyframe request from step id {} for stream {:?} handled by step id {}",
+                    step_id, stream_id, upstream_step_id
+                ),
+
+                None => info!(
+                    stream_id = ?stream_id,
+                    "No step before step id {} was able to act on the keyframe request for stream {:?}",
+                    step_id, stream_id
+                ),
+            }
+        }
+    }
+
+    /// Logs a warning the first time each stream passes the step media in a codec that the step
+    /// has not declared support for, since the step will most likely not handle it correctly
+    fn warn_on_unsupported_codecs(&mut self, step_id: u64) {
+        let supported_codecs = match self.steps_by_definition_id.get(&step_id) {
+            Some(step) => step.get_supported_codecs(),
+            None => return,
+        };
+
+        if supported_codecs == SupportedCodecs::default() {
+            return;
+        }
+
+        for media in &self.step_inputs.media {
+            if media.content == MediaNotificationContent::StreamDisconnected {
+                // Allow a warning to be logged again if the stream reconnects
+                self.unsupported_codec_warnings
+                    .remove(&(step_id, media.stream_id.clone()));
+
+                continue;
+            }
+
+            let codec = match supported_codecs.get_unsupported_codec(&media.content) {
+                Some(codec) => codec,
+                None => continue,
+            };
+
+            if self
+                .unsupported_codec_warnings
+                .insert((step_id, media.stream_id.clone()))
+            {
+                let step_type = self
+                    .step_definitions
+                    .get(&step_id)
+                    .map(|definition| definition.step_type.0.as_str())
+                    .unwrap_or_default();
+
+                warn!(
+                    step_id = step_id,
+                    stream_id = ?media.stream_id,
+                    "Step id {} ({}) received {} for stream {:?}, which it does not support.  The \
+                    step will likely not process this media correctly",
+                    step_id, step_type, codec, media.stream_id
+                );
+            }
+        }
+    }
+
+    /// Drops any media the last executed step output past the maximum allowed for a single
+    /// execution, so a misbehaving step can't flood the rest of the workflow
+    fn limit_media_outputs(&mut self, step_id: u64) {
+        let limit = match self.max_media_outputs_per_execution {
+            Some(limit) => limit,
+            None => return,
+        };
+
+        let output_count = self
+            .step_outputs
+            .media
+            .iter()
+            .filter(|media| is_audio_or_video(media))
+            .count();
+
+        if output_count > limit {
+            warn!(
+                step_id = step_id,
+                "Step id {} output {} audio and video notifications in a single execution, which \
+                exceeds the limit of {}.  Dropping the extra notifications",
+                step_id,
+                output_count,
+                limit
+            );
+
+            let mut kept = 0;
+            self.step_outputs.media.retain(|media| {
+                if !is_audio_or_video(media) {
+                    return true;
+                }
+
+                kept += 1;
+                kept <= limit
+            });
+        }
+    }
+
     fn check_if_all_pending_steps_are_active(&mut self, swap_if_pending_is_empty: bool) {
         let mut all_are_active = true;
         for id in &self.pending_steps {
+            if self.is_restarting(*id) {
+                all_are_active = false;
+                continue;
+            }
+
             let step = match self.steps_by_definition_id.get(id) {
                 Some(x) => Some(x),
                 None => {
@@ -471,15 +1156,28 @@ impl Actor {
             if let Some(step) = step {
                 match step.get_status() {
                     StepStatus::Created => all_are_active = false,
+                    StepStatus::Reconnecting { .. } => all_are_active = false,
                     StepStatus::Active => (),
 
                     StepStatus::Error { message } => {
                         let id = *id;
                         let message = message.clone();
-                        self.set_status_to_error(id, message);
+                        self.handle_step_error(id, message);
+                        return;
+                    }
+                    StepStatus::ShuttingDown => {
+                        let id = *id;
+                        self.set_status_to_error(
+                            id,
+                            "step was unexpectedly shutting down".to_string(),
+                        );
+                        return;
+                    }
+                    StepStatus::Shutdown => {
+                        let id = *id;
+                        self.set_status_to_error(id, "step was unexpectedly shut down".to_string());
                         return;
                     }
-                    StepStatus::Shutdown => return,
                 }
             } else {
                 // the step is still waiting to be instantiated by the factory
@@ -516,12 +1214,32 @@ impl Actor {
                     // from these streams.
                     info!(step_id = step_id, "Removing now unused step id {}", step_id);
                     self.step_definitions.remove(&step_id);
-                    if let Some(mut step) = self.steps_by_definition_id.remove(&step_id) {
+                    self.step_execution_timings.remove(&step_id);
+                    self.step_restarts.remove(&step_id);
+
+                    let mut step = self.steps_by_definition_id.remove(&step_id);
+                    let generation = self.step_generations.remove(&step_id).unwrap_or_default();
+                    if let Some(step) = &mut step {
                         let span = span!(Level::INFO, "Step Shutdown", step_id = %step_id);
                         let _enter = span.enter();
                         step.shutdown();
                     }
 
+                    let is_shutting_down = step
+                        .as_ref()
+                        .map(|step| *step.get_status() == StepStatus::ShuttingDown)
+                        .unwrap_or(false);
+
+                    if self.step_drain_period > Duration::from_secs(0) || is_shutting_down {
+                        if let Some(step) = step {
+                            self.start_draining_step(index, step_id, generation, step);
+                        }
+
+                        continue;
+                    }
+
+                    self.cached_step_gops.remove(&step_id);
+                    self.cache_usage.remove(&Some(step_id));
                     if let Some(cache) = self.cached_step_media.remove(&step_id) {
                         for key in cache.keys() {
                             if let Some(stream) = self.active_streams.get(key) {
@@ -532,6 +1250,7 @@ impl Actor {
                                         self.step_inputs.media.push(MediaNotification {
                                             stream_id: key.clone(),
                                             content: MediaNotificationContent::StreamDisconnected,
+                                            tags: Vec::new(),
                                         });
 
                                         self.execute_step(self.active_steps[x]);
@@ -552,26 +1271,13 @@ impl Actor {
                 let current_step_id = self.pending_steps[index];
                 if !self.active_steps.contains(&current_step_id) {
                     // This is a new step
-                    let notifications = if index == 0 {
-                        // The first step uses the inbound cache, not step based cache
-                        self.cached_inbound_media
-                            .values()
-                            .flatten()
-                            .map(|x| x.clone())
-                            .collect::<Vec<_>>()
+                    let previous_step_id = if index == 0 {
+                        None
                     } else {
-                        let previous_step_id = self.pending_steps[index - 1];
-                        if let Some(cache) = self.cached_step_media.get(&previous_step_id) {
-                            cache
-                                .values()
-                                .flatten()
-                                .map(|x| x.clone())
-                                .collect::<Vec<_>>()
-                        } else {
-                            Vec::new()
-                        }
+                        Some(self.pending_steps[index - 1])
                     };
 
+                    let notifications = self.get_cached_media_for_new_step(previous_step_id);
                     self.step_inputs.clear();
                     self.step_inputs.media.extend(notifications);
                     self.execute_steps(current_step_id, None, true, false);
@@ -589,53 +1295,517 @@ impl Actor {
             }
 
             std::mem::swap(&mut self.pending_steps, &mut self.active_steps);
-            self.pending_steps.clear();
+            let previous_steps = std::mem::take(&mut self.pending_steps);
 
             info!("All pending steps moved to active");
+
+            self.raise_discontinuities_for_changed_steps(&previous_steps);
         }
     }
 
-    fn update_stream_details(&mut self, current_step_id: u64) {
-        for media in &self.step_outputs.media {
-            match &media.content {
-                MediaNotificationContent::Video { .. } => (),
-                MediaNotificationContent::Audio { .. } => (),
-                MediaNotificationContent::Metadata { .. } => (),
-                MediaNotificationContent::NewIncomingStream { .. } => {
-                    if !self.active_streams.contains_key(&media.stream_id) {
-                        // Since this is the first time we've gotten a new incoming stream
-                        // notification for this stream, assume this this stream originates from
-                        // the current step
-                        self.active_streams.insert(
-                            media.stream_id.clone(),
-                            StreamDetails {
-                                originating_step_id: current_step_id,
-                            },
-                        );
-                    }
-                }
+    /// Gets the cached media a newly created step needs to be caught up on the streams flowing
+    /// into it, based on the step before it.  The first step uses the inbound cache instead.
+    fn get_cached_media_for_new_step(
+        &self,
+        previous_step_id: Option<u64>,
+    ) -> Vec<MediaNotification> {
+        let (mut notifications, gop_cache) = match previous_step_id {
+            None => {
+                let notifications = self
+                    .cached_inbound_media
+                    .values()
+                    .flatten()
+                    .map(|x| x.clone())
+                    .collect::<Vec<_>>();
+
+                (notifications, Some(&self.cached_inbound_gops))
+            }
 
-                MediaNotificationContent::StreamDisconnected => {
-                    if let Some(details) = self.active_streams.get(&media.stream_id) {
-                        if details.originating_step_id == current_step_id {
-                            self.active_streams.remove(&media.stream_id);
+            Some(previous_step_id) => {
+                let notifications =
+                    if let Some(cache) = self.cached_step_media.get(&previous_step_id) {
+                        cache
+                            .values()
+                            .flatten()
+                            .map(|x| x.clone())
+                            .collect::<Vec<_>>()
+                    } else {
+                        Vec::new()
+                    };
+
+                (notifications, self.cached_step_gops.get(&previous_step_id))
+            }
+        };
+
+        // The cached GOPs must come after all sequence headers, otherwise the new step won't be
+        // able to decode them
+        if let Some(gop_cache) = gop_cache {
+            notifications.extend(gop_cache.values().flatten().cloned());
+        }
+
+        notifications
+    }
+
+    /// When the active steps change, steps after the first changed position may start receiving
+    /// media for existing streams that has gone through a different chain of steps than before
+    /// (e.g. a transcoding step was added or removed).  A discontinuity is raised for those
+    /// streams, so later steps know the media may not be continuous with what they've already
+    /// received.
+    fn raise_discontinuities_for_changed_steps(&mut self, previous_steps: &[u64]) {
+        let changed_index = match self
+            .active_steps
+            .iter()
+            .zip(previous_steps.iter())
+            .position(|(current, previous)| current != previous)
+        {
+            Some(index) => index,
+
+            // Steps were only added to or removed from the end of the workflow, so no step
+            // that existed before will see a different source of media.
+            None => return,
+        };
+
+        // Steps that were just added get the stream's cached media replayed to them, so only
+        // steps that were already receiving media for the stream need to know about the change.
+        let has_existing_downstream_step = self.active_steps[changed_index..]
+            .iter()
+            .any(|step_id| previous_steps.contains(step_id));
+
+        if !has_existing_downstream_step {
+            return;
+        }
+
+        let unchanged_steps = &self.active_steps[..changed_index];
+        let mut stream_ids = self
+            .active_streams
+            .iter()
+            .filter(|(_, details)| unchanged_steps.contains(&details.originating_step_id))
+            .map(|(stream_id, _)| stream_id.clone())
+            .collect::<Vec<_>>();
+
+        if stream_ids.is_empty() {
+            return;
+        }
+
+        stream_ids.sort_by(|a, b| a.0.cmp(&b.0));
+        info!(
+            "Raising discontinuities for {} streams due to workflow step changes",
+            stream_ids.len()
+        );
+
+        // Carry over the tags the stream had when it left the last unchanged step, so the
+        // discontinuity follows the same branches as the stream's media.
+        let previous_step_cache = self
+            .cached_step_media
+            .get(&unchanged_steps[changed_index - 1]);
+
+        self.step_inputs.clear();
+        for stream_id in stream_ids {
+            let tags = previous_step_cache
+                .and_then(|cache| cache.get(&stream_id))
+                .and_then(|media| media.last())
+                .map(|media| media.tags.clone())
+                .unwrap_or_default();
+
+            self.step_inputs.media.push(MediaNotification {
+                stream_id,
+                content: MediaNotificationContent::Discontinuity,
+                tags,
+            });
+        }
+
+        let step_id = self.active_steps[changed_index];
+        self.execute_steps(step_id, None, true, false);
+    }
+
+    /// If a stream's sequence headers change after they were first seen, downstream steps can't
+    /// assume the media that follows is continuous with what came before it.  So a discontinuity
+    /// is placed right before any changed sequence header raised by the step the stream
+    /// originates from.
+    fn insert_sequence_header_discontinuities(&mut self, step_id: u64) {
+        let step_cache = self.cached_step_media.get(&step_id);
+        let active_streams = &self.active_streams;
+        let needs_discontinuity = |media: &MediaNotification| {
+            let is_originating_step = match active_streams.get(&media.stream_id) {
+                Some(details) => details.originating_step_id == step_id,
+                None => false,
+            };
+
+            is_originating_step
+                && is_sequence_header_change(
+                    step_cache.and_then(|cache| cache.get(&media.stream_id)),
+                    media,
+                )
+        };
+
+        if !self.step_outputs.media.iter().any(&needs_discontinuity) {
+            return;
+        }
+
+        let mut media_with_discontinuities = Vec::new();
+        for media in self.step_outputs.media.drain(..) {
+            if needs_discontinuity(&media) {
+                info!(
+                    stream_id = ?media.stream_id,
+                    "Sequence header changed for stream {:?}, raising discontinuity", media.stream_id
+                );
+
+                media_with_discontinuities.push(MediaNotification {
+                    stream_id: media.stream_id.clone(),
+                    content: MediaNotificationContent::Discontinuity,
+                    tags: media.tags.clone(),
+                });
+            }
+
+            media_with_discontinuities.push(media);
+        }
+
+        self.step_outputs.media = media_with_discontinuities;
+    }
+
+    /// Publishes an event to the event hub for every step whose status has changed since the last
+    /// time events were published, and for every step that is no longer part of the workflow.
+    fn publish_step_events(&mut self) {
+        if self.event_hub_publisher.is_none() {
+            return;
+        }
+
+        let mut transitions = Vec::new();
+        for (step_id, step) in &self.steps_by_definition_id {
+            let status = step.get_status();
+            let has_changed = match self.published_step_states.get(step_id) {
+                Some(state) => &state.status != status,
+                None => true,
+            };
+
+            if has_changed {
+                let step_type = step.get_definition().step_type.clone();
+                transitions.push((
+                    *step_id,
+                    step_type.clone(),
+                    WorkflowStepTransition::StatusChanged(status.clone()),
+                ));
+
+                self.published_step_states.insert(
+                    *step_id,
+                    PublishedStepState {
+                        step_type,
+                        status: status.clone(),
+                    },
+                );
+            }
+        }
+
+        let removed_step_ids = self
+            .published_step_states
+            .keys()
+            .filter(|id| !self.steps_by_definition_id.contains_key(id) && !self.is_restarting(**id))
+            .copied()
+            .collect::<Vec<_>>();
+
+        for step_id in removed_step_ids {
+            if let Some(state) = self.published_step_states.remove(&step_id) {
+                transitions.push((step_id, state.step_type, WorkflowStepTransition::Removed));
+            }
+        }
+
+        for (step_id, step_type, transition) in transitions {
+            self.send_step_event(step_id, step_type, transition);
+        }
+    }
+
+    fn send_step_event(
+        &self,
+        step_id: u64,
+        step_type: WorkflowStepType,
+        transition: WorkflowStepTransition,
+    ) {
+        if let Some(publisher) = &self.event_hub_publisher {
+            let _ = publisher.send(PublishEventRequest::WorkflowStepEvent(WorkflowStepEvent {
+                workflow_name: self.name.clone(),
+                step_id,
+                step_type,
+                transition,
+            }));
+        }
+    }
+
+    fn get_step_state(&self, step_id: u64) -> Option<WorkflowStepState> {
+        let definition = match self.step_definitions.get(&step_id) {
+            Some(definition) => definition.clone(),
+            None => {
+                error!(step_id = %step_id, "No definition was found for step id {}", step_id);
+                return None;
+            }
+        };
+
+        if let Some(step) = self.steps_by_definition_id.get(&step_id) {
+            return Some(WorkflowStepState {
+                step_id,
+                definition,
+                status: step.get_status().clone(),
+                execution_timing: self.get_execution_timing(step_id),
+                stream_details: step.get_stream_details(),
+            });
+        }
+
+        let status = match self.step_restarts.get(&step_id) {
+            Some(restarts) if restarts.is_restarting => StepStatus::Reconnecting {
+                attempt: restarts.recent_failures.len() as u32,
+            },
+
+            _ => StepStatus::Error {
+                message: "Step not instantiated".to_string(),
+            },
+        };
+
+        Some(WorkflowStepState {
+            step_id,
+            definition,
+            status,
+            execution_timing: None,
+            stream_details: HashMap::new(),
+        })
+    }
+
+    fn get_execution_timing(&self, step_id: u64) -> Option<StepExecutionTiming> {
+        self.step_execution_timings
+            .get(&step_id)
+            .and_then(|timings| timings.summarize())
+    }
+
+    fn start_draining_step(
+        &mut self,
+        active_index: usize,
+        step_id: u64,
+        generation: u64,
+        step: Box<dyn WorkflowStep>,
+    ) {
+        let next_step_id = self.active_steps[(active_index + 1)..]
+            .iter()
+            .find(|id| self.pending_steps.contains(*id))
+            .map(|id| *id);
+
+        let drain_period_elapsed = self.step_drain_period == Duration::from_secs(0);
+        if drain_period_elapsed {
+            info!(
+                step_id = step_id,
+                "Waiting for step id {} to finish shutting down", step_id
+            );
+        } else {
+            info!(
+                step_id = step_id,
+                "Draining step id {} for {} milliseconds",
+                step_id,
+                self.step_drain_period.as_millis()
+            );
+
+            self.futures
+                .push(wait_for_drain_period(step_id, self.step_drain_period).boxed());
+        }
+
+        self.draining_steps.insert(
+            step_id,
+            DrainingStep {
+                step,
+                generation,
+                next_step_id,
+                cached_media: self.cached_step_media.remove(&step_id).unwrap_or_default(),
+                drain_period_elapsed,
+            },
+        );
+
+        self.cached_step_gops.remove(&step_id);
+        self.cache_usage.remove(&Some(step_id));
+    }
+
+    fn execute_draining_step(&mut self, step_id: u64, result: Box<dyn StepFutureResult>) {
+        let span = span!(Level::INFO, "Draining Step Execution", step_id = step_id);
+        let _enter = span.enter();
+
+        let (next_step_id, is_finished) = match self.draining_steps.get_mut(&step_id) {
+            Some(draining) => {
+                self.step_inputs.clear();
+                self.step_outputs.clear();
+                self.step_inputs.notifications.push(result);
+                draining
+                    .step
+                    .execute(&mut self.step_inputs, &mut self.step_outputs);
+
+                for future in self.step_outputs.futures.drain(..) {
+                    self.futures
+                        .push(wait_for_step_future(step_id, draining.generation, future).boxed());
+                }
+
+                let is_finished = draining.drain_period_elapsed
+                    && *draining.step.get_status() != StepStatus::ShuttingDown;
+
+                (draining.next_step_id, is_finished)
+            }
+
+            None => return,
+        };
+
+        self.limit_media_outputs(step_id);
+        let media = self.step_outputs.media.drain(..).collect::<Vec<_>>();
+        self.step_inputs.clear();
+        self.step_outputs.clear();
+
+        if let Some(next_step_id) = next_step_id {
+            if !media.is_empty() && self.steps_by_definition_id.contains_key(&next_step_id) {
+                self.step_inputs.media.extend(media);
+                self.execute_steps(next_step_id, None, true, false);
+            }
+        }
+
+        if is_finished {
+            self.finish_draining_step(step_id);
+        }
+    }
+
+    fn handle_drain_period_elapsed(&mut self, step_id: u64) {
+        let draining = match self.draining_steps.get_mut(&step_id) {
+            Some(draining) => draining,
+            None => return,
+        };
+
+        if *draining.step.get_status() == StepStatus::ShuttingDown {
+            // The step isn't safe to drop until it's done shutting down, so keep it around until
+            // it reports that it has.
+            info!(
+                step_id = step_id,
+                "Drain period elapsed for step id {} but it is still shutting down", step_id
+            );
+
+            draining.drain_period_elapsed = true;
+            return;
+        }
+
+        self.finish_draining_step(step_id);
+    }
+
+    fn finish_draining_step(&mut self, step_id: u64) {
+        let draining = match self.draining_steps.remove(&step_id) {
+            Some(draining) => draining,
+            None => return,
+        };
+
+        info!(step_id = step_id, "Step id {} finished draining", step_id);
+
+        // Now that the step is gone for good, any streams that originated from it need
+        // disconnection notices raised for the steps that came after it.
+        let start_index = draining
+            .next_step_id
+            .and_then(|id| self.active_steps.iter().position(|x| *x == id));
+
+        for key in draining.cached_media.keys() {
+            if let Some(stream) = self.active_streams.get(key) {
+                if stream.originating_step_id == step_id {
+                    if let Some(start_index) = start_index {
+                        for x in start_index..self.active_steps.len() {
+                            self.step_outputs.clear();
+                            self.step_inputs.clear();
+                            self.step_inputs.media.push(MediaNotification {
+                                stream_id: key.clone(),
+                                content: MediaNotificationContent::StreamDisconnected,
+                                tags: Vec::new(),
+                            });
+
+                            self.execute_step(self.active_steps[x]);
                         }
                     }
+
+                    self.active_streams.remove(key);
                 }
             }
         }
     }
 
+    fn update_stream_details(&mut self, current_step_id: u64) {
+        for media in &self.step_outputs.media {
+            match &media.content {
+                MediaNotificationContent::Video { .. } => (),
+                MediaNotificationContent::Audio { .. } => (),
+                MediaNotificationContent::Metadata { .. } => (),
+                MediaNotificationContent::Discontinuity => (),
+                MediaNotificationContent::Cue { .. } => (),
+                MediaNotificationContent::NewIncomingStream { stream_name } => {
+                    match self.active_streams.get_mut(&media.stream_id) {
+                        Some(details) => {
+                            // The originating step re-announcing the stream may have changed its
+                            // name.  Later steps renaming the stream don't change what it
+                            // originated as.
+                            if details.originating_step_id == current_step_id {
+                                details.stream_name = stream_name.clone();
+                            }
+                        }
+
+                        None => {
+                            // Since this is the first time we've gotten a new incoming stream
+                            // notification for this stream, assume this this stream originates
+                            // from the current step
+                            self.active_streams.insert(
+                                media.stream_id.clone(),
+                                StreamDetails {
+                                    originating_step_id: current_step_id,
+                                    stream_name: stream_name.clone(),
+                                },
+                            );
+                        }
+                    }
+                }
+
+                MediaNotificationContent::StreamDisconnected => {
+                    if let Some(details) = self.active_streams.get(&media.stream_id) {
+                        if details.originating_step_id == current_step_id {
+                            self.active_streams.remove(&media.stream_id);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_byte_counts(&mut self, current_step_id: u64) {
+        for media in &self.step_outputs.media {
+            // Only count media from the step the stream originated from, otherwise the same media
+            // would be counted again by every step it passes through
+            match self.active_streams.get(&media.stream_id) {
+                Some(details) if details.originating_step_id == current_step_id => (),
+                _ => continue,
+            }
+
+            match &media.content {
+                MediaNotificationContent::Video { data, .. } => {
+                    self.video_bytes += data.len() as u64;
+                }
+
+                MediaNotificationContent::Audio { data, .. } => {
+                    self.audio_bytes += data.len() as u64;
+                }
+
+                _ => (),
+            }
+        }
+    }
+
     fn update_inbound_media_cache(&mut self, media: &MediaNotification) {
+        let usage = self.cache_usage.entry(None).or_default();
         match media.content {
             MediaNotificationContent::NewIncomingStream { .. } => {
                 let collection = vec![media.clone()];
-                self.cached_inbound_media
-                    .insert(media.stream_id.clone(), collection);
+                if let Some(previous) = self
+                    .cached_inbound_media
+                    .insert(media.stream_id.clone(), collection)
+                {
+                    usage.remove(&previous);
+                }
             }
 
             MediaNotificationContent::StreamDisconnected => {
-                self.cached_inbound_media.remove(&media.stream_id);
+                if let Some(previous) = self.cached_inbound_media.remove(&media.stream_id) {
+                    usage.remove(&previous);
+                }
             }
 
             MediaNotificationContent::Audio {
@@ -644,6 +1814,7 @@ impl Actor {
             } => {
                 if let Some(collection) = self.cached_inbound_media.get_mut(&media.stream_id) {
                     collection.push(media.clone());
+                    usage.add(media);
                 }
             }
 
@@ -653,11 +1824,24 @@ impl Actor {
             } => {
                 if let Some(collectoin) = self.cached_inbound_media.get_mut(&media.stream_id) {
                     collectoin.push(media.clone());
+                    usage.add(media);
                 }
             }
 
             _ => (),
         }
+
+        if self.cache_latest_gop {
+            update_gop_cache(&mut self.cached_inbound_gops, usage, media);
+        }
+
+        if self.has_media_cache_limits() {
+            self.next_cache_update += 1;
+            self.stream_cache_updates
+                .insert(media.stream_id.clone(), self.next_cache_update);
+
+            self.enforce_media_cache_limits();
+        }
     }
 
     fn update_media_cache_from_outputs(&mut self, step_id: u64) {
@@ -666,6 +1850,8 @@ impl Actor {
             .entry(step_id)
             .or_insert(HashMap::new());
 
+        let usage = self.cache_usage.entry(Some(step_id)).or_default();
+
         for media in &self.step_outputs.media {
             enum Operation {
                 Add,
@@ -686,6 +1872,12 @@ impl Actor {
                     Operation::Ignore
                 }
 
+                MediaNotificationContent::Discontinuity => Operation::Ignore,
+
+                // Cues are tied to the moment they were raised, so replaying them to new steps
+                // would signal the same event twice
+                MediaNotificationContent::Cue { .. } => Operation::Ignore,
+
                 MediaNotificationContent::Video {
                     is_sequence_header, ..
                 } => {
@@ -711,7 +1903,9 @@ impl Actor {
             match operation {
                 Operation::Ignore => (),
                 Operation::Remove => {
-                    step_cache.remove(&media.stream_id);
+                    if let Some(previous) = step_cache.remove(&media.stream_id) {
+                        usage.remove(&previous);
+                    }
                 }
 
                 Operation::Add => {
@@ -720,9 +1914,483 @@ impl Actor {
                         .or_insert(Vec::new());
 
                     collection.push(media.clone());
+                    usage.add(media);
                 }
             }
         }
+
+        if self.cache_latest_gop {
+            let gop_cache = self
+                .cached_step_gops
+                .entry(step_id)
+                .or_insert(HashMap::new());
+
+            for media in &self.step_outputs.media {
+                update_gop_cache(gop_cache, usage, media);
+            }
+        }
+
+        if self.has_media_cache_limits() {
+            for media in &self.step_outputs.media {
+                self.next_cache_update += 1;
+                self.stream_cache_updates
+                    .insert(media.stream_id.clone(), self.next_cache_update);
+            }
+
+            self.enforce_media_cache_limits();
+        }
+    }
+
+    fn has_media_cache_limits(&self) -> bool {
+        self.max_cached_bytes_per_step.is_some() || self.max_cached_bytes_per_workflow.is_some()
+    }
+
+    /// Evicts cached media until every step's media cache, and all media caches combined, are
+    /// within their configured limits.
+    fn enforce_media_cache_limits(&mut self) {
+        // Checking the running totals first avoids building the full list of entries for every
+        // media notification when nothing needs to be evicted
+        let mut step_totals = self
+            .cache_usage
+            .iter()
+            .map(|(step_id, usage)| (*step_id, usage.bytes))
+            .collect::<HashMap<_, _>>();
+
+        let workflow_total = step_totals.values().sum::<usize>();
+        let step_limit_exceeded = match self.max_cached_bytes_per_step {
+            Some(max) => step_totals.values().any(|total| *total > max),
+            None => false,
+        };
+
+        let workflow_limit_exceeded = match self.max_cached_bytes_per_workflow {
+            Some(max) => workflow_total > max,
+            None => false,
+        };
+
+        if !step_limit_exceeded && !workflow_limit_exceeded {
+            return;
+        }
+
+        let mut entries = self.get_cached_media_entries();
+        entries.sort_by_key(|entry| entry.retention_priority);
+
+        let mut workflow_total = workflow_total;
+        for entry in entries {
+            let step_total = step_totals.entry(entry.step_id).or_default();
+            let exceeds_step_limit = match self.max_cached_bytes_per_step {
+                Some(max) => *step_total > max,
+                None => false,
+            };
+
+            let exceeds_workflow_limit = match self.max_cached_bytes_per_workflow {
+                Some(max) => workflow_total > max,
+                None => false,
+            };
+
+            if exceeds_step_limit || exceeds_workflow_limit {
+                *step_total = step_total.saturating_sub(entry.bytes);
+                workflow_total = workflow_total.saturating_sub(entry.bytes);
+
+                if exceeds_step_limit {
+                    self.evict_cached_media(entry, "step");
+                } else {
+                    self.evict_cached_media(entry, "workflow");
+                }
+            }
+        }
+
+        let cached_streams = self
+            .get_cached_media_entries()
+            .into_iter()
+            .map(|entry| entry.stream_id)
+            .collect::<HashSet<_>>();
+
+        let active_streams = &self.active_streams;
+        self.stream_cache_updates.retain(|stream_id, _| {
+            cached_streams.contains(stream_id) || active_streams.contains_key(stream_id)
+        });
+    }
+
+    /// Gets every stream's cached audio and video in all media caches.  Streams that don't have
+    /// any cached audio or video are not included, as evicting them would not free up anything.
+    fn get_cached_media_entries(&self) -> Vec<CachedMediaEntry> {
+        let caches = self
+            .cached_step_media
+            .iter()
+            .map(|(id, cache)| (Some(*id), cache, false))
+            .chain(std::iter::once((None, &self.cached_inbound_media, false)))
+            .chain(
+                self.cached_step_gops
+                    .iter()
+                    .map(|(id, cache)| (Some(*id), cache, true)),
+            )
+            .chain(std::iter::once((None, &self.cached_inbound_gops, true)));
+
+        let mut entries = Vec::new();
+        for (step_id, cache, is_gop) in caches {
+            for (stream_id, media) in cache {
+                let bytes = media.iter().map(get_media_size).sum::<usize>();
+                if bytes == 0 {
+                    continue;
+                }
+
+                let group = match (self.active_streams.contains_key(stream_id), is_gop) {
+                    (false, _) => 0,
+                    (true, true) => 1,
+                    (true, false) => 2,
+                };
+
+                let last_update = self
+                    .stream_cache_updates
+                    .get(stream_id)
+                    .copied()
+                    .unwrap_or_default();
+
+                entries.push(CachedMediaEntry {
+                    step_id,
+                    stream_id: stream_id.clone(),
+                    is_gop,
+                    bytes,
+                    retention_priority: (group, last_update),
+                });
+            }
+        }
+
+        entries
+    }
+
+    fn evict_cached_media(&mut self, entry: CachedMediaEntry, limit: &str) {
+        let is_active = self.active_streams.contains_key(&entry.stream_id);
+        let cache = match (entry.step_id, entry.is_gop) {
+            (Some(step_id), false) => self.cached_step_media.get_mut(&step_id),
+            (Some(step_id), true) => self.cached_step_gops.get_mut(&step_id),
+            (None, false) => Some(&mut self.cached_inbound_media),
+            (None, true) => Some(&mut self.cached_inbound_gops),
+        };
+
+        let cache = match cache {
+            Some(cache) => cache,
+            None => return,
+        };
+
+        let removed = if is_active && !entry.is_gop {
+            // Keep the new incoming stream notification, so steps created later still know the
+            // stream exists
+            cache.get_mut(&entry.stream_id).map(|media| {
+                let removed = CacheUsage::of(media);
+                media.retain(|media| !is_audio_or_video(media));
+                removed
+            })
+        } else {
+            cache
+                .remove(&entry.stream_id)
+                .map(|media| CacheUsage::of(&media))
+        };
+
+        let removed = removed.unwrap_or_default();
+        if let Some(usage) = self.cache_usage.get_mut(&entry.step_id) {
+            usage.subtract(removed);
+        }
+
+        warn!(
+            step_id = ?entry.step_id,
+            stream_id = ?entry.stream_id,
+            "Evicted {} bytes ({} packets) of cached {} for {} stream {:?} to stay within the per \
+                {} media cache limit",
+            removed.bytes,
+            removed.packets,
+            if entry.is_gop { "GOP media" } else { "sequence headers" },
+            if is_active { "active" } else { "inactive" },
+            entry.stream_id,
+            limit
+        );
+    }
+
+    /// Responds to the caller waiting on the latest definition update, if that update has either
+    /// become fully active or failed.
+    fn resolve_definition_update_waiter(&mut self) {
+        let result = match &self.status {
+            WorkflowStatus::Error {
+                failed_step_id,
+                message,
+            } => DefinitionUpdateResult::Failed {
+                failed_step_id: *failed_step_id,
+                message: message.clone(),
+            },
+
+            WorkflowStatus::Running if self.pending_steps.is_empty() => {
+                DefinitionUpdateResult::Applied
+            }
+
+            WorkflowStatus::Running => return,
+        };
+
+        if let Some(waiter) = self.definition_update_waiter.take() {
+            let _ = waiter.response_channel.send(result);
+        }
+    }
+
+    fn is_restarting(&self, step_id: u64) -> bool {
+        self.step_restarts
+            .get(&step_id)
+            .map(|restarts| restarts.is_restarting)
+            .unwrap_or(false)
+    }
+
+    /// Handles a step entering an error state.  If a restart policy was given and the step is
+    /// active, the step is shut down and recreated once its backoff period elapses.  The workflow
+    /// is only put into an error state if there's no restart policy, the step isn't active yet,
+    /// or the step has failed too many times within the policy's failure window.
+    fn handle_step_error(&mut self, step_id: u64, message: String) {
+        let policy = match &self.step_restart_policy {
+            Some(policy) => policy.clone(),
+            None => {
+                self.set_status_to_error(step_id, message);
+                return;
+            }
+        };
+
+        let active_index = match self.active_steps.iter().position(|id| *id == step_id) {
+            Some(index) => index,
+            None => {
+                self.set_status_to_error(step_id, message);
+                return;
+            }
+        };
+
+        let backoff = match self.record_step_failure(step_id, &policy, &message) {
+            Some(backoff) => backoff,
+            None => {
+                self.set_status_to_error(step_id, message);
+                return;
+            }
+        };
+
+        // Publish the error before the step is removed, otherwise it would never be seen
+        self.publish_step_events();
+
+        // Futures the failed instance still has outstanding are ignored once they resolve
+        self.step_generations.remove(&step_id);
+        if let Some(mut step) = self.steps_by_definition_id.remove(&step_id) {
+            let span = span!(Level::INFO, "Step Shutdown", step_id = %step_id);
+            let _enter = span.enter();
+            step.shutdown();
+        }
+
+        // Any media the step produced before it errored is not passed on
+        self.step_inputs.clear();
+        self.step_outputs.clear();
+
+        // Streams that originated from the failed step are gone, so later steps need to know not
+        // to expect any more media from them.
+        self.cached_step_gops.remove(&step_id);
+        if let Some(cache) = self.cached_step_media.remove(&step_id) {
+            for key in cache.keys() {
+                if let Some(stream) = self.active_streams.get(key) {
+                    if stream.originating_step_id == step_id {
+                        for x in (active_index + 1)..self.active_steps.len() {
+                            self.step_outputs.clear();
+                            self.step_inputs.clear();
+                            self.step_inputs.media.push(MediaNotification {
+                                stream_id: key.clone(),
+                                content: MediaNotificationContent::StreamDisconnected,
+                                tags: Vec::new(),
+                            });
+
+                            self.execute_step(self.active_steps[x]);
+                        }
+
+                        self.active_streams.remove(key);
+                    }
+                }
+            }
+
+            self.step_inputs.clear();
+            self.step_outputs.clear();
+        }
+
+        self.futures
+            .push(wait_for_step_restart_backoff(step_id, backoff).boxed());
+    }
+
+    /// Recreates a step that was shut down due to an error, and catches it up on the streams
+    /// flowing into it.
+    fn restart_step(&mut self, step_id: u64) {
+        match self.step_restarts.get_mut(&step_id) {
+            Some(restarts) if restarts.is_restarting => restarts.is_restarting = false,
+            _ => return, // The step was removed from the workflow while waiting
+        }
+
+        if self.status != WorkflowStatus::Running {
+            return;
+        }
+
+        let active_index = match self.active_steps.iter().position(|id| *id == step_id) {
+            Some(index) => index,
+            None => return,
+        };
+
+        let definition = match self.step_definitions.get(&step_id) {
+            Some(definition) => definition.clone(),
+            None => return,
+        };
+
+        {
+            let span = span!(Level::INFO, "Step Restart", step_id = step_id);
+            let _enter = span.enter();
+
+            info!("Restarting step type '{}'", definition.step_type);
+            if let Err(error) = self.create_step_instance(step_id, definition) {
+                self.handle_step_creation_error(step_id, error);
+                return;
+            }
+        }
+
+        let previous_step_id = if active_index == 0 {
+            None
+        } else {
+            Some(self.active_steps[active_index - 1])
+        };
+
+        let notifications = self.get_cached_media_for_new_step(previous_step_id);
+        self.step_inputs.clear();
+        self.step_inputs.media.extend(notifications);
+        self.execute_steps(step_id, None, true, true);
+    }
+
+    /// Records a failure of the specified step against the restart policy.  If the step can be
+    /// restarted it's marked as restarting and the backoff to wait before recreating it is
+    /// returned.  `None` is returned if the step has failed too many times to be restarted.
+    fn record_step_failure(
+        &mut self,
+        step_id: u64,
+        policy: &StepRestartPolicy,
+        message: &str,
+    ) -> Option<Duration> {
+        let now = Instant::now();
+        let restarts = self.step_restarts.entry(step_id).or_default();
+        while let Some(failed_at) = restarts.recent_failures.front() {
+            if now.saturating_duration_since(*failed_at) < policy.failure_window {
+                break;
+            }
+
+            restarts.recent_failures.pop_front();
+        }
+
+        restarts.recent_failures.push_back(now);
+        let failure_count = restarts.recent_failures.len() as u32;
+        if failure_count > policy.max_restarts {
+            error!(
+                step_id = step_id,
+                "Step id {} failed {} times within {} seconds, no longer restarting it",
+                step_id,
+                failure_count,
+                policy.failure_window.as_secs()
+            );
+
+            return None;
+        }
+
+        restarts.is_restarting = true;
+        let backoff = policy
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(failure_count - 1));
+
+        warn!(
+            step_id = step_id,
+            "Step id {} errored ({}), restarting it in {}ms (restart {} of {})",
+            step_id,
+            message,
+            backoff.as_millis(),
+            failure_count,
+            policy.max_restarts
+        );
+
+        Some(backoff)
+    }
+
+    /// Creates a new instance of the specified step and starts tracking its futures
+    fn create_step_instance(
+        &mut self,
+        step_id: u64,
+        definition: WorkflowStepDefinition,
+    ) -> Result<(), StepCreationError> {
+        let (step, futures) = match self.step_factory.create_step(definition) {
+            Ok(step_result) => step_result?,
+            Err(error) => return Err(StepCreationError::InvalidConfiguration(Box::new(error))),
+        };
+
+        let generation = self.next_step_generation;
+        self.next_step_generation += 1;
+        for future in futures {
+            self.futures
+                .push(wait_for_step_future(step_id, generation, future).boxed());
+        }
+
+        self.step_generations.insert(step_id, generation);
+        if let Some(mut previous) = self.steps_by_definition_id.insert(step_id, step) {
+            let span = span!(Level::INFO, "Step Shutdown", step_id = %step_id);
+            let _enter = span.enter();
+            previous.shutdown();
+        }
+
+        Ok(())
+    }
+
+    /// Handles a step instance failing to be created while a definition is being applied.
+    /// Creation failures would fail the same way if retried, so they are never retried, even if a
+    /// restart policy was given.  If the step's configuration is invalid and the workflow was
+    /// fully running a previous definition, the new definition is rejected and the previous one
+    /// keeps running.  Otherwise the workflow is put into an error state.
+    fn handle_step_creation_error(
+        &mut self,
+        step_id: u64,
+        error: StepCreationError,
+        previous_definition: Option<WorkflowDefinition>,
+    ) -> Result<(), RejectedDefinition> {
+        error!("Step could not be created: {}", error);
+        let message = format!("Failed to create step: {}", error);
+        match (error, previous_definition) {
+            (StepCreationError::InvalidConfiguration(_), Some(previous_definition)) => {
+                self.reject_new_definition(previous_definition);
+
+                Err(RejectedDefinition {
+                    failed_step_id: step_id,
+                    message,
+                })
+            }
+
+            _ => {
+                self.set_status_to_error(step_id, message);
+                Ok(())
+            }
+        }
+    }
+
+    /// Discards the definition that was being applied, shutting down any steps that were created
+    /// for it, and goes back to the previous definition.  Steps from the previous definition are
+    /// still active, so media keeps flowing through them as if the update never happened.
+    fn reject_new_definition(&mut self, previous_definition: WorkflowDefinition) {
+        warn!("New workflow definition rejected, the previous definition will keep running");
+        self.definition = previous_definition;
+
+        for step_id in std::mem::take(&mut self.pending_steps) {
+            if self.active_steps.contains(&step_id) {
+                continue;
+            }
+
+            self.step_definitions.remove(&step_id);
+            self.step_generations.remove(&step_id);
+            self.step_execution_timings.remove(&step_id);
+            self.step_restarts.remove(&step_id);
+            if let Some(mut step) = self.steps_by_definition_id.remove(&step_id) {
+                let span = span!(Level::INFO, "Step Shutdown", step_id = %step_id);
+                let _enter = span.enter();
+                step.shutdown();
+            }
+        }
+
+        self.publish_step_events();
     }
 
     fn set_status_to_error(&mut self, step_id: u64, message: String) {
@@ -730,6 +2398,9 @@ impl Actor {
             "Workflow set to error state due to step id {}: {}",
             step_id, message
         );
+
+        // Steps are about to be shut down, which would hide the errored step's status
+        self.publish_step_events();
         self.status = WorkflowStatus::Error {
             failed_step_id: step_id,
             message,
@@ -751,6 +2422,158 @@ impl Actor {
 
 unsafe impl Send for Actor {}
 
+impl StepExecutionTimings {
+    fn record(&mut self, duration: Duration) {
+        if self.recent.len() >= EXECUTION_TIMING_WINDOW {
+            if let Some(oldest) = self.recent.pop_front() {
+                self.total -= oldest;
+            }
+        }
+
+        self.recent.push_back(duration);
+        self.total += duration;
+    }
+
+    /// Records that an execution exceeded the slow step threshold.  Returns the number of slow
+    /// executions to include in a warning if one should be logged now, or `None` if a warning was
+    /// logged for the step too recently.
+    fn record_slow_execution(&mut self, now: Instant) -> Option<u32> {
+        self.unreported_slow_executions += 1;
+
+        let should_warn = match self.last_slow_warning {
+            Some(last_warning) => now.duration_since(last_warning) >= SLOW_STEP_WARNING_INTERVAL,
+            None => true,
+        };
+
+        if !should_warn {
+            return None;
+        }
+
+        self.last_slow_warning = Some(now);
+        Some(std::mem::take(&mut self.unreported_slow_executions))
+    }
+
+    fn summarize(&self) -> Option<StepExecutionTiming> {
+        let max = *self.recent.iter().max()?;
+        let average = self.total / self.recent.len() as u32;
+
+        Some(StepExecutionTiming { average, max })
+    }
+}
+
+/// Determines if the media is a sequence header that differs from the latest sequence header of
+/// the same type in the cached media for its stream.
+fn is_sequence_header_change(
+    cached_media: Option<&Vec<MediaNotification>>,
+    media: &MediaNotification,
+) -> bool {
+    let cached_media = match cached_media {
+        Some(cached_media) => cached_media,
+        None => return false,
+    };
+
+    // Only the codec and data are compared, as sequence headers are commonly resent with new
+    // timestamps without anything about the stream changing.
+    cached_media
+        .iter()
+        .rev()
+        .find_map(|cached| match (&cached.content, &media.content) {
+            (
+                MediaNotificationContent::Video {
+                    is_sequence_header: true,
+                    codec: previous_codec,
+                    data: previous_data,
+                    ..
+                },
+                MediaNotificationContent::Video {
+                    is_sequence_header: true,
+                    codec,
+                    data,
+                    ..
+                },
+            ) => Some(previous_codec != codec || previous_data != data),
+
+            (
+                MediaNotificationContent::Audio {
+                    is_sequence_header: true,
+                    codec: previous_codec,
+                    data: previous_data,
+                    ..
+                },
+                MediaNotificationContent::Audio {
+                    is_sequence_header: true,
+                    codec,
+                    data,
+                    ..
+                },
+            ) => Some(previous_codec != codec || previous_data != data),
+
+            _ => None,
+        })
+        .unwrap_or(false)
+}
+
+fn get_media_size(media: &MediaNotification) -> usize {
+    match &media.content {
+        MediaNotificationContent::Video { data, .. } => data.len(),
+        MediaNotificationContent::Audio { data, .. } => data.len(),
+        _ => 0,
+    }
+}
+
+/// Keeps track of the latest keyframe for each stream, and all audio and video that came after it.
+/// Media that arrives before the first keyframe of a stream is not cached.  The cache's usage is
+/// updated with any media added or removed.
+fn update_gop_cache(
+    cache: &mut HashMap<StreamId, Vec<MediaNotification>>,
+    usage: &mut CacheUsage,
+    media: &MediaNotification,
+) {
+    match &media.content {
+        MediaNotificationContent::NewIncomingStream { .. }
+        | MediaNotificationContent::StreamDisconnected => {
+            if let Some(previous) = cache.remove(&media.stream_id) {
+                usage.remove(&previous);
+            }
+        }
+
+        MediaNotificationContent::Video {
+            is_sequence_header: false,
+            is_keyframe: true,
+            ..
+        } => {
+            if let Some(previous) = cache.insert(media.stream_id.clone(), vec![media.clone()]) {
+                usage.remove(&previous);
+            }
+
+            usage.add(media);
+        }
+
+        MediaNotificationContent::Video {
+            is_sequence_header: false,
+            ..
+        }
+        | MediaNotificationContent::Audio {
+            is_sequence_header: false,
+            ..
+        } => {
+            if let Some(gop) = cache.get_mut(&media.stream_id) {
+                gop.push(media.clone());
+                usage.add(media);
+            }
+        }
+
+        _ => (),
+    }
+}
+
+fn is_audio_or_video(media: &MediaNotification) -> bool {
+    matches!(
+        media.content,
+        MediaNotificationContent::Video { .. } | MediaNotificationContent::Audio { .. }
+    )
+}
+
 async fn wait_for_workflow_request(
     mut receiver: UnboundedReceiver<WorkflowRequest>,
 ) -> FutureResult {
@@ -760,10 +2583,30 @@ async fn wait_for_workflow_request(
     }
 }
 
+async fn wait_for_drain_period(step_id: u64, drain_period: Duration) -> FutureResult {
+    tokio::time::sleep(drain_period).await;
+    FutureResult::StepDrainPeriodElapsed { step_id }
+}
+
+async fn wait_for_definition_update_timeout(update_id: u64, timeout: Duration) -> FutureResult {
+    tokio::time::sleep(timeout).await;
+    FutureResult::DefinitionUpdateTimeoutElapsed { update_id }
+}
+
+async fn wait_for_step_restart_backoff(step_id: u64, backoff: Duration) -> FutureResult {
+    tokio::time::sleep(backoff).await;
+    FutureResult::StepRestartBackoffElapsed { step_id }
+}
+
 async fn wait_for_step_future(
     step_id: u64,
+    generation: u64,
     future: BoxFuture<'static, Box<dyn StepFutureResult>>,
 ) -> FutureResult {
     let result = future.await;
-    FutureResult::StepFutureResolved { step_id, result }
+    FutureResult::StepFutureResolved {
+        step_id,
+        generation,
+        result,
+    }
 }
@@ -1,13 +1,15 @@
 use crate::workflows::definitions::{WorkflowDefinition, WorkflowStepDefinition, WorkflowStepType};
 use crate::workflows::runner::test_steps::{TestInputStepGenerator, TestOutputStepGenerator};
 use crate::workflows::steps::factory::WorkflowStepFactory;
-use crate::workflows::steps::StepStatus;
+use crate::workflows::steps::{StepStatus, SupportedCodecs};
 use crate::workflows::{
-    start_workflow, MediaNotification, MediaNotificationContent, WorkflowRequest,
+    start_workflow_with_options, MediaNotification, MediaNotificationContent, WorkflowRequest,
+    WorkflowRunnerOptions,
 };
 use crate::StreamId;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::watch::{channel, Sender};
 
@@ -19,27 +21,67 @@ pub struct TestContext {
     pub output_status: Sender<StepStatus>,
     pub input_step_id: u64,
     pub output_step_id: u64,
+    pub keyframe_requests: UnboundedReceiver<StreamId>,
 }
 
 impl TestContext {
     pub fn new() -> Self {
+        TestContext::with_drain_period(Duration::from_secs(0))
+    }
+
+    pub fn with_drain_period(drain_period: Duration) -> Self {
+        TestContext::with_options(WorkflowRunnerOptions {
+            step_drain_period: drain_period,
+            ..Default::default()
+        })
+    }
+
+    pub fn with_options(options: WorkflowRunnerOptions) -> Self {
+        TestContext::create(options, false, SupportedCodecs::default())
+    }
+
+    /// Creates a context whose input step does not finish shutting down until a `Shutdown`
+    /// status is sent to it
+    pub fn with_graceful_input_shutdown() -> Self {
+        TestContext::create(
+            WorkflowRunnerOptions::default(),
+            true,
+            SupportedCodecs::default(),
+        )
+    }
+
+    /// Creates a context whose output step only supports the specified codecs
+    pub fn with_output_supported_codecs(supported_codecs: SupportedCodecs) -> Self {
+        TestContext::create(WorkflowRunnerOptions::default(), false, supported_codecs)
+    }
+
+    fn create(
+        options: WorkflowRunnerOptions,
+        graceful_input_shutdown: bool,
+        output_supported_codecs: SupportedCodecs,
+    ) -> Self {
         let (input_media_sender, input_media_receiver) = channel(MediaNotification {
             stream_id: StreamId("invalid".to_string()),
             content: MediaNotificationContent::StreamDisconnected,
+            tags: Vec::new(),
         });
 
         let (output_media_sender, output_media_receiver) = unbounded_channel();
         let (input_status_sender, input_status_receiver) = channel(StepStatus::Created);
         let (output_status_sender, output_status_receiver) = channel(StepStatus::Created);
+        let (keyframe_request_sender, keyframe_request_receiver) = unbounded_channel();
 
         let input_step = TestInputStepGenerator {
             media_receiver: input_media_receiver,
             status_change: input_status_receiver,
+            graceful_shutdown: graceful_input_shutdown,
+            keyframe_requests: keyframe_request_sender,
         };
 
         let output_step = TestOutputStepGenerator {
             media_sender: output_media_sender,
             status_change: output_status_receiver,
+            supported_codecs: output_supported_codecs,
         };
 
         let mut factory = WorkflowStepFactory::new();
@@ -72,7 +114,7 @@ impl TestContext {
         let input_step_id = definition.steps[0].get_id();
         let output_step_id = definition.steps[1].get_id();
 
-        let workflow = start_workflow(definition, Arc::new(factory));
+        let workflow = start_workflow_with_options(definition, Arc::new(factory), options);
 
         TestContext {
             workflow,
@@ -82,6 +124,7 @@ impl TestContext {
             output_status: output_status_sender,
             input_step_id,
             output_step_id,
+            keyframe_requests: keyframe_request_receiver,
         }
     }
 }
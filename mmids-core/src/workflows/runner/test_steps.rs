@@ -1,32 +1,50 @@
 use crate::workflows::definitions::WorkflowStepDefinition;
 use crate::workflows::steps::factory::StepGenerator;
 use crate::workflows::steps::{
-    StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus, WorkflowStep,
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    SupportedCodecs, WorkflowStep,
 };
-use crate::workflows::MediaNotification;
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
 use futures::FutureExt;
+use thiserror::Error;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::watch::Receiver;
 
 pub struct TestInputStepGenerator {
     pub media_receiver: Receiver<MediaNotification>,
     pub status_change: Receiver<StepStatus>,
+
+    /// If true, generated steps will stay in the `ShuttingDown` state when shut down until a
+    /// `Shutdown` status is received
+    pub graceful_shutdown: bool,
+
+    /// Receives the stream ids of any keyframe requests the generated steps act on
+    pub keyframe_requests: UnboundedSender<StreamId>,
 }
 
+pub struct TestFailingStepGenerator;
+
+pub struct TestFatalStepGenerator;
+
 pub struct TestOutputStepGenerator {
     pub media_sender: UnboundedSender<MediaNotification>,
     pub status_change: Receiver<StepStatus>,
+    pub supported_codecs: SupportedCodecs,
 }
 
 struct TestInputStep {
     status: StepStatus,
     definition: WorkflowStepDefinition,
+    graceful_shutdown: bool,
+    keyframe_requests: UnboundedSender<StreamId>,
 }
 
 struct TestOutputStep {
     status: StepStatus,
     definition: WorkflowStepDefinition,
     media: UnboundedSender<MediaNotification>,
+    supported_codecs: SupportedCodecs,
 }
 
 impl StepFutureResult for InputFutureResult {}
@@ -48,6 +66,8 @@ impl StepGenerator for TestInputStepGenerator {
         let step = TestInputStep {
             status: StepStatus::Created,
             definition: definition.clone(),
+            graceful_shutdown: self.graceful_shutdown,
+            keyframe_requests: self.keyframe_requests.clone(),
         };
 
         let futures = vec![
@@ -59,12 +79,31 @@ impl StepGenerator for TestInputStepGenerator {
     }
 }
 
+#[derive(Error, Debug)]
+#[error("test step failed to generate")]
+struct TestStepGenerationError;
+
+impl StepGenerator for TestFailingStepGenerator {
+    fn generate(&self, _definition: WorkflowStepDefinition) -> StepCreationResult {
+        Err(StepCreationError::InvalidConfiguration(Box::new(
+            TestStepGenerationError,
+        )))
+    }
+}
+
+impl StepGenerator for TestFatalStepGenerator {
+    fn generate(&self, _definition: WorkflowStepDefinition) -> StepCreationResult {
+        Err(StepCreationError::Fatal(Box::new(TestStepGenerationError)))
+    }
+}
+
 impl StepGenerator for TestOutputStepGenerator {
     fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
         let step = TestOutputStep {
             status: StepStatus::Created,
             definition: definition.clone(),
             media: self.media_sender.clone(),
+            supported_codecs: self.supported_codecs.clone(),
         };
 
         let futures = vec![output_status_received(self.status_change.clone()).boxed()];
@@ -123,8 +162,17 @@ impl WorkflowStep for TestInputStep {
         }
     }
 
+    fn request_keyframe(&mut self, stream_id: &StreamId) -> bool {
+        let _ = self.keyframe_requests.send(stream_id.clone());
+        true
+    }
+
     fn shutdown(&mut self) {
-        self.status = StepStatus::Shutdown;
+        if self.graceful_shutdown {
+            self.status = StepStatus::ShuttingDown;
+        } else {
+            self.status = StepStatus::Shutdown;
+        }
     }
 }
 
@@ -161,10 +209,21 @@ impl WorkflowStep for TestOutputStep {
         }
 
         for media in inputs.media.drain(..) {
+            // Allows tests to have the step raise a keyframe request
+            if let MediaNotificationContent::Metadata { data } = &media.content {
+                if data.contains_key("request_keyframe") {
+                    outputs.keyframe_requests.push(media.stream_id.clone());
+                }
+            }
+
             let _ = self.media.send(media);
         }
     }
 
+    fn get_supported_codecs(&self) -> SupportedCodecs {
+        self.supported_codecs.clone()
+    }
+
     fn shutdown(&mut self) {
         self.status = StepStatus::Shutdown;
     }
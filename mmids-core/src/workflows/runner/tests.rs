@@ -1,17 +1,27 @@
+use crate::codecs::{AudioCodec, VideoCodec};
+use crate::event_hub::{PublishEventRequest, WorkflowStepEvent, WorkflowStepTransition};
 use crate::workflows::definitions::{WorkflowDefinition, WorkflowStepDefinition, WorkflowStepType};
 use crate::workflows::runner::test_context::TestContext;
+use crate::workflows::runner::test_steps::{
+    TestFailingStepGenerator, TestFatalStepGenerator, TestOutputStepGenerator,
+};
+use crate::workflows::runner::{StepExecutionTimings, SLOW_STEP_WARNING_INTERVAL};
 use crate::workflows::steps::factory::WorkflowStepFactory;
-use crate::workflows::steps::StepStatus;
+use crate::workflows::steps::{StepStatus, SupportedCodecs};
 use crate::workflows::MediaNotificationContent::StreamDisconnected;
 use crate::workflows::{
-    start_workflow, MediaNotification, MediaNotificationContent, WorkflowRequest,
-    WorkflowRequestOperation, WorkflowStatus,
+    start_workflow, start_workflow_with_options, DefinitionUpdateResult, MediaNotification,
+    MediaNotificationContent, StepRestartPolicy, WorkflowRequest, WorkflowRequestOperation,
+    WorkflowRunnerOptions, WorkflowState, WorkflowStatus, WorkflowStreamState,
 };
-use crate::{test_utils, StreamId};
+use crate::{test_utils, StreamId, VideoTimestamp};
+use bytes::Bytes;
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::oneshot::channel;
+use tokio::sync::watch;
 use tokio::time::timeout;
 
 #[tokio::test]
@@ -33,6 +43,7 @@ async fn workflow_created_with_steps_in_pending_state() {
     assert!(response.is_some(), "Expected workflow state returned");
 
     let workflow = response.unwrap();
+    assert_eq!(workflow.name, "abc", "Unexpected workflow name");
     assert_eq!(
         workflow.status,
         WorkflowStatus::Running,
@@ -218,6 +229,7 @@ async fn workflow_passes_media_from_one_step_to_the_next() {
         .send(MediaNotification {
             stream_id: StreamId("abc".to_string()),
             content: StreamDisconnected,
+            tags: Vec::new(),
         })
         .expect("Failed to send media notification to step");
 
@@ -234,6 +246,90 @@ async fn workflow_passes_media_from_one_step_to_the_next() {
     }
 }
 
+#[tokio::test]
+async fn keyframe_request_passed_to_earlier_step() {
+    let mut context = TestContext::new();
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let mut data = HashMap::new();
+    data.insert("request_keyframe".to_string(), "".to_string());
+    context
+        .media_sender
+        .send(MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: MediaNotificationContent::Metadata { data },
+            tags: Vec::new(),
+        })
+        .expect("Failed to send media notification to step");
+
+    let stream_id = test_utils::expect_mpsc_response(&mut context.keyframe_requests).await;
+    assert_eq!(
+        stream_id,
+        StreamId("abc".to_string()),
+        "Unexpected stream id"
+    );
+}
+
+#[tokio::test]
+async fn media_over_output_limit_not_passed_to_next_step() {
+    let mut context = TestContext::with_options(WorkflowRunnerOptions {
+        max_media_outputs_per_execution: Some(0),
+        ..Default::default()
+    });
+
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    context
+        .media_sender
+        .send(video_notification(1, true, false))
+        .expect("Failed to send media notification to step");
+
+    test_utils::expect_mpsc_timeout(&mut context.media_receiver).await;
+}
+
+#[tokio::test]
+async fn non_audio_video_media_passed_to_next_step_when_over_output_limit() {
+    let mut context = TestContext::with_options(WorkflowRunnerOptions {
+        max_media_outputs_per_execution: Some(0),
+        ..Default::default()
+    });
+
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let media = MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: StreamDisconnected,
+        tags: Vec::new(),
+    };
+
+    let received = send_and_receive(&mut context, media.clone()).await;
+    assert_eq!(received, media, "Unexpected media notification");
+}
+
 #[tokio::test]
 async fn media_sent_to_workflow_flows_through_steps() {
     let mut context = TestContext::new();
@@ -255,6 +351,7 @@ async fn media_sent_to_workflow_flows_through_steps() {
                 media: MediaNotification {
                     stream_id: StreamId("abc".to_string()),
                     content: StreamDisconnected,
+                    tags: Vec::new(),
                 },
             },
         })
@@ -466,6 +563,23 @@ async fn channel_closed_after_shutdown() {
     }
 }
 
+#[tokio::test]
+async fn channel_closed_after_shutdown_request() {
+    let context = TestContext::new();
+    context
+        .workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::Shutdown,
+        })
+        .expect("Failed to send shutdown message");
+
+    match timeout(Duration::from_millis(10), context.workflow.closed()).await {
+        Ok(_) => (),
+        Err(_) => panic!("Workflow channel didn't close"),
+    }
+}
+
 #[tokio::test]
 async fn workflow_in_error_state_if_factory_cant_find_step() {
     let factory = Arc::new(WorkflowStepFactory::new());
@@ -508,7 +622,7 @@ async fn workflow_in_error_state_if_factory_cant_find_step() {
 }
 
 #[tokio::test]
-async fn workflow_in_error_state_if_updated_steps_arent_registered_with_factory() {
+async fn previous_definition_kept_if_updated_steps_arent_registered_with_factory() {
     let context = TestContext::new();
     context
         .output_status
@@ -530,8 +644,6 @@ async fn workflow_in_error_state_if_updated_steps_arent_registered_with_factory(
         }],
     };
 
-    let step1_id = definition.steps[0].get_id();
-
     context
         .workflow
         .send(WorkflowRequest {
@@ -544,28 +656,1708 @@ async fn workflow_in_error_state_if_updated_steps_arent_registered_with_factory(
 
     tokio::time::sleep(Duration::from_millis(10)).await;
 
-    let (sender, receiver) = channel();
+    let state = get_workflow_state(&context).await;
+    assert_eq!(
+        state.status,
+        WorkflowStatus::Running,
+        "Unexpected workflow status"
+    );
+    assert!(state.pending_steps.is_empty(), "Expected no pending steps");
+    assert_eq!(
+        state.definition.steps[0].step_type,
+        WorkflowStepType("input".to_string()),
+        "Expected the previous definition to be kept"
+    );
+
+    let active_step_ids = state
+        .active_steps
+        .iter()
+        .map(|step| step.step_id)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        active_step_ids,
+        vec![context.input_step_id, context.output_step_id],
+        "Unexpected active steps"
+    );
+}
+
+#[tokio::test]
+async fn workflow_in_error_state_if_updated_step_has_fatal_creation_error() {
+    let (_status_sender, status_receiver) = watch::channel(StepStatus::Active);
+    let mut factory = WorkflowStepFactory::new();
+    factory
+        .register(
+            WorkflowStepType("output".to_string()),
+            Box::new(TestOutputStepGenerator {
+                media_sender: unbounded_channel().0,
+                status_change: status_receiver,
+                supported_codecs: SupportedCodecs::default(),
+            }),
+        )
+        .expect("Failed to register output step");
+
+    factory
+        .register(
+            WorkflowStepType("fatal".to_string()),
+            Box::new(TestFatalStepGenerator),
+        )
+        .expect("Failed to register fatal step");
+
+    let mut definition = WorkflowDefinition {
+        name: "abc".to_string(),
+        routed_by_reactor: false,
+        steps: vec![WorkflowStepDefinition {
+            step_type: WorkflowStepType("output".to_string()),
+            parameters: HashMap::new(),
+        }],
+    };
+
+    let workflow = start_workflow(definition.clone(), Arc::new(factory));
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    definition.steps.push(WorkflowStepDefinition {
+        step_type: WorkflowStepType("fatal".to_string()),
+        parameters: HashMap::new(),
+    });
+
+    let step_id = definition.steps[1].get_id();
+    workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::UpdateDefinition {
+                new_definition: definition,
+            },
+        })
+        .expect("Failed to send update request");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    match get_state(&workflow).await.status {
+        WorkflowStatus::Error { failed_step_id, .. } => {
+            assert_eq!(failed_step_id, step_id, "Unexpected failed step id");
+        }
+
+        status => panic!("Unexpected workflow status: {:?}", status),
+    }
+}
+
+#[tokio::test]
+async fn removed_step_media_passed_to_next_step_while_draining() {
+    let mut context = TestContext::with_drain_period(Duration::from_millis(500));
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let definition = WorkflowDefinition {
+        name: "abc".to_string(),
+        routed_by_reactor: false,
+        steps: vec![WorkflowStepDefinition {
+            step_type: WorkflowStepType("output".to_string()),
+            parameters: HashMap::new(),
+        }],
+    };
+
     context
         .workflow
         .send(WorkflowRequest {
             request_id: "".to_string(),
-            operation: WorkflowRequestOperation::GetState {
-                response_channel: sender,
+            operation: WorkflowRequestOperation::UpdateDefinition {
+                new_definition: definition,
             },
         })
-        .expect("Failed to send get state request");
+        .expect("Failed to send update request");
 
-    let response = test_utils::expect_oneshot_response(receiver).await;
-    assert!(response.is_some(), "Expected valid response");
+    tokio::time::sleep(Duration::from_millis(10)).await;
 
-    match response.unwrap().status {
-        WorkflowStatus::Error {
-            message: _,
-            failed_step_id,
-        } => {
-            assert_eq!(failed_step_id, step1_id, "Unexpected failed step id");
+    context
+        .media_sender
+        .send(MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: MediaNotificationContent::NewIncomingStream {
+                stream_name: "def".to_string(),
+            },
+            tags: Vec::new(),
+        })
+        .expect("Failed to send media");
+
+    let media = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    assert_eq!(media.stream_id.0, "abc", "Unexpected stream id");
+    match media.content {
+        MediaNotificationContent::NewIncomingStream { stream_name } => {
+            assert_eq!(stream_name, "def", "Unexpected stream name");
         }
 
-        status => panic!("Unexpected workflow status: {:?}", status),
+        content => panic!("Unexpected media content: {:?}", content),
     }
 }
+
+#[tokio::test]
+async fn stream_disconnected_after_removed_step_finishes_draining() {
+    let mut context = TestContext::with_drain_period(Duration::from_millis(50));
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    context
+        .media_sender
+        .send(MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: MediaNotificationContent::NewIncomingStream {
+                stream_name: "def".to_string(),
+            },
+            tags: Vec::new(),
+        })
+        .expect("Failed to send media");
+
+    let _ = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+
+    let definition = WorkflowDefinition {
+        name: "abc".to_string(),
+        routed_by_reactor: false,
+        steps: vec![WorkflowStepDefinition {
+            step_type: WorkflowStepType("output".to_string()),
+            parameters: HashMap::new(),
+        }],
+    };
+
+    context
+        .workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::UpdateDefinition {
+                new_definition: definition,
+            },
+        })
+        .expect("Failed to send update request");
+
+    // No disconnection should be raised until the drain period has elapsed
+    test_utils::expect_mpsc_timeout(&mut context.media_receiver).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let media = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    assert_eq!(media.stream_id.0, "abc", "Unexpected stream id");
+    assert_eq!(
+        media.content, StreamDisconnected,
+        "Expected stream disconnected notification"
+    );
+}
+
+#[tokio::test]
+async fn removed_step_kept_until_it_finishes_shutting_down() {
+    let mut context = TestContext::with_graceful_input_shutdown();
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    context
+        .media_sender
+        .send(MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: MediaNotificationContent::NewIncomingStream {
+                stream_name: "def".to_string(),
+            },
+            tags: Vec::new(),
+        })
+        .expect("Failed to send media");
+
+    let _ = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+
+    let definition = WorkflowDefinition {
+        name: "abc".to_string(),
+        routed_by_reactor: false,
+        steps: vec![WorkflowStepDefinition {
+            step_type: WorkflowStepType("output".to_string()),
+            parameters: HashMap::new(),
+        }],
+    };
+
+    context
+        .workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::UpdateDefinition {
+                new_definition: definition,
+            },
+        })
+        .expect("Failed to send update request");
+
+    // No disconnection should be raised while the removed step is still shutting down
+    test_utils::expect_mpsc_timeout(&mut context.media_receiver).await;
+
+    context
+        .input_status
+        .send(StepStatus::Shutdown)
+        .expect("Failed to set input state");
+
+    let media = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    assert_eq!(media.stream_id.0, "abc", "Unexpected stream id");
+    assert_eq!(
+        media.content, StreamDisconnected,
+        "Expected stream disconnected notification"
+    );
+}
+
+#[tokio::test]
+async fn state_contains_media_byte_counts_and_active_streams() {
+    let mut context = TestContext::new();
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let notifications = vec![
+        MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            is_sequence_header: false,
+            is_keyframe: true,
+            data: Bytes::from(vec![1, 2, 3]),
+            timestamp: VideoTimestamp::from_zero(),
+        },
+        MediaNotificationContent::Audio {
+            codec: AudioCodec::Aac,
+            is_sequence_header: false,
+            data: Bytes::from(vec![1, 2]),
+            timestamp: Duration::from_millis(0),
+        },
+    ];
+
+    for content in notifications {
+        context
+            .media_sender
+            .send(MediaNotification {
+                stream_id: StreamId("abc".to_string()),
+                content,
+                tags: Vec::new(),
+            })
+            .expect("Failed to send media");
+
+        let _ = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    }
+
+    let (sender, receiver) = channel();
+    context
+        .workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::GetState {
+                response_channel: sender,
+            },
+        })
+        .expect("Failed to send get state request");
+
+    let response = test_utils::expect_oneshot_response(receiver).await;
+    let workflow = response.expect("Expected workflow state returned");
+    assert_eq!(workflow.video_bytes, 3, "Unexpected video byte count");
+    assert_eq!(workflow.audio_bytes, 2, "Unexpected audio byte count");
+    assert_eq!(
+        workflow.active_stream_count, 1,
+        "Unexpected active stream count"
+    );
+}
+
+#[tokio::test]
+async fn state_lists_active_streams_until_they_disconnect() {
+    let mut context = TestContext::new();
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    context
+        .media_sender
+        .send(MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: MediaNotificationContent::NewIncomingStream {
+                stream_name: "def".to_string(),
+            },
+            tags: Vec::new(),
+        })
+        .expect("Failed to send media");
+
+    let _ = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+
+    let state = get_workflow_state(&context).await;
+    assert_eq!(
+        state.active_streams,
+        vec![WorkflowStreamState {
+            stream_id: StreamId("abc".to_string()),
+            stream_name: "def".to_string(),
+            originating_step_id: context.input_step_id,
+        }],
+        "Unexpected active streams"
+    );
+
+    context
+        .media_sender
+        .send(MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: StreamDisconnected,
+            tags: Vec::new(),
+        })
+        .expect("Failed to send media");
+
+    let _ = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+
+    let state = get_workflow_state(&context).await;
+    assert!(
+        state.active_streams.is_empty(),
+        "Expected no active streams, instead found {:?}",
+        state.active_streams
+    );
+}
+
+#[tokio::test]
+async fn workflow_in_error_state_if_step_generator_returns_error() {
+    let mut factory = WorkflowStepFactory::new();
+    factory
+        .register(
+            WorkflowStepType("failing".to_string()),
+            Box::new(TestFailingStepGenerator),
+        )
+        .expect("Failed to register failing step");
+
+    let definition = WorkflowDefinition {
+        name: "abc".to_string(),
+        routed_by_reactor: false,
+        steps: vec![WorkflowStepDefinition {
+            step_type: WorkflowStepType("failing".to_string()),
+            parameters: HashMap::new(),
+        }],
+    };
+
+    let step_id = definition.steps[0].get_id();
+    let workflow = start_workflow(definition, Arc::new(factory));
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let (sender, receiver) = channel();
+    workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::GetState {
+                response_channel: sender,
+            },
+        })
+        .expect("Failed to send get state request");
+
+    let response = test_utils::expect_oneshot_response(receiver).await;
+    assert!(response.is_some(), "Expected valid response");
+
+    match response.unwrap().status {
+        WorkflowStatus::Error {
+            message: _,
+            failed_step_id,
+        } => {
+            assert_eq!(failed_step_id, step_id, "Unexpected failed step id");
+        }
+
+        status => panic!("Unexpected workflow status: {:?}", status),
+    }
+}
+
+#[tokio::test]
+async fn media_not_passed_through_steps_when_workflow_in_error_state() {
+    let mut context = TestContext::new();
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    context
+        .output_status
+        .send(StepStatus::Error {
+            message: "hi".to_string(),
+        })
+        .expect("Failed to set output state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    context
+        .media_sender
+        .send(MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: MediaNotificationContent::NewIncomingStream {
+                stream_name: "def".to_string(),
+            },
+            tags: Vec::new(),
+        })
+        .expect("Failed to send media");
+
+    test_utils::expect_mpsc_timeout(&mut context.media_receiver).await;
+}
+
+#[tokio::test]
+async fn workflow_recovers_from_error_state_when_valid_definition_applied() {
+    let context = TestContext::new();
+    context
+        .output_status
+        .send(StepStatus::Error {
+            message: "hi".to_string(),
+        })
+        .expect("Failed to set output state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    // Newly created steps pick up the latest status, so make sure they come back up as active
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    let definition = WorkflowDefinition {
+        name: "abc".to_string(),
+        routed_by_reactor: false,
+        steps: vec![
+            WorkflowStepDefinition {
+                step_type: WorkflowStepType("input".to_string()),
+                parameters: HashMap::new(),
+            },
+            WorkflowStepDefinition {
+                step_type: WorkflowStepType("output".to_string()),
+                parameters: HashMap::new(),
+            },
+        ],
+    };
+
+    context
+        .workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::UpdateDefinition {
+                new_definition: definition,
+            },
+        })
+        .expect("Failed to send update request");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let (sender, receiver) = channel();
+    context
+        .workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::GetState {
+                response_channel: sender,
+            },
+        })
+        .expect("Failed to send get state request");
+
+    let response = test_utils::expect_oneshot_response(receiver).await;
+    let workflow = response.expect("Expected workflow state returned");
+    assert_eq!(
+        workflow.status,
+        WorkflowStatus::Running,
+        "Unexpected workflow status"
+    );
+    assert_eq!(workflow.active_steps.len(), 2, "Expected two active steps");
+    assert!(
+        workflow.pending_steps.is_empty(),
+        "Expected no pending steps"
+    );
+}
+
+fn definition_with_new_output_step() -> WorkflowDefinition {
+    let mut parameters = HashMap::new();
+    parameters.insert("a".to_string(), Some("b".to_string()));
+
+    WorkflowDefinition {
+        name: "abc".to_string(),
+        routed_by_reactor: false,
+        steps: vec![
+            WorkflowStepDefinition {
+                step_type: WorkflowStepType("input".to_string()),
+                parameters: HashMap::new(),
+            },
+            WorkflowStepDefinition {
+                step_type: WorkflowStepType("output".to_string()),
+                parameters,
+            },
+        ],
+    }
+}
+
+#[tokio::test]
+async fn update_and_wait_responds_once_all_new_steps_are_active() {
+    let context = TestContext::new();
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let (sender, mut receiver) = channel();
+    context
+        .workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::UpdateDefinitionAndWait {
+                new_definition: definition_with_new_output_step(),
+                timeout: Duration::from_secs(5),
+                response_channel: sender,
+            },
+        })
+        .expect("Failed to send update request");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(
+        receiver.try_recv().is_err(),
+        "Expected no response while the new step is pending"
+    );
+
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+
+    let response = test_utils::expect_oneshot_response(receiver).await;
+    assert_eq!(response, DefinitionUpdateResult::Applied, "Unexpected result");
+}
+
+#[tokio::test]
+async fn update_and_wait_responds_with_timeout_if_steps_never_activate() {
+    let context = TestContext::new();
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let (sender, receiver) = channel();
+    context
+        .workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::UpdateDefinitionAndWait {
+                new_definition: definition_with_new_output_step(),
+                timeout: Duration::from_millis(50),
+                response_channel: sender,
+            },
+        })
+        .expect("Failed to send update request");
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+
+    let response = test_utils::expect_oneshot_response(receiver).await;
+    assert_eq!(response, DefinitionUpdateResult::TimedOut, "Unexpected result");
+}
+
+#[tokio::test]
+async fn update_and_wait_responds_with_rejection_when_new_step_is_invalid() {
+    let context = TestContext::new();
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let definition = WorkflowDefinition {
+        name: "abc".to_string(),
+        routed_by_reactor: false,
+        steps: vec![WorkflowStepDefinition {
+            step_type: WorkflowStepType("output2".to_string()),
+            parameters: HashMap::new(),
+        }],
+    };
+
+    let step_id = definition.steps[0].get_id();
+    let (sender, receiver) = channel();
+    context
+        .workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::UpdateDefinitionAndWait {
+                new_definition: definition,
+                timeout: Duration::from_secs(5),
+                response_channel: sender,
+            },
+        })
+        .expect("Failed to send update request");
+
+    let response = test_utils::expect_oneshot_response(receiver).await;
+    match response {
+        DefinitionUpdateResult::Rejected { failed_step_id, .. } => {
+            assert_eq!(failed_step_id, step_id, "Unexpected failed step id");
+        }
+
+        response => panic!("Unexpected result: {:?}", response),
+    }
+}
+
+#[tokio::test]
+async fn update_and_wait_responds_with_failure_when_workflow_errors() {
+    let context = TestContext::new();
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let (sender, receiver) = channel();
+    context
+        .workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::UpdateDefinitionAndWait {
+                new_definition: definition_with_new_output_step(),
+                timeout: Duration::from_secs(5),
+                response_channel: sender,
+            },
+        })
+        .expect("Failed to send update request");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    context
+        .output_status
+        .send(StepStatus::Error {
+            message: "test".to_string(),
+        })
+        .expect("Failed to set output state");
+
+    let response = test_utils::expect_oneshot_response(receiver).await;
+    match response {
+        DefinitionUpdateResult::Failed { .. } => (),
+        response => panic!("Unexpected result: {:?}", response),
+    }
+}
+
+fn video_notification(data: u8, is_keyframe: bool, is_sequence_header: bool) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            is_sequence_header,
+            is_keyframe,
+            data: Bytes::from(vec![data]),
+            timestamp: VideoTimestamp::from_zero(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+async fn send_stream_with_gop(context: &mut TestContext) {
+    let notifications = vec![
+        MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: MediaNotificationContent::NewIncomingStream {
+                stream_name: "def".to_string(),
+            },
+            tags: Vec::new(),
+        },
+        video_notification(1, true, true),
+        video_notification(2, false, false),
+        video_notification(3, true, false),
+        video_notification(4, false, false),
+    ];
+
+    // The input step only sees the latest media sent, so wait for each to pass through
+    for notification in notifications {
+        context
+            .media_sender
+            .send(notification)
+            .expect("Failed to send media notification to step");
+
+        test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    }
+}
+
+async fn replace_output_step(context: &mut TestContext) {
+    context
+        .workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::UpdateDefinition {
+                new_definition: definition_with_new_output_step(),
+            },
+        })
+        .expect("Failed to send update request");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+}
+
+fn assert_video_data(media: &MediaNotification, expected_data: u8) {
+    match &media.content {
+        MediaNotificationContent::Video { data, .. } => {
+            assert_eq!(data, &vec![expected_data], "Unexpected video data");
+        }
+
+        content => panic!("Unexpected media notification: {:?}", content),
+    }
+}
+
+#[tokio::test]
+async fn new_step_receives_latest_gop_after_sequence_header_when_enabled() {
+    let mut context = TestContext::with_options(WorkflowRunnerOptions {
+        cache_latest_gop: true,
+        ..Default::default()
+    });
+
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    send_stream_with_gop(&mut context).await;
+    replace_output_step(&mut context).await;
+
+    let media = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    match media.content {
+        MediaNotificationContent::NewIncomingStream { .. } => (),
+        content => panic!("Unexpected media notification: {:?}", content),
+    }
+
+    let media = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    assert_video_data(&media, 1);
+
+    let media = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    assert_video_data(&media, 3);
+
+    let media = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    assert_video_data(&media, 4);
+
+    test_utils::expect_mpsc_timeout(&mut context.media_receiver).await;
+}
+
+#[tokio::test]
+async fn new_step_does_not_receive_latest_gop_when_not_enabled() {
+    let mut context = TestContext::new();
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    send_stream_with_gop(&mut context).await;
+    replace_output_step(&mut context).await;
+
+    let media = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    match media.content {
+        MediaNotificationContent::NewIncomingStream { .. } => (),
+        content => panic!("Unexpected media notification: {:?}", content),
+    }
+
+    let media = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    assert_video_data(&media, 1);
+
+    test_utils::expect_mpsc_timeout(&mut context.media_receiver).await;
+}
+
+async fn send_to_input_step(context: &mut TestContext, notifications: Vec<MediaNotification>) {
+    // The input step only sees the latest media sent, so wait for each to pass through
+    for notification in notifications {
+        context
+            .media_sender
+            .send(notification)
+            .expect("Failed to send media notification to step");
+
+        test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    }
+}
+
+/// Receives media until none arrives for a short period, returning a short description of each
+/// (e.g. `abc:new` or `abc:v1` for video with data of `1`)
+async fn receive_all_media(context: &mut TestContext) -> Vec<String> {
+    let mut received = Vec::new();
+    while let Ok(Some(media)) =
+        timeout(Duration::from_millis(10), context.media_receiver.recv()).await
+    {
+        let description = match &media.content {
+            MediaNotificationContent::NewIncomingStream { .. } => "new".to_string(),
+            MediaNotificationContent::Video { data, .. } => format!("v{}", data[0]),
+            content => panic!("Unexpected media notification: {:?}", content),
+        };
+
+        received.push(format!("{}:{}", media.stream_id.0, description));
+    }
+
+    received.sort();
+    received
+}
+
+fn new_stream_notification(stream_id: &str) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: stream_id.to_string(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn least_recently_updated_stream_evicted_when_step_cache_limit_exceeded() {
+    let mut context = TestContext::with_options(WorkflowRunnerOptions {
+        max_cached_bytes_per_step: Some(1),
+        ..Default::default()
+    });
+
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let mut def_sequence_header = video_notification(2, true, true);
+    def_sequence_header.stream_id = StreamId("def".to_string());
+
+    send_to_input_step(
+        &mut context,
+        vec![
+            new_stream_notification("abc"),
+            video_notification(1, true, true),
+            new_stream_notification("def"),
+            def_sequence_header,
+        ],
+    )
+    .await;
+
+    replace_output_step(&mut context).await;
+
+    let received = receive_all_media(&mut context).await;
+    assert_eq!(
+        received,
+        vec!["abc:new", "def:new", "def:v2"],
+        "Unexpected media received by new step"
+    );
+}
+
+#[tokio::test]
+async fn disconnected_stream_no_longer_counts_towards_cache_limit() {
+    let mut context = TestContext::with_options(WorkflowRunnerOptions {
+        max_cached_bytes_per_step: Some(1),
+        ..Default::default()
+    });
+
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let mut def_sequence_header = video_notification(2, true, true);
+    def_sequence_header.stream_id = StreamId("def".to_string());
+
+    send_to_input_step(
+        &mut context,
+        vec![
+            new_stream_notification("abc"),
+            video_notification(1, true, true),
+            MediaNotification {
+                stream_id: StreamId("abc".to_string()),
+                content: MediaNotificationContent::StreamDisconnected,
+                tags: Vec::new(),
+            },
+            new_stream_notification("def"),
+            def_sequence_header,
+        ],
+    )
+    .await;
+
+    replace_output_step(&mut context).await;
+
+    let received = receive_all_media(&mut context).await;
+    assert_eq!(
+        received,
+        vec!["def:new", "def:v2"],
+        "Unexpected media received by new step"
+    );
+}
+
+#[tokio::test]
+async fn gop_evicted_before_sequence_headers_when_workflow_cache_limit_exceeded() {
+    let mut context = TestContext::with_options(WorkflowRunnerOptions {
+        cache_latest_gop: true,
+        max_cached_bytes_per_workflow: Some(1),
+        ..Default::default()
+    });
+
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    send_stream_with_gop(&mut context).await;
+    replace_output_step(&mut context).await;
+
+    let received = receive_all_media(&mut context).await;
+    assert_eq!(
+        received,
+        vec!["abc:new", "abc:v1"],
+        "Unexpected media received by new step"
+    );
+}
+
+fn assert_discontinuity(media: &MediaNotification) {
+    assert_eq!(
+        media.stream_id,
+        StreamId("abc".to_string()),
+        "Unexpected stream id"
+    );
+
+    match &media.content {
+        MediaNotificationContent::Discontinuity => (),
+        content => panic!("Unexpected media notification: {:?}", content),
+    }
+}
+
+async fn send_and_receive(
+    context: &mut TestContext,
+    media: MediaNotification,
+) -> MediaNotification {
+    context
+        .media_sender
+        .send(media)
+        .expect("Failed to send media notification to step");
+
+    test_utils::expect_mpsc_response(&mut context.media_receiver).await
+}
+
+#[tokio::test]
+async fn discontinuity_raised_before_changed_sequence_header() {
+    let mut context = TestContext::new();
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    send_stream_with_gop(&mut context).await;
+
+    let media = send_and_receive(&mut context, video_notification(5, true, true)).await;
+    assert_discontinuity(&media);
+
+    let media = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    assert_video_data(&media, 5);
+
+    test_utils::expect_mpsc_timeout(&mut context.media_receiver).await;
+}
+
+#[tokio::test]
+async fn no_discontinuity_raised_for_repeated_sequence_header() {
+    let mut context = TestContext::new();
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    send_stream_with_gop(&mut context).await;
+
+    let media = send_and_receive(&mut context, video_notification(1, true, true)).await;
+    assert_video_data(&media, 1);
+
+    test_utils::expect_mpsc_timeout(&mut context.media_receiver).await;
+}
+
+#[tokio::test]
+async fn discontinuity_raised_when_step_inserted_before_existing_step() {
+    let mut context = TestContext::new();
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    send_stream_with_gop(&mut context).await;
+
+    let mut definition = definition_with_new_output_step();
+    definition.steps.push(WorkflowStepDefinition {
+        step_type: WorkflowStepType("output".to_string()),
+        parameters: HashMap::new(),
+    });
+
+    context
+        .workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::UpdateDefinition {
+                new_definition: definition,
+            },
+        })
+        .expect("Failed to send update request");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+
+    // The inserted output step is replayed the cached stream before the discontinuity
+    let media = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    match media.content {
+        MediaNotificationContent::NewIncomingStream { .. } => (),
+        content => panic!("Unexpected media notification: {:?}", content),
+    }
+
+    let media = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    assert_video_data(&media, 1);
+
+    let media = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    assert_discontinuity(&media);
+
+    test_utils::expect_mpsc_timeout(&mut context.media_receiver).await;
+}
+
+#[tokio::test]
+async fn state_contains_execution_timings_for_executed_steps() {
+    let mut context = TestContext::new();
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    send_stream_with_gop(&mut context).await;
+
+    let (sender, receiver) = channel();
+    context
+        .workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::GetState {
+                response_channel: sender,
+            },
+        })
+        .expect("Failed to send get state request");
+
+    let response = test_utils::expect_oneshot_response(receiver).await;
+    let workflow = response.expect("Expected workflow state returned");
+    for step in &workflow.active_steps {
+        let timing = step
+            .execution_timing
+            .as_ref()
+            .expect("Expected execution timing for active step");
+
+        assert!(
+            timing.average <= timing.max,
+            "Average execution time should not exceed the max"
+        );
+    }
+}
+
+#[test]
+fn execution_timings_only_summarize_most_recent_executions() {
+    let mut timings = StepExecutionTimings::default();
+    assert!(timings.summarize().is_none(), "Expected no timing summary");
+
+    timings.record(Duration::from_millis(500));
+    for _ in 0..100 {
+        timings.record(Duration::from_millis(2));
+    }
+
+    let summary = timings.summarize().expect("Expected timing summary");
+    assert_eq!(summary.max, Duration::from_millis(2), "Unexpected max");
+    assert_eq!(
+        summary.average,
+        Duration::from_millis(2),
+        "Unexpected average"
+    );
+}
+
+async fn expect_step_event(
+    receiver: &mut UnboundedReceiver<PublishEventRequest>,
+) -> WorkflowStepEvent {
+    match test_utils::expect_mpsc_response(receiver).await {
+        PublishEventRequest::WorkflowStepEvent(event) => event,
+        event => panic!("Unexpected event published: {:?}", event),
+    }
+}
+
+fn assert_step_event(
+    event: &WorkflowStepEvent,
+    step_id: u64,
+    step_type: &str,
+    transition: WorkflowStepTransition,
+) {
+    assert_eq!(&event.workflow_name, "abc", "Unexpected workflow name");
+    assert_eq!(event.step_id, step_id, "Unexpected step id");
+    assert_eq!(
+        event.step_type,
+        WorkflowStepType(step_type.to_string()),
+        "Unexpected step type"
+    );
+    assert_eq!(event.transition, transition, "Unexpected transition");
+}
+
+#[tokio::test]
+async fn step_events_published_when_steps_created() {
+    let (publisher, mut events) = unbounded_channel();
+    let context = TestContext::with_options(WorkflowRunnerOptions {
+        event_hub_publisher: Some(publisher),
+        ..Default::default()
+    });
+
+    let event = expect_step_event(&mut events).await;
+    assert_step_event(
+        &event,
+        context.input_step_id,
+        "input",
+        WorkflowStepTransition::StatusChanged(StepStatus::Created),
+    );
+
+    let event = expect_step_event(&mut events).await;
+    assert_step_event(
+        &event,
+        context.output_step_id,
+        "output",
+        WorkflowStepTransition::StatusChanged(StepStatus::Created),
+    );
+
+    test_utils::expect_mpsc_timeout(&mut events).await;
+}
+
+#[tokio::test]
+async fn step_event_published_when_step_status_changes() {
+    let (publisher, mut events) = unbounded_channel();
+    let context = TestContext::with_options(WorkflowRunnerOptions {
+        event_hub_publisher: Some(publisher),
+        ..Default::default()
+    });
+
+    expect_step_event(&mut events).await;
+    expect_step_event(&mut events).await;
+
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+
+    let event = expect_step_event(&mut events).await;
+    assert_step_event(
+        &event,
+        context.output_step_id,
+        "output",
+        WorkflowStepTransition::StatusChanged(StepStatus::Active),
+    );
+
+    test_utils::expect_mpsc_timeout(&mut events).await;
+}
+
+#[tokio::test]
+async fn step_removed_event_published_when_step_swapped_out() {
+    let (publisher, mut events) = unbounded_channel();
+    let mut context = TestContext::with_options(WorkflowRunnerOptions {
+        event_hub_publisher: Some(publisher),
+        ..Default::default()
+    });
+
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    replace_output_step(&mut context).await;
+
+    loop {
+        let event = expect_step_event(&mut events).await;
+        if event.step_id == context.output_step_id
+            && event.transition == WorkflowStepTransition::Removed
+        {
+            assert_step_event(
+                &event,
+                context.output_step_id,
+                "output",
+                WorkflowStepTransition::Removed,
+            );
+
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn step_removed_events_published_when_workflow_stopped() {
+    let (publisher, mut events) = unbounded_channel();
+    let context = TestContext::with_options(WorkflowRunnerOptions {
+        event_hub_publisher: Some(publisher),
+        ..Default::default()
+    });
+
+    expect_step_event(&mut events).await;
+    expect_step_event(&mut events).await;
+
+    context
+        .workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::StopWorkflow,
+        })
+        .expect("Failed to send stop request");
+
+    let mut removed_step_ids = Vec::new();
+    for _ in 0..2 {
+        let event = expect_step_event(&mut events).await;
+        assert_eq!(
+            event.transition,
+            WorkflowStepTransition::Removed,
+            "Unexpected transition"
+        );
+
+        removed_step_ids.push(event.step_id);
+    }
+
+    assert!(
+        removed_step_ids.contains(&context.input_step_id),
+        "Expected input step to be removed"
+    );
+    assert!(
+        removed_step_ids.contains(&context.output_step_id),
+        "Expected output step to be removed"
+    );
+}
+
+fn restart_policy(max_restarts: u32) -> StepRestartPolicy {
+    StepRestartPolicy {
+        max_restarts,
+        failure_window: Duration::from_secs(60),
+        initial_backoff: Duration::from_millis(20),
+    }
+}
+
+async fn get_workflow_state(context: &TestContext) -> WorkflowState {
+    get_state(&context.workflow).await
+}
+
+#[tokio::test]
+async fn errored_step_restarted_when_restart_policy_given() {
+    let mut context = TestContext::with_options(WorkflowRunnerOptions {
+        step_restart_policy: Some(restart_policy(3)),
+        ..Default::default()
+    });
+
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let stream = MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        tags: Vec::new(),
+    };
+
+    send_and_receive(&mut context, stream.clone()).await;
+
+    context
+        .output_status
+        .send(StepStatus::Error {
+            message: "hi".to_string(),
+        })
+        .expect("Failed to set output state");
+
+    tokio::time::sleep(Duration::from_millis(5)).await;
+
+    let workflow = get_workflow_state(&context).await;
+    assert_eq!(
+        workflow.status,
+        WorkflowStatus::Running,
+        "Unexpected workflow status"
+    );
+
+    let output_step = workflow
+        .active_steps
+        .iter()
+        .find(|step| step.step_id == context.output_step_id)
+        .expect("Expected output step to still be active");
+
+    assert_eq!(
+        output_step.status,
+        StepStatus::Reconnecting { attempt: 1 },
+        "Unexpected output step status"
+    );
+
+    // Newly created steps pick up the latest status
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+
+    // The recreated step should be caught up on the existing stream
+    let media = test_utils::expect_mpsc_response(&mut context.media_receiver).await;
+    assert_eq!(media, stream, "Unexpected media notification");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let workflow = get_workflow_state(&context).await;
+    assert_eq!(
+        workflow.status,
+        WorkflowStatus::Running,
+        "Unexpected workflow status"
+    );
+
+    for step in &workflow.active_steps {
+        assert_eq!(step.status, StepStatus::Active, "Unexpected step status");
+    }
+}
+
+#[tokio::test]
+async fn futures_from_errored_step_instance_not_passed_to_restarted_instance() {
+    let mut context = TestContext::with_options(WorkflowRunnerOptions {
+        step_restart_policy: Some(restart_policy(3)),
+        ..Default::default()
+    });
+
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let stream = MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        tags: Vec::new(),
+    };
+
+    send_and_receive(&mut context, stream).await;
+
+    // The errored input step instance still has its media future outstanding
+    context
+        .input_status
+        .send(StepStatus::Error {
+            message: "hi".to_string(),
+        })
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    while context.media_receiver.try_recv().is_ok() {}
+
+    let media = MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Metadata {
+            data: HashMap::new(),
+        },
+        tags: Vec::new(),
+    };
+
+    let received = send_and_receive(&mut context, media.clone()).await;
+    assert_eq!(received, media, "Unexpected media notification");
+
+    // The old instance's media future resolving must not cause the media to be raised again
+    test_utils::expect_mpsc_timeout(&mut context.media_receiver).await;
+}
+
+#[tokio::test]
+async fn workflow_in_error_state_once_step_exceeds_max_restarts() {
+    let context = TestContext::with_options(WorkflowRunnerOptions {
+        step_restart_policy: Some(restart_policy(2)),
+        ..Default::default()
+    });
+
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    // Recreated steps pick up the latest status, so the step keeps failing
+    context
+        .output_status
+        .send(StepStatus::Error {
+            message: "hi".to_string(),
+        })
+        .expect("Failed to set output state");
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let workflow = get_workflow_state(&context).await;
+    match workflow.status {
+        WorkflowStatus::Error { failed_step_id, .. } => {
+            assert_eq!(
+                failed_step_id, context.output_step_id,
+                "Unexpected failed step id"
+            );
+        }
+
+        status => panic!("Unexpected workflow status: {:?}", status),
+    }
+}
+
+async fn get_state(workflow: &UnboundedSender<WorkflowRequest>) -> WorkflowState {
+    let (sender, receiver) = channel();
+    workflow
+        .send(WorkflowRequest {
+            request_id: "".to_string(),
+            operation: WorkflowRequestOperation::GetState {
+                response_channel: sender,
+            },
+        })
+        .expect("Failed to send get state request");
+
+    let response = test_utils::expect_oneshot_response(receiver).await;
+    response.expect("Expected workflow state returned")
+}
+
+#[tokio::test]
+async fn step_configuration_error_not_retried_with_restart_policy() {
+    let mut factory = WorkflowStepFactory::new();
+    factory
+        .register(
+            WorkflowStepType("failing".to_string()),
+            Box::new(TestFailingStepGenerator),
+        )
+        .expect("Failed to register failing step");
+
+    let definition = WorkflowDefinition {
+        name: "abc".to_string(),
+        routed_by_reactor: false,
+        steps: vec![WorkflowStepDefinition {
+            step_type: WorkflowStepType("failing".to_string()),
+            parameters: HashMap::new(),
+        }],
+    };
+
+    let step_id = definition.steps[0].get_id();
+    let workflow = start_workflow_with_options(
+        definition,
+        Arc::new(factory),
+        WorkflowRunnerOptions {
+            step_restart_policy: Some(restart_policy(3)),
+            ..Default::default()
+        },
+    );
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let state = get_state(&workflow).await;
+    match state.status {
+        WorkflowStatus::Error { failed_step_id, .. } => {
+            assert_eq!(failed_step_id, step_id, "Unexpected failed step id");
+        }
+
+        status => panic!("Unexpected workflow status: {:?}", status),
+    }
+}
+
+/// Collects everything logged while it's the default subscriber
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl CapturedLogs {
+    fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).to_string()
+    }
+}
+
+#[tokio::test]
+async fn unsupported_codec_warning_logged_once_per_stream() {
+    let logs = CapturedLogs::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let mut context = TestContext::with_output_supported_codecs(SupportedCodecs {
+        video: Some(&[VideoCodec::H264]),
+        audio: None,
+    });
+
+    context
+        .output_status
+        .send(StepStatus::Active)
+        .expect("Failed to set output state");
+    context
+        .input_status
+        .send(StepStatus::Active)
+        .expect("Failed to set input state");
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let mut unsupported_video = video_notification(1, true, false);
+    if let MediaNotificationContent::Video { codec, .. } = &mut unsupported_video.content {
+        *codec = VideoCodec::Unknown;
+    }
+
+    send_to_input_step(
+        &mut context,
+        vec![
+            new_stream_notification("abc"),
+            video_notification(1, true, false),
+            unsupported_video.clone(),
+            unsupported_video,
+        ],
+    )
+    .await;
+
+    let warnings = logs.contents().matches("which it does not support").count();
+    assert_eq!(
+        warnings, 1,
+        "Unexpected number of unsupported codec warnings"
+    );
+}
+
+#[test]
+fn slow_execution_warnings_are_rate_limited() {
+    let mut timings = StepExecutionTimings::default();
+    let start = Instant::now();
+
+    assert_eq!(
+        timings.record_slow_execution(start),
+        Some(1),
+        "Expected the first slow execution to be reported"
+    );
+
+    for _ in 0..5 {
+        assert_eq!(
+            timings.record_slow_execution(start + Duration::from_secs(1)),
+            None,
+            "Expected no warning within the warning interval"
+        );
+    }
+
+    assert_eq!(
+        timings.record_slow_execution(start + SLOW_STEP_WARNING_INTERVAL),
+        Some(6),
+        "Expected the next warning to include the unreported slow executions"
+    );
+}
@@ -0,0 +1,66 @@
+//! The audio only step strips video from all media streams that pass through it.  Audio, metadata,
+//! and stream lifecycle notifications are passed to subsequent steps untouched, while all video
+//! notifications (including video sequence headers) are dropped.
+
+#[cfg(test)]
+mod tests;
+
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationResult, StepInputs, StepOutputs, StepStatus, WorkflowStep,
+};
+use crate::workflows::MediaNotificationContent;
+
+/// Generates a new audio only step
+pub struct AudioOnlyStepGenerator {}
+
+struct AudioOnlyStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+}
+
+impl AudioOnlyStepGenerator {
+    pub fn new() -> Self {
+        AudioOnlyStepGenerator {}
+    }
+}
+
+impl StepGenerator for AudioOnlyStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let step = AudioOnlyStep {
+            definition,
+            status: StepStatus::Active,
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+}
+
+impl WorkflowStep for AudioOnlyStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for media in inputs.media.drain(..) {
+            match &media.content {
+                MediaNotificationContent::Video { .. } => (),
+                MediaNotificationContent::Audio { .. }
+                | MediaNotificationContent::Metadata { .. }
+                | MediaNotificationContent::NewIncomingStream { .. }
+                | MediaNotificationContent::StreamDisconnected
+                | MediaNotificationContent::Discontinuity
+                | MediaNotificationContent::Cue { .. } => outputs.media.push(media),
+            }
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+    }
+}
@@ -0,0 +1,197 @@
+use super::*;
+use crate::codecs::{AudioCodec, VideoCodec};
+use crate::workflows::steps::test_utils::{
+    audio_content, create_definition, disconnected_content, media, new_stream_content,
+    video_content,
+};
+use crate::workflows::steps::StepTestContext;
+use crate::workflows::MediaNotification;
+use crate::{StreamId, VideoTimestamp};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+
+fn create_context() -> StepTestContext {
+    let generator = AudioOnlyStepGenerator::new();
+    let definition = create_definition("audio_only", &[]);
+
+    StepTestContext::new(Box::new(generator), definition).unwrap()
+}
+
+#[test]
+fn step_is_active_immediately() {
+    let context = create_context();
+
+    assert_eq!(
+        context.step.get_status(),
+        &StepStatus::Active,
+        "Unexpected step status"
+    );
+}
+
+#[test]
+fn new_stream_notification_passed_as_output() {
+    let mut context = create_context();
+    context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        tags: Vec::new(),
+    });
+
+    assert_eq!(
+        context.media_outputs.len(),
+        1,
+        "Unexpected number of outputs"
+    );
+    match &context.media_outputs[0].content {
+        MediaNotificationContent::NewIncomingStream { stream_name } => {
+            assert_eq!(stream_name, "def", "Unexpected stream name");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[test]
+fn stream_disconnected_notification_passed_as_output() {
+    let mut context = create_context();
+    context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
+    });
+
+    assert_eq!(
+        context.media_outputs.len(),
+        1,
+        "Unexpected number of outputs"
+    );
+    match &context.media_outputs[0].content {
+        MediaNotificationContent::StreamDisconnected => (),
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[test]
+fn metadata_notification_passed_as_output() {
+    let mut context = create_context();
+    let mut metadata = HashMap::new();
+    metadata.insert("width".to_string(), "1920".to_string());
+
+    context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Metadata { data: metadata },
+        tags: Vec::new(),
+    });
+
+    assert_eq!(
+        context.media_outputs.len(),
+        1,
+        "Unexpected number of outputs"
+    );
+    match &context.media_outputs[0].content {
+        MediaNotificationContent::Metadata { data } => {
+            assert_eq!(
+                data.get("width"),
+                Some(&"1920".to_string()),
+                "Unexpected width"
+            );
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[test]
+fn audio_sequence_header_passed_as_output() {
+    let mut context = create_context();
+    context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Audio {
+            data: Bytes::from(vec![1, 2, 3]),
+            codec: AudioCodec::Aac,
+            timestamp: Duration::from_millis(5),
+            is_sequence_header: true,
+        },
+        tags: Vec::new(),
+    });
+
+    assert_eq!(
+        context.media_outputs.len(),
+        1,
+        "Unexpected number of outputs"
+    );
+    match &context.media_outputs[0].content {
+        MediaNotificationContent::Audio {
+            data,
+            is_sequence_header,
+            ..
+        } => {
+            assert_eq!(data, &vec![1, 2, 3], "Unexpected bytes");
+            assert!(is_sequence_header, "Expected is_sequence_header to be true");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[test]
+fn video_notifications_are_dropped() {
+    let mut context = create_context();
+    context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Video {
+            data: Bytes::from(vec![1, 2, 3]),
+            codec: VideoCodec::H264,
+            timestamp: VideoTimestamp::from_durations(
+                Duration::from_millis(5),
+                Duration::from_millis(15),
+            ),
+            is_keyframe: true,
+            is_sequence_header: true,
+        },
+        tags: Vec::new(),
+    });
+
+    assert!(
+        context.media_outputs.is_empty(),
+        "Expected no media outputs"
+    );
+}
+
+#[test]
+fn discontinuity_notification_passed_as_output() {
+    let mut context = create_context();
+    context.assert_media_passed_through(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Discontinuity,
+        tags: Vec::new(),
+    });
+}
+
+#[test]
+fn only_video_removed_from_stream() {
+    let mut context = create_context();
+    context.execute_inputs(vec![
+        media("abc", new_stream_content("def")).into(),
+        media("abc", video_content(true, true, Duration::from_millis(0))).into(),
+        media("abc", audio_content(true, Duration::from_millis(0))).into(),
+        media("abc", video_content(true, false, Duration::from_millis(5))).into(),
+        media("abc", audio_content(false, Duration::from_millis(6))).into(),
+        media("abc", disconnected_content()).into(),
+    ]);
+
+    let expected_outputs = vec![
+        media("abc", new_stream_content("def")),
+        media("abc", audio_content(true, Duration::from_millis(0))),
+        media("abc", audio_content(false, Duration::from_millis(6))),
+        media("abc", disconnected_content()),
+    ];
+
+    assert_eq!(
+        context.media_outputs, expected_outputs,
+        "Unexpected media outputs"
+    );
+}
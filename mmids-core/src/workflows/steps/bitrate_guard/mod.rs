@@ -0,0 +1,390 @@
+//! The bitrate guard step measures the rolling bitrate of each stream that passes through it, and
+//! acts on streams that stay above a configured maximum for longer than a grace period.  In
+//! reject mode the stream is disconnected from subsequent steps, while in warn mode a warning is
+//! logged and the stream is left alone.
+//!
+//! The bitrate is measured from the size of video and audio payloads over a short rolling window
+//! of media timestamps, so single large packets (such as keyframes) don't trip the guard on their
+//! own.  Once a stream goes over the limit it's only considered back under the limit after its
+//! bitrate drops below a percentage of the maximum, so streams hovering around the limit don't
+//! keep restarting their grace period.
+//!
+//! Media is passed to subsequent steps untouched while a stream is under the limit.  After a
+//! stream is rejected, any media received for it is dropped until the stream is announced again.
+
+#[cfg(test)]
+mod tests;
+
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{info, warn};
+
+pub const MAX_KBPS: &str = "max_kbps";
+pub const GRACE_SECONDS: &str = "grace_seconds";
+pub const MODE: &str = "mode";
+pub const REJECT_MODE: &str = "reject";
+pub const WARN_MODE: &str = "warn";
+
+/// The name each stream's current bitrate is reported under in the workflow's state
+pub const BITRATE_KBPS_DETAIL: &str = "bitrate_kbps";
+
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// How far back media is considered when calculating a stream's bitrate
+const ROLLING_WINDOW: Duration = Duration::from_secs(2);
+
+/// The percentage of the maximum bitrate a stream that went over the limit has to drop below
+/// before it's considered back under the limit
+const RECOVERY_PERCENT: u64 = 90;
+
+/// Generates new instances of the bitrate guard workflow step based on specified step definitions.
+pub struct BitrateGuardStepGenerator {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Reject,
+    Warn,
+}
+
+struct BitrateGuardStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    max_kbps: u64,
+    grace_period: Duration,
+    mode: Mode,
+    streams: HashMap<StreamId, StreamTracker>,
+}
+
+#[derive(Default)]
+struct StreamTracker {
+    stream_name: Option<String>,
+    video_packets: VecDeque<(Duration, usize)>,
+    audio_packets: VecDeque<(Duration, usize)>,
+
+    /// The media timestamp the stream most recently went over the limit at.  `None` while the
+    /// stream is under the limit.
+    over_limit_since: Option<Duration>,
+
+    /// Set once the stream has been over the limit for the whole grace period, until it drops
+    /// back under the limit.
+    limit_enforced: bool,
+}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error(
+        "No maximum bitrate specified.  A '{}' parameter is required",
+        MAX_KBPS
+    )]
+    NoMaxKbpsProvided,
+
+    #[error(
+        "Invalid maximum bitrate of '{0}'.  {} should be a positive number of kilobits per second",
+        MAX_KBPS
+    )]
+    InvalidMaxKbps(String),
+
+    #[error(
+        "Invalid grace period of '{0}'.  {} should be a number of seconds",
+        GRACE_SECONDS
+    )]
+    InvalidGracePeriod(String),
+
+    #[error(
+        "Invalid mode of '{0}'.  {} should be either '{}' or '{}'",
+        MODE,
+        REJECT_MODE,
+        WARN_MODE
+    )]
+    InvalidMode(String),
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl BitrateGuardStepGenerator {
+    pub fn new() -> Self {
+        BitrateGuardStepGenerator {}
+    }
+}
+
+impl StepGenerator for BitrateGuardStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let max_kbps = parse_max_kbps(&definition)?;
+        let grace_period = parse_grace_period(&definition)?;
+        let mode = parse_mode(&definition)?;
+        let step = BitrateGuardStep {
+            definition,
+            status: StepStatus::Active,
+            max_kbps,
+            grace_period,
+            mode,
+            streams: HashMap::new(),
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_max_kbps(definition)?;
+        parse_grace_period(definition)?;
+        parse_mode(definition)?;
+        Ok(())
+    }
+}
+
+fn parse_max_kbps(definition: &WorkflowStepDefinition) -> Result<u64, StepStartupError> {
+    match definition.parameters.get(MAX_KBPS) {
+        Some(Some(value)) => match value.parse::<u64>() {
+            Ok(num) if num > 0 => Ok(num),
+            _ => Err(StepStartupError::InvalidMaxKbps(value.clone())),
+        },
+
+        _ => Err(StepStartupError::NoMaxKbpsProvided),
+    }
+}
+
+fn parse_grace_period(definition: &WorkflowStepDefinition) -> Result<Duration, StepStartupError> {
+    match definition.parameters.get(GRACE_SECONDS) {
+        Some(Some(value)) => match value.parse::<u64>() {
+            Ok(num) => Ok(Duration::from_secs(num)),
+            Err(_) => Err(StepStartupError::InvalidGracePeriod(value.clone())),
+        },
+
+        _ => Ok(DEFAULT_GRACE_PERIOD),
+    }
+}
+
+fn parse_mode(definition: &WorkflowStepDefinition) -> Result<Mode, StepStartupError> {
+    match definition.parameters.get(MODE) {
+        Some(Some(value)) => match value.to_lowercase().as_str() {
+            REJECT_MODE => Ok(Mode::Reject),
+            WARN_MODE => Ok(Mode::Warn),
+            _ => Err(StepStartupError::InvalidMode(value.clone())),
+        },
+
+        _ => Ok(Mode::Reject),
+    }
+}
+
+impl StreamTracker {
+    fn get_bitrate_kbps(&self) -> u64 {
+        get_bitrate_kbps(&self.video_packets) + get_bitrate_kbps(&self.audio_packets)
+    }
+
+    /// Updates whether the stream is over the limit as of the specified media timestamp, and
+    /// returns true if the stream has just been over the limit for the full grace period.
+    fn check_limit(&mut self, timestamp: Duration, max_kbps: u64, grace_period: Duration) -> bool {
+        let bitrate = self.get_bitrate_kbps();
+        match self.over_limit_since {
+            None if bitrate > max_kbps => {
+                self.over_limit_since = Some(timestamp);
+            }
+
+            Some(_) if bitrate * 100 < max_kbps * RECOVERY_PERCENT => {
+                self.over_limit_since = None;
+                self.limit_enforced = false;
+
+                return false;
+            }
+
+            _ => (),
+        }
+
+        match self.over_limit_since {
+            Some(since) if !self.limit_enforced => {
+                if timestamp.saturating_sub(since) >= grace_period {
+                    self.limit_enforced = true;
+                    return true;
+                }
+
+                false
+            }
+
+            _ => false,
+        }
+    }
+}
+
+impl BitrateGuardStep {
+    fn handle_media(&mut self, media: MediaNotification, outputs: &mut StepOutputs) {
+        let (timestamp, size, is_video) = match &media.content {
+            MediaNotificationContent::NewIncomingStream { stream_name } => {
+                self.streams.insert(
+                    media.stream_id.clone(),
+                    StreamTracker {
+                        stream_name: Some(stream_name.clone()),
+                        ..Default::default()
+                    },
+                );
+
+                outputs.media.push(media);
+                return;
+            }
+
+            MediaNotificationContent::StreamDisconnected => {
+                if let Some(stream) = self.streams.remove(&media.stream_id) {
+                    if stream.limit_enforced && self.mode == Mode::Reject {
+                        return; // Subsequent steps were already told the stream disconnected
+                    }
+                }
+
+                outputs.media.push(media);
+                return;
+            }
+
+            MediaNotificationContent::Video {
+                is_sequence_header: false,
+                data,
+                timestamp,
+                ..
+            } => (timestamp.dts(), data.len(), true),
+
+            MediaNotificationContent::Audio {
+                is_sequence_header: false,
+                data,
+                timestamp,
+                ..
+            } => (*timestamp, data.len(), false),
+
+            _ => {
+                if let Some(stream) = self.streams.get(&media.stream_id) {
+                    if stream.limit_enforced && self.mode == Mode::Reject {
+                        return;
+                    }
+                }
+
+                outputs.media.push(media);
+                return;
+            }
+        };
+
+        let stream = self.streams.entry(media.stream_id.clone()).or_default();
+        if stream.limit_enforced && self.mode == Mode::Reject {
+            return;
+        }
+
+        if is_video {
+            add_packet(&mut stream.video_packets, timestamp, size);
+        } else {
+            add_packet(&mut stream.audio_packets, timestamp, size);
+        }
+
+        if !stream.check_limit(timestamp, self.max_kbps, self.grace_period) {
+            outputs.media.push(media);
+            return;
+        }
+
+        let stream_name = stream.stream_name.as_deref().unwrap_or("");
+        match self.mode {
+            Mode::Reject => {
+                info!(
+                    stream_id = ?media.stream_id,
+                    stream_name = %stream_name,
+                    "Stream {:?} has been over the maximum bitrate of {} kbps for {:?} ({} kbps), disconnecting it",
+                    media.stream_id, self.max_kbps, self.grace_period, stream.get_bitrate_kbps()
+                );
+
+                outputs.media.push(MediaNotification {
+                    stream_id: media.stream_id,
+                    content: MediaNotificationContent::StreamDisconnected,
+                    tags: Vec::new(),
+                });
+            }
+
+            Mode::Warn => {
+                warn!(
+                    stream_id = ?media.stream_id,
+                    stream_name = %stream_name,
+                    "Stream {:?} has been over the maximum bitrate of {} kbps for {:?} ({} kbps)",
+                    media.stream_id, self.max_kbps, self.grace_period, stream.get_bitrate_kbps()
+                );
+
+                outputs.media.push(media);
+            }
+        }
+    }
+}
+
+impl WorkflowStep for BitrateGuardStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for media in inputs.media.drain(..) {
+            self.handle_media(media, outputs);
+        }
+    }
+
+    fn get_stream_details(&self) -> HashMap<StreamId, HashMap<String, String>> {
+        self.streams
+            .iter()
+            .map(|(stream_id, stream)| {
+                let mut details = HashMap::new();
+                details.insert(
+                    BITRATE_KBPS_DETAIL.to_string(),
+                    stream.get_bitrate_kbps().to_string(),
+                );
+
+                (stream_id.clone(), details)
+            })
+            .collect()
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+        self.streams.clear();
+    }
+}
+
+fn add_packet(packets: &mut VecDeque<(Duration, usize)>, timestamp: Duration, size: usize) {
+    // If timestamps went backwards (e.g. the source reset its clock) the packets in the window
+    // can no longer be compared against new ones, so the window starts over
+    if let Some((newest, _)) = packets.back() {
+        if timestamp < *newest {
+            packets.clear();
+        }
+    }
+
+    packets.push_back((timestamp, size));
+    while let Some((oldest, _)) = packets.front() {
+        if timestamp.saturating_sub(*oldest) > ROLLING_WINDOW {
+            packets.pop_front();
+        } else {
+            break;
+        }
+    }
+}
+
+fn get_bitrate_kbps(packets: &VecDeque<(Duration, usize)>) -> u64 {
+    let length = match (packets.front(), packets.back()) {
+        (Some((first, _)), Some((last, _))) if last > first => *last - *first,
+        _ => return 0,
+    };
+
+    // The last packet marks the end of the window, so it's not counted as part of it
+    let bits = packets
+        .iter()
+        .take(packets.len() - 1)
+        .map(|(_, size)| *size as u64 * 8)
+        .sum::<u64>();
+
+    (bits as f64 / length.as_secs_f64() / 1000.0) as u64
+}
@@ -0,0 +1,215 @@
+use super::*;
+use crate::codecs::VideoCodec;
+use crate::workflows::steps::test_utils::{
+    create_definition, disconnected_content, media, new_stream_content,
+};
+use crate::workflows::steps::StepTestContext;
+use crate::VideoTimestamp;
+use bytes::Bytes;
+
+/// Size of video packets that, when sent every 10 milliseconds, is 1000 kbps
+const LARGE_PACKET: usize = 1250;
+
+/// Size of video packets that, when sent every 10 milliseconds, is 95.2 kbps
+const SLIGHTLY_UNDER_LIMIT_PACKET: usize = 119;
+
+/// Size of video packets that, when sent every 10 milliseconds, is 80 kbps
+const SMALL_PACKET: usize = 100;
+
+fn create_context(mode: &str) -> StepTestContext {
+    let generator = BitrateGuardStepGenerator::new();
+    let definition = create_definition(
+        "bitrate_guard",
+        &[(MAX_KBPS, "100"), (GRACE_SECONDS, "3"), (MODE, mode)],
+    );
+    StepTestContext::new(Box::new(generator), definition).unwrap()
+}
+
+fn video(millis: u64, size: usize) -> MediaNotification {
+    media(
+        "abc",
+        MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            is_keyframe: false,
+            is_sequence_header: false,
+            data: Bytes::from(vec![0; size]),
+            timestamp: VideoTimestamp::from_durations(
+                Duration::from_millis(millis),
+                Duration::from_millis(millis),
+            ),
+        },
+    )
+}
+
+/// Sends a video packet every 10 milliseconds from `start` up to (but not including) `end`,
+/// returning the timestamp of the first packet that did not get passed through unchanged.
+fn send_video(context: &mut StepTestContext, start: u64, end: u64, size: usize) -> Option<u64> {
+    for millis in (start..end).step_by(10) {
+        let media = video(millis, size);
+        context.execute_with_media(media.clone());
+        if context.media_outputs != vec![media] {
+            return Some(millis);
+        }
+    }
+
+    None
+}
+
+#[test]
+fn step_fails_to_generate_without_max_kbps() {
+    let generator = BitrateGuardStepGenerator::new();
+    assert!(generator
+        .generate(create_definition("bitrate_guard", &[]))
+        .is_err());
+}
+
+#[test]
+fn step_fails_to_generate_with_zero_max_kbps() {
+    let generator = BitrateGuardStepGenerator::new();
+    assert!(generator
+        .generate(create_definition("bitrate_guard", &[(MAX_KBPS, "0")]))
+        .is_err());
+}
+
+#[test]
+fn step_fails_to_generate_with_non_numeric_grace_seconds() {
+    let generator = BitrateGuardStepGenerator::new();
+    assert!(generator
+        .generate(create_definition(
+            "bitrate_guard",
+            &[(MAX_KBPS, "100"), (GRACE_SECONDS, "abc")]
+        ))
+        .is_err());
+}
+
+#[test]
+fn step_fails_to_generate_with_unknown_mode() {
+    let generator = BitrateGuardStepGenerator::new();
+    assert!(generator
+        .generate(create_definition(
+            "bitrate_guard",
+            &[(MAX_KBPS, "100"), (MODE, "abc")]
+        ))
+        .is_err());
+}
+
+#[test]
+fn validation_passes_with_only_max_kbps() {
+    let generator = BitrateGuardStepGenerator::new();
+    assert!(generator
+        .validate(&create_definition("bitrate_guard", &[(MAX_KBPS, "100")]))
+        .is_ok());
+}
+
+#[test]
+fn stream_under_limit_passed_through() {
+    let mut context = create_context(REJECT_MODE);
+    context.assert_media_passed_through(media("abc", new_stream_content("def")));
+
+    assert_eq!(send_video(&mut context, 0, 5000, SMALL_PACKET), None);
+}
+
+#[test]
+fn stream_over_limit_for_grace_period_disconnected_in_reject_mode() {
+    let mut context = create_context(REJECT_MODE);
+    context.execute_with_media(media("abc", new_stream_content("def")));
+
+    // Goes over the limit at 10ms, once there's enough packets to measure a bitrate
+    assert_eq!(send_video(&mut context, 0, 5000, LARGE_PACKET), Some(3010));
+    assert_eq!(
+        context.media_outputs,
+        vec![media("abc", disconnected_content())],
+        "Unexpected media outputs"
+    );
+}
+
+#[test]
+fn media_dropped_after_stream_rejected() {
+    let mut context = create_context(REJECT_MODE);
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    send_video(&mut context, 0, 3020, LARGE_PACKET);
+
+    context.assert_media_not_passed_through(video(3020, SMALL_PACKET));
+    context.assert_media_not_passed_through(media("abc", disconnected_content()));
+}
+
+#[test]
+fn reconnect_after_rejection_passes_media_through() {
+    let mut context = create_context(REJECT_MODE);
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    send_video(&mut context, 0, 3020, LARGE_PACKET);
+
+    context.assert_media_passed_through(media("abc", new_stream_content("def")));
+    context.assert_media_passed_through(video(0, SMALL_PACKET));
+}
+
+#[test]
+fn stream_over_limit_passed_through_in_warn_mode() {
+    let mut context = create_context(WARN_MODE);
+    context.execute_with_media(media("abc", new_stream_content("def")));
+
+    assert_eq!(send_video(&mut context, 0, 5000, LARGE_PACKET), None);
+}
+
+#[test]
+fn burst_shorter_than_grace_period_does_not_disconnect() {
+    let mut context = create_context(REJECT_MODE);
+    context.execute_with_media(media("abc", new_stream_content("def")));
+
+    assert_eq!(send_video(&mut context, 0, 200, LARGE_PACKET), None);
+    assert_eq!(send_video(&mut context, 200, 8000, SMALL_PACKET), None);
+}
+
+#[test]
+fn stream_slightly_under_limit_does_not_restart_grace_period() {
+    let mut context = create_context(REJECT_MODE);
+    context.execute_with_media(media("abc", new_stream_content("def")));
+
+    assert_eq!(send_video(&mut context, 0, 500, LARGE_PACKET), None);
+    assert_eq!(
+        send_video(&mut context, 500, 5000, SLIGHTLY_UNDER_LIMIT_PACKET),
+        Some(3010)
+    );
+}
+
+#[test]
+fn stream_well_under_limit_restarts_grace_period() {
+    let mut context = create_context(REJECT_MODE);
+    context.execute_with_media(media("abc", new_stream_content("def")));
+
+    assert_eq!(send_video(&mut context, 0, 500, LARGE_PACKET), None);
+    assert_eq!(send_video(&mut context, 500, 5000, SMALL_PACKET), None);
+}
+
+#[test]
+fn stream_bitrate_included_in_stream_details() {
+    let mut context = create_context(REJECT_MODE);
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    send_video(&mut context, 0, 1000, SMALL_PACKET);
+
+    let details = context.step.get_stream_details();
+    let bitrate = details
+        .get(&StreamId("abc".to_string()))
+        .and_then(|details| details.get(BITRATE_KBPS_DETAIL));
+
+    assert_eq!(bitrate, Some(&"80".to_string()), "Unexpected bitrate");
+}
+
+#[test]
+fn window_cleared_when_timestamps_go_backwards() {
+    let mut packets = VecDeque::new();
+    add_packet(&mut packets, Duration::from_millis(5000), LARGE_PACKET);
+    add_packet(&mut packets, Duration::from_millis(5010), LARGE_PACKET);
+    add_packet(&mut packets, Duration::from_millis(10), SMALL_PACKET);
+    add_packet(&mut packets, Duration::from_millis(20), SMALL_PACKET);
+
+    assert_eq!(
+        packets,
+        VecDeque::from(vec![
+            (Duration::from_millis(10), SMALL_PACKET),
+            (Duration::from_millis(20), SMALL_PACKET),
+        ]),
+        "Unexpected packets in window"
+    );
+    assert_eq!(get_bitrate_kbps(&packets), 80, "Unexpected bitrate");
+}
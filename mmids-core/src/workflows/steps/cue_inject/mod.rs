@@ -0,0 +1,366 @@
+//! The cue inject step inserts cue markers (such as for SCTE-35 style ad insertion signaling) into
+//! streams passing through it at operator commanded times.  Cues are requested through the
+//! workflow manager with an `InjectCue` request, which names the step's cue channel and the
+//! stream the cue is for.  The cue is output inline with that stream's media at the point the
+//! request was received, so later steps (e.g. HLS or DASH packagers) can translate it into their
+//! own markers.  Steps that don't understand cues pass them through unchanged.
+//!
+//! The step registers its cue channel with the workflow manager, which is found via the event
+//! hub, and unregisters it when the step is shut down.  The step errors if another step has
+//! already registered the same cue channel.  All media is passed through unchanged.
+
+#[cfg(test)]
+mod tests;
+
+use crate::event_hub::{SubscriptionRequest, WorkflowManagerEvent};
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::manager::{WorkflowManagerRequest, WorkflowManagerRequestOperation};
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot::{channel, Receiver, Sender};
+use tracing::{error, info, warn};
+
+pub const CUE_CHANNEL: &str = "cue_channel";
+
+/// A request for a cue to be injected into the media of a stream
+#[derive(Debug)]
+pub struct CueInjectionRequest {
+    /// The name of the stream the cue should be injected into
+    pub stream_name: String,
+
+    /// Identifies the cue, so it can be correlated with the event that triggered it
+    pub id: String,
+
+    /// How long the signaled event (e.g. an ad break) lasts
+    pub duration: Duration,
+
+    /// If provided, will be sent `true` if the cue was injected, or `false` if no stream with
+    /// the specified name is passing through the step
+    pub response_channel: Option<Sender<bool>>,
+}
+
+/// Generates new instances of the cue inject workflow step
+pub struct CueInjectStepGenerator {
+    event_hub_subscriber: UnboundedSender<SubscriptionRequest>,
+}
+
+struct CueInjectStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    cue_channel: String,
+    cue_sender: UnboundedSender<CueInjectionRequest>,
+    streams: HashMap<StreamId, ActiveStream>,
+
+    /// The workflow manager the cue channel was registered with
+    workflow_manager: Option<UnboundedSender<WorkflowManagerRequest>>,
+}
+
+struct ActiveStream {
+    name: String,
+    tags: Vec<String>,
+}
+
+enum FutureResult {
+    EventHubGone,
+    CueChannelClosed,
+    WorkflowManagerEventReceived(
+        WorkflowManagerEvent,
+        UnboundedReceiver<WorkflowManagerEvent>,
+    ),
+
+    CueRequestReceived(CueInjectionRequest, UnboundedReceiver<CueInjectionRequest>),
+    CueChannelRegistrationResponse(bool),
+}
+
+impl StepFutureResult for FutureResult {}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error("No cue channel specified.  A '{}' parameter is required", CUE_CHANNEL)]
+    NoCueChannelSpecified,
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl CueInjectStepGenerator {
+    pub fn new(event_hub_subscriber: UnboundedSender<SubscriptionRequest>) -> Self {
+        CueInjectStepGenerator {
+            event_hub_subscriber,
+        }
+    }
+}
+
+impl StepGenerator for CueInjectStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let cue_channel = parse_cue_channel(&definition)?;
+
+        let (event_sender, event_receiver) = unbounded_channel();
+        let _ = self
+            .event_hub_subscriber
+            .send(SubscriptionRequest::WorkflowManagerEvents {
+                channel: event_sender,
+            });
+
+        let (cue_sender, cue_receiver) = unbounded_channel();
+        let step = CueInjectStep {
+            definition: definition.clone(),
+            status: StepStatus::Active,
+            cue_channel,
+            cue_sender,
+            streams: HashMap::new(),
+            workflow_manager: None,
+        };
+
+        let futures = vec![
+            wait_for_workflow_manager_event(event_receiver).boxed(),
+            wait_for_cue_request(cue_receiver).boxed(),
+        ];
+
+        Ok((Box::new(step), futures))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_cue_channel(definition)?;
+        Ok(())
+    }
+}
+
+fn parse_cue_channel(definition: &WorkflowStepDefinition) -> Result<String, StepStartupError> {
+    match definition.parameters.get(CUE_CHANNEL) {
+        Some(Some(name)) => Ok(name.clone()),
+        _ => Err(StepStartupError::NoCueChannelSpecified),
+    }
+}
+
+impl CueInjectStep {
+    fn handle_cue_request(&self, request: CueInjectionRequest, outputs: &mut StepOutputs) {
+        if self.status == StepStatus::Shutdown {
+            // Requests can still arrive while the step is being removed from its workflow
+            if let Some(response_channel) = request.response_channel {
+                let _ = response_channel.send(false);
+            }
+
+            return;
+        }
+
+        let mut was_injected = false;
+        for (stream_id, stream) in &self.streams {
+            if stream.name != request.stream_name {
+                continue;
+            }
+
+            info!(
+                stream_id = ?stream_id,
+                cue_id = %request.id,
+                "Injecting cue '{}' with a duration of {:?} into stream {:?}",
+                request.id, request.duration, stream_id
+            );
+
+            outputs.media.push(MediaNotification {
+                stream_id: stream_id.clone(),
+                content: MediaNotificationContent::Cue {
+                    id: request.id.clone(),
+                    duration: request.duration,
+                },
+                tags: stream.tags.clone(),
+            });
+
+            was_injected = true;
+        }
+
+        if !was_injected {
+            warn!(
+                cue_id = %request.id,
+                "Cue '{}' not injected, as no stream named '{}' is active",
+                request.id, request.stream_name
+            );
+        }
+
+        if let Some(response_channel) = request.response_channel {
+            let _ = response_channel.send(was_injected);
+        }
+    }
+}
+
+impl WorkflowStep for CueInjectStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        // Media needs to be handled first, so cues requested for a stream that's just connecting
+        // come after its new stream notification
+        for media in inputs.media.drain(..) {
+            match &media.content {
+                MediaNotificationContent::NewIncomingStream { stream_name } => {
+                    self.streams.insert(
+                        media.stream_id.clone(),
+                        ActiveStream {
+                            name: stream_name.clone(),
+                            tags: media.tags.clone(),
+                        },
+                    );
+                }
+
+                MediaNotificationContent::StreamDisconnected => {
+                    self.streams.remove(&media.stream_id);
+                }
+
+                _ => (),
+            }
+
+            outputs.media.push(media);
+        }
+
+        for notification in inputs.notifications.drain(..) {
+            let future_result = match notification.downcast::<FutureResult>() {
+                Ok(x) => *x,
+                Err(_) => {
+                    error!("Cue inject step received a notification that is not a known type");
+                    self.status = StepStatus::Error {
+                        message: "Received future result of unknown type".to_string(),
+                    };
+
+                    return;
+                }
+            };
+
+            match future_result {
+                FutureResult::EventHubGone => {
+                    error!("Received a notification that the event hub is gone");
+                    self.status = StepStatus::Error {
+                        message: "Event hub gone".to_string(),
+                    };
+
+                    return;
+                }
+
+                FutureResult::CueChannelClosed => {
+                    // Should never happen, since the step holds onto a sender for the channel
+                    error!("Cue channel unexpectedly closed");
+                    self.status = StepStatus::Error {
+                        message: "Cue channel closed".to_string(),
+                    };
+
+                    return;
+                }
+
+                FutureResult::WorkflowManagerEventReceived(event, receiver) => {
+                    outputs
+                        .futures
+                        .push(wait_for_workflow_manager_event(receiver).boxed());
+
+                    match event {
+                        WorkflowManagerEvent::WorkflowManagerRegistered { channel: manager } => {
+                            info!(
+                                "Registering cue channel '{}' with the workflow manager",
+                                self.cue_channel
+                            );
+
+                            let (sender, receiver) = channel();
+                            let _ = manager.send(WorkflowManagerRequest {
+                                request_id: "cue-inject".to_string(),
+                                operation: WorkflowManagerRequestOperation::RegisterCueChannel {
+                                    name: self.cue_channel.clone(),
+                                    channel: self.cue_sender.clone(),
+                                    response_channel: Some(sender),
+                                },
+                            });
+
+                            self.workflow_manager = Some(manager);
+                            outputs
+                                .futures
+                                .push(wait_for_registration_response(receiver).boxed());
+                        }
+                    }
+                }
+
+                FutureResult::CueRequestReceived(request, receiver) => {
+                    outputs.futures.push(wait_for_cue_request(receiver).boxed());
+                    self.handle_cue_request(request, outputs);
+                }
+
+                FutureResult::CueChannelRegistrationResponse(was_registered) => {
+                    if !was_registered && self.status != StepStatus::Shutdown {
+                        error!(
+                            "Cue channel '{}' is already registered by another step",
+                            self.cue_channel
+                        );
+
+                        self.workflow_manager = None;
+                        self.status = StepStatus::Error {
+                            message: format!(
+                                "Cue channel '{}' is already registered by another step",
+                                self.cue_channel
+                            ),
+                        };
+
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+
+        // Another step may want to use the same cue channel (e.g. when this step is restarted)
+        if let Some(manager) = self.workflow_manager.take() {
+            let _ = manager.send(WorkflowManagerRequest {
+                request_id: "cue-inject".to_string(),
+                operation: WorkflowManagerRequestOperation::UnregisterCueChannel {
+                    name: self.cue_channel.clone(),
+                    channel: self.cue_sender.clone(),
+                },
+            });
+        }
+    }
+}
+
+async fn wait_for_workflow_manager_event(
+    mut receiver: UnboundedReceiver<WorkflowManagerEvent>,
+) -> Box<dyn StepFutureResult> {
+    let result = match receiver.recv().await {
+        Some(event) => FutureResult::WorkflowManagerEventReceived(event, receiver),
+        None => FutureResult::EventHubGone,
+    };
+
+    Box::new(result)
+}
+
+async fn wait_for_registration_response(receiver: Receiver<bool>) -> Box<dyn StepFutureResult> {
+    // If the manager went away without responding, a new one will be registered with later
+    let was_registered = receiver.await.unwrap_or(true);
+
+    Box::new(FutureResult::CueChannelRegistrationResponse(was_registered))
+}
+
+async fn wait_for_cue_request(
+    mut receiver: UnboundedReceiver<CueInjectionRequest>,
+) -> Box<dyn StepFutureResult> {
+    let result = match receiver.recv().await {
+        Some(request) => FutureResult::CueRequestReceived(request, receiver),
+        None => FutureResult::CueChannelClosed,
+    };
+
+    Box::new(result)
+}
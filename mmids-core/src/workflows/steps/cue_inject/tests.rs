@@ -0,0 +1,241 @@
+use super::*;
+use crate::test_utils;
+use crate::workflows::steps::test_utils::create_definition;
+use crate::workflows::steps::StepTestContext;
+use tokio::sync::oneshot::channel;
+
+struct TestContext {
+    step_context: StepTestContext,
+    cue_channel: UnboundedSender<CueInjectionRequest>,
+    manager: UnboundedReceiver<WorkflowManagerRequest>,
+}
+
+impl TestContext {
+    async fn new() -> Self {
+        TestContext::with_registration_response(true).await
+    }
+
+    /// Creates the step, responding to its cue channel registration with the specified result
+    async fn with_registration_response(was_registered: bool) -> Self {
+        let (sub_sender, mut sub_receiver) = unbounded_channel();
+        let (manager_sender, mut manager_receiver) = unbounded_channel();
+        let generator = CueInjectStepGenerator::new(sub_sender);
+        let mut step_context = StepTestContext::new(
+            Box::new(generator),
+            create_definition("cue_inject", &[(CUE_CHANNEL, "cues")]),
+        )
+        .unwrap();
+
+        // It must subscribe to workflow manager events on startup
+        let event = test_utils::expect_mpsc_response(&mut sub_receiver).await;
+        let event_channel = match event {
+            SubscriptionRequest::WorkflowManagerEvents { channel } => channel,
+            event => panic!("Unexpected event: {:?}", event),
+        };
+
+        event_channel
+            .send(WorkflowManagerEvent::WorkflowManagerRegistered {
+                channel: manager_sender,
+            })
+            .expect("Failed to send workflow manager registered event");
+
+        let result = test_utils::expect_future_resolved(&mut step_context.futures).await;
+        step_context.execute_notification(result).await;
+
+        let request = test_utils::expect_mpsc_response(&mut manager_receiver).await;
+        let cue_channel = match request.operation {
+            WorkflowManagerRequestOperation::RegisterCueChannel {
+                name,
+                channel,
+                response_channel,
+            } => {
+                assert_eq!(&name, "cues", "Unexpected cue channel name");
+                let response_channel =
+                    response_channel.expect("Expected a registration response channel");
+
+                let _ = response_channel.send(was_registered);
+                channel
+            }
+
+            operation => panic!("Unexpected manager operation: {:?}", operation),
+        };
+
+        let result = test_utils::expect_future_resolved(&mut step_context.futures).await;
+        step_context.execute_notification(result).await;
+
+        TestContext {
+            step_context,
+            cue_channel,
+            manager: manager_receiver,
+        }
+    }
+
+    /// Requests a cue for the specified stream name, returning if the step reported it as injected
+    async fn inject_cue(&mut self, stream_name: &str) -> bool {
+        let (sender, receiver) = channel();
+        self.cue_channel
+            .send(CueInjectionRequest {
+                stream_name: stream_name.to_string(),
+                id: "cue1".to_string(),
+                duration: Duration::from_secs(30),
+                response_channel: Some(sender),
+            })
+            .expect("Failed to send cue request");
+
+        let result = test_utils::expect_future_resolved(&mut self.step_context.futures).await;
+        self.step_context.execute_notification(result).await;
+
+        test_utils::expect_oneshot_response(receiver).await
+    }
+}
+
+fn new_stream(stream_id: &str, stream_name: &str) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: stream_name.to_string(),
+        },
+        tags: vec!["tag".to_string()],
+    }
+}
+
+#[test]
+fn validation_fails_without_cue_channel() {
+    let (sender, _receiver) = unbounded_channel();
+    let generator = CueInjectStepGenerator::new(sender);
+
+    assert!(generator
+        .validate(&create_definition("cue_inject", &[]))
+        .is_err());
+}
+
+#[tokio::test]
+async fn media_passed_through() {
+    let mut context = TestContext::new().await;
+
+    context
+        .step_context
+        .assert_media_passed_through(new_stream("abc", "def"));
+}
+
+#[tokio::test]
+async fn cue_output_for_stream_with_requested_name() {
+    let mut context = TestContext::new().await;
+    context
+        .step_context
+        .execute_with_media(new_stream("abc", "def"));
+
+    let was_injected = context.inject_cue("def").await;
+    assert!(was_injected, "Expected cue to be injected");
+    assert_eq!(
+        context.step_context.media_outputs.len(),
+        1,
+        "Unexpected number of media outputs"
+    );
+
+    let media = &context.step_context.media_outputs[0];
+    assert_eq!(
+        media.stream_id,
+        StreamId("abc".to_string()),
+        "Unexpected stream id"
+    );
+    assert_eq!(
+        media.tags,
+        vec!["tag".to_string()],
+        "Expected cue to have the stream's tags"
+    );
+
+    match &media.content {
+        MediaNotificationContent::Cue { id, duration } => {
+            assert_eq!(id, "cue1", "Unexpected cue id");
+            assert_eq!(
+                duration,
+                &Duration::from_secs(30),
+                "Unexpected cue duration"
+            );
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[tokio::test]
+async fn no_cue_output_for_unknown_stream_name() {
+    let mut context = TestContext::new().await;
+    context
+        .step_context
+        .execute_with_media(new_stream("abc", "def"));
+
+    let was_injected = context.inject_cue("other").await;
+    assert!(!was_injected, "Expected cue to not be injected");
+    assert!(
+        context.step_context.media_outputs.is_empty(),
+        "Expected no media outputs"
+    );
+}
+
+#[tokio::test]
+async fn no_cue_output_after_stream_disconnects() {
+    let mut context = TestContext::new().await;
+    context
+        .step_context
+        .execute_with_media(new_stream("abc", "def"));
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
+    });
+
+    let was_injected = context.inject_cue("def").await;
+    assert!(!was_injected, "Expected cue to not be injected");
+    assert!(
+        context.step_context.media_outputs.is_empty(),
+        "Expected no media outputs"
+    );
+}
+
+#[tokio::test]
+async fn step_errors_when_cue_channel_already_registered() {
+    let context = TestContext::with_registration_response(false).await;
+
+    match context.step_context.step.get_status() {
+        StepStatus::Error { .. } => (),
+        status => panic!("Unexpected step status: {:?}", status),
+    }
+}
+
+#[tokio::test]
+async fn cue_channel_unregistered_on_shutdown() {
+    let mut context = TestContext::new().await;
+    context.step_context.step.shutdown();
+
+    let request = test_utils::expect_mpsc_response(&mut context.manager).await;
+    match request.operation {
+        WorkflowManagerRequestOperation::UnregisterCueChannel { name, channel } => {
+            assert_eq!(&name, "cues", "Unexpected cue channel name");
+            assert!(
+                channel.same_channel(&context.cue_channel),
+                "Expected the step's own cue channel to be unregistered"
+            );
+        }
+
+        operation => panic!("Unexpected manager operation: {:?}", operation),
+    }
+}
+
+#[tokio::test]
+async fn cue_not_injected_after_shutdown() {
+    let mut context = TestContext::new().await;
+    context
+        .step_context
+        .execute_with_media(new_stream("abc", "def"));
+
+    context.step_context.step.shutdown();
+
+    let was_injected = context.inject_cue("def").await;
+    assert!(!was_injected, "Expected cue to not be injected");
+    assert!(
+        context.step_context.media_outputs.is_empty(),
+        "Expected no media outputs"
+    );
+}
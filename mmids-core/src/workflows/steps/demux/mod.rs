@@ -0,0 +1,120 @@
+//! The demux step splits each stream that passes through it into two derived streams, one only
+//! carrying the stream's video and the other only carrying its audio.  This allows later steps
+//! (or workflows) to process each track independently.
+//!
+//! Derived stream ids (and names) are the original ones with a `_video` or `_audio` suffix, so
+//! they stay the same across reconnects of the source stream.  Notifications that apply to the
+//! whole stream, such as metadata and disconnections, are sent for both derived streams.  The
+//! original stream id is never passed to subsequent steps.
+
+#[cfg(test)]
+mod tests;
+
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationResult, StepInputs, StepOutputs, StepStatus, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
+
+/// The suffix added to the id and name of the stream that only carries video
+pub const VIDEO_SUFFIX: &str = "_video";
+
+/// The suffix added to the id and name of the stream that only carries audio
+pub const AUDIO_SUFFIX: &str = "_audio";
+
+/// Generates new instances of the demux workflow step based on specified step definitions.
+pub struct DemuxStepGenerator {}
+
+struct DemuxStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+}
+
+impl DemuxStepGenerator {
+    pub fn new() -> Self {
+        DemuxStepGenerator {}
+    }
+}
+
+impl StepGenerator for DemuxStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let step = DemuxStep {
+            definition,
+            status: StepStatus::Active,
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+}
+
+/// Gets the id of the stream derived from the specified stream id with the given suffix
+pub fn derived_stream_id(stream_id: &StreamId, suffix: &str) -> StreamId {
+    StreamId(format!("{}{}", stream_id.0, suffix))
+}
+
+impl DemuxStep {
+    fn handle_media(media: MediaNotification, outputs: &mut StepOutputs) {
+        match &media.content {
+            MediaNotificationContent::NewIncomingStream { stream_name } => {
+                for suffix in [VIDEO_SUFFIX, AUDIO_SUFFIX].iter() {
+                    outputs.media.push(MediaNotification {
+                        stream_id: derived_stream_id(&media.stream_id, suffix),
+                        content: MediaNotificationContent::NewIncomingStream {
+                            stream_name: format!("{}{}", stream_name, suffix),
+                        },
+                        tags: media.tags.clone(),
+                    });
+                }
+            }
+
+            MediaNotificationContent::Video { .. } => {
+                outputs.media.push(MediaNotification {
+                    stream_id: derived_stream_id(&media.stream_id, VIDEO_SUFFIX),
+                    ..media
+                });
+            }
+
+            MediaNotificationContent::Audio { .. } => {
+                outputs.media.push(MediaNotification {
+                    stream_id: derived_stream_id(&media.stream_id, AUDIO_SUFFIX),
+                    ..media
+                });
+            }
+
+            MediaNotificationContent::StreamDisconnected
+            | MediaNotificationContent::Metadata { .. }
+            | MediaNotificationContent::Discontinuity
+            | MediaNotificationContent::Cue { .. } => {
+                for suffix in [VIDEO_SUFFIX, AUDIO_SUFFIX].iter() {
+                    outputs.media.push(MediaNotification {
+                        stream_id: derived_stream_id(&media.stream_id, suffix),
+                        content: media.content.clone(),
+                        tags: media.tags.clone(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl WorkflowStep for DemuxStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for media in inputs.media.drain(..) {
+            DemuxStep::handle_media(media, outputs);
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+    }
+}
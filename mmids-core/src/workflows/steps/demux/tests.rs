@@ -0,0 +1,135 @@
+use super::*;
+use crate::workflows::steps::test_utils::{
+    audio_content, create_definition, disconnected_content, media, metadata_content,
+    new_stream_content, video_content,
+};
+use crate::workflows::steps::StepTestContext;
+use std::time::Duration;
+
+fn create_context() -> StepTestContext {
+    let generator = DemuxStepGenerator::new();
+    let definition = create_definition("demux", &[]);
+
+    StepTestContext::new(Box::new(generator), definition).unwrap()
+}
+
+#[test]
+fn step_is_active_immediately() {
+    let context = create_context();
+
+    assert_eq!(
+        context.step.get_status(),
+        &StepStatus::Active,
+        "Unexpected step status"
+    );
+}
+
+#[test]
+fn new_stream_announced_for_video_and_audio_streams() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+
+    assert_eq!(
+        context.media_outputs,
+        vec![
+            media("abc_video", new_stream_content("def_video")),
+            media("abc_audio", new_stream_content("def_audio")),
+        ],
+        "Unexpected media outputs"
+    );
+}
+
+#[test]
+fn video_only_sent_to_video_stream() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+
+    let content = video_content(true, false, Duration::from_millis(10));
+    context.execute_with_media(media("abc", content.clone()));
+
+    assert_eq!(
+        context.media_outputs,
+        vec![media("abc_video", content)],
+        "Unexpected media outputs"
+    );
+}
+
+#[test]
+fn audio_only_sent_to_audio_stream() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+
+    let content = audio_content(false, Duration::from_millis(10));
+    context.execute_with_media(media("abc", content.clone()));
+
+    assert_eq!(
+        context.media_outputs,
+        vec![media("abc_audio", content)],
+        "Unexpected media outputs"
+    );
+}
+
+#[test]
+fn metadata_sent_to_both_streams() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+
+    let content = metadata_content(&[("width", "1920")]);
+    context.execute_with_media(media("abc", content.clone()));
+
+    assert_eq!(
+        context.media_outputs,
+        vec![
+            media("abc_video", content.clone()),
+            media("abc_audio", content),
+        ],
+        "Unexpected media outputs"
+    );
+}
+
+#[test]
+fn disconnection_sent_to_both_streams() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    context.execute_with_media(media("abc", disconnected_content()));
+
+    assert_eq!(
+        context.media_outputs,
+        vec![
+            media("abc_video", disconnected_content()),
+            media("abc_audio", disconnected_content()),
+        ],
+        "Unexpected media outputs"
+    );
+}
+
+#[test]
+fn derived_stream_ids_same_after_reconnect() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    let first_outputs = context.media_outputs.clone();
+
+    context.execute_with_media(media("abc", disconnected_content()));
+    context.execute_with_media(media("abc", new_stream_content("def")));
+
+    assert_eq!(
+        context.media_outputs, first_outputs,
+        "Expected the same streams to be announced after reconnecting"
+    );
+}
+
+#[test]
+fn tags_kept_on_derived_streams() {
+    let mut context = create_context();
+    let mut notification = media("abc", new_stream_content("def"));
+    notification.tags.push("tag".to_string());
+    context.execute_with_media(notification);
+
+    assert!(
+        context
+            .media_outputs
+            .iter()
+            .all(|media| media.tags == vec!["tag".to_string()]),
+        "Expected all outputs to keep the tag"
+    );
+}
@@ -0,0 +1,135 @@
+//! The drop B-frames step removes non-reference H264 B-frames from all media streams that pass
+//! through it, for playback branches where latency matters more than quality.  B-frames require
+//! decoders to reorder frames, which delays playback, and B-frames that no other frames reference
+//! can be removed without breaking the decoding of the rest of the stream.
+//!
+//! A video frame is only dropped if every coded slice in it is a non-reference B slice.  Keyframes,
+//! sequence headers, non-H264 video, frames that can't be parsed, and all other media are passed
+//! to subsequent steps untouched.
+
+#[cfg(test)]
+mod tests;
+
+use crate::codecs::h264::{
+    get_nal_unit_type, get_nalu_length_size, get_slice_type, is_referenced, split_nal_units,
+    SliceType, DEFAULT_NALU_LENGTH_SIZE, NAL_UNIT_TYPE_IDR_SLICE, NAL_UNIT_TYPE_NON_IDR_SLICE,
+};
+use crate::codecs::VideoCodec;
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationResult, StepInputs, StepOutputs, StepStatus, WorkflowStep,
+};
+use crate::workflows::MediaNotificationContent;
+use crate::StreamId;
+use std::collections::HashMap;
+
+/// Generates new instances of the drop B-frames workflow step
+pub struct DropBFramesStepGenerator {}
+
+struct DropBFramesStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    nalu_length_sizes: HashMap<StreamId, usize>,
+}
+
+impl DropBFramesStepGenerator {
+    pub fn new() -> Self {
+        DropBFramesStepGenerator {}
+    }
+}
+
+impl StepGenerator for DropBFramesStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let step = DropBFramesStep {
+            definition,
+            status: StepStatus::Active,
+            nalu_length_sizes: HashMap::new(),
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+}
+
+impl WorkflowStep for DropBFramesStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for media in inputs.media.drain(..) {
+            match &media.content {
+                MediaNotificationContent::StreamDisconnected => {
+                    self.nalu_length_sizes.remove(&media.stream_id);
+                }
+
+                MediaNotificationContent::Video {
+                    codec: VideoCodec::H264,
+                    is_sequence_header: true,
+                    data,
+                    ..
+                } => {
+                    if let Some(size) = get_nalu_length_size(data) {
+                        self.nalu_length_sizes.insert(media.stream_id.clone(), size);
+                    }
+                }
+
+                MediaNotificationContent::Video {
+                    codec: VideoCodec::H264,
+                    is_sequence_header: false,
+                    is_keyframe: false,
+                    data,
+                    ..
+                } => {
+                    let nalu_length_size = self
+                        .nalu_length_sizes
+                        .get(&media.stream_id)
+                        .copied()
+                        .unwrap_or(DEFAULT_NALU_LENGTH_SIZE);
+
+                    if is_droppable_b_frame(data, nalu_length_size) {
+                        continue;
+                    }
+                }
+
+                _ => (),
+            }
+
+            outputs.media.push(media);
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+        self.nalu_length_sizes.clear();
+    }
+}
+
+/// Returns if the frame only contains B slices that no other frames reference
+fn is_droppable_b_frame(data: &[u8], nalu_length_size: usize) -> bool {
+    let nal_units = match split_nal_units(data, nalu_length_size) {
+        Some(nal_units) => nal_units,
+        None => return false,
+    };
+
+    let mut has_slices = false;
+    for nal_unit in nal_units {
+        match get_nal_unit_type(nal_unit) {
+            Some(NAL_UNIT_TYPE_NON_IDR_SLICE) => (),
+            Some(NAL_UNIT_TYPE_IDR_SLICE) => return false,
+            _ => continue,
+        }
+
+        if is_referenced(nal_unit) || get_slice_type(nal_unit) != Some(SliceType::B) {
+            return false;
+        }
+
+        has_slices = true;
+    }
+
+    has_slices
+}
@@ -0,0 +1,149 @@
+use super::*;
+use crate::workflows::steps::test_utils::{
+    audio_content, create_definition, media, new_stream_content,
+};
+use crate::workflows::steps::StepTestContext;
+use crate::workflows::MediaNotification;
+use crate::VideoTimestamp;
+use bytes::Bytes;
+use std::time::Duration;
+
+const NON_REFERENCE_B_SLICE: [u8; 3] = [0x01, 0xa0, 0xff];
+const REFERENCE_B_SLICE: [u8; 3] = [0x21, 0xa0, 0xff];
+const P_SLICE: [u8; 3] = [0x41, 0xc0, 0xff];
+const IDR_SLICE: [u8; 3] = [0x65, 0x88, 0xff];
+const ACCESS_UNIT_DELIMITER: [u8; 2] = [0x09, 0xf0];
+
+fn create_context() -> StepTestContext {
+    let generator = DropBFramesStepGenerator::new();
+    let definition = create_definition("drop_bframes", &[]);
+
+    StepTestContext::new(Box::new(generator), definition).unwrap()
+}
+
+/// Creates AVCC formatted data with the specified NAL units
+fn avcc(nal_units: &[&[u8]], nalu_length_size: usize) -> Bytes {
+    let mut data = Vec::new();
+    for nal_unit in nal_units {
+        let length = (nal_unit.len() as u32).to_be_bytes();
+        data.extend_from_slice(&length[4 - nalu_length_size..]);
+        data.extend_from_slice(nal_unit);
+    }
+
+    Bytes::from(data)
+}
+
+fn video(data: Bytes, is_keyframe: bool, is_sequence_header: bool) -> MediaNotification {
+    media(
+        "abc",
+        MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            is_keyframe,
+            is_sequence_header,
+            data,
+            timestamp: VideoTimestamp::from_zero(),
+        },
+    )
+}
+
+#[test]
+fn non_reference_b_frame_dropped() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+
+    context.assert_media_not_passed_through(video(
+        avcc(&[&ACCESS_UNIT_DELIMITER, &NON_REFERENCE_B_SLICE], 4),
+        false,
+        false,
+    ));
+}
+
+#[test]
+fn reference_b_frame_passed_through() {
+    let mut context = create_context();
+    context.assert_media_passed_through(video(avcc(&[&REFERENCE_B_SLICE], 4), false, false));
+}
+
+#[test]
+fn p_frame_passed_through() {
+    let mut context = create_context();
+    context.assert_media_passed_through(video(avcc(&[&P_SLICE], 4), false, false));
+}
+
+#[test]
+fn frame_with_b_and_p_slices_passed_through() {
+    let mut context = create_context();
+    context.assert_media_passed_through(video(
+        avcc(&[&NON_REFERENCE_B_SLICE, &P_SLICE], 4),
+        false,
+        false,
+    ));
+}
+
+#[test]
+fn idr_frame_passed_through() {
+    let mut context = create_context();
+    context.assert_media_passed_through(video(avcc(&[&IDR_SLICE], 4), true, false));
+}
+
+#[test]
+fn keyframe_never_dropped() {
+    let mut context = create_context();
+    context.assert_media_passed_through(video(avcc(&[&NON_REFERENCE_B_SLICE], 4), true, false));
+}
+
+#[test]
+fn sequence_header_passed_through() {
+    let mut context = create_context();
+    context.assert_media_passed_through(video(
+        Bytes::from(vec![0x01, 0x64, 0x00, 0x1f, 0xff, 0xe1]),
+        true,
+        true,
+    ));
+}
+
+#[test]
+fn unparseable_frame_passed_through() {
+    let mut context = create_context();
+    context.assert_media_passed_through(video(Bytes::from(vec![0, 0, 0, 9, 0x01]), false, false));
+}
+
+#[test]
+fn non_h264_video_passed_through() {
+    let mut context = create_context();
+    context.assert_media_passed_through(media(
+        "abc",
+        MediaNotificationContent::Video {
+            codec: VideoCodec::Unknown,
+            is_keyframe: false,
+            is_sequence_header: false,
+            data: avcc(&[&NON_REFERENCE_B_SLICE], 4),
+            timestamp: VideoTimestamp::from_zero(),
+        },
+    ));
+}
+
+#[test]
+fn audio_passed_through() {
+    let mut context = create_context();
+    context
+        .assert_media_passed_through(media("abc", audio_content(false, Duration::from_millis(5))));
+}
+
+#[test]
+fn nalu_length_size_from_sequence_header_used() {
+    let mut context = create_context();
+
+    // Decoder configuration record with 2 byte NAL unit lengths
+    context.execute_with_media(video(
+        Bytes::from(vec![0x01, 0x64, 0x00, 0x1f, 0xfd, 0xe1]),
+        true,
+        true,
+    ));
+
+    context.assert_media_not_passed_through(video(
+        avcc(&[&NON_REFERENCE_B_SLICE], 2),
+        false,
+        false,
+    ));
+}
@@ -0,0 +1,347 @@
+//! The exec hook step runs a configured command whenever a stream starts or stops passing
+//! through it, so external systems can be integrated with stream events.  The command is run
+//! with any configured arguments, followed by the event (`started` or `stopped`), the stream's
+//! name, and the stream's id.  The same values are provided in the `MMIDS_EVENT`,
+//! `MMIDS_STREAM_NAME`, and `MMIDS_STREAM_ID` environment variables.
+//!
+//! Commands are run in the background, and the step does not wait for them to finish before
+//! processing more media.  A command that fails to start or exits unsuccessfully is logged, but
+//! never puts the step into an error state.  Commands that run longer than the configured timeout
+//! are killed, as are any commands still running when the step is shut down.  All media is
+//! passed through unchanged.
+
+#[cfg(test)]
+mod tests;
+
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use crate::workflows::MediaNotificationContent;
+use crate::StreamId;
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::process::Command;
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+pub const COMMAND: &str = "command";
+pub const ARGUMENTS: &str = "arguments";
+pub const TIMEOUT_SECONDS: &str = "timeout_seconds";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+const EVENT_ENV_VAR: &str = "MMIDS_EVENT";
+const STREAM_NAME_ENV_VAR: &str = "MMIDS_STREAM_NAME";
+const STREAM_ID_ENV_VAR: &str = "MMIDS_STREAM_ID";
+
+/// Generates new instances of the exec hook workflow step
+pub struct ExecHookStepGenerator {}
+
+struct ExecHookStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    command: String,
+    arguments: Vec<String>,
+    timeout: Duration,
+    stream_names: HashMap<StreamId, String>,
+
+    /// Set to true when the step shuts down, so hooks that are still running are killed
+    shutdown_sender: watch::Sender<bool>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum StreamEvent {
+    Started,
+    Stopped,
+}
+
+#[derive(Clone, Debug)]
+enum HookOutcome {
+    Exited(ExitStatus),
+    TimedOut,
+    KilledOnShutdown,
+    FailedToRun(String),
+}
+
+enum FutureResult {
+    HookCompleted {
+        event: StreamEvent,
+        stream_id: StreamId,
+        outcome: HookOutcome,
+    },
+}
+
+impl StepFutureResult for FutureResult {}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error("No command specified.  A '{}' parameter is required", COMMAND)]
+    NoCommandSpecified,
+
+    #[error(
+        "Invalid timeout of '{0}'.  {} should be a positive number of seconds",
+        TIMEOUT_SECONDS
+    )]
+    InvalidTimeout(String),
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl StreamEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            StreamEvent::Started => "started",
+            StreamEvent::Stopped => "stopped",
+        }
+    }
+}
+
+impl ExecHookStepGenerator {
+    pub fn new() -> Self {
+        ExecHookStepGenerator {}
+    }
+}
+
+impl StepGenerator for ExecHookStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let command = parse_command(&definition)?;
+        let timeout = parse_timeout(&definition)?;
+        let arguments = definition.get_list_parameter(ARGUMENTS).unwrap_or_default();
+        let (shutdown_sender, _) = watch::channel(false);
+        let step = ExecHookStep {
+            definition,
+            status: StepStatus::Active,
+            command,
+            arguments,
+            timeout,
+            stream_names: HashMap::new(),
+            shutdown_sender,
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_command(definition)?;
+        parse_timeout(definition)?;
+        Ok(())
+    }
+}
+
+fn parse_command(definition: &WorkflowStepDefinition) -> Result<String, StepStartupError> {
+    match definition.parameters.get(COMMAND) {
+        Some(Some(command)) if !command.trim().is_empty() => Ok(command.clone()),
+        _ => Err(StepStartupError::NoCommandSpecified),
+    }
+}
+
+fn parse_timeout(definition: &WorkflowStepDefinition) -> Result<Duration, StepStartupError> {
+    match definition.parameters.get(TIMEOUT_SECONDS) {
+        Some(Some(value)) => match value.parse::<u64>() {
+            Ok(num) if num > 0 => Ok(Duration::from_secs(num)),
+            _ => Err(StepStartupError::InvalidTimeout(value.clone())),
+        },
+
+        _ => Ok(DEFAULT_TIMEOUT),
+    }
+}
+
+impl ExecHookStep {
+    fn run_hook(
+        &self,
+        event: StreamEvent,
+        stream_id: StreamId,
+        stream_name: String,
+        outputs: &mut StepOutputs,
+    ) {
+        info!(
+            stream_id = ?stream_id,
+            stream_name = %stream_name,
+            "Running '{}' for stream {:?} being {}",
+            self.command, stream_id, event.name()
+        );
+
+        let mut command = Command::new(&self.command);
+        command
+            .args(&self.arguments)
+            .arg(event.name())
+            .arg(&stream_name)
+            .arg(&stream_id.0)
+            .env(EVENT_ENV_VAR, event.name())
+            .env(STREAM_NAME_ENV_VAR, &stream_name)
+            .env(STREAM_ID_ENV_VAR, &stream_id.0)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+
+        outputs.futures.push(
+            run_command(
+                command,
+                self.timeout,
+                self.shutdown_sender.subscribe(),
+                event,
+                stream_id,
+            )
+            .boxed(),
+        );
+    }
+
+    fn handle_hook_completed(&self, event: StreamEvent, stream_id: StreamId, outcome: HookOutcome) {
+        match outcome {
+            HookOutcome::Exited(status) if status.success() => {
+                info!(
+                    stream_id = ?stream_id,
+                    "Hook '{}' for stream {:?} being {} completed successfully",
+                    self.command, stream_id, event.name()
+                );
+            }
+
+            HookOutcome::Exited(status) => {
+                warn!(
+                    stream_id = ?stream_id,
+                    "Hook '{}' for stream {:?} being {} failed with {}",
+                    self.command, stream_id, event.name(), status
+                );
+            }
+
+            HookOutcome::TimedOut => {
+                warn!(
+                    stream_id = ?stream_id,
+                    "Hook '{}' for stream {:?} being {} did not finish within {} seconds and was killed",
+                    self.command, stream_id, event.name(), self.timeout.as_secs()
+                );
+            }
+
+            HookOutcome::KilledOnShutdown => {
+                info!(
+                    stream_id = ?stream_id,
+                    "Hook '{}' for stream {:?} being {} was killed as the step shut down",
+                    self.command, stream_id, event.name()
+                );
+            }
+
+            HookOutcome::FailedToRun(error) => {
+                warn!(
+                    stream_id = ?stream_id,
+                    "Hook '{}' for stream {:?} being {} could not be run: {}",
+                    self.command, stream_id, event.name(), error
+                );
+            }
+        }
+    }
+}
+
+impl WorkflowStep for ExecHookStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for notification in inputs.notifications.drain(..) {
+            match notification.downcast::<FutureResult>() {
+                Ok(result) => match *result {
+                    FutureResult::HookCompleted {
+                        event,
+                        stream_id,
+                        outcome,
+                    } => self.handle_hook_completed(event, stream_id, outcome),
+                },
+
+                Err(_) => {
+                    error!("Exec hook step received a notification that is not a known type");
+                    self.status = StepStatus::Error {
+                        message: "Received future result of unknown type".to_string(),
+                    };
+
+                    return;
+                }
+            }
+        }
+
+        for media in inputs.media.drain(..) {
+            match &media.content {
+                MediaNotificationContent::NewIncomingStream { stream_name } => {
+                    self.stream_names
+                        .insert(media.stream_id.clone(), stream_name.clone());
+
+                    self.run_hook(
+                        StreamEvent::Started,
+                        media.stream_id.clone(),
+                        stream_name.clone(),
+                        outputs,
+                    );
+                }
+
+                MediaNotificationContent::StreamDisconnected => {
+                    if let Some(stream_name) = self.stream_names.remove(&media.stream_id) {
+                        self.run_hook(
+                            StreamEvent::Stopped,
+                            media.stream_id.clone(),
+                            stream_name,
+                            outputs,
+                        );
+                    }
+                }
+
+                _ => (),
+            }
+
+            outputs.media.push(media);
+        }
+    }
+
+    fn shutdown(&mut self) {
+        let _ = self.shutdown_sender.send(true);
+        self.status = StepStatus::Shutdown;
+    }
+}
+
+async fn run_command(
+    mut command: Command,
+    timeout: Duration,
+    mut shutdown_receiver: watch::Receiver<bool>,
+    event: StreamEvent,
+    stream_id: StreamId,
+) -> Box<dyn StepFutureResult> {
+    let outcome = match command.spawn() {
+        Ok(mut child) => {
+            // The step being dropped closes the channel, which also counts as a shutdown
+            let outcome = tokio::select! {
+                result = child.wait() => match result {
+                    Ok(status) => HookOutcome::Exited(status),
+                    Err(error) => HookOutcome::FailedToRun(error.to_string()),
+                },
+
+                _ = tokio::time::sleep(timeout) => HookOutcome::TimedOut,
+                _ = shutdown_receiver.changed() => HookOutcome::KilledOnShutdown,
+            };
+
+            if let HookOutcome::TimedOut | HookOutcome::KilledOnShutdown = outcome {
+                let _ = child.kill().await;
+            }
+
+            outcome
+        }
+
+        Err(error) => HookOutcome::FailedToRun(error.to_string()),
+    };
+
+    Box::new(FutureResult::HookCompleted {
+        event,
+        stream_id,
+        outcome,
+    })
+}
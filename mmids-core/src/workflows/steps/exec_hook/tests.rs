@@ -0,0 +1,231 @@
+use super::*;
+use crate::workflows::steps::test_utils::create_definition;
+use crate::workflows::steps::StepTestContext;
+use crate::workflows::MediaNotification;
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::time::timeout;
+
+fn create_context(command: &str) -> StepTestContext {
+    StepTestContext::new(
+        Box::new(ExecHookStepGenerator::new()),
+        create_definition("exec_hook", &[(COMMAND, command)]),
+    )
+    .unwrap()
+}
+
+fn create_context_with_parameters(command: &str, parameters: &[(&str, &str)]) -> StepTestContext {
+    let mut definition = create_definition("exec_hook", &[(COMMAND, command)]);
+    for (key, value) in parameters {
+        definition
+            .parameters
+            .insert(key.to_string(), Some(value.to_string()));
+    }
+
+    StepTestContext::new(Box::new(ExecHookStepGenerator::new()), definition).unwrap()
+}
+
+fn new_stream(stream_id: &str, stream_name: &str) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: stream_name.to_string(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn disconnection(stream_id: &str) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
+    }
+}
+
+/// Waits for the hook's command to finish and passes the result into the step, returning the
+/// event and stream the hook was run for, and the outcome of the command.  Processes take longer
+/// to run than the usual test timeouts allow for.
+async fn complete_hook(context: &mut StepTestContext) -> (StreamEvent, StreamId, HookOutcome) {
+    let result = match timeout(Duration::from_secs(5), context.futures.next()).await {
+        Ok(Some(result)) => result,
+        _ => panic!("Hook did not complete within timeout period"),
+    };
+
+    let details = match result.downcast_ref::<FutureResult>() {
+        Some(FutureResult::HookCompleted {
+            event,
+            stream_id,
+            outcome,
+        }) => (*event, stream_id.clone(), outcome.clone()),
+
+        None => panic!("Unexpected future result type"),
+    };
+
+    context.execute_notification(result).await;
+
+    details
+}
+
+#[test]
+fn validation_fails_without_command() {
+    let generator = ExecHookStepGenerator::new();
+
+    assert!(generator
+        .validate(&create_definition("exec_hook", &[]))
+        .is_err());
+}
+
+#[test]
+fn validation_fails_with_empty_command() {
+    let generator = ExecHookStepGenerator::new();
+
+    assert!(generator
+        .validate(&create_definition("exec_hook", &[(COMMAND, " ")]))
+        .is_err());
+}
+
+#[test]
+fn validation_fails_with_invalid_timeout() {
+    let generator = ExecHookStepGenerator::new();
+
+    for value in ["abc", "0", "-1"] {
+        let mut definition = create_definition("exec_hook", &[(COMMAND, "true")]);
+        definition
+            .parameters
+            .insert(TIMEOUT_SECONDS.to_string(), Some(value.to_string()));
+
+        assert!(
+            generator.validate(&definition).is_err(),
+            "Expected timeout of '{}' to be invalid",
+            value
+        );
+    }
+}
+
+#[tokio::test]
+async fn media_passed_through() {
+    let mut context = create_context("true");
+
+    context.assert_media_passed_through(new_stream("abc", "def"));
+    context.assert_media_passed_through(disconnection("abc"));
+}
+
+#[tokio::test]
+async fn hook_run_when_stream_starts() {
+    let mut context = create_context("true");
+    context.execute_with_media(new_stream("abc", "def"));
+
+    assert_eq!(context.futures.len(), 1, "Expected a hook to be running");
+
+    let (event, stream_id, _) = complete_hook(&mut context).await;
+    assert_eq!(event, StreamEvent::Started, "Unexpected event");
+    assert_eq!(
+        stream_id,
+        StreamId("abc".to_string()),
+        "Unexpected stream id"
+    );
+}
+
+#[tokio::test]
+async fn hook_run_when_stream_disconnects() {
+    let mut context = create_context("true");
+    context.execute_with_media(new_stream("abc", "def"));
+    complete_hook(&mut context).await;
+
+    context.execute_with_media(disconnection("abc"));
+
+    assert_eq!(context.futures.len(), 1, "Expected a hook to be running");
+
+    let (event, stream_id, _) = complete_hook(&mut context).await;
+    assert_eq!(event, StreamEvent::Stopped, "Unexpected event");
+    assert_eq!(
+        stream_id,
+        StreamId("abc".to_string()),
+        "Unexpected stream id"
+    );
+}
+
+#[tokio::test]
+async fn no_hook_run_when_unknown_stream_disconnects() {
+    let mut context = create_context("true");
+    context.execute_with_media(disconnection("abc"));
+
+    assert!(context.futures.is_empty(), "Expected no hook to be running");
+}
+
+#[tokio::test]
+async fn failing_command_does_not_error_step() {
+    let mut context = create_context("false");
+    context.execute_with_media(new_stream("abc", "def"));
+    complete_hook(&mut context).await;
+
+    assert_eq!(
+        context.step.get_status(),
+        &StepStatus::Active,
+        "Unexpected step status"
+    );
+}
+
+#[tokio::test]
+async fn missing_command_does_not_error_step() {
+    let mut context = create_context("/mmids/does/not/exist");
+    context.execute_with_media(new_stream("abc", "def"));
+
+    let (_, _, outcome) = complete_hook(&mut context).await;
+    assert!(
+        matches!(outcome, HookOutcome::FailedToRun(_)),
+        "Expected command to fail to run, instead got {:?}",
+        outcome
+    );
+
+    assert_eq!(
+        context.step.get_status(),
+        &StepStatus::Active,
+        "Unexpected step status"
+    );
+}
+
+#[tokio::test]
+async fn arguments_passed_before_event_arguments() {
+    let mut context = create_context_with_parameters("sh", &[(ARGUMENTS, "-c, exit 3")]);
+    context.execute_with_media(new_stream("abc", "def"));
+
+    let (_, _, outcome) = complete_hook(&mut context).await;
+    match outcome {
+        HookOutcome::Exited(status) => {
+            assert_eq!(status.code(), Some(3), "Unexpected exit code");
+        }
+
+        outcome => panic!("Expected command to exit, instead got {:?}", outcome),
+    }
+}
+
+#[tokio::test]
+async fn hook_killed_when_timeout_elapses() {
+    let mut context =
+        create_context_with_parameters("sleep", &[(ARGUMENTS, "10"), (TIMEOUT_SECONDS, "1")]);
+
+    context.execute_with_media(new_stream("abc", "def"));
+
+    let (_, _, outcome) = complete_hook(&mut context).await;
+    assert!(
+        matches!(outcome, HookOutcome::TimedOut),
+        "Expected command to time out, instead got {:?}",
+        outcome
+    );
+}
+
+#[tokio::test]
+async fn running_hook_killed_on_shutdown() {
+    let mut context = create_context_with_parameters("sleep", &[(ARGUMENTS, "10")]);
+    context.execute_with_media(new_stream("abc", "def"));
+    context.step.shutdown();
+
+    let (_, _, outcome) = complete_hook(&mut context).await;
+    assert!(
+        matches!(outcome, HookOutcome::KilledOnShutdown),
+        "Expected command to be killed, instead got {:?}",
+        outcome
+    );
+}
@@ -225,9 +225,12 @@ impl ExternalStreamReader {
                                 rtmp_stream_key: StreamKeyRegistration::Exact(stream.id.0.clone()),
                                 port: 1935,
                                 media_channel: media_receiver,
+                                media_backlog: None,
                                 ip_restrictions: IpRestriction::None,
                                 use_tls: false,
                                 requires_registrant_approval: false,
+                                max_watchers: None,
+                                start_on_keyframe: false,
                             });
 
                     outputs.futures.push(
@@ -340,6 +343,7 @@ impl ExternalStreamReader {
 
                 RtmpEndpointWatcherNotification::StreamKeyBecameActive { .. } => (),
                 RtmpEndpointWatcherNotification::StreamKeyBecameInactive { .. } => (),
+                RtmpEndpointWatcherNotification::WatcherJoined { .. } => (),
 
                 RtmpEndpointWatcherNotification::WatcherRequiringApproval { .. } => {
                     error!("Received request for approval but requests should be auto-approved");
@@ -467,6 +471,7 @@ mod tests {
                 content: MediaNotificationContent::NewIncomingStream {
                     stream_name: "def".to_string(),
                 },
+                tags: Vec::new(),
             };
 
             self.external_stream_reader
@@ -506,6 +511,7 @@ mod tests {
             content: MediaNotificationContent::NewIncomingStream {
                 stream_name: "def".to_string(),
             },
+            tags: Vec::new(),
         };
 
         context
@@ -521,6 +527,7 @@ mod tests {
                 rtmp_stream_key: _,
                 requires_registrant_approval,
                 media_channel: _,
+                media_backlog: _,
                 use_tls,
                 ip_restrictions,
                 notification_channel: _,
@@ -553,6 +560,7 @@ mod tests {
             content: MediaNotificationContent::NewIncomingStream {
                 stream_name: "def".to_string(),
             },
+            tags: Vec::new(),
         };
 
         context
@@ -578,6 +586,7 @@ mod tests {
         let media = MediaNotification {
             stream_id: StreamId("abc".to_string()),
             content: MediaNotificationContent::StreamDisconnected,
+            tags: Vec::new(),
         };
 
         context
@@ -605,6 +614,7 @@ mod tests {
             content: MediaNotificationContent::Metadata {
                 data: metadata.clone(),
             },
+            tags: Vec::new(),
         };
 
         context
@@ -639,6 +649,7 @@ mod tests {
                 is_keyframe: true,
                 is_sequence_header: true,
             },
+            tags: Vec::new(),
         };
 
         context
@@ -679,6 +690,7 @@ mod tests {
                 timestamp: Duration::from_millis(5),
                 is_sequence_header: true,
             },
+            tags: Vec::new(),
         };
 
         context
@@ -714,6 +726,7 @@ mod tests {
             content: MediaNotificationContent::NewIncomingStream {
                 stream_name: "def".to_string(),
             },
+            tags: Vec::new(),
         };
 
         context
@@ -753,6 +766,7 @@ mod tests {
         let media = MediaNotification {
             stream_id: StreamId("abc".to_string()),
             content: MediaNotificationContent::StreamDisconnected,
+            tags: Vec::new(),
         };
 
         context
@@ -771,6 +785,7 @@ mod tests {
         let media = MediaNotification {
             stream_id: StreamId("abc".to_string()),
             content: MediaNotificationContent::StreamDisconnected,
+            tags: Vec::new(),
         };
 
         context
@@ -794,6 +809,7 @@ mod tests {
         let media = MediaNotification {
             stream_id: StreamId("abc".to_string()),
             content: MediaNotificationContent::Metadata { data: raw_metadata },
+            tags: Vec::new(),
         };
 
         let mut outputs = StepOutputs::new();
@@ -830,6 +846,7 @@ mod tests {
                 codec: VideoCodec::H264,
                 timestamp: video_timestamp.clone(),
             },
+            tags: Vec::new(),
         };
 
         let mut outputs = StepOutputs::new();
@@ -881,6 +898,7 @@ mod tests {
                 codec: AudioCodec::Aac,
                 timestamp: Duration::from_millis(5),
             },
+            tags: Vec::new(),
         };
 
         let mut outputs = StepOutputs::new();
@@ -1,19 +1,52 @@
 use crate::workflows::definitions::{WorkflowStepDefinition, WorkflowStepType};
-use crate::workflows::steps::StepCreationResult;
+use crate::workflows::steps::{StepCreationResult, StepValidationResult};
 use std::collections::HashMap;
 use thiserror::Error;
 
+/// Describes the role a workflow step plays in the flow of media through a workflow
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepKind {
+    /// The step brings new media streams into the workflow (e.g. from RTMP publishers)
+    Source,
+
+    /// The step takes media streams from previous steps and passes modified or unmodified media
+    /// onto later steps
+    Transform,
+
+    /// The step sends media streams out of the workflow (e.g. to RTMP watchers or to disk)
+    Sink,
+}
+
 /// Represents a type that can generate an instance of a workflow step
 pub trait StepGenerator {
     /// Creates a brand new instance of a workflow step based on the supplied definition
     fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult;
+
+    /// The kind of step this generator creates.  Used to validate the order of steps in a
+    /// workflow before any steps are created.
+    fn kind(&self) -> StepKind {
+        StepKind::Transform
+    }
+
+    /// Checks that the supplied definition's parameters are valid without creating the step.
+    /// Unlike `generate()` this must not have any side effects, such as registering with
+    /// endpoints, so it can be used to check a configuration before any workflows are started.
+    ///
+    /// Generators that accept all parameter values don't need to override this.
+    fn validate(&self, _definition: &WorkflowStepDefinition) -> StepValidationResult {
+        Ok(())
+    }
 }
 
 /// The workflow step factory allows consumers to register different workflow step generation
 /// instances to use for specific workflow step types.  Consumers can then request the factory
 /// to generate workflow steps based on the passed in step definition.
+///
+/// Generators can also be registered with aliases, which allows step types to be referred to by
+/// shorter names, or to be renamed without breaking existing configurations.
 pub struct WorkflowStepFactory {
     generators: HashMap<WorkflowStepType, Box<dyn StepGenerator + Sync + Send>>,
+    aliases: HashMap<WorkflowStepType, WorkflowStepType>,
 }
 
 /// Errors that can occur when an attempting to register a generator fails
@@ -28,8 +61,11 @@ pub enum FactoryRegistrationError {
 /// Errors that can occur when an attempt to generate a workflow step fails
 #[derive(Error, Debug)]
 pub enum FactoryCreateError {
-    #[error("No workflow step generator is registered for the type '{0}'")]
-    NoRegisteredStep(WorkflowStepType),
+    #[error(
+        "No workflow step generator is registered for the type '{0}'.  Known step types: {}",
+        .1.join(", ")
+    )]
+    NoRegisteredStep(WorkflowStepType, Vec<String>),
 }
 
 impl WorkflowStepFactory {
@@ -37,6 +73,7 @@ impl WorkflowStepFactory {
     pub fn new() -> Self {
         WorkflowStepFactory {
             generators: HashMap::new(),
+            aliases: HashMap::new(),
         }
     }
 
@@ -46,10 +83,33 @@ impl WorkflowStepFactory {
         step_type: WorkflowStepType,
         generator: Box<dyn StepGenerator + Sync + Send>,
     ) -> Result<(), FactoryRegistrationError> {
-        if self.generators.contains_key(&step_type) {
+        self.register_with_aliases(step_type, Vec::new(), generator)
+    }
+
+    /// Attempts to register a specific generator instance with the specified step type, which can
+    /// also be referred to by any of the specified aliases.  Nothing is registered if the step
+    /// type or any of the aliases are already in use by another step type or alias.
+    pub fn register_with_aliases(
+        &mut self,
+        step_type: WorkflowStepType,
+        aliases: Vec<WorkflowStepType>,
+        generator: Box<dyn StepGenerator + Sync + Send>,
+    ) -> Result<(), FactoryRegistrationError> {
+        if self.is_name_in_use(&step_type) {
             return Err(FactoryRegistrationError::DuplicateName(step_type));
         }
 
+        for (index, alias) in aliases.iter().enumerate() {
+            if *alias == step_type || aliases[..index].contains(alias) || self.is_name_in_use(alias)
+            {
+                return Err(FactoryRegistrationError::DuplicateName(alias.clone()));
+            }
+        }
+
+        for alias in aliases {
+            self.aliases.insert(alias, step_type.clone());
+        }
+
         self.generators.insert(step_type, generator);
         return Ok(());
     }
@@ -59,11 +119,242 @@ impl WorkflowStepFactory {
         &self,
         definition: WorkflowStepDefinition,
     ) -> Result<StepCreationResult, FactoryCreateError> {
-        let generator = match self.generators.get(&definition.step_type) {
+        let generator = match self.get_generator(&definition.step_type) {
             Some(generator) => generator,
-            None => return Err(FactoryCreateError::NoRegisteredStep(definition.step_type)),
+            None => {
+                return Err(FactoryCreateError::NoRegisteredStep(
+                    definition.step_type,
+                    self.registered_types(),
+                ))
+            }
         };
 
         Ok(generator.generate(definition))
     }
+
+    /// Checks if the specified definition could be used to create a workflow step, without
+    /// actually creating it
+    pub fn validate_step(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        match self.get_generator(&definition.step_type) {
+            Some(generator) => generator.validate(definition),
+            None => Err(Box::new(FactoryCreateError::NoRegisteredStep(
+                definition.step_type.clone(),
+                self.registered_types(),
+            ))),
+        }
+    }
+
+    /// Gets the names of all step types that have a generator registered, in alphabetical order
+    pub fn registered_types(&self) -> Vec<String> {
+        let mut types = self
+            .generators
+            .keys()
+            .map(|step_type| step_type.0.clone())
+            .collect::<Vec<_>>();
+
+        types.sort();
+        types
+    }
+
+    /// Gets the kind of step generated for the specified step type, if a generator has been
+    /// registered for it
+    pub fn get_step_kind(&self, step_type: &WorkflowStepType) -> Option<StepKind> {
+        self.get_generator(step_type)
+            .map(|generator| generator.kind())
+    }
+
+    /// Gets the generator registered for the specified step type, resolving it first if it's an
+    /// alias
+    fn get_generator(
+        &self,
+        step_type: &WorkflowStepType,
+    ) -> Option<&(dyn StepGenerator + Sync + Send)> {
+        let step_type = self.aliases.get(step_type).unwrap_or(step_type);
+        self.generators
+            .get(step_type)
+            .map(|generator| generator.as_ref())
+    }
+
+    fn is_name_in_use(&self, name: &WorkflowStepType) -> bool {
+        self.generators.contains_key(name) || self.aliases.contains_key(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflows::steps::StepCreationError;
+
+    struct NoopStepGenerator;
+
+    impl StepGenerator for NoopStepGenerator {
+        fn generate(&self, _definition: WorkflowStepDefinition) -> StepCreationResult {
+            Err(StepCreationError::Fatal("Not supported in tests".into()))
+        }
+    }
+
+    /// Fails step creation with its name, so tests can tell which generator was used
+    struct NamedStepGenerator(&'static str);
+
+    impl StepGenerator for NamedStepGenerator {
+        fn generate(&self, _definition: WorkflowStepDefinition) -> StepCreationResult {
+            Err(StepCreationError::Fatal(self.0.into()))
+        }
+    }
+
+    fn create_factory() -> WorkflowStepFactory {
+        let mut factory = WorkflowStepFactory::new();
+        factory
+            .register(
+                WorkflowStepType("second".to_string()),
+                Box::new(NoopStepGenerator),
+            )
+            .unwrap();
+
+        factory
+            .register(
+                WorkflowStepType("first".to_string()),
+                Box::new(NoopStepGenerator),
+            )
+            .unwrap();
+
+        factory
+    }
+
+    #[test]
+    fn validating_unknown_step_type_returns_error() {
+        let factory = create_factory();
+        let definition = WorkflowStepDefinition {
+            step_type: WorkflowStepType("third".to_string()),
+            parameters: HashMap::new(),
+        };
+
+        assert!(factory.validate_step(&definition).is_err());
+    }
+
+    #[test]
+    fn validating_registered_step_type_uses_generator() {
+        let factory = create_factory();
+        let definition = WorkflowStepDefinition {
+            step_type: WorkflowStepType("first".to_string()),
+            parameters: HashMap::new(),
+        };
+
+        assert!(factory.validate_step(&definition).is_ok());
+    }
+
+    #[test]
+    fn registered_types_returns_all_types_in_order() {
+        let factory = create_factory();
+
+        assert_eq!(
+            factory.registered_types(),
+            vec!["first".to_string(), "second".to_string()],
+            "Unexpected registered types"
+        );
+    }
+
+    #[test]
+    fn unknown_step_type_error_lists_registered_types() {
+        let factory = create_factory();
+        let definition = WorkflowStepDefinition {
+            step_type: WorkflowStepType("third".to_string()),
+            parameters: HashMap::new(),
+        };
+
+        let error = match factory.create_step(definition) {
+            Err(error) => error,
+            Ok(_) => panic!("Expected an error"),
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "No workflow step generator is registered for the type 'third'.  Known step types: first, second",
+            "Unexpected error message"
+        );
+    }
+
+    #[test]
+    fn aliased_step_type_creates_step_from_aliased_generator() {
+        let mut factory = create_factory();
+        factory
+            .register_with_aliases(
+                WorkflowStepType("rtmp_receive".to_string()),
+                vec![WorkflowStepType("receive".to_string())],
+                Box::new(NamedStepGenerator("rtmp_receive")),
+            )
+            .unwrap();
+
+        let definition = WorkflowStepDefinition {
+            step_type: WorkflowStepType("receive".to_string()),
+            parameters: HashMap::new(),
+        };
+
+        let error = match factory.create_step(definition) {
+            Ok(Err(error)) => error,
+            Ok(Ok(_)) => panic!("Expected the named generator to fail step creation"),
+            Err(error) => panic!("Expected alias to be resolved: {:?}", error),
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "rtmp_receive",
+            "Unexpected generator used"
+        );
+    }
+
+    #[test]
+    fn aliased_step_type_can_be_validated() {
+        let mut factory = create_factory();
+        factory
+            .register_with_aliases(
+                WorkflowStepType("rtmp_receive".to_string()),
+                vec![WorkflowStepType("receive".to_string())],
+                Box::new(NoopStepGenerator),
+            )
+            .unwrap();
+
+        let definition = WorkflowStepDefinition {
+            step_type: WorkflowStepType("receive".to_string()),
+            parameters: HashMap::new(),
+        };
+
+        assert!(factory.validate_step(&definition).is_ok());
+    }
+
+    #[test]
+    fn alias_matching_registered_step_type_is_rejected() {
+        let mut factory = create_factory();
+        let result = factory.register_with_aliases(
+            WorkflowStepType("third".to_string()),
+            vec![WorkflowStepType("first".to_string())],
+            Box::new(NoopStepGenerator),
+        );
+
+        assert!(result.is_err(), "Expected registration to fail");
+        assert_eq!(
+            factory.registered_types(),
+            vec!["first".to_string(), "second".to_string()],
+            "Expected the step type to not be registered"
+        );
+    }
+
+    #[test]
+    fn step_type_matching_registered_alias_is_rejected() {
+        let mut factory = create_factory();
+        factory
+            .register_with_aliases(
+                WorkflowStepType("third".to_string()),
+                vec![WorkflowStepType("alias".to_string())],
+                Box::new(NoopStepGenerator),
+            )
+            .unwrap();
+
+        let result = factory.register(
+            WorkflowStepType("alias".to_string()),
+            Box::new(NoopStepGenerator),
+        );
+
+        assert!(result.is_err(), "Expected registration to fail");
+    }
 }
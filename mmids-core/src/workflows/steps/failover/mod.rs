@@ -0,0 +1,422 @@
+//! The failover step combines a primary and a backup stream into a single output stream.  Media
+//! from the primary stream is output while it's connected.  When the primary stream disconnects
+//! the step fails over to the backup stream, and when the primary stream reconnects the step
+//! switches back to it.
+//!
+//! Sources are only switched on video keyframes, so the output stays decodable across switches.
+//! When switching, the new source's metadata and sequence headers are output right before its
+//! keyframe, preceded by a discontinuity notification so later steps know the media may not be
+//! continuous (e.g. timestamps will jump).  Until the source being switched to sends a keyframe,
+//! the previous source keeps being output if it's still connected.
+//!
+//! The output stream is announced when either source first connects, and is disconnected once
+//! both sources have disconnected.  The primary and backup streams themselves are not passed on
+//! to later steps, while all other streams are passed through unchanged.
+
+#[cfg(test)]
+mod tests;
+
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
+use std::collections::HashMap;
+use thiserror::Error;
+use tracing::info;
+use uuid::Uuid;
+
+const PRIMARY: &str = "primary";
+const BACKUP: &str = "backup";
+const STREAM_NAME: &str = "stream_name";
+
+/// Generates new instances of the failover workflow step based on specified step definitions.
+pub struct FailoverStepGenerator {}
+
+struct FailoverStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    primary_name: String,
+    backup_name: String,
+    output_name: String,
+    primary: SourceState,
+    backup: SourceState,
+    source_by_stream_id: HashMap<StreamId, Source>,
+    output: Option<OutputStream>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Source {
+    Primary,
+    Backup,
+}
+
+/// The latest stream details for a single source, which are needed to make the output decodable
+/// when switching to this source
+#[derive(Default)]
+struct SourceState {
+    stream_id: Option<StreamId>,
+    metadata: Option<MediaNotificationContent>,
+    video_sequence_header: Option<MediaNotificationContent>,
+    audio_sequence_header: Option<MediaNotificationContent>,
+}
+
+struct OutputStream {
+    stream_id: StreamId,
+
+    /// The source whose media is currently being output.  `None` until a source sends a keyframe
+    /// we can start the output from.
+    active_source: Option<Source>,
+
+    /// If any source's media has been output yet.  Used to know if switching sources should
+    /// raise a discontinuity.
+    has_output_media: bool,
+}
+
+struct StepParameters {
+    primary_name: String,
+    backup_name: String,
+    output_name: String,
+}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error("No primary stream name specified.  A 'primary' parameter is required")]
+    NoPrimaryStreamName,
+
+    #[error("No backup stream name specified.  A 'backup' parameter is required")]
+    NoBackupStreamName,
+
+    #[error("The primary and backup stream names must be different")]
+    SameStreamNames,
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl FailoverStepGenerator {
+    pub fn new() -> Self {
+        FailoverStepGenerator {}
+    }
+}
+
+impl StepGenerator for FailoverStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let parameters = parse_parameters(&definition)?;
+        let step = FailoverStep {
+            definition: definition.clone(),
+            status: StepStatus::Active,
+            primary_name: parameters.primary_name,
+            backup_name: parameters.backup_name,
+            output_name: parameters.output_name,
+            primary: SourceState::default(),
+            backup: SourceState::default(),
+            source_by_stream_id: HashMap::new(),
+            output: None,
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_parameters(definition)?;
+        Ok(())
+    }
+}
+
+fn parse_parameters(
+    definition: &WorkflowStepDefinition,
+) -> Result<StepParameters, StepStartupError> {
+    let get_parameter = |name: &str| match definition.parameters.get(name) {
+        Some(Some(value)) => Some(value.to_string()),
+        _ => None,
+    };
+
+    let primary_name = match get_parameter(PRIMARY) {
+        Some(name) => name,
+        None => return Err(StepStartupError::NoPrimaryStreamName),
+    };
+
+    let backup_name = match get_parameter(BACKUP) {
+        Some(name) => name,
+        None => return Err(StepStartupError::NoBackupStreamName),
+    };
+
+    if primary_name == backup_name {
+        return Err(StepStartupError::SameStreamNames);
+    }
+
+    let output_name = get_parameter(STREAM_NAME).unwrap_or_else(|| primary_name.clone());
+
+    Ok(StepParameters {
+        primary_name,
+        backup_name,
+        output_name,
+    })
+}
+
+impl FailoverStep {
+    fn source_state(&mut self, source: Source) -> &mut SourceState {
+        match source {
+            Source::Primary => &mut self.primary,
+            Source::Backup => &mut self.backup,
+        }
+    }
+
+    /// The source that should be output, based on which sources are currently connected
+    fn preferred_source(&self) -> Option<Source> {
+        if self.primary.stream_id.is_some() {
+            Some(Source::Primary)
+        } else if self.backup.stream_id.is_some() {
+            Some(Source::Backup)
+        } else {
+            None
+        }
+    }
+
+    fn get_source_for_name(&self, stream_name: &str) -> Option<Source> {
+        if stream_name == self.primary_name {
+            Some(Source::Primary)
+        } else if stream_name == self.backup_name {
+            Some(Source::Backup)
+        } else {
+            None
+        }
+    }
+
+    fn handle_new_source_stream(
+        &mut self,
+        source: Source,
+        media: MediaNotification,
+        outputs: &mut StepOutputs,
+    ) {
+        info!(
+            stream_id = ?media.stream_id,
+            "Stream {:?} connected as the {:?} source", media.stream_id, source
+        );
+
+        // A new stream for a source that's already connected replaces it
+        let state = self.source_state(source);
+        let previous_stream_id = state.stream_id.replace(media.stream_id.clone());
+        state.metadata = None;
+        state.video_sequence_header = None;
+        state.audio_sequence_header = None;
+
+        if let Some(previous_stream_id) = previous_stream_id {
+            self.source_by_stream_id.remove(&previous_stream_id);
+            if let Some(output) = &mut self.output {
+                if output.active_source == Some(source) {
+                    output.active_source = None;
+                }
+            }
+        }
+
+        self.source_by_stream_id
+            .insert(media.stream_id.clone(), source);
+
+        if self.output.is_none() {
+            let output = OutputStream {
+                stream_id: StreamId(Uuid::new_v4().to_string()),
+                active_source: None,
+                has_output_media: false,
+            };
+
+            info!(
+                stream_id = ?output.stream_id,
+                "Starting failover output stream {:?} named '{}'", output.stream_id, self.output_name
+            );
+
+            outputs.media.push(MediaNotification {
+                stream_id: output.stream_id.clone(),
+                content: MediaNotificationContent::NewIncomingStream {
+                    stream_name: self.output_name.clone(),
+                },
+                tags: media.tags,
+            });
+
+            self.output = Some(output);
+        }
+    }
+
+    fn handle_disconnection(&mut self, media: MediaNotification, outputs: &mut StepOutputs) {
+        let source = match self.source_by_stream_id.remove(&media.stream_id) {
+            Some(source) => source,
+            None => {
+                outputs.media.push(media);
+                return;
+            }
+        };
+
+        info!(
+            stream_id = ?media.stream_id,
+            "The {:?} source stream {:?} disconnected", source, media.stream_id
+        );
+
+        *self.source_state(source) = SourceState::default();
+
+        let both_disconnected = self.preferred_source().is_none();
+        let output = match &mut self.output {
+            Some(output) => output,
+            None => return,
+        };
+
+        if output.active_source == Some(source) {
+            output.active_source = None;
+        }
+
+        if both_disconnected {
+            info!(
+                stream_id = ?output.stream_id,
+                "All sources disconnected, ending failover output stream {:?}", output.stream_id
+            );
+
+            outputs.media.push(MediaNotification {
+                stream_id: output.stream_id.clone(),
+                content: MediaNotificationContent::StreamDisconnected,
+                tags: media.tags,
+            });
+
+            self.output = None;
+        }
+    }
+
+    fn handle_source_media(
+        &mut self,
+        source: Source,
+        media: MediaNotification,
+        outputs: &mut StepOutputs,
+    ) {
+        let is_switch_point = match &media.content {
+            MediaNotificationContent::Metadata { .. } => {
+                self.source_state(source).metadata = Some(media.content.clone());
+                false
+            }
+
+            MediaNotificationContent::Video {
+                is_sequence_header: true,
+                ..
+            } => {
+                self.source_state(source).video_sequence_header = Some(media.content.clone());
+                false
+            }
+
+            MediaNotificationContent::Audio {
+                is_sequence_header: true,
+                ..
+            } => {
+                self.source_state(source).audio_sequence_header = Some(media.content.clone());
+                false
+            }
+
+            MediaNotificationContent::Video { is_keyframe, .. } => *is_keyframe,
+            _ => false,
+        };
+
+        let preferred_source = self.preferred_source();
+        let output = match &mut self.output {
+            Some(output) => output,
+            None => return,
+        };
+
+        if output.active_source != Some(source) {
+            if !is_switch_point || preferred_source != Some(source) {
+                return;
+            }
+
+            info!(
+                stream_id = ?output.stream_id,
+                "Switching failover output stream {:?} to the {:?} source", output.stream_id, source
+            );
+
+            let stream_id = output.stream_id.clone();
+            let raise_discontinuity = output.has_output_media;
+            output.active_source = Some(source);
+            output.has_output_media = true;
+
+            if raise_discontinuity {
+                outputs.media.push(MediaNotification {
+                    stream_id: stream_id.clone(),
+                    content: MediaNotificationContent::Discontinuity,
+                    tags: media.tags.clone(),
+                });
+            }
+
+            let state = self.source_state(source);
+            let stream_details = [
+                &state.metadata,
+                &state.video_sequence_header,
+                &state.audio_sequence_header,
+            ];
+
+            for content in stream_details.iter().filter_map(|content| content.as_ref()) {
+                outputs.media.push(MediaNotification {
+                    stream_id: stream_id.clone(),
+                    content: content.clone(),
+                    tags: media.tags.clone(),
+                });
+            }
+
+            outputs.media.push(MediaNotification {
+                stream_id,
+                content: media.content,
+                tags: media.tags,
+            });
+
+            return;
+        }
+
+        outputs.media.push(MediaNotification {
+            stream_id: output.stream_id.clone(),
+            content: media.content,
+            tags: media.tags,
+        });
+    }
+}
+
+impl WorkflowStep for FailoverStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for media in inputs.media.drain(..) {
+            match &media.content {
+                MediaNotificationContent::NewIncomingStream { stream_name } => {
+                    match self.get_source_for_name(stream_name) {
+                        Some(source) => self.handle_new_source_stream(source, media, outputs),
+                        None => outputs.media.push(media),
+                    }
+                }
+
+                MediaNotificationContent::StreamDisconnected => {
+                    self.handle_disconnection(media, outputs);
+                }
+
+                MediaNotificationContent::Video { .. }
+                | MediaNotificationContent::Audio { .. }
+                | MediaNotificationContent::Metadata { .. }
+                | MediaNotificationContent::Discontinuity
+                | MediaNotificationContent::Cue { .. } => {
+                    match self.source_by_stream_id.get(&media.stream_id) {
+                        Some(&source) => self.handle_source_media(source, media, outputs),
+                        None => outputs.media.push(media),
+                    }
+                }
+            }
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+    }
+}
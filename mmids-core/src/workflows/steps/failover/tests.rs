@@ -0,0 +1,308 @@
+use super::*;
+use crate::codecs::{AudioCodec, VideoCodec};
+use crate::workflows::steps::test_utils::create_definition;
+use crate::workflows::steps::StepTestContext;
+use crate::VideoTimestamp;
+use bytes::Bytes;
+use std::time::Duration;
+
+fn create_context() -> StepTestContext {
+    let generator = FailoverStepGenerator::new();
+    let definition = create_definition(
+        "failover",
+        &[(PRIMARY, "main"), (BACKUP, "spare"), (STREAM_NAME, "out")],
+    );
+
+    StepTestContext::new(Box::new(generator), definition).unwrap()
+}
+
+fn new_stream(stream_id: &str, stream_name: &str) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: stream_name.to_string(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn disconnection(stream_id: &str) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
+    }
+}
+
+fn video(
+    stream_id: &str,
+    data: u8,
+    is_keyframe: bool,
+    is_sequence_header: bool,
+) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            is_keyframe,
+            is_sequence_header,
+            data: Bytes::from(vec![data]),
+            timestamp: VideoTimestamp::from_zero(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn audio(stream_id: &str, data: u8, is_sequence_header: bool) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::Audio {
+            codec: AudioCodec::Aac,
+            is_sequence_header,
+            data: Bytes::from(vec![data]),
+            timestamp: Duration::from_millis(0),
+        },
+        tags: Vec::new(),
+    }
+}
+
+/// Starts the primary stream and returns the output stream's id
+fn start_primary(context: &mut StepTestContext) -> StreamId {
+    context.execute_with_media(new_stream("1", "main"));
+    assert_eq!(context.media_outputs.len(), 1, "Expected one media output");
+
+    let output = &context.media_outputs[0];
+    match &output.content {
+        MediaNotificationContent::NewIncomingStream { stream_name } => {
+            assert_eq!(stream_name, "out", "Unexpected stream name");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+
+    output.stream_id.clone()
+}
+
+fn assert_video(media: &MediaNotification, stream_id: &StreamId, expected_data: u8) {
+    assert_eq!(&media.stream_id, stream_id, "Unexpected stream id");
+    match &media.content {
+        MediaNotificationContent::Video { data, .. } => {
+            assert_eq!(data, &vec![expected_data], "Unexpected video data");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+fn assert_audio(media: &MediaNotification, stream_id: &StreamId, expected_data: u8) {
+    assert_eq!(&media.stream_id, stream_id, "Unexpected stream id");
+    match &media.content {
+        MediaNotificationContent::Audio { data, .. } => {
+            assert_eq!(data, &vec![expected_data], "Unexpected audio data");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[test]
+fn step_fails_to_generate_without_primary() {
+    let generator = FailoverStepGenerator::new();
+    let definition = create_definition("failover", &[(BACKUP, "spare")]);
+    assert!(generator.generate(definition).is_err());
+}
+
+#[test]
+fn step_fails_to_generate_without_backup() {
+    let generator = FailoverStepGenerator::new();
+    let definition = create_definition("failover", &[(PRIMARY, "main")]);
+    assert!(generator.generate(definition).is_err());
+}
+
+#[test]
+fn step_fails_to_generate_with_same_primary_and_backup() {
+    let generator = FailoverStepGenerator::new();
+    let definition = create_definition("failover", &[(PRIMARY, "main"), (BACKUP, "main")]);
+    assert!(generator.generate(definition).is_err());
+}
+
+#[test]
+fn validation_fails_without_backup() {
+    let generator = FailoverStepGenerator::new();
+    let definition = create_definition("failover", &[(PRIMARY, "main")]);
+    assert!(generator.validate(&definition).is_err());
+}
+
+#[test]
+fn validation_passes_with_primary_and_backup() {
+    let generator = FailoverStepGenerator::new();
+    let definition = create_definition("failover", &[(PRIMARY, "main"), (BACKUP, "spare")]);
+    assert!(generator.validate(&definition).is_ok());
+}
+
+#[test]
+fn output_stream_named_after_primary_when_no_stream_name_given() {
+    let generator = FailoverStepGenerator::new();
+    let definition = create_definition("failover", &[(PRIMARY, "main"), (BACKUP, "spare")]);
+    let mut context = StepTestContext::new(Box::new(generator), definition).unwrap();
+    context.execute_with_media(new_stream("1", "main"));
+
+    assert_eq!(context.media_outputs.len(), 1, "Expected one media output");
+    match &context.media_outputs[0].content {
+        MediaNotificationContent::NewIncomingStream { stream_name } => {
+            assert_eq!(stream_name, "main", "Unexpected stream name");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[test]
+fn unrelated_streams_passed_through() {
+    let mut context = create_context();
+    context.assert_media_passed_through(new_stream("9", "other"));
+    context.assert_media_passed_through(video("9", 1, true, false));
+    context.assert_media_passed_through(disconnection("9"));
+}
+
+#[test]
+fn output_does_not_start_until_keyframe() {
+    let mut context = create_context();
+    start_primary(&mut context);
+
+    context.assert_media_not_passed_through(video("1", 1, false, true));
+    context.assert_media_not_passed_through(video("1", 2, false, false));
+    context.assert_media_not_passed_through(audio("1", 3, false));
+}
+
+#[test]
+fn sequence_headers_output_before_first_keyframe() {
+    let mut context = create_context();
+    let output_id = start_primary(&mut context);
+    context.execute_with_media(video("1", 1, false, true));
+    context.execute_with_media(audio("1", 2, true));
+
+    context.execute_with_media(video("1", 3, true, false));
+    assert_eq!(
+        context.media_outputs.len(),
+        3,
+        "Unexpected number of outputs"
+    );
+    assert_video(&context.media_outputs[0], &output_id, 1);
+    assert_audio(&context.media_outputs[1], &output_id, 2);
+    assert_video(&context.media_outputs[2], &output_id, 3);
+
+    context.execute_with_media(audio("1", 4, false));
+    assert_eq!(
+        context.media_outputs.len(),
+        1,
+        "Unexpected number of outputs"
+    );
+    assert_audio(&context.media_outputs[0], &output_id, 4);
+}
+
+#[test]
+fn backup_not_output_while_primary_active() {
+    let mut context = create_context();
+    let output_id = start_primary(&mut context);
+    context.execute_with_media(video("1", 1, true, false));
+
+    context.assert_media_not_passed_through(new_stream("2", "spare"));
+    context.assert_media_not_passed_through(video("2", 2, true, false));
+
+    context.execute_with_media(video("1", 3, false, false));
+    assert_eq!(
+        context.media_outputs.len(),
+        1,
+        "Unexpected number of outputs"
+    );
+    assert_video(&context.media_outputs[0], &output_id, 3);
+}
+
+#[test]
+fn switches_to_backup_on_keyframe_after_primary_disconnects() {
+    let mut context = create_context();
+    let output_id = start_primary(&mut context);
+    context.execute_with_media(video("1", 1, true, false));
+    context.execute_with_media(new_stream("2", "spare"));
+    context.execute_with_media(video("2", 2, false, true));
+
+    context.assert_media_not_passed_through(disconnection("1"));
+    context.assert_media_not_passed_through(video("2", 3, false, false));
+
+    context.execute_with_media(video("2", 4, true, false));
+    assert_eq!(
+        context.media_outputs.len(),
+        3,
+        "Unexpected number of outputs"
+    );
+    match &context.media_outputs[0].content {
+        MediaNotificationContent::Discontinuity => (),
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+
+    assert_video(&context.media_outputs[1], &output_id, 2);
+    assert_video(&context.media_outputs[2], &output_id, 4);
+}
+
+#[test]
+fn switches_back_to_primary_on_keyframe_after_reconnect() {
+    let mut context = create_context();
+    let output_id = start_primary(&mut context);
+    context.execute_with_media(new_stream("2", "spare"));
+    context.execute_with_media(disconnection("1"));
+    context.execute_with_media(video("2", 1, true, false));
+
+    context.assert_media_not_passed_through(new_stream("3", "main"));
+
+    // Backup keeps being output until the primary sends a keyframe
+    context.execute_with_media(video("3", 2, false, true));
+    context.execute_with_media(video("2", 3, false, false));
+    assert_eq!(
+        context.media_outputs.len(),
+        1,
+        "Unexpected number of outputs"
+    );
+    assert_video(&context.media_outputs[0], &output_id, 3);
+
+    context.execute_with_media(video("3", 4, true, false));
+    assert_eq!(
+        context.media_outputs.len(),
+        3,
+        "Unexpected number of outputs"
+    );
+    match &context.media_outputs[0].content {
+        MediaNotificationContent::Discontinuity => (),
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+
+    assert_video(&context.media_outputs[1], &output_id, 2);
+    assert_video(&context.media_outputs[2], &output_id, 4);
+
+    context.assert_media_not_passed_through(video("2", 5, true, false));
+}
+
+#[test]
+fn output_disconnected_once_both_sources_disconnect() {
+    let mut context = create_context();
+    let output_id = start_primary(&mut context);
+    context.execute_with_media(new_stream("2", "spare"));
+
+    context.assert_media_not_passed_through(disconnection("1"));
+
+    context.execute_with_media(disconnection("2"));
+    assert_eq!(
+        context.media_outputs.len(),
+        1,
+        "Unexpected number of outputs"
+    );
+    assert_eq!(
+        context.media_outputs[0].stream_id, output_id,
+        "Unexpected stream id"
+    );
+
+    match &context.media_outputs[0].content {
+        MediaNotificationContent::StreamDisconnected => (),
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
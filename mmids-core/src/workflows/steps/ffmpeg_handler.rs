@@ -1,9 +1,10 @@
+use crate::codecs::{AudioCodec, VideoCodec};
 use crate::endpoints::ffmpeg::{FfmpegEndpointNotification, FfmpegEndpointRequest, FfmpegParams};
 use crate::workflows::steps::external_stream_handler::{
     ExternalStreamHandler, ExternalStreamHandlerGenerator, ResolvedFutureStatus,
     StreamHandlerFutureResult, StreamHandlerFutureWrapper,
 };
-use crate::workflows::steps::{StepFutureResult, StepOutputs};
+use crate::workflows::steps::{StepFutureResult, StepOutputs, SupportedCodecs};
 use crate::StreamId;
 use futures::FutureExt;
 use std::sync::Arc;
@@ -11,6 +12,13 @@ use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tracing::{error, info, instrument, warn};
 use uuid::Uuid;
 
+/// The codecs steps that send media to ffmpeg support.  Media is passed to ffmpeg over RTMP, so
+/// only codecs that can be packaged into RTMP can be sent to it.
+pub const FFMPEG_SUPPORTED_CODECS: SupportedCodecs = SupportedCodecs {
+    video: Some(&[VideoCodec::H264]),
+    audio: Some(&[AudioCodec::Aac]),
+};
+
 pub struct FfmpegHandler {
     ffmpeg_endpoint: UnboundedSender<FfmpegEndpointRequest>,
     status: FfmpegHandlerStatus,
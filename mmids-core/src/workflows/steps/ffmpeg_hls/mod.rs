@@ -2,28 +2,47 @@
 //!
 //! Media packets that are received from previous steps are passed to the RTMP endpoint for ffmpeg
 //! consumption, and then passed on to the next step as-is.
+//!
+//! When a maximum segment count is specified, segments that roll off the playlist are deleted from
+//! disk.  Once a stream disconnects its playlist is finalized with an `#EXT-X-ENDLIST` tag, so
+//! players know no more segments will be added.
+
+#[cfg(test)]
+mod tests;
 
 use crate::endpoints::ffmpeg::{
     AudioTranscodeParams, FfmpegEndpointRequest, FfmpegParams, TargetParams, VideoTranscodeParams,
 };
 use crate::endpoints::rtmp_server::RtmpEndpointRequest;
 use crate::workflows::definitions::WorkflowStepDefinition;
-use crate::workflows::steps::factory::StepGenerator;
-use crate::workflows::steps::ffmpeg_handler::{FfmpegHandlerGenerator, FfmpegParameterGenerator};
+use crate::workflows::steps::factory::{StepGenerator, StepKind};
+use crate::workflows::steps::ffmpeg_handler::{
+    FfmpegHandlerGenerator, FfmpegParameterGenerator, FFMPEG_SUPPORTED_CODECS,
+};
 use crate::workflows::steps::{
-    ExternalStreamReader, StepCreationResult, StepFutureResult, StepInputs, StepOutputs,
-    StepStatus, WorkflowStep,
+    ExternalStreamReader, StepCreationError, StepCreationResult, StepFutureResult, StepInputs,
+    StepOutputs, StepStatus, StepValidationResult, SupportedCodecs, WorkflowStep,
 };
+use crate::workflows::MediaNotificationContent;
 use crate::StreamId;
 use futures::FutureExt;
+use std::collections::HashMap;
+use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::mpsc::UnboundedSender;
-use tracing::error;
+use tokio::sync::oneshot;
+use tracing::{error, info};
 
 const PATH: &str = "path";
 const SEGMENT_DURATION: &str = "duration";
 const SEGMENT_COUNT: &str = "count";
 const STREAM_NAME: &str = "stream_name";
+const END_LIST_TAG: &str = "#EXT-X-ENDLIST";
+
+/// How long to wait after a stream disconnects before finalizing its playlist, to give ffmpeg a
+/// chance to finish writing it.  If a stream using the same playlist connects during this delay
+/// the finalization is cancelled, as ffmpeg will be writing to the playlist again.
+const PLAYLIST_FINALIZE_DELAY: Duration = Duration::from_secs(1);
 
 /// Generates new instances of the ffmpeg HLS workflow step based on specified step definitions.
 pub struct FfmpegHlsStepGenerator {
@@ -36,15 +55,36 @@ struct FfmpegHlsStep {
     status: StepStatus,
     stream_reader: ExternalStreamReader,
     path: String,
+    stream_name: Option<String>,
+    playlist_paths: HashMap<StreamId, String>,
+
+    /// Playlists waiting to be finalized, keyed by their path.  Dropping the sender cancels the
+    /// finalization.
+    pending_finalizations: HashMap<String, oneshot::Sender<()>>,
 }
 
 enum FutureResult {
     FfmpegEndpointGone,
     HlsPathCreated(tokio::io::Result<()>),
+    PlaylistFinalized {
+        playlist_path: String,
+        result: tokio::io::Result<()>,
+    },
+
+    PlaylistFinalizationCancelled {
+        playlist_path: String,
+    },
 }
 
 impl StepFutureResult for FutureResult {}
 
+struct StepParameters {
+    path: String,
+    duration: u16,
+    count: u16,
+    stream_name: Option<String>,
+}
+
 #[derive(Error, Debug)]
 enum StepStartupError {
     #[error("No path specified.  A 'path' is required")]
@@ -60,6 +100,12 @@ enum StepStartupError {
     InvalidSegmentCount(String),
 }
 
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
 struct ParamGenerator {
     rtmp_app: String,
     path: String,
@@ -82,45 +128,19 @@ impl FfmpegHlsStepGenerator {
 
 impl StepGenerator for FfmpegHlsStepGenerator {
     fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
-        let path = match definition.parameters.get(PATH) {
-            Some(Some(value)) => value,
-            _ => return Err(Box::new(StepStartupError::NoPathProvided)),
-        };
-
-        let duration = match definition.parameters.get(SEGMENT_DURATION) {
-            Some(Some(value)) => match value.parse() {
-                Ok(num) => num,
-                Err(_) => {
-                    return Err(Box::new(StepStartupError::InvalidSegmentLength(
-                        value.clone(),
-                    )));
-                }
-            },
-
-            _ => 2,
-        };
-
-        let count = match definition.parameters.get(SEGMENT_COUNT) {
-            Some(Some(value)) => match value.parse::<u16>() {
-                Ok(num) => num,
-                Err(_) => {
-                    return Err(Box::new(StepStartupError::InvalidSegmentCount(
-                        value.clone(),
-                    )));
-                }
-            },
-
-            _ => 0,
-        };
-
-        let stream_name = definition.parameters.get(STREAM_NAME).cloned().flatten();
+        let StepParameters {
+            path,
+            duration,
+            count,
+            stream_name,
+        } = parse_parameters(&definition)?;
 
         let param_generator = ParamGenerator {
             rtmp_app: get_rtmp_app(definition.get_id().to_string()),
             path: path.clone(),
             segment_duration: duration,
             segment_count: count,
-            stream_name,
+            stream_name: stream_name.clone(),
         };
 
         let handler_generator =
@@ -137,6 +157,9 @@ impl StepGenerator for FfmpegHlsStepGenerator {
             status: StepStatus::Created,
             stream_reader: reader,
             path: path.clone(),
+            stream_name,
+            playlist_paths: HashMap::new(),
+            pending_finalizations: HashMap::new(),
         };
 
         futures.push(notify_when_ffmpeg_endpoint_is_gone(self.ffmpeg_endpoint.clone()).boxed());
@@ -144,6 +167,55 @@ impl StepGenerator for FfmpegHlsStepGenerator {
 
         Ok((Box::new(step), futures))
     }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_parameters(definition)?;
+        Ok(())
+    }
+
+    fn kind(&self) -> StepKind {
+        StepKind::Sink
+    }
+}
+
+fn parse_parameters(
+    definition: &WorkflowStepDefinition,
+) -> Result<StepParameters, StepStartupError> {
+    let path = match definition.parameters.get(PATH) {
+        Some(Some(value)) => value.clone(),
+        _ => return Err(StepStartupError::NoPathProvided),
+    };
+
+    let duration = match definition.parameters.get(SEGMENT_DURATION) {
+        Some(Some(value)) => match value.parse() {
+            Ok(num) => num,
+            Err(_) => {
+                return Err(StepStartupError::InvalidSegmentLength(value.clone()));
+            }
+        },
+
+        _ => 2,
+    };
+
+    let count = match definition.parameters.get(SEGMENT_COUNT) {
+        Some(Some(value)) => match value.parse::<u16>() {
+            Ok(num) => num,
+            Err(_) => {
+                return Err(StepStartupError::InvalidSegmentCount(value.clone()));
+            }
+        },
+
+        _ => 0,
+    };
+
+    let stream_name = definition.parameters.get(STREAM_NAME).cloned().flatten();
+
+    Ok(StepParameters {
+        path,
+        duration,
+        count,
+        stream_name,
+    })
 }
 
 impl WorkflowStep for FfmpegHlsStep {
@@ -195,15 +267,74 @@ impl WorkflowStep for FfmpegHlsStep {
                             return;
                         }
                     },
+
+                    FutureResult::PlaylistFinalized {
+                        playlist_path,
+                        result,
+                    } => {
+                        // Only the entry whose future just completed has a closed receiver, so a
+                        // newer finalization for the same path is kept
+                        self.pending_finalizations
+                            .retain(|_, cancellation| !cancellation.is_closed());
+
+                        match result {
+                            Ok(()) => info!("Playlist '{}' finalized", playlist_path),
+                            Err(error) => {
+                                error!(
+                                    "Failed to finalize playlist '{}': {:?}",
+                                    playlist_path, error
+                                );
+                            }
+                        }
+                    }
+
+                    FutureResult::PlaylistFinalizationCancelled { playlist_path } => {
+                        info!(
+                            "Finalization of playlist '{}' cancelled, as a new stream is using it",
+                            playlist_path
+                        );
+                    }
                 },
             };
         }
 
         for media in inputs.media.drain(..) {
+            match &media.content {
+                MediaNotificationContent::NewIncomingStream { stream_name } => {
+                    let playlist_path = get_playlist_path(
+                        &self.path,
+                        self.stream_name.as_deref().unwrap_or(stream_name),
+                    );
+
+                    // ffmpeg is about to write to this playlist again, so it must not be ended
+                    self.pending_finalizations.remove(&playlist_path);
+                    self.playlist_paths
+                        .insert(media.stream_id.clone(), playlist_path);
+                }
+
+                MediaNotificationContent::StreamDisconnected => {
+                    if let Some(playlist_path) = self.playlist_paths.remove(&media.stream_id) {
+                        let (sender, receiver) = oneshot::channel();
+                        self.pending_finalizations
+                            .insert(playlist_path.clone(), sender);
+
+                        outputs
+                            .futures
+                            .push(finalize_playlist(playlist_path, receiver).boxed());
+                    }
+                }
+
+                _ => (),
+            }
+
             self.stream_reader.handle_media(media, outputs);
         }
     }
 
+    fn get_supported_codecs(&self) -> SupportedCodecs {
+        FFMPEG_SUPPORTED_CODECS
+    }
+
     fn shutdown(&mut self) {
         self.stream_reader.stop_all_streams();
         self.status = StepStatus::Shutdown;
@@ -220,13 +351,13 @@ impl FfmpegParameterGenerator for ParamGenerator {
             scale: None,
             bitrate_in_kbps: None,
             target: TargetParams::Hls {
-                path: format!(
-                    "{}/{}.m3u8",
-                    self.path,
-                    self.stream_name.as_deref().unwrap_or(stream_name)
+                path: get_playlist_path(
+                    &self.path,
+                    self.stream_name.as_deref().unwrap_or(stream_name),
                 ),
                 max_entries: Some(self.segment_count),
                 segment_length: self.segment_duration,
+                delete_old_segments: self.segment_count > 0,
             },
         }
     }
@@ -236,6 +367,12 @@ fn get_rtmp_app(id: String) -> String {
     format!("ffmpeg-hls-{}", id)
 }
 
+fn get_playlist_path(path: &str, stream_name: &str) -> String {
+    // Stream names come from clients, so don't allow them to escape the output directory
+    let file_name = stream_name.replace(|c: char| c == '/' || c == '\\' || c == '.', "_");
+    format!("{}/{}.m3u8", path, file_name)
+}
+
 async fn notify_when_ffmpeg_endpoint_is_gone(
     endpoint: UnboundedSender<FfmpegEndpointRequest>,
 ) -> Box<dyn StepFutureResult> {
@@ -248,3 +385,39 @@ async fn notify_when_path_created(path: String) -> Box<dyn StepFutureResult> {
     let result = tokio::fs::create_dir_all(&path).await;
     Box::new(FutureResult::HlsPathCreated(result))
 }
+
+async fn finalize_playlist(
+    playlist_path: String,
+    cancellation: oneshot::Receiver<()>,
+) -> Box<dyn StepFutureResult> {
+    tokio::select! {
+        _ = tokio::time::sleep(PLAYLIST_FINALIZE_DELAY) => (),
+        _ = cancellation => {
+            return Box::new(FutureResult::PlaylistFinalizationCancelled { playlist_path });
+        }
+    }
+
+    let result = append_end_list_tag(&playlist_path).await;
+
+    Box::new(FutureResult::PlaylistFinalized {
+        playlist_path,
+        result,
+    })
+}
+
+async fn append_end_list_tag(playlist_path: &str) -> tokio::io::Result<()> {
+    let content = tokio::fs::read_to_string(playlist_path).await?;
+    if content.trim_end().ends_with(END_LIST_TAG) {
+        // ffmpeg managed to finalize the playlist itself
+        return Ok(());
+    }
+
+    let mut content = content;
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+
+    content.push_str(END_LIST_TAG);
+    content.push('\n');
+    tokio::fs::write(playlist_path, content).await
+}
@@ -0,0 +1,24 @@
+use super::*;
+
+#[test]
+fn playlist_path_uses_stream_name() {
+    let path = get_playlist_path("/hls", "abc");
+
+    assert_eq!(path, "/hls/abc.m3u8", "Unexpected playlist path");
+}
+
+#[test]
+fn path_separators_in_stream_name_do_not_escape_playlist_directory() {
+    // Stream names include the rtmp app when receive steps match any app
+    assert_eq!(
+        get_playlist_path("/hls", "app/key"),
+        "/hls/app_key.m3u8",
+        "Unexpected playlist path"
+    );
+
+    assert_eq!(
+        get_playlist_path("/hls", "../../etc/key"),
+        "/hls/______etc_key.m3u8",
+        "Unexpected playlist path"
+    );
+}
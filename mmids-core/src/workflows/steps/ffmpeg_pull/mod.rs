@@ -10,13 +10,14 @@ use crate::endpoints::ffmpeg::{
     TargetParams, VideoTranscodeParams,
 };
 use crate::endpoints::rtmp_server::{
-    IpRestriction, RegistrationType, RtmpEndpointPublisherMessage, RtmpEndpointRequest,
-    StreamKeyRegistration,
+    IpRestriction, RegistrationType, RtmpAppMatching, RtmpEndpointPublisherMessage,
+    RtmpEndpointRequest, StreamKeyRegistration,
 };
 use crate::workflows::definitions::WorkflowStepDefinition;
-use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::factory::{StepGenerator, StepKind};
 use crate::workflows::steps::{
-    StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus, WorkflowStep,
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
 };
 use crate::workflows::{MediaNotification, MediaNotificationContent};
 use crate::{StreamId, VideoTimestamp};
@@ -63,6 +64,11 @@ enum FutureResult {
 
 impl StepFutureResult for FutureResult {}
 
+struct StepParameters {
+    location: String,
+    stream_name: String,
+}
+
 #[derive(Error, Debug)]
 enum StepStartupError {
     #[error("No {} parameter specified", LOCATION)]
@@ -72,6 +78,12 @@ enum StepStartupError {
     NoStreamNameSpecified,
 }
 
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
 impl FfmpegPullStepGenerator {
     pub fn new(
         rtmp_endpoint: UnboundedSender<RtmpEndpointRequest>,
@@ -86,15 +98,10 @@ impl FfmpegPullStepGenerator {
 
 impl StepGenerator for FfmpegPullStepGenerator {
     fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
-        let location = match definition.parameters.get(LOCATION) {
-            Some(Some(value)) => value.clone(),
-            _ => return Err(Box::new(StepStartupError::NoLocationSpecified)),
-        };
-
-        let stream_name = match definition.parameters.get(STREAM_NAME) {
-            Some(Some(value)) => value.clone(),
-            _ => return Err(Box::new(StepStartupError::NoStreamNameSpecified)),
-        };
+        let StepParameters {
+            location,
+            stream_name,
+        } = parse_parameters(&definition)?;
 
         let step = FfmpegPullStep {
             definition: definition.clone(),
@@ -114,12 +121,14 @@ impl StepGenerator for FfmpegPullStepGenerator {
             .send(RtmpEndpointRequest::ListenForPublishers {
                 port: 1935,
                 rtmp_app: step.rtmp_app.clone(),
+                rtmp_app_matching: RtmpAppMatching::Exact,
                 rtmp_stream_key: StreamKeyRegistration::Exact(stream_name),
                 stream_id: None,
                 message_channel: sender,
                 ip_restrictions: IpRestriction::None,
                 use_tls: false,
                 requires_registrant_approval: false,
+                max_connects_per_minute: None,
             });
 
         let futures = vec![
@@ -130,6 +139,34 @@ impl StepGenerator for FfmpegPullStepGenerator {
 
         Ok((Box::new(step), futures))
     }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_parameters(definition)?;
+        Ok(())
+    }
+
+    fn kind(&self) -> StepKind {
+        StepKind::Source
+    }
+}
+
+fn parse_parameters(
+    definition: &WorkflowStepDefinition,
+) -> Result<StepParameters, StepStartupError> {
+    let location = match definition.parameters.get(LOCATION) {
+        Some(Some(value)) => value.clone(),
+        _ => return Err(StepStartupError::NoLocationSpecified),
+    };
+
+    let stream_name = match definition.parameters.get(STREAM_NAME) {
+        Some(Some(value)) => value.clone(),
+        _ => return Err(StepStartupError::NoStreamNameSpecified),
+    };
+
+    Ok(StepParameters {
+        location,
+        stream_name,
+    })
 }
 
 impl FfmpegPullStep {
@@ -213,8 +250,10 @@ impl FfmpegPullStep {
 
             RtmpEndpointPublisherMessage::NewPublisherConnected {
                 stream_id,
+                rtmp_app: _,
                 stream_key,
                 connection_id,
+                remote_address: _,
                 reactor_update_channel: _,
             } => {
                 info!(
@@ -247,6 +286,7 @@ impl FfmpegPullStep {
                     content: MediaNotificationContent::NewIncomingStream {
                         stream_name: self.stream_name.clone(),
                     },
+                    tags: Vec::new(),
                 });
             }
 
@@ -256,6 +296,7 @@ impl FfmpegPullStep {
                     outputs.media.push(MediaNotification {
                         stream_id: stream_id.clone(),
                         content: MediaNotificationContent::StreamDisconnected,
+                        tags: Vec::new(),
                     });
                 }
             }
@@ -270,6 +311,7 @@ impl FfmpegPullStep {
                         content: MediaNotificationContent::Metadata {
                             data: crate::utils::stream_metadata_to_hash_map(metadata),
                         },
+                        tags: Vec::new(),
                     });
                 } else {
                     error!("Received stream metadata without an active stream id");
@@ -302,6 +344,7 @@ impl FfmpegPullStep {
                             is_sequence_header,
                             data,
                         },
+                        tags: Vec::new(),
                     });
                 } else {
                     error!("Received video data without an active stream id");
@@ -328,6 +371,7 @@ impl FfmpegPullStep {
                             is_sequence_header,
                             data,
                         },
+                        tags: Vec::new(),
                     });
                 } else {
                     error!("Received audio data without an active stream id");
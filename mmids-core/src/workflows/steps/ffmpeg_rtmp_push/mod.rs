@@ -10,10 +10,13 @@ use crate::endpoints::ffmpeg::{
 };
 use crate::endpoints::rtmp_server::RtmpEndpointRequest;
 use crate::workflows::definitions::WorkflowStepDefinition;
-use crate::workflows::steps::factory::StepGenerator;
-use crate::workflows::steps::ffmpeg_handler::{FfmpegHandlerGenerator, FfmpegParameterGenerator};
+use crate::workflows::steps::factory::{StepGenerator, StepKind};
+use crate::workflows::steps::ffmpeg_handler::{
+    FfmpegHandlerGenerator, FfmpegParameterGenerator, FFMPEG_SUPPORTED_CODECS,
+};
 use crate::workflows::steps::{
-    StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus, WorkflowStep,
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, SupportedCodecs, WorkflowStep,
 };
 use crate::StreamId;
 use futures::FutureExt;
@@ -47,6 +50,12 @@ enum StepStartupError {
     NoTargetProvided,
 }
 
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
 struct ParamGenerator {
     rtmp_app: String,
     target: String,
@@ -66,14 +75,10 @@ impl FfmpegRtmpPushStepGenerator {
 
 impl StepGenerator for FfmpegRtmpPushStepGenerator {
     fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
-        let target = match definition.parameters.get(TARGET) {
-            Some(Some(value)) => value,
-            _ => return Err(Box::new(StepStartupError::NoTargetProvided)),
-        };
-
+        let target = parse_target(&definition)?;
         let param_generator = ParamGenerator {
             rtmp_app: get_rtmp_app(definition.get_id().to_string()),
-            target: target.to_string(),
+            target,
         };
 
         let handler_generator =
@@ -95,6 +100,22 @@ impl StepGenerator for FfmpegRtmpPushStepGenerator {
 
         Ok((Box::new(step), futures))
     }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_target(definition)?;
+        Ok(())
+    }
+
+    fn kind(&self) -> StepKind {
+        StepKind::Sink
+    }
+}
+
+fn parse_target(definition: &WorkflowStepDefinition) -> Result<String, StepStartupError> {
+    match definition.parameters.get(TARGET) {
+        Some(Some(value)) => Ok(value.to_string()),
+        _ => Err(StepStartupError::NoTargetProvided),
+    }
 }
 
 impl WorkflowStep for FfmpegRtmpPushStep {
@@ -139,6 +160,10 @@ impl WorkflowStep for FfmpegRtmpPushStep {
         }
     }
 
+    fn get_supported_codecs(&self) -> SupportedCodecs {
+        FFMPEG_SUPPORTED_CODECS
+    }
+
     fn shutdown(&mut self) {
         self.stream_reader.stop_all_streams();
         self.status = StepStatus::Shutdown;
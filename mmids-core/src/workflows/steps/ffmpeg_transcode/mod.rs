@@ -19,14 +19,17 @@ use crate::endpoints::ffmpeg::{
     H264Preset, TargetParams, VideoScale, VideoTranscodeParams,
 };
 use crate::endpoints::rtmp_server::{
-    IpRestriction, RegistrationType, RtmpEndpointMediaMessage, RtmpEndpointPublisherMessage,
-    RtmpEndpointRequest, RtmpEndpointWatcherNotification, StreamKeyRegistration,
+    IpRestriction, RegistrationType, RtmpAppMatching, RtmpEndpointMediaMessage,
+    RtmpEndpointPublisherMessage, RtmpEndpointRequest, RtmpEndpointWatcherNotification,
+    StreamKeyRegistration,
 };
 use crate::utils::stream_metadata_to_hash_map;
 use crate::workflows::definitions::WorkflowStepDefinition;
 use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::ffmpeg_handler::FFMPEG_SUPPORTED_CODECS;
 use crate::workflows::steps::{
-    StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus, WorkflowStep,
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, SupportedCodecs, WorkflowStep,
 };
 use crate::workflows::{MediaNotification, MediaNotificationContent};
 use crate::{StreamId, VideoTimestamp};
@@ -122,6 +125,13 @@ enum FutureResult {
 
 impl StepFutureResult for FutureResult {}
 
+struct StepParameters {
+    vcodec: VideoTranscodeParams,
+    acodec: AudioTranscodeParams,
+    size: Option<VideoScale>,
+    bitrate: Option<u16>,
+}
+
 #[derive(Error, Debug)]
 enum StepStartupError {
     #[error("Invalid video codec specified ({0}).  {} is a required field and valid values are: 'copy' and 'h264'", VIDEO_CODEC_NAME)]
@@ -143,6 +153,12 @@ enum StepStartupError {
     InvalidBitrateSpecified(String),
 }
 
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
 impl FfmpegTranscoderStepGenerator {
     pub fn new(
         rtmp_endpoint: UnboundedSender<RtmpEndpointRequest>,
@@ -157,122 +173,12 @@ impl FfmpegTranscoderStepGenerator {
 
 impl StepGenerator for FfmpegTranscoderStepGenerator {
     fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
-        let vcodec = match definition.parameters.get(VIDEO_CODEC_NAME) {
-            Some(Some(value)) => match value.to_lowercase().trim() {
-                "copy" => VideoTranscodeParams::Copy,
-                "h264" => match definition.parameters.get(H264_PRESET_NAME) {
-                    Some(Some(value)) => match value.to_lowercase().trim() {
-                        "ultrafast" => VideoTranscodeParams::H264 {
-                            preset: H264Preset::UltraFast,
-                        },
-                        "superfast" => VideoTranscodeParams::H264 {
-                            preset: H264Preset::SuperFast,
-                        },
-                        "veryfast" => VideoTranscodeParams::H264 {
-                            preset: H264Preset::VeryFast,
-                        },
-                        "faster" => VideoTranscodeParams::H264 {
-                            preset: H264Preset::Faster,
-                        },
-                        "fast" => VideoTranscodeParams::H264 {
-                            preset: H264Preset::Fast,
-                        },
-                        "medium" => VideoTranscodeParams::H264 {
-                            preset: H264Preset::Medium,
-                        },
-                        "slow" => VideoTranscodeParams::H264 {
-                            preset: H264Preset::Slow,
-                        },
-                        "slower" => VideoTranscodeParams::H264 {
-                            preset: H264Preset::Slower,
-                        },
-                        "veryslow" => VideoTranscodeParams::H264 {
-                            preset: H264Preset::VerySlow,
-                        },
-                        x => {
-                            return Err(Box::new(StepStartupError::InvalidH264PresetSpecified(
-                                x.to_string(),
-                            )))
-                        }
-                    },
-                    _ => VideoTranscodeParams::H264 {
-                        preset: H264Preset::VeryFast,
-                    },
-                },
-                x => {
-                    return Err(Box::new(StepStartupError::InvalidVideoCodecSpecified(
-                        x.to_string(),
-                    )))
-                }
-            },
-
-            _ => {
-                return Err(Box::new(StepStartupError::InvalidVideoCodecSpecified(
-                    "".to_string(),
-                )))
-            }
-        };
-
-        let acodec = match definition.parameters.get(AUDIO_CODEC_NAME) {
-            Some(Some(value)) => match value.to_lowercase().trim() {
-                "copy" => AudioTranscodeParams::Copy,
-                "aac" => AudioTranscodeParams::Aac,
-                x => {
-                    return Err(Box::new(StepStartupError::InvalidAudioCodecSpecified(
-                        x.to_string(),
-                    )))
-                }
-            },
-
-            _ => {
-                return Err(Box::new(StepStartupError::InvalidAudioCodecSpecified(
-                    "".to_string(),
-                )))
-            }
-        };
-
-        let size = match definition.parameters.get(SIZE_NAME) {
-            Some(Some(value)) => {
-                let mut dimensions = Vec::new();
-                for part in value.split('x') {
-                    match part.parse::<u16>() {
-                        Ok(num) => dimensions.push(num),
-                        Err(_) => {
-                            return Err(Box::new(StepStartupError::InvalidVideoSizeSpecified(
-                                value.clone(),
-                            )))
-                        }
-                    }
-                }
-
-                if dimensions.len() != 2 {
-                    return Err(Box::new(StepStartupError::InvalidVideoSizeSpecified(
-                        value.clone(),
-                    )));
-                }
-
-                Some(VideoScale {
-                    width: dimensions[0],
-                    height: dimensions[1],
-                })
-            }
-
-            _ => None,
-        };
-
-        let bitrate = match definition.parameters.get(BITRATE_NAME) {
-            Some(Some(value)) => {
-                if let Ok(num) = value.parse() {
-                    Some(num)
-                } else {
-                    return Err(Box::new(StepStartupError::InvalidBitrateSpecified(
-                        value.clone(),
-                    )));
-                }
-            }
-
-            _ => None,
-        };
+        let StepParameters {
+            vcodec,
+            acodec,
+            size,
+            bitrate,
+        } = parse_parameters(&definition)?;
 
         let step = FfmpegTranscoder {
             definition: definition.clone(),
@@ -293,6 +199,113 @@ impl StepGenerator for FfmpegTranscoderStepGenerator {
 
         Ok((Box::new(step), futures))
     }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_parameters(definition)?;
+        Ok(())
+    }
+}
+
+fn parse_parameters(
+    definition: &WorkflowStepDefinition,
+) -> Result<StepParameters, StepStartupError> {
+    let vcodec = match definition.parameters.get(VIDEO_CODEC_NAME) {
+        Some(Some(value)) => match value.to_lowercase().trim() {
+            "copy" => VideoTranscodeParams::Copy,
+            "h264" => match definition.parameters.get(H264_PRESET_NAME) {
+                Some(Some(value)) => match value.to_lowercase().trim() {
+                    "ultrafast" => VideoTranscodeParams::H264 {
+                        preset: H264Preset::UltraFast,
+                    },
+                    "superfast" => VideoTranscodeParams::H264 {
+                        preset: H264Preset::SuperFast,
+                    },
+                    "veryfast" => VideoTranscodeParams::H264 {
+                        preset: H264Preset::VeryFast,
+                    },
+                    "faster" => VideoTranscodeParams::H264 {
+                        preset: H264Preset::Faster,
+                    },
+                    "fast" => VideoTranscodeParams::H264 {
+                        preset: H264Preset::Fast,
+                    },
+                    "medium" => VideoTranscodeParams::H264 {
+                        preset: H264Preset::Medium,
+                    },
+                    "slow" => VideoTranscodeParams::H264 {
+                        preset: H264Preset::Slow,
+                    },
+                    "slower" => VideoTranscodeParams::H264 {
+                        preset: H264Preset::Slower,
+                    },
+                    "veryslow" => VideoTranscodeParams::H264 {
+                        preset: H264Preset::VerySlow,
+                    },
+                    x => return Err(StepStartupError::InvalidH264PresetSpecified(x.to_string())),
+                },
+                _ => VideoTranscodeParams::H264 {
+                    preset: H264Preset::VeryFast,
+                },
+            },
+            x => return Err(StepStartupError::InvalidVideoCodecSpecified(x.to_string())),
+        },
+
+        _ => return Err(StepStartupError::InvalidVideoCodecSpecified("".to_string())),
+    };
+
+    let acodec = match definition.parameters.get(AUDIO_CODEC_NAME) {
+        Some(Some(value)) => match value.to_lowercase().trim() {
+            "copy" => AudioTranscodeParams::Copy,
+            "aac" => AudioTranscodeParams::Aac,
+            x => return Err(StepStartupError::InvalidAudioCodecSpecified(x.to_string())),
+        },
+
+        _ => return Err(StepStartupError::InvalidAudioCodecSpecified("".to_string())),
+    };
+
+    let size = match definition.parameters.get(SIZE_NAME) {
+        Some(Some(value)) => {
+            let mut dimensions = Vec::new();
+            for part in value.split('x') {
+                match part.parse::<u16>() {
+                    Ok(num) => dimensions.push(num),
+                    Err(_) => {
+                        return Err(StepStartupError::InvalidVideoSizeSpecified(value.clone()))
+                    }
+                }
+            }
+
+            if dimensions.len() != 2 {
+                return Err(StepStartupError::InvalidVideoSizeSpecified(value.clone()));
+            }
+
+            Some(VideoScale {
+                width: dimensions[0],
+                height: dimensions[1],
+            })
+        }
+
+        _ => None,
+    };
+
+    let bitrate = match definition.parameters.get(BITRATE_NAME) {
+        Some(Some(value)) => {
+            if let Ok(num) = value.parse() {
+                Some(num)
+            } else {
+                return Err(StepStartupError::InvalidBitrateSpecified(value.clone()));
+            }
+        }
+
+        _ => None,
+    };
+
+    Ok(StepParameters {
+        vcodec,
+        acodec,
+        size,
+        bitrate,
+    })
 }
 
 impl FfmpegTranscoder {
@@ -486,9 +499,12 @@ impl FfmpegTranscoder {
                                 rtmp_stream_key: StreamKeyRegistration::Exact(stream.id.0.clone()),
                                 port: 1935,
                                 media_channel: media_receiver,
+                                media_backlog: None,
                                 ip_restrictions: IpRestriction::None,
                                 use_tls: false,
                                 requires_registrant_approval: false,
+                                max_watchers: None,
+                                start_on_keyframe: false,
                             });
 
                     outputs.futures.push(
@@ -529,12 +545,14 @@ impl FfmpegTranscoder {
                             .send(RtmpEndpointRequest::ListenForPublishers {
                                 port: 1935,
                                 rtmp_app: result_rtmp_app.clone(),
+                                rtmp_app_matching: RtmpAppMatching::Exact,
                                 rtmp_stream_key: StreamKeyRegistration::Exact(stream.id.0.clone()),
                                 stream_id: Some(stream.id.clone()),
                                 message_channel: sender,
                                 ip_restrictions: IpRestriction::None,
                                 use_tls: false,
                                 requires_registrant_approval: false,
+                                max_connects_per_minute: None,
                             });
 
                     outputs
@@ -685,6 +703,7 @@ impl FfmpegTranscoder {
                 } => (),
 
                 RtmpEndpointWatcherNotification::StreamKeyBecameInactive { stream_key: _ } => (),
+                RtmpEndpointWatcherNotification::WatcherJoined { stream_key: _ } => (),
 
                 RtmpEndpointWatcherNotification::WatcherRequiringApproval { .. } => {
                     error!("Watcher requires approval but all watchers should be auto-approved");
@@ -729,8 +748,10 @@ impl FfmpegTranscoder {
 
                 RtmpEndpointPublisherMessage::NewPublisherConnected {
                     stream_id: _,
+                    rtmp_app: _,
                     stream_key: _,
                     connection_id: _,
+                    remote_address: _,
                     reactor_update_channel: _,
                 } => (),
                 RtmpEndpointPublisherMessage::PublishingStopped { connection_id: _ } => (),
@@ -743,6 +764,7 @@ impl FfmpegTranscoder {
                     outputs.media.push(MediaNotification {
                         stream_id: stream_id.clone(),
                         content: MediaNotificationContent::Metadata { data: metadata },
+                        tags: Vec::new(),
                     });
                 }
 
@@ -766,6 +788,7 @@ impl FfmpegTranscoder {
                         is_sequence_header,
                         data,
                     },
+                    tags: Vec::new(),
                 }),
 
                 RtmpEndpointPublisherMessage::NewAudioData {
@@ -782,6 +805,7 @@ impl FfmpegTranscoder {
                         is_sequence_header,
                         data,
                     },
+                    tags: Vec::new(),
                 }),
 
                 RtmpEndpointPublisherMessage::PublisherRequiringApproval { .. } => {
@@ -878,6 +902,10 @@ impl WorkflowStep for FfmpegTranscoder {
         }
     }
 
+    fn get_supported_codecs(&self) -> SupportedCodecs {
+        FFMPEG_SUPPORTED_CODECS
+    }
+
     fn shutdown(&mut self) {
         let stream_ids = self.active_streams.drain().map(|x| x.0).collect::<Vec<_>>();
         for stream_id in stream_ids {
@@ -299,6 +299,24 @@ fn step_fails_to_build_when_invalid_size_specified() {
     }
 }
 
+#[test]
+fn validation_fails_when_invalid_size_specified() {
+    let definition = DefinitionBuilder::new().size("abc").build();
+    let generator =
+        FfmpegTranscoderStepGenerator::new(unbounded_channel().0, unbounded_channel().0);
+
+    assert!(generator.validate(&definition).is_err());
+}
+
+#[test]
+fn validation_passes_for_valid_definition() {
+    let definition = DefinitionBuilder::new().build();
+    let generator =
+        FfmpegTranscoderStepGenerator::new(unbounded_channel().0, unbounded_channel().0);
+
+    assert!(generator.validate(&definition).is_ok());
+}
+
 #[tokio::test]
 async fn rtmp_watch_registration_raised_on_new_stream() {
     let definition = DefinitionBuilder::new().build();
@@ -309,6 +327,7 @@ async fn rtmp_watch_registration_raised_on_new_stream() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let request = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
@@ -337,6 +356,7 @@ async fn rtmp_publish_registration_raised_after_watch_accepted() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let _watch_channels = context.accept_watch_registration().await;
@@ -367,6 +387,7 @@ async fn ffmpeg_request_raised_after_publish_accepted() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let _watch_channels = context.accept_watch_registration().await;
@@ -393,6 +414,7 @@ async fn h264_with_preset_passed_to_ffmpeg() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let _watch_channels = context.accept_watch_registration().await;
@@ -418,6 +440,7 @@ async fn video_copy_passed_to_ffmpeg() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let _watch_channels = context.accept_watch_registration().await;
@@ -441,6 +464,7 @@ async fn aac_acodec_passed_to_ffmpeg() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let _watch_channels = context.accept_watch_registration().await;
@@ -464,6 +488,7 @@ async fn copy_acodec_passed_to_ffmpeg() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let _watch_channels = context.accept_watch_registration().await;
@@ -491,6 +516,7 @@ async fn size_passed_to_ffmpeg() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let _watch_channels = context.accept_watch_registration().await;
@@ -517,6 +543,7 @@ async fn bitrate_passed_to_ffmpeg() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let _watch_channels = context.accept_watch_registration().await;
@@ -537,6 +564,7 @@ async fn ffmpeg_always_told_to_read_in_real_time() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let _watch_channels = context.accept_watch_registration().await;
@@ -559,6 +587,7 @@ async fn ffmpeg_instructed_to_read_from_rtmp() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let _watch_channels = context.accept_watch_registration().await;
@@ -589,6 +618,7 @@ async fn if_ffmpeg_process_stops_unexpectedly_it_starts_again_with_same_id_and_p
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let _watch_channels = context.accept_watch_registration().await;
@@ -619,6 +649,7 @@ fn stream_started_notification_passed_through_immediately() {
             content: MediaNotificationContent::NewIncomingStream {
                 stream_name: "abc".to_string(),
             },
+            tags: Vec::new(),
         });
 }
 
@@ -632,6 +663,7 @@ fn disconnection_notification_passed_through_immediately() {
         .assert_media_passed_through(MediaNotification {
             stream_id: StreamId("abc".to_string()),
             content: MediaNotificationContent::StreamDisconnected,
+            tags: Vec::new(),
         });
 }
 #[test]
@@ -646,6 +678,7 @@ fn metadata_notification_passed_as_input_does_not_get_passed_as_output() {
             content: MediaNotificationContent::Metadata {
                 data: HashMap::new(),
             },
+            tags: Vec::new(),
         });
 }
 
@@ -668,6 +701,7 @@ fn video_notification_passed_as_input_does_not_get_passed_as_output() {
                     Duration::from_millis(0),
                 ),
             },
+            tags: Vec::new(),
         });
 }
 
@@ -686,6 +720,7 @@ fn audio_notification_passed_as_input_does_not_get_passed_as_output() {
                 timestamp: Duration::from_millis(5),
                 is_sequence_header: true,
             },
+            tags: Vec::new(),
         });
 }
 
@@ -699,6 +734,7 @@ async fn video_packet_sent_to_watcher_media_channel() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let (_notification, mut media_channel) = context.accept_watch_registration().await;
@@ -717,6 +753,7 @@ async fn video_packet_sent_to_watcher_media_channel() {
             is_keyframe: true,
             is_sequence_header: true,
         },
+        tags: Vec::new(),
     };
 
     context.step_context.execute_with_media(media.clone());
@@ -740,6 +777,7 @@ async fn audio_packet_sent_to_watcher_media_channel() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let (_notification, mut media_channel) = context.accept_watch_registration().await;
@@ -754,6 +792,7 @@ async fn audio_packet_sent_to_watcher_media_channel() {
             timestamp: Duration::from_millis(5),
             is_sequence_header: true,
         },
+        tags: Vec::new(),
     };
 
     context.step_context.execute_with_media(media.clone());
@@ -777,6 +816,7 @@ async fn metadata_packet_sent_to_watcher_media_channel() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let (_notification, mut media_channel) = context.accept_watch_registration().await;
@@ -788,6 +828,7 @@ async fn metadata_packet_sent_to_watcher_media_channel() {
         content: MediaNotificationContent::Metadata {
             data: HashMap::new(),
         },
+        tags: Vec::new(),
     };
 
     context.step_context.execute_with_media(media.clone());
@@ -812,6 +853,7 @@ async fn video_packet_with_other_stream_id_not_sent_to_watcher_media_channel() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let (_notification, mut media_channel) = context.accept_watch_registration().await;
@@ -827,6 +869,7 @@ async fn video_packet_with_other_stream_id_not_sent_to_watcher_media_channel() {
             is_keyframe: true,
             is_sequence_header: true,
         },
+        tags: Vec::new(),
     };
 
     context.step_context.execute_with_media(media.clone());
@@ -844,6 +887,7 @@ async fn video_packet_from_publisher_passed_as_media_output() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let _watch_channels = context.accept_watch_registration().await;
@@ -906,6 +950,7 @@ async fn audio_packet_from_publisher_passed_as_media_output() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let _watch_channels = context.accept_watch_registration().await;
@@ -963,6 +1008,7 @@ async fn metadata_packet_from_publisher_passed_as_media_output() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let _watch_channels = context.accept_watch_registration().await;
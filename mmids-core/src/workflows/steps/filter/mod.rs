@@ -0,0 +1,95 @@
+//! The filter step only passes along media that has been tagged with a specific branch by an
+//! earlier `tag` step.  All other media is dropped.
+//!
+//! If the filter is configured for the `any` branch then all media is passed along, including
+//! media that has not been tagged at all.
+
+#[cfg(test)]
+mod tests;
+
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use thiserror::Error;
+
+const BRANCH: &str = "branch";
+
+/// Branch name that allows all media through the filter, whether it has been tagged or not
+pub const ANY_BRANCH: &str = "any";
+
+/// Generates new instances of the filter workflow step based on specified step definitions.
+pub struct FilterStepGenerator {}
+
+struct FilterStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    branch: String,
+}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error("No branch specified.  A 'branch' parameter is required")]
+    NoBranchProvided,
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl FilterStepGenerator {
+    pub fn new() -> Self {
+        FilterStepGenerator {}
+    }
+}
+
+impl StepGenerator for FilterStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let branch = parse_branch(&definition)?;
+        let step = FilterStep {
+            definition,
+            status: StepStatus::Active,
+            branch,
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_branch(definition)?;
+        Ok(())
+    }
+}
+
+fn parse_branch(definition: &WorkflowStepDefinition) -> Result<String, StepStartupError> {
+    match definition.parameters.get(BRANCH) {
+        Some(Some(value)) => Ok(value.to_string()),
+        _ => Err(StepStartupError::NoBranchProvided),
+    }
+}
+
+impl WorkflowStep for FilterStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for media in inputs.media.drain(..) {
+            if self.branch == ANY_BRANCH || media.tags.contains(&self.branch) {
+                outputs.media.push(media);
+            }
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+    }
+}
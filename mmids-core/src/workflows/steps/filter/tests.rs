@@ -0,0 +1,70 @@
+use super::*;
+use crate::workflows::steps::test_utils::create_definition;
+use crate::workflows::steps::StepTestContext;
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
+
+fn create_context(branch: &str) -> StepTestContext {
+    let generator = FilterStepGenerator::new();
+    let definition = create_definition("filter", &[(BRANCH, branch)]);
+    StepTestContext::new(Box::new(generator), definition).unwrap()
+}
+
+fn create_media(tags: Vec<String>) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags,
+    }
+}
+
+#[test]
+fn step_fails_to_generate_without_branch() {
+    let generator = FilterStepGenerator::new();
+    let definition = create_definition("filter", &[]);
+
+    assert!(generator.generate(definition).is_err());
+}
+
+#[test]
+fn validation_fails_without_branch() {
+    let generator = FilterStepGenerator::new();
+    let definition = create_definition("filter", &[]);
+
+    assert!(generator.validate(&definition).is_err());
+}
+
+#[test]
+fn media_with_matching_tag_passed_through() {
+    let mut context = create_context("hls");
+
+    context.assert_media_passed_through(create_media(vec!["other".to_string(), "hls".to_string()]));
+}
+
+#[test]
+fn media_without_matching_tag_not_passed_through() {
+    let mut context = create_context("hls");
+
+    context.assert_media_not_passed_through(create_media(vec!["other".to_string()]));
+}
+
+#[test]
+fn untagged_media_not_passed_through_branch_filter() {
+    let mut context = create_context("hls");
+
+    context.assert_media_not_passed_through(create_media(Vec::new()));
+}
+
+#[test]
+fn untagged_media_passed_through_any_filter() {
+    let mut context = create_context("any");
+
+    context.assert_media_passed_through(create_media(Vec::new()));
+}
+
+#[test]
+fn tagged_media_passed_through_any_filter() {
+    let mut context = create_context("any");
+
+    context.assert_media_passed_through(create_media(vec!["hls".to_string()]));
+}
@@ -0,0 +1,224 @@
+//! The gap monitor step watches the timestamps of each stream that passes through it, and logs a
+//! warning whenever a stream's video or audio timestamps jump forward by more than a configured
+//! threshold or go backwards.  These gaps are usually caused by encoder or network hiccups, and
+//! often end up causing playback stalls further down the line.
+//!
+//! Video and audio are tracked separately, and sequence headers are ignored.  The number of gaps
+//! seen for each stream is reported in the workflow's state, and is reset when the stream
+//! disconnects.  All media notifications are passed to subsequent steps unchanged.
+
+#[cfg(test)]
+mod tests;
+
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
+use std::collections::HashMap;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::warn;
+
+pub const MAX_GAP_MS: &str = "max_gap_ms";
+const DEFAULT_MAX_GAP: Duration = Duration::from_millis(1000);
+
+/// The name the gap count is reported under in the workflow's state
+pub const GAP_COUNT_DETAIL: &str = "gap_count";
+
+/// Generates new instances of the gap monitor workflow step
+pub struct GapMonitorStepGenerator {}
+
+struct GapMonitorStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    max_gap: Duration,
+    streams: HashMap<StreamId, StreamTimestamps>,
+}
+
+#[derive(Default)]
+struct StreamTimestamps {
+    stream_name: Option<String>,
+    last_video: Option<Duration>,
+    last_audio: Option<Duration>,
+    gap_count: u64,
+}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error(
+        "Invalid max gap of '{0}'.  {} should be a positive number of milliseconds",
+        MAX_GAP_MS
+    )]
+    InvalidMaxGap(String),
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl GapMonitorStepGenerator {
+    pub fn new() -> Self {
+        GapMonitorStepGenerator {}
+    }
+}
+
+impl StepGenerator for GapMonitorStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let max_gap = parse_max_gap(&definition)?;
+        let step = GapMonitorStep {
+            definition,
+            status: StepStatus::Active,
+            max_gap,
+            streams: HashMap::new(),
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_max_gap(definition)?;
+        Ok(())
+    }
+}
+
+fn parse_max_gap(definition: &WorkflowStepDefinition) -> Result<Duration, StepStartupError> {
+    match definition.parameters.get(MAX_GAP_MS) {
+        Some(Some(value)) => match value.parse::<u64>() {
+            Ok(num) if num > 0 => Ok(Duration::from_millis(num)),
+            _ => Err(StepStartupError::InvalidMaxGap(value.clone())),
+        },
+
+        _ => Ok(DEFAULT_MAX_GAP),
+    }
+}
+
+impl GapMonitorStep {
+    fn handle_media(&mut self, media: &MediaNotification) {
+        match &media.content {
+            MediaNotificationContent::NewIncomingStream { stream_name } => {
+                self.streams.insert(
+                    media.stream_id.clone(),
+                    StreamTimestamps {
+                        stream_name: Some(stream_name.clone()),
+                        ..Default::default()
+                    },
+                );
+            }
+
+            MediaNotificationContent::StreamDisconnected => {
+                self.streams.remove(&media.stream_id);
+            }
+
+            MediaNotificationContent::Video {
+                is_sequence_header: false,
+                timestamp,
+                ..
+            } => {
+                let stream = self.streams.entry(media.stream_id.clone()).or_default();
+                let last_video = stream.last_video.replace(timestamp.dts());
+                stream.check_for_gap(
+                    &media.stream_id,
+                    "video",
+                    last_video,
+                    timestamp.dts(),
+                    self.max_gap,
+                );
+            }
+
+            MediaNotificationContent::Audio {
+                is_sequence_header: false,
+                timestamp,
+                ..
+            } => {
+                let stream = self.streams.entry(media.stream_id.clone()).or_default();
+                let last_audio = stream.last_audio.replace(*timestamp);
+                stream.check_for_gap(
+                    &media.stream_id,
+                    "audio",
+                    last_audio,
+                    *timestamp,
+                    self.max_gap,
+                );
+            }
+
+            _ => (),
+        }
+    }
+}
+
+impl StreamTimestamps {
+    fn check_for_gap(
+        &mut self,
+        stream_id: &StreamId,
+        media_type: &str,
+        previous: Option<Duration>,
+        current: Duration,
+        max_gap: Duration,
+    ) {
+        let previous = match previous {
+            Some(previous) => previous,
+            None => return,
+        };
+
+        let stream_name = self.stream_name.as_deref().unwrap_or("");
+        if current < previous {
+            warn!(
+                stream_id = ?stream_id,
+                stream_name = %stream_name,
+                "Stream {:?} {} timestamp went backwards from {:?} to {:?}",
+                stream_id, media_type, previous, current
+            );
+        } else if current - previous > max_gap {
+            warn!(
+                stream_id = ?stream_id,
+                stream_name = %stream_name,
+                "Stream {:?} {} timestamp jumped {:?} (from {:?} to {:?})",
+                stream_id, media_type, current - previous, previous, current
+            );
+        } else {
+            return;
+        }
+
+        self.gap_count += 1;
+    }
+}
+
+impl WorkflowStep for GapMonitorStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for media in inputs.media.drain(..) {
+            self.handle_media(&media);
+            outputs.media.push(media);
+        }
+    }
+
+    fn get_stream_details(&self) -> HashMap<StreamId, HashMap<String, String>> {
+        self.streams
+            .iter()
+            .map(|(stream_id, stream)| {
+                let mut details = HashMap::new();
+                details.insert(GAP_COUNT_DETAIL.to_string(), stream.gap_count.to_string());
+
+                (stream_id.clone(), details)
+            })
+            .collect()
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+        self.streams.clear();
+    }
+}
@@ -0,0 +1,151 @@
+use super::*;
+use crate::workflows::steps::test_utils::{
+    audio_content, create_definition, disconnected_content, media, new_stream_content,
+    video_content,
+};
+use crate::workflows::steps::StepTestContext;
+
+fn create_context() -> StepTestContext {
+    let generator = GapMonitorStepGenerator::new();
+    StepTestContext::new(
+        Box::new(generator),
+        create_definition("gap_monitor", &[(MAX_GAP_MS, "500")]),
+    )
+    .unwrap()
+}
+
+fn video(millis: u64) -> MediaNotification {
+    media(
+        "abc",
+        video_content(false, false, Duration::from_millis(millis)),
+    )
+}
+
+fn audio(millis: u64) -> MediaNotification {
+    media("abc", audio_content(false, Duration::from_millis(millis)))
+}
+
+fn get_gap_count(context: &StepTestContext, stream_id: &str) -> Option<String> {
+    context
+        .step
+        .get_stream_details()
+        .get(&StreamId(stream_id.to_string()))
+        .and_then(|details| details.get(GAP_COUNT_DETAIL).cloned())
+}
+
+#[test]
+fn step_fails_to_generate_with_zero_max_gap() {
+    let generator = GapMonitorStepGenerator::new();
+    assert!(generator
+        .generate(create_definition("gap_monitor", &[(MAX_GAP_MS, "0")]))
+        .is_err());
+}
+
+#[test]
+fn step_fails_to_generate_with_non_numeric_max_gap() {
+    let generator = GapMonitorStepGenerator::new();
+    assert!(generator
+        .generate(create_definition("gap_monitor", &[(MAX_GAP_MS, "abc")]))
+        .is_err());
+}
+
+#[test]
+fn step_generated_without_max_gap() {
+    let generator = GapMonitorStepGenerator::new();
+    assert!(generator
+        .generate(create_definition("gap_monitor", &[]))
+        .is_ok());
+}
+
+#[test]
+fn all_media_passed_through() {
+    let mut context = create_context();
+    context.assert_media_passed_through(media("abc", new_stream_content("def")));
+    context.assert_media_passed_through(video(0));
+    context.assert_media_passed_through(video(5000));
+    context.assert_media_passed_through(audio(100));
+    context.assert_media_passed_through(audio(0));
+    context.assert_media_passed_through(media("abc", disconnected_content()));
+}
+
+#[test]
+fn new_stream_starts_with_no_gaps() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+
+    assert_eq!(get_gap_count(&context, "abc"), Some("0".to_string()));
+}
+
+#[test]
+fn timestamps_within_max_gap_not_counted() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    context.execute_with_media(video(0));
+    context.execute_with_media(video(500));
+    context.execute_with_media(audio(0));
+    context.execute_with_media(audio(400));
+
+    assert_eq!(get_gap_count(&context, "abc"), Some("0".to_string()));
+}
+
+#[test]
+fn video_jump_beyond_max_gap_counted() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    context.execute_with_media(video(0));
+    context.execute_with_media(video(501));
+
+    assert_eq!(get_gap_count(&context, "abc"), Some("1".to_string()));
+}
+
+#[test]
+fn audio_going_backwards_counted() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    context.execute_with_media(audio(100));
+    context.execute_with_media(audio(50));
+
+    assert_eq!(get_gap_count(&context, "abc"), Some("1".to_string()));
+}
+
+#[test]
+fn video_and_audio_tracked_separately() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    context.execute_with_media(video(1000));
+    context.execute_with_media(audio(0));
+    context.execute_with_media(video(1100));
+    context.execute_with_media(audio(100));
+
+    assert_eq!(get_gap_count(&context, "abc"), Some("0".to_string()));
+}
+
+#[test]
+fn sequence_headers_ignored() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    context.execute_with_media(video(5000));
+    context.execute_with_media(media(
+        "abc",
+        video_content(true, true, Duration::from_millis(0)),
+    ));
+    context.execute_with_media(video(5100));
+
+    assert_eq!(get_gap_count(&context, "abc"), Some("0".to_string()));
+}
+
+#[test]
+fn gap_counts_reset_when_stream_disconnects() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    context.execute_with_media(video(0));
+    context.execute_with_media(video(5000));
+    context.execute_with_media(media("abc", disconnected_content()));
+
+    assert_eq!(get_gap_count(&context, "abc"), None);
+
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    context.execute_with_media(video(0));
+
+    assert_eq!(get_gap_count(&context, "abc"), Some("0".to_string()));
+}
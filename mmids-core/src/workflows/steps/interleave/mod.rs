@@ -0,0 +1,224 @@
+//! The interleave step reorders the audio and video of each stream that passes through it, so
+//! they are output in timestamp order.  Sources commonly send bursts of one type of media followed
+//! by bursts of the other, which some consumers can't handle.
+//!
+//! Media of each stream is buffered until the step knows nothing earlier can still arrive, which
+//! is once both the audio and video of the stream have reached its timestamp.  Since a stream
+//! might never send one of the types of media, media is never held longer than the maximum buffer
+//! duration (measured in media time from the latest timestamp seen for the stream).  This means
+//! the step adds up to that much latency to each stream.
+//!
+//! Video is ordered by its decoding timestamp.  Metadata and cues are kept in position relative to
+//! the media received around it.  All buffered media of a stream is output when the stream disconnects,
+//! reconnects, or raises a discontinuity, since timestamps on either side of those can't be
+//! compared.  Media of streams that haven't been announced is passed through unchanged.
+
+#[cfg(test)]
+mod tests;
+
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use thiserror::Error;
+
+const MAX_BUFFER_MS: &str = "max_buffer_ms";
+const DEFAULT_MAX_BUFFER: Duration = Duration::from_millis(500);
+
+/// Generates new instances of the interleave workflow step
+pub struct InterleaveStepGenerator {}
+
+struct InterleaveStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    max_buffer: Duration,
+    streams: HashMap<StreamId, StreamBuffer>,
+}
+
+#[derive(Default)]
+struct StreamBuffer {
+    /// Buffered media keyed by timestamp, with a sequence number so media with the same
+    /// timestamp is output in the order it was received
+    media: BTreeMap<(Duration, u64), MediaNotification>,
+    next_sequence: u64,
+
+    latest_video: Option<Duration>,
+    latest_audio: Option<Duration>,
+    latest_timestamp: Option<Duration>,
+}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error("Invalid max buffer of '{0}'.  A positive number of milliseconds is required")]
+    InvalidMaxBuffer(String),
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl InterleaveStepGenerator {
+    pub fn new() -> Self {
+        InterleaveStepGenerator {}
+    }
+}
+
+impl StepGenerator for InterleaveStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let max_buffer = parse_max_buffer(&definition)?;
+        let step = InterleaveStep {
+            definition,
+            status: StepStatus::Active,
+            max_buffer,
+            streams: HashMap::new(),
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_max_buffer(definition)?;
+        Ok(())
+    }
+}
+
+fn parse_max_buffer(definition: &WorkflowStepDefinition) -> Result<Duration, StepStartupError> {
+    match definition.parameters.get(MAX_BUFFER_MS) {
+        Some(Some(value)) => match value.parse::<u64>() {
+            Ok(num) if num > 0 => Ok(Duration::from_millis(num)),
+            _ => Err(StepStartupError::InvalidMaxBuffer(value.clone())),
+        },
+
+        _ => Ok(DEFAULT_MAX_BUFFER),
+    }
+}
+
+impl StreamBuffer {
+    fn add(&mut self, media: MediaNotification) {
+        let timestamp = match &media.content {
+            MediaNotificationContent::Video { timestamp, .. } => {
+                let dts = timestamp.dts();
+                self.latest_video = Some(dts);
+                dts
+            }
+
+            MediaNotificationContent::Audio { timestamp, .. } => {
+                self.latest_audio = Some(*timestamp);
+                *timestamp
+            }
+
+            // Anything else stays after the media that was received before it
+            _ => self.latest_timestamp.unwrap_or_default(),
+        };
+
+        if self.latest_timestamp < Some(timestamp) {
+            self.latest_timestamp = Some(timestamp);
+        }
+
+        self.media.insert((timestamp, self.next_sequence), media);
+        self.next_sequence += 1;
+    }
+
+    /// Removes all buffered media that no longer needs to wait for earlier media to arrive
+    fn take_ready(&mut self, max_buffer: Duration, outputs: &mut StepOutputs) {
+        let both_types_reached = match (self.latest_video, self.latest_audio) {
+            (Some(video), Some(audio)) => Some(video.min(audio)),
+            _ => None,
+        };
+
+        let buffer_limit = self
+            .latest_timestamp
+            .and_then(|latest| latest.checked_sub(max_buffer));
+
+        let release_up_to = match (both_types_reached, buffer_limit) {
+            (Some(first), Some(second)) => first.max(second),
+            (Some(timestamp), None) | (None, Some(timestamp)) => timestamp,
+            (None, None) => return,
+        };
+
+        while let Some(key) = self.media.keys().next().copied() {
+            if key.0 > release_up_to {
+                break;
+            }
+
+            if let Some(media) = self.media.remove(&key) {
+                outputs.media.push(media);
+            }
+        }
+    }
+
+    fn flush(&mut self, outputs: &mut StepOutputs) {
+        let media = std::mem::take(&mut self.media);
+        outputs
+            .media
+            .extend(media.into_iter().map(|(_, media)| media));
+
+        self.latest_video = None;
+        self.latest_audio = None;
+        self.latest_timestamp = None;
+    }
+}
+
+impl WorkflowStep for InterleaveStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for media in inputs.media.drain(..) {
+            match &media.content {
+                MediaNotificationContent::NewIncomingStream { .. } => {
+                    let buffer = self.streams.entry(media.stream_id.clone()).or_default();
+                    buffer.flush(outputs);
+                    outputs.media.push(media);
+                }
+
+                MediaNotificationContent::StreamDisconnected => {
+                    if let Some(mut buffer) = self.streams.remove(&media.stream_id) {
+                        buffer.flush(outputs);
+                    }
+
+                    outputs.media.push(media);
+                }
+
+                MediaNotificationContent::Discontinuity => {
+                    if let Some(buffer) = self.streams.get_mut(&media.stream_id) {
+                        buffer.flush(outputs);
+                    }
+
+                    outputs.media.push(media);
+                }
+
+                MediaNotificationContent::Video { .. }
+                | MediaNotificationContent::Audio { .. }
+                | MediaNotificationContent::Metadata { .. }
+                | MediaNotificationContent::Cue { .. } => {
+                    match self.streams.get_mut(&media.stream_id) {
+                        Some(buffer) => {
+                            buffer.add(media);
+                            buffer.take_ready(self.max_buffer, outputs);
+                        }
+
+                        None => outputs.media.push(media),
+                    }
+                }
+            }
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+    }
+}
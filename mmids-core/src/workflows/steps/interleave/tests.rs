@@ -0,0 +1,245 @@
+use super::*;
+use crate::codecs::{AudioCodec, VideoCodec};
+use crate::workflows::steps::test_utils::create_definition;
+use crate::workflows::steps::StepTestContext;
+use crate::VideoTimestamp;
+use bytes::Bytes;
+
+fn create_context() -> StepTestContext {
+    let generator = InterleaveStepGenerator::new();
+    let mut context = StepTestContext::new(
+        Box::new(generator),
+        create_definition("interleave", &[(MAX_BUFFER_MS, "100")]),
+    )
+    .unwrap();
+
+    context.execute_with_media(new_stream("1"));
+    context
+}
+
+fn new_stream(stream_id: &str) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "abc".to_string(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn video(stream_id: &str, timestamp: u64) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            is_keyframe: true,
+            is_sequence_header: false,
+            data: Bytes::from(vec![1, 2, 3]),
+            timestamp: VideoTimestamp::from_durations(
+                Duration::from_millis(timestamp),
+                Duration::from_millis(timestamp),
+            ),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn audio(stream_id: &str, timestamp: u64) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::Audio {
+            codec: AudioCodec::Aac,
+            is_sequence_header: false,
+            data: Bytes::from(vec![1, 2, 3]),
+            timestamp: Duration::from_millis(timestamp),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn notification(stream_id: &str, content: MediaNotificationContent) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content,
+        tags: Vec::new(),
+    }
+}
+
+/// Executes the step with each media notification, returning a short description of every media
+/// notification that was output (e.g. `v40` for video with a 40ms timestamp)
+fn execute(context: &mut StepTestContext, media: Vec<MediaNotification>) -> Vec<String> {
+    let mut outputs = Vec::new();
+    for media in media {
+        context.execute_with_media(media);
+        outputs.extend(context.media_outputs.iter().map(describe));
+    }
+
+    outputs
+}
+
+fn describe(media: &MediaNotification) -> String {
+    match &media.content {
+        MediaNotificationContent::Video { timestamp, .. } => {
+            format!("v{}", timestamp.dts().as_millis())
+        }
+
+        MediaNotificationContent::Audio { timestamp, .. } => {
+            format!("a{}", timestamp.as_millis())
+        }
+
+        MediaNotificationContent::NewIncomingStream { .. } => "new".to_string(),
+        MediaNotificationContent::StreamDisconnected => "disconnected".to_string(),
+        MediaNotificationContent::Metadata { .. } => "metadata".to_string(),
+        MediaNotificationContent::Discontinuity => "discontinuity".to_string(),
+        MediaNotificationContent::Cue { .. } => "cue".to_string(),
+    }
+}
+
+#[test]
+fn validation_fails_for_non_numeric_max_buffer() {
+    let generator = InterleaveStepGenerator::new();
+    assert!(generator
+        .validate(&create_definition("interleave", &[(MAX_BUFFER_MS, "abc")]))
+        .is_err());
+}
+
+#[test]
+fn validation_fails_for_zero_max_buffer() {
+    let generator = InterleaveStepGenerator::new();
+    assert!(generator
+        .validate(&create_definition("interleave", &[(MAX_BUFFER_MS, "0")]))
+        .is_err());
+}
+
+#[test]
+fn validation_passes_without_max_buffer() {
+    let generator = InterleaveStepGenerator::new();
+    assert!(generator
+        .validate(&create_definition("interleave", &[]))
+        .is_ok());
+}
+
+#[test]
+fn new_stream_notification_passed_through() {
+    let generator = InterleaveStepGenerator::new();
+    let mut context =
+        StepTestContext::new(Box::new(generator), create_definition("interleave", &[])).unwrap();
+
+    context.assert_media_passed_through(new_stream("1"));
+}
+
+#[test]
+fn media_for_unknown_stream_passed_through() {
+    let mut context = create_context();
+    context.assert_media_passed_through(video("2", 50));
+}
+
+#[test]
+fn media_held_until_both_audio_and_video_reach_its_timestamp() {
+    let mut context = create_context();
+    let outputs = execute(
+        &mut context,
+        vec![video("1", 0), video("1", 20), video("1", 40)],
+    );
+
+    assert!(outputs.is_empty(), "Expected no outputs: {:?}", outputs);
+
+    let outputs = execute(&mut context, vec![audio("1", 10), audio("1", 30)]);
+    assert_eq!(
+        outputs,
+        vec!["v0", "a10", "v20", "a30"],
+        "Unexpected outputs"
+    );
+}
+
+#[test]
+fn media_released_once_max_buffer_exceeded() {
+    let mut context = create_context();
+    let outputs = execute(
+        &mut context,
+        vec![video("1", 0), video("1", 50), video("1", 100)],
+    );
+
+    assert_eq!(outputs, vec!["v0"], "Unexpected outputs");
+
+    let outputs = execute(&mut context, vec![video("1", 160)]);
+    assert_eq!(outputs, vec!["v50"], "Unexpected outputs");
+}
+
+#[test]
+fn metadata_kept_after_media_received_before_it() {
+    let mut context = create_context();
+    let metadata = notification(
+        "1",
+        MediaNotificationContent::Metadata {
+            data: HashMap::new(),
+        },
+    );
+
+    let outputs = execute(
+        &mut context,
+        vec![video("1", 0), video("1", 20), metadata, audio("1", 30)],
+    );
+
+    assert_eq!(outputs, vec!["v0", "v20", "metadata"], "Unexpected outputs");
+}
+
+#[test]
+fn buffered_media_flushed_before_disconnection() {
+    let mut context = create_context();
+    let outputs = execute(
+        &mut context,
+        vec![
+            video("1", 20),
+            audio("1", 0),
+            video("1", 40),
+            notification("1", MediaNotificationContent::StreamDisconnected),
+        ],
+    );
+
+    assert_eq!(
+        outputs,
+        vec!["a0", "v20", "v40", "disconnected"],
+        "Unexpected outputs"
+    );
+}
+
+#[test]
+fn buffered_media_flushed_before_discontinuity() {
+    let mut context = create_context();
+    let outputs = execute(
+        &mut context,
+        vec![
+            video("1", 1000),
+            notification("1", MediaNotificationContent::Discontinuity),
+            video("1", 0),
+            audio("1", 0),
+        ],
+    );
+
+    assert_eq!(
+        outputs,
+        vec!["v1000", "discontinuity", "v0", "a0"],
+        "Unexpected outputs"
+    );
+}
+
+#[test]
+fn streams_are_buffered_independently() {
+    let mut context = create_context();
+    context.execute_with_media(new_stream("2"));
+
+    let outputs = execute(
+        &mut context,
+        vec![video("1", 0), video("2", 0), audio("2", 0)],
+    );
+
+    assert_eq!(outputs, vec!["v0", "a0"], "Unexpected outputs");
+    assert!(
+        context
+            .media_outputs
+            .iter()
+            .all(|media| media.stream_id == StreamId("2".to_string())),
+        "Expected only media from the second stream"
+    );
+}
@@ -0,0 +1,398 @@
+//! The keyframe capture step writes video keyframes to disk, so they can be used to generate
+//! preview images of media streams.  At most one keyframe is captured per stream every interval,
+//! based on the keyframe's decoding timestamp.
+//!
+//! Captures are written as H264 annex B files containing the stream's latest SPS and PPS followed
+//! by the keyframe itself, so each file is independently decodable (e.g. by ffmpeg).  Each capture
+//! overwrites the previous capture for the same stream, and keyframes that arrive before the
+//! stream's sequence header are not captured.
+//!
+//! All media notifications are passed to subsequent steps untouched.
+
+#[cfg(test)]
+mod tests;
+
+use crate::codecs::VideoCodec;
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, SupportedCodecs, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
+use bytes::Bytes;
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{error, warn};
+
+pub const OUTPUT_DIR: &str = "output_dir";
+pub const INTERVAL_SECONDS: &str = "interval_seconds";
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+const ANNEX_B_START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// Generates new instances of the keyframe capture workflow step
+pub struct KeyframeCaptureStepGenerator {}
+
+struct StreamDetails {
+    stream_name: String,
+    parameter_sets: Option<ParameterSets>,
+    last_capture_dts: Option<Duration>,
+}
+
+/// The SPS and PPS units from an H264 sequence header, already in annex B format
+#[derive(Debug, PartialEq)]
+struct ParameterSets {
+    annex_b: Vec<u8>,
+    nalu_length_size: usize,
+}
+
+struct KeyframeCaptureStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    output_dir: String,
+    interval: Duration,
+    streams: HashMap<StreamId, StreamDetails>,
+}
+
+enum FutureResult {
+    OutputDirCreated(tokio::io::Result<()>),
+    CaptureWritten {
+        path: String,
+        result: tokio::io::Result<()>,
+    },
+}
+
+impl StepFutureResult for FutureResult {}
+
+struct StepParameters {
+    output_dir: String,
+    interval: Duration,
+}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error("No output directory specified.  A '{}' is required", OUTPUT_DIR)]
+    NoOutputDirProvided,
+
+    #[error(
+        "Invalid interval of '{0}'.  {} should be a positive number",
+        INTERVAL_SECONDS
+    )]
+    InvalidInterval(String),
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl KeyframeCaptureStepGenerator {
+    pub fn new() -> Self {
+        KeyframeCaptureStepGenerator {}
+    }
+}
+
+impl StepGenerator for KeyframeCaptureStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let StepParameters {
+            output_dir,
+            interval,
+        } = parse_parameters(&definition)?;
+
+        let step = KeyframeCaptureStep {
+            definition,
+            status: StepStatus::Created,
+            output_dir: output_dir.clone(),
+            interval,
+            streams: HashMap::new(),
+        };
+
+        let futures = vec![notify_when_output_dir_created(output_dir).boxed()];
+
+        Ok((Box::new(step), futures))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_parameters(definition)?;
+        Ok(())
+    }
+}
+
+fn parse_parameters(
+    definition: &WorkflowStepDefinition,
+) -> Result<StepParameters, StepStartupError> {
+    let output_dir = match definition.parameters.get(OUTPUT_DIR) {
+        Some(Some(value)) if !value.trim().is_empty() => value.trim().to_string(),
+        _ => return Err(StepStartupError::NoOutputDirProvided),
+    };
+
+    let interval = match definition.parameters.get(INTERVAL_SECONDS) {
+        Some(Some(value)) => match value.parse::<u64>() {
+            Ok(num) if num > 0 => Duration::from_secs(num),
+            _ => return Err(StepStartupError::InvalidInterval(value.clone())),
+        },
+
+        _ => DEFAULT_INTERVAL,
+    };
+
+    Ok(StepParameters {
+        output_dir,
+        interval,
+    })
+}
+
+impl KeyframeCaptureStep {
+    fn handle_media(&mut self, media: &MediaNotification, outputs: &mut StepOutputs) {
+        match &media.content {
+            MediaNotificationContent::NewIncomingStream { stream_name } => {
+                self.streams.insert(
+                    media.stream_id.clone(),
+                    StreamDetails {
+                        stream_name: stream_name.clone(),
+                        parameter_sets: None,
+                        last_capture_dts: None,
+                    },
+                );
+            }
+
+            MediaNotificationContent::StreamDisconnected => {
+                self.streams.remove(&media.stream_id);
+            }
+
+            MediaNotificationContent::Video {
+                codec: VideoCodec::H264,
+                is_sequence_header: true,
+                data,
+                ..
+            } => {
+                if let Some(stream) = self.streams.get_mut(&media.stream_id) {
+                    stream.parameter_sets = parse_sequence_header(data);
+                    if stream.parameter_sets.is_none() {
+                        warn!(
+                            stream_id = ?media.stream_id,
+                            "Failed to parse H264 sequence header for stream {:?}", media.stream_id
+                        );
+                    }
+                }
+            }
+
+            MediaNotificationContent::Video {
+                codec: VideoCodec::H264,
+                is_keyframe: true,
+                data,
+                timestamp,
+                ..
+            } => {
+                if self.status != StepStatus::Active {
+                    return; // Output directory isn't ready yet
+                }
+
+                let stream = match self.streams.get_mut(&media.stream_id) {
+                    Some(stream) => stream,
+                    None => return,
+                };
+
+                let parameter_sets = match &stream.parameter_sets {
+                    Some(parameter_sets) => parameter_sets,
+                    None => return, // Keyframe wouldn't be decodable without the SPS/PPS
+                };
+
+                let dts = timestamp.dts();
+                if let Some(last_dts) = stream.last_capture_dts {
+                    // A timestamp going backwards means the stream was reset, so capture anyway
+                    if dts >= last_dts && dts - last_dts < self.interval {
+                        return;
+                    }
+                }
+
+                let content = match create_capture(parameter_sets, data) {
+                    Some(content) => content,
+                    None => {
+                        warn!(
+                            stream_id = ?media.stream_id,
+                            "Keyframe for stream {:?} could not be converted to annex B", media.stream_id
+                        );
+
+                        return;
+                    }
+                };
+
+                stream.last_capture_dts = Some(dts);
+                let path = get_capture_path(&self.output_dir, &stream.stream_name);
+                outputs
+                    .futures
+                    .push(write_capture(path, content).boxed());
+            }
+
+            _ => (),
+        }
+    }
+}
+
+impl WorkflowStep for KeyframeCaptureStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for notification in inputs.notifications.drain(..) {
+            let future_result = match notification.downcast::<FutureResult>() {
+                Ok(result) => *result,
+                Err(_) => {
+                    error!("Keyframe capture step received a notification that is not a keyframe capture future result");
+                    self.status = StepStatus::Error {
+                        message: "Received a notification that is not a keyframe capture future result".to_string(),
+                    };
+
+                    return;
+                }
+            };
+
+            match future_result {
+                FutureResult::OutputDirCreated(result) => match result {
+                    Ok(()) => {
+                        self.status = StepStatus::Active;
+                    }
+
+                    Err(error) => {
+                        error!(
+                            "Could not create output directory '{}': {:?}",
+                            self.output_dir, error
+                        );
+
+                        self.status = StepStatus::Error {
+                            message: format!(
+                                "Could not create output directory '{}': {:?}",
+                                self.output_dir, error
+                            ),
+                        };
+
+                        return;
+                    }
+                },
+
+                FutureResult::CaptureWritten { path, result } => {
+                    if let Err(error) = result {
+                        error!("Failed to write keyframe capture '{}': {:?}", path, error);
+                    }
+                }
+            }
+        }
+
+        for media in inputs.media.drain(..) {
+            self.handle_media(&media, outputs);
+            outputs.media.push(media);
+        }
+    }
+
+    fn get_supported_codecs(&self) -> SupportedCodecs {
+        SupportedCodecs {
+            video: Some(&[VideoCodec::H264]),
+            audio: None,
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+    }
+}
+
+/// Reads the SPS and PPS units out of an AVC decoder configuration record and converts them to
+/// annex B format.
+fn parse_sequence_header(data: &[u8]) -> Option<ParameterSets> {
+    if data.len() < 6 || data[0] != 1 {
+        return None;
+    }
+
+    let nalu_length_size = ((data[4] & 0x03) + 1) as usize;
+    let mut annex_b = Vec::new();
+    let mut index = 5;
+
+    let sps_count = (data[index] & 0x1f) as usize;
+    index += 1;
+    for _ in 0..sps_count {
+        index = copy_parameter_set(data, index, &mut annex_b)?;
+    }
+
+    let pps_count = *data.get(index)? as usize;
+    index += 1;
+    for _ in 0..pps_count {
+        index = copy_parameter_set(data, index, &mut annex_b)?;
+    }
+
+    if annex_b.is_empty() {
+        return None;
+    }
+
+    Some(ParameterSets {
+        annex_b,
+        nalu_length_size,
+    })
+}
+
+/// Copies a 16 bit length prefixed parameter set into the annex B buffer, returning the index
+/// after the parameter set.
+fn copy_parameter_set(data: &[u8], index: usize, annex_b: &mut Vec<u8>) -> Option<usize> {
+    let length_bytes = data.get(index..index + 2)?;
+    let length = ((length_bytes[0] as usize) << 8) | length_bytes[1] as usize;
+    let start = index + 2;
+    let unit = data.get(start..start + length)?;
+
+    annex_b.extend_from_slice(&ANNEX_B_START_CODE);
+    annex_b.extend_from_slice(unit);
+
+    Some(start + length)
+}
+
+/// Creates the contents of a capture file by converting the length prefixed keyframe NAL units
+/// into annex B, prefixed with the stream's parameter sets.
+fn create_capture(parameter_sets: &ParameterSets, data: &Bytes) -> Option<Vec<u8>> {
+    let mut capture = parameter_sets.annex_b.clone();
+    let mut index = 0;
+    while index < data.len() {
+        let length_bytes = data.get(index..index + parameter_sets.nalu_length_size)?;
+        let length = length_bytes
+            .iter()
+            .fold(0_usize, |length, byte| (length << 8) | *byte as usize);
+
+        let start = index + parameter_sets.nalu_length_size;
+        let unit = data.get(start..start + length)?;
+
+        capture.extend_from_slice(&ANNEX_B_START_CODE);
+        capture.extend_from_slice(unit);
+        index = start + length;
+    }
+
+    Some(capture)
+}
+
+fn get_capture_path(output_dir: &str, stream_name: &str) -> String {
+    // Stream names come from clients, so don't allow them to escape the output directory
+    let file_name = stream_name.replace(|c: char| c == '/' || c == '\\' || c == '.', "_");
+    format!("{}/{}.h264", output_dir, file_name)
+}
+
+async fn notify_when_output_dir_created(path: String) -> Box<dyn StepFutureResult> {
+    let result = tokio::fs::create_dir_all(&path).await;
+    Box::new(FutureResult::OutputDirCreated(result))
+}
+
+async fn write_capture(path: String, content: Vec<u8>) -> Box<dyn StepFutureResult> {
+    // Write to a temporary file first so readers never see a partially written capture
+    let temp_path = format!("{}.tmp", path);
+    let result = match tokio::fs::write(&temp_path, content).await {
+        Ok(()) => tokio::fs::rename(&temp_path, &path).await,
+        Err(error) => Err(error),
+    };
+
+    Box::new(FutureResult::CaptureWritten { path, result })
+}
@@ -0,0 +1,335 @@
+use super::*;
+use crate::codecs::AudioCodec;
+use crate::workflows::steps::test_utils::create_definition;
+use crate::workflows::steps::StepTestContext;
+use crate::VideoTimestamp;
+use std::path::{Path, PathBuf};
+
+const SPS: [u8; 4] = [0x67, 0x64, 0x00, 0x1f];
+const PPS: [u8; 3] = [0x68, 0xeb, 0xe3];
+
+fn get_test_dir(name: &str) -> PathBuf {
+    let mut path = std::env::temp_dir();
+    path.push(format!("mmids-keyframe-capture-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+
+    path
+}
+
+fn sequence_header() -> Bytes {
+    let mut data = vec![0x01, 0x64, 0x00, 0x1f, 0xff, 0xe1];
+    data.extend_from_slice(&(SPS.len() as u16).to_be_bytes());
+    data.extend_from_slice(&SPS);
+    data.push(0x01);
+    data.extend_from_slice(&(PPS.len() as u16).to_be_bytes());
+    data.extend_from_slice(&PPS);
+
+    Bytes::from(data)
+}
+
+fn length_prefixed_nalus(nalus: &[&[u8]]) -> Bytes {
+    let mut data = Vec::new();
+    for nalu in nalus {
+        data.extend_from_slice(&(nalu.len() as u32).to_be_bytes());
+        data.extend_from_slice(nalu);
+    }
+
+    Bytes::from(data)
+}
+
+fn new_stream() -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn video(data: Bytes, is_keyframe: bool, is_sequence_header: bool, dts: u64) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            is_keyframe,
+            is_sequence_header,
+            data,
+            timestamp: VideoTimestamp::from_durations(
+                Duration::from_secs(dts),
+                Duration::from_secs(dts),
+            ),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn expected_capture(keyframe_nalus: &[&[u8]]) -> Vec<u8> {
+    let mut expected = vec![0, 0, 0, 1];
+    expected.extend_from_slice(&SPS);
+    expected.extend_from_slice(&[0, 0, 0, 1]);
+    expected.extend_from_slice(&PPS);
+    for nalu in keyframe_nalus {
+        expected.extend_from_slice(&[0, 0, 0, 1]);
+        expected.extend_from_slice(nalu);
+    }
+
+    expected
+}
+
+async fn create_active_context(dir: &Path, interval: Option<&str>) -> StepTestContext {
+    let mut definition =
+        create_definition("keyframe_capture", &[(OUTPUT_DIR, dir.to_str().unwrap())]);
+    if let Some(interval) = interval {
+        definition
+            .parameters
+            .insert(INTERVAL_SECONDS.to_string(), Some(interval.to_string()));
+    }
+
+    let generator = KeyframeCaptureStepGenerator::new();
+    let mut context = StepTestContext::new(Box::new(generator), definition).unwrap();
+    context.execute_pending_notifications().await;
+
+    assert_eq!(
+        context.step.get_status(),
+        &StepStatus::Active,
+        "Unexpected step status"
+    );
+
+    context
+}
+
+#[test]
+fn step_fails_to_generate_without_output_dir() {
+    let mut definition = create_definition("keyframe_capture", &[(OUTPUT_DIR, "dir")]);
+    definition.parameters.remove(OUTPUT_DIR);
+
+    let generator = KeyframeCaptureStepGenerator::new();
+    assert!(generator.generate(definition).is_err());
+}
+
+#[test]
+fn step_fails_to_generate_with_invalid_interval() {
+    for interval in ["abc", "0", "-1"] {
+        let definition = create_definition(
+            "keyframe_capture",
+            &[(OUTPUT_DIR, "dir"), (INTERVAL_SECONDS, interval)],
+        );
+        let generator = KeyframeCaptureStepGenerator::new();
+        assert!(
+            generator.generate(definition).is_err(),
+            "Expected error for interval of '{}'",
+            interval
+        );
+    }
+}
+
+#[test]
+fn validation_fails_with_invalid_interval() {
+    let definition = create_definition(
+        "keyframe_capture",
+        &[(OUTPUT_DIR, "dir"), (INTERVAL_SECONDS, "abc")],
+    );
+    let generator = KeyframeCaptureStepGenerator::new();
+    assert!(generator.validate(&definition).is_err());
+}
+
+#[test]
+fn validation_does_not_create_output_dir() {
+    let dir = get_test_dir("validate");
+    let definition = create_definition("keyframe_capture", &[(OUTPUT_DIR, dir.to_str().unwrap())]);
+    let generator = KeyframeCaptureStepGenerator::new();
+
+    generator.validate(&definition).unwrap();
+    assert!(!dir.exists(), "Output directory was created");
+}
+
+#[test]
+fn can_parse_sequence_header() {
+    let parameter_sets = parse_sequence_header(&sequence_header()).unwrap();
+
+    assert_eq!(
+        parameter_sets.nalu_length_size, 4,
+        "Unexpected nalu length size"
+    );
+    assert_eq!(
+        parameter_sets.annex_b,
+        expected_capture(&[]),
+        "Unexpected annex b parameter sets"
+    );
+}
+
+#[test]
+fn truncated_sequence_header_is_not_parsed() {
+    let header = sequence_header();
+    let truncated = &header[..header.len() - 1];
+
+    assert_eq!(parse_sequence_header(truncated), None);
+}
+
+#[tokio::test]
+async fn media_is_passed_through() {
+    let dir = get_test_dir("passthrough");
+    let mut context = create_active_context(&dir, None).await;
+
+    context.assert_media_passed_through(new_stream());
+    context.assert_media_passed_through(video(sequence_header(), true, true, 0));
+    context.assert_media_passed_through(video(
+        length_prefixed_nalus(&[&[0x65, 1, 2]]),
+        true,
+        false,
+        0,
+    ));
+    context.assert_media_passed_through(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Audio {
+            codec: AudioCodec::Aac,
+            is_sequence_header: false,
+            data: Bytes::from(vec![1, 2, 3]),
+            timestamp: Duration::from_millis(5),
+        },
+        tags: Vec::new(),
+    });
+    context.assert_media_passed_through(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
+    });
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn keyframe_written_with_parameter_sets() {
+    let dir = get_test_dir("written");
+    let mut context = create_active_context(&dir, None).await;
+
+    let nalus: [&[u8]; 2] = [&[0x06, 9, 9], &[0x65, 1, 2, 3]];
+    context.execute_with_media(new_stream());
+    context.execute_with_media(video(sequence_header(), true, true, 0));
+    context.execute_with_media(video(length_prefixed_nalus(&nalus), true, false, 0));
+    context.execute_pending_notifications().await;
+
+    let content = std::fs::read(dir.join("def.h264")).expect("Failed to read capture");
+    assert_eq!(content, expected_capture(&nalus), "Unexpected capture");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn keyframe_not_written_without_sequence_header() {
+    let dir = get_test_dir("no-header");
+    let mut context = create_active_context(&dir, None).await;
+
+    context.execute_with_media(new_stream());
+    context.execute_with_media(video(
+        length_prefixed_nalus(&[&[0x65, 1, 2]]),
+        true,
+        false,
+        0,
+    ));
+    context.execute_pending_notifications().await;
+
+    assert!(
+        !dir.join("def.h264").exists(),
+        "Expected no capture to be written"
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn non_keyframes_are_not_written() {
+    let dir = get_test_dir("non-keyframe");
+    let mut context = create_active_context(&dir, None).await;
+
+    context.execute_with_media(new_stream());
+    context.execute_with_media(video(sequence_header(), true, true, 0));
+    context.execute_with_media(video(
+        length_prefixed_nalus(&[&[0x41, 1, 2]]),
+        false,
+        false,
+        0,
+    ));
+    context.execute_pending_notifications().await;
+
+    assert!(
+        !dir.join("def.h264").exists(),
+        "Expected no capture to be written"
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn keyframes_within_interval_are_not_written() {
+    let dir = get_test_dir("interval");
+    let mut context = create_active_context(&dir, Some("5")).await;
+
+    let first: [&[u8]; 1] = [&[0x65, 1]];
+    let second: [&[u8]; 1] = [&[0x65, 2]];
+    let third: [&[u8]; 1] = [&[0x65, 3]];
+
+    context.execute_with_media(new_stream());
+    context.execute_with_media(video(sequence_header(), true, true, 0));
+    context.execute_with_media(video(length_prefixed_nalus(&first), true, false, 10));
+    context.execute_pending_notifications().await;
+
+    context.execute_with_media(video(length_prefixed_nalus(&second), true, false, 14));
+    context.execute_pending_notifications().await;
+
+    let content = std::fs::read(dir.join("def.h264")).expect("Failed to read capture");
+    assert_eq!(content, expected_capture(&first), "Unexpected capture");
+
+    context.execute_with_media(video(length_prefixed_nalus(&third), true, false, 15));
+    context.execute_pending_notifications().await;
+
+    let content = std::fs::read(dir.join("def.h264")).expect("Failed to read capture");
+    assert_eq!(content, expected_capture(&third), "Unexpected capture");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn capture_path_does_not_allow_directory_traversal() {
+    assert_eq!(
+        get_capture_path("/captures", "../etc/passwd"),
+        "/captures/___etc_passwd.h264"
+    );
+}
+
+#[tokio::test]
+async fn only_h264_video_is_supported() {
+    let dir = get_test_dir("codecs");
+    let context = create_active_context(&dir, None).await;
+    let codecs = context.step.get_supported_codecs();
+
+    let unknown_video = MediaNotificationContent::Video {
+        codec: VideoCodec::Unknown,
+        is_keyframe: true,
+        is_sequence_header: false,
+        data: Bytes::from(vec![1, 2, 3]),
+        timestamp: VideoTimestamp::from_zero(),
+    };
+
+    let unknown_audio = MediaNotificationContent::Audio {
+        codec: AudioCodec::Unknown,
+        is_sequence_header: false,
+        data: Bytes::from(vec![1, 2, 3]),
+        timestamp: Duration::from_millis(0),
+    };
+
+    assert_eq!(
+        codecs.get_unsupported_codec(&unknown_video),
+        Some("Unknown video".to_string()),
+        "Expected unknown video codec to be unsupported"
+    );
+
+    assert_eq!(
+        codecs.get_unsupported_codec(&unknown_audio),
+        None,
+        "Expected audio codecs to not be checked"
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
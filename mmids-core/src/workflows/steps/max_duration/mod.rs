@@ -0,0 +1,233 @@
+//! The max duration step disconnects streams once they have been connected for a configured
+//! amount of time, such as for trial or otherwise limited sessions.  The time is measured from
+//! when the step receives the stream's new incoming stream notification, and another new incoming
+//! stream notification for the same stream (e.g. a reconnect) restarts it.
+//!
+//! Media is passed to subsequent steps untouched until the limit is reached, at which point a
+//! stream disconnected notification is raised for the stream.  Any media received for the stream
+//! after that is dropped until the stream is announced again.
+
+#[cfg(test)]
+mod tests;
+
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{error, info};
+
+pub const MAX_SECONDS: &str = "max_seconds";
+
+/// Generates new instances of the max duration workflow step based on specified step definitions.
+pub struct MaxDurationStepGenerator {}
+
+struct StreamDetails {
+    /// Identifies the timer for the stream's current session, so timers from previous sessions
+    /// of the same stream are ignored
+    timer_id: u64,
+    limit_reached: bool,
+}
+
+struct MaxDurationStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    max_duration: Duration,
+    streams: HashMap<StreamId, StreamDetails>,
+    next_timer_id: u64,
+}
+
+enum FutureResult {
+    LimitReached { stream_id: StreamId, timer_id: u64 },
+}
+
+impl StepFutureResult for FutureResult {}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error(
+        "No maximum duration specified.  A '{}' parameter is required",
+        MAX_SECONDS
+    )]
+    NoMaxDurationProvided,
+
+    #[error(
+        "Invalid maximum duration of '{0}'.  {} should be a positive number of seconds",
+        MAX_SECONDS
+    )]
+    InvalidMaxDuration(String),
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl MaxDurationStepGenerator {
+    pub fn new() -> Self {
+        MaxDurationStepGenerator {}
+    }
+}
+
+impl StepGenerator for MaxDurationStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let max_duration = parse_max_duration(&definition)?;
+        let step = MaxDurationStep {
+            definition: definition.clone(),
+            status: StepStatus::Active,
+            max_duration,
+            streams: HashMap::new(),
+            next_timer_id: 0,
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_max_duration(definition)?;
+        Ok(())
+    }
+}
+
+fn parse_max_duration(definition: &WorkflowStepDefinition) -> Result<Duration, StepStartupError> {
+    match definition.parameters.get(MAX_SECONDS) {
+        Some(Some(value)) => match value.parse::<u64>() {
+            Ok(num) if num > 0 => Ok(Duration::from_secs(num)),
+            _ => Err(StepStartupError::InvalidMaxDuration(value.clone())),
+        },
+
+        _ => Err(StepStartupError::NoMaxDurationProvided),
+    }
+}
+
+impl MaxDurationStep {
+    fn handle_media(&mut self, media: MediaNotification, outputs: &mut StepOutputs) {
+        match &media.content {
+            MediaNotificationContent::NewIncomingStream { .. } => {
+                let timer_id = self.next_timer_id;
+                self.next_timer_id += 1;
+                self.streams.insert(
+                    media.stream_id.clone(),
+                    StreamDetails {
+                        timer_id,
+                        limit_reached: false,
+                    },
+                );
+
+                outputs.futures.push(
+                    wait_for_limit(media.stream_id.clone(), timer_id, self.max_duration).boxed(),
+                );
+            }
+
+            MediaNotificationContent::StreamDisconnected => {
+                if let Some(stream) = self.streams.remove(&media.stream_id) {
+                    if stream.limit_reached {
+                        return; // Subsequent steps were already told the stream disconnected
+                    }
+                }
+            }
+
+            _ => {
+                if let Some(stream) = self.streams.get(&media.stream_id) {
+                    if stream.limit_reached {
+                        return;
+                    }
+                }
+            }
+        }
+
+        outputs.media.push(media);
+    }
+
+    fn handle_limit_reached(&mut self, stream_id: StreamId, timer_id: u64) -> Option<StreamId> {
+        let stream = match self.streams.get_mut(&stream_id) {
+            Some(stream) => stream,
+            None => return None,
+        };
+
+        if stream.timer_id != timer_id || stream.limit_reached {
+            return None; // Timer from a previous session of this stream
+        }
+
+        info!(
+            stream_id = ?stream_id,
+            "Stream {:?} reached its maximum duration of {:?}, disconnecting it",
+            stream_id, self.max_duration
+        );
+
+        stream.limit_reached = true;
+        Some(stream_id)
+    }
+}
+
+impl WorkflowStep for MaxDurationStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for notification in inputs.notifications.drain(..) {
+            let future_result = match notification.downcast::<FutureResult>() {
+                Ok(result) => *result,
+                Err(_) => {
+                    error!("Max duration step received a notification that is not a max duration future result");
+                    self.status = StepStatus::Error {
+                        message: "Received a notification that is not a max duration future result"
+                            .to_string(),
+                    };
+
+                    return;
+                }
+            };
+
+            match future_result {
+                FutureResult::LimitReached {
+                    stream_id,
+                    timer_id,
+                } => {
+                    if let Some(stream_id) = self.handle_limit_reached(stream_id, timer_id) {
+                        outputs.media.push(MediaNotification {
+                            stream_id,
+                            content: MediaNotificationContent::StreamDisconnected,
+                            tags: Vec::new(),
+                        });
+                    }
+                }
+            }
+        }
+
+        for media in inputs.media.drain(..) {
+            self.handle_media(media, outputs);
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+        self.streams.clear();
+    }
+}
+
+async fn wait_for_limit(
+    stream_id: StreamId,
+    timer_id: u64,
+    max_duration: Duration,
+) -> Box<dyn StepFutureResult> {
+    tokio::time::sleep(max_duration).await;
+
+    Box::new(FutureResult::LimitReached {
+        stream_id,
+        timer_id,
+    })
+}
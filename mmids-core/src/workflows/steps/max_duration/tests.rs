@@ -0,0 +1,162 @@
+use super::*;
+use crate::workflows::steps::test_utils::{
+    create_definition, disconnected_content, media, new_stream_content, video_content,
+};
+use crate::workflows::steps::StepTestContext;
+
+fn create_context() -> StepTestContext {
+    let generator = MaxDurationStepGenerator::new();
+    StepTestContext::new(
+        Box::new(generator),
+        create_definition("max_duration", &[(MAX_SECONDS, "60")]),
+    )
+    .unwrap()
+}
+
+/// Timer ids are assigned sequentially starting at zero, in the order streams are announced
+fn limit_reached(stream_id: &str, timer_id: u64) -> Box<dyn StepFutureResult> {
+    Box::new(FutureResult::LimitReached {
+        stream_id: StreamId(stream_id.to_string()),
+        timer_id,
+    })
+}
+
+#[test]
+fn step_fails_to_generate_without_max_seconds() {
+    let generator = MaxDurationStepGenerator::new();
+    assert!(generator
+        .generate(create_definition("max_duration", &[]))
+        .is_err());
+}
+
+#[test]
+fn step_fails_to_generate_with_zero_max_seconds() {
+    let generator = MaxDurationStepGenerator::new();
+    assert!(generator
+        .generate(create_definition("max_duration", &[(MAX_SECONDS, "0")]))
+        .is_err());
+}
+
+#[test]
+fn step_fails_to_generate_with_non_numeric_max_seconds() {
+    let generator = MaxDurationStepGenerator::new();
+    assert!(generator
+        .generate(create_definition("max_duration", &[(MAX_SECONDS, "abc")]))
+        .is_err());
+}
+
+#[test]
+fn validation_passes_with_valid_max_seconds() {
+    let generator = MaxDurationStepGenerator::new();
+    assert!(generator
+        .validate(&create_definition("max_duration", &[(MAX_SECONDS, "30")]))
+        .is_ok());
+}
+
+#[test]
+fn new_stream_passed_through_and_starts_timer() {
+    let mut context = create_context();
+    context.assert_media_passed_through(media("abc", new_stream_content("def")));
+
+    assert_eq!(context.futures.len(), 1, "Expected a timer future");
+}
+
+#[test]
+fn media_passed_through_before_limit_reached() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+
+    context.assert_media_passed_through(media(
+        "abc",
+        video_content(true, false, Duration::from_millis(5)),
+    ));
+}
+
+#[test]
+fn media_for_unknown_stream_passed_through() {
+    let mut context = create_context();
+    context.assert_media_passed_through(media(
+        "abc",
+        video_content(true, false, Duration::from_millis(5)),
+    ));
+}
+
+#[tokio::test]
+async fn stream_disconnected_when_limit_reached() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    context.execute_notification(limit_reached("abc", 0)).await;
+
+    assert_eq!(
+        context.media_outputs,
+        vec![media("abc", disconnected_content())],
+        "Unexpected media outputs"
+    );
+}
+
+#[tokio::test]
+async fn media_dropped_after_limit_reached() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    context.execute_notification(limit_reached("abc", 0)).await;
+
+    context.assert_media_not_passed_through(media(
+        "abc",
+        video_content(true, false, Duration::from_millis(5)),
+    ));
+}
+
+#[tokio::test]
+async fn disconnect_not_passed_through_after_limit_reached() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    context.execute_notification(limit_reached("abc", 0)).await;
+
+    context.assert_media_not_passed_through(media("abc", disconnected_content()));
+}
+
+#[tokio::test]
+async fn reconnect_after_limit_reached_passes_media_through() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    context.execute_notification(limit_reached("abc", 0)).await;
+
+    context.assert_media_passed_through(media("abc", new_stream_content("def")));
+    context.assert_media_passed_through(media(
+        "abc",
+        video_content(true, false, Duration::from_millis(5)),
+    ));
+}
+
+#[tokio::test]
+async fn reconnect_resets_timer() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    context.execute_with_media(media("abc", new_stream_content("def")));
+
+    context.execute_notification(limit_reached("abc", 0)).await;
+    assert!(
+        context.media_outputs.is_empty(),
+        "Expected no media outputs for previous session's timer"
+    );
+
+    context.execute_notification(limit_reached("abc", 1)).await;
+    assert_eq!(
+        context.media_outputs,
+        vec![media("abc", disconnected_content())],
+        "Unexpected media outputs"
+    );
+}
+
+#[tokio::test]
+async fn timer_ignored_after_stream_disconnects() {
+    let mut context = create_context();
+    context.execute_with_media(media("abc", new_stream_content("def")));
+    context.execute_with_media(media("abc", disconnected_content()));
+    context.execute_notification(limit_reached("abc", 0)).await;
+
+    assert!(
+        context.media_outputs.is_empty(),
+        "Expected no media outputs"
+    );
+}
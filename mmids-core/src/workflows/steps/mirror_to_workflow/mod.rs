@@ -0,0 +1,293 @@
+//! The mirror to workflow step sends a copy of every media notification it receives to another
+//! workflow, by way of the workflow manager.  All media notifications are also passed to
+//! subsequent steps unchanged, so the target workflow can process the stream independently of
+//! the workflow the step is in.
+//!
+//! The workflow manager is found via the event hub.  If the target workflow is not running when
+//! a stream starts, that stream is not mirrored until it reconnects.  A missing target workflow
+//! is logged but never puts the step into an error state.
+
+#[cfg(test)]
+mod tests;
+
+use crate::event_hub::{SubscriptionRequest, WorkflowManagerEvent};
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::manager::{WorkflowManagerRequest, WorkflowManagerRequestOperation};
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
+use futures::FutureExt;
+use std::collections::HashSet;
+use thiserror::Error;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot::{channel, Receiver};
+use tracing::{error, info, warn};
+
+pub const TARGET_WORKFLOW: &str = "target_workflow";
+
+/// Generates new instances of the mirror to workflow step
+pub struct MirrorToWorkflowStepGenerator {
+    event_hub_subscriber: UnboundedSender<SubscriptionRequest>,
+}
+
+struct MirrorToWorkflowStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    target_workflow: String,
+    workflow_manager: Option<UnboundedSender<WorkflowManagerRequest>>,
+
+    /// Streams whose media is being mirrored to the target workflow
+    mirrored_streams: HashSet<StreamId>,
+}
+
+enum FutureResult {
+    EventHubGone,
+    WorkflowManagerEventReceived(
+        WorkflowManagerEvent,
+        UnboundedReceiver<WorkflowManagerEvent>,
+    ),
+
+    MirrorResponseReceived {
+        stream_id: StreamId,
+        workflow_was_running: bool,
+    },
+}
+
+impl StepFutureResult for FutureResult {}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error(
+        "No target workflow specified.  A '{}' parameter is required",
+        TARGET_WORKFLOW
+    )]
+    NoTargetWorkflowSpecified,
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl MirrorToWorkflowStepGenerator {
+    pub fn new(event_hub_subscriber: UnboundedSender<SubscriptionRequest>) -> Self {
+        MirrorToWorkflowStepGenerator {
+            event_hub_subscriber,
+        }
+    }
+}
+
+impl StepGenerator for MirrorToWorkflowStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let target_workflow = parse_target_workflow(&definition)?;
+
+        let (event_sender, event_receiver) = unbounded_channel();
+        let _ = self
+            .event_hub_subscriber
+            .send(SubscriptionRequest::WorkflowManagerEvents {
+                channel: event_sender,
+            });
+
+        let step = MirrorToWorkflowStep {
+            definition: definition.clone(),
+            status: StepStatus::Active,
+            target_workflow,
+            workflow_manager: None,
+            mirrored_streams: HashSet::new(),
+        };
+
+        let futures = vec![wait_for_workflow_manager_event(event_receiver).boxed()];
+
+        Ok((Box::new(step), futures))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_target_workflow(definition)?;
+        Ok(())
+    }
+}
+
+fn parse_target_workflow(definition: &WorkflowStepDefinition) -> Result<String, StepStartupError> {
+    match definition.parameters.get(TARGET_WORKFLOW) {
+        Some(Some(name)) => Ok(name.clone()),
+        _ => Err(StepStartupError::NoTargetWorkflowSpecified),
+    }
+}
+
+impl MirrorToWorkflowStep {
+    fn handle_media(&mut self, media: &MediaNotification, outputs: &mut StepOutputs) {
+        match &media.content {
+            MediaNotificationContent::NewIncomingStream { .. } => {
+                let manager = match &self.workflow_manager {
+                    Some(manager) => manager,
+                    None => {
+                        warn!(
+                            stream_id = ?media.stream_id,
+                            "No workflow manager is known yet, so stream {:?} will not be mirrored",
+                            media.stream_id
+                        );
+
+                        return;
+                    }
+                };
+
+                // We need to know if the target workflow exists, otherwise we'd keep sending it
+                // media that will just be dropped
+                let (sender, receiver) = channel();
+                let _ = manager.send(WorkflowManagerRequest {
+                    request_id: "mirror-to-workflow".to_string(),
+                    operation: WorkflowManagerRequestOperation::SendMediaToWorkflow {
+                        name: self.target_workflow.clone(),
+                        media: media.clone(),
+                        response_channel: Some(sender),
+                    },
+                });
+
+                outputs
+                    .futures
+                    .push(wait_for_mirror_response(media.stream_id.clone(), receiver).boxed());
+
+                self.mirrored_streams.insert(media.stream_id.clone());
+            }
+
+            MediaNotificationContent::StreamDisconnected => {
+                if self.mirrored_streams.remove(&media.stream_id) {
+                    self.send_to_target(media.clone());
+                }
+            }
+
+            _ => {
+                if self.mirrored_streams.contains(&media.stream_id) {
+                    self.send_to_target(media.clone());
+                }
+            }
+        }
+    }
+
+    fn send_to_target(&self, media: MediaNotification) {
+        if let Some(manager) = &self.workflow_manager {
+            let _ = manager.send(WorkflowManagerRequest {
+                request_id: "mirror-to-workflow".to_string(),
+                operation: WorkflowManagerRequestOperation::SendMediaToWorkflow {
+                    name: self.target_workflow.clone(),
+                    media,
+                    response_channel: None,
+                },
+            });
+        }
+    }
+}
+
+impl WorkflowStep for MirrorToWorkflowStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for notification in inputs.notifications.drain(..) {
+            let future_result = match notification.downcast::<FutureResult>() {
+                Ok(x) => *x,
+                Err(_) => {
+                    error!(
+                        "Mirror to workflow step received a notification that is not a known type"
+                    );
+
+                    self.status = StepStatus::Error {
+                        message: "Received future result of unknown type".to_string(),
+                    };
+
+                    return;
+                }
+            };
+
+            match future_result {
+                FutureResult::EventHubGone => {
+                    error!("Received a notification that the event hub is gone");
+                    self.status = StepStatus::Error {
+                        message: "Event hub gone".to_string(),
+                    };
+
+                    return;
+                }
+
+                FutureResult::WorkflowManagerEventReceived(event, receiver) => {
+                    outputs
+                        .futures
+                        .push(wait_for_workflow_manager_event(receiver).boxed());
+
+                    match event {
+                        WorkflowManagerEvent::WorkflowManagerRegistered { channel } => {
+                            info!("Mirror to workflow step received a workflow manager channel");
+                            self.workflow_manager = Some(channel);
+                        }
+                    }
+                }
+
+                FutureResult::MirrorResponseReceived {
+                    stream_id,
+                    workflow_was_running,
+                } => {
+                    if !workflow_was_running && self.mirrored_streams.remove(&stream_id) {
+                        warn!(
+                            stream_id = ?stream_id,
+                            "Target workflow '{}' is not running, so stream {:?} will not be mirrored",
+                            self.target_workflow, stream_id
+                        );
+                    }
+                }
+            }
+        }
+
+        for media in inputs.media.drain(..) {
+            self.handle_media(&media, outputs);
+            outputs.media.push(media);
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+
+        // Let the target workflow know not to expect any more media from the mirrored streams
+        let stream_ids = self.mirrored_streams.drain().collect::<Vec<_>>();
+        for stream_id in stream_ids {
+            self.send_to_target(MediaNotification {
+                stream_id,
+                content: MediaNotificationContent::StreamDisconnected,
+                tags: Vec::new(),
+            });
+        }
+    }
+}
+
+async fn wait_for_workflow_manager_event(
+    mut receiver: UnboundedReceiver<WorkflowManagerEvent>,
+) -> Box<dyn StepFutureResult> {
+    let result = match receiver.recv().await {
+        Some(event) => FutureResult::WorkflowManagerEventReceived(event, receiver),
+        None => FutureResult::EventHubGone,
+    };
+
+    Box::new(result)
+}
+
+async fn wait_for_mirror_response(
+    stream_id: StreamId,
+    receiver: Receiver<bool>,
+) -> Box<dyn StepFutureResult> {
+    // If the workflow manager went away without responding the media was never delivered
+    let workflow_was_running = receiver.await.unwrap_or(false);
+
+    Box::new(FutureResult::MirrorResponseReceived {
+        stream_id,
+        workflow_was_running,
+    })
+}
@@ -0,0 +1,207 @@
+use super::*;
+use crate::codecs::VideoCodec;
+use crate::workflows::steps::test_utils::create_definition;
+use crate::workflows::steps::StepTestContext;
+use crate::{test_utils, VideoTimestamp};
+use bytes::Bytes;
+use tokio::sync::oneshot::Sender;
+
+struct TestContext {
+    step_context: StepTestContext,
+    manager: UnboundedReceiver<WorkflowManagerRequest>,
+    manager_sender: UnboundedSender<WorkflowManagerRequest>,
+    manager_event_channel: UnboundedSender<WorkflowManagerEvent>,
+}
+
+impl TestContext {
+    async fn new() -> Self {
+        let (sub_sender, mut sub_receiver) = unbounded_channel();
+        let (manager_sender, manager_receiver) = unbounded_channel();
+        let generator = MirrorToWorkflowStepGenerator::new(sub_sender);
+        let step_context = StepTestContext::new(
+            Box::new(generator),
+            create_definition("mirror_to_workflow", &[(TARGET_WORKFLOW, "target")]),
+        )
+        .unwrap();
+
+        // It must subscribe to workflow manager events on startup
+        let event = test_utils::expect_mpsc_response(&mut sub_receiver).await;
+        let channel = match event {
+            SubscriptionRequest::WorkflowManagerEvents { channel } => channel,
+            event => panic!("Unexpected event: {:?}", event),
+        };
+
+        TestContext {
+            step_context,
+            manager: manager_receiver,
+            manager_sender,
+            manager_event_channel: channel,
+        }
+    }
+
+    async fn register_workflow_manager(&mut self) {
+        self.manager_event_channel
+            .send(WorkflowManagerEvent::WorkflowManagerRegistered {
+                channel: self.manager_sender.clone(),
+            })
+            .expect("Failed to send workflow manager registered event");
+
+        let result = test_utils::expect_future_resolved(&mut self.step_context.futures).await;
+        self.step_context.execute_notification(result).await;
+    }
+
+    /// Expects media to be sent to the target workflow, returning the media and the response
+    /// channel that was included with it
+    async fn expect_mirrored_media(&mut self) -> (MediaNotification, Option<Sender<bool>>) {
+        let request = test_utils::expect_mpsc_response(&mut self.manager).await;
+        match request.operation {
+            WorkflowManagerRequestOperation::SendMediaToWorkflow {
+                name,
+                media,
+                response_channel,
+            } => {
+                assert_eq!(&name, "target", "Unexpected target workflow");
+                (media, response_channel)
+            }
+
+            operation => panic!("Unexpected manager operation: {:?}", operation),
+        }
+    }
+
+    /// Starts the stream and responds to the mirrored new stream notification
+    async fn start_stream(&mut self, target_is_running: bool) {
+        self.step_context.assert_media_passed_through(new_stream());
+
+        let (media, response_channel) = self.expect_mirrored_media().await;
+        assert_eq!(media, new_stream(), "Unexpected mirrored media");
+
+        response_channel
+            .expect("Expected a response channel for the new stream")
+            .send(target_is_running)
+            .expect("Failed to send response");
+
+        let result = test_utils::expect_future_resolved(&mut self.step_context.futures).await;
+        self.step_context.execute_notification(result).await;
+    }
+}
+
+fn new_stream() -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn video() -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            is_keyframe: true,
+            is_sequence_header: false,
+            data: Bytes::from(vec![1, 2, 3]),
+            timestamp: VideoTimestamp::from_zero(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn disconnection() -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
+    }
+}
+
+#[test]
+fn validation_fails_without_target_workflow() {
+    let generator = MirrorToWorkflowStepGenerator::new(unbounded_channel().0);
+    let definition = create_definition("mirror_to_workflow", &[]);
+
+    assert!(generator.validate(&definition).is_err());
+}
+
+#[test]
+fn validation_does_not_subscribe_to_event_hub() {
+    let (sub_sender, mut sub_receiver) = unbounded_channel();
+    let generator = MirrorToWorkflowStepGenerator::new(sub_sender);
+
+    generator
+        .validate(&create_definition(
+            "mirror_to_workflow",
+            &[(TARGET_WORKFLOW, "target")],
+        ))
+        .unwrap();
+    assert!(
+        sub_receiver.try_recv().is_err(),
+        "Expected no event hub subscriptions"
+    );
+}
+
+#[tokio::test]
+async fn media_mirrored_to_target_workflow_and_passed_through() {
+    let mut context = TestContext::new().await;
+    context.register_workflow_manager().await;
+    context.start_stream(true).await;
+
+    context.step_context.assert_media_passed_through(video());
+    let (media, response_channel) = context.expect_mirrored_media().await;
+    assert_eq!(media, video(), "Unexpected mirrored media");
+    assert!(
+        response_channel.is_none(),
+        "Expected no response channel for video"
+    );
+
+    context
+        .step_context
+        .assert_media_passed_through(disconnection());
+    let (media, _) = context.expect_mirrored_media().await;
+    assert_eq!(media, disconnection(), "Unexpected mirrored media");
+}
+
+#[tokio::test]
+async fn media_not_mirrored_when_target_workflow_not_running() {
+    let mut context = TestContext::new().await;
+    context.register_workflow_manager().await;
+    context.start_stream(false).await;
+
+    context.step_context.assert_media_passed_through(video());
+    context
+        .step_context
+        .assert_media_passed_through(disconnection());
+
+    test_utils::expect_mpsc_timeout(&mut context.manager).await;
+    assert_eq!(
+        context.step_context.step.get_status(),
+        &StepStatus::Active,
+        "Unexpected step status"
+    );
+}
+
+#[tokio::test]
+async fn media_passed_through_when_no_workflow_manager_known() {
+    let mut context = TestContext::new().await;
+
+    context
+        .step_context
+        .assert_media_passed_through(new_stream());
+    context.step_context.assert_media_passed_through(video());
+
+    test_utils::expect_mpsc_timeout(&mut context.manager).await;
+}
+
+#[tokio::test]
+async fn disconnection_mirrored_for_active_streams_on_shutdown() {
+    let mut context = TestContext::new().await;
+    context.register_workflow_manager().await;
+    context.start_stream(true).await;
+
+    context.step_context.step.shutdown();
+
+    let (media, _) = context.expect_mirrored_media().await;
+    assert_eq!(media, disconnection(), "Unexpected mirrored media");
+}
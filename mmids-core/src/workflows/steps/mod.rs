@@ -1,21 +1,51 @@
 //! Workflow steps are individual actions that can be taken on media as part of a media pipeline.
 
+pub mod audio_only;
+pub mod bitrate_guard;
+pub mod cue_inject;
+pub mod demux;
+pub mod drop_bframes;
+pub mod exec_hook;
 mod external_stream_handler;
 mod external_stream_reader;
 pub mod factory;
+pub mod failover;
 mod ffmpeg_handler;
 pub mod ffmpeg_hls;
 pub mod ffmpeg_pull;
 pub mod ffmpeg_rtmp_push;
 pub mod ffmpeg_transcode;
+pub mod filter;
+pub mod gap_monitor;
+pub mod interleave;
+pub mod keyframe_capture;
+pub mod max_duration;
+pub mod mirror_to_workflow;
+pub mod normalize_timestamps;
+pub mod record;
+pub mod rename_stream;
+pub mod rtmp_pull;
+pub mod rtmp_push;
 pub mod rtmp_receive;
 pub mod rtmp_watch;
+pub mod srt_receive;
+pub mod stream_stats;
+pub mod tag;
+#[cfg(any(test, feature = "test-source"))]
+pub mod test_source;
+#[cfg(test)]
+pub mod test_utils;
 pub mod workflow_forwarder;
 
-use super::MediaNotification;
+use super::{MediaNotification, MediaNotificationContent};
+use crate::codecs::{AudioCodec, VideoCodec};
 use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::stream_stats::StreamStatistics;
+use crate::StreamId;
 use downcast_rs::{impl_downcast, Downcast};
 use futures::future::BoxFuture;
+use std::collections::HashMap;
+use thiserror::Error;
 
 pub use external_stream_handler::*;
 pub use external_stream_reader::*;
@@ -26,13 +56,29 @@ pub trait StepFutureResult: Downcast {}
 impl_downcast!(StepFutureResult);
 
 pub type FutureList = Vec<BoxFuture<'static, Box<dyn StepFutureResult>>>;
-pub type StepCreationResult = Result<
-    (Box<dyn WorkflowStep + Sync + Send>, FutureList),
-    Box<dyn std::error::Error + Sync + Send>,
->;
+pub type StepCreationResult =
+    Result<(Box<dyn WorkflowStep + Sync + Send>, FutureList), StepCreationError>;
+pub type StepValidationResult = Result<(), Box<dyn std::error::Error + Sync + Send>>;
 pub type CreateFactoryFnResult =
     Box<dyn Fn(&WorkflowStepDefinition) -> StepCreationResult + Send + Sync>;
 
+/// Errors that can occur when a workflow step generator fails to create a step.  Creating a step
+/// only fails for reasons that creating it again won't fix, so the workflow does not retry it.
+/// Problems with resources a step depends on that occur after it has been created, such as an
+/// endpoint rejecting its registration, are reported through the step's status instead.
+#[derive(Error, Debug)]
+pub enum StepCreationError {
+    /// The step's definition is not valid, such as a required parameter being missing.  Creating
+    /// the step again with the same definition will always fail.
+    #[error("Invalid step configuration: {0}")]
+    InvalidConfiguration(Box<dyn std::error::Error + Sync + Send>),
+
+    /// The step can not be created for a reason that won't resolve itself, such as a subsystem
+    /// it depends on having shut down.
+    #[error("{0}")]
+    Fatal(Box<dyn std::error::Error + Sync + Send>),
+}
+
 /// Various statuses of an individual step
 #[derive(Clone, Debug, PartialEq)]
 pub enum StepStatus {
@@ -46,11 +92,49 @@ pub enum StepStatus {
     /// notifications.  It will likely have to be recreated.
     Error { message: String },
 
+    /// The step lost a resource it depends on and is attempting to re-establish it.  Media will
+    /// not flow through the step until it becomes active again.
+    Reconnecting { attempt: u32 },
+
+    /// The step has been told to shut down but is still finishing work it had in progress, such
+    /// as waiting for an endpoint to confirm a registration was removed.  The workflow keeps
+    /// executing the step's futures (but gives it no new media) until it reports `Shutdown`, so
+    /// the step is not dropped before it's safe to do so.
+    ShuttingDown,
+
     /// The step has been shut down and is not expected to be invoked anymore. If it's wanted to be
     /// used it will have to be recreated
     Shutdown,
 }
 
+/// The codecs a workflow step is able to process.  A value of `None` means the step accepts any
+/// codec for that type of media (or doesn't do anything with it).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SupportedCodecs {
+    pub video: Option<&'static [VideoCodec]>,
+    pub audio: Option<&'static [AudioCodec]>,
+}
+
+impl SupportedCodecs {
+    /// Returns a description of the codec of the media content if it's a codec that isn't
+    /// supported, or `None` if the content is supported or isn't video or audio.
+    pub fn get_unsupported_codec(&self, content: &MediaNotificationContent) -> Option<String> {
+        match content {
+            MediaNotificationContent::Video { codec, .. } => match self.video {
+                Some(codecs) if !codecs.contains(codec) => Some(format!("{:?} video", codec)),
+                _ => None,
+            },
+
+            MediaNotificationContent::Audio { codec, .. } => match self.audio {
+                Some(codecs) if !codecs.contains(codec) => Some(format!("{:?} audio", codec)),
+                _ => None,
+            },
+
+            _ => None,
+        }
+    }
+}
+
 /// Inputs to be passed in for execution of a workflow step.
 pub struct StepInputs {
     /// Media notifications that the step may be interested in
@@ -81,6 +165,10 @@ pub struct StepOutputs {
 
     /// Any futures the workflow should track for this step
     pub futures: Vec<BoxFuture<'static, Box<dyn StepFutureResult>>>,
+
+    /// Streams the workflow step wants a keyframe for as soon as possible.  These are passed to
+    /// the steps before this one, starting with the closest, until one of them acts on it.
+    pub keyframe_requests: Vec<StreamId>,
 }
 
 impl StepOutputs {
@@ -88,12 +176,14 @@ impl StepOutputs {
         StepOutputs {
             media: Vec::new(),
             futures: Vec::new(),
+            keyframe_requests: Vec::new(),
         }
     }
 
     pub fn clear(&mut self) {
         self.futures.clear();
         self.media.clear();
+        self.keyframe_requests.clear();
     }
 }
 
@@ -113,6 +203,34 @@ pub trait WorkflowStep {
     /// state.
     fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs);
 
+    /// Returns the codecs the step is able to process.  The workflow logs a warning the first
+    /// time each stream sends the step video or audio in a codec it does not support, but the
+    /// media is still passed to the step.  By default steps are assumed to support all codecs.
+    fn get_supported_codecs(&self) -> SupportedCodecs {
+        SupportedCodecs::default()
+    }
+
+    /// Returns values the step tracks for each stream it's handling, keyed by the stream's id and
+    /// then the name of the value, for inclusion in the workflow's state.  By default steps don't
+    /// report any per-stream values.
+    fn get_stream_details(&self) -> HashMap<StreamId, HashMap<String, String>> {
+        HashMap::new()
+    }
+
+    /// Returns the latest statistics for each stream the step is measuring.  Only steps that
+    /// measure streams (such as the stream stats step) return anything by default.
+    fn get_stream_statistics(&self) -> Vec<StreamStatistics> {
+        Vec::new()
+    }
+
+    /// Asks the step to have the specified stream produce a keyframe as soon as possible, such as
+    /// when a new watcher joins a stream with a long keyframe interval.  Returns true if the step
+    /// acted on the request, in which case it is not passed to any steps before this one.  By
+    /// default steps can't act on keyframe requests.
+    fn request_keyframe(&mut self, _stream_id: &StreamId) -> bool {
+        false
+    }
+
     /// Notifies the step that it is no longer needed and that all streams its managing should be
     /// closed.  All endpoints the step has interacted with should be proactively notified that it
     /// is being removed, as it can not be guaranteed that all channels will be automatically
@@ -134,12 +252,15 @@ use futures::StreamExt;
 use std::iter::FromIterator;
 #[cfg(test)]
 use std::time::Duration;
+#[cfg(test)]
+use test_utils::StepInput;
 
 #[cfg(test)]
 struct StepTestContext {
     step: Box<dyn WorkflowStep>,
     futures: FuturesUnordered<BoxFuture<'static, Box<dyn StepFutureResult>>>,
     media_outputs: Vec<MediaNotification>,
+    keyframe_requests: Vec<StreamId>,
 }
 
 #[cfg(test)]
@@ -153,6 +274,7 @@ impl StepTestContext {
             step,
             futures: FuturesUnordered::from_iter(futures),
             media_outputs: Vec::new(),
+            keyframe_requests: Vec::new(),
         })
     }
 
@@ -167,6 +289,30 @@ impl StepTestContext {
         self.media_outputs = outputs.media;
     }
 
+    /// Executes the step once per input, in the order given, with `media_outputs` containing the
+    /// media raised across all of the executions.  Futures raised by the step are tracked but not
+    /// polled.
+    fn execute_inputs(&mut self, inputs: Vec<StepInput>) {
+        self.media_outputs.clear();
+        for input in inputs {
+            let mut outputs = StepOutputs::new();
+            let mut step_inputs = StepInputs::new();
+            match input {
+                StepInput::Media(media) => step_inputs.media.push(media),
+                StepInput::Notification(notification) => {
+                    step_inputs.notifications.push(notification)
+                }
+            }
+
+            self.step.execute(&mut step_inputs, &mut outputs);
+
+            self.futures.extend(outputs.futures.drain(..));
+            self.keyframe_requests
+                .extend(outputs.keyframe_requests.drain(..));
+            self.media_outputs.append(&mut outputs.media);
+        }
+    }
+
     async fn execute_notification(&mut self, notification: Box<dyn StepFutureResult>) {
         let mut outputs = StepOutputs::new();
         let mut inputs = StepInputs::new();
@@ -175,6 +321,8 @@ impl StepTestContext {
         self.step.execute(&mut inputs, &mut outputs);
 
         self.futures.extend(outputs.futures.drain(..));
+        self.keyframe_requests
+            .extend(outputs.keyframe_requests.drain(..));
         self.media_outputs = outputs.media;
 
         self.execute_pending_notifications().await;
@@ -195,6 +343,8 @@ impl StepTestContext {
             self.step.execute(&mut inputs, &mut outputs);
 
             self.futures.extend(outputs.futures.drain(..));
+            self.keyframe_requests
+                .extend(outputs.keyframe_requests.drain(..));
             self.media_outputs = outputs.media;
         }
     }
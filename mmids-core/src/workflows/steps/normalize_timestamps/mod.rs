@@ -0,0 +1,314 @@
+//! The normalize timestamps step rebases the audio and video timestamps of each stream that passes
+//! through it, so every stream starts at zero regardless of the epoch its source used (e.g. RTMP
+//! millisecond timestamps vs an RTP clock).  Audio and video of the same stream share a single
+//! base, so they stay in sync with each other.
+//!
+//! Timestamps never go backwards.  If a stream's timestamps jump backwards (such as from a
+//! timestamp wraparound or a source being switched mid-stream), the stream is rebased so that it
+//! continues from the latest timestamp that was output.  When a stream reconnects, either by a
+//! repeated `NewIncomingStream` notification for the same stream id or by a new stream with the
+//! same name as a disconnected one, its timestamps continue from where the previous stream left
+//! off instead of restarting at zero.  A disconnected stream's timestamps are only remembered for
+//! a grace period, after which a stream with the same name starts at zero again.
+//!
+//! All other media notifications are passed through unchanged.
+
+#[cfg(test)]
+mod tests;
+
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus, WorkflowStep,
+};
+use crate::workflows::MediaNotificationContent;
+use crate::{StreamId, VideoTimestamp};
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// How long the timestamps of a disconnected stream are remembered for it to reconnect
+const RECONNECT_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// Generates new instances of the normalize timestamps workflow step
+pub struct NormalizeTimestampsStepGenerator {
+    reconnect_grace_period: Duration,
+}
+
+struct NormalizeTimestampsStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    streams: HashMap<StreamId, StreamTimestamps>,
+    reconnect_grace_period: Duration,
+
+    /// The last timestamp output for streams that have disconnected, keyed by stream name, so
+    /// a reconnecting stream can continue where it left off.  Entries are removed when the
+    /// stream reconnects or its grace period elapses.
+    disconnected_streams: HashMap<String, DisconnectedStream>,
+    next_timer_id: u64,
+}
+
+struct DisconnectedStream {
+    latest_output: Duration,
+    timer_id: u64,
+}
+
+enum FutureResult {
+    GracePeriodElapsed { stream_name: String, timer_id: u64 },
+}
+
+impl StepFutureResult for FutureResult {}
+
+/// Tracks the last timestamps seen for a single type of media (audio or video)
+#[derive(Default)]
+struct Timeline {
+    last_input: Option<Duration>,
+    last_output: Duration,
+}
+
+struct StreamTimestamps {
+    stream_name: String,
+
+    /// The source timestamp that corresponds to `start`.  `None` until the first audio or video
+    /// is received after the stream (re)connects.
+    base: Option<Duration>,
+
+    /// The normalized timestamp that `base` maps to
+    start: Duration,
+
+    /// The highest normalized timestamp that's been output for this stream
+    latest_output: Duration,
+
+    video: Timeline,
+    audio: Timeline,
+}
+
+#[derive(Clone, Copy)]
+enum MediaType {
+    Video,
+    Audio,
+}
+
+impl NormalizeTimestampsStepGenerator {
+    pub fn new() -> Self {
+        NormalizeTimestampsStepGenerator {
+            reconnect_grace_period: RECONNECT_GRACE_PERIOD,
+        }
+    }
+}
+
+impl StepGenerator for NormalizeTimestampsStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let step = NormalizeTimestampsStep {
+            definition,
+            status: StepStatus::Active,
+            streams: HashMap::new(),
+            reconnect_grace_period: self.reconnect_grace_period,
+            disconnected_streams: HashMap::new(),
+            next_timer_id: 0,
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+}
+
+impl StreamTimestamps {
+    fn new(stream_name: String, start: Duration) -> Self {
+        StreamTimestamps {
+            stream_name,
+            base: None,
+            start,
+            latest_output: start,
+            video: Timeline::default(),
+            audio: Timeline::default(),
+        }
+    }
+
+    /// Restarts the stream's timeline so the next media received continues from the latest
+    /// timestamp that was output.
+    fn rebase(&mut self) {
+        self.base = None;
+        self.start = self.latest_output;
+        self.video.last_input = None;
+        self.audio.last_input = None;
+    }
+
+    fn normalize(&mut self, timestamp: Duration, media_type: MediaType) -> Duration {
+        let went_backwards = match media_type {
+            MediaType::Video => self.video.last_input,
+            MediaType::Audio => self.audio.last_input,
+        }
+        .map(|last_input| timestamp < last_input)
+        .unwrap_or(false);
+
+        if went_backwards {
+            self.rebase();
+        }
+
+        let base = *self.base.get_or_insert(timestamp);
+        let timeline = match media_type {
+            MediaType::Video => &mut self.video,
+            MediaType::Audio => &mut self.audio,
+        };
+
+        // Audio and video share a base, so the first packet of one type may have a source
+        // timestamp slightly before the base.  Clamp it so neither type ever goes backwards.
+        let normalized = self.start + timestamp.saturating_sub(base);
+        let normalized = normalized.max(timeline.last_output);
+
+        timeline.last_input = Some(timestamp);
+        timeline.last_output = normalized;
+        if normalized > self.latest_output {
+            self.latest_output = normalized;
+        }
+
+        normalized
+    }
+}
+
+impl NormalizeTimestampsStep {
+    fn handle_new_stream(&mut self, stream_id: &StreamId, stream_name: &str) {
+        if let Some(stream) = self.streams.get_mut(stream_id) {
+            info!(
+                stream_id = ?stream_id,
+                "Stream {:?} reconnected, continuing timestamps from {:?}",
+                stream_id, stream.latest_output
+            );
+
+            stream.stream_name = stream_name.to_string();
+            stream.rebase();
+
+            return;
+        }
+
+        let start = self
+            .disconnected_streams
+            .remove(stream_name)
+            .map(|stream| stream.latest_output)
+            .unwrap_or_default();
+
+        self.streams.insert(
+            stream_id.clone(),
+            StreamTimestamps::new(stream_name.to_string(), start),
+        );
+    }
+
+    fn handle_disconnection(&mut self, stream_id: &StreamId, outputs: &mut StepOutputs) {
+        if let Some(stream) = self.streams.remove(stream_id) {
+            self.next_timer_id += 1;
+            let timer_id = self.next_timer_id;
+            self.disconnected_streams.insert(
+                stream.stream_name.clone(),
+                DisconnectedStream {
+                    latest_output: stream.latest_output,
+                    timer_id,
+                },
+            );
+
+            outputs.futures.push(
+                wait_for_grace_period(stream.stream_name, timer_id, self.reconnect_grace_period)
+                    .boxed(),
+            );
+        }
+    }
+
+    fn handle_grace_period_elapsed(&mut self, stream_name: String, timer_id: u64) {
+        // The stream may have reconnected and disconnected again since this timer was started
+        let is_current_timer = self
+            .disconnected_streams
+            .get(&stream_name)
+            .map(|stream| stream.timer_id == timer_id)
+            .unwrap_or(false);
+
+        if is_current_timer {
+            self.disconnected_streams.remove(&stream_name);
+        }
+    }
+}
+
+impl WorkflowStep for NormalizeTimestampsStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for notification in inputs.notifications.drain(..) {
+            let future_result = match notification.downcast::<FutureResult>() {
+                Ok(result) => *result,
+                Err(_) => {
+                    error!("Normalize timestamps step received a notification that is not a normalize timestamps future result");
+                    self.status = StepStatus::Error {
+                        message:
+                            "Received a notification that is not a normalize timestamps future result"
+                                .to_string(),
+                    };
+
+                    return;
+                }
+            };
+
+            match future_result {
+                FutureResult::GracePeriodElapsed {
+                    stream_name,
+                    timer_id,
+                } => self.handle_grace_period_elapsed(stream_name, timer_id),
+            }
+        }
+
+        for mut media in inputs.media.drain(..) {
+            match &mut media.content {
+                MediaNotificationContent::NewIncomingStream { stream_name } => {
+                    self.handle_new_stream(&media.stream_id, stream_name);
+                }
+
+                MediaNotificationContent::StreamDisconnected => {
+                    self.handle_disconnection(&media.stream_id, outputs);
+                }
+
+                MediaNotificationContent::Video { timestamp, .. } => {
+                    if let Some(stream) = self.streams.get_mut(&media.stream_id) {
+                        let dts = stream.normalize(timestamp.dts(), MediaType::Video);
+                        *timestamp = VideoTimestamp {
+                            dts,
+                            pts_offset: timestamp.pts_offset(),
+                        };
+                    }
+                }
+
+                MediaNotificationContent::Audio { timestamp, .. } => {
+                    if let Some(stream) = self.streams.get_mut(&media.stream_id) {
+                        *timestamp = stream.normalize(*timestamp, MediaType::Audio);
+                    }
+                }
+
+                MediaNotificationContent::Metadata { .. } => (),
+                MediaNotificationContent::Discontinuity => (),
+                MediaNotificationContent::Cue { .. } => (),
+            }
+
+            outputs.media.push(media);
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+    }
+}
+
+async fn wait_for_grace_period(
+    stream_name: String,
+    timer_id: u64,
+    grace_period: Duration,
+) -> Box<dyn StepFutureResult> {
+    tokio::time::sleep(grace_period).await;
+
+    Box::new(FutureResult::GracePeriodElapsed {
+        stream_name,
+        timer_id,
+    })
+}
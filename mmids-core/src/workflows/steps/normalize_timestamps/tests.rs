@@ -0,0 +1,217 @@
+use super::*;
+use crate::codecs::{AudioCodec, VideoCodec};
+use crate::workflows::steps::test_utils::create_definition;
+use crate::workflows::steps::StepTestContext;
+use crate::workflows::MediaNotification;
+use bytes::Bytes;
+
+fn create_context() -> StepTestContext {
+    let definition = create_definition("normalize_timestamps", &[]);
+
+    let generator = NormalizeTimestampsStepGenerator::new();
+    StepTestContext::new(Box::new(generator), definition).unwrap()
+}
+
+fn create_context_with_grace_period(reconnect_grace_period: Duration) -> StepTestContext {
+    let definition = create_definition("normalize_timestamps", &[]);
+
+    let generator = NormalizeTimestampsStepGenerator {
+        reconnect_grace_period,
+    };
+
+    StepTestContext::new(Box::new(generator), definition).unwrap()
+}
+
+fn new_stream(stream_id: &str, stream_name: &str) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: stream_name.to_string(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn disconnection(stream_id: &str) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
+    }
+}
+
+fn video(stream_id: &str, dts: u64, pts: u64) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            is_keyframe: true,
+            is_sequence_header: false,
+            data: Bytes::from(vec![1, 2, 3]),
+            timestamp: VideoTimestamp::from_durations(
+                Duration::from_millis(dts),
+                Duration::from_millis(pts),
+            ),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn audio(stream_id: &str, timestamp: u64) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::Audio {
+            codec: AudioCodec::Aac,
+            is_sequence_header: false,
+            data: Bytes::from(vec![1, 2, 3]),
+            timestamp: Duration::from_millis(timestamp),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn get_output_timestamp(context: &StepTestContext) -> Duration {
+    assert_eq!(context.media_outputs.len(), 1, "Expected one media output");
+    match &context.media_outputs[0].content {
+        MediaNotificationContent::Video { timestamp, .. } => timestamp.dts(),
+        MediaNotificationContent::Audio { timestamp, .. } => *timestamp,
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+fn assert_output_timestamp(context: &mut StepTestContext, media: MediaNotification, expected: u64) {
+    context.execute_with_media(media);
+    assert_eq!(
+        get_output_timestamp(context),
+        Duration::from_millis(expected),
+        "Unexpected output timestamp"
+    );
+}
+
+#[test]
+fn new_stream_notification_passed_through() {
+    let mut context = create_context();
+    context.assert_media_passed_through(new_stream("1", "abc"));
+}
+
+#[test]
+fn media_for_unknown_stream_passed_through_unchanged() {
+    let mut context = create_context();
+    assert_output_timestamp(&mut context, video("1", 5000, 5000), 5000);
+}
+
+#[test]
+fn stream_timestamps_rebased_to_start_at_zero() {
+    let mut context = create_context();
+    context.execute_with_media(new_stream("1", "abc"));
+
+    assert_output_timestamp(&mut context, video("1", 5000, 5000), 0);
+    assert_output_timestamp(&mut context, video("1", 5033, 5033), 33);
+}
+
+#[test]
+fn audio_and_video_share_the_same_base() {
+    let mut context = create_context();
+    context.execute_with_media(new_stream("1", "abc"));
+
+    assert_output_timestamp(&mut context, video("1", 5000, 5000), 0);
+    assert_output_timestamp(&mut context, audio("1", 5020), 20);
+    assert_output_timestamp(&mut context, video("1", 5033, 5033), 33);
+}
+
+#[test]
+fn pts_offset_is_preserved() {
+    let mut context = create_context();
+    context.execute_with_media(new_stream("1", "abc"));
+    context.execute_with_media(video("1", 5000, 5080));
+
+    match &context.media_outputs[0].content {
+        MediaNotificationContent::Video { timestamp, .. } => {
+            assert_eq!(timestamp.dts(), Duration::from_millis(0), "Unexpected dts");
+            assert_eq!(timestamp.pts(), Duration::from_millis(80), "Unexpected pts");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[test]
+fn backwards_jump_continues_from_latest_timestamp() {
+    let mut context = create_context();
+    context.execute_with_media(new_stream("1", "abc"));
+
+    assert_output_timestamp(&mut context, video("1", 5000, 5000), 0);
+    assert_output_timestamp(&mut context, video("1", 6000, 6000), 1000);
+    assert_output_timestamp(&mut context, video("1", 10, 10), 1000);
+    assert_output_timestamp(&mut context, video("1", 43, 43), 1033);
+}
+
+#[test]
+fn media_before_shared_base_is_clamped_forward() {
+    let mut context = create_context();
+    context.execute_with_media(new_stream("1", "abc"));
+
+    assert_output_timestamp(&mut context, video("1", 5000, 5000), 0);
+    assert_output_timestamp(&mut context, audio("1", 4990), 0);
+    assert_output_timestamp(&mut context, audio("1", 5013), 13);
+}
+
+#[test]
+fn streams_are_normalized_independently() {
+    let mut context = create_context();
+    context.execute_with_media(new_stream("1", "abc"));
+    context.execute_with_media(new_stream("2", "def"));
+
+    assert_output_timestamp(&mut context, video("1", 5000, 5000), 0);
+    assert_output_timestamp(&mut context, video("2", 90000, 90000), 0);
+    assert_output_timestamp(&mut context, video("1", 5100, 5100), 100);
+}
+
+#[test]
+fn repeated_new_stream_notification_continues_timestamps() {
+    let mut context = create_context();
+    context.execute_with_media(new_stream("1", "abc"));
+    assert_output_timestamp(&mut context, video("1", 5000, 5000), 0);
+    assert_output_timestamp(&mut context, video("1", 7000, 7000), 2000);
+
+    context.execute_with_media(new_stream("1", "abc"));
+    assert_output_timestamp(&mut context, video("1", 100, 100), 2000);
+    assert_output_timestamp(&mut context, video("1", 133, 133), 2033);
+}
+
+#[test]
+fn reconnected_stream_with_same_name_continues_timestamps() {
+    let mut context = create_context();
+    context.execute_with_media(new_stream("1", "abc"));
+    assert_output_timestamp(&mut context, video("1", 5000, 5000), 0);
+    assert_output_timestamp(&mut context, video("1", 7000, 7000), 2000);
+    context.execute_with_media(disconnection("1"));
+
+    context.execute_with_media(new_stream("2", "abc"));
+    assert_output_timestamp(&mut context, video("2", 100, 100), 2000);
+}
+
+#[test]
+fn new_stream_with_different_name_starts_at_zero_after_disconnection() {
+    let mut context = create_context();
+    context.execute_with_media(new_stream("1", "abc"));
+    assert_output_timestamp(&mut context, video("1", 5000, 5000), 0);
+    assert_output_timestamp(&mut context, video("1", 7000, 7000), 2000);
+    context.execute_with_media(disconnection("1"));
+
+    context.execute_with_media(new_stream("2", "def"));
+    assert_output_timestamp(&mut context, video("2", 100, 100), 0);
+}
+
+#[tokio::test]
+async fn reconnected_stream_starts_at_zero_after_grace_period() {
+    let mut context = create_context_with_grace_period(Duration::from_millis(1));
+    context.execute_with_media(new_stream("1", "abc"));
+    assert_output_timestamp(&mut context, video("1", 5000, 5000), 0);
+    assert_output_timestamp(&mut context, video("1", 7000, 7000), 2000);
+    context.execute_with_media(disconnection("1"));
+    context.execute_pending_notifications().await;
+
+    context.execute_with_media(new_stream("2", "abc"));
+    assert_output_timestamp(&mut context, video("2", 100, 100), 0);
+}
@@ -0,0 +1,248 @@
+use super::{Finalization, MediaFileWriter, RecordedMedia};
+use crate::endpoints::rtmp_server::{wrap_audio_into_flv, wrap_video_into_flv};
+use bytes::BufMut;
+use std::time::Duration;
+
+const FLV_HEADER: [u8; 9] = [b'F', b'L', b'V', 0x01, 0x05, 0x00, 0x00, 0x00, 0x09];
+const AUDIO_TAG_TYPE: u8 = 8;
+const VIDEO_TAG_TYPE: u8 = 9;
+const SCRIPT_TAG_TYPE: u8 = 18;
+const TAG_HEADER_SIZE: usize = 11;
+
+/// Offset of the duration's number value in the `onMetaData` script tag body
+const METADATA_DURATION_OFFSET: usize = 29;
+
+/// Writes media into the FLV container format.  An `onMetaData` script tag is written after the
+/// header with a placeholder duration, which is filled in when the recording is finalized.
+pub struct FlvWriter {
+    first_timestamp: Option<Duration>,
+    last_timestamp: Duration,
+    duration_offset: u64,
+}
+
+impl FlvWriter {
+    pub fn new() -> Self {
+        FlvWriter {
+            first_timestamp: None,
+            last_timestamp: Duration::new(0, 0),
+            duration_offset: 0,
+        }
+    }
+
+    /// Gets the timestamp relative to the first media written, so recordings always start at zero
+    fn relative_timestamp(&mut self, timestamp: Duration) -> Duration {
+        let first = *self.first_timestamp.get_or_insert(timestamp);
+        let relative = timestamp.saturating_sub(first);
+        if relative > self.last_timestamp {
+            self.last_timestamp = relative;
+        }
+
+        relative
+    }
+}
+
+impl MediaFileWriter for FlvWriter {
+    fn header(&mut self) -> Vec<u8> {
+        let mut bytes = FLV_HEADER.to_vec();
+        bytes.put_u32(0); // Previous tag size for the non-existent tag before the first tag
+
+        self.duration_offset = (bytes.len() + TAG_HEADER_SIZE + METADATA_DURATION_OFFSET) as u64;
+        write_tag(&mut bytes, SCRIPT_TAG_TYPE, 0, &metadata_body(0.0));
+
+        bytes
+    }
+
+    fn write_media(&mut self, media: RecordedMedia) -> Vec<u8> {
+        let (tag_type, timestamp, data) = match media {
+            RecordedMedia::Video {
+                codec,
+                is_keyframe,
+                is_sequence_header,
+                data,
+                timestamp,
+            } => {
+                let data = match wrap_video_into_flv(
+                    data,
+                    codec,
+                    is_keyframe,
+                    is_sequence_header,
+                    timestamp.pts_offset(),
+                ) {
+                    Ok(data) => data,
+                    Err(_) => return Vec::new(),
+                };
+
+                (VIDEO_TAG_TYPE, timestamp.dts(), data)
+            }
+
+            RecordedMedia::Audio {
+                codec,
+                is_sequence_header,
+                data,
+                timestamp,
+            } => {
+                let data = match wrap_audio_into_flv(data, codec, is_sequence_header) {
+                    Ok(data) => data,
+                    Err(_) => return Vec::new(),
+                };
+
+                (AUDIO_TAG_TYPE, timestamp, data)
+            }
+        };
+
+        let timestamp = self.relative_timestamp(timestamp);
+        let mut bytes = Vec::with_capacity(TAG_HEADER_SIZE + data.len() + 4);
+        write_tag(&mut bytes, tag_type, timestamp.as_millis() as u32, &data);
+
+        bytes
+    }
+
+    fn finalize(&mut self) -> Finalization {
+        let duration = self.last_timestamp.as_secs_f64();
+
+        Finalization {
+            trailer: Vec::new(),
+            patches: vec![(self.duration_offset, duration.to_be_bytes().to_vec())],
+        }
+    }
+}
+
+/// Writes an FLV tag, including the previous tag size that follows it
+fn write_tag(bytes: &mut Vec<u8>, tag_type: u8, timestamp: u32, data: &[u8]) {
+    bytes.put_u8(tag_type);
+    bytes.put_uint(data.len() as u64, 3);
+    bytes.put_uint((timestamp & 0x00ffffff) as u64, 3);
+    bytes.put_u8((timestamp >> 24) as u8);
+    bytes.put_uint(0, 3); // stream id
+    bytes.put_slice(data);
+    bytes.put_u32((TAG_HEADER_SIZE + data.len()) as u32);
+}
+
+/// Creates the AMF0 encoded body of an `onMetaData` script tag containing only a duration
+fn metadata_body(duration: f64) -> Vec<u8> {
+    let mut body = Vec::new();
+    write_amf0_string(&mut body, "onMetaData");
+
+    body.put_u8(0x08); // ECMA array marker
+    body.put_u32(1);
+    write_amf0_key(&mut body, "duration");
+    body.put_u8(0x00); // Number marker
+    body.put_f64(duration);
+
+    body.put_slice(&[0x00, 0x00, 0x09]); // Object end marker
+
+    body
+}
+
+fn write_amf0_string(bytes: &mut Vec<u8>, value: &str) {
+    bytes.put_u8(0x02);
+    write_amf0_key(bytes, value);
+}
+
+fn write_amf0_key(bytes: &mut Vec<u8>, value: &str) {
+    bytes.put_u16(value.len() as u16);
+    bytes.put_slice(value.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codecs::{AudioCodec, VideoCodec};
+    use crate::VideoTimestamp;
+    use bytes::Bytes;
+
+    fn video(dts: u64, pts: u64) -> RecordedMedia {
+        RecordedMedia::Video {
+            codec: VideoCodec::H264,
+            is_keyframe: true,
+            is_sequence_header: false,
+            data: Bytes::from(vec![1, 2, 3]),
+            timestamp: VideoTimestamp::from_durations(
+                Duration::from_millis(dts),
+                Duration::from_millis(pts),
+            ),
+        }
+    }
+
+    #[test]
+    fn header_starts_with_flv_signature() {
+        let mut writer = FlvWriter::new();
+        let header = writer.header();
+
+        assert_eq!(&header[..9], &FLV_HEADER, "Unexpected flv header");
+        assert_eq!(
+            &header[9..13],
+            &[0, 0, 0, 0],
+            "Unexpected previous tag size"
+        );
+        assert_eq!(header[13], SCRIPT_TAG_TYPE, "Expected metadata script tag");
+    }
+
+    #[test]
+    fn duration_offset_points_to_metadata_duration() {
+        let mut writer = FlvWriter::new();
+        let mut header = writer.header();
+
+        let offset = writer.duration_offset as usize;
+        assert_eq!(
+            header[offset - 1],
+            0x00,
+            "Expected number marker before duration"
+        );
+
+        header[offset..offset + 8].copy_from_slice(&2.5_f64.to_be_bytes());
+        assert_eq!(
+            &header[13 + TAG_HEADER_SIZE..header.len() - 4],
+            &metadata_body(2.5)[..],
+            "Unexpected metadata body after patching duration"
+        );
+    }
+
+    #[test]
+    fn video_tag_contains_relative_timestamp_and_flv_wrapped_data() {
+        let mut writer = FlvWriter::new();
+        let _ = writer.header();
+        let _ = writer.write_media(video(1000, 1000));
+        let tag = writer.write_media(video(1040, 1080));
+
+        assert_eq!(tag[0], VIDEO_TAG_TYPE, "Unexpected tag type");
+        assert_eq!(&tag[1..4], &[0, 0, 8], "Unexpected data size");
+        assert_eq!(&tag[4..8], &[0, 0, 40, 0], "Unexpected timestamp");
+        assert_eq!(
+            &tag[11..19],
+            &[0x17, 0x01, 0, 0, 40, 1, 2, 3],
+            "Unexpected tag data"
+        );
+        assert_eq!(&tag[19..], &[0, 0, 0, 19], "Unexpected previous tag size");
+    }
+
+    #[test]
+    fn unknown_codecs_are_not_written() {
+        let mut writer = FlvWriter::new();
+        let _ = writer.header();
+        let bytes = writer.write_media(RecordedMedia::Audio {
+            codec: AudioCodec::Unknown,
+            is_sequence_header: false,
+            data: Bytes::from(vec![1, 2, 3]),
+            timestamp: Duration::from_millis(0),
+        });
+
+        assert!(bytes.is_empty(), "Expected no bytes written");
+    }
+
+    #[test]
+    fn finalization_patches_duration() {
+        let mut writer = FlvWriter::new();
+        let _ = writer.header();
+        let _ = writer.write_media(video(1000, 1000));
+        let _ = writer.write_media(video(3500, 3500));
+
+        let finalization = writer.finalize();
+        assert!(finalization.trailer.is_empty(), "Expected no trailer");
+        assert_eq!(
+            finalization.patches,
+            vec![(writer.duration_offset, 2.5_f64.to_be_bytes().to_vec())],
+            "Unexpected patches"
+        );
+    }
+}
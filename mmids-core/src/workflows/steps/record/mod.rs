@@ -0,0 +1,398 @@
+//! The record step archives each stream that passes through it to disk, in either the FLV or MP4
+//! container format.  A new file is started every time a stream announces itself with a
+//! `NewIncomingStream` notification, and the file is finalized when the stream disconnects (or
+//! the step is shut down).  If a stream reconnects with the same stream id, the previous recording
+//! is finalized and a new file is started.
+//!
+//! Files are written to `<output_dir>/<stream name>_<unix time in milliseconds>.<flv|mp4>`.
+//!
+//! All media notifications are passed to subsequent steps untouched.
+
+mod flv;
+mod mp4;
+#[cfg(test)]
+mod tests;
+
+use crate::codecs::{AudioCodec, VideoCodec};
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::{StepGenerator, StepKind};
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, SupportedCodecs, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::{StreamId, VideoTimestamp};
+use bytes::Bytes;
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tracing::{error, info};
+
+pub const OUTPUT_DIR: &str = "output_dir";
+pub const FORMAT: &str = "format";
+
+/// Generates new instances of the record workflow step
+pub struct RecordStepGenerator {}
+
+/// Container formats that streams can be recorded into
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RecordingFormat {
+    Flv,
+    Mp4,
+}
+
+/// Media that's been passed to a stream's recording
+enum RecordedMedia {
+    Video {
+        codec: VideoCodec,
+        is_keyframe: bool,
+        is_sequence_header: bool,
+        data: Bytes,
+        timestamp: VideoTimestamp,
+    },
+
+    Audio {
+        codec: AudioCodec,
+        is_sequence_header: bool,
+        data: Bytes,
+        timestamp: Duration,
+    },
+}
+
+/// Bytes that need to be written to a recording once no more media will be added to it.
+struct Finalization {
+    /// Bytes to append to the end of the file
+    trailer: Vec<u8>,
+
+    /// Bytes that should overwrite previously written bytes at the specified file offsets
+    patches: Vec<(u64, Vec<u8>)>,
+}
+
+/// Serializes media into a specific container format.  Writers only produce bytes, and it's up
+/// to the caller to write them to the file in the order they were produced.
+trait MediaFileWriter: Send {
+    /// Bytes to write at the start of the file
+    fn header(&mut self) -> Vec<u8>;
+
+    /// Bytes to append to the file for the specified media.  May be empty if the media can't be
+    /// represented in the container.
+    fn write_media(&mut self, media: RecordedMedia) -> Vec<u8>;
+
+    /// Bytes needed to make the file complete
+    fn finalize(&mut self) -> Finalization;
+}
+
+struct ActiveRecording {
+    media_sender: UnboundedSender<RecordedMedia>,
+}
+
+struct RecordStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    output_dir: String,
+    format: RecordingFormat,
+    recordings: HashMap<StreamId, ActiveRecording>,
+}
+
+enum FutureResult {
+    RecordingFinished {
+        path: String,
+        result: tokio::io::Result<()>,
+    },
+}
+
+impl StepFutureResult for FutureResult {}
+
+struct StepParameters {
+    output_dir: String,
+    format: RecordingFormat,
+}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error("No output directory specified.  A '{}' is required", OUTPUT_DIR)]
+    NoOutputDirProvided,
+
+    #[error("No format specified.  A '{}' of 'flv' or 'mp4' is required", FORMAT)]
+    NoFormatProvided,
+
+    #[error("Invalid format of '{0}'.  Only 'flv' and 'mp4' are supported")]
+    InvalidFormat(String),
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl RecordingFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            RecordingFormat::Flv => "flv",
+            RecordingFormat::Mp4 => "mp4",
+        }
+    }
+
+    fn create_writer(&self) -> Box<dyn MediaFileWriter> {
+        match self {
+            RecordingFormat::Flv => Box::new(flv::FlvWriter::new()),
+            RecordingFormat::Mp4 => Box::new(mp4::Mp4Writer::new()),
+        }
+    }
+}
+
+impl RecordStepGenerator {
+    pub fn new() -> Self {
+        RecordStepGenerator {}
+    }
+}
+
+impl StepGenerator for RecordStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let StepParameters { output_dir, format } = parse_parameters(&definition)?;
+
+        let step = RecordStep {
+            definition,
+            status: StepStatus::Active,
+            output_dir,
+            format,
+            recordings: HashMap::new(),
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_parameters(definition)?;
+        Ok(())
+    }
+
+    fn kind(&self) -> StepKind {
+        StepKind::Sink
+    }
+}
+
+fn parse_parameters(
+    definition: &WorkflowStepDefinition,
+) -> Result<StepParameters, StepStartupError> {
+    let output_dir = match definition.parameters.get(OUTPUT_DIR) {
+        Some(Some(value)) if !value.trim().is_empty() => value.trim().to_string(),
+        _ => return Err(StepStartupError::NoOutputDirProvided),
+    };
+
+    let format = match definition.parameters.get(FORMAT) {
+        Some(Some(value)) => match value.trim().to_lowercase().as_str() {
+            "flv" => RecordingFormat::Flv,
+            "mp4" => RecordingFormat::Mp4,
+            _ => return Err(StepStartupError::InvalidFormat(value.clone())),
+        },
+
+        _ => return Err(StepStartupError::NoFormatProvided),
+    };
+
+    Ok(StepParameters { output_dir, format })
+}
+
+impl RecordStep {
+    fn handle_media(&mut self, media: &MediaNotification, outputs: &mut StepOutputs) {
+        match &media.content {
+            MediaNotificationContent::NewIncomingStream { stream_name } => {
+                // If the stream id is already being recorded, then the stream reconnected without
+                // a disconnection notification.  Replacing the recording drops the old sender,
+                // which finalizes the old file.
+                let path = get_recording_path(&self.output_dir, stream_name, self.format);
+                info!(
+                    stream_id = ?media.stream_id,
+                    "Recording stream {:?} to '{}'", media.stream_id, path
+                );
+
+                let (sender, receiver) = unbounded_channel();
+                self.recordings.insert(
+                    media.stream_id.clone(),
+                    ActiveRecording {
+                        media_sender: sender,
+                    },
+                );
+
+                outputs
+                    .futures
+                    .push(record(self.output_dir.clone(), path, self.format, receiver).boxed());
+            }
+
+            MediaNotificationContent::StreamDisconnected => {
+                self.recordings.remove(&media.stream_id);
+            }
+
+            MediaNotificationContent::Video {
+                codec,
+                is_keyframe,
+                is_sequence_header,
+                data,
+                timestamp,
+            } => {
+                if let Some(recording) = self.recordings.get(&media.stream_id) {
+                    let _ = recording.media_sender.send(RecordedMedia::Video {
+                        codec: *codec,
+                        is_keyframe: *is_keyframe,
+                        is_sequence_header: *is_sequence_header,
+                        data: data.clone(),
+                        timestamp: timestamp.clone(),
+                    });
+                }
+            }
+
+            MediaNotificationContent::Audio {
+                codec,
+                is_sequence_header,
+                data,
+                timestamp,
+            } => {
+                if let Some(recording) = self.recordings.get(&media.stream_id) {
+                    let _ = recording.media_sender.send(RecordedMedia::Audio {
+                        codec: *codec,
+                        is_sequence_header: *is_sequence_header,
+                        data: data.clone(),
+                        timestamp: *timestamp,
+                    });
+                }
+            }
+
+            MediaNotificationContent::Metadata { .. } => (),
+            MediaNotificationContent::Discontinuity => (),
+            MediaNotificationContent::Cue { .. } => (),
+        }
+    }
+}
+
+impl WorkflowStep for RecordStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for notification in inputs.notifications.drain(..) {
+            let future_result = match notification.downcast::<FutureResult>() {
+                Ok(result) => *result,
+                Err(_) => {
+                    error!(
+                        "Record step received a notification that is not a record future result"
+                    );
+                    self.status = StepStatus::Error {
+                        message: "Received a notification that is not a record future result"
+                            .to_string(),
+                    };
+
+                    return;
+                }
+            };
+
+            match future_result {
+                FutureResult::RecordingFinished { path, result } => match result {
+                    Ok(()) => info!("Recording '{}' finalized", path),
+                    Err(error) => error!("Failed to write recording '{}': {:?}", path, error),
+                },
+            }
+        }
+
+        for media in inputs.media.drain(..) {
+            self.handle_media(&media, outputs);
+            outputs.media.push(media);
+        }
+    }
+
+    fn get_supported_codecs(&self) -> SupportedCodecs {
+        // Media in unknown codecs can't be written into either recording format
+        SupportedCodecs {
+            video: Some(&[VideoCodec::H264]),
+            audio: Some(&[AudioCodec::Aac]),
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+
+        // Dropping the senders finalizes any in progress recordings
+        self.recordings.clear();
+    }
+}
+
+fn get_recording_path(output_dir: &str, stream_name: &str, format: RecordingFormat) -> String {
+    // Stream names come from clients, so don't allow them to escape the output directory
+    let file_name = stream_name.replace(|c: char| c == '/' || c == '\\' || c == '.', "_");
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+
+    format!(
+        "{}/{}_{}.{}",
+        output_dir,
+        file_name,
+        timestamp,
+        format.extension()
+    )
+}
+
+async fn record(
+    output_dir: String,
+    path: String,
+    format: RecordingFormat,
+    receiver: UnboundedReceiver<RecordedMedia>,
+) -> Box<dyn StepFutureResult> {
+    // The recording is written from its own task so it still gets finalized when the step is
+    // removed and the workflow stops polling this future.
+    let task_path = path.clone();
+    let handle =
+        tokio::spawn(
+            async move { write_recording(&output_dir, &task_path, format, receiver).await },
+        );
+
+    let result = match handle.await {
+        Ok(result) => result,
+        Err(error) => Err(std::io::Error::new(std::io::ErrorKind::Other, error)),
+    };
+
+    Box::new(FutureResult::RecordingFinished { path, result })
+}
+
+/// Writes all media received to the recording until the sender is dropped, at which point the
+/// recording is finalized.
+async fn write_recording(
+    output_dir: &str,
+    path: &str,
+    format: RecordingFormat,
+    mut receiver: UnboundedReceiver<RecordedMedia>,
+) -> tokio::io::Result<()> {
+    tokio::fs::create_dir_all(output_dir).await?;
+
+    let mut file = BufWriter::new(tokio::fs::File::create(path).await?);
+    let mut writer = format.create_writer();
+    file.write_all(&writer.header()).await?;
+
+    while let Some(media) = receiver.recv().await {
+        let bytes = writer.write_media(media);
+        if !bytes.is_empty() {
+            file.write_all(&bytes).await?;
+        }
+    }
+
+    let finalization = writer.finalize();
+    file.write_all(&finalization.trailer).await?;
+    for (offset, bytes) in finalization.patches {
+        file.seek(SeekFrom::Start(offset)).await?;
+        file.write_all(&bytes).await?;
+    }
+
+    file.flush().await?;
+
+    Ok(())
+}
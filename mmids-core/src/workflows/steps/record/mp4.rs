@@ -0,0 +1,918 @@
+use super::{Finalization, MediaFileWriter, RecordedMedia};
+use crate::codecs::aac::AudioSpecificConfig;
+use crate::codecs::{AudioCodec, VideoCodec};
+use bytes::{BufMut, Bytes};
+use std::convert::TryInto;
+use std::time::Duration;
+use tracing::warn;
+
+/// All tracks use millisecond timescales, as that's the precision of timestamps within mmids
+const TIMESCALE: u32 = 1000;
+const VIDEO_TRACK_ID: u32 = 1;
+const AUDIO_TRACK_ID: u32 = 2;
+const MDAT_HEADER_SIZE: u64 = 16;
+const UNITY_MATRIX: [u32; 9] = [0x00010000, 0, 0, 0, 0x00010000, 0, 0, 0, 0x40000000];
+
+/// Writes H264 and AAC media into a non-fragmented MP4 file.  Samples are written into a single
+/// `mdat` box as they arrive, while the sample tables are kept in memory until the recording is
+/// finalized, at which point the `moov` box is appended and the `mdat` size is filled in.
+///
+/// Only the first sequence header of each track is used, and media that arrives before its
+/// track's sequence header is dropped, as it would not be decodable.
+pub struct Mp4Writer {
+    first_timestamp: Option<Duration>,
+    position: u64,
+    mdat_offset: u64,
+    video_config: Option<VideoConfig>,
+    audio_config: Option<AudioConfig>,
+    video_samples: Vec<Sample>,
+    audio_samples: Vec<Sample>,
+}
+
+#[derive(Debug, PartialEq)]
+struct VideoConfig {
+    avc_decoder_configuration: Bytes,
+    width: u16,
+    height: u16,
+}
+
+#[derive(Debug, PartialEq)]
+struct AudioConfig {
+    audio_specific_config: Bytes,
+    sample_rate: u32,
+    channels: u16,
+}
+
+struct Sample {
+    offset: u64,
+    size: u32,
+    dts: u64,
+    composition_offset: u32,
+    is_keyframe: bool,
+}
+
+enum TrackKind<'a> {
+    Video(&'a VideoConfig),
+    Audio(&'a AudioConfig),
+}
+
+struct Track<'a> {
+    id: u32,
+    kind: TrackKind<'a>,
+    samples: &'a [Sample],
+    durations: Vec<u32>,
+}
+
+impl Mp4Writer {
+    pub fn new() -> Self {
+        Mp4Writer {
+            first_timestamp: None,
+            position: 0,
+            mdat_offset: 0,
+            video_config: None,
+            audio_config: None,
+            video_samples: Vec::new(),
+            audio_samples: Vec::new(),
+        }
+    }
+
+    /// Gets the timestamp in milliseconds relative to the first media written, so recordings
+    /// always start at zero
+    fn relative_timestamp(&mut self, timestamp: Duration) -> u64 {
+        let first = *self.first_timestamp.get_or_insert(timestamp);
+        timestamp.saturating_sub(first).as_millis() as u64
+    }
+
+    fn create_sample(&mut self, size: usize, timestamp: Duration) -> Sample {
+        let sample = Sample {
+            offset: self.position,
+            size: size as u32,
+            dts: self.relative_timestamp(timestamp),
+            composition_offset: 0,
+            is_keyframe: true,
+        };
+
+        self.position += size as u64;
+        sample
+    }
+
+    fn create_moov(&self) -> Vec<u8> {
+        let mut tracks = Vec::new();
+        if let Some(config) = &self.video_config {
+            if !self.video_samples.is_empty() {
+                tracks.push(Track {
+                    id: VIDEO_TRACK_ID,
+                    kind: TrackKind::Video(config),
+                    samples: &self.video_samples,
+                    durations: get_sample_durations(&self.video_samples),
+                });
+            }
+        }
+
+        if let Some(config) = &self.audio_config {
+            if !self.audio_samples.is_empty() {
+                tracks.push(Track {
+                    id: AUDIO_TRACK_ID,
+                    kind: TrackKind::Audio(config),
+                    samples: &self.audio_samples,
+                    durations: get_sample_durations(&self.audio_samples),
+                });
+            }
+        }
+
+        let movie_duration = tracks
+            .iter()
+            .map(|track| track.start_time() + track.duration())
+            .max()
+            .unwrap_or(0);
+
+        let mut bytes = Vec::new();
+        write_box(&mut bytes, b"moov", |bytes| {
+            write_full_box(bytes, b"mvhd", 0, 0, |bytes| {
+                bytes.put_u32(0); // creation time
+                bytes.put_u32(0); // modification time
+                bytes.put_u32(TIMESCALE);
+                bytes.put_u32(movie_duration);
+                bytes.put_u32(0x00010000); // rate
+                bytes.put_u16(0x0100); // volume
+                bytes.put_slice(&[0; 10]); // reserved
+                write_matrix(bytes);
+                bytes.put_slice(&[0; 24]); // pre_defined
+                bytes.put_u32(AUDIO_TRACK_ID + 1); // next track id
+            });
+
+            for track in &tracks {
+                write_track(bytes, track);
+            }
+        });
+
+        bytes
+    }
+}
+
+impl MediaFileWriter for Mp4Writer {
+    fn header(&mut self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_box(&mut bytes, b"ftyp", |bytes| {
+            bytes.put_slice(b"isom");
+            bytes.put_u32(512);
+            bytes.put_slice(b"isomiso2avc1mp41");
+        });
+
+        // The mdat size isn't known until the recording is finalized, so use the 64 bit size
+        // form with a placeholder that gets patched in later
+        self.mdat_offset = bytes.len() as u64;
+        bytes.put_u32(1);
+        bytes.put_slice(b"mdat");
+        bytes.put_u64(0);
+
+        self.position = bytes.len() as u64;
+        bytes
+    }
+
+    fn write_media(&mut self, media: RecordedMedia) -> Vec<u8> {
+        match media {
+            RecordedMedia::Video {
+                codec: VideoCodec::H264,
+                is_sequence_header: true,
+                data,
+                ..
+            } => {
+                if self.video_config.is_none() {
+                    let (width, height) = parse_dimensions(&data).unwrap_or_else(|| {
+                        warn!("Could not read video dimensions from the H264 sequence header");
+                        (0, 0)
+                    });
+
+                    self.video_config = Some(VideoConfig {
+                        avc_decoder_configuration: data,
+                        width,
+                        height,
+                    });
+                }
+
+                Vec::new()
+            }
+
+            RecordedMedia::Video {
+                codec: VideoCodec::H264,
+                is_keyframe,
+                data,
+                timestamp,
+                ..
+            } => {
+                if self.video_config.is_none() {
+                    return Vec::new();
+                }
+
+                let mut sample = self.create_sample(data.len(), timestamp.dts());
+                sample.is_keyframe = is_keyframe;
+                sample.composition_offset = timestamp.pts_offset().max(0) as u32;
+                self.video_samples.push(sample);
+
+                data.to_vec()
+            }
+
+            RecordedMedia::Audio {
+                codec: AudioCodec::Aac,
+                is_sequence_header: true,
+                data,
+                ..
+            } => {
+                if self.audio_config.is_none() {
+                    match parse_audio_specific_config(data) {
+                        Some(config) => self.audio_config = Some(config),
+                        None => warn!("Could not parse the AAC sequence header"),
+                    }
+                }
+
+                Vec::new()
+            }
+
+            RecordedMedia::Audio {
+                codec: AudioCodec::Aac,
+                data,
+                timestamp,
+                ..
+            } => {
+                if self.audio_config.is_none() {
+                    return Vec::new();
+                }
+
+                let sample = self.create_sample(data.len(), timestamp);
+                self.audio_samples.push(sample);
+
+                data.to_vec()
+            }
+
+            RecordedMedia::Video {
+                codec: VideoCodec::Unknown,
+                ..
+            } => Vec::new(),
+
+            RecordedMedia::Audio {
+                codec: AudioCodec::Unknown,
+                ..
+            } => Vec::new(),
+        }
+    }
+
+    fn finalize(&mut self) -> Finalization {
+        let mdat_size = self.position - self.mdat_offset;
+
+        Finalization {
+            trailer: self.create_moov(),
+            patches: vec![(self.mdat_offset + 8, mdat_size.to_be_bytes().to_vec())],
+        }
+    }
+}
+
+impl<'a> Track<'a> {
+    fn start_time(&self) -> u32 {
+        self.samples.first().map(|sample| sample.dts).unwrap_or(0) as u32
+    }
+
+    fn duration(&self) -> u32 {
+        self.durations.iter().sum()
+    }
+}
+
+fn write_track(bytes: &mut Vec<u8>, track: &Track) {
+    let (width, height) = match track.kind {
+        TrackKind::Video(config) => (config.width, config.height),
+        TrackKind::Audio(_) => (0, 0),
+    };
+
+    write_box(bytes, b"trak", |bytes| {
+        write_full_box(bytes, b"tkhd", 0, 0x03, |bytes| {
+            bytes.put_u32(0); // creation time
+            bytes.put_u32(0); // modification time
+            bytes.put_u32(track.id);
+            bytes.put_u32(0); // reserved
+            bytes.put_u32(track.start_time() + track.duration());
+            bytes.put_u64(0); // reserved
+            bytes.put_u16(0); // layer
+            bytes.put_u16(0); // alternate group
+            bytes.put_u16(match track.kind {
+                TrackKind::Video(_) => 0,
+                TrackKind::Audio(_) => 0x0100,
+            });
+            bytes.put_u16(0); // reserved
+            write_matrix(bytes);
+            bytes.put_u32((width as u32) << 16);
+            bytes.put_u32((height as u32) << 16);
+        });
+
+        // Tracks that start after the beginning of the recording need an empty edit, otherwise
+        // players will start them at time zero and they'll be out of sync with other tracks
+        if track.start_time() > 0 {
+            write_box(bytes, b"edts", |bytes| {
+                write_full_box(bytes, b"elst", 0, 0, |bytes| {
+                    bytes.put_u32(2);
+                    bytes.put_u32(track.start_time());
+                    bytes.put_i32(-1);
+                    bytes.put_u32(0x00010000);
+                    bytes.put_u32(track.duration());
+                    bytes.put_i32(0);
+                    bytes.put_u32(0x00010000);
+                });
+            });
+        }
+
+        write_box(bytes, b"mdia", |bytes| {
+            write_full_box(bytes, b"mdhd", 0, 0, |bytes| {
+                bytes.put_u32(0); // creation time
+                bytes.put_u32(0); // modification time
+                bytes.put_u32(TIMESCALE);
+                bytes.put_u32(track.duration());
+                bytes.put_u16(0x55c4); // 'und' language
+                bytes.put_u16(0); // pre_defined
+            });
+
+            let (handler, name) = match track.kind {
+                TrackKind::Video(_) => (b"vide", b"VideoHandler\0"),
+                TrackKind::Audio(_) => (b"soun", b"SoundHandler\0"),
+            };
+
+            write_full_box(bytes, b"hdlr", 0, 0, |bytes| {
+                bytes.put_u32(0); // pre_defined
+                bytes.put_slice(handler);
+                bytes.put_slice(&[0; 12]); // reserved
+                bytes.put_slice(name);
+            });
+
+            write_box(bytes, b"minf", |bytes| {
+                match track.kind {
+                    TrackKind::Video(_) => write_full_box(bytes, b"vmhd", 0, 1, |bytes| {
+                        bytes.put_u16(0); // graphics mode
+                        bytes.put_slice(&[0; 6]); // opcolor
+                    }),
+
+                    TrackKind::Audio(_) => write_full_box(bytes, b"smhd", 0, 0, |bytes| {
+                        bytes.put_u16(0); // balance
+                        bytes.put_u16(0); // reserved
+                    }),
+                }
+
+                write_box(bytes, b"dinf", |bytes| {
+                    write_full_box(bytes, b"dref", 0, 0, |bytes| {
+                        bytes.put_u32(1);
+                        write_full_box(bytes, b"url ", 0, 1, |_| ()); // media is in this file
+                    });
+                });
+
+                write_sample_table(bytes, track);
+            });
+        });
+    });
+}
+
+fn write_sample_table(bytes: &mut Vec<u8>, track: &Track) {
+    write_box(bytes, b"stbl", |bytes| {
+        write_full_box(bytes, b"stsd", 0, 0, |bytes| {
+            bytes.put_u32(1);
+            match track.kind {
+                TrackKind::Video(config) => write_avc1_sample_entry(bytes, config),
+                TrackKind::Audio(config) => write_mp4a_sample_entry(bytes, config),
+            }
+        });
+
+        let time_to_sample = run_length_encode(track.durations.iter().copied());
+        write_full_box(bytes, b"stts", 0, 0, |bytes| {
+            bytes.put_u32(time_to_sample.len() as u32);
+            for (count, duration) in &time_to_sample {
+                bytes.put_u32(*count);
+                bytes.put_u32(*duration);
+            }
+        });
+
+        if track.samples.iter().any(|s| s.composition_offset > 0) {
+            let offsets = run_length_encode(track.samples.iter().map(|s| s.composition_offset));
+            write_full_box(bytes, b"ctts", 0, 0, |bytes| {
+                bytes.put_u32(offsets.len() as u32);
+                for (count, offset) in &offsets {
+                    bytes.put_u32(*count);
+                    bytes.put_u32(*offset);
+                }
+            });
+        }
+
+        if let TrackKind::Video(_) = track.kind {
+            let keyframes = track
+                .samples
+                .iter()
+                .enumerate()
+                .filter(|(_, sample)| sample.is_keyframe)
+                .map(|(index, _)| index as u32 + 1)
+                .collect::<Vec<_>>();
+
+            write_full_box(bytes, b"stss", 0, 0, |bytes| {
+                bytes.put_u32(keyframes.len() as u32);
+                for sample_number in &keyframes {
+                    bytes.put_u32(*sample_number);
+                }
+            });
+        }
+
+        // Samples from different tracks are interleaved, so each sample is its own chunk
+        write_full_box(bytes, b"stsc", 0, 0, |bytes| {
+            bytes.put_u32(1);
+            bytes.put_u32(1); // first chunk
+            bytes.put_u32(1); // samples per chunk
+            bytes.put_u32(1); // sample description index
+        });
+
+        write_full_box(bytes, b"stsz", 0, 0, |bytes| {
+            bytes.put_u32(0); // samples have different sizes
+            bytes.put_u32(track.samples.len() as u32);
+            for sample in track.samples {
+                bytes.put_u32(sample.size);
+            }
+        });
+
+        write_full_box(bytes, b"co64", 0, 0, |bytes| {
+            bytes.put_u32(track.samples.len() as u32);
+            for sample in track.samples {
+                bytes.put_u64(sample.offset);
+            }
+        });
+    });
+}
+
+fn write_avc1_sample_entry(bytes: &mut Vec<u8>, config: &VideoConfig) {
+    write_box(bytes, b"avc1", |bytes| {
+        bytes.put_slice(&[0; 6]); // reserved
+        bytes.put_u16(1); // data reference index
+        bytes.put_slice(&[0; 16]); // pre_defined and reserved
+        bytes.put_u16(config.width);
+        bytes.put_u16(config.height);
+        bytes.put_u32(0x00480000); // 72 dpi horizontal resolution
+        bytes.put_u32(0x00480000); // 72 dpi vertical resolution
+        bytes.put_u32(0); // reserved
+        bytes.put_u16(1); // frame count
+        bytes.put_slice(&[0; 32]); // compressor name
+        bytes.put_u16(0x0018); // depth
+        bytes.put_i16(-1); // pre_defined
+
+        write_box(bytes, b"avcC", |bytes| {
+            bytes.put_slice(&config.avc_decoder_configuration);
+        });
+    });
+}
+
+fn write_mp4a_sample_entry(bytes: &mut Vec<u8>, config: &AudioConfig) {
+    write_box(bytes, b"mp4a", |bytes| {
+        bytes.put_slice(&[0; 6]); // reserved
+        bytes.put_u16(1); // data reference index
+        bytes.put_u64(0); // reserved
+        bytes.put_u16(config.channels);
+        bytes.put_u16(16); // sample size
+        bytes.put_u16(0); // pre_defined
+        bytes.put_u16(0); // reserved
+
+        // The sample rate is a 16.16 fixed point number, so rates that don't fit are left for
+        // the decoder to read from the audio specific config
+        let sample_rate = if config.sample_rate <= u16::MAX as u32 {
+            config.sample_rate << 16
+        } else {
+            0
+        };
+
+        bytes.put_u32(sample_rate);
+
+        write_full_box(bytes, b"esds", 0, 0, |bytes| {
+            let mut decoder_config = Vec::new();
+            decoder_config.put_u8(0x40); // MPEG-4 audio
+            decoder_config.put_u8(0x15); // audio stream
+            decoder_config.put_uint(0, 3); // buffer size
+            decoder_config.put_u32(0); // max bitrate
+            decoder_config.put_u32(0); // average bitrate
+            write_descriptor(&mut decoder_config, 0x05, &config.audio_specific_config);
+
+            let mut es_descriptor = Vec::new();
+            es_descriptor.put_u16(0); // ES id
+            es_descriptor.put_u8(0); // flags
+            write_descriptor(&mut es_descriptor, 0x04, &decoder_config);
+            write_descriptor(&mut es_descriptor, 0x06, &[0x02]);
+
+            write_descriptor(bytes, 0x03, &es_descriptor);
+        });
+    });
+}
+
+fn write_box(bytes: &mut Vec<u8>, box_type: &[u8; 4], content: impl FnOnce(&mut Vec<u8>)) {
+    let start = bytes.len();
+    bytes.put_u32(0); // size placeholder
+    bytes.put_slice(box_type);
+    content(bytes);
+
+    let size = (bytes.len() - start) as u32;
+    bytes[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+fn write_full_box(
+    bytes: &mut Vec<u8>,
+    box_type: &[u8; 4],
+    version: u8,
+    flags: u32,
+    content: impl FnOnce(&mut Vec<u8>),
+) {
+    write_box(bytes, box_type, |bytes| {
+        bytes.put_u8(version);
+        bytes.put_uint(flags as u64, 3);
+        content(bytes);
+    });
+}
+
+fn write_matrix(bytes: &mut Vec<u8>) {
+    for value in &UNITY_MATRIX {
+        bytes.put_u32(*value);
+    }
+}
+
+/// Writes an MPEG-4 descriptor.  The length always uses the 4 byte form so descriptors don't
+/// need to be sized ahead of time.
+fn write_descriptor(bytes: &mut Vec<u8>, tag: u8, content: &[u8]) {
+    let length = content.len() as u32;
+    bytes.put_u8(tag);
+    bytes.put_u8(((length >> 21) & 0x7f) as u8 | 0x80);
+    bytes.put_u8(((length >> 14) & 0x7f) as u8 | 0x80);
+    bytes.put_u8(((length >> 7) & 0x7f) as u8 | 0x80);
+    bytes.put_u8((length & 0x7f) as u8);
+    bytes.put_slice(content);
+}
+
+/// Sample durations are the difference between the sample's dts and the next sample's dts.  The
+/// last sample has no next sample, so it's assumed to last as long as the one before it.
+fn get_sample_durations(samples: &[Sample]) -> Vec<u32> {
+    let mut durations = samples
+        .windows(2)
+        .map(|pair| pair[1].dts.saturating_sub(pair[0].dts) as u32)
+        .collect::<Vec<_>>();
+
+    if !samples.is_empty() {
+        durations.push(durations.last().copied().unwrap_or(0));
+    }
+
+    durations
+}
+
+fn run_length_encode(values: impl Iterator<Item = u32>) -> Vec<(u32, u32)> {
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for value in values {
+        match runs.last_mut() {
+            Some((count, last_value)) if *last_value == value => *count += 1,
+            _ => runs.push((1, value)),
+        }
+    }
+
+    runs
+}
+
+fn parse_audio_specific_config(data: Bytes) -> Option<AudioConfig> {
+    let config = AudioSpecificConfig::parse(&data).ok()?;
+
+    Some(AudioConfig {
+        audio_specific_config: data,
+        sample_rate: config.sample_rate,
+        channels: config.channel_configuration as u16,
+    })
+}
+
+/// Reads the video dimensions out of the first SPS in an AVC decoder configuration record
+fn parse_dimensions(avc_decoder_configuration: &[u8]) -> Option<(u16, u16)> {
+    if avc_decoder_configuration.len() < 8 || avc_decoder_configuration[5] & 0x1f == 0 {
+        return None;
+    }
+
+    let length = u16::from_be_bytes([avc_decoder_configuration[6], avc_decoder_configuration[7]]);
+    let sps = avc_decoder_configuration.get(8..8 + length as usize)?;
+    let rbsp = remove_emulation_prevention(sps.get(1..)?);
+    let mut reader = BitReader::new(&rbsp);
+
+    let profile_idc = reader.read_bits(8)?;
+    reader.read_bits(16)?; // constraint flags and level
+    reader.read_ue()?; // seq_parameter_set_id
+
+    let mut chroma_format_idc = 1;
+    if [100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134, 135].contains(&profile_idc) {
+        chroma_format_idc = reader.read_ue()?;
+        if chroma_format_idc == 3 {
+            reader.read_bits(1)?; // separate_colour_plane_flag
+        }
+
+        reader.read_ue()?; // bit_depth_luma_minus8
+        reader.read_ue()?; // bit_depth_chroma_minus8
+        reader.read_bits(1)?; // qpprime_y_zero_transform_bypass_flag
+
+        if reader.read_bits(1)? == 1 {
+            let list_count = if chroma_format_idc == 3 { 12 } else { 8 };
+            for index in 0..list_count {
+                if reader.read_bits(1)? == 1 {
+                    skip_scaling_list(&mut reader, if index < 6 { 16 } else { 64 })?;
+                }
+            }
+        }
+    }
+
+    reader.read_ue()?; // log2_max_frame_num_minus4
+    match reader.read_ue()? {
+        0 => {
+            reader.read_ue()?; // log2_max_pic_order_cnt_lsb_minus4
+        }
+
+        1 => {
+            reader.read_bits(1)?; // delta_pic_order_always_zero_flag
+            reader.read_se()?; // offset_for_non_ref_pic
+            reader.read_se()?; // offset_for_top_to_bottom_field
+            for _ in 0..reader.read_ue()? {
+                reader.read_se()?; // offset_for_ref_frame
+            }
+        }
+
+        _ => (),
+    }
+
+    reader.read_ue()?; // max_num_ref_frames
+    reader.read_bits(1)?; // gaps_in_frame_num_value_allowed_flag
+    let width_in_mbs = reader.read_ue()? as u64 + 1;
+    let height_in_map_units = reader.read_ue()? as u64 + 1;
+    let frame_mbs_only = reader.read_bits(1)? as u64;
+    if frame_mbs_only == 0 {
+        reader.read_bits(1)?; // mb_adaptive_frame_field_flag
+    }
+
+    reader.read_bits(1)?; // direct_8x8_inference_flag
+    let (crop_left, crop_right, crop_top, crop_bottom) = if reader.read_bits(1)? == 1 {
+        (
+            reader.read_ue()? as u64,
+            reader.read_ue()? as u64,
+            reader.read_ue()? as u64,
+            reader.read_ue()? as u64,
+        )
+    } else {
+        (0, 0, 0, 0)
+    };
+
+    let crop_unit_x = match chroma_format_idc {
+        1 | 2 => 2,
+        _ => 1,
+    };
+
+    let crop_unit_y = match chroma_format_idc {
+        1 => 2,
+        _ => 1,
+    } * (2 - frame_mbs_only);
+
+    let width = (width_in_mbs * 16).saturating_sub(crop_unit_x * (crop_left + crop_right));
+    let height = ((2 - frame_mbs_only) * height_in_map_units * 16)
+        .saturating_sub(crop_unit_y * (crop_top + crop_bottom));
+
+    Some((width.try_into().ok()?, height.try_into().ok()?))
+}
+
+fn skip_scaling_list(reader: &mut BitReader, size: usize) -> Option<()> {
+    let mut last_scale = 8;
+    let mut next_scale = 8;
+    for _ in 0..size {
+        if next_scale != 0 {
+            let delta = reader.read_se()?;
+            next_scale = (last_scale + delta + 256) % 256;
+        }
+
+        if next_scale != 0 {
+            last_scale = next_scale;
+        }
+    }
+
+    Some(())
+}
+
+fn remove_emulation_prevention(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    let mut zero_count = 0;
+    for byte in data {
+        if zero_count >= 2 && *byte == 0x03 {
+            zero_count = 0;
+            continue;
+        }
+
+        zero_count = if *byte == 0 { zero_count + 1 } else { 0 };
+        result.push(*byte);
+    }
+
+    result
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader { data, position: 0 }
+    }
+
+    fn read_bits(&mut self, count: u32) -> Option<u32> {
+        let mut value = 0_u64;
+        for _ in 0..count {
+            let byte = self.data.get(self.position / 8)?;
+            let bit = (byte >> (7 - (self.position % 8))) & 0x01;
+            value = (value << 1) | bit as u64;
+            self.position += 1;
+        }
+
+        Some(value as u32)
+    }
+
+    /// Reads an unsigned exponential golomb coded value
+    fn read_ue(&mut self) -> Option<u32> {
+        let mut leading_zeros = 0;
+        while self.read_bits(1)? == 0 {
+            leading_zeros += 1;
+            if leading_zeros > 31 {
+                return None;
+            }
+        }
+
+        let suffix = self.read_bits(leading_zeros)? as u64;
+        Some(((1_u64 << leading_zeros) - 1 + suffix) as u32)
+    }
+
+    /// Reads a signed exponential golomb coded value
+    fn read_se(&mut self) -> Option<i32> {
+        let value = self.read_ue()? as i64;
+        let result = if value % 2 == 0 {
+            -(value / 2)
+        } else {
+            (value + 1) / 2
+        };
+
+        Some(result as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VideoTimestamp;
+
+    /// Baseline profile SPS for 1280x720 video
+    const SPS: [u8; 9] = [0x67, 0x42, 0x00, 0x1e, 0xda, 0x01, 0x40, 0x16, 0xe4];
+    const PPS: [u8; 3] = [0x68, 0xce, 0x3c];
+
+    /// AAC LC, 44.1khz stereo
+    const AUDIO_SPECIFIC_CONFIG: [u8; 2] = [0x12, 0x10];
+
+    fn video_sequence_header() -> Bytes {
+        let mut data = vec![0x01, 0x42, 0x00, 0x1e, 0xff, 0xe1];
+        data.extend_from_slice(&(SPS.len() as u16).to_be_bytes());
+        data.extend_from_slice(&SPS);
+        data.push(0x01);
+        data.extend_from_slice(&(PPS.len() as u16).to_be_bytes());
+        data.extend_from_slice(&PPS);
+
+        Bytes::from(data)
+    }
+
+    fn video(is_sequence_header: bool, data: Bytes, dts: u64) -> RecordedMedia {
+        RecordedMedia::Video {
+            codec: VideoCodec::H264,
+            is_keyframe: true,
+            is_sequence_header,
+            data,
+            timestamp: VideoTimestamp::from_durations(
+                Duration::from_millis(dts),
+                Duration::from_millis(dts),
+            ),
+        }
+    }
+
+    fn audio(is_sequence_header: bool, data: Bytes, timestamp: u64) -> RecordedMedia {
+        RecordedMedia::Audio {
+            codec: AudioCodec::Aac,
+            is_sequence_header,
+            data,
+            timestamp: Duration::from_millis(timestamp),
+        }
+    }
+
+    fn count_occurrences(data: &[u8], pattern: &[u8]) -> usize {
+        data.windows(pattern.len())
+            .filter(|window| *window == pattern)
+            .count()
+    }
+
+    #[test]
+    fn can_parse_dimensions_from_sequence_header() {
+        let dimensions = parse_dimensions(&video_sequence_header());
+
+        assert_eq!(dimensions, Some((1280, 720)), "Unexpected dimensions");
+    }
+
+    #[test]
+    fn can_parse_audio_specific_config() {
+        let config = parse_audio_specific_config(Bytes::from(AUDIO_SPECIFIC_CONFIG.to_vec()));
+
+        assert_eq!(
+            config,
+            Some(AudioConfig {
+                audio_specific_config: Bytes::from(AUDIO_SPECIFIC_CONFIG.to_vec()),
+                sample_rate: 44100,
+                channels: 2,
+            }),
+            "Unexpected audio config"
+        );
+    }
+
+    #[test]
+    fn emulation_prevention_bytes_removed() {
+        let result = remove_emulation_prevention(&[0x01, 0x00, 0x00, 0x03, 0x01, 0x00, 0x03]);
+
+        assert_eq!(result, vec![0x01, 0x00, 0x00, 0x01, 0x00, 0x03]);
+    }
+
+    #[test]
+    fn media_before_sequence_headers_is_dropped() {
+        let mut writer = Mp4Writer::new();
+        let _ = writer.header();
+
+        let video_bytes = writer.write_media(video(false, Bytes::from(vec![1, 2, 3]), 0));
+        let audio_bytes = writer.write_media(audio(false, Bytes::from(vec![4, 5]), 0));
+
+        assert!(video_bytes.is_empty(), "Expected no video bytes");
+        assert!(audio_bytes.is_empty(), "Expected no audio bytes");
+    }
+
+    #[test]
+    fn samples_written_into_mdat_and_finalized_with_moov() {
+        let mut writer = Mp4Writer::new();
+        let header = writer.header();
+        assert_eq!(&header[4..8], b"ftyp", "Expected ftyp box first");
+        assert_eq!(
+            &header[writer.mdat_offset as usize + 4..writer.mdat_offset as usize + 8],
+            b"mdat",
+            "Expected mdat box after ftyp"
+        );
+
+        let _ = writer.write_media(video(true, video_sequence_header(), 100));
+        let _ = writer.write_media(audio(
+            true,
+            Bytes::from(AUDIO_SPECIFIC_CONFIG.to_vec()),
+            100,
+        ));
+
+        let first = writer.write_media(video(false, Bytes::from(vec![1, 2, 3]), 100));
+        let second = writer.write_media(audio(false, Bytes::from(vec![4, 5]), 120));
+        let third = writer.write_media(video(false, Bytes::from(vec![6, 7, 8, 9]), 133));
+
+        assert_eq!(first, vec![1, 2, 3], "Unexpected first sample bytes");
+        assert_eq!(second, vec![4, 5], "Unexpected second sample bytes");
+        assert_eq!(third, vec![6, 7, 8, 9], "Unexpected third sample bytes");
+
+        let finalization = writer.finalize();
+        assert_eq!(
+            finalization.patches,
+            vec![(
+                writer.mdat_offset + 8,
+                (MDAT_HEADER_SIZE + 9).to_be_bytes().to_vec()
+            )],
+            "Unexpected mdat size patch"
+        );
+
+        let moov = &finalization.trailer;
+        assert_eq!(&moov[4..8], b"moov", "Expected moov box");
+        assert_eq!(count_occurrences(moov, b"trak"), 2, "Expected two tracks");
+        assert_eq!(count_occurrences(moov, b"avc1"), 1, "Expected avc1 entry");
+        assert_eq!(count_occurrences(moov, b"mp4a"), 1, "Expected mp4a entry");
+        assert_eq!(
+            count_occurrences(moov, b"edts"),
+            1,
+            "Expected audio track to have an edit list for its late start"
+        );
+    }
+
+    #[test]
+    fn sample_durations_based_on_next_sample() {
+        let samples = [0, 33, 66, 100]
+            .iter()
+            .map(|dts| Sample {
+                offset: 0,
+                size: 0,
+                dts: *dts,
+                composition_offset: 0,
+                is_keyframe: false,
+            })
+            .collect::<Vec<_>>();
+
+        let durations = get_sample_durations(&samples);
+
+        assert_eq!(durations, vec![33, 33, 34, 34], "Unexpected durations");
+        assert_eq!(
+            run_length_encode(durations.into_iter()),
+            vec![(2, 33), (2, 34)],
+            "Unexpected run lengths"
+        );
+    }
+}
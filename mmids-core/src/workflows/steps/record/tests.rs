@@ -0,0 +1,271 @@
+use super::*;
+use crate::workflows::steps::test_utils::create_definition;
+use crate::workflows::steps::StepTestContext;
+use futures::StreamExt;
+use std::path::{Path, PathBuf};
+
+fn get_test_dir(name: &str) -> PathBuf {
+    let mut path = std::env::temp_dir();
+    path.push(format!("mmids-record-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+
+    path
+}
+
+fn create_context(output_dir: &Path, format: &str) -> StepTestContext {
+    let definition = create_definition(
+        "record",
+        &[(OUTPUT_DIR, output_dir.to_str().unwrap()), (FORMAT, format)],
+    );
+    let generator = RecordStepGenerator::new();
+
+    StepTestContext::new(Box::new(generator), definition).unwrap()
+}
+
+fn new_stream() -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn disconnection() -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
+    }
+}
+
+fn video() -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            is_keyframe: true,
+            is_sequence_header: false,
+            data: Bytes::from(vec![0, 0, 0, 1, 0x65]),
+            timestamp: VideoTimestamp::from_zero(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn audio() -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Audio {
+            codec: AudioCodec::Aac,
+            is_sequence_header: false,
+            data: Bytes::from(vec![1, 2, 3]),
+            timestamp: Duration::from_millis(0),
+        },
+        tags: Vec::new(),
+    }
+}
+
+async fn wait_for_recording_to_finish(context: &mut StepTestContext) {
+    let notification = tokio::time::timeout(Duration::from_secs(5), context.futures.next())
+        .await
+        .expect("Timed out waiting for recording to finish")
+        .expect("No recording future pending");
+
+    context.execute_notification(notification).await;
+}
+
+fn get_recordings(output_dir: &Path) -> Vec<PathBuf> {
+    let mut files = std::fs::read_dir(output_dir)
+        .expect("Failed to read output dir")
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+
+    files.sort();
+    files
+}
+
+#[test]
+fn error_if_no_output_dir_specified() {
+    let mut definition = create_definition("record", &[(OUTPUT_DIR, "abc"), (FORMAT, "flv")]);
+    definition.parameters.remove(OUTPUT_DIR);
+
+    let generator = RecordStepGenerator::new();
+    let result = StepTestContext::new(Box::new(generator), definition);
+
+    assert!(result.is_err(), "Expected error");
+}
+
+#[test]
+fn error_if_no_format_specified() {
+    let definition = create_definition("record", &[(OUTPUT_DIR, "abc")]);
+    let generator = RecordStepGenerator::new();
+    let result = StepTestContext::new(Box::new(generator), definition);
+
+    assert!(result.is_err(), "Expected error");
+}
+
+#[test]
+fn error_if_unknown_format_specified() {
+    let definition = create_definition("record", &[(OUTPUT_DIR, "abc"), (FORMAT, "mkv")]);
+    let generator = RecordStepGenerator::new();
+    let result = StepTestContext::new(Box::new(generator), definition);
+
+    assert!(result.is_err(), "Expected error");
+}
+
+#[test]
+fn format_is_case_insensitive() {
+    let definition = create_definition("record", &[(OUTPUT_DIR, "abc"), (FORMAT, "MP4")]);
+    let generator = RecordStepGenerator::new();
+    let result = StepTestContext::new(Box::new(generator), definition);
+
+    assert!(result.is_ok(), "Expected step to be created");
+}
+
+#[test]
+fn validation_fails_for_unknown_format() {
+    let definition = create_definition("record", &[(OUTPUT_DIR, "abc"), (FORMAT, "mkv")]);
+    let generator = RecordStepGenerator::new();
+
+    assert!(generator.validate(&definition).is_err(), "Expected error");
+}
+
+#[test]
+fn validation_passes_for_known_format() {
+    let definition = create_definition("record", &[(OUTPUT_DIR, "abc"), (FORMAT, "flv")]);
+    let generator = RecordStepGenerator::new();
+
+    assert!(generator.validate(&definition).is_ok(), "Expected no error");
+}
+
+#[tokio::test]
+async fn media_passes_through_unchanged() {
+    let output_dir = get_test_dir("pass-through");
+    let mut context = create_context(&output_dir, "flv");
+
+    context.assert_media_passed_through(new_stream());
+    context.assert_media_passed_through(video());
+    context.assert_media_passed_through(audio());
+    context.assert_media_passed_through(disconnection());
+
+    wait_for_recording_to_finish(&mut context).await;
+    let _ = std::fs::remove_dir_all(&output_dir);
+}
+
+#[tokio::test]
+async fn flv_recording_written_and_finalized_on_disconnection() {
+    let output_dir = get_test_dir("flv");
+    let mut context = create_context(&output_dir, "flv");
+
+    context.execute_with_media(new_stream());
+    context.execute_with_media(video());
+    context.execute_with_media(audio());
+    context.execute_with_media(disconnection());
+    wait_for_recording_to_finish(&mut context).await;
+
+    let recordings = get_recordings(&output_dir);
+    assert_eq!(recordings.len(), 1, "Unexpected number of recordings");
+
+    let file_name = recordings[0].file_name().unwrap().to_str().unwrap();
+    assert!(
+        file_name.starts_with("def_"),
+        "Expected file name to start with the stream name: {}",
+        file_name
+    );
+    assert!(
+        file_name.ends_with(".flv"),
+        "Expected flv extension: {}",
+        file_name
+    );
+
+    let content = std::fs::read(&recordings[0]).unwrap();
+    assert_eq!(&content[..3], b"FLV", "Expected flv signature");
+
+    let _ = std::fs::remove_dir_all(&output_dir);
+}
+
+#[tokio::test]
+async fn mp4_recording_finalized_with_moov_on_disconnection() {
+    let output_dir = get_test_dir("mp4");
+    let mut context = create_context(&output_dir, "mp4");
+
+    context.execute_with_media(new_stream());
+    context.execute_with_media(disconnection());
+    wait_for_recording_to_finish(&mut context).await;
+
+    let recordings = get_recordings(&output_dir);
+    assert_eq!(recordings.len(), 1, "Unexpected number of recordings");
+
+    let content = std::fs::read(&recordings[0]).unwrap();
+    assert_eq!(&content[4..8], b"ftyp", "Expected ftyp box");
+    assert!(
+        content.windows(4).any(|window| window == b"moov"),
+        "Expected moov box"
+    );
+
+    let _ = std::fs::remove_dir_all(&output_dir);
+}
+
+#[tokio::test]
+async fn reconnection_with_same_stream_id_starts_new_recording() {
+    let output_dir = get_test_dir("reconnect");
+    let mut context = create_context(&output_dir, "flv");
+
+    context.execute_with_media(new_stream());
+    context.execute_with_media(video());
+    tokio::time::sleep(Duration::from_millis(5)).await;
+
+    context.execute_with_media(new_stream());
+    wait_for_recording_to_finish(&mut context).await;
+
+    context.execute_with_media(video());
+    context.execute_with_media(disconnection());
+    wait_for_recording_to_finish(&mut context).await;
+
+    let recordings = get_recordings(&output_dir);
+    assert_eq!(recordings.len(), 2, "Unexpected number of recordings");
+
+    let _ = std::fs::remove_dir_all(&output_dir);
+}
+
+#[tokio::test]
+async fn shutdown_finalizes_recordings() {
+    let output_dir = get_test_dir("shutdown");
+    let mut context = create_context(&output_dir, "flv");
+
+    context.execute_with_media(new_stream());
+    context.step.shutdown();
+    wait_for_recording_to_finish(&mut context).await;
+
+    let recordings = get_recordings(&output_dir);
+    assert_eq!(recordings.len(), 1, "Unexpected number of recordings");
+
+    let _ = std::fs::remove_dir_all(&output_dir);
+}
+
+#[tokio::test]
+async fn only_h264_and_aac_are_supported() {
+    let output_dir = get_test_dir("codecs");
+    let context = create_context(&output_dir, "mp4");
+    let codecs = context.step.get_supported_codecs();
+
+    assert_eq!(codecs.get_unsupported_codec(&video().content), None);
+    assert_eq!(codecs.get_unsupported_codec(&audio().content), None);
+
+    let unknown_audio = MediaNotificationContent::Audio {
+        codec: AudioCodec::Unknown,
+        is_sequence_header: false,
+        data: Bytes::from(vec![1, 2, 3]),
+        timestamp: Duration::from_millis(0),
+    };
+
+    assert_eq!(
+        codecs.get_unsupported_codec(&unknown_audio),
+        Some("Unknown audio".to_string()),
+        "Expected unknown audio codec to be unsupported"
+    );
+
+    let _ = std::fs::remove_dir_all(&output_dir);
+}
@@ -0,0 +1,158 @@
+//! The rename stream step changes the name of streams that pass through it.  Streams can either be
+//! renamed from one specific name to another (via the `from` and `to` parameters), or all streams
+//! can have a prefix added to their name (via the `prefix` parameter).
+//!
+//! Only new incoming stream notifications carry the stream's name, so they are the only
+//! notifications that are modified.  All other media is keyed by stream id and is passed along
+//! as is.
+
+#[cfg(test)]
+mod tests;
+
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use crate::workflows::MediaNotificationContent;
+use crate::StreamId;
+use std::collections::HashMap;
+use thiserror::Error;
+
+const FROM: &str = "from";
+const TO: &str = "to";
+const PREFIX: &str = "prefix";
+
+/// Generates new instances of the rename stream workflow step based on specified step definitions.
+pub struct RenameStreamStepGenerator {}
+
+struct RenameStreamStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    rename: RenameType,
+    renamed_streams: HashMap<StreamId, String>,
+}
+
+enum RenameType {
+    Exact { from: String, to: String },
+    Prefix(String),
+}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error("No rename specified.  Either 'from' and 'to' parameters or a 'prefix' parameter is required")]
+    NoRenameProvided,
+
+    #[error("Both a 'from' and 'to' parameter must be specified together")]
+    IncompleteRename,
+
+    #[error("A 'prefix' parameter can not be combined with 'from' and 'to' parameters")]
+    ConflictingRenames,
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl RenameStreamStepGenerator {
+    pub fn new() -> Self {
+        RenameStreamStepGenerator {}
+    }
+}
+
+impl StepGenerator for RenameStreamStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let rename = parse_rename(&definition)?;
+        let step = RenameStreamStep {
+            definition: definition.clone(),
+            status: StepStatus::Active,
+            rename,
+            renamed_streams: HashMap::new(),
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_rename(definition)?;
+        Ok(())
+    }
+}
+
+fn parse_rename(definition: &WorkflowStepDefinition) -> Result<RenameType, StepStartupError> {
+    let get_parameter = |name: &str| match definition.parameters.get(name) {
+        Some(Some(value)) => Some(value.to_string()),
+        _ => None,
+    };
+
+    match (
+        get_parameter(FROM),
+        get_parameter(TO),
+        get_parameter(PREFIX),
+    ) {
+        (Some(from), Some(to), None) => Ok(RenameType::Exact { from, to }),
+        (None, None, Some(prefix)) => Ok(RenameType::Prefix(prefix)),
+        (None, None, None) => Err(StepStartupError::NoRenameProvided),
+        (_, _, Some(_)) => Err(StepStartupError::ConflictingRenames),
+        _ => Err(StepStartupError::IncompleteRename),
+    }
+}
+
+impl RenameStreamStep {
+    fn get_new_name(&self, stream_name: &str) -> Option<String> {
+        match &self.rename {
+            RenameType::Exact { from, to } if from == stream_name => Some(to.clone()),
+            RenameType::Exact { .. } => None,
+            RenameType::Prefix(prefix) => Some(format!("{}{}", prefix, stream_name)),
+        }
+    }
+}
+
+impl WorkflowStep for RenameStreamStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for mut media in inputs.media.drain(..) {
+            match &media.content {
+                MediaNotificationContent::NewIncomingStream { stream_name } => {
+                    // Re-use an existing mapping, so duplicate notifications for the same stream
+                    // stay consistent with the name that was first given
+                    let new_name = match self.renamed_streams.get(&media.stream_id) {
+                        Some(name) => Some(name.clone()),
+                        None => self.get_new_name(stream_name),
+                    };
+
+                    if let Some(new_name) = new_name {
+                        self.renamed_streams
+                            .insert(media.stream_id.clone(), new_name.clone());
+
+                        media.content = MediaNotificationContent::NewIncomingStream {
+                            stream_name: new_name,
+                        };
+                    }
+                }
+
+                MediaNotificationContent::StreamDisconnected => {
+                    self.renamed_streams.remove(&media.stream_id);
+                }
+
+                _ => (),
+            }
+
+            outputs.media.push(media);
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+    }
+}
@@ -0,0 +1,140 @@
+use super::*;
+use crate::codecs::VideoCodec;
+use crate::workflows::steps::test_utils::create_definition;
+use crate::workflows::steps::StepTestContext;
+use crate::workflows::MediaNotification;
+use crate::VideoTimestamp;
+use bytes::Bytes;
+
+fn create_context(parameters: &[(&str, &str)]) -> StepTestContext {
+    let generator = RenameStreamStepGenerator::new();
+    StepTestContext::new(
+        Box::new(generator),
+        create_definition("rename_stream", parameters),
+    )
+    .unwrap()
+}
+
+fn new_stream(stream_id: &str, stream_name: &str) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: stream_name.to_string(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn assert_output_stream_name(context: &StepTestContext, expected_name: &str) {
+    assert_eq!(context.media_outputs.len(), 1, "Expected one media output");
+    match &context.media_outputs[0].content {
+        MediaNotificationContent::NewIncomingStream { stream_name } => {
+            assert_eq!(stream_name, expected_name, "Unexpected stream name");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[test]
+fn step_fails_to_generate_without_parameters() {
+    let generator = RenameStreamStepGenerator::new();
+    assert!(generator
+        .generate(create_definition("rename_stream", &[]))
+        .is_err());
+}
+
+#[test]
+fn step_fails_to_generate_with_from_but_no_to() {
+    let generator = RenameStreamStepGenerator::new();
+    assert!(generator
+        .generate(create_definition("rename_stream", &[(FROM, "abc")]))
+        .is_err());
+}
+
+#[test]
+fn step_fails_to_generate_with_prefix_and_from_to() {
+    let generator = RenameStreamStepGenerator::new();
+    let definition = create_definition(
+        "rename_stream",
+        &[(FROM, "abc"), (TO, "def"), (PREFIX, "tenant-")],
+    );
+    assert!(generator.generate(definition).is_err());
+}
+
+#[test]
+fn validation_fails_with_from_but_no_to() {
+    let generator = RenameStreamStepGenerator::new();
+    assert!(generator
+        .validate(&create_definition("rename_stream", &[(FROM, "abc")]))
+        .is_err());
+}
+
+#[test]
+fn validation_passes_with_prefix() {
+    let generator = RenameStreamStepGenerator::new();
+    assert!(generator
+        .validate(&create_definition("rename_stream", &[(PREFIX, "tenant-")]))
+        .is_ok());
+}
+
+#[test]
+fn matching_stream_renamed_with_from_and_to() {
+    let mut context = create_context(&[(FROM, "abc"), (TO, "def")]);
+    context.execute_with_media(new_stream("1", "abc"));
+
+    assert_output_stream_name(&context, "def");
+}
+
+#[test]
+fn non_matching_stream_not_renamed_with_from_and_to() {
+    let mut context = create_context(&[(FROM, "abc"), (TO, "def")]);
+    context.assert_media_passed_through(new_stream("1", "xyz"));
+}
+
+#[test]
+fn stream_renamed_with_prefix() {
+    let mut context = create_context(&[(PREFIX, "tenant-")]);
+    context.execute_with_media(new_stream("1", "abc"));
+
+    assert_output_stream_name(&context, "tenant-abc");
+}
+
+#[test]
+fn duplicate_new_stream_notification_uses_original_mapping() {
+    let mut context = create_context(&[(FROM, "abc"), (TO, "def")]);
+    context.execute_with_media(new_stream("1", "abc"));
+    context.execute_with_media(new_stream("1", "xyz"));
+
+    assert_output_stream_name(&context, "def");
+}
+
+#[test]
+fn mapping_removed_after_stream_disconnected() {
+    let mut context = create_context(&[(FROM, "abc"), (TO, "def")]);
+    context.execute_with_media(new_stream("1", "abc"));
+    context.assert_media_passed_through(MediaNotification {
+        stream_id: StreamId("1".to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
+    });
+
+    context.assert_media_passed_through(new_stream("1", "xyz"));
+}
+
+#[test]
+fn video_passed_through_unchanged() {
+    let mut context = create_context(&[(PREFIX, "tenant-")]);
+    context.execute_with_media(new_stream("1", "abc"));
+    context.assert_media_passed_through(MediaNotification {
+        stream_id: StreamId("1".to_string()),
+        content: MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            is_keyframe: true,
+            is_sequence_header: false,
+            data: Bytes::from(vec![1, 2, 3]),
+            timestamp: VideoTimestamp::from_zero(),
+        },
+        tags: Vec::new(),
+    });
+}
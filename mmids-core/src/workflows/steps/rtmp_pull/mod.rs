@@ -0,0 +1,670 @@
+//! The rtmp pull step ingests a stream by connecting out to a remote RTMP server and playing a
+//! stream from it, instead of waiting for a publisher to connect to mmids.  It's the inbound
+//! counterpart of the `rtmp_push` step.
+//!
+//! Once the remote server accepts the playback request a new stream is raised, named after the
+//! stream key being played, and all media received is passed along to the next workflow step.
+//! If the connection fails or is dropped the stream is disconnected, and a new connection is
+//! attempted after a delay.  The delay doubles for each consecutive failure, up to a maximum, and
+//! is reset once playback has successfully started.
+//!
+//! Any media passed into the step is passed through unchanged.
+
+#[cfg(test)]
+mod tests;
+
+use super::rtmp_push::parse_target_url;
+use crate::endpoints::rtmp_server::{
+    unwrap_audio_from_flv, unwrap_video_from_flv, UnwrappedAudio, UnwrappedVideo,
+};
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::{StepGenerator, StepKind};
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::{StreamId, VideoTimestamp};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use rml_rtmp::handshake::{Handshake, HandshakeProcessResult, PeerType};
+use rml_rtmp::sessions::{
+    ClientSession, ClientSessionConfig, ClientSessionError, ClientSessionEvent,
+    ClientSessionResult, StreamMetadata,
+};
+use rml_rtmp::time::RtmpTimestamp;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+pub const SOURCE_URL: &str = "source_url";
+pub const APP: &str = "app";
+pub const STREAM_KEY: &str = "stream_key";
+
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// How long the connection can go without receiving anything from the RTMP server before it's
+/// considered stalled and dropped, so the step can reconnect.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Generates new instances of the rtmp pull workflow step based on specified step definitions.
+pub struct RtmpPullStepGenerator {}
+
+struct RtmpPullStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    source: Arc<PullSource>,
+    active_stream_id: Option<StreamId>,
+    consecutive_failures: u32,
+
+    /// Dropping this closes the current connection to the remote server
+    connection_cancellation: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Debug)]
+struct PullSource {
+    host: String,
+    port: u16,
+    app: String,
+    stream_key: String,
+}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error("No source url specified.  A 'source_url' parameter is required")]
+    NoSourceUrlProvided,
+
+    #[error(
+        "Invalid source url of '{0}'.  Source urls must be in the format of 'rtmp://host[:port]'"
+    )]
+    InvalidSourceUrl(String),
+
+    #[error("No rtmp application specified.  An 'app' parameter is required")]
+    NoAppProvided,
+
+    #[error("No stream key specified.  A 'stream_key' parameter is required")]
+    NoStreamKeyProvided,
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+#[derive(Error, Debug)]
+enum PullError {
+    #[error("Timed out connecting to the RTMP server")]
+    Timeout,
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("The RTMP server closed the connection")]
+    ConnectionClosed,
+
+    #[error("No data was received from the RTMP server for {0:?}")]
+    ReadTimeout(Duration),
+
+    #[error("RTMP handshake failed: {0}")]
+    Handshake(String),
+
+    #[error("RTMP session error: {0}")]
+    Session(String),
+
+    #[error("The RTMP server rejected the connection request: {0}")]
+    ConnectionRejected(String),
+}
+
+/// Events raised by the connection to the remote server
+enum PullEvent {
+    PlaybackStarted,
+    Metadata(StreamMetadata),
+    Video {
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    },
+    Audio {
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    },
+    ConnectionFailed(PullError),
+    ConnectionLost(PullError),
+}
+
+enum FutureResult {
+    PullEventReceived(PullEvent, UnboundedReceiver<PullEvent>),
+    ConnectionEnded,
+    ReconnectDelayElapsed,
+}
+
+impl StepFutureResult for FutureResult {}
+
+struct PullConnection {
+    socket: TcpStream,
+    session: ClientSession,
+
+    /// Events that were received along with the playback acceptance
+    pending_events: Vec<ClientSessionEvent>,
+
+    read_timeout: Duration,
+}
+
+impl RtmpPullStepGenerator {
+    pub fn new() -> Self {
+        RtmpPullStepGenerator {}
+    }
+}
+
+impl StepGenerator for RtmpPullStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let source = parse_pull_source(&definition)?;
+        let mut step = RtmpPullStep {
+            definition,
+            status: StepStatus::Active,
+            source: Arc::new(source),
+            active_stream_id: None,
+            consecutive_failures: 0,
+            connection_cancellation: None,
+        };
+
+        let futures = vec![step.start_connection()];
+
+        Ok((Box::new(step), futures))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_pull_source(definition)?;
+        Ok(())
+    }
+
+    fn kind(&self) -> StepKind {
+        StepKind::Source
+    }
+}
+
+fn parse_pull_source(definition: &WorkflowStepDefinition) -> Result<PullSource, StepStartupError> {
+    let source_url = match definition.parameters.get(SOURCE_URL) {
+        Some(Some(value)) => value,
+        _ => return Err(StepStartupError::NoSourceUrlProvided),
+    };
+
+    let (host, port) = match parse_target_url(source_url) {
+        Some(x) => x,
+        None => return Err(StepStartupError::InvalidSourceUrl(source_url.to_string())),
+    };
+
+    let app = match definition.parameters.get(APP) {
+        Some(Some(value)) => value.to_string(),
+        _ => return Err(StepStartupError::NoAppProvided),
+    };
+
+    let stream_key = match definition.parameters.get(STREAM_KEY) {
+        Some(Some(value)) => value.to_string(),
+        _ => return Err(StepStartupError::NoStreamKeyProvided),
+    };
+
+    Ok(PullSource {
+        host,
+        port,
+        app,
+        stream_key,
+    })
+}
+
+impl RtmpPullStep {
+    fn start_connection(&mut self) -> BoxFuture<'static, Box<dyn StepFutureResult>> {
+        info!(
+            "Rtmp pull step connecting to {}:{} to play '{}/{}'",
+            self.source.host, self.source.port, self.source.app, self.source.stream_key
+        );
+
+        let (event_sender, event_receiver) = unbounded_channel();
+        let (cancellation_sender, cancellation_receiver) = oneshot::channel();
+        tokio::spawn(pull_stream(
+            self.source.clone(),
+            event_sender,
+            cancellation_receiver,
+        ));
+
+        self.connection_cancellation = Some(cancellation_sender);
+
+        wait_for_pull_event(event_receiver).boxed()
+    }
+
+    fn reconnect_delay(&self) -> Duration {
+        let exponent = self.consecutive_failures.saturating_sub(1).min(16);
+        let delay = INITIAL_RECONNECT_DELAY * 2_u32.pow(exponent);
+
+        delay.min(MAX_RECONNECT_DELAY)
+    }
+
+    fn handle_pull_event(&mut self, event: PullEvent, outputs: &mut StepOutputs) {
+        match event {
+            PullEvent::PlaybackStarted => {
+                let stream_id = StreamId(Uuid::new_v4().to_string());
+                info!(
+                    stream_id = ?stream_id,
+                    "Rtmp pull step started playing '{}/{}' from {}:{} as stream {:?}",
+                    self.source.app, self.source.stream_key, self.source.host, self.source.port,
+                    stream_id
+                );
+
+                self.consecutive_failures = 0;
+                self.active_stream_id = Some(stream_id.clone());
+                outputs.media.push(MediaNotification {
+                    stream_id,
+                    content: MediaNotificationContent::NewIncomingStream {
+                        stream_name: self.source.stream_key.clone(),
+                    },
+                    tags: Vec::new(),
+                });
+            }
+
+            PullEvent::Metadata(metadata) => {
+                self.push_media(
+                    outputs,
+                    MediaNotificationContent::Metadata {
+                        data: crate::utils::stream_metadata_to_hash_map(metadata),
+                    },
+                );
+            }
+
+            PullEvent::Video { data, timestamp } => {
+                let UnwrappedVideo {
+                    codec,
+                    is_keyframe,
+                    is_sequence_header,
+                    data,
+                    composition_time_in_ms,
+                } = unwrap_video_from_flv(data);
+
+                self.push_media(
+                    outputs,
+                    MediaNotificationContent::Video {
+                        is_keyframe,
+                        is_sequence_header,
+                        data,
+                        codec,
+                        timestamp: VideoTimestamp::from_rtmp_data(
+                            timestamp,
+                            composition_time_in_ms,
+                        ),
+                    },
+                );
+            }
+
+            PullEvent::Audio { data, timestamp } => {
+                let UnwrappedAudio {
+                    codec,
+                    is_sequence_header,
+                    data,
+                } = unwrap_audio_from_flv(data);
+
+                self.push_media(
+                    outputs,
+                    MediaNotificationContent::Audio {
+                        is_sequence_header,
+                        data,
+                        codec,
+                        timestamp: Duration::from_millis(timestamp.value as u64),
+                    },
+                );
+            }
+
+            PullEvent::ConnectionFailed(error) => {
+                warn!(
+                    "Rtmp pull step failed to connect to {}:{}: {}",
+                    self.source.host, self.source.port, error
+                );
+
+                self.handle_disconnection(outputs);
+            }
+
+            PullEvent::ConnectionLost(error) => {
+                warn!(
+                    "Rtmp pull step lost its connection to {}:{}: {}",
+                    self.source.host, self.source.port, error
+                );
+
+                self.handle_disconnection(outputs);
+            }
+        }
+    }
+
+    fn push_media(&self, outputs: &mut StepOutputs, content: MediaNotificationContent) {
+        if let Some(stream_id) = &self.active_stream_id {
+            outputs.media.push(MediaNotification {
+                stream_id: stream_id.clone(),
+                content,
+                tags: Vec::new(),
+            });
+        }
+    }
+
+    fn handle_disconnection(&mut self, outputs: &mut StepOutputs) {
+        self.connection_cancellation = None;
+        if let Some(stream_id) = self.active_stream_id.take() {
+            info!(
+                stream_id = ?stream_id,
+                "Rtmp pull step disconnecting stream {:?}", stream_id
+            );
+
+            outputs.media.push(MediaNotification {
+                stream_id,
+                content: MediaNotificationContent::StreamDisconnected,
+                tags: Vec::new(),
+            });
+        }
+
+        self.consecutive_failures += 1;
+        let delay = self.reconnect_delay();
+        info!(
+            "Rtmp pull step reconnecting to {}:{} in {:?}",
+            self.source.host, self.source.port, delay
+        );
+
+        outputs
+            .futures
+            .push(wait_for_reconnect_delay(delay).boxed());
+    }
+}
+
+impl WorkflowStep for RtmpPullStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for notification in inputs.notifications.drain(..) {
+            match notification.downcast::<FutureResult>() {
+                Ok(result) => match *result {
+                    FutureResult::PullEventReceived(event, receiver) => {
+                        if self.status == StepStatus::Active {
+                            self.handle_pull_event(event, outputs);
+                            outputs.futures.push(wait_for_pull_event(receiver).boxed());
+                        }
+                    }
+
+                    FutureResult::ConnectionEnded => (),
+
+                    FutureResult::ReconnectDelayElapsed => {
+                        if self.status == StepStatus::Active
+                            && self.connection_cancellation.is_none()
+                        {
+                            let future = self.start_connection();
+                            outputs.futures.push(future);
+                        }
+                    }
+                },
+
+                Err(_) => {
+                    error!("Rtmp pull step received a notification that is not a known type");
+                    self.status = StepStatus::Error {
+                        message: "Received future result of unknown type".to_string(),
+                    };
+
+                    return;
+                }
+            }
+        }
+
+        for media in inputs.media.drain(..) {
+            outputs.media.push(media);
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.connection_cancellation = None;
+        self.status = StepStatus::Shutdown;
+    }
+}
+
+impl PullConnection {
+    async fn connect(source: &PullSource) -> Result<Self, PullError> {
+        let mut socket = TcpStream::connect((source.host.as_str(), source.port)).await?;
+        let remaining_bytes = perform_handshake(&mut socket).await?;
+
+        let (session, results) =
+            ClientSession::new(ClientSessionConfig::new()).map_err(session_error)?;
+
+        let mut connection = PullConnection {
+            socket,
+            session,
+            pending_events: Vec::new(),
+            read_timeout: READ_TIMEOUT,
+        };
+
+        connection.handle_session_results(results).await?;
+        if !remaining_bytes.is_empty() {
+            connection.handle_input(&remaining_bytes).await?;
+        }
+
+        let result = connection
+            .session
+            .request_connection(source.app.clone())
+            .map_err(session_error)?;
+
+        connection.handle_session_results(vec![result]).await?;
+        loop {
+            let events = connection.read_events().await?;
+            for event in events {
+                match event {
+                    ClientSessionEvent::ConnectionRequestAccepted => {
+                        let result = connection
+                            .session
+                            .request_playback(source.stream_key.clone())
+                            .map_err(session_error)?;
+
+                        connection.handle_session_results(vec![result]).await?;
+                        connection.wait_for_playback_acceptance().await?;
+
+                        return Ok(connection);
+                    }
+
+                    ClientSessionEvent::ConnectionRequestRejected { description } => {
+                        return Err(PullError::ConnectionRejected(description));
+                    }
+
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    async fn wait_for_playback_acceptance(&mut self) -> Result<(), PullError> {
+        loop {
+            let events = self.read_events().await?;
+            let accepted_index = events
+                .iter()
+                .position(|event| matches!(event, ClientSessionEvent::PlaybackRequestAccepted));
+
+            if let Some(index) = accepted_index {
+                // Media can arrive in the same packets as the acceptance
+                self.pending_events = events.into_iter().skip(index + 1).collect();
+                return Ok(());
+            }
+        }
+    }
+
+    /// Forwards received media to the step until the connection fails.  `Ok` is returned if
+    /// the step is no longer listening for events.
+    async fn receive_media(
+        &mut self,
+        sender: &UnboundedSender<PullEvent>,
+    ) -> Result<(), PullError> {
+        let mut events = std::mem::take(&mut self.pending_events);
+        loop {
+            for event in events {
+                let event = match event {
+                    ClientSessionEvent::StreamMetadataReceived { metadata } => {
+                        PullEvent::Metadata(metadata)
+                    }
+
+                    ClientSessionEvent::VideoDataReceived { data, timestamp } => {
+                        PullEvent::Video { data, timestamp }
+                    }
+
+                    ClientSessionEvent::AudioDataReceived { data, timestamp } => {
+                        PullEvent::Audio { data, timestamp }
+                    }
+
+                    _ => continue,
+                };
+
+                if sender.send(event).is_err() {
+                    return Ok(());
+                }
+            }
+
+            events = self.read_events().await?;
+        }
+    }
+
+    async fn read_events(&mut self) -> Result<Vec<ClientSessionEvent>, PullError> {
+        let mut buffer = [0; 4096];
+        let bytes_read = tokio::time::timeout(self.read_timeout, self.socket.read(&mut buffer))
+            .await
+            .map_err(|_| PullError::ReadTimeout(self.read_timeout))??;
+
+        if bytes_read == 0 {
+            return Err(PullError::ConnectionClosed);
+        }
+
+        self.handle_input(&buffer[..bytes_read]).await
+    }
+
+    async fn handle_input(&mut self, bytes: &[u8]) -> Result<Vec<ClientSessionEvent>, PullError> {
+        let results = self.session.handle_input(bytes).map_err(session_error)?;
+        self.handle_session_results(results).await
+    }
+
+    /// Sends any outbound packets to the RTMP server, returning all raised events
+    async fn handle_session_results(
+        &mut self,
+        results: Vec<ClientSessionResult>,
+    ) -> Result<Vec<ClientSessionEvent>, PullError> {
+        let mut events = Vec::new();
+        for result in results {
+            match result {
+                ClientSessionResult::OutboundResponse(packet) => {
+                    self.socket.write_all(&packet.bytes).await?;
+                }
+
+                ClientSessionResult::RaisedEvent(event) => events.push(event),
+
+                _ => (),
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+fn session_error(error: ClientSessionError) -> PullError {
+    PullError::Session(format!("{:?}", error))
+}
+
+/// Performs the client side of the RTMP handshake, returning any bytes received after the
+/// handshake completed
+async fn perform_handshake(socket: &mut TcpStream) -> Result<Vec<u8>, PullError> {
+    let mut handshake = Handshake::new(PeerType::Client);
+    let p0_and_p1 = handshake
+        .generate_outbound_p0_and_p1()
+        .map_err(|error| PullError::Handshake(format!("{:?}", error)))?;
+
+    socket.write_all(&p0_and_p1).await?;
+
+    let mut buffer = [0; 4096];
+    loop {
+        let bytes_read = socket.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            return Err(PullError::ConnectionClosed);
+        }
+
+        let result = handshake
+            .process_bytes(&buffer[..bytes_read])
+            .map_err(|error| PullError::Handshake(format!("{:?}", error)))?;
+
+        match result {
+            HandshakeProcessResult::InProgress { response_bytes } => {
+                socket.write_all(&response_bytes).await?;
+            }
+
+            HandshakeProcessResult::Completed {
+                response_bytes,
+                remaining_bytes,
+            } => {
+                socket.write_all(&response_bytes).await?;
+
+                return Ok(remaining_bytes);
+            }
+        }
+    }
+}
+
+/// Plays the stream from the remote server, raising events to the step until the connection
+/// fails or is cancelled
+async fn pull_stream(
+    source: Arc<PullSource>,
+    events: UnboundedSender<PullEvent>,
+    mut cancellation: oneshot::Receiver<()>,
+) {
+    let connect = tokio::time::timeout(CONNECTION_TIMEOUT, PullConnection::connect(&source));
+    let connection = tokio::select! {
+        connection = connect => connection,
+        _ = &mut cancellation => return,
+    };
+
+    let mut connection = match connection {
+        Ok(Ok(connection)) => connection,
+        Ok(Err(error)) => {
+            let _ = events.send(PullEvent::ConnectionFailed(error));
+            return;
+        }
+
+        Err(_) => {
+            let _ = events.send(PullEvent::ConnectionFailed(PullError::Timeout));
+            return;
+        }
+    };
+
+    let _ = events.send(PullEvent::PlaybackStarted);
+    let result = tokio::select! {
+        result = connection.receive_media(&events) => result,
+        _ = &mut cancellation => return,
+    };
+
+    if let Err(error) = result {
+        let _ = events.send(PullEvent::ConnectionLost(error));
+    }
+}
+
+async fn wait_for_pull_event(
+    mut receiver: UnboundedReceiver<PullEvent>,
+) -> Box<dyn StepFutureResult> {
+    let result = match receiver.recv().await {
+        Some(event) => FutureResult::PullEventReceived(event, receiver),
+        None => FutureResult::ConnectionEnded,
+    };
+
+    Box::new(result)
+}
+
+async fn wait_for_reconnect_delay(delay: Duration) -> Box<dyn StepFutureResult> {
+    tokio::time::sleep(delay).await;
+    Box::new(FutureResult::ReconnectDelayElapsed)
+}
@@ -0,0 +1,297 @@
+use super::*;
+use crate::codecs::{AudioCodec, VideoCodec};
+use crate::workflows::steps::test_utils;
+use crate::workflows::steps::StepTestContext;
+use tokio::net::TcpListener;
+
+fn create_definition(source_url: &str) -> WorkflowStepDefinition {
+    test_utils::create_definition(
+        "rtmp_pull",
+        &[(SOURCE_URL, source_url), (APP, "live"), (STREAM_KEY, "key")],
+    )
+}
+
+/// Creates a step context connected to a server that never responds, along with a channel that
+/// can be used to raise pull events to the step as if they came from the connection.
+async fn create_playing_context() -> (StepTestContext, TcpListener, UnboundedSender<PullEvent>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let generator = RtmpPullStepGenerator::new();
+    let definition = create_definition(&format!("rtmp://127.0.0.1:{}", port));
+    let mut context = StepTestContext::new(Box::new(generator), definition).unwrap();
+
+    let (sender, receiver) = unbounded_channel();
+    context
+        .execute_notification(Box::new(FutureResult::PullEventReceived(
+            PullEvent::PlaybackStarted,
+            receiver,
+        )))
+        .await;
+
+    (context, listener, sender)
+}
+
+fn get_stream_id(context: &StepTestContext) -> StreamId {
+    assert_eq!(
+        context.media_outputs.len(),
+        1,
+        "Unexpected number of media outputs"
+    );
+
+    context.media_outputs[0].stream_id.clone()
+}
+
+#[test]
+fn step_fails_to_generate_without_source_url() {
+    let mut definition = create_definition("rtmp://localhost");
+    definition.parameters.remove(SOURCE_URL);
+
+    let generator = RtmpPullStepGenerator::new();
+    assert!(generator.generate(definition).is_err());
+}
+
+#[test]
+fn step_fails_to_generate_with_invalid_source_url() {
+    let definition = create_definition("localhost:1935");
+
+    let generator = RtmpPullStepGenerator::new();
+    assert!(generator.generate(definition).is_err());
+}
+
+#[test]
+fn step_fails_to_generate_without_app() {
+    let mut definition = create_definition("rtmp://localhost");
+    definition.parameters.remove(APP);
+
+    let generator = RtmpPullStepGenerator::new();
+    assert!(generator.generate(definition).is_err());
+}
+
+#[test]
+fn step_fails_to_generate_without_stream_key() {
+    let mut definition = create_definition("rtmp://localhost");
+    definition.parameters.remove(STREAM_KEY);
+
+    let generator = RtmpPullStepGenerator::new();
+    assert!(generator.generate(definition).is_err());
+}
+
+#[test]
+fn validation_passes_with_valid_source() {
+    let definition = create_definition("rtmp://localhost:1940");
+
+    let generator = RtmpPullStepGenerator::new();
+    assert!(generator.validate(&definition).is_ok());
+}
+
+#[tokio::test]
+async fn connection_opened_and_handshake_started_when_created() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let generator = RtmpPullStepGenerator::new();
+    let definition = create_definition(&format!("rtmp://127.0.0.1:{}", port));
+    let _context = StepTestContext::new(Box::new(generator), definition).unwrap();
+
+    let (mut socket, _) = tokio::time::timeout(Duration::from_millis(100), listener.accept())
+        .await
+        .expect("Timed out waiting for connection")
+        .expect("Failed to accept connection");
+
+    let mut buffer = [0; 1];
+    tokio::time::timeout(Duration::from_millis(100), socket.read_exact(&mut buffer))
+        .await
+        .expect("Timed out waiting for handshake")
+        .expect("Failed to read handshake");
+
+    assert_eq!(
+        buffer[0], 3,
+        "Expected RTMP version 3 as first handshake byte"
+    );
+}
+
+#[tokio::test]
+async fn new_stream_raised_when_playback_starts() {
+    let (context, _listener, _sender) = create_playing_context().await;
+
+    assert_eq!(
+        context.media_outputs.len(),
+        1,
+        "Unexpected number of media outputs"
+    );
+
+    match &context.media_outputs[0].content {
+        MediaNotificationContent::NewIncomingStream { stream_name } => {
+            assert_eq!(stream_name, "key", "Unexpected stream name");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[tokio::test]
+async fn received_video_is_unwrapped_from_flv() {
+    let (mut context, _listener, sender) = create_playing_context().await;
+    let stream_id = get_stream_id(&context);
+
+    sender
+        .send(PullEvent::Video {
+            data: Bytes::from(vec![0x17, 0, 0, 0, 0, 1, 2, 3]),
+            timestamp: RtmpTimestamp::new(5),
+        })
+        .unwrap();
+
+    context.execute_pending_notifications().await;
+
+    assert_eq!(
+        context.media_outputs.len(),
+        1,
+        "Unexpected number of media outputs"
+    );
+
+    let media = &context.media_outputs[0];
+    assert_eq!(media.stream_id, stream_id, "Unexpected stream id");
+    match &media.content {
+        MediaNotificationContent::Video {
+            codec,
+            is_keyframe,
+            is_sequence_header,
+            data,
+            timestamp,
+        } => {
+            assert_eq!(codec, &VideoCodec::H264, "Unexpected codec");
+            assert!(is_keyframe, "Expected keyframe");
+            assert!(is_sequence_header, "Expected sequence header");
+            assert_eq!(&data[..], &[1, 2, 3], "Unexpected data");
+            assert_eq!(timestamp.dts(), Duration::from_millis(5), "Unexpected dts");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[tokio::test]
+async fn received_audio_is_unwrapped_from_flv() {
+    let (mut context, _listener, sender) = create_playing_context().await;
+    let stream_id = get_stream_id(&context);
+
+    sender
+        .send(PullEvent::Audio {
+            data: Bytes::from(vec![0xaf, 1, 4, 5]),
+            timestamp: RtmpTimestamp::new(6),
+        })
+        .unwrap();
+
+    context.execute_pending_notifications().await;
+
+    assert_eq!(
+        context.media_outputs.len(),
+        1,
+        "Unexpected number of media outputs"
+    );
+
+    let media = &context.media_outputs[0];
+    assert_eq!(media.stream_id, stream_id, "Unexpected stream id");
+    assert_eq!(
+        media.content,
+        MediaNotificationContent::Audio {
+            codec: AudioCodec::Aac,
+            is_sequence_header: false,
+            data: Bytes::from(vec![4, 5]),
+            timestamp: Duration::from_millis(6),
+        },
+        "Unexpected media content"
+    );
+}
+
+#[tokio::test]
+async fn lost_connection_disconnects_stream() {
+    let (mut context, _listener, sender) = create_playing_context().await;
+    let stream_id = get_stream_id(&context);
+
+    sender
+        .send(PullEvent::ConnectionLost(PullError::ConnectionClosed))
+        .unwrap();
+
+    context.execute_pending_notifications().await;
+
+    assert_eq!(
+        context.media_outputs.len(),
+        1,
+        "Unexpected number of media outputs"
+    );
+
+    assert_eq!(
+        context.media_outputs[0],
+        MediaNotification {
+            stream_id,
+            content: MediaNotificationContent::StreamDisconnected,
+            tags: Vec::new(),
+        },
+        "Unexpected media notification"
+    );
+
+    assert_eq!(
+        context.step.get_status(),
+        &StepStatus::Active,
+        "Unexpected step status"
+    );
+}
+
+#[tokio::test]
+async fn media_is_passed_through() {
+    let (mut context, _listener, _sender) = create_playing_context().await;
+
+    context.assert_media_passed_through(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
+    });
+}
+
+#[test]
+fn reconnect_delay_doubles_up_to_maximum() {
+    let mut step = RtmpPullStep {
+        definition: create_definition("rtmp://localhost"),
+        status: StepStatus::Active,
+        source: Arc::new(parse_pull_source(&create_definition("rtmp://localhost")).unwrap()),
+        active_stream_id: None,
+        consecutive_failures: 1,
+        connection_cancellation: None,
+    };
+
+    assert_eq!(step.reconnect_delay(), Duration::from_secs(1));
+
+    step.consecutive_failures = 3;
+    assert_eq!(step.reconnect_delay(), Duration::from_secs(4));
+
+    step.consecutive_failures = 100;
+    assert_eq!(step.reconnect_delay(), MAX_RECONNECT_DELAY);
+}
+
+#[tokio::test]
+async fn read_times_out_when_server_stops_sending_data() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let socket = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let (_server_socket, _) = listener.accept().await.unwrap();
+
+    let (session, _) = ClientSession::new(ClientSessionConfig::new()).unwrap();
+    let mut connection = PullConnection {
+        socket,
+        session,
+        pending_events: Vec::new(),
+        read_timeout: Duration::from_millis(50),
+    };
+
+    let result = tokio::time::timeout(Duration::from_secs(1), connection.read_events())
+        .await
+        .expect("Read did not time out");
+
+    match result {
+        Err(PullError::ReadTimeout(_)) => (),
+        Err(error) => panic!("Expected read timeout error, instead received {:?}", error),
+        Ok(_) => panic!("Expected read timeout error, instead received events"),
+    }
+}
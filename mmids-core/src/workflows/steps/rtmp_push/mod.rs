@@ -0,0 +1,809 @@
+//! The rtmp push step sends media for each stream it receives to an external RTMP server, such as
+//! a YouTube or Twitch ingest server.  Unlike the `ffmpeg_push` step, the outbound RTMP connection
+//! is made by mmids itself and does not require an external process.
+//!
+//! Each stream that passes through the step gets its own outbound RTMP connection.  If a
+//! connection is dropped it will be re-established after a short delay.  If several connection
+//! attempts fail in a row then the step is put into an error state.
+//!
+//! Media can optionally be paced, so it's sent according to its timestamps instead of as fast as
+//! it arrives, and capped to a maximum bitrate.
+//!
+//! All media packets are passed along as is to the next workflow step.
+
+mod pacer;
+#[cfg(test)]
+mod tests;
+
+use crate::endpoints::rtmp_server::{
+    wrap_audio_into_flv, wrap_video_into_flv, RtmpEndpointMediaData,
+};
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::external_stream_handler::{
+    ExternalStreamHandler, ResolvedFutureStatus, StreamHandlerFutureResult,
+    StreamHandlerFutureWrapper,
+};
+use crate::workflows::steps::factory::{StepGenerator, StepKind};
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
+use futures::FutureExt;
+use pacer::MediaPacer;
+use rml_rtmp::handshake::{Handshake, HandshakeProcessResult, PeerType};
+use rml_rtmp::sessions::{
+    ClientSession, ClientSessionConfig, ClientSessionError, ClientSessionEvent,
+    ClientSessionResult, PublishRequestType,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::time::{sleep_until, Instant};
+use tracing::{error, info, warn};
+
+const TARGET_URL: &str = "target_url";
+const APP: &str = "app";
+const STREAM_KEY: &str = "stream_key";
+const PACING: &str = "pacing";
+const MAX_KBPS: &str = "max_kbps";
+const DEFAULT_RTMP_PORT: u16 = 1935;
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const MAX_CONSECUTIVE_FAILURES: u32 = 5;
+
+/// Generates new instances of the rtmp push workflow step based on specified step definitions.
+pub struct RtmpPushStepGenerator {}
+
+struct RtmpPushStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    target: Arc<PushTarget>,
+    active_streams: HashMap<StreamId, ActiveStream>,
+}
+
+struct ActiveStream {
+    stream_name: String,
+    handler: RtmpPushHandler,
+}
+
+#[derive(Debug)]
+struct PushTarget {
+    host: String,
+    port: u16,
+    app: String,
+    stream_key: String,
+    pacing: bool,
+    max_kbps: Option<u32>,
+}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error("No target url specified.  A 'target_url' parameter is required")]
+    NoTargetUrlProvided,
+
+    #[error(
+        "Invalid target url of '{0}'.  Target urls must be in the format of 'rtmp://host[:port]'"
+    )]
+    InvalidTargetUrl(String),
+
+    #[error("No rtmp application specified.  An 'app' parameter is required")]
+    NoAppProvided,
+
+    #[error("No stream key specified.  A 'stream_key' parameter is required")]
+    NoStreamKeyProvided,
+
+    #[error("Invalid max kbps of '{0}'.  A positive number is required")]
+    InvalidMaxKbps(String),
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+#[derive(Error, Debug)]
+enum PushError {
+    #[error("Timed out connecting to the RTMP server")]
+    Timeout,
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("The RTMP server closed the connection")]
+    ConnectionClosed,
+
+    #[error("RTMP handshake failed: {0}")]
+    Handshake(String),
+
+    #[error("RTMP session error: {0}")]
+    Session(String),
+
+    #[error("The RTMP server rejected the connection request: {0}")]
+    ConnectionRejected(String),
+}
+
+/// Manages the outbound RTMP connection for a single stream
+struct RtmpPushHandler {
+    stream_id: StreamId,
+    target: Arc<PushTarget>,
+    status: PushStatus,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+    metadata: Option<MediaNotificationContent>,
+    video_sequence_header: Option<MediaNotificationContent>,
+    audio_sequence_header: Option<MediaNotificationContent>,
+}
+
+enum PushStatus {
+    Inactive,
+    Active {
+        media_channel: UnboundedSender<MediaNotificationContent>,
+    },
+    WaitingToReconnect,
+}
+
+enum FutureResult {
+    MediaChannelClosed,
+    ConnectionFailed(PushError),
+    ConnectionLost(PushError),
+    ReconnectDelayElapsed,
+}
+
+impl StreamHandlerFutureResult for FutureResult {}
+
+struct PushConnection {
+    socket: TcpStream,
+    session: ClientSession,
+    keyframe_sent: bool,
+}
+
+impl RtmpPushStepGenerator {
+    pub fn new() -> Self {
+        RtmpPushStepGenerator {}
+    }
+}
+
+impl StepGenerator for RtmpPushStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let target = parse_push_target(&definition)?;
+        let step = RtmpPushStep {
+            definition: definition.clone(),
+            status: StepStatus::Active,
+            target: Arc::new(target),
+            active_streams: HashMap::new(),
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_push_target(definition)?;
+        Ok(())
+    }
+
+    fn kind(&self) -> StepKind {
+        StepKind::Sink
+    }
+}
+
+fn parse_push_target(definition: &WorkflowStepDefinition) -> Result<PushTarget, StepStartupError> {
+    let target_url = match definition.parameters.get(TARGET_URL) {
+        Some(Some(value)) => value,
+        _ => return Err(StepStartupError::NoTargetUrlProvided),
+    };
+
+    let (host, port) = match parse_target_url(target_url) {
+        Some(x) => x,
+        None => return Err(StepStartupError::InvalidTargetUrl(target_url.to_string())),
+    };
+
+    let app = match definition.parameters.get(APP) {
+        Some(Some(value)) => value.to_string(),
+        _ => return Err(StepStartupError::NoAppProvided),
+    };
+
+    let stream_key = match definition.parameters.get(STREAM_KEY) {
+        Some(Some(value)) => value.to_string(),
+        _ => return Err(StepStartupError::NoStreamKeyProvided),
+    };
+
+    let pacing = definition.parameters.contains_key(PACING);
+    let max_kbps = match definition.parameters.get(MAX_KBPS) {
+        Some(Some(value)) => match value.parse::<u32>() {
+            Ok(num) if num > 0 => Some(num),
+            _ => return Err(StepStartupError::InvalidMaxKbps(value.clone())),
+        },
+
+        _ => None,
+    };
+
+    Ok(PushTarget {
+        host,
+        port,
+        app,
+        stream_key,
+        pacing,
+        max_kbps,
+    })
+}
+
+impl RtmpPushStep {
+    fn handle_media(&mut self, media: &MediaNotification, outputs: &mut StepOutputs) {
+        match &media.content {
+            MediaNotificationContent::NewIncomingStream { stream_name } => {
+                if self.active_streams.contains_key(&media.stream_id) {
+                    // Duplicate notification, the stream is already being pushed
+                    return;
+                }
+
+                info!(
+                    stream_id = ?media.stream_id,
+                    stream_name = %stream_name,
+                    "Starting rtmp push for stream id {:?} to {}:{}",
+                    media.stream_id, self.target.host, self.target.port
+                );
+
+                let mut stream = ActiveStream {
+                    stream_name: stream_name.clone(),
+                    handler: RtmpPushHandler::new(media.stream_id.clone(), self.target.clone()),
+                };
+
+                stream.handler.prepare_stream(stream_name, outputs);
+                self.active_streams.insert(media.stream_id.clone(), stream);
+            }
+
+            MediaNotificationContent::StreamDisconnected => {
+                if let Some(mut stream) = self.active_streams.remove(&media.stream_id) {
+                    info!(
+                        stream_id = ?media.stream_id,
+                        "Stopping rtmp push for stream id {:?} due to stream disconnection",
+                        media.stream_id
+                    );
+
+                    stream.handler.stop_stream();
+                }
+            }
+
+            content => {
+                if let Some(stream) = self.active_streams.get_mut(&media.stream_id) {
+                    stream.handler.send_media(content);
+                }
+            }
+        }
+    }
+
+    fn handle_resolved_future(
+        &mut self,
+        wrapper: StreamHandlerFutureWrapper,
+        outputs: &mut StepOutputs,
+    ) {
+        let stream = match self.active_streams.get_mut(&wrapper.stream_id) {
+            Some(stream) => stream,
+            None => return, // Late notification for a stream that's been stopped
+        };
+
+        match stream
+            .handler
+            .handle_resolved_future(wrapper.future, outputs)
+        {
+            ResolvedFutureStatus::Success => {
+                stream.handler.prepare_stream(&stream.stream_name, outputs);
+            }
+
+            ResolvedFutureStatus::StreamShouldBeStopped => {
+                let message = format!(
+                    "Failed to push stream '{}' to {}:{} after {} attempts: {}",
+                    stream.stream_name,
+                    self.target.host,
+                    self.target.port,
+                    stream.handler.consecutive_failures,
+                    stream
+                        .handler
+                        .last_error
+                        .as_deref()
+                        .unwrap_or("unknown error"),
+                );
+
+                error!(stream_id = ?wrapper.stream_id, "{}", message);
+
+                stream.handler.stop_stream();
+                self.active_streams.remove(&wrapper.stream_id);
+                self.status = StepStatus::Error { message };
+            }
+        }
+    }
+}
+
+impl WorkflowStep for RtmpPushStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for notification in inputs.notifications.drain(..) {
+            if let Ok(wrapper) = notification.downcast::<StreamHandlerFutureWrapper>() {
+                self.handle_resolved_future(*wrapper, outputs);
+            }
+        }
+
+        for media in inputs.media.drain(..) {
+            self.handle_media(&media, outputs);
+            outputs.media.push(media);
+        }
+    }
+
+    fn shutdown(&mut self) {
+        for (_, mut stream) in self.active_streams.drain() {
+            stream.handler.stop_stream();
+        }
+
+        self.status = StepStatus::Shutdown;
+    }
+}
+
+impl RtmpPushHandler {
+    fn new(stream_id: StreamId, target: Arc<PushTarget>) -> Self {
+        RtmpPushHandler {
+            stream_id,
+            target,
+            status: PushStatus::Inactive,
+            consecutive_failures: 0,
+            last_error: None,
+            metadata: None,
+            video_sequence_header: None,
+            audio_sequence_header: None,
+        }
+    }
+
+    fn send_media(&mut self, content: &MediaNotificationContent) {
+        // Keep the latest metadata and sequence headers, so they can be sent first upon reconnection
+        match content {
+            MediaNotificationContent::Metadata { .. } => {
+                self.metadata = Some(content.clone());
+            }
+
+            MediaNotificationContent::Video {
+                is_sequence_header: true,
+                ..
+            } => {
+                self.video_sequence_header = Some(content.clone());
+            }
+
+            MediaNotificationContent::Audio {
+                is_sequence_header: true,
+                ..
+            } => {
+                self.audio_sequence_header = Some(content.clone());
+            }
+
+            _ => (),
+        }
+
+        if let PushStatus::Active { media_channel } = &self.status {
+            let _ = media_channel.send(content.clone());
+        }
+    }
+
+    fn handle_connection_failure(&mut self, error: PushError) -> ResolvedFutureStatus {
+        self.consecutive_failures += 1;
+        self.last_error = Some(error.to_string());
+        self.status = PushStatus::Inactive;
+
+        if self.consecutive_failures >= MAX_CONSECUTIVE_FAILURES {
+            return ResolvedFutureStatus::StreamShouldBeStopped;
+        }
+
+        ResolvedFutureStatus::Success
+    }
+}
+
+impl ExternalStreamHandler for RtmpPushHandler {
+    fn prepare_stream(&mut self, stream_name: &str, outputs: &mut StepOutputs) {
+        if let PushStatus::Inactive = &self.status {
+            let stream_key = if self.target.stream_key == "*" {
+                stream_name.to_string()
+            } else {
+                self.target.stream_key.clone()
+            };
+
+            let (sender, receiver) = unbounded_channel();
+            let cached_media = [
+                &self.metadata,
+                &self.video_sequence_header,
+                &self.audio_sequence_header,
+            ];
+
+            for content in cached_media.iter().copied().flatten() {
+                let _ = sender.send(content.clone());
+            }
+
+            outputs.futures.push(
+                push_stream(
+                    self.stream_id.clone(),
+                    self.target.clone(),
+                    stream_key,
+                    receiver,
+                )
+                .boxed(),
+            );
+
+            self.status = PushStatus::Active {
+                media_channel: sender,
+            };
+        }
+    }
+
+    fn stop_stream(&mut self) {
+        // Dropping the media channel causes the connection to be closed
+        self.status = PushStatus::Inactive;
+    }
+
+    fn handle_resolved_future(
+        &mut self,
+        future: Box<dyn StreamHandlerFutureResult>,
+        outputs: &mut StepOutputs,
+    ) -> ResolvedFutureStatus {
+        let future = match future.downcast::<FutureResult>() {
+            Ok(x) => *x,
+            Err(_) => return ResolvedFutureStatus::Success,
+        };
+
+        match future {
+            FutureResult::MediaChannelClosed => ResolvedFutureStatus::Success,
+
+            FutureResult::ConnectionFailed(error) => {
+                warn!(
+                    stream_id = ?self.stream_id,
+                    "Failed to connect to {}:{} for stream id {:?}: {}",
+                    self.target.host, self.target.port, self.stream_id, error
+                );
+
+                let result = self.handle_connection_failure(error);
+                if let ResolvedFutureStatus::Success = result {
+                    self.status = PushStatus::WaitingToReconnect;
+                    outputs
+                        .futures
+                        .push(wait_for_reconnect_delay(self.stream_id.clone()).boxed());
+                }
+
+                result
+            }
+
+            FutureResult::ConnectionLost(error) => {
+                warn!(
+                    stream_id = ?self.stream_id,
+                    "Connection to {}:{} for stream id {:?} was lost: {}",
+                    self.target.host, self.target.port, self.stream_id, error
+                );
+
+                // The connection had been successfully publishing, so start the failure count over
+                self.consecutive_failures = 0;
+                self.last_error = Some(error.to_string());
+                self.status = PushStatus::WaitingToReconnect;
+                outputs
+                    .futures
+                    .push(wait_for_reconnect_delay(self.stream_id.clone()).boxed());
+
+                ResolvedFutureStatus::Success
+            }
+
+            FutureResult::ReconnectDelayElapsed => {
+                if let PushStatus::WaitingToReconnect = &self.status {
+                    self.status = PushStatus::Inactive;
+                }
+
+                ResolvedFutureStatus::Success
+            }
+        }
+    }
+}
+
+impl PushConnection {
+    async fn connect(target: &PushTarget, stream_key: String) -> Result<Self, PushError> {
+        let mut socket = TcpStream::connect((target.host.as_str(), target.port)).await?;
+        let remaining_bytes = perform_handshake(&mut socket).await?;
+
+        let (session, results) =
+            ClientSession::new(ClientSessionConfig::new()).map_err(session_error)?;
+
+        let mut connection = PushConnection {
+            socket,
+            session,
+            keyframe_sent: false,
+        };
+
+        connection.handle_session_results(results).await?;
+        if !remaining_bytes.is_empty() {
+            connection.handle_input(&remaining_bytes).await?;
+        }
+
+        let result = connection
+            .session
+            .request_connection(target.app.clone())
+            .map_err(session_error)?;
+
+        connection.handle_session_results(vec![result]).await?;
+        loop {
+            let events = connection.read_events().await?;
+            for event in events {
+                match event {
+                    ClientSessionEvent::ConnectionRequestAccepted => {
+                        let result = connection
+                            .session
+                            .request_publishing(stream_key, PublishRequestType::Live)
+                            .map_err(session_error)?;
+
+                        connection.handle_session_results(vec![result]).await?;
+                        connection.wait_for_publish_acceptance().await?;
+
+                        return Ok(connection);
+                    }
+
+                    ClientSessionEvent::ConnectionRequestRejected { description } => {
+                        return Err(PushError::ConnectionRejected(description));
+                    }
+
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    async fn wait_for_publish_acceptance(&mut self) -> Result<(), PushError> {
+        loop {
+            let events = self.read_events().await?;
+            for event in events {
+                if let ClientSessionEvent::PublishRequestAccepted = event {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Forwards media to the RTMP server until the media channel is closed
+    async fn forward_media(
+        &mut self,
+        mut media: UnboundedReceiver<MediaNotificationContent>,
+        mut pacer: MediaPacer,
+    ) -> Result<(), PushError> {
+        let mut buffer = [0; 4096];
+        loop {
+            for content in pacer.take_ready(Instant::now()) {
+                self.publish(content).await?;
+            }
+
+            let next_release = pacer.next_release_time(Instant::now());
+            let wait_for_release = sleep_until(next_release.unwrap_or_else(Instant::now));
+            tokio::select! {
+                bytes_read = self.socket.read(&mut buffer) => {
+                    let bytes_read = bytes_read?;
+                    if bytes_read == 0 {
+                        return Err(PushError::ConnectionClosed);
+                    }
+
+                    self.handle_input(&buffer[..bytes_read]).await?;
+                }
+
+                content = media.recv() => {
+                    match content {
+                        Some(content) => pacer.enqueue(content),
+                        None => return Ok(()),
+                    }
+                }
+
+                _ = wait_for_release, if next_release.is_some() => (),
+            }
+        }
+    }
+
+    async fn publish(&mut self, content: MediaNotificationContent) -> Result<(), PushError> {
+        let result = match content.to_rtmp_media_data() {
+            Some(RtmpEndpointMediaData::NewStreamMetaData { metadata }) => {
+                self.session.publish_metadata(&metadata)
+            }
+
+            Some(RtmpEndpointMediaData::NewVideoData {
+                codec,
+                is_keyframe,
+                is_sequence_header,
+                data,
+                timestamp,
+                composition_time_offset,
+            }) => {
+                // The remote server can't decode anything until it receives a keyframe
+                if !is_sequence_header && !is_keyframe && !self.keyframe_sent {
+                    return Ok(());
+                }
+
+                let data = match wrap_video_into_flv(
+                    data,
+                    codec,
+                    is_keyframe,
+                    is_sequence_header,
+                    composition_time_offset,
+                ) {
+                    Ok(data) => data,
+                    Err(()) => return Ok(()),
+                };
+
+                if is_keyframe {
+                    self.keyframe_sent = true;
+                }
+
+                self.session
+                    .publish_video_data(data, timestamp, !is_keyframe)
+            }
+
+            Some(RtmpEndpointMediaData::NewAudioData {
+                codec,
+                is_sequence_header,
+                data,
+                timestamp,
+            }) => {
+                let data = match wrap_audio_into_flv(data, codec, is_sequence_header) {
+                    Ok(data) => data,
+                    Err(()) => return Ok(()),
+                };
+
+                self.session.publish_audio_data(data, timestamp, false)
+            }
+
+            None => return Ok(()),
+        };
+
+        let result = result.map_err(session_error)?;
+        self.handle_session_results(vec![result]).await?;
+
+        Ok(())
+    }
+
+    async fn read_events(&mut self) -> Result<Vec<ClientSessionEvent>, PushError> {
+        let mut buffer = [0; 4096];
+        let bytes_read = self.socket.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            return Err(PushError::ConnectionClosed);
+        }
+
+        self.handle_input(&buffer[..bytes_read]).await
+    }
+
+    async fn handle_input(&mut self, bytes: &[u8]) -> Result<Vec<ClientSessionEvent>, PushError> {
+        let results = self.session.handle_input(bytes).map_err(session_error)?;
+        self.handle_session_results(results).await
+    }
+
+    /// Sends any outbound packets to the RTMP server, returning all raised events
+    async fn handle_session_results(
+        &mut self,
+        results: Vec<ClientSessionResult>,
+    ) -> Result<Vec<ClientSessionEvent>, PushError> {
+        let mut events = Vec::new();
+        for result in results {
+            match result {
+                ClientSessionResult::OutboundResponse(packet) => {
+                    self.socket.write_all(&packet.bytes).await?;
+                }
+
+                ClientSessionResult::RaisedEvent(event) => events.push(event),
+
+                _ => (),
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+/// Parses a url in the form of `rtmp://host[:port]` into its host and port
+pub(super) fn parse_target_url(url: &str) -> Option<(String, u16)> {
+    let address = url.strip_prefix("rtmp://")?.trim_end_matches('/');
+    if address.is_empty() || address.contains('/') {
+        return None;
+    }
+
+    match address.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port.parse().ok()?;
+            if host.is_empty() {
+                return None;
+            }
+
+            Some((host.to_string(), port))
+        }
+
+        None => Some((address.to_string(), DEFAULT_RTMP_PORT)),
+    }
+}
+
+fn session_error(error: ClientSessionError) -> PushError {
+    PushError::Session(format!("{:?}", error))
+}
+
+/// Performs the client side of the RTMP handshake, returning any bytes received after the
+/// handshake completed
+async fn perform_handshake(socket: &mut TcpStream) -> Result<Vec<u8>, PushError> {
+    let mut handshake = Handshake::new(PeerType::Client);
+    let p0_and_p1 = handshake
+        .generate_outbound_p0_and_p1()
+        .map_err(|error| PushError::Handshake(format!("{:?}", error)))?;
+
+    socket.write_all(&p0_and_p1).await?;
+
+    let mut buffer = [0; 4096];
+    loop {
+        let bytes_read = socket.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            return Err(PushError::ConnectionClosed);
+        }
+
+        let result = handshake
+            .process_bytes(&buffer[..bytes_read])
+            .map_err(|error| PushError::Handshake(format!("{:?}", error)))?;
+
+        match result {
+            HandshakeProcessResult::InProgress { response_bytes } => {
+                socket.write_all(&response_bytes).await?;
+            }
+
+            HandshakeProcessResult::Completed {
+                response_bytes,
+                remaining_bytes,
+            } => {
+                socket.write_all(&response_bytes).await?;
+
+                return Ok(remaining_bytes);
+            }
+        }
+    }
+}
+
+async fn push_stream(
+    stream_id: StreamId,
+    target: Arc<PushTarget>,
+    stream_key: String,
+    media: UnboundedReceiver<MediaNotificationContent>,
+) -> Box<dyn StepFutureResult> {
+    let connection = tokio::time::timeout(
+        CONNECTION_TIMEOUT,
+        PushConnection::connect(&target, stream_key),
+    )
+    .await;
+
+    let result = match connection {
+        Ok(Ok(mut connection)) => {
+            let pacer = MediaPacer::new(target.pacing, target.max_kbps);
+            match connection.forward_media(media, pacer).await {
+                Ok(()) => FutureResult::MediaChannelClosed,
+                Err(error) => FutureResult::ConnectionLost(error),
+            }
+        }
+
+        Ok(Err(error)) => FutureResult::ConnectionFailed(error),
+        Err(_) => FutureResult::ConnectionFailed(PushError::Timeout),
+    };
+
+    Box::new(StreamHandlerFutureWrapper {
+        stream_id,
+        future: Box::new(result),
+    })
+}
+
+async fn wait_for_reconnect_delay(stream_id: StreamId) -> Box<dyn StepFutureResult> {
+    tokio::time::sleep(RECONNECT_DELAY).await;
+
+    Box::new(StreamHandlerFutureWrapper {
+        stream_id,
+        future: Box::new(FutureResult::ReconnectDelayElapsed),
+    })
+}
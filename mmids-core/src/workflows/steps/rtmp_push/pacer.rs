@@ -0,0 +1,149 @@
+//! Controls when media is sent over an outbound RTMP connection.  Without any options set media is
+//! released as soon as it's received.  When pacing is enabled, media is held until the wall clock
+//! catches up to its timestamp, which smooths out bursts of frames.  When a bitrate cap is set,
+//! non-keyframe video is dropped whenever sending it would go over the cap.
+
+use crate::workflows::MediaNotificationContent;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// If the next packet's timestamp is further than this ahead of the paced clock, the timestamps
+/// are assumed to have jumped and the clock is restarted from that packet.
+const MAX_PACING_GAP: Duration = Duration::from_secs(5);
+
+/// How many seconds worth of bytes can be sent in a single burst before the bitrate cap kicks in
+const BURST_SECONDS: f64 = 1.0;
+
+pub struct MediaPacer {
+    pacing: bool,
+    max_bytes_per_second: Option<f64>,
+    queue: VecDeque<MediaNotificationContent>,
+
+    /// The wall clock time and media timestamp the paced clock was started at
+    clock_start: Option<(Instant, Duration)>,
+
+    /// Bytes that can be sent before the bitrate cap is exceeded.  Goes negative when media that
+    /// is never dropped (such as keyframes) is sent over the cap.
+    available_bytes: f64,
+    last_refill: Option<Instant>,
+
+    /// Once a non-keyframe is dropped, the frames after it can't be decoded until the next
+    /// keyframe, so they are dropped as well.
+    dropping_until_keyframe: bool,
+}
+
+impl MediaPacer {
+    pub fn new(pacing: bool, max_kbps: Option<u32>) -> Self {
+        let max_bytes_per_second = max_kbps.map(|kbps| kbps as f64 * 1000.0 / 8.0);
+        MediaPacer {
+            pacing,
+            max_bytes_per_second,
+            queue: VecDeque::new(),
+            clock_start: None,
+            available_bytes: max_bytes_per_second.unwrap_or_default() * BURST_SECONDS,
+            last_refill: None,
+            dropping_until_keyframe: false,
+        }
+    }
+
+    pub fn enqueue(&mut self, content: MediaNotificationContent) {
+        self.queue.push_back(content);
+    }
+
+    /// The time the next queued packet should be released at, if any are queued
+    pub fn next_release_time(&mut self, now: Instant) -> Option<Instant> {
+        let timestamp = match self.queue.front() {
+            Some(content) => get_timestamp(content),
+            None => return None,
+        };
+
+        let timestamp = match timestamp {
+            Some(timestamp) if self.pacing => timestamp,
+            _ => return Some(now),
+        };
+
+        let (start_instant, start_timestamp) = *self.clock_start.get_or_insert((now, timestamp));
+        let release_time = start_instant + timestamp.saturating_sub(start_timestamp);
+
+        // Timestamps jumping far backwards or ahead (e.g. a discontinuity) would otherwise flood
+        // or stall the connection, so restart the clock from this packet instead
+        let jumped_backwards = timestamp + MAX_PACING_GAP < start_timestamp;
+        if jumped_backwards || release_time > now + MAX_PACING_GAP {
+            self.clock_start = Some((now, timestamp));
+            return Some(now);
+        }
+
+        Some(release_time)
+    }
+
+    /// Removes all queued media that should be sent by the specified time.  Media that was
+    /// dropped to stay under the bitrate cap is not returned.
+    pub fn take_ready(&mut self, now: Instant) -> Vec<MediaNotificationContent> {
+        let mut ready = Vec::new();
+        while let Some(release_time) = self.next_release_time(now) {
+            if release_time > now {
+                break;
+            }
+
+            if let Some(content) = self.queue.pop_front() {
+                if self.should_send(&content, now) {
+                    ready.push(content);
+                }
+            }
+        }
+
+        ready
+    }
+
+    fn should_send(&mut self, content: &MediaNotificationContent, now: Instant) -> bool {
+        let max_bytes_per_second = match self.max_bytes_per_second {
+            Some(max) => max,
+            None => return true,
+        };
+
+        if let Some(last_refill) = self.last_refill {
+            let elapsed = now.saturating_duration_since(last_refill).as_secs_f64();
+            self.available_bytes = (self.available_bytes + elapsed * max_bytes_per_second)
+                .min(max_bytes_per_second * BURST_SECONDS);
+        }
+
+        self.last_refill = Some(now);
+
+        let (size, can_drop) = match content {
+            MediaNotificationContent::Video {
+                is_keyframe,
+                is_sequence_header,
+                data,
+                ..
+            } => {
+                if *is_keyframe && !is_sequence_header {
+                    self.dropping_until_keyframe = false;
+                }
+
+                (data.len(), !is_keyframe && !is_sequence_header)
+            }
+
+            // Audio is small compared to video, and dropping it is more noticeable to viewers
+            MediaNotificationContent::Audio { data, .. } => (data.len(), false),
+            _ => (0, false),
+        };
+
+        let size = size as f64;
+        if can_drop && (self.dropping_until_keyframe || size > self.available_bytes) {
+            self.dropping_until_keyframe = true;
+            return false;
+        }
+
+        self.available_bytes -= size;
+        true
+    }
+}
+
+fn get_timestamp(content: &MediaNotificationContent) -> Option<Duration> {
+    match content {
+        MediaNotificationContent::Video { timestamp, .. } => Some(timestamp.dts()),
+        MediaNotificationContent::Audio { timestamp, .. } => Some(*timestamp),
+        _ => None,
+    }
+}
@@ -0,0 +1,281 @@
+use super::pacer::MediaPacer;
+use super::*;
+use crate::codecs::VideoCodec;
+use crate::workflows::steps::test_utils;
+use crate::workflows::steps::StepTestContext;
+use crate::VideoTimestamp;
+use bytes::Bytes;
+use tokio::net::TcpListener;
+
+fn create_definition(target_url: &str) -> WorkflowStepDefinition {
+    test_utils::create_definition(
+        "rtmp_push",
+        &[(TARGET_URL, target_url), (APP, "live"), (STREAM_KEY, "key")],
+    )
+}
+
+fn video(is_keyframe: bool, size: usize, timestamp_ms: u64) -> MediaNotificationContent {
+    let timestamp = Duration::from_millis(timestamp_ms);
+    MediaNotificationContent::Video {
+        codec: VideoCodec::H264,
+        is_keyframe,
+        is_sequence_header: false,
+        data: Bytes::from(vec![0; size]),
+        timestamp: VideoTimestamp::from_durations(timestamp, timestamp),
+    }
+}
+
+fn new_stream_notification() -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+#[test]
+fn can_parse_target_urls() {
+    assert_eq!(
+        parse_target_url("rtmp://localhost"),
+        Some(("localhost".to_string(), 1935))
+    );
+    assert_eq!(
+        parse_target_url("rtmp://localhost:1940/"),
+        Some(("localhost".to_string(), 1940))
+    );
+    assert_eq!(parse_target_url("http://localhost"), None);
+    assert_eq!(parse_target_url("rtmp://localhost/live"), None);
+    assert_eq!(parse_target_url("rtmp://localhost:abc"), None);
+    assert_eq!(parse_target_url("rtmp://"), None);
+}
+
+#[test]
+fn step_fails_to_generate_without_target_url() {
+    let mut definition = create_definition("rtmp://localhost");
+    definition.parameters.remove(TARGET_URL);
+
+    let generator = RtmpPushStepGenerator::new();
+    assert!(generator.generate(definition).is_err());
+}
+
+#[test]
+fn step_fails_to_generate_with_invalid_target_url() {
+    let definition = create_definition("localhost:1935");
+
+    let generator = RtmpPushStepGenerator::new();
+    assert!(generator.generate(definition).is_err());
+}
+
+#[test]
+fn step_fails_to_generate_without_app() {
+    let mut definition = create_definition("rtmp://localhost");
+    definition.parameters.remove(APP);
+
+    let generator = RtmpPushStepGenerator::new();
+    assert!(generator.generate(definition).is_err());
+}
+
+#[test]
+fn step_fails_to_generate_without_stream_key() {
+    let mut definition = create_definition("rtmp://localhost");
+    definition.parameters.remove(STREAM_KEY);
+
+    let generator = RtmpPushStepGenerator::new();
+    assert!(generator.generate(definition).is_err());
+}
+
+#[test]
+fn validation_fails_with_invalid_target_url() {
+    let definition = create_definition("localhost:1935");
+
+    let generator = RtmpPushStepGenerator::new();
+    assert!(generator.validate(&definition).is_err());
+}
+
+#[test]
+fn validation_fails_with_invalid_max_kbps() {
+    let mut definition = create_definition("rtmp://localhost");
+    definition
+        .parameters
+        .insert(MAX_KBPS.to_string(), Some("0".to_string()));
+
+    let generator = RtmpPushStepGenerator::new();
+    assert!(generator.validate(&definition).is_err());
+}
+
+#[test]
+fn validation_passes_with_valid_target() {
+    let definition = create_definition("rtmp://localhost");
+
+    let generator = RtmpPushStepGenerator::new();
+    assert!(generator.validate(&definition).is_ok());
+}
+
+#[tokio::test]
+async fn media_is_passed_through() {
+    let generator = RtmpPushStepGenerator::new();
+    let definition = create_definition("rtmp://localhost:9");
+    let mut context = StepTestContext::new(Box::new(generator), definition).unwrap();
+
+    context.assert_media_passed_through(new_stream_notification());
+    context.assert_media_passed_through(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            is_keyframe: true,
+            is_sequence_header: false,
+            data: Bytes::from(vec![1, 2, 3]),
+            timestamp: VideoTimestamp::from_zero(),
+        },
+        tags: Vec::new(),
+    });
+
+    context.assert_media_passed_through(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
+    });
+}
+
+#[tokio::test]
+async fn new_stream_opens_connection_and_starts_handshake() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let generator = RtmpPushStepGenerator::new();
+    let definition = create_definition(&format!("rtmp://127.0.0.1:{}", port));
+    let mut context = StepTestContext::new(Box::new(generator), definition).unwrap();
+
+    context.execute_with_media(new_stream_notification());
+    context.execute_pending_notifications().await;
+
+    let (mut socket, _) = tokio::time::timeout(Duration::from_millis(100), listener.accept())
+        .await
+        .expect("Timed out waiting for connection")
+        .expect("Failed to accept connection");
+
+    let mut buffer = [0; 1];
+    tokio::time::timeout(Duration::from_millis(100), socket.read_exact(&mut buffer))
+        .await
+        .expect("Timed out waiting for handshake")
+        .expect("Failed to read handshake");
+
+    assert_eq!(
+        buffer[0], 3,
+        "Expected RTMP version 3 as first handshake byte"
+    );
+}
+
+#[tokio::test]
+async fn dropped_connection_does_not_put_step_in_error_state() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let generator = RtmpPushStepGenerator::new();
+    let definition = create_definition(&format!("rtmp://127.0.0.1:{}", port));
+    let mut context = StepTestContext::new(Box::new(generator), definition).unwrap();
+
+    context.execute_with_media(new_stream_notification());
+    context.execute_pending_notifications().await;
+
+    let (socket, _) = listener.accept().await.unwrap();
+    drop(socket);
+
+    context.execute_pending_notifications().await;
+
+    assert_eq!(
+        context.step.get_status(),
+        &StepStatus::Active,
+        "Unexpected step status"
+    );
+}
+
+#[test]
+fn unpaced_media_is_released_immediately() {
+    let now = Instant::now();
+    let mut pacer = MediaPacer::new(false, None);
+    pacer.enqueue(video(true, 10, 0));
+    pacer.enqueue(video(false, 10, 1000));
+
+    assert_eq!(
+        pacer.take_ready(now).len(),
+        2,
+        "Unexpected number of packets"
+    );
+    assert_eq!(
+        pacer.next_release_time(now),
+        None,
+        "Expected an empty queue"
+    );
+}
+
+#[test]
+fn paced_media_is_released_based_on_timestamps() {
+    let now = Instant::now();
+    let mut pacer = MediaPacer::new(true, None);
+    pacer.enqueue(video(true, 10, 100));
+    pacer.enqueue(video(false, 10, 600));
+
+    assert_eq!(
+        pacer.take_ready(now).len(),
+        1,
+        "Unexpected number of packets"
+    );
+    assert_eq!(
+        pacer.next_release_time(now),
+        Some(now + Duration::from_millis(500)),
+        "Unexpected release time"
+    );
+
+    let later = now + Duration::from_millis(499);
+    assert!(pacer.take_ready(later).is_empty(), "Expected no packets");
+
+    let later = now + Duration::from_millis(500);
+    assert_eq!(
+        pacer.take_ready(later).len(),
+        1,
+        "Unexpected number of packets"
+    );
+}
+
+#[test]
+fn pacing_clock_restarts_when_timestamps_jump() {
+    let now = Instant::now();
+    let mut pacer = MediaPacer::new(true, None);
+    pacer.enqueue(video(true, 10, 0));
+    pacer.take_ready(now);
+
+    pacer.enqueue(video(true, 10, 60_000));
+    assert_eq!(
+        pacer.take_ready(now).len(),
+        1,
+        "Expected timestamp jump to be released immediately"
+    );
+}
+
+#[test]
+fn non_keyframes_dropped_until_next_keyframe_when_over_bitrate_cap() {
+    let now = Instant::now();
+
+    // 8 kbps allows 1000 bytes per second
+    let mut pacer = MediaPacer::new(false, Some(8));
+    pacer.enqueue(video(true, 900, 0));
+    pacer.enqueue(video(false, 200, 0));
+    pacer.enqueue(video(false, 10, 0));
+    pacer.enqueue(video(true, 900, 0));
+    pacer.enqueue(video(false, 10, 0));
+
+    let released = pacer.take_ready(now);
+    assert_eq!(released.len(), 2, "Unexpected number of packets");
+    for content in released {
+        match content {
+            MediaNotificationContent::Video { is_keyframe, .. } => {
+                assert!(is_keyframe, "Expected only keyframes to be released");
+            }
+
+            content => panic!("Unexpected media content: {:?}", content),
+        }
+    }
+}
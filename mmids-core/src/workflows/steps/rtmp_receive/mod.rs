@@ -3,40 +3,79 @@
 //! RTMP publishers send in will be sent to the next steps.
 //!
 //! All media packets that come in from previous workflow steps are ignored.
+//!
+//! If the RTMP endpoint drops the registration, the step can optionally be configured to attempt
+//! re-registering with an exponential backoff before giving up and entering an error state.
+//!
+//! Publishers can optionally be authorized against an external HTTP service.  When an auth url is
+//! configured, each publish request is sent to it as a json POST and only publishers that receive a
+//! 2xx response are allowed through.  Authorization happens before any reactor is queried.
+//!
+//! A media timeout can also be configured.  If a connected publisher does not send any audio or
+//! video within the timeout, the stream is reported as disconnected to later steps.  If the
+//! publisher starts sending media again it will be announced as a new incoming stream.
+//!
+//! The number of publish attempts each IP address can make per minute can also be limited, with
+//! attempts over the limit being disconnected by the RTMP endpoint.
+//!
+//! An RTMP app of `*` accepts publishers on any application name that doesn't have its own
+//! registration.  Since the same stream key could then be published on multiple apps, the app is
+//! made part of the stream name (e.g. `app/key`).  App names can also be matched case-insensitively.
 #[cfg(test)]
 mod tests;
 
 use crate::endpoints::rtmp_server::{
-    IpRestriction, RegistrationType, RtmpEndpointPublisherMessage, RtmpEndpointRequest,
-    StreamKeyRegistration, ValidationResponse,
+    IpRestriction, RegistrationType, RtmpAppMatching, RtmpEndpointPublisherMessage,
+    RtmpEndpointRequest, StreamKeyRegistration, ValidationResponse,
 };
 
 use crate::net::{ConnectionId, IpAddress, IpAddressParseError};
 use crate::workflows::definitions::WorkflowStepDefinition;
-use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::factory::{StepGenerator, StepKind};
 use crate::workflows::steps::{
-    StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus, WorkflowStep,
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
 };
 
 use crate::reactors::manager::ReactorManagerRequest;
 use crate::reactors::ReactorWorkflowUpdate;
 use crate::workflows::{MediaNotification, MediaNotificationContent};
 use crate::{StreamId, VideoTimestamp};
+use futures::future::BoxFuture;
 use futures::FutureExt;
+use hyper::http::HeaderValue;
+use hyper::{Body, Client, Method, Request};
+use serde::Serialize;
 use std::collections::HashMap;
-use std::time::Duration;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 use thiserror::Error as ThisError;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::oneshot::Sender;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 pub const PORT_PROPERTY_NAME: &'static str = "port";
 pub const APP_PROPERTY_NAME: &'static str = "rtmp_app";
 pub const STREAM_KEY_PROPERTY_NAME: &'static str = "stream_key";
 pub const IP_ALLOW_PROPERTY_NAME: &'static str = "allow_ips";
 pub const IP_DENY_PROPERTY_NAME: &'static str = "deny_ips";
+pub const IP_MODE_PROPERTY_NAME: &'static str = "ip_mode";
+pub const IP_MODE_ALLOW_WITH_EXCEPTIONS: &'static str = "allow_with_exceptions";
 pub const RTMPS_FLAG: &'static str = "rtmps";
+pub const IGNORE_APP_CASE_FLAG: &'static str = "ignore_app_case";
 pub const REACTOR_NAME: &'static str = "reactor";
+pub const RECONNECT_ATTEMPTS_PROPERTY_NAME: &'static str = "reconnect_attempts";
+pub const RECONNECT_BASE_DELAY_PROPERTY_NAME: &'static str = "reconnect_base_delay_ms";
+pub const AUTH_URL_PROPERTY_NAME: &'static str = "auth_url";
+pub const MEDIA_TIMEOUT_PROPERTY_NAME: &'static str = "media_timeout_ms";
+pub const MAX_CONNECTS_PER_MINUTE_PROPERTY_NAME: &'static str = "max_connects_per_minute";
+
+/// The name each publisher's address is reported under in the workflow's state
+pub const REMOTE_ADDRESS_DETAIL: &str = "remote_address";
+
+const DEFAULT_RECONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+const AUTH_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Generates new rtmp receiver workflow step instances based on specified step definitions.
 pub struct RtmpReceiverStepGenerator {
@@ -46,6 +85,21 @@ pub struct RtmpReceiverStepGenerator {
 
 struct ConnectionDetails {
     stream_id: StreamId,
+    stream_key: String,
+    stream_name: String,
+    remote_address: SocketAddr,
+    last_media_received_at: Instant,
+
+    // Set when the media watchdog has reported the stream as disconnected due to the publisher
+    // not sending any media.
+    timed_out: bool,
+
+    // The latest sequence headers and metadata the publisher sent.  Subsequent steps forget
+    // these when a stream disconnects, so they are raised again when a timed out stream is
+    // announced as a new stream.
+    video_sequence_header: Option<MediaNotificationContent>,
+    audio_sequence_header: Option<MediaNotificationContent>,
+    metadata: Option<MediaNotificationContent>,
 
     // Used to cancel the reactor update future. When a stream disconnects, this cancellation
     // channel will be dropped causing the future waiting for reactor updates to be closed. This
@@ -61,10 +115,26 @@ struct RtmpReceiverStep {
     reactor_manager: UnboundedSender<ReactorManagerRequest>,
     port: u16,
     rtmp_app: String,
+    rtmp_app_matching: RtmpAppMatching,
     stream_key: StreamKeyRegistration,
+    ip_restriction: IpRestriction,
+    use_tls: bool,
     status: StepStatus,
     connection_details: HashMap<ConnectionId, ConnectionDetails>,
     reactor_name: Option<String>,
+    max_reconnect_attempts: u32,
+    reconnect_base_delay: Duration,
+    reconnect_attempts_made: u32,
+    auth_url: Option<String>,
+    media_timeout: Option<Duration>,
+    max_connects_per_minute: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct AuthRequestContent {
+    connection_id: String,
+    rtmp_app: String,
+    stream_key: String,
 }
 
 impl StepFutureResult for FutureResult {}
@@ -92,6 +162,30 @@ enum FutureResult {
     },
 
     ReactorCancellationReceived,
+    ReconnectDelayElapsed,
+    MediaWatchdogTick,
+
+    PublisherAuthorizationReturned {
+        is_allowed: bool,
+        connection_id: ConnectionId,
+        stream_name: String,
+        response_channel: Sender<ValidationResponse>,
+    },
+}
+
+struct StepParameters {
+    use_rtmps: bool,
+    port: u16,
+    app: String,
+    app_matching: RtmpAppMatching,
+    stream_key: String,
+    ip_restriction: IpRestriction,
+    reactor_name: Option<String>,
+    max_reconnect_attempts: u32,
+    reconnect_base_delay: Duration,
+    auth_url: Option<String>,
+    media_timeout: Option<Duration>,
+    max_connects_per_minute: Option<u32>,
 }
 
 #[derive(ThisError, Debug)]
@@ -117,11 +211,57 @@ enum StepStartupError {
     InvalidIpAddressSpecified(#[from] IpAddressParseError),
 
     #[error(
-        "Both {} and {} were specified, but only one is allowed",
+        "Both {} and {} were specified, but only one is allowed unless {}={}",
         IP_ALLOW_PROPERTY_NAME,
-        IP_DENY_PROPERTY_NAME
+        IP_DENY_PROPERTY_NAME,
+        IP_MODE_PROPERTY_NAME,
+        IP_MODE_ALLOW_WITH_EXCEPTIONS
     )]
     BothDenyAndAllowIpRestrictionsSpecified,
+
+    #[error(
+        "Invalid {} value of '{0}' specified.  Only '{}' is supported",
+        IP_MODE_PROPERTY_NAME,
+        IP_MODE_ALLOW_WITH_EXCEPTIONS
+    )]
+    InvalidIpModeSpecified(String),
+
+    #[error(
+        "The '{}' ip mode requires a non-empty {} parameter",
+        IP_MODE_ALLOW_WITH_EXCEPTIONS,
+        IP_ALLOW_PROPERTY_NAME
+    )]
+    NoAllowedIpsForIpMode,
+
+    #[error(
+        "Invalid {} value of '{0}' specified. A non-negative number is required",
+        RECONNECT_ATTEMPTS_PROPERTY_NAME
+    )]
+    InvalidReconnectAttemptsSpecified(String),
+
+    #[error(
+        "Invalid {} value of '{0}' specified.  A non-negative number of milliseconds is required",
+        RECONNECT_BASE_DELAY_PROPERTY_NAME
+    )]
+    InvalidReconnectBaseDelaySpecified(String),
+
+    #[error(
+        "Invalid {} value of '{0}' specified.  A positive number of milliseconds is required",
+        MEDIA_TIMEOUT_PROPERTY_NAME
+    )]
+    InvalidMediaTimeoutSpecified(String),
+
+    #[error(
+        "Invalid {} value of '{0}' specified.  A positive number is required",
+        MAX_CONNECTS_PER_MINUTE_PROPERTY_NAME
+    )]
+    InvalidMaxConnectsPerMinuteSpecified(String),
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
 }
 
 impl RtmpReceiverStepGenerator {
@@ -138,107 +278,324 @@ impl RtmpReceiverStepGenerator {
 
 impl StepGenerator for RtmpReceiverStepGenerator {
     fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
-        let use_rtmps = match definition.parameters.get(RTMPS_FLAG) {
-            Some(_) => true,
-            None => false,
-        };
+        let StepParameters {
+            use_rtmps,
+            port,
+            app,
+            app_matching,
+            stream_key,
+            ip_restriction,
+            reactor_name,
+            max_reconnect_attempts,
+            reconnect_base_delay,
+            auth_url,
+            media_timeout,
+            max_connects_per_minute,
+        } = parse_parameters(&definition)?;
+
+        // Registration requests are sent without waiting for a response, so a closed endpoint
+        // would otherwise leave the step waiting forever
+        if self.rtmp_endpoint_sender.is_closed() {
+            return Err(StepCreationError::Fatal(
+                "The RTMP endpoint is no longer running".into(),
+            ));
+        }
 
-        let port = match definition.parameters.get(PORT_PROPERTY_NAME) {
-            Some(Some(value)) => match value.parse::<u16>() {
-                Ok(num) => num,
-                Err(_) => {
-                    return Err(Box::new(StepStartupError::InvalidPortSpecified(
-                        value.clone(),
-                    )));
-                }
+        let step = RtmpReceiverStep {
+            definition: definition.clone(),
+            status: StepStatus::Created,
+            rtmp_endpoint_sender: self.rtmp_endpoint_sender.clone(),
+            reactor_manager: self.reactor_manager.clone(),
+            port,
+            rtmp_app: app,
+            rtmp_app_matching: app_matching,
+            connection_details: HashMap::new(),
+            reactor_name,
+            stream_key: if stream_key == "*" {
+                StreamKeyRegistration::Any
+            } else {
+                StreamKeyRegistration::Exact(stream_key)
             },
+            ip_restriction,
+            use_tls: use_rtmps,
+            max_reconnect_attempts,
+            reconnect_base_delay,
+            reconnect_attempts_made: 0,
+            auth_url,
+            media_timeout,
+            max_connects_per_minute,
+        };
 
-            _ => {
-                if use_rtmps {
-                    443
-                } else {
-                    1935
-                }
+        let mut futures = vec![
+            step.register_with_endpoint(),
+            notify_reactor_manager_gone(self.reactor_manager.clone()).boxed(),
+        ];
+
+        if let Some(timeout) = media_timeout {
+            futures.push(wait_for_watchdog_tick(get_watchdog_interval(timeout)).boxed());
+        }
+
+        Ok((Box::new(step), futures))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_parameters(definition)?;
+        Ok(())
+    }
+
+    fn kind(&self) -> StepKind {
+        StepKind::Source
+    }
+}
+
+fn parse_parameters(
+    definition: &WorkflowStepDefinition,
+) -> Result<StepParameters, StepStartupError> {
+    let use_rtmps = match definition.parameters.get(RTMPS_FLAG) {
+        Some(_) => true,
+        None => false,
+    };
+
+    let port = match definition.parameters.get(PORT_PROPERTY_NAME) {
+        Some(Some(value)) => match value.parse::<u16>() {
+            Ok(num) => num,
+            Err(_) => {
+                return Err(StepStartupError::InvalidPortSpecified(value.clone()));
             }
-        };
+        },
 
-        let app = match definition.parameters.get(APP_PROPERTY_NAME) {
-            Some(Some(x)) => x.trim(),
-            _ => return Err(Box::new(StepStartupError::NoRtmpAppSpecified)),
-        };
+        _ => {
+            if use_rtmps {
+                443
+            } else {
+                1935
+            }
+        }
+    };
 
-        let stream_key = match definition.parameters.get(STREAM_KEY_PROPERTY_NAME) {
-            Some(Some(x)) => x.trim(),
-            _ => return Err(Box::new(StepStartupError::NoStreamKeySpecified)),
-        };
+    let app = match definition.parameters.get(APP_PROPERTY_NAME) {
+        Some(Some(x)) => x.trim().to_string(),
+        _ => return Err(StepStartupError::NoRtmpAppSpecified),
+    };
 
-        let allowed_ips = match definition.parameters.get(IP_ALLOW_PROPERTY_NAME) {
-            Some(Some(value)) => IpAddress::parse_comma_delimited_list(Some(value))?,
-            _ => Vec::new(),
-        };
+    let app_matching = if app == "*" {
+        RtmpAppMatching::Any
+    } else if definition.parameters.contains_key(IGNORE_APP_CASE_FLAG) {
+        RtmpAppMatching::CaseInsensitive
+    } else {
+        RtmpAppMatching::Exact
+    };
 
-        let denied_ips = match definition.parameters.get(IP_DENY_PROPERTY_NAME) {
-            Some(Some(value)) => IpAddress::parse_comma_delimited_list(Some(value))?,
-            _ => Vec::new(),
-        };
+    let stream_key = match definition.parameters.get(STREAM_KEY_PROPERTY_NAME) {
+        Some(Some(x)) => x.trim().to_string(),
+        _ => return Err(StepStartupError::NoStreamKeySpecified),
+    };
 
-        let ip_restriction = match (allowed_ips.len() > 0, denied_ips.len() > 0) {
+    let allowed_ips = match definition.parameters.get(IP_ALLOW_PROPERTY_NAME) {
+        Some(Some(value)) => IpAddress::parse_comma_delimited_list(Some(value))?,
+        _ => Vec::new(),
+    };
+
+    let denied_ips = match definition.parameters.get(IP_DENY_PROPERTY_NAME) {
+        Some(Some(value)) => IpAddress::parse_comma_delimited_list(Some(value))?,
+        _ => Vec::new(),
+    };
+
+    let ip_restriction = match definition.parameters.get(IP_MODE_PROPERTY_NAME) {
+        Some(Some(mode)) if mode == IP_MODE_ALLOW_WITH_EXCEPTIONS => {
+            if allowed_ips.is_empty() {
+                return Err(StepStartupError::NoAllowedIpsForIpMode);
+            }
+
+            IpRestriction::AllowWithExceptions {
+                allow: allowed_ips,
+                deny: denied_ips,
+            }
+        }
+
+        Some(Some(mode)) => return Err(StepStartupError::InvalidIpModeSpecified(mode.clone())),
+
+        _ => match (allowed_ips.len() > 0, denied_ips.len() > 0) {
             (true, true) => {
-                return Err(Box::new(
-                    StepStartupError::BothDenyAndAllowIpRestrictionsSpecified,
-                ));
+                return Err(StepStartupError::BothDenyAndAllowIpRestrictionsSpecified);
             }
             (true, false) => IpRestriction::Allow(allowed_ips),
             (false, true) => IpRestriction::Deny(denied_ips),
             (false, false) => IpRestriction::None,
-        };
+        },
+    };
 
-        let reactor_name = match definition.parameters.get(REACTOR_NAME) {
-            Some(Some(value)) => Some(value.clone()),
-            _ => None,
-        };
+    let reactor_name = match definition.parameters.get(REACTOR_NAME) {
+        Some(Some(value)) => Some(value.clone()),
+        _ => None,
+    };
 
-        let step = RtmpReceiverStep {
-            definition: definition.clone(),
-            status: StepStatus::Created,
-            rtmp_endpoint_sender: self.rtmp_endpoint_sender.clone(),
-            reactor_manager: self.reactor_manager.clone(),
-            port,
-            rtmp_app: app.to_string(),
-            connection_details: HashMap::new(),
-            reactor_name,
-            stream_key: if stream_key == "*" {
-                StreamKeyRegistration::Any
-            } else {
-                StreamKeyRegistration::Exact(stream_key.to_string())
-            },
-        };
+    let max_reconnect_attempts = match definition.parameters.get(RECONNECT_ATTEMPTS_PROPERTY_NAME) {
+        Some(Some(value)) => match value.parse::<u32>() {
+            Ok(num) => num,
+            Err(_) => {
+                return Err(StepStartupError::InvalidReconnectAttemptsSpecified(
+                    value.clone(),
+                ));
+            }
+        },
+
+        _ => 0,
+    };
 
+    let reconnect_base_delay = match definition
+        .parameters
+        .get(RECONNECT_BASE_DELAY_PROPERTY_NAME)
+    {
+        Some(Some(value)) => match value.parse::<u64>() {
+            Ok(num) => Duration::from_millis(num),
+            Err(_) => {
+                return Err(StepStartupError::InvalidReconnectBaseDelaySpecified(
+                    value.clone(),
+                ));
+            }
+        },
+
+        _ => DEFAULT_RECONNECT_BASE_DELAY,
+    };
+
+    let auth_url = match definition.parameters.get(AUTH_URL_PROPERTY_NAME) {
+        Some(Some(value)) if !value.trim().is_empty() => Some(value.trim().to_string()),
+        _ => None,
+    };
+
+    let media_timeout = match definition.parameters.get(MEDIA_TIMEOUT_PROPERTY_NAME) {
+        Some(Some(value)) => match value.parse::<u64>() {
+            Ok(num) if num > 0 => Some(Duration::from_millis(num)),
+            _ => {
+                return Err(StepStartupError::InvalidMediaTimeoutSpecified(
+                    value.clone(),
+                ));
+            }
+        },
+
+        _ => None,
+    };
+
+    let max_connects_per_minute = match definition
+        .parameters
+        .get(MAX_CONNECTS_PER_MINUTE_PROPERTY_NAME)
+    {
+        Some(Some(value)) => match value.parse::<u32>() {
+            Ok(num) if num > 0 => Some(num),
+            _ => {
+                return Err(StepStartupError::InvalidMaxConnectsPerMinuteSpecified(
+                    value.clone(),
+                ));
+            }
+        },
+
+        _ => None,
+    };
+
+    Ok(StepParameters {
+        use_rtmps,
+        port,
+        app,
+        app_matching,
+        stream_key,
+        ip_restriction,
+        reactor_name,
+        max_reconnect_attempts,
+        reconnect_base_delay,
+        auth_url,
+        media_timeout,
+        max_connects_per_minute,
+    })
+}
+
+/// Gets the name to give streams published on the specified app and stream key.  When publishers
+/// can connect on any app the app is included, otherwise streams with the same stream key on
+/// different apps could not be told apart by later steps.
+fn get_stream_name(app_matching: RtmpAppMatching, rtmp_app: &str, stream_key: &str) -> String {
+    match app_matching {
+        RtmpAppMatching::Any => format!("{}/{}", rtmp_app, stream_key),
+        RtmpAppMatching::Exact | RtmpAppMatching::CaseInsensitive => stream_key.to_string(),
+    }
+}
+
+impl RtmpReceiverStep {
+    fn register_with_endpoint(&self) -> BoxFuture<'static, Box<dyn StepFutureResult>> {
         let (sender, receiver) = unbounded_channel();
-        let _ = step
+        let _ = self
             .rtmp_endpoint_sender
             .send(RtmpEndpointRequest::ListenForPublishers {
                 message_channel: sender,
-                port: step.port,
-                rtmp_app: step.rtmp_app.clone(),
-                rtmp_stream_key: step.stream_key.clone(),
+                port: self.port,
+                rtmp_app: self.rtmp_app.clone(),
+                rtmp_app_matching: self.rtmp_app_matching,
+                rtmp_stream_key: self.stream_key.clone(),
                 stream_id: None,
-                ip_restrictions: ip_restriction,
-                use_tls: use_rtmps,
-                requires_registrant_approval: step.reactor_name.is_some(),
+                ip_restrictions: self.ip_restriction.clone(),
+                use_tls: self.use_tls,
+                requires_registrant_approval: self.reactor_name.is_some()
+                    || self.auth_url.is_some(),
+                max_connects_per_minute: self.max_connects_per_minute,
             });
 
-        Ok((
-            Box::new(step),
-            vec![
-                wait_for_rtmp_endpoint_response(receiver).boxed(),
-                notify_reactor_manager_gone(self.reactor_manager.clone()).boxed(),
-            ],
-        ))
+        wait_for_rtmp_endpoint_response(receiver).boxed()
+    }
+
+    fn handle_endpoint_registration_dropped(&mut self, outputs: &mut StepOutputs) {
+        if self.status == StepStatus::Shutdown {
+            return;
+        }
+
+        // Any publishers that were connected are gone along with the registration, so let
+        // later steps know not to expect any more media from them.
+        for (_, connection) in self.connection_details.drain() {
+            if connection.timed_out {
+                continue; // Already reported as disconnected
+            }
+
+            outputs.media.push(MediaNotification {
+                stream_id: connection.stream_id,
+                content: MediaNotificationContent::StreamDisconnected,
+                tags: Vec::new(),
+            });
+        }
+
+        if self.status == StepStatus::ShuttingDown {
+            // The endpoint drops the registration once it has processed our removal request, so
+            // it's now safe for the step to go away.
+            info!("Rtmp receive step's registration was removed, shutdown complete");
+            self.status = StepStatus::Shutdown;
+            return;
+        }
+
+        if self.reconnect_attempts_made >= self.max_reconnect_attempts {
+            error!("Rtmp receive step stopping as the rtmp endpoint dropped the registration");
+            self.status = StepStatus::Error {
+                message: "Rtmp receive step stopping as the rtmp endpoint dropped the registration"
+                    .to_string(),
+            };
+
+            return;
+        }
+
+        self.reconnect_attempts_made += 1;
+        let delay = get_reconnect_delay(self.reconnect_base_delay, self.reconnect_attempts_made);
+
+        warn!(
+            attempt = self.reconnect_attempts_made,
+            "Rtmp endpoint dropped the registration.  Attempting to re-register in {:?} (attempt {} of {})",
+            delay, self.reconnect_attempts_made, self.max_reconnect_attempts
+        );
+
+        self.status = StepStatus::Reconnecting {
+            attempt: self.reconnect_attempts_made,
+        };
+
+        outputs
+            .futures
+            .push(wait_for_reconnect_delay(delay).boxed());
     }
-}
 
-impl RtmpReceiverStep {
     fn handle_rtmp_publisher_message(
         &mut self,
         outputs: &mut StepOutputs,
@@ -257,7 +614,13 @@ impl RtmpReceiverStep {
 
             RtmpEndpointPublisherMessage::PublisherRegistrationSuccessful => {
                 info!("Rtmp receive step successfully registered for publishing");
+                if self.status == StepStatus::ShuttingDown {
+                    // Still waiting on the endpoint to process the removal request
+                    return;
+                }
+
                 self.status = StepStatus::Active;
+                self.reconnect_attempts_made = 0;
 
                 return;
             }
@@ -266,13 +629,18 @@ impl RtmpReceiverStep {
                 stream_id,
                 connection_id,
                 stream_key,
+                rtmp_app,
+                remote_address,
                 reactor_update_channel,
             } => {
                 info!(
                     stream_id = ?stream_id,
                     connection_id = ?connection_id,
                     stream_key = %stream_key,
-                    "Rtmp receive step seen new publisher: {:?}, {:?}, {:?}", stream_id, connection_id, stream_key
+                    rtmp_app = %rtmp_app,
+                    remote_address = %remote_address,
+                    "Rtmp receive step seen new publisher from {}: {:?}, {:?}, {:?}",
+                    remote_address, stream_id, connection_id, stream_key
                 );
 
                 let cancellation_token = if let Some(update_channel) = reactor_update_channel {
@@ -289,25 +657,34 @@ impl RtmpReceiverStep {
                     None
                 };
 
+                let stream_name = get_stream_name(self.rtmp_app_matching, &rtmp_app, &stream_key);
                 self.connection_details.insert(
                     connection_id,
                     ConnectionDetails {
                         stream_id: stream_id.clone(),
+                        stream_key: stream_key.clone(),
+                        stream_name: stream_name.clone(),
+                        remote_address,
+                        last_media_received_at: Instant::now(),
+                        timed_out: false,
+                        video_sequence_header: None,
+                        audio_sequence_header: None,
+                        metadata: None,
                         _cancellation_channel: cancellation_token,
                     },
                 );
 
                 outputs.media.push(MediaNotification {
                     stream_id,
-                    content: MediaNotificationContent::NewIncomingStream {
-                        stream_name: stream_key,
-                    },
+                    content: MediaNotificationContent::NewIncomingStream { stream_name },
+                    tags: Vec::new(),
                 });
             }
 
             RtmpEndpointPublisherMessage::PublishingStopped { connection_id } => {
                 match self.connection_details.remove(&connection_id) {
                     None => (),
+                    Some(connection) if connection.timed_out => (),
                     Some(connection) => {
                         info!(
                             stream_id = ?connection.stream_id,
@@ -319,6 +696,7 @@ impl RtmpReceiverStep {
                         outputs.media.push(MediaNotification {
                             stream_id: connection.stream_id,
                             content: MediaNotificationContent::StreamDisconnected,
+                            tags: Vec::new(),
                         });
                     }
                 }
@@ -327,14 +705,20 @@ impl RtmpReceiverStep {
             RtmpEndpointPublisherMessage::StreamMetadataChanged {
                 publisher,
                 metadata,
-            } => match self.connection_details.get(&publisher) {
+            } => match self.connection_details.get_mut(&publisher) {
                 None => (),
-                Some(connection) => outputs.media.push(MediaNotification {
-                    stream_id: connection.stream_id.clone(),
-                    content: MediaNotificationContent::Metadata {
+                Some(connection) => {
+                    let content = MediaNotificationContent::Metadata {
                         data: crate::utils::stream_metadata_to_hash_map(metadata),
-                    },
-                }),
+                    };
+
+                    connection.metadata = Some(content.clone());
+                    outputs.media.push(MediaNotification {
+                        stream_id: connection.stream_id.clone(),
+                        content,
+                        tags: Vec::new(),
+                    });
+                }
             },
 
             RtmpEndpointPublisherMessage::NewVideoData {
@@ -345,21 +729,29 @@ impl RtmpReceiverStep {
                 is_sequence_header,
                 is_keyframe,
                 composition_time_offset,
-            } => match self.connection_details.get(&publisher) {
+            } => match self.connection_details.get_mut(&publisher) {
                 None => (),
                 Some(connection) => {
+                    connection.record_media_received(outputs);
+                    let content = MediaNotificationContent::Video {
+                        is_keyframe,
+                        is_sequence_header,
+                        data,
+                        codec,
+                        timestamp: VideoTimestamp::from_rtmp_data(
+                            timestamp,
+                            composition_time_offset,
+                        ),
+                    };
+
+                    if is_sequence_header {
+                        connection.video_sequence_header = Some(content.clone());
+                    }
+
                     outputs.media.push(MediaNotification {
                         stream_id: connection.stream_id.clone(),
-                        content: MediaNotificationContent::Video {
-                            is_keyframe,
-                            is_sequence_header,
-                            data,
-                            codec,
-                            timestamp: VideoTimestamp::from_rtmp_data(
-                                timestamp,
-                                composition_time_offset,
-                            ),
-                        },
+                        content,
+                        tags: Vec::new(),
                     });
                 }
             },
@@ -370,17 +762,25 @@ impl RtmpReceiverStep {
                 data,
                 codec,
                 timestamp,
-            } => match self.connection_details.get(&publisher) {
+            } => match self.connection_details.get_mut(&publisher) {
                 None => (),
                 Some(connection) => {
+                    connection.record_media_received(outputs);
+                    let content = MediaNotificationContent::Audio {
+                        is_sequence_header,
+                        data,
+                        codec,
+                        timestamp: Duration::from_millis(timestamp.value as u64),
+                    };
+
+                    if is_sequence_header {
+                        connection.audio_sequence_header = Some(content.clone());
+                    }
+
                     outputs.media.push(MediaNotification {
                         stream_id: connection.stream_id.clone(),
-                        content: MediaNotificationContent::Audio {
-                            is_sequence_header,
-                            data,
-                            codec,
-                            timestamp: Duration::from_millis(timestamp.value as u64),
-                        },
+                        content,
+                        tags: Vec::new(),
                     });
                 }
             },
@@ -388,34 +788,141 @@ impl RtmpReceiverStep {
             RtmpEndpointPublisherMessage::PublisherRequiringApproval {
                 connection_id,
                 stream_key,
+                rtmp_app,
                 response_channel,
             } => {
-                if let Some(name) = &self.reactor_name {
-                    let (sender, receiver) = unbounded_channel();
-                    let _ = self.reactor_manager.send(
-                        ReactorManagerRequest::CreateWorkflowForStreamName {
-                            reactor_name: name.clone(),
-                            stream_name: stream_key,
-                            response_channel: sender,
-                        },
+                let stream_name = get_stream_name(self.rtmp_app_matching, &rtmp_app, &stream_key);
+                if let Some(url) = &self.auth_url {
+                    let future = check_publisher_authorization(
+                        url.clone(),
+                        connection_id,
+                        rtmp_app,
+                        stream_key,
+                        stream_name,
+                        response_channel,
                     );
 
-                    outputs
-                        .futures
-                        .push(wait_for_reactor_response(receiver, response_channel).boxed());
+                    outputs.futures.push(future.boxed());
                 } else {
-                    error!(
-                        connection_id = %connection_id,
-                        stream_key = %stream_key,
-                        "Publisher requires approval for stream key {} but no reactor name was set",
-                        stream_key
+                    self.request_reactor_approval(
+                        outputs,
+                        connection_id,
+                        stream_name,
+                        response_channel,
                     );
-
-                    let _ = response_channel.send(ValidationResponse::Reject);
                 }
             }
         }
     }
+
+    fn handle_watchdog_tick(&mut self, outputs: &mut StepOutputs) {
+        let timeout = match self.media_timeout {
+            Some(timeout) => timeout,
+            None => return,
+        };
+
+        if self.status == StepStatus::Shutdown || self.status == StepStatus::ShuttingDown {
+            return;
+        }
+
+        for (connection_id, connection) in self.connection_details.iter_mut() {
+            if connection.timed_out || connection.last_media_received_at.elapsed() < timeout {
+                continue;
+            }
+
+            warn!(
+                stream_id = ?connection.stream_id,
+                connection_id = ?connection_id,
+                "No media received from connection {:?} on stream key {} within {:?}, reporting stream {:?} as disconnected",
+                connection_id, connection.stream_key, timeout, connection.stream_id
+            );
+
+            connection.timed_out = true;
+            outputs.media.push(MediaNotification {
+                stream_id: connection.stream_id.clone(),
+                content: MediaNotificationContent::StreamDisconnected,
+                tags: Vec::new(),
+            });
+        }
+
+        outputs
+            .futures
+            .push(wait_for_watchdog_tick(get_watchdog_interval(timeout)).boxed());
+    }
+
+    fn request_reactor_approval(
+        &mut self,
+        outputs: &mut StepOutputs,
+        connection_id: ConnectionId,
+        stream_name: String,
+        response_channel: Sender<ValidationResponse>,
+    ) {
+        if let Some(name) = &self.reactor_name {
+            let (sender, receiver) = unbounded_channel();
+            let _ = self
+                .reactor_manager
+                .send(ReactorManagerRequest::CreateWorkflowForStreamName {
+                    reactor_name: name.clone(),
+                    stream_name,
+                    metadata: None, // Publishers are validated before any media is received
+                    response_channel: sender,
+                });
+
+            outputs
+                .futures
+                .push(wait_for_reactor_response(receiver, response_channel).boxed());
+        } else if self.auth_url.is_some() {
+            // Publisher was already authorized and there's no reactor to consult
+            let _ = response_channel.send(ValidationResponse::Approve {
+                reactor_update_channel: None,
+            });
+        } else {
+            error!(
+                connection_id = %connection_id,
+                stream_name = %stream_name,
+                "Publisher requires approval for stream {} but no reactor name was set",
+                stream_name
+            );
+
+            let _ = response_channel.send(ValidationResponse::Reject);
+        }
+    }
+}
+
+impl ConnectionDetails {
+    fn record_media_received(&mut self, outputs: &mut StepOutputs) {
+        self.last_media_received_at = Instant::now();
+        if self.timed_out {
+            info!(
+                stream_id = ?self.stream_id,
+                "Media received for timed out stream {:?}, announcing it as a new stream",
+                self.stream_id
+            );
+
+            self.timed_out = false;
+            outputs.media.push(MediaNotification {
+                stream_id: self.stream_id.clone(),
+                content: MediaNotificationContent::NewIncomingStream {
+                    stream_name: self.stream_name.clone(),
+                },
+                tags: Vec::new(),
+            });
+
+            let cached_content = [
+                &self.metadata,
+                &self.video_sequence_header,
+                &self.audio_sequence_header,
+            ];
+
+            for content in cached_content.iter().filter_map(|content| content.as_ref()) {
+                outputs.media.push(MediaNotification {
+                    stream_id: self.stream_id.clone(),
+                    content: content.clone(),
+                    tags: Vec::new(),
+                });
+            }
+        }
+    }
 }
 
 unsafe impl Send for RtmpReceiverStep {}
@@ -447,15 +954,25 @@ impl WorkflowStep for RtmpReceiverStep {
 
             match future_result {
                 FutureResult::RtmpEndpointDroppedRegistration => {
-                    error!(
-                        "Rtmp receive step stopping as the rtmp endpoint dropped the registration"
+                    self.handle_endpoint_registration_dropped(outputs);
+                    if let StepStatus::Error { .. } = &self.status {
+                        return;
+                    }
+                }
+
+                FutureResult::ReconnectDelayElapsed => {
+                    if self.status == StepStatus::Shutdown
+                        || self.status == StepStatus::ShuttingDown
+                    {
+                        continue;
+                    }
+
+                    info!(
+                        attempt = self.reconnect_attempts_made,
+                        "Rtmp receive step re-registering with the rtmp endpoint"
                     );
-                    self.status = StepStatus::Error {
-                        message: "Rtmp receive step stopping as the rtmp endpoint dropped the registration"
-                            .to_string(),
-                    };
 
-                    return;
+                    outputs.futures.push(self.register_with_endpoint());
                 }
 
                 FutureResult::ReactorManagerGone => {
@@ -496,7 +1013,7 @@ impl WorkflowStep for RtmpReceiverStep {
                 } => {
                     if is_valid {
                         let _ = response_channel.send(ValidationResponse::Approve {
-                            reactor_update_channel: reactor_receiver,
+                            reactor_update_channel: Some(reactor_receiver),
                         });
                     } else {
                         let _ = response_channel.send(ValidationResponse::Reject);
@@ -530,20 +1047,75 @@ impl WorkflowStep for RtmpReceiverStep {
                 }
 
                 FutureResult::ReactorCancellationReceived => {}
+
+                FutureResult::MediaWatchdogTick => {
+                    self.handle_watchdog_tick(outputs);
+                }
+
+                FutureResult::PublisherAuthorizationReturned {
+                    is_allowed,
+                    connection_id,
+                    stream_name,
+                    response_channel,
+                } => {
+                    if is_allowed {
+                        self.request_reactor_approval(
+                            outputs,
+                            connection_id,
+                            stream_name,
+                            response_channel,
+                        );
+                    } else {
+                        let _ = response_channel.send(ValidationResponse::Reject);
+                    }
+                }
             }
         }
     }
 
+    fn get_stream_details(&self) -> HashMap<StreamId, HashMap<String, String>> {
+        self.connection_details
+            .values()
+            .filter(|connection| !connection.timed_out)
+            .map(|connection| {
+                let mut details = HashMap::new();
+                details.insert(
+                    REMOTE_ADDRESS_DETAIL.to_string(),
+                    connection.remote_address.to_string(),
+                );
+
+                (connection.stream_id.clone(), details)
+            })
+            .collect()
+    }
+
     fn shutdown(&mut self) {
-        self.status = StepStatus::Shutdown;
-        let _ = self
+        let has_registration = match &self.status {
+            StepStatus::Created | StepStatus::Active => true,
+            StepStatus::Reconnecting { .. }
+            | StepStatus::Error { .. }
+            | StepStatus::ShuttingDown
+            | StepStatus::Shutdown => false,
+        };
+
+        let removal_sent = self
             .rtmp_endpoint_sender
             .send(RtmpEndpointRequest::RemoveRegistration {
                 registration_type: RegistrationType::Publisher,
                 port: self.port,
                 rtmp_app: self.rtmp_app.clone(),
                 rtmp_stream_key: self.stream_key.clone(),
-            });
+            })
+            .is_ok();
+
+        // The endpoint drops the registration once it processes the removal, so until that's
+        // been seen the step isn't safe to drop (e.g. a replacement step could have its own
+        // registration rejected as a duplicate).
+        if has_registration && removal_sent {
+            self.status = StepStatus::ShuttingDown;
+        } else {
+            self.status = StepStatus::Shutdown;
+        }
     }
 }
 
@@ -601,9 +1173,122 @@ async fn wait_for_reactor_update(
     Box::new(result)
 }
 
+async fn wait_for_reconnect_delay(delay: Duration) -> Box<dyn StepFutureResult> {
+    tokio::time::sleep(delay).await;
+    Box::new(FutureResult::ReconnectDelayElapsed)
+}
+
+async fn wait_for_watchdog_tick(interval: Duration) -> Box<dyn StepFutureResult> {
+    tokio::time::sleep(interval).await;
+    Box::new(FutureResult::MediaWatchdogTick)
+}
+
+fn get_watchdog_interval(media_timeout: Duration) -> Duration {
+    // Checking at half the timeout means a stalled stream is reported at most 1.5x the timeout
+    // after its last media packet.
+    media_timeout / 2
+}
+
+fn get_reconnect_delay(base_delay: Duration, attempt: u32) -> Duration {
+    // Exponential backoff starting at the base delay, capped so a long outage doesn't cause
+    // extremely long gaps between attempts once the endpoint comes back.
+    let multiplier = 2_u32.saturating_pow(attempt.saturating_sub(1));
+    match base_delay.checked_mul(multiplier) {
+        Some(delay) if delay < MAX_RECONNECT_DELAY => delay,
+        _ => MAX_RECONNECT_DELAY,
+    }
+}
+
 async fn notify_reactor_manager_gone(
     sender: UnboundedSender<ReactorManagerRequest>,
 ) -> Box<dyn StepFutureResult> {
     sender.closed().await;
     Box::new(FutureResult::ReactorManagerGone)
 }
+
+async fn check_publisher_authorization(
+    url: String,
+    connection_id: ConnectionId,
+    rtmp_app: String,
+    stream_key: String,
+    stream_name: String,
+    response_channel: Sender<ValidationResponse>,
+) -> Box<dyn StepFutureResult> {
+    let is_allowed = match tokio::time::timeout(
+        AUTH_REQUEST_TIMEOUT,
+        execute_auth_request(&url, &connection_id, &rtmp_app, &stream_key),
+    )
+    .await
+    {
+        Ok(is_allowed) => is_allowed,
+        Err(_) => {
+            warn!(
+                connection_id = %connection_id,
+                "Auth request to {} timed out",
+                url
+            );
+
+            false
+        }
+    };
+
+    if !is_allowed {
+        info!(
+            connection_id = %connection_id,
+            stream_key = %stream_key,
+            "Publisher on stream key {} was denied by {}",
+            stream_key, url
+        );
+    }
+
+    Box::new(FutureResult::PublisherAuthorizationReturned {
+        is_allowed,
+        connection_id,
+        stream_name,
+        response_channel,
+    })
+}
+
+async fn execute_auth_request(
+    url: &str,
+    connection_id: &ConnectionId,
+    rtmp_app: &str,
+    stream_key: &str,
+) -> bool {
+    let content = match serde_json::to_string(&AuthRequestContent {
+        connection_id: connection_id.0.clone(),
+        rtmp_app: rtmp_app.to_string(),
+        stream_key: stream_key.to_string(),
+    }) {
+        Ok(json) => json,
+        Err(error) => {
+            error!("Failed to serialize auth request to json: {:?}", error);
+            return false;
+        }
+    };
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header(
+            hyper::http::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        )
+        .body(Body::from(content));
+
+    let request = match request {
+        Ok(request) => request,
+        Err(error) => {
+            error!("Failed to build auth request: {}", error);
+            return false;
+        }
+    };
+
+    match Client::new().request(request).await {
+        Ok(response) => response.status().is_success(),
+        Err(error) => {
+            error!("Error performing auth request to {}: {}", url, error);
+            false
+        }
+    }
+}
@@ -8,9 +8,12 @@ use crate::workflows::{MediaNotification, MediaNotificationContent};
 use crate::{test_utils, StreamId};
 use anyhow::Result;
 use bytes::Bytes;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Response, Server, StatusCode};
 use rml_rtmp::sessions::StreamMetadata;
 use rml_rtmp::time::RtmpTimestamp;
 use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
 use std::time::Duration;
 use tokio::sync::oneshot::channel;
 
@@ -25,6 +28,10 @@ struct DefinitionBuilder {
     app: Option<String>,
     key: Option<String>,
     reactor: Option<String>,
+    reconnect_attempts: Option<u32>,
+    auth_url: Option<String>,
+    media_timeout_ms: Option<u64>,
+    max_connects_per_minute: Option<u32>,
 }
 
 impl DefinitionBuilder {
@@ -34,6 +41,10 @@ impl DefinitionBuilder {
             app: None,
             key: None,
             reactor: None,
+            reconnect_attempts: None,
+            auth_url: None,
+            media_timeout_ms: None,
+            max_connects_per_minute: None,
         }
     }
 
@@ -57,6 +68,26 @@ impl DefinitionBuilder {
         self
     }
 
+    fn reconnect_attempts(mut self, attempts: u32) -> Self {
+        self.reconnect_attempts = Some(attempts);
+        self
+    }
+
+    fn auth_url(mut self, url: &str) -> Self {
+        self.auth_url = Some(url.to_string());
+        self
+    }
+
+    fn media_timeout_ms(mut self, timeout: u64) -> Self {
+        self.media_timeout_ms = Some(timeout);
+        self
+    }
+
+    fn max_connects_per_minute(mut self, max_connects: u32) -> Self {
+        self.max_connects_per_minute = Some(max_connects);
+        self
+    }
+
     fn build(self) -> WorkflowStepDefinition {
         let mut definition = WorkflowStepDefinition {
             step_type: WorkflowStepType("rtmp_receive".to_string()),
@@ -95,6 +126,38 @@ impl DefinitionBuilder {
                 .insert(REACTOR_NAME.to_string(), Some(reactor));
         }
 
+        if let Some(attempts) = self.reconnect_attempts {
+            definition.parameters.insert(
+                RECONNECT_ATTEMPTS_PROPERTY_NAME.to_string(),
+                Some(attempts.to_string()),
+            );
+
+            definition.parameters.insert(
+                RECONNECT_BASE_DELAY_PROPERTY_NAME.to_string(),
+                Some("1".to_string()),
+            );
+        }
+
+        if let Some(url) = self.auth_url {
+            definition
+                .parameters
+                .insert(AUTH_URL_PROPERTY_NAME.to_string(), Some(url));
+        }
+
+        if let Some(timeout) = self.media_timeout_ms {
+            definition.parameters.insert(
+                MEDIA_TIMEOUT_PROPERTY_NAME.to_string(),
+                Some(timeout.to_string()),
+            );
+        }
+
+        if let Some(max_connects) = self.max_connects_per_minute {
+            definition.parameters.insert(
+                MAX_CONNECTS_PER_MINUTE_PROPERTY_NAME.to_string(),
+                Some(max_connects.to_string()),
+            );
+        }
+
         definition
     }
 }
@@ -150,6 +213,77 @@ impl TestContext {
     }
 }
 
+/// Starts an http server that responds to every request with the specified status code, and
+/// returns the url to it along with a channel that receives the body of each request
+fn start_auth_server(status: StatusCode) -> (String, UnboundedReceiver<String>) {
+    let (sender, receiver) = unbounded_channel();
+    let make_service = make_service_fn(move |_| {
+        let sender = sender.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: hyper::Request<Body>| {
+                let sender = sender.clone();
+                async move {
+                    let bytes = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                    let _ = sender.send(String::from_utf8(bytes.to_vec()).unwrap());
+
+                    let mut response = Response::new(Body::empty());
+                    *response.status_mut() = status;
+                    Ok::<_, Infallible>(response)
+                }
+            }))
+        }
+    });
+
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let url = format!("http://{}/auth", server.local_addr());
+    tokio::spawn(server);
+
+    (url, receiver)
+}
+
+fn send_publisher_connected(publish_channel: &UnboundedSender<RtmpEndpointPublisherMessage>) {
+    publish_channel
+        .send(RtmpEndpointPublisherMessage::NewPublisherConnected {
+            stream_id: StreamId("test".to_string()),
+            stream_key: "abc".to_string(),
+            rtmp_app: "app".to_string(),
+            connection_id: ConnectionId("connection".to_string()),
+            remote_address: "127.0.0.1:1234".parse().unwrap(),
+            reactor_update_channel: None,
+        })
+        .expect("Failed to send publisher connected message");
+}
+
+fn send_video(publish_channel: &UnboundedSender<RtmpEndpointPublisherMessage>) {
+    publish_channel
+        .send(RtmpEndpointPublisherMessage::NewVideoData {
+            publisher: ConnectionId("connection".to_string()),
+            data: Bytes::from(vec![1, 2, 3]),
+            codec: VideoCodec::H264,
+            timestamp: RtmpTimestamp::new(5),
+            is_keyframe: true,
+            is_sequence_header: false,
+            composition_time_offset: 0,
+        })
+        .expect("Failed to send video message");
+}
+
+fn send_publisher_requiring_approval(
+    publish_channel: &UnboundedSender<RtmpEndpointPublisherMessage>,
+) -> tokio::sync::oneshot::Receiver<ValidationResponse> {
+    let (sender, receiver) = channel();
+    publish_channel
+        .send(RtmpEndpointPublisherMessage::PublisherRequiringApproval {
+            stream_key: "ab123".to_string(),
+            rtmp_app: "app".to_string(),
+            connection_id: ConnectionId("connection".to_string()),
+            response_channel: sender,
+        })
+        .expect("Failed to send publisher message");
+
+    receiver
+}
+
 #[tokio::test]
 async fn requests_registration_for_publishers() {
     let definition = DefinitionBuilder::new()
@@ -219,6 +353,76 @@ async fn asterisk_stream_key_acts_as_wildcard() {
     }
 }
 
+#[tokio::test]
+async fn exact_app_matching_used_by_default() {
+    let definition = DefinitionBuilder::new().app("live").build();
+    let mut context = TestContext::new(definition).unwrap();
+
+    let response = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match response {
+        RtmpEndpointRequest::ListenForPublishers {
+            rtmp_app_matching, ..
+        } => {
+            assert_eq!(
+                rtmp_app_matching,
+                RtmpAppMatching::Exact,
+                "Unexpected app matching"
+            );
+        }
+
+        response => panic!("Unexpected rtmp request: {:?}", response),
+    }
+}
+
+#[tokio::test]
+async fn asterisk_app_matches_any_app() {
+    let definition = DefinitionBuilder::new().app("*").build();
+    let mut context = TestContext::new(definition).unwrap();
+
+    let response = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match response {
+        RtmpEndpointRequest::ListenForPublishers {
+            rtmp_app_matching, ..
+        } => {
+            assert_eq!(
+                rtmp_app_matching,
+                RtmpAppMatching::Any,
+                "Unexpected app matching"
+            );
+        }
+
+        response => panic!("Unexpected rtmp request: {:?}", response),
+    }
+}
+
+#[tokio::test]
+async fn ignore_app_case_flag_matches_app_case_insensitively() {
+    let mut definition = DefinitionBuilder::new().app("live").build();
+    definition
+        .parameters
+        .insert(IGNORE_APP_CASE_FLAG.to_string(), None);
+
+    let mut context = TestContext::new(definition).unwrap();
+
+    let response = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match response {
+        RtmpEndpointRequest::ListenForPublishers {
+            rtmp_app,
+            rtmp_app_matching,
+            ..
+        } => {
+            assert_eq!(&rtmp_app, "live", "Unexpected rtmp app");
+            assert_eq!(
+                rtmp_app_matching,
+                RtmpAppMatching::CaseInsensitive,
+                "Unexpected app matching"
+            );
+        }
+
+        response => panic!("Unexpected rtmp request: {:?}", response),
+    }
+}
+
 #[tokio::test]
 async fn error_if_no_app_specified() {
     let mut definition = DefinitionBuilder::new().build();
@@ -241,6 +445,100 @@ async fn error_if_no_key_specified() {
     }
 }
 
+#[tokio::test]
+async fn error_if_both_allow_and_deny_ips_specified_without_ip_mode() {
+    let mut definition = DefinitionBuilder::new().build();
+    definition.parameters.insert(
+        IP_ALLOW_PROPERTY_NAME.to_string(),
+        Some("10.0.0.0/8".to_string()),
+    );
+    definition.parameters.insert(
+        IP_DENY_PROPERTY_NAME.to_string(),
+        Some("10.0.0.5".to_string()),
+    );
+
+    match TestContext::new(definition) {
+        Ok(_) => panic!("Expected failure"),
+        Err(_) => (),
+    }
+}
+
+#[tokio::test]
+async fn error_if_unknown_ip_mode_specified() {
+    let mut definition = DefinitionBuilder::new().build();
+    definition.parameters.insert(
+        IP_ALLOW_PROPERTY_NAME.to_string(),
+        Some("10.0.0.0/8".to_string()),
+    );
+    definition
+        .parameters
+        .insert(IP_MODE_PROPERTY_NAME.to_string(), Some("abc".to_string()));
+
+    match TestContext::new(definition) {
+        Ok(_) => panic!("Expected failure"),
+        Err(_) => (),
+    }
+}
+
+#[tokio::test]
+async fn error_if_allow_with_exceptions_ip_mode_has_no_allowed_ips() {
+    let mut definition = DefinitionBuilder::new().build();
+    definition.parameters.insert(
+        IP_DENY_PROPERTY_NAME.to_string(),
+        Some("10.0.0.5".to_string()),
+    );
+    definition.parameters.insert(
+        IP_MODE_PROPERTY_NAME.to_string(),
+        Some(IP_MODE_ALLOW_WITH_EXCEPTIONS.to_string()),
+    );
+
+    match TestContext::new(definition) {
+        Ok(_) => panic!("Expected failure"),
+        Err(_) => (),
+    }
+}
+
+#[tokio::test]
+async fn allow_with_exceptions_ip_mode_denies_ips_in_deny_list() {
+    let mut definition = DefinitionBuilder::new().build();
+    definition.parameters.insert(
+        IP_ALLOW_PROPERTY_NAME.to_string(),
+        Some("10.0.0.0/8".to_string()),
+    );
+    definition.parameters.insert(
+        IP_DENY_PROPERTY_NAME.to_string(),
+        Some("10.0.0.5".to_string()),
+    );
+    definition.parameters.insert(
+        IP_MODE_PROPERTY_NAME.to_string(),
+        Some(IP_MODE_ALLOW_WITH_EXCEPTIONS.to_string()),
+    );
+
+    let mut context = TestContext::new(definition).unwrap();
+    let response = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    let ip_restrictions = match response {
+        RtmpEndpointRequest::ListenForPublishers {
+            ip_restrictions, ..
+        } => ip_restrictions,
+
+        response => panic!("Unexpected rtmp request: {:?}", response),
+    };
+
+    let allowed = |ip: &str| ip_restrictions.is_allowed(&format!("{}:1234", ip).parse().unwrap());
+    assert!(
+        allowed("10.0.0.1"),
+        "Expected ip in allow list to be allowed"
+    );
+    assert!(
+        !allowed("10.0.0.5"),
+        "Expected ip in deny list to be denied"
+    );
+    assert!(
+        !allowed("192.168.0.1"),
+        "Expected ip not in allow list to be denied"
+    );
+}
+
 #[test]
 fn step_starts_in_created_state() {
     let definition = DefinitionBuilder::new().build();
@@ -318,7 +616,9 @@ async fn stream_started_notification_raised_when_publisher_connects() {
         .send(RtmpEndpointPublisherMessage::NewPublisherConnected {
             stream_id: StreamId("test".to_string()),
             stream_key: "abc".to_string(),
+            rtmp_app: "app".to_string(),
             connection_id: ConnectionId("connection".to_string()),
+            remote_address: "127.0.0.1:1234".parse().unwrap(),
             reactor_update_channel: None,
         })
         .expect("Failed to send publisher connected message");
@@ -343,6 +643,74 @@ async fn stream_started_notification_raised_when_publisher_connects() {
     }
 }
 
+#[tokio::test]
+async fn stream_name_includes_app_when_matching_any_app() {
+    let definition = DefinitionBuilder::new().app("*").build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    send_publisher_connected(&channel);
+    context.step_context.execute_pending_notifications().await;
+
+    assert_eq!(
+        context.step_context.media_outputs.len(),
+        1,
+        "Unexpected number of media outputs"
+    );
+
+    match &context.step_context.media_outputs[0].content {
+        MediaNotificationContent::NewIncomingStream { stream_name } => {
+            assert_eq!(stream_name, "app/abc", "Unexpected stream name");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[tokio::test]
+async fn publisher_remote_address_included_in_stream_details() {
+    let definition = DefinitionBuilder::new().build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    send_publisher_connected(&channel);
+    context.step_context.execute_pending_notifications().await;
+
+    let details = context.step_context.step.get_stream_details();
+    let stream_details = details
+        .get(&StreamId("test".to_string()))
+        .expect("No details for stream");
+
+    assert_eq!(
+        stream_details.get(REMOTE_ADDRESS_DETAIL),
+        Some(&"127.0.0.1:1234".to_string()),
+        "Unexpected remote address"
+    );
+}
+
+#[tokio::test]
+async fn reactor_queried_with_app_in_stream_name_when_matching_any_app() {
+    let definition = DefinitionBuilder::new()
+        .app("*")
+        .reactor_name("abc")
+        .build();
+
+    let mut context = TestContext::new(definition).unwrap();
+    let publish_channel = context.accept_registration().await;
+
+    let _receiver = send_publisher_requiring_approval(&publish_channel);
+    context.step_context.execute_pending_notifications().await;
+
+    let request = test_utils::expect_mpsc_response(&mut context.reactor_manager).await;
+    match request {
+        ReactorManagerRequest::CreateWorkflowForStreamName { stream_name, .. } => {
+            assert_eq!(&stream_name, "app/ab123", "Unexpected stream name");
+        }
+
+        request => panic!("Unexpected request received: {:?}", request),
+    }
+}
+
 #[tokio::test]
 async fn stream_disconnected_notification_raised_when_publisher_disconnects() {
     let definition = DefinitionBuilder::new().build();
@@ -353,7 +721,9 @@ async fn stream_disconnected_notification_raised_when_publisher_disconnects() {
         .send(RtmpEndpointPublisherMessage::NewPublisherConnected {
             stream_id: StreamId("test".to_string()),
             stream_key: "abc".to_string(),
+            rtmp_app: "app".to_string(),
             connection_id: ConnectionId("connection".to_string()),
+            remote_address: "127.0.0.1:1234".parse().unwrap(),
             reactor_update_channel: None,
         })
         .expect("Failed to send publisher connected message");
@@ -394,7 +764,9 @@ async fn metadata_notification_raised_when_publisher_sends_one() {
         .send(RtmpEndpointPublisherMessage::NewPublisherConnected {
             stream_id: StreamId("test".to_string()),
             stream_key: "abc".to_string(),
+            rtmp_app: "app".to_string(),
             connection_id: ConnectionId("connection".to_string()),
+            remote_address: "127.0.0.1:1234".parse().unwrap(),
             reactor_update_channel: None,
         })
         .expect("Failed to send publisher connected message");
@@ -445,7 +817,9 @@ async fn video_notification_received_when_publisher_sends_video() {
         .send(RtmpEndpointPublisherMessage::NewPublisherConnected {
             stream_id: StreamId("test".to_string()),
             stream_key: "abc".to_string(),
+            rtmp_app: "app".to_string(),
             connection_id: ConnectionId("connection".to_string()),
+            remote_address: "127.0.0.1:1234".parse().unwrap(),
             reactor_update_channel: None,
         })
         .expect("Failed to send publisher connected message");
@@ -505,7 +879,9 @@ async fn audio_notification_received_when_publisher_sends_audio() {
         .send(RtmpEndpointPublisherMessage::NewPublisherConnected {
             stream_id: StreamId("test".to_string()),
             stream_key: "abc".to_string(),
+            rtmp_app: "app".to_string(),
             connection_id: ConnectionId("connection".to_string()),
+            remote_address: "127.0.0.1:1234".parse().unwrap(),
             reactor_update_channel: None,
         })
         .expect("Failed to send publisher connected message");
@@ -562,6 +938,7 @@ fn stream_started_notification_passed_as_input_does_not_get_passed_as_output() {
             content: MediaNotificationContent::NewIncomingStream {
                 stream_name: "name".to_string(),
             },
+            tags: Vec::new(),
         });
 }
 
@@ -575,6 +952,7 @@ fn stream_disconnected_notification_passed_as_input_does_not_get_passed_as_outpu
         .assert_media_not_passed_through(MediaNotification {
             stream_id: StreamId("test".to_string()),
             content: StreamDisconnected,
+            tags: Vec::new(),
         });
 }
 
@@ -590,6 +968,7 @@ fn metadata_notification_passed_as_input_does_not_get_passed_as_output() {
             content: MediaNotificationContent::Metadata {
                 data: HashMap::new(),
             },
+            tags: Vec::new(),
         });
 }
 
@@ -609,6 +988,7 @@ fn video_notification_passed_as_input_does_not_get_passed_as_output() {
                 is_keyframe: true,
                 is_sequence_header: true,
             },
+            tags: Vec::new(),
         });
 }
 
@@ -627,6 +1007,7 @@ fn audio_notification_passed_as_input_does_not_get_passed_as_output() {
                 timestamp: Duration::from_millis(5),
                 is_sequence_header: true,
             },
+            tags: Vec::new(),
         });
 }
 
@@ -660,6 +1041,7 @@ async fn reactor_queried_for_stream_key_when_approval_required() {
     publish_channel
         .send(RtmpEndpointPublisherMessage::PublisherRequiringApproval {
             stream_key: "ab123".to_string(),
+            rtmp_app: "app".to_string(),
             connection_id: ConnectionId("connection".to_string()),
             response_channel: sender,
         })
@@ -692,6 +1074,7 @@ async fn rejection_sent_when_reactor_says_stream_is_not_valid() {
     publish_channel
         .send(RtmpEndpointPublisherMessage::PublisherRequiringApproval {
             stream_key: "ab123".to_string(),
+            rtmp_app: "app".to_string(),
             connection_id: ConnectionId("connection".to_string()),
             response_channel: sender,
         })
@@ -704,6 +1087,7 @@ async fn rejection_sent_when_reactor_says_stream_is_not_valid() {
         .send(ReactorWorkflowUpdate {
             is_valid: false,
             routable_workflow_names: HashSet::new(),
+            definition_hash: None,
         })
         .expect("Failed to send reactor response");
 
@@ -726,6 +1110,7 @@ async fn approval_sent_when_reactor_says_stream_is_valid() {
     publish_channel
         .send(RtmpEndpointPublisherMessage::PublisherRequiringApproval {
             stream_key: "ab123".to_string(),
+            rtmp_app: "app".to_string(),
             connection_id: ConnectionId("connection".to_string()),
             response_channel: sender,
         })
@@ -738,6 +1123,7 @@ async fn approval_sent_when_reactor_says_stream_is_valid() {
         .send(ReactorWorkflowUpdate {
             is_valid: true,
             routable_workflow_names: HashSet::new(),
+            definition_hash: None,
         })
         .expect("Failed to send reactor response");
 
@@ -749,3 +1135,654 @@ async fn approval_sent_when_reactor_says_stream_is_valid() {
         response => panic!("Unexpected response: {:?}", response),
     }
 }
+
+#[tokio::test]
+async fn approval_required_requested_when_auth_url_specified() {
+    let definition = DefinitionBuilder::new()
+        .auth_url("http://localhost/auth")
+        .build();
+
+    let mut context = TestContext::new(definition).unwrap();
+    let request = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match request {
+        RtmpEndpointRequest::ListenForPublishers {
+            requires_registrant_approval,
+            ..
+        } => {
+            assert!(
+                requires_registrant_approval,
+                "Expected requires approval to be true"
+            );
+        }
+
+        request => panic!("Unexpected rtmp request seen: {:?}", request),
+    };
+}
+
+#[tokio::test]
+async fn auth_service_receives_publisher_details() {
+    let (url, mut requests) = start_auth_server(StatusCode::OK);
+    let definition = DefinitionBuilder::new().app("*").auth_url(&url).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let publish_channel = context.accept_registration().await;
+
+    let _receiver = send_publisher_requiring_approval(&publish_channel);
+    context.step_context.execute_pending_notifications().await;
+
+    let body = test_utils::expect_mpsc_response(&mut requests).await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        json["connection_id"], "connection",
+        "Unexpected connection id"
+    );
+    assert_eq!(json["rtmp_app"], "app", "Unexpected rtmp app");
+    assert_eq!(json["stream_key"], "ab123", "Unexpected stream key");
+}
+
+#[tokio::test]
+async fn approval_sent_when_auth_service_allows_publisher() {
+    let (url, _requests) = start_auth_server(StatusCode::OK);
+    let definition = DefinitionBuilder::new().auth_url(&url).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let publish_channel = context.accept_registration().await;
+
+    let receiver = send_publisher_requiring_approval(&publish_channel);
+    context.step_context.execute_pending_notifications().await;
+
+    let response = test_utils::expect_oneshot_response(receiver).await;
+    match response {
+        ValidationResponse::Approve {
+            reactor_update_channel: None,
+        } => (),
+        response => panic!("Unexpected response: {:?}", response),
+    }
+}
+
+#[tokio::test]
+async fn rejection_sent_when_auth_service_denies_publisher() {
+    let (url, _requests) = start_auth_server(StatusCode::FORBIDDEN);
+    let definition = DefinitionBuilder::new().auth_url(&url).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let publish_channel = context.accept_registration().await;
+
+    let receiver = send_publisher_requiring_approval(&publish_channel);
+    context.step_context.execute_pending_notifications().await;
+
+    let response = test_utils::expect_oneshot_response(receiver).await;
+    match response {
+        ValidationResponse::Reject => (),
+        response => panic!("Unexpected response: {:?}", response),
+    }
+}
+
+#[tokio::test]
+async fn rejection_sent_when_auth_service_is_unreachable() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/auth", listener.local_addr().unwrap());
+    drop(listener);
+
+    let definition = DefinitionBuilder::new().auth_url(&url).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let publish_channel = context.accept_registration().await;
+
+    let receiver = send_publisher_requiring_approval(&publish_channel);
+    context.step_context.execute_pending_notifications().await;
+
+    let response = test_utils::expect_oneshot_response(receiver).await;
+    match response {
+        ValidationResponse::Reject => (),
+        response => panic!("Unexpected response: {:?}", response),
+    }
+}
+
+#[tokio::test]
+async fn reactor_queried_after_auth_service_allows_publisher() {
+    let (url, _requests) = start_auth_server(StatusCode::OK);
+    let definition = DefinitionBuilder::new()
+        .auth_url(&url)
+        .reactor_name("abc")
+        .build();
+
+    let mut context = TestContext::new(definition).unwrap();
+    let publish_channel = context.accept_registration().await;
+
+    let _receiver = send_publisher_requiring_approval(&publish_channel);
+    context.step_context.execute_pending_notifications().await;
+
+    let request = test_utils::expect_mpsc_response(&mut context.reactor_manager).await;
+    match request {
+        ReactorManagerRequest::CreateWorkflowForStreamName { stream_name, .. } => {
+            assert_eq!(&stream_name, "ab123", "Unexpected stream name");
+        }
+
+        request => panic!("Unexpected request received: {:?}", request),
+    }
+}
+
+#[tokio::test]
+async fn reactor_not_queried_when_auth_service_denies_publisher() {
+    let (url, _requests) = start_auth_server(StatusCode::FORBIDDEN);
+    let definition = DefinitionBuilder::new()
+        .auth_url(&url)
+        .reactor_name("abc")
+        .build();
+
+    let mut context = TestContext::new(definition).unwrap();
+    let publish_channel = context.accept_registration().await;
+
+    let receiver = send_publisher_requiring_approval(&publish_channel);
+    context.step_context.execute_pending_notifications().await;
+
+    let response = test_utils::expect_oneshot_response(receiver).await;
+    match response {
+        ValidationResponse::Reject => (),
+        response => panic!("Unexpected response: {:?}", response),
+    }
+
+    test_utils::expect_mpsc_timeout(&mut context.reactor_manager).await;
+}
+
+#[tokio::test]
+async fn endpoint_dropping_registration_sets_status_to_error_when_no_reconnects_allowed() {
+    let definition = DefinitionBuilder::new().build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    drop(channel);
+    context.step_context.execute_pending_notifications().await;
+
+    let status = context.step_context.step.get_status();
+    match status {
+        StepStatus::Error { message: _ } => (),
+        _ => panic!("Unexpected status: {:?}", status),
+    }
+}
+
+#[tokio::test]
+async fn endpoint_dropping_registration_causes_re_registration_when_reconnects_allowed() {
+    let definition = DefinitionBuilder::new().reconnect_attempts(3).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    drop(channel);
+    context.step_context.execute_pending_notifications().await;
+
+    let request = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match request {
+        RtmpEndpointRequest::ListenForPublishers { .. } => (),
+        request => panic!("Unexpected rtmp request seen: {:?}", request),
+    }
+
+    let status = context.step_context.step.get_status();
+    assert_eq!(
+        status,
+        &StepStatus::Reconnecting { attempt: 1 },
+        "Unexpected step status"
+    );
+}
+
+#[tokio::test]
+async fn successful_re_registration_sets_status_back_to_active() {
+    let definition = DefinitionBuilder::new().reconnect_attempts(3).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    drop(channel);
+    context.step_context.execute_pending_notifications().await;
+    let _channel = context.accept_registration().await;
+
+    let status = context.step_context.step.get_status();
+    assert_eq!(status, &StepStatus::Active, "Unexpected step status");
+}
+
+#[tokio::test]
+async fn error_status_set_after_all_reconnect_attempts_exhausted() {
+    let definition = DefinitionBuilder::new().reconnect_attempts(1).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    drop(channel);
+    context.step_context.execute_pending_notifications().await;
+
+    // Drop the re-registration attempt as well
+    let request = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    drop(request);
+    context.step_context.execute_pending_notifications().await;
+
+    let status = context.step_context.step.get_status();
+    match status {
+        StepStatus::Error { message: _ } => (),
+        _ => panic!("Unexpected status: {:?}", status),
+    }
+}
+
+#[tokio::test]
+async fn no_re_registration_attempted_after_shutdown() {
+    let definition = DefinitionBuilder::new().reconnect_attempts(3).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    context.step_context.step.shutdown();
+    let _ = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await; // remove request
+
+    drop(channel);
+    context.step_context.execute_pending_notifications().await;
+
+    test_utils::expect_mpsc_timeout(&mut context.rtmp_endpoint).await;
+    assert_eq!(
+        context.step_context.step.get_status(),
+        &StepStatus::Shutdown,
+        "Unexpected step status"
+    );
+}
+
+#[tokio::test]
+async fn step_shutting_down_until_endpoint_removes_registration() {
+    let definition = DefinitionBuilder::new().build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    context.step_context.step.shutdown();
+    let request = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match request {
+        RtmpEndpointRequest::RemoveRegistration { .. } => (),
+        request => panic!("Unexpected rtmp request seen: {:?}", request),
+    }
+
+    assert_eq!(
+        context.step_context.step.get_status(),
+        &StepStatus::ShuttingDown,
+        "Unexpected step status"
+    );
+
+    drop(channel);
+    context.step_context.execute_pending_notifications().await;
+
+    assert_eq!(
+        context.step_context.step.get_status(),
+        &StepStatus::Shutdown,
+        "Unexpected step status"
+    );
+}
+
+#[tokio::test]
+async fn step_immediately_shutdown_when_not_registered() {
+    let definition = DefinitionBuilder::new().reconnect_attempts(3).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    drop(channel);
+    context.step_context.execute_pending_notifications().await;
+
+    context.step_context.step.shutdown();
+    assert_eq!(
+        context.step_context.step.get_status(),
+        &StepStatus::Shutdown,
+        "Unexpected step status"
+    );
+}
+
+#[tokio::test]
+async fn publisher_disconnection_raised_when_endpoint_drops_registration() {
+    let definition = DefinitionBuilder::new().reconnect_attempts(3).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    channel
+        .send(RtmpEndpointPublisherMessage::NewPublisherConnected {
+            stream_id: StreamId("test".to_string()),
+            stream_key: "abc".to_string(),
+            rtmp_app: "app".to_string(),
+            connection_id: ConnectionId("connection".to_string()),
+            remote_address: "127.0.0.1:1234".parse().unwrap(),
+            reactor_update_channel: None,
+        })
+        .expect("Failed to send publisher connected message");
+
+    context.step_context.execute_pending_notifications().await;
+
+    let mut outputs = StepOutputs::new();
+    let mut inputs = StepInputs::new();
+    inputs
+        .notifications
+        .push(Box::new(FutureResult::RtmpEndpointDroppedRegistration));
+
+    context.step_context.step.execute(&mut inputs, &mut outputs);
+
+    assert_eq!(outputs.media.len(), 1, "Unexpected number of media outputs");
+    assert_eq!(
+        outputs.media[0],
+        MediaNotification {
+            stream_id: StreamId("test".to_string()),
+            content: StreamDisconnected,
+            tags: Vec::new(),
+        },
+        "Unexpected media output"
+    );
+}
+
+#[tokio::test]
+async fn error_if_media_timeout_is_not_a_positive_number() {
+    for value in ["abc", "0", "-5"] {
+        let mut definition = DefinitionBuilder::new().build();
+        definition.parameters.insert(
+            MEDIA_TIMEOUT_PROPERTY_NAME.to_string(),
+            Some(value.to_string()),
+        );
+
+        if TestContext::new(definition).is_ok() {
+            panic!("Expected failure for media timeout of '{}'", value);
+        }
+    }
+}
+
+#[test]
+fn validation_fails_for_non_numeric_port() {
+    let mut definition = DefinitionBuilder::new().build();
+    definition
+        .parameters
+        .insert(PORT_PROPERTY_NAME.to_string(), Some("abc".to_string()));
+
+    let generator = RtmpReceiverStepGenerator::new(unbounded_channel().0, unbounded_channel().0);
+    assert!(generator.validate(&definition).is_err());
+}
+
+#[test]
+fn validation_does_not_register_with_rtmp_endpoint() {
+    let definition = DefinitionBuilder::new().build();
+    let (rtmp_sender, mut rtmp_receiver) = unbounded_channel();
+    let generator = RtmpReceiverStepGenerator::new(rtmp_sender, unbounded_channel().0);
+
+    generator.validate(&definition).unwrap();
+    assert!(
+        rtmp_receiver.try_recv().is_err(),
+        "Expected no rtmp endpoint requests"
+    );
+}
+
+#[tokio::test]
+async fn max_connects_per_minute_passed_to_endpoint_when_specified() {
+    let definition = DefinitionBuilder::new().max_connects_per_minute(5).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let request = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match request {
+        RtmpEndpointRequest::ListenForPublishers {
+            max_connects_per_minute,
+            ..
+        } => {
+            assert_eq!(
+                max_connects_per_minute,
+                Some(5),
+                "Unexpected max connects per minute"
+            );
+        }
+
+        request => panic!("Unexpected rtmp request seen: {:?}", request),
+    };
+}
+
+#[tokio::test]
+async fn no_connection_rate_limit_when_max_connects_per_minute_not_specified() {
+    let definition = DefinitionBuilder::new().build();
+    let mut context = TestContext::new(definition).unwrap();
+    let request = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match request {
+        RtmpEndpointRequest::ListenForPublishers {
+            max_connects_per_minute,
+            ..
+        } => {
+            assert_eq!(
+                max_connects_per_minute, None,
+                "Unexpected max connects per minute"
+            );
+        }
+
+        request => panic!("Unexpected rtmp request seen: {:?}", request),
+    };
+}
+
+#[tokio::test]
+async fn error_if_max_connects_per_minute_is_not_a_positive_number() {
+    for value in ["abc", "0", "-5"] {
+        let mut definition = DefinitionBuilder::new().build();
+        definition.parameters.insert(
+            MAX_CONNECTS_PER_MINUTE_PROPERTY_NAME.to_string(),
+            Some(value.to_string()),
+        );
+
+        if TestContext::new(definition).is_ok() {
+            panic!(
+                "Expected failure for max connects per minute of '{}'",
+                value
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn stream_disconnected_raised_when_no_media_received_within_timeout() {
+    let definition = DefinitionBuilder::new().media_timeout_ms(50).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    send_publisher_connected(&channel);
+    context.step_context.execute_pending_notifications().await;
+
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    context.step_context.execute_pending_notifications().await;
+
+    assert_eq!(
+        context.step_context.media_outputs.len(),
+        1,
+        "Unexpected number of media outputs"
+    );
+
+    let media = &context.step_context.media_outputs[0];
+    assert_eq!(&media.stream_id.0, "test", "Unexpected stream id");
+    assert_eq!(
+        media.content, StreamDisconnected,
+        "Unexpected media content"
+    );
+}
+
+#[tokio::test]
+async fn no_stream_disconnected_raised_when_media_received_within_timeout() {
+    let definition = DefinitionBuilder::new().media_timeout_ms(200).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    send_publisher_connected(&channel);
+    context.step_context.execute_pending_notifications().await;
+
+    tokio::time::sleep(Duration::from_millis(120)).await;
+    send_video(&channel);
+    context.step_context.execute_pending_notifications().await;
+
+    tokio::time::sleep(Duration::from_millis(120)).await;
+    context.step_context.execute_pending_notifications().await;
+
+    assert!(
+        context
+            .step_context
+            .media_outputs
+            .iter()
+            .all(|media| media.content != StreamDisconnected),
+        "Expected no stream disconnected notification"
+    );
+}
+
+#[tokio::test]
+async fn no_stream_disconnected_raised_when_media_timeout_not_specified() {
+    let definition = DefinitionBuilder::new().build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    send_publisher_connected(&channel);
+    context.step_context.execute_pending_notifications().await;
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    context.step_context.execute_pending_notifications().await;
+
+    assert!(
+        context.step_context.media_outputs.is_empty(),
+        "Expected no media outputs"
+    );
+}
+
+#[tokio::test]
+async fn timed_out_stream_announced_as_new_stream_when_media_resumes() {
+    let definition = DefinitionBuilder::new().media_timeout_ms(50).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    send_publisher_connected(&channel);
+    context.step_context.execute_pending_notifications().await;
+
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    context.step_context.execute_pending_notifications().await;
+
+    send_video(&channel);
+    context.step_context.execute_pending_notifications().await;
+
+    assert_eq!(
+        context.step_context.media_outputs.len(),
+        2,
+        "Unexpected number of media outputs"
+    );
+
+    match &context.step_context.media_outputs[0].content {
+        MediaNotificationContent::NewIncomingStream { stream_name } => {
+            assert_eq!(stream_name, "abc", "Unexpected stream name");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+
+    match &context.step_context.media_outputs[1].content {
+        MediaNotificationContent::Video { .. } => (),
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[tokio::test]
+async fn sequence_headers_and_metadata_raised_again_when_timed_out_stream_resumes() {
+    let definition = DefinitionBuilder::new().media_timeout_ms(50).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    send_publisher_connected(&channel);
+    channel
+        .send(RtmpEndpointPublisherMessage::StreamMetadataChanged {
+            publisher: ConnectionId("connection".to_string()),
+            metadata: StreamMetadata::new(),
+        })
+        .expect("Failed to send metadata message");
+
+    channel
+        .send(RtmpEndpointPublisherMessage::NewVideoData {
+            publisher: ConnectionId("connection".to_string()),
+            data: Bytes::from(vec![4, 5, 6]),
+            codec: VideoCodec::H264,
+            timestamp: RtmpTimestamp::new(0),
+            is_keyframe: true,
+            is_sequence_header: true,
+            composition_time_offset: 0,
+        })
+        .expect("Failed to send video sequence header");
+
+    channel
+        .send(RtmpEndpointPublisherMessage::NewAudioData {
+            publisher: ConnectionId("connection".to_string()),
+            data: Bytes::from(vec![7, 8, 9]),
+            codec: AudioCodec::Aac,
+            timestamp: RtmpTimestamp::new(0),
+            is_sequence_header: true,
+        })
+        .expect("Failed to send audio sequence header");
+
+    context.step_context.execute_pending_notifications().await;
+
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    context.step_context.execute_pending_notifications().await;
+
+    send_video(&channel);
+    context.step_context.execute_pending_notifications().await;
+
+    let outputs = &context.step_context.media_outputs;
+    assert_eq!(outputs.len(), 5, "Unexpected number of media outputs");
+
+    match &outputs[0].content {
+        MediaNotificationContent::NewIncomingStream { .. } => (),
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+
+    match &outputs[1].content {
+        MediaNotificationContent::Metadata { .. } => (),
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+
+    match &outputs[2].content {
+        MediaNotificationContent::Video {
+            is_sequence_header: true,
+            data,
+            ..
+        } => assert_eq!(data, &vec![4, 5, 6], "Unexpected video data"),
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+
+    match &outputs[3].content {
+        MediaNotificationContent::Audio {
+            is_sequence_header: true,
+            data,
+            ..
+        } => assert_eq!(data, &vec![7, 8, 9], "Unexpected audio data"),
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+
+    match &outputs[4].content {
+        MediaNotificationContent::Video {
+            is_sequence_header: false,
+            ..
+        } => (),
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[tokio::test]
+async fn publisher_stopping_after_timeout_does_not_raise_second_disconnection() {
+    let definition = DefinitionBuilder::new().media_timeout_ms(50).build();
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    send_publisher_connected(&channel);
+    context.step_context.execute_pending_notifications().await;
+
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    context.step_context.execute_pending_notifications().await;
+
+    channel
+        .send(RtmpEndpointPublisherMessage::PublishingStopped {
+            connection_id: ConnectionId("connection".to_string()),
+        })
+        .expect("Failed to send publishing stopped message");
+
+    context.step_context.execute_pending_notifications().await;
+
+    assert!(
+        context.step_context.media_outputs.is_empty(),
+        "Expected no media outputs"
+    );
+}
+
+#[test]
+fn reconnect_delay_grows_exponentially_and_is_capped() {
+    let base = Duration::from_millis(100);
+
+    assert_eq!(get_reconnect_delay(base, 1), Duration::from_millis(100));
+    assert_eq!(get_reconnect_delay(base, 2), Duration::from_millis(200));
+    assert_eq!(get_reconnect_delay(base, 3), Duration::from_millis(400));
+    assert_eq!(get_reconnect_delay(base, 100), MAX_RECONNECT_DELAY);
+}
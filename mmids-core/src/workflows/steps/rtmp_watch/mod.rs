@@ -10,6 +10,33 @@
 //! If an exact stream key is configured, then the first media stream that comes into the step will
 //! be surfaced on that stream key.
 //!
+//! Multiple exact stream keys can be specified as a comma delimited list.  In that case each stream
+//! key is registered separately, and media streams are only surfaced on the stream key that
+//! matches their stream name.
+//!
+//! When `max_buffer_frames` is specified, the number of media messages waiting to be read by the
+//! RTMP endpoint is tracked for each stream key registration.  Once that backlog reaches the
+//! configured size, non-keyframe video frames are dropped until a keyframe arrives after the
+//! endpoint has caught up.  Keyframes, sequence headers, audio, and metadata are never dropped.
+//!
+//! When `max_watchers` is specified, the RTMP endpoint disconnects any client that attempts to
+//! watch a stream key that already has that many watchers connected.
+//!
+//! Only one media stream can be surfaced on a stream key at a time.  If a second stream arrives
+//! for a stream key that's already in use (e.g. any new stream when a single exact stream key is
+//! configured), it is rejected with a warning and its media is dropped.  The step can instead be
+//! configured to replace the existing stream, in which case the existing stream's media is
+//! dropped from then on.
+//!
+//! When the `start_on_keyframe` flag is specified, the RTMP endpoint withholds audio and video
+//! from each new watcher until the next keyframe, so the first frame a watcher receives is a
+//! keyframe (preceded by the latest sequence headers).
+//!
+//! When the `request_keyframes` flag is specified, the step asks the steps before it for a
+//! keyframe of the stream whenever a new watcher joins its stream key, so watchers of streams
+//! with long keyframe intervals don't have to wait as long for video.  Whether a keyframe is
+//! actually produced depends on whether any earlier step is able to act on the request.
+//!
 //! All media notifications that are passed into this step are passed onto the next step.
 
 #[cfg(test)]
@@ -25,15 +52,18 @@ use crate::reactors::manager::ReactorManagerRequest;
 use crate::reactors::ReactorWorkflowUpdate;
 use crate::utils::hash_map_to_stream_metadata;
 use crate::workflows::definitions::WorkflowStepDefinition;
-use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::factory::{StepGenerator, StepKind};
 use crate::workflows::steps::{
-    StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus, WorkflowStep,
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
 };
 use crate::workflows::{MediaNotification, MediaNotificationContent};
 use crate::StreamId;
 use futures::FutureExt;
 use rml_rtmp::time::RtmpTimestamp;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use thiserror::Error as ThisError;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::oneshot::Sender;
@@ -44,8 +74,15 @@ pub const APP_PROPERTY_NAME: &'static str = "rtmp_app";
 pub const STREAM_KEY_PROPERTY_NAME: &'static str = "stream_key";
 pub const IP_ALLOW_PROPERTY_NAME: &'static str = "allow_ips";
 pub const IP_DENY_PROPERTY_NAME: &'static str = "deny_ips";
+pub const IP_MODE_PROPERTY_NAME: &'static str = "ip_mode";
+pub const IP_MODE_ALLOW_WITH_EXCEPTIONS: &'static str = "allow_with_exceptions";
 pub const RTMPS_FLAG: &'static str = "rtmps";
 pub const REACTOR_NAME: &'static str = "reactor";
+pub const MAX_BUFFER_FRAMES_PROPERTY_NAME: &'static str = "max_buffer_frames";
+pub const MAX_WATCHERS_PROPERTY_NAME: &'static str = "max_watchers";
+pub const START_ON_KEYFRAME_FLAG: &'static str = "start_on_keyframe";
+pub const REQUEST_KEYFRAMES_FLAG: &'static str = "request_keyframes";
+pub const ON_DUPLICATE_STREAM_PROPERTY_NAME: &'static str = "on_duplicate_stream";
 
 /// Generates new rtmp watch workflow step instances based on a given step definition.
 pub struct RtmpWatchStepGenerator {
@@ -60,18 +97,39 @@ struct StreamWatchers {
     _reactor_cancel_channel: Option<UnboundedSender<()>>,
 }
 
+struct WatchRegistration {
+    stream_key: StreamKeyRegistration,
+    media_channel: UnboundedSender<RtmpEndpointMediaMessage>,
+    media_backlog: Option<Arc<AtomicUsize>>,
+    dropped_frame_count: usize,
+    is_dropping_frames: bool,
+}
+
+/// What to do when a new stream arrives for a stream key another stream is already surfaced on
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DuplicateStreamAction {
+    /// Keep the existing stream and drop the new stream's media
+    Reject,
+
+    /// Drop the existing stream's media and surface the new stream instead
+    Replace,
+}
+
 struct RtmpWatchStep {
     definition: WorkflowStepDefinition,
     port: u16,
     rtmp_app: String,
-    stream_key: StreamKeyRegistration,
+    registrations: Vec<WatchRegistration>,
+    successful_registration_count: usize,
     reactor_name: Option<String>,
+    max_buffer_frames: Option<usize>,
     status: StepStatus,
     rtmp_endpoint_sender: UnboundedSender<RtmpEndpointRequest>,
     reactor_manager: UnboundedSender<ReactorManagerRequest>,
-    media_channel: UnboundedSender<RtmpEndpointMediaMessage>,
     stream_id_to_name_map: HashMap<StreamId, String>,
     stream_watchers: HashMap<String, StreamWatchers>,
+    duplicate_stream_action: DuplicateStreamAction,
+    request_keyframes: bool,
 }
 
 impl StepFutureResult for RtmpWatchStepFutureResult {}
@@ -103,6 +161,20 @@ enum RtmpWatchStepFutureResult {
     },
 }
 
+struct StepParameters {
+    use_rtmps: bool,
+    start_on_keyframe: bool,
+    request_keyframes: bool,
+    port: u16,
+    app: String,
+    stream_keys: Vec<String>,
+    ip_restriction: IpRestriction,
+    reactor_name: Option<String>,
+    max_buffer_frames: Option<usize>,
+    max_watchers: Option<usize>,
+    duplicate_stream_action: DuplicateStreamAction,
+}
+
 #[derive(ThisError, Debug)]
 enum StepStartupError {
     #[error(
@@ -126,11 +198,57 @@ enum StepStartupError {
     InvalidIpAddressSpecified(#[from] IpAddressParseError),
 
     #[error(
-        "Both {} and {} were specified, but only one is allowed",
+        "Both {} and {} were specified, but only one is allowed unless {}={}",
         IP_ALLOW_PROPERTY_NAME,
-        IP_DENY_PROPERTY_NAME
+        IP_DENY_PROPERTY_NAME,
+        IP_MODE_PROPERTY_NAME,
+        IP_MODE_ALLOW_WITH_EXCEPTIONS
     )]
     BothDenyAndAllowIpRestrictionsSpecified,
+
+    #[error(
+        "Invalid {} value of '{0}' specified.  Only '{}' is supported",
+        IP_MODE_PROPERTY_NAME,
+        IP_MODE_ALLOW_WITH_EXCEPTIONS
+    )]
+    InvalidIpModeSpecified(String),
+
+    #[error(
+        "The '{}' ip mode requires a non-empty {} parameter",
+        IP_MODE_ALLOW_WITH_EXCEPTIONS,
+        IP_ALLOW_PROPERTY_NAME
+    )]
+    NoAllowedIpsForIpMode,
+
+    #[error(
+        "A wildcard stream key of '*' cannot be combined with other stream keys in '{}'",
+        STREAM_KEY_PROPERTY_NAME
+    )]
+    WildcardInStreamKeyList,
+
+    #[error(
+        "Invalid {} value of '{0}' specified.  A positive number is required",
+        MAX_BUFFER_FRAMES_PROPERTY_NAME
+    )]
+    InvalidMaxBufferFrames(String),
+
+    #[error(
+        "Invalid {} value of '{0}' specified.  A non-negative number is required",
+        MAX_WATCHERS_PROPERTY_NAME
+    )]
+    InvalidMaxWatchers(String),
+
+    #[error(
+        "Invalid {} value of '{0}' specified.  Either 'reject' or 'replace' is required",
+        ON_DUPLICATE_STREAM_PROPERTY_NAME
+    )]
+    InvalidDuplicateStreamAction(String),
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
 }
 
 impl RtmpWatchStepGenerator {
@@ -147,110 +265,236 @@ impl RtmpWatchStepGenerator {
 
 impl StepGenerator for RtmpWatchStepGenerator {
     fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
-        let use_rtmps = match definition.parameters.get(RTMPS_FLAG) {
-            Some(_) => true,
-            None => false,
+        let StepParameters {
+            use_rtmps,
+            start_on_keyframe,
+            request_keyframes,
+            port,
+            app,
+            stream_keys,
+            ip_restriction,
+            reactor_name,
+            max_buffer_frames,
+            max_watchers,
+            duplicate_stream_action,
+        } = parse_parameters(&definition)?;
+
+        // Registration requests are sent without waiting for a response, so a closed endpoint
+        // would otherwise leave the step waiting forever
+        if self.rtmp_endpoint_sender.is_closed() {
+            return Err(StepCreationError::Fatal(
+                "The RTMP endpoint is no longer running".into(),
+            ));
+        }
+
+        let mut registrations = Vec::new();
+        let mut futures =
+            vec![notify_on_reactor_manager_close(self.reactor_manager.clone()).boxed()];
+        for stream_key in stream_keys {
+            let stream_key = StreamKeyRegistration::from_configured_key(stream_key);
+
+            let (media_sender, media_receiver) = unbounded_channel();
+            let (notification_sender, notification_receiver) = unbounded_channel();
+            let media_backlog = max_buffer_frames.map(|_| Arc::new(AtomicUsize::new(0)));
+            let _ = self
+                .rtmp_endpoint_sender
+                .send(RtmpEndpointRequest::ListenForWatchers {
+                    port,
+                    rtmp_app: app.to_string(),
+                    rtmp_stream_key: stream_key.clone(),
+                    media_channel: media_receiver,
+                    media_backlog: media_backlog.clone(),
+                    notification_channel: notification_sender,
+                    ip_restrictions: ip_restriction.clone(),
+                    use_tls: use_rtmps,
+                    requires_registrant_approval: reactor_name.is_some(),
+                    max_watchers,
+                    start_on_keyframe,
+                });
+
+            futures.push(wait_for_endpoint_notification(notification_receiver).boxed());
+            registrations.push(WatchRegistration {
+                stream_key,
+                media_channel: media_sender,
+                media_backlog,
+                dropped_frame_count: 0,
+                is_dropping_frames: false,
+            });
+        }
+
+        let step = RtmpWatchStep {
+            definition: definition.clone(),
+            status: StepStatus::Created,
+            port,
+            rtmp_app: app.to_string(),
+            rtmp_endpoint_sender: self.rtmp_endpoint_sender.clone(),
+            reactor_manager: self.reactor_manager.clone(),
+            registrations,
+            successful_registration_count: 0,
+            stream_id_to_name_map: HashMap::new(),
+            reactor_name,
+            max_buffer_frames,
+            stream_watchers: HashMap::new(),
+            duplicate_stream_action,
+            request_keyframes,
         };
 
-        let port = match definition.parameters.get(PORT_PROPERTY_NAME) {
-            Some(Some(value)) => match value.parse::<u16>() {
-                Ok(num) => num,
-                Err(_) => {
-                    return Err(Box::new(StepStartupError::InvalidPortSpecified(
-                        value.clone(),
-                    )));
-                }
-            },
+        Ok((Box::new(step), futures))
+    }
 
-            _ => {
-                if use_rtmps {
-                    443
-                } else {
-                    1935
-                }
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_parameters(definition)?;
+        Ok(())
+    }
+
+    fn kind(&self) -> StepKind {
+        StepKind::Sink
+    }
+}
+
+fn parse_parameters(
+    definition: &WorkflowStepDefinition,
+) -> Result<StepParameters, StepStartupError> {
+    let use_rtmps = match definition.parameters.get(RTMPS_FLAG) {
+        Some(_) => true,
+        None => false,
+    };
+
+    let start_on_keyframe = match definition.parameters.get(START_ON_KEYFRAME_FLAG) {
+        Some(_) => true,
+        None => false,
+    };
+
+    let request_keyframes = match definition.parameters.get(REQUEST_KEYFRAMES_FLAG) {
+        Some(_) => true,
+        None => false,
+    };
+
+    let port = match definition.parameters.get(PORT_PROPERTY_NAME) {
+        Some(Some(value)) => match value.parse::<u16>() {
+            Ok(num) => num,
+            Err(_) => {
+                return Err(StepStartupError::InvalidPortSpecified(value.clone()));
             }
-        };
+        },
 
-        let app = match definition.parameters.get(APP_PROPERTY_NAME) {
-            Some(Some(x)) => x.trim(),
-            _ => return Err(Box::new(StepStartupError::NoRtmpAppSpecified)),
-        };
+        _ => {
+            if use_rtmps {
+                443
+            } else {
+                1935
+            }
+        }
+    };
 
-        let stream_key = match definition.parameters.get(STREAM_KEY_PROPERTY_NAME) {
-            Some(Some(x)) => x.trim(),
-            _ => return Err(Box::new(StepStartupError::NoStreamKeySpecified)),
-        };
+    let app = match definition.parameters.get(APP_PROPERTY_NAME) {
+        Some(Some(x)) => x.trim().to_string(),
+        _ => return Err(StepStartupError::NoRtmpAppSpecified),
+    };
 
-        let stream_key = if stream_key == "*" {
-            StreamKeyRegistration::Any
-        } else {
-            StreamKeyRegistration::Exact(stream_key.to_string())
-        };
+    let stream_keys = definition
+        .get_list_parameter(STREAM_KEY_PROPERTY_NAME)
+        .unwrap_or_default();
 
-        let allowed_ips = match definition.parameters.get(IP_ALLOW_PROPERTY_NAME) {
-            Some(Some(value)) => IpAddress::parse_comma_delimited_list(Some(value))?,
-            _ => Vec::new(),
-        };
+    if stream_keys.is_empty() {
+        return Err(StepStartupError::NoStreamKeySpecified);
+    }
 
-        let denied_ips = match definition.parameters.get(IP_DENY_PROPERTY_NAME) {
-            Some(Some(value)) => IpAddress::parse_comma_delimited_list(Some(value))?,
-            _ => Vec::new(),
-        };
+    if stream_keys.len() > 1 && stream_keys.iter().any(|key| key == "*") {
+        return Err(StepStartupError::WildcardInStreamKeyList);
+    }
+
+    let allowed_ips = match definition.parameters.get(IP_ALLOW_PROPERTY_NAME) {
+        Some(Some(value)) => IpAddress::parse_comma_delimited_list(Some(value))?,
+        _ => Vec::new(),
+    };
+
+    let denied_ips = match definition.parameters.get(IP_DENY_PROPERTY_NAME) {
+        Some(Some(value)) => IpAddress::parse_comma_delimited_list(Some(value))?,
+        _ => Vec::new(),
+    };
+
+    let ip_restriction = match definition.parameters.get(IP_MODE_PROPERTY_NAME) {
+        Some(Some(mode)) if mode == IP_MODE_ALLOW_WITH_EXCEPTIONS => {
+            if allowed_ips.is_empty() {
+                return Err(StepStartupError::NoAllowedIpsForIpMode);
+            }
+
+            IpRestriction::AllowWithExceptions {
+                allow: allowed_ips,
+                deny: denied_ips,
+            }
+        }
 
-        let ip_restriction = match (allowed_ips.len() > 0, denied_ips.len() > 0) {
+        Some(Some(mode)) => return Err(StepStartupError::InvalidIpModeSpecified(mode.clone())),
+
+        _ => match (allowed_ips.len() > 0, denied_ips.len() > 0) {
             (true, true) => {
-                return Err(Box::new(
-                    StepStartupError::BothDenyAndAllowIpRestrictionsSpecified,
-                ));
+                return Err(StepStartupError::BothDenyAndAllowIpRestrictionsSpecified);
             }
             (true, false) => IpRestriction::Allow(allowed_ips),
             (false, true) => IpRestriction::Deny(denied_ips),
             (false, false) => IpRestriction::None,
-        };
+        },
+    };
 
-        let reactor_name = match definition.parameters.get(REACTOR_NAME) {
-            Some(Some(value)) => Some(value.clone()),
-            _ => None,
-        };
+    let reactor_name = match definition.parameters.get(REACTOR_NAME) {
+        Some(Some(value)) => Some(value.clone()),
+        _ => None,
+    };
 
-        let (media_sender, media_receiver) = unbounded_channel();
+    let max_buffer_frames = match definition.parameters.get(MAX_BUFFER_FRAMES_PROPERTY_NAME) {
+        Some(Some(value)) => match value.parse::<usize>() {
+            Ok(num) if num > 0 => Some(num),
+            _ => {
+                return Err(StepStartupError::InvalidMaxBufferFrames(value.clone()));
+            }
+        },
 
-        let step = RtmpWatchStep {
-            definition: definition.clone(),
-            status: StepStatus::Created,
-            port,
-            rtmp_app: app.to_string(),
-            rtmp_endpoint_sender: self.rtmp_endpoint_sender.clone(),
-            reactor_manager: self.reactor_manager.clone(),
-            media_channel: media_sender,
-            stream_key,
-            stream_id_to_name_map: HashMap::new(),
-            reactor_name,
-            stream_watchers: HashMap::new(),
-        };
+        _ => None,
+    };
 
-        let (notification_sender, notification_receiver) = unbounded_channel();
-        let _ = step
-            .rtmp_endpoint_sender
-            .send(RtmpEndpointRequest::ListenForWatchers {
-                port: step.port,
-                rtmp_app: step.rtmp_app.clone(),
-                rtmp_stream_key: step.stream_key.clone(),
-                media_channel: media_receiver,
-                notification_channel: notification_sender,
-                ip_restrictions: ip_restriction,
-                use_tls: use_rtmps,
-                requires_registrant_approval: step.reactor_name.is_some(),
-            });
+    // Zero is treated the same as not specifying a limit
+    let max_watchers = match definition.parameters.get(MAX_WATCHERS_PROPERTY_NAME) {
+        Some(Some(value)) => match value.parse::<usize>() {
+            Ok(0) => None,
+            Ok(num) => Some(num),
+            Err(_) => {
+                return Err(StepStartupError::InvalidMaxWatchers(value.clone()));
+            }
+        },
 
-        Ok((
-            Box::new(step),
-            vec![
-                wait_for_endpoint_notification(notification_receiver).boxed(),
-                notify_on_reactor_manager_close(self.reactor_manager.clone()).boxed(),
-            ],
-        ))
-    }
+        _ => None,
+    };
+
+    let duplicate_stream_action = match definition.parameters.get(ON_DUPLICATE_STREAM_PROPERTY_NAME)
+    {
+        Some(Some(value)) => match value.as_str() {
+            "reject" => DuplicateStreamAction::Reject,
+            "replace" => DuplicateStreamAction::Replace,
+            _ => {
+                return Err(StepStartupError::InvalidDuplicateStreamAction(
+                    value.clone(),
+                ));
+            }
+        },
+
+        _ => DuplicateStreamAction::Reject,
+    };
+
+    Ok(StepParameters {
+        use_rtmps,
+        start_on_keyframe,
+        request_keyframes,
+        port,
+        app,
+        stream_keys,
+        ip_restriction,
+        reactor_name,
+        max_buffer_frames,
+        max_watchers,
+        duplicate_stream_action,
+    })
 }
 
 impl RtmpWatchStep {
@@ -269,7 +513,12 @@ impl RtmpWatchStep {
 
             RtmpEndpointWatcherNotification::WatcherRegistrationSuccessful => {
                 info!("Registration for RTMP watchers was accepted");
-                self.status = StepStatus::Active;
+                self.successful_registration_count += 1;
+
+                // Only consider the step active once every stream key has been registered
+                if self.successful_registration_count >= self.registrations.len() {
+                    self.status = StepStatus::Active;
+                }
             }
 
             RtmpEndpointWatcherNotification::StreamKeyBecameActive {
@@ -314,6 +563,29 @@ impl RtmpWatchStep {
                 self.stream_watchers.remove(&stream_key);
             }
 
+            RtmpEndpointWatcherNotification::WatcherJoined { stream_key } => {
+                if !self.request_keyframes {
+                    return;
+                }
+
+                let stream_ids = self
+                    .stream_id_to_name_map
+                    .iter()
+                    .filter(|(_, name)| **name == stream_key)
+                    .map(|(id, _)| id.clone());
+
+                for stream_id in stream_ids {
+                    info!(
+                        stream_id = ?stream_id,
+                        stream_key = %stream_key,
+                        "New watcher joined stream key '{}', requesting a keyframe for stream {:?}",
+                        stream_key, stream_id
+                    );
+
+                    outputs.keyframe_requests.push(stream_id);
+                }
+            }
+
             RtmpEndpointWatcherNotification::WatcherRequiringApproval {
                 connection_id,
                 stream_key,
@@ -325,6 +597,7 @@ impl RtmpWatchStep {
                         ReactorManagerRequest::CreateWorkflowForStreamName {
                             reactor_name: reactor.clone(),
                             stream_name: stream_key,
+                            metadata: None,
                             response_channel: sender,
                         },
                     );
@@ -346,19 +619,51 @@ impl RtmpWatchStep {
         }
     }
 
+    /// Determines which stream key watchers will see the specified stream on.  If this step was
+    /// registered with a single exact stream key, then we don't care what stream name this was
+    /// originally published as and it's treated as the configured stream key.  Otherwise only a
+    /// stream whose name matches one of the registered stream keys or patterns is watchable.
+    fn get_watch_stream_key(&self, stream_name: &str) -> Option<String> {
+        if let [registration] = self.registrations.as_slice() {
+            if let StreamKeyRegistration::Exact(key) = &registration.stream_key {
+                return Some(key.clone());
+            }
+        }
+
+        self.registrations
+            .iter()
+            .find(|registration| registration.stream_key.matches(stream_name))
+            .map(|_| stream_name.to_string())
+    }
+
+    fn send_to_endpoint(&mut self, media: RtmpEndpointMediaMessage) {
+        let registration = self
+            .registrations
+            .iter_mut()
+            .find(|registration| registration.stream_key.matches(&media.stream_key));
+
+        if let Some(registration) = registration {
+            registration.send(media, self.max_buffer_frames);
+        }
+    }
+
     fn handle_media(&mut self, media: MediaNotification, outputs: &mut StepOutputs) {
         outputs.media.push(media.clone());
 
         if self.status == StepStatus::Active {
             match &media.content {
                 MediaNotificationContent::NewIncomingStream { stream_name } => {
-                    // If this step was registered with an exact stream name, then we don't care
-                    // what stream name this was originally published as.  For watch purposes treat
-                    // it as the configured stream key
-                    let stream_name = match &self.stream_key {
-                        StreamKeyRegistration::Any => stream_name,
-                        StreamKeyRegistration::Exact(configured_stream_name) => {
-                            configured_stream_name
+                    let stream_name = match self.get_watch_stream_key(stream_name) {
+                        Some(stream_name) => stream_name,
+                        None => {
+                            info!(
+                                stream_id = ?media.stream_id,
+                                stream_name = %stream_name,
+                                "New incoming stream '{}' does not match any registered stream key, \
+                                    so it will not be available to watchers", stream_name
+                            );
+
+                            return;
                         }
                     };
 
@@ -371,7 +676,7 @@ impl RtmpWatchStep {
                     match self.stream_id_to_name_map.get(&media.stream_id) {
                         None => (),
                         Some(current_stream_name) => {
-                            if current_stream_name == stream_name {
+                            if *current_stream_name == stream_name {
                                 warn!(
                                     stream_id = ?media.stream_id,
                                     stream_name = %stream_name,
@@ -390,8 +695,42 @@ impl RtmpWatchStep {
                         }
                     }
 
+                    let existing_stream_id = self
+                        .stream_id_to_name_map
+                        .iter()
+                        .find(|(id, name)| **id != media.stream_id && **name == stream_name)
+                        .map(|(id, _)| id.clone());
+
+                    if let Some(existing_stream_id) = existing_stream_id {
+                        match self.duplicate_stream_action {
+                            DuplicateStreamAction::Reject => {
+                                warn!(
+                                    stream_id = ?media.stream_id,
+                                    stream_name = %stream_name,
+                                    existing_stream_id = ?existing_stream_id,
+                                    "New incoming stream {:?} rejected, as stream {:?} is already being \
+                                        surfaced on stream key '{}'", media.stream_id, existing_stream_id, stream_name
+                                );
+
+                                return;
+                            }
+
+                            DuplicateStreamAction::Replace => {
+                                warn!(
+                                    stream_id = ?media.stream_id,
+                                    stream_name = %stream_name,
+                                    existing_stream_id = ?existing_stream_id,
+                                    "New incoming stream {:?} is replacing stream {:?} on stream key '{}'",
+                                    media.stream_id, existing_stream_id, stream_name
+                                );
+
+                                self.stream_id_to_name_map.remove(&existing_stream_id);
+                            }
+                        }
+                    }
+
                     self.stream_id_to_name_map
-                        .insert(media.stream_id.clone(), stream_name.clone());
+                        .insert(media.stream_id.clone(), stream_name);
                 }
 
                 MediaNotificationContent::StreamDisconnected => {
@@ -422,7 +761,7 @@ impl RtmpWatchStep {
                         data: RtmpEndpointMediaData::NewStreamMetaData { metadata },
                     };
 
-                    let _ = self.media_channel.send(rtmp_media);
+                    self.send_to_endpoint(rtmp_media);
                 }
 
                 MediaNotificationContent::Video {
@@ -449,7 +788,7 @@ impl RtmpWatchStep {
                         },
                     };
 
-                    let _ = self.media_channel.send(rtmp_media);
+                    self.send_to_endpoint(rtmp_media);
                 }
 
                 MediaNotificationContent::Audio {
@@ -473,9 +812,78 @@ impl RtmpWatchStep {
                         },
                     };
 
-                    let _ = self.media_channel.send(rtmp_media);
+                    self.send_to_endpoint(rtmp_media);
                 }
+
+                // RTMP has no way to signal a discontinuity or cue to watchers
+                MediaNotificationContent::Discontinuity => (),
+                MediaNotificationContent::Cue { .. } => (),
+            }
+        }
+    }
+}
+
+impl WatchRegistration {
+    fn send(&mut self, media: RtmpEndpointMediaMessage, max_buffer_frames: Option<usize>) {
+        let (backlog, max_buffer_frames) = match (&self.media_backlog, max_buffer_frames) {
+            (Some(backlog), Some(max)) => (backlog, max),
+            _ => {
+                let _ = self.media_channel.send(media);
+                return;
+            }
+        };
+
+        let pending_count = backlog.load(Ordering::Relaxed);
+        let is_droppable = matches!(
+            media.data,
+            RtmpEndpointMediaData::NewVideoData {
+                is_keyframe: false,
+                is_sequence_header: false,
+                ..
             }
+        );
+
+        if is_droppable && (self.is_dropping_frames || pending_count >= max_buffer_frames) {
+            if !self.is_dropping_frames {
+                warn!(
+                    stream_key = %media.stream_key,
+                    pending_count = %pending_count,
+                    "RTMP endpoint has {} unread media messages for stream key '{}', dropping \
+                        non-keyframe video until it catches up", pending_count, media.stream_key
+                );
+
+                self.is_dropping_frames = true;
+            }
+
+            self.dropped_frame_count += 1;
+            return;
+        }
+
+        // Only resume on a keyframe, so watchers aren't sent frames they can't decode
+        let is_keyframe = matches!(
+            media.data,
+            RtmpEndpointMediaData::NewVideoData {
+                is_keyframe: true,
+                is_sequence_header: false,
+                ..
+            }
+        );
+
+        if self.is_dropping_frames && is_keyframe && pending_count < max_buffer_frames {
+            info!(
+                stream_key = %media.stream_key,
+                dropped_frame_count = %self.dropped_frame_count,
+                "RTMP endpoint caught up for stream key '{}' after {} video frames were dropped",
+                media.stream_key, self.dropped_frame_count
+            );
+
+            self.is_dropping_frames = false;
+            self.dropped_frame_count = 0;
+        }
+
+        backlog.fetch_add(1, Ordering::Relaxed);
+        if self.media_channel.send(media).is_err() {
+            backlog.fetch_sub(1, Ordering::Relaxed);
         }
     }
 }
@@ -554,7 +962,7 @@ impl WorkflowStep for RtmpWatchStep {
                 } => {
                     if is_valid {
                         let _ = validation_channel.send(ValidationResponse::Approve {
-                            reactor_update_channel,
+                            reactor_update_channel: Some(reactor_update_channel),
                         });
                     } else {
                         let _ = validation_channel.send(ValidationResponse::Reject);
@@ -605,14 +1013,16 @@ impl WorkflowStep for RtmpWatchStep {
 
     fn shutdown(&mut self) {
         self.status = StepStatus::Shutdown;
-        let _ = self
-            .rtmp_endpoint_sender
-            .send(RtmpEndpointRequest::RemoveRegistration {
-                registration_type: RegistrationType::Watcher,
-                port: self.port,
-                rtmp_app: self.rtmp_app.clone(),
-                rtmp_stream_key: self.stream_key.clone(),
-            });
+        for registration in &self.registrations {
+            let _ = self
+                .rtmp_endpoint_sender
+                .send(RtmpEndpointRequest::RemoveRegistration {
+                    registration_type: RegistrationType::Watcher,
+                    port: self.port,
+                    rtmp_app: self.rtmp_app.clone(),
+                    rtmp_stream_key: registration.stream_key.clone(),
+                });
+        }
     }
 }
 
@@ -28,6 +28,11 @@ struct DefinitionBuilder {
     app: Option<String>,
     key: Option<String>,
     reactor: Option<String>,
+    max_buffer_frames: Option<String>,
+    max_watchers: Option<String>,
+    start_on_keyframe: bool,
+    request_keyframes: bool,
+    on_duplicate_stream: Option<String>,
 }
 
 impl DefinitionBuilder {
@@ -37,6 +42,11 @@ impl DefinitionBuilder {
             app: None,
             key: None,
             reactor: None,
+            max_buffer_frames: None,
+            max_watchers: None,
+            start_on_keyframe: false,
+            request_keyframes: false,
+            on_duplicate_stream: None,
         }
     }
 
@@ -60,6 +70,31 @@ impl DefinitionBuilder {
         self
     }
 
+    fn max_buffer_frames(mut self, max: &str) -> Self {
+        self.max_buffer_frames = Some(max.to_string());
+        self
+    }
+
+    fn max_watchers(mut self, max: &str) -> Self {
+        self.max_watchers = Some(max.to_string());
+        self
+    }
+
+    fn start_on_keyframe(mut self) -> Self {
+        self.start_on_keyframe = true;
+        self
+    }
+
+    fn request_keyframes(mut self) -> Self {
+        self.request_keyframes = true;
+        self
+    }
+
+    fn on_duplicate_stream(mut self, action: &str) -> Self {
+        self.on_duplicate_stream = Some(action.to_string());
+        self
+    }
+
     fn build(self) -> WorkflowStepDefinition {
         let mut definition = WorkflowStepDefinition {
             step_type: WorkflowStepType("rtmp_watch".to_string()),
@@ -98,6 +133,36 @@ impl DefinitionBuilder {
                 .insert(REACTOR_NAME.to_string(), Some(reactor));
         }
 
+        if let Some(max) = self.max_buffer_frames {
+            definition
+                .parameters
+                .insert(MAX_BUFFER_FRAMES_PROPERTY_NAME.to_string(), Some(max));
+        }
+
+        if let Some(max) = self.max_watchers {
+            definition
+                .parameters
+                .insert(MAX_WATCHERS_PROPERTY_NAME.to_string(), Some(max));
+        }
+
+        if self.start_on_keyframe {
+            definition
+                .parameters
+                .insert(START_ON_KEYFRAME_FLAG.to_string(), None);
+        }
+
+        if self.request_keyframes {
+            definition
+                .parameters
+                .insert(REQUEST_KEYFRAMES_FLAG.to_string(), None);
+        }
+
+        if let Some(action) = self.on_duplicate_stream {
+            definition
+                .parameters
+                .insert(ON_DUPLICATE_STREAM_PROPERTY_NAME.to_string(), Some(action));
+        }
+
         definition
     }
 }
@@ -233,6 +298,27 @@ async fn asterisk_stream_key_acts_as_wildcard() {
     }
 }
 
+#[tokio::test]
+async fn stream_key_with_glob_characters_registered_as_pattern() {
+    let definition = DefinitionBuilder::new().key("live_*").build();
+    let mut context = TestContext::new(definition).unwrap();
+
+    let response = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match response {
+        RtmpEndpointRequest::ListenForWatchers {
+            rtmp_stream_key, ..
+        } => {
+            assert_eq!(
+                rtmp_stream_key,
+                StreamKeyRegistration::Pattern("live_*".to_string()),
+                "Unexpected stream key"
+            );
+        }
+
+        response => panic!("Unexpected response: {:?}", response),
+    }
+}
+
 #[test]
 fn error_if_no_app_provided() {
     let mut definition = DefinitionBuilder::new().build();
@@ -255,6 +341,52 @@ fn error_if_no_stream_key_provided() {
     }
 }
 
+#[test]
+fn validation_fails_for_non_numeric_max_watchers() {
+    let definition = DefinitionBuilder::new().max_watchers("abc").build();
+    let generator = RtmpWatchStepGenerator::new(unbounded_channel().0, unbounded_channel().0);
+
+    assert!(generator.validate(&definition).is_err());
+}
+
+#[test]
+fn invalid_parameters_are_a_configuration_error() {
+    let definition = DefinitionBuilder::new().max_watchers("abc").build();
+    let (rtmp_sender, _rtmp_receiver) = unbounded_channel();
+    let generator = RtmpWatchStepGenerator::new(rtmp_sender, unbounded_channel().0);
+
+    match generator.generate(definition) {
+        Err(StepCreationError::InvalidConfiguration(_)) => (),
+        Err(error) => panic!("Unexpected error: {:?}", error),
+        Ok(_) => panic!("Expected an error"),
+    }
+}
+
+#[test]
+fn closed_rtmp_endpoint_is_a_fatal_error() {
+    let definition = DefinitionBuilder::new().build();
+    let generator = RtmpWatchStepGenerator::new(unbounded_channel().0, unbounded_channel().0);
+
+    match generator.generate(definition) {
+        Err(StepCreationError::Fatal(_)) => (),
+        Err(error) => panic!("Unexpected error: {:?}", error),
+        Ok(_) => panic!("Expected an error"),
+    }
+}
+
+#[test]
+fn validation_does_not_register_with_rtmp_endpoint() {
+    let definition = DefinitionBuilder::new().build();
+    let (rtmp_sender, mut rtmp_receiver) = unbounded_channel();
+    let generator = RtmpWatchStepGenerator::new(rtmp_sender, unbounded_channel().0);
+
+    generator.validate(&definition).unwrap();
+    assert!(
+        rtmp_receiver.try_recv().is_err(),
+        "Expected no rtmp endpoint requests"
+    );
+}
+
 #[test]
 fn new_step_is_in_created_status() {
     let definition = DefinitionBuilder::new().build();
@@ -339,6 +471,7 @@ async fn video_packet_not_sent_to_media_channel_if_new_stream_message_not_receiv
             is_sequence_header: true,
             timestamp: VideoTimestamp::from_durations(Duration::new(0, 0), Duration::new(0, 0)),
         },
+        tags: Vec::new(),
     });
 
     test_utils::expect_mpsc_timeout(&mut media_channel).await;
@@ -355,6 +488,7 @@ async fn video_packet_sent_to_media_channel_after_new_stream_message_received()
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     context.step_context.execute_with_media(MediaNotification {
@@ -369,6 +503,7 @@ async fn video_packet_sent_to_media_channel_after_new_stream_message_received()
                 Duration::from_millis(15),
             ),
         },
+        tags: Vec::new(),
     });
 
     let media = expect_mpsc_response(&mut media_channel).await;
@@ -409,11 +544,13 @@ async fn video_packet_not_sent_to_media_channel_after_stream_disconnection_messa
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     context.step_context.execute_with_media(MediaNotification {
         stream_id: StreamId("abc".to_string()),
         content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
     });
 
     context.step_context.execute_with_media(MediaNotification {
@@ -428,6 +565,7 @@ async fn video_packet_not_sent_to_media_channel_after_stream_disconnection_messa
                 Duration::from_millis(15),
             ),
         },
+        tags: Vec::new(),
     });
 
     test_utils::expect_mpsc_timeout(&mut media_channel).await;
@@ -444,6 +582,7 @@ async fn video_packet_not_sent_to_media_channel_when_new_stream_is_for_different
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     context.step_context.execute_with_media(MediaNotification {
@@ -458,6 +597,7 @@ async fn video_packet_not_sent_to_media_channel_when_new_stream_is_for_different
                 Duration::from_millis(15),
             ),
         },
+        tags: Vec::new(),
     });
 
     test_utils::expect_mpsc_timeout(&mut media_channel).await;
@@ -474,6 +614,7 @@ async fn audio_packet_sent_to_media_channel_after_new_stream_message_received()
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     context.step_context.execute_with_media(MediaNotification {
@@ -484,6 +625,7 @@ async fn audio_packet_sent_to_media_channel_after_new_stream_message_received()
             is_sequence_header: true,
             timestamp: Duration::from_millis(1),
         },
+        tags: Vec::new(),
     });
 
     let media = expect_mpsc_response(&mut media_channel).await;
@@ -517,6 +659,7 @@ async fn metadata_packet_sent_to_media_channel_after_new_stream_message_received
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let mut metadata = HashMap::new();
@@ -525,6 +668,7 @@ async fn metadata_packet_sent_to_media_channel_after_new_stream_message_received
     context.step_context.execute_with_media(MediaNotification {
         stream_id: StreamId("abc".to_string()),
         content: MediaNotificationContent::Metadata { data: metadata },
+        tags: Vec::new(),
     });
 
     let media = expect_mpsc_response(&mut media_channel).await;
@@ -550,6 +694,7 @@ async fn media_message_uses_strict_stream_key_when_exact_key_registered() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     context.step_context.execute_with_media(MediaNotification {
@@ -564,6 +709,7 @@ async fn media_message_uses_strict_stream_key_when_exact_key_registered() {
                 Duration::from_millis(15),
             ),
         },
+        tags: Vec::new(),
     });
 
     let media = expect_mpsc_response(&mut media_channel).await;
@@ -583,6 +729,7 @@ async fn new_stream_message_passed_as_output() {
             content: MediaNotificationContent::NewIncomingStream {
                 stream_name: "def".to_string(),
             },
+            tags: Vec::new(),
         });
 }
 
@@ -597,6 +744,7 @@ async fn stream_disconnected_message_passed_as_output() {
         .assert_media_passed_through(MediaNotification {
             stream_id: StreamId("abc".to_string()),
             content: MediaNotificationContent::StreamDisconnected,
+            tags: Vec::new(),
         });
 }
 
@@ -620,6 +768,7 @@ async fn video_message_passed_as_output() {
                     Duration::from_millis(15),
                 ),
             },
+            tags: Vec::new(),
         });
 }
 
@@ -639,6 +788,7 @@ async fn audio_message_passed_as_output() {
                 is_sequence_header: true,
                 timestamp: Duration::from_millis(1),
             },
+            tags: Vec::new(),
         });
 }
 
@@ -656,6 +806,7 @@ async fn metadata_message_passed_as_output() {
         .assert_media_passed_through(MediaNotification {
             stream_id: StreamId("abc".to_string()),
             content: MediaNotificationContent::Metadata { data: metadata },
+            tags: Vec::new(),
         });
 }
 
@@ -717,6 +868,7 @@ async fn reactor_responding_with_invalid_sends_rejection_response() {
         .send(ReactorWorkflowUpdate {
             is_valid: false,
             routable_workflow_names: HashSet::new(),
+            definition_hash: None,
         })
         .expect("Failed to send reactor response");
 
@@ -752,6 +904,7 @@ async fn reactor_responding_with_valid_sends_approved_response() {
         .send(ReactorWorkflowUpdate {
             is_valid: true,
             routable_workflow_names: HashSet::new(),
+            definition_hash: None,
         })
         .expect("Failed to send reactor response");
 
@@ -762,3 +915,593 @@ async fn reactor_responding_with_valid_sends_approved_response() {
         response => panic!("Unexpected response: {:?}", response),
     }
 }
+
+#[tokio::test]
+async fn comma_delimited_stream_keys_are_each_registered() {
+    let definition = DefinitionBuilder::new().key("abc, def").build();
+    let mut context = TestContext::new(definition).unwrap();
+
+    let mut registered_keys = Vec::new();
+    for _ in 0..2 {
+        let response = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+        match response {
+            RtmpEndpointRequest::ListenForWatchers {
+                rtmp_stream_key, ..
+            } => registered_keys.push(rtmp_stream_key),
+
+            response => panic!("Unexpected response: {:?}", response),
+        }
+    }
+
+    assert_eq!(
+        registered_keys,
+        vec![
+            StreamKeyRegistration::Exact("abc".to_string()),
+            StreamKeyRegistration::Exact("def".to_string()),
+        ],
+        "Unexpected registered stream keys"
+    );
+}
+
+#[test]
+fn error_if_wildcard_combined_with_other_stream_keys() {
+    let definition = DefinitionBuilder::new().key("abc,*").build();
+
+    match TestContext::new(definition) {
+        Ok(_) => panic!("Expected failure"),
+        Err(_) => (),
+    }
+}
+
+#[tokio::test]
+async fn multiple_stream_keys_only_active_after_all_registrations_succeed() {
+    let definition = DefinitionBuilder::new().key("abc,def").build();
+    let mut context = TestContext::new(definition).unwrap();
+
+    let _first = context.accept_registration().await;
+    assert_eq!(
+        context.step_context.step.get_status(),
+        &StepStatus::Created,
+        "Unexpected status after first registration"
+    );
+
+    let _second = context.accept_registration().await;
+    assert_eq!(
+        context.step_context.step.get_status(),
+        &StepStatus::Active,
+        "Unexpected status after second registration"
+    );
+}
+
+#[tokio::test]
+async fn media_routed_to_registration_matching_stream_name_when_multiple_keys() {
+    let definition = DefinitionBuilder::new().key("abc,def").build();
+    let mut context = TestContext::new(definition).unwrap();
+    let (_abc_notifications, mut abc_media) = context.accept_registration().await;
+    let (_def_notifications, mut def_media) = context.accept_registration().await;
+
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("stream".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        tags: Vec::new(),
+    });
+
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("stream".to_string()),
+        content: MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            data: Bytes::from(vec![3, 4]),
+            is_keyframe: true,
+            is_sequence_header: true,
+            timestamp: VideoTimestamp::from_zero(),
+        },
+        tags: Vec::new(),
+    });
+
+    let media = expect_mpsc_response(&mut def_media).await;
+    assert_eq!(&media.stream_key, "def", "Unexpected stream key");
+    test_utils::expect_mpsc_timeout(&mut abc_media).await;
+}
+
+#[tokio::test]
+async fn media_not_sent_when_stream_name_matches_no_key_when_multiple_keys() {
+    let definition = DefinitionBuilder::new().key("abc,def").build();
+    let mut context = TestContext::new(definition).unwrap();
+    let (_abc_notifications, mut abc_media) = context.accept_registration().await;
+    let (_def_notifications, mut def_media) = context.accept_registration().await;
+
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("stream".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "ghi".to_string(),
+        },
+        tags: Vec::new(),
+    });
+
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("stream".to_string()),
+        content: MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            data: Bytes::from(vec![3, 4]),
+            is_keyframe: true,
+            is_sequence_header: true,
+            timestamp: VideoTimestamp::from_zero(),
+        },
+        tags: Vec::new(),
+    });
+
+    test_utils::expect_mpsc_timeout(&mut abc_media).await;
+    test_utils::expect_mpsc_timeout(&mut def_media).await;
+}
+
+#[tokio::test]
+async fn media_sent_with_stream_name_when_stream_matches_pattern() {
+    let definition = DefinitionBuilder::new().key("live_*").build();
+    let mut context = TestContext::new(definition).unwrap();
+    let (_notification_channel, mut media_channel) = context.accept_registration().await;
+
+    context
+        .step_context
+        .execute_with_media(new_stream("stream", "live_abc"));
+    context.step_context.execute_with_media(video("stream"));
+
+    let media = expect_mpsc_response(&mut media_channel).await;
+    assert_eq!(&media.stream_key, "live_abc", "Unexpected stream key");
+}
+
+#[tokio::test]
+async fn media_not_sent_when_stream_does_not_match_pattern() {
+    let definition = DefinitionBuilder::new().key("live_*").build();
+    let mut context = TestContext::new(definition).unwrap();
+    let (_notification_channel, mut media_channel) = context.accept_registration().await;
+
+    context
+        .step_context
+        .execute_with_media(new_stream("stream", "test_abc"));
+    context.step_context.execute_with_media(video("stream"));
+
+    test_utils::expect_mpsc_timeout(&mut media_channel).await;
+}
+
+#[tokio::test]
+async fn media_routed_to_pattern_when_combined_with_exact_keys() {
+    let definition = DefinitionBuilder::new().key("abc,live_*").build();
+    let mut context = TestContext::new(definition).unwrap();
+    let (_abc_notifications, mut abc_media) = context.accept_registration().await;
+    let (_live_notifications, mut live_media) = context.accept_registration().await;
+
+    context
+        .step_context
+        .execute_with_media(new_stream("stream", "live_1"));
+    context.step_context.execute_with_media(video("stream"));
+
+    let media = expect_mpsc_response(&mut live_media).await;
+    assert_eq!(&media.stream_key, "live_1", "Unexpected stream key");
+    test_utils::expect_mpsc_timeout(&mut abc_media).await;
+}
+
+#[test]
+fn error_if_max_buffer_frames_is_not_positive() {
+    let definition = DefinitionBuilder::new().max_buffer_frames("0").build();
+
+    match TestContext::new(definition) {
+        Ok(_) => panic!("Expected failure"),
+        Err(_) => (),
+    }
+}
+
+#[test]
+fn error_if_max_watchers_is_not_a_number() {
+    let definition = DefinitionBuilder::new().max_watchers("abc").build();
+
+    match TestContext::new(definition) {
+        Ok(_) => panic!("Expected failure"),
+        Err(_) => (),
+    }
+}
+
+#[tokio::test]
+async fn max_watchers_passed_to_endpoint() {
+    let definition = DefinitionBuilder::new().max_watchers("5").build();
+    let mut context = TestContext::new(definition).unwrap();
+
+    let request = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match request {
+        RtmpEndpointRequest::ListenForWatchers { max_watchers, .. } => {
+            assert_eq!(max_watchers, Some(5), "Unexpected max watchers");
+        }
+
+        request => panic!("Unexpected rtmp request seen: {:?}", request),
+    }
+}
+
+#[tokio::test]
+async fn zero_max_watchers_is_unlimited() {
+    let definition = DefinitionBuilder::new().max_watchers("0").build();
+    let mut context = TestContext::new(definition).unwrap();
+
+    let request = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match request {
+        RtmpEndpointRequest::ListenForWatchers { max_watchers, .. } => {
+            assert_eq!(max_watchers, None, "Expected no max watchers");
+        }
+
+        request => panic!("Unexpected rtmp request seen: {:?}", request),
+    }
+}
+
+#[tokio::test]
+async fn no_max_watchers_when_not_specified() {
+    let definition = DefinitionBuilder::new().build();
+    let mut context = TestContext::new(definition).unwrap();
+
+    let request = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match request {
+        RtmpEndpointRequest::ListenForWatchers { max_watchers, .. } => {
+            assert_eq!(max_watchers, None, "Expected no max watchers");
+        }
+
+        request => panic!("Unexpected rtmp request seen: {:?}", request),
+    }
+}
+
+#[tokio::test]
+async fn start_on_keyframe_passed_to_endpoint_when_flag_specified() {
+    let definition = DefinitionBuilder::new().start_on_keyframe().build();
+    let mut context = TestContext::new(definition).unwrap();
+
+    let request = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match request {
+        RtmpEndpointRequest::ListenForWatchers {
+            start_on_keyframe, ..
+        } => {
+            assert!(start_on_keyframe, "Expected start on keyframe to be set");
+        }
+
+        request => panic!("Unexpected rtmp request seen: {:?}", request),
+    }
+}
+
+#[tokio::test]
+async fn start_on_keyframe_not_set_when_flag_not_specified() {
+    let definition = DefinitionBuilder::new().build();
+    let mut context = TestContext::new(definition).unwrap();
+
+    let request = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match request {
+        RtmpEndpointRequest::ListenForWatchers {
+            start_on_keyframe, ..
+        } => {
+            assert!(
+                !start_on_keyframe,
+                "Expected start on keyframe to not be set"
+            );
+        }
+
+        request => panic!("Unexpected rtmp request seen: {:?}", request),
+    }
+}
+
+#[tokio::test]
+async fn keyframe_requested_when_watcher_joins_with_flag_specified() {
+    let definition = DefinitionBuilder::new().request_keyframes().build();
+    let mut context = TestContext::new(definition).unwrap();
+    let (notification_channel, _media_channel) = context.accept_registration().await;
+
+    context
+        .step_context
+        .execute_with_media(new_stream("abc", "def"));
+
+    notification_channel
+        .send(RtmpEndpointWatcherNotification::WatcherJoined {
+            stream_key: "def".to_string(),
+        })
+        .expect("Failed to send watcher joined notification");
+
+    context.step_context.execute_pending_notifications().await;
+
+    assert_eq!(
+        context.step_context.keyframe_requests,
+        vec![StreamId("abc".to_string())],
+        "Unexpected keyframe requests"
+    );
+}
+
+#[tokio::test]
+async fn keyframe_not_requested_when_watcher_joins_without_flag() {
+    let definition = DefinitionBuilder::new().build();
+    let mut context = TestContext::new(definition).unwrap();
+    let (notification_channel, _media_channel) = context.accept_registration().await;
+
+    context
+        .step_context
+        .execute_with_media(new_stream("abc", "def"));
+
+    notification_channel
+        .send(RtmpEndpointWatcherNotification::WatcherJoined {
+            stream_key: "def".to_string(),
+        })
+        .expect("Failed to send watcher joined notification");
+
+    context.step_context.execute_pending_notifications().await;
+
+    assert!(
+        context.step_context.keyframe_requests.is_empty(),
+        "Expected no keyframe requests"
+    );
+}
+
+#[tokio::test]
+async fn keyframe_not_requested_for_streams_on_other_stream_keys() {
+    let definition = DefinitionBuilder::new().request_keyframes().build();
+    let mut context = TestContext::new(definition).unwrap();
+    let (notification_channel, _media_channel) = context.accept_registration().await;
+
+    context
+        .step_context
+        .execute_with_media(new_stream("abc", "def"));
+
+    notification_channel
+        .send(RtmpEndpointWatcherNotification::WatcherJoined {
+            stream_key: "ghi".to_string(),
+        })
+        .expect("Failed to send watcher joined notification");
+
+    context.step_context.execute_pending_notifications().await;
+
+    assert!(
+        context.step_context.keyframe_requests.is_empty(),
+        "Expected no keyframe requests"
+    );
+}
+
+#[tokio::test]
+async fn no_media_backlog_registered_without_max_buffer_frames() {
+    let definition = DefinitionBuilder::new().build();
+    let mut context = TestContext::new(definition).unwrap();
+
+    let request = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    match request {
+        RtmpEndpointRequest::ListenForWatchers { media_backlog, .. } => {
+            assert!(media_backlog.is_none(), "Expected no media backlog");
+        }
+
+        request => panic!("Unexpected rtmp request seen: {:?}", request),
+    }
+}
+
+#[tokio::test]
+async fn non_keyframes_dropped_once_backlog_reaches_max_buffer_frames() {
+    let definition = DefinitionBuilder::new().max_buffer_frames("2").build();
+    let mut context = TestContext::new(definition).unwrap();
+    let (_notification_channel, mut media_channel) = context.accept_registration().await;
+
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        tags: Vec::new(),
+    });
+
+    // Nothing reads the media channel, so the backlog is full after two messages
+    for (data, is_keyframe, is_sequence_header) in [
+        (1, true, true),
+        (2, false, false),
+        (3, false, false),
+        (4, true, false),
+    ] {
+        context.step_context.execute_with_media(MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: MediaNotificationContent::Video {
+                codec: VideoCodec::H264,
+                data: Bytes::from(vec![data]),
+                is_keyframe,
+                is_sequence_header,
+                timestamp: VideoTimestamp::from_zero(),
+            },
+            tags: Vec::new(),
+        });
+    }
+
+    for expected_data in [1, 2, 4] {
+        let media = expect_mpsc_response(&mut media_channel).await;
+        match &media.data {
+            RtmpEndpointMediaData::NewVideoData { data, .. } => {
+                assert_eq!(data, &vec![expected_data], "Unexpected video bytes");
+            }
+
+            _ => panic!("Unexpected media data: {:?}", media.data),
+        }
+    }
+
+    test_utils::expect_mpsc_timeout(&mut media_channel).await;
+}
+
+#[tokio::test]
+async fn non_keyframes_resume_after_keyframe_once_backlog_drained() {
+    let definition = DefinitionBuilder::new().max_buffer_frames("1").build();
+    let mut context = TestContext::new(definition).unwrap();
+    let request = test_utils::expect_mpsc_response(&mut context.rtmp_endpoint).await;
+    let (notification_channel, mut media_channel, media_backlog) = match request {
+        RtmpEndpointRequest::ListenForWatchers {
+            notification_channel,
+            media_channel,
+            media_backlog,
+            ..
+        } => (
+            notification_channel,
+            media_channel,
+            media_backlog.expect("Expected a media backlog"),
+        ),
+
+        request => panic!("Unexpected rtmp request seen: {:?}", request),
+    };
+
+    notification_channel
+        .send(RtmpEndpointWatcherNotification::WatcherRegistrationSuccessful)
+        .expect("Failed to send registration response");
+
+    context.step_context.execute_pending_notifications().await;
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        tags: Vec::new(),
+    });
+
+    for (data, is_keyframe) in [(1, true), (2, false)] {
+        context.step_context.execute_with_media(MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: MediaNotificationContent::Video {
+                codec: VideoCodec::H264,
+                data: Bytes::from(vec![data]),
+                is_keyframe,
+                is_sequence_header: false,
+                timestamp: VideoTimestamp::from_zero(),
+            },
+            tags: Vec::new(),
+        });
+    }
+
+    // Simulate the endpoint reading the queued message
+    let _ = expect_mpsc_response(&mut media_channel).await;
+    media_backlog.fetch_sub(1, Ordering::Relaxed);
+
+    for (data, is_keyframe) in [(3, false), (4, true), (5, false)] {
+        context.step_context.execute_with_media(MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: MediaNotificationContent::Video {
+                codec: VideoCodec::H264,
+                data: Bytes::from(vec![data]),
+                is_keyframe,
+                is_sequence_header: false,
+                timestamp: VideoTimestamp::from_zero(),
+            },
+            tags: Vec::new(),
+        });
+
+        if is_keyframe {
+            let _ = expect_mpsc_response(&mut media_channel).await;
+            media_backlog.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    let media = expect_mpsc_response(&mut media_channel).await;
+    match &media.data {
+        RtmpEndpointMediaData::NewVideoData { data, .. } => {
+            assert_eq!(data, &vec![5], "Unexpected video bytes");
+        }
+
+        _ => panic!("Unexpected media data: {:?}", media.data),
+    }
+
+    test_utils::expect_mpsc_timeout(&mut media_channel).await;
+}
+
+fn new_stream(stream_id: &str, stream_name: &str) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: stream_name.to_string(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+fn video(stream_id: &str) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content: MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            data: Bytes::from(vec![1, 2, 3]),
+            is_keyframe: true,
+            is_sequence_header: false,
+            timestamp: VideoTimestamp::from_zero(),
+        },
+        tags: Vec::new(),
+    }
+}
+
+#[test]
+fn error_if_on_duplicate_stream_is_invalid() {
+    let definition = DefinitionBuilder::new().on_duplicate_stream("abc").build();
+
+    match TestContext::new(definition) {
+        Ok(_) => panic!("Expected failure"),
+        Err(_) => (),
+    }
+}
+
+#[tokio::test]
+async fn second_stream_on_exact_key_rejected_by_default() {
+    let definition = DefinitionBuilder::new().key("specific_key").build();
+    let mut context = TestContext::new(definition).unwrap();
+    let (_notification_channel, mut media_channel) = context.accept_registration().await;
+
+    context
+        .step_context
+        .execute_with_media(new_stream("abc", "first"));
+    context
+        .step_context
+        .execute_with_media(new_stream("def", "second"));
+
+    context.step_context.execute_with_media(video("def"));
+    test_utils::expect_mpsc_timeout(&mut media_channel).await;
+
+    context.step_context.execute_with_media(video("abc"));
+    let media = expect_mpsc_response(&mut media_channel).await;
+    assert_eq!(&media.stream_key, "specific_key", "Unexpected stream key");
+}
+
+#[tokio::test]
+async fn second_stream_on_exact_key_replaces_first_when_configured() {
+    let definition = DefinitionBuilder::new()
+        .key("specific_key")
+        .on_duplicate_stream("replace")
+        .build();
+
+    let mut context = TestContext::new(definition).unwrap();
+    let (_notification_channel, mut media_channel) = context.accept_registration().await;
+
+    context
+        .step_context
+        .execute_with_media(new_stream("abc", "first"));
+    context
+        .step_context
+        .execute_with_media(new_stream("def", "second"));
+
+    context.step_context.execute_with_media(video("abc"));
+    test_utils::expect_mpsc_timeout(&mut media_channel).await;
+
+    context.step_context.execute_with_media(video("def"));
+    let media = expect_mpsc_response(&mut media_channel).await;
+    assert_eq!(&media.stream_key, "specific_key", "Unexpected stream key");
+}
+
+#[tokio::test]
+async fn new_stream_on_exact_key_accepted_after_existing_stream_disconnects() {
+    let definition = DefinitionBuilder::new().key("specific_key").build();
+    let mut context = TestContext::new(definition).unwrap();
+    let (_notification_channel, mut media_channel) = context.accept_registration().await;
+
+    context
+        .step_context
+        .execute_with_media(new_stream("abc", "first"));
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
+    });
+
+    context
+        .step_context
+        .execute_with_media(new_stream("def", "second"));
+    context.step_context.execute_with_media(video("def"));
+
+    let media = expect_mpsc_response(&mut media_channel).await;
+    assert_eq!(&media.stream_key, "specific_key", "Unexpected stream key");
+}
@@ -0,0 +1,383 @@
+//! The SRT Receive step registers with the SRT server endpoint to allow publishers to connect with
+//! the specified port and stream key combination.  Any media packets that SRT publishers send in
+//! will be sent to the next steps.
+//!
+//! All media packets that come in from previous workflow steps are ignored.
+#[cfg(test)]
+mod tests;
+
+use crate::endpoints::rtmp_server::{IpRestriction, StreamKeyRegistration};
+use crate::endpoints::srt_server::{SrtEndpointPublisherMessage, SrtEndpointRequest};
+use crate::net::{ConnectionId, IpAddress, IpAddressParseError};
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::{StepGenerator, StepKind};
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
+use futures::FutureExt;
+use std::collections::HashMap;
+use thiserror::Error as ThisError;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tracing::{error, info};
+
+pub const PORT_PROPERTY_NAME: &str = "port";
+pub const STREAM_KEY_PROPERTY_NAME: &str = "stream_key";
+pub const IP_ALLOW_PROPERTY_NAME: &str = "allow_ips";
+pub const IP_DENY_PROPERTY_NAME: &str = "deny_ips";
+
+/// Generates new SRT receiver workflow step instances based on specified step definitions.
+pub struct SrtReceiverStepGenerator {
+    srt_endpoint_sender: UnboundedSender<SrtEndpointRequest>,
+}
+
+struct SrtReceiverStep {
+    definition: WorkflowStepDefinition,
+    srt_endpoint_sender: UnboundedSender<SrtEndpointRequest>,
+    port: u16,
+    stream_key: StreamKeyRegistration,
+    status: StepStatus,
+    connection_stream_ids: HashMap<ConnectionId, StreamId>,
+}
+
+impl StepFutureResult for FutureResult {}
+
+enum FutureResult {
+    SrtEndpointDroppedRegistration,
+    SrtEndpointResponseReceived(
+        SrtEndpointPublisherMessage,
+        UnboundedReceiver<SrtEndpointPublisherMessage>,
+    ),
+}
+
+struct StepParameters {
+    port: u16,
+    stream_key: StreamKeyRegistration,
+    ip_restrictions: IpRestriction,
+}
+
+#[derive(ThisError, Debug)]
+enum StepStartupError {
+    #[error(
+        "No port specified.  A non-empty parameter of '{}' is required",
+        PORT_PROPERTY_NAME
+    )]
+    NoPortSpecified,
+
+    #[error(
+        "No stream key specified.  A non-empty parameter of '{}' is required",
+        STREAM_KEY_PROPERTY_NAME
+    )]
+    NoStreamKeySpecified,
+
+    #[error(
+        "Invalid port value of '{0}' specified.  A number from 0 to 65535 should be specified"
+    )]
+    InvalidPortSpecified(String),
+
+    #[error("Failed to parse ip address")]
+    InvalidIpAddressSpecified(#[from] IpAddressParseError),
+
+    #[error(
+        "Both {} and {} were specified, but only one is allowed",
+        IP_ALLOW_PROPERTY_NAME,
+        IP_DENY_PROPERTY_NAME
+    )]
+    BothDenyAndAllowIpRestrictionsSpecified,
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl SrtReceiverStepGenerator {
+    pub fn new(srt_endpoint_sender: UnboundedSender<SrtEndpointRequest>) -> Self {
+        SrtReceiverStepGenerator {
+            srt_endpoint_sender,
+        }
+    }
+}
+
+impl StepGenerator for SrtReceiverStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let StepParameters {
+            port,
+            stream_key,
+            ip_restrictions,
+        } = parse_parameters(&definition)?;
+
+        let step = SrtReceiverStep {
+            definition: definition.clone(),
+            srt_endpoint_sender: self.srt_endpoint_sender.clone(),
+            port,
+            stream_key: stream_key.clone(),
+            status: StepStatus::Created,
+            connection_stream_ids: HashMap::new(),
+        };
+
+        let (sender, receiver) = unbounded_channel();
+        let _ = self
+            .srt_endpoint_sender
+            .send(SrtEndpointRequest::ListenForPublishers {
+                port,
+                stream_key,
+                message_channel: sender,
+                stream_id: None,
+                ip_restrictions,
+            });
+
+        Ok((
+            Box::new(step),
+            vec![wait_for_srt_endpoint_response(receiver).boxed()],
+        ))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_parameters(definition)?;
+        Ok(())
+    }
+
+    fn kind(&self) -> StepKind {
+        StepKind::Source
+    }
+}
+
+fn parse_parameters(
+    definition: &WorkflowStepDefinition,
+) -> Result<StepParameters, StepStartupError> {
+    let port = match definition.parameters.get(PORT_PROPERTY_NAME) {
+        Some(Some(value)) => match value.parse::<u16>() {
+            Ok(num) => num,
+            Err(_) => {
+                return Err(StepStartupError::InvalidPortSpecified(value.clone()));
+            }
+        },
+
+        _ => return Err(StepStartupError::NoPortSpecified),
+    };
+
+    let stream_key = match definition.parameters.get(STREAM_KEY_PROPERTY_NAME) {
+        Some(Some(x)) if !x.trim().is_empty() => x.trim(),
+        _ => return Err(StepStartupError::NoStreamKeySpecified),
+    };
+
+    let allowed_ips = match definition.parameters.get(IP_ALLOW_PROPERTY_NAME) {
+        Some(Some(value)) => IpAddress::parse_comma_delimited_list(Some(value))?,
+        _ => Vec::new(),
+    };
+
+    let denied_ips = match definition.parameters.get(IP_DENY_PROPERTY_NAME) {
+        Some(Some(value)) => IpAddress::parse_comma_delimited_list(Some(value))?,
+        _ => Vec::new(),
+    };
+
+    let ip_restrictions = match (!allowed_ips.is_empty(), !denied_ips.is_empty()) {
+        (true, true) => {
+            return Err(StepStartupError::BothDenyAndAllowIpRestrictionsSpecified);
+        }
+        (true, false) => IpRestriction::Allow(allowed_ips),
+        (false, true) => IpRestriction::Deny(denied_ips),
+        (false, false) => IpRestriction::None,
+    };
+
+    let stream_key = if stream_key == "*" {
+        StreamKeyRegistration::Any
+    } else {
+        StreamKeyRegistration::Exact(stream_key.to_string())
+    };
+
+    Ok(StepParameters {
+        port,
+        stream_key,
+        ip_restrictions,
+    })
+}
+
+impl SrtReceiverStep {
+    fn handle_srt_publisher_message(
+        &mut self,
+        outputs: &mut StepOutputs,
+        message: SrtEndpointPublisherMessage,
+    ) {
+        match message {
+            SrtEndpointPublisherMessage::PublisherRegistrationFailed => {
+                error!("Srt receive step failed to register for publish registration");
+                self.status = StepStatus::Error {
+                    message: "Srt receive step failed to register for publish registration"
+                        .to_string(),
+                };
+            }
+
+            SrtEndpointPublisherMessage::PublisherRegistrationSuccessful => {
+                info!("Srt receive step successfully registered for publishing");
+                self.status = StepStatus::Active;
+            }
+
+            SrtEndpointPublisherMessage::NewPublisherConnected {
+                connection_id,
+                stream_id,
+                stream_key,
+            } => {
+                info!(
+                    stream_id = ?stream_id,
+                    connection_id = ?connection_id,
+                    stream_key = %stream_key,
+                    "Srt receive step seen new publisher: {:?}, {:?}, {:?}", stream_id, connection_id, stream_key
+                );
+
+                self.connection_stream_ids
+                    .insert(connection_id, stream_id.clone());
+
+                outputs.media.push(MediaNotification {
+                    stream_id,
+                    content: MediaNotificationContent::NewIncomingStream {
+                        stream_name: stream_key,
+                    },
+                    tags: Vec::new(),
+                });
+            }
+
+            SrtEndpointPublisherMessage::PublishingStopped { connection_id } => {
+                if let Some(stream_id) = self.connection_stream_ids.remove(&connection_id) {
+                    info!(
+                        stream_id = ?stream_id,
+                        connection_id = ?connection_id,
+                        "Srt receive step notified that connection {:?} is no longer publishing stream {:?}",
+                        connection_id, stream_id
+                    );
+
+                    outputs.media.push(MediaNotification {
+                        stream_id,
+                        content: MediaNotificationContent::StreamDisconnected,
+                        tags: Vec::new(),
+                    });
+                }
+            }
+
+            SrtEndpointPublisherMessage::NewVideoData {
+                publisher,
+                codec,
+                is_keyframe,
+                is_sequence_header,
+                data,
+                timestamp,
+            } => {
+                if let Some(stream_id) = self.connection_stream_ids.get(&publisher) {
+                    outputs.media.push(MediaNotification {
+                        stream_id: stream_id.clone(),
+                        content: MediaNotificationContent::Video {
+                            codec,
+                            is_keyframe,
+                            is_sequence_header,
+                            data,
+                            timestamp,
+                        },
+                        tags: Vec::new(),
+                    });
+                }
+            }
+
+            SrtEndpointPublisherMessage::NewAudioData {
+                publisher,
+                codec,
+                is_sequence_header,
+                data,
+                timestamp,
+            } => {
+                if let Some(stream_id) = self.connection_stream_ids.get(&publisher) {
+                    outputs.media.push(MediaNotification {
+                        stream_id: stream_id.clone(),
+                        content: MediaNotificationContent::Audio {
+                            codec,
+                            is_sequence_header,
+                            data,
+                            timestamp,
+                        },
+                        tags: Vec::new(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl WorkflowStep for SrtReceiverStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for future_result in inputs.notifications.drain(..) {
+            let future_result = match future_result.downcast::<FutureResult>() {
+                Ok(result) => *result,
+                Err(_) => {
+                    error!("Srt receive step received a notification that is not an 'SrtReceiveFutureResult' type");
+                    self.status = StepStatus::Error {
+                        message: "Srt receive step received a notification that is not an 'SrtReceiveFutureResult' type".to_string(),
+                    };
+
+                    return;
+                }
+            };
+
+            match future_result {
+                FutureResult::SrtEndpointDroppedRegistration => {
+                    if self.status == StepStatus::Shutdown {
+                        return;
+                    }
+
+                    error!(
+                        "Srt receive step stopping as the srt endpoint dropped the registration"
+                    );
+                    self.status = StepStatus::Error {
+                        message:
+                            "Srt receive step stopping as the srt endpoint dropped the registration"
+                                .to_string(),
+                    };
+
+                    return;
+                }
+
+                FutureResult::SrtEndpointResponseReceived(message, receiver) => {
+                    outputs
+                        .futures
+                        .push(wait_for_srt_endpoint_response(receiver).boxed());
+
+                    self.handle_srt_publisher_message(outputs, message);
+                    if let StepStatus::Error { .. } = &self.status {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+        let _ = self
+            .srt_endpoint_sender
+            .send(SrtEndpointRequest::RemoveRegistration {
+                port: self.port,
+                stream_key: self.stream_key.clone(),
+            });
+    }
+}
+
+async fn wait_for_srt_endpoint_response(
+    mut receiver: UnboundedReceiver<SrtEndpointPublisherMessage>,
+) -> Box<dyn StepFutureResult> {
+    let notification = match receiver.recv().await {
+        None => FutureResult::SrtEndpointDroppedRegistration,
+        Some(message) => FutureResult::SrtEndpointResponseReceived(message, receiver),
+    };
+
+    Box::new(notification)
+}
@@ -0,0 +1,420 @@
+use super::*;
+use crate::codecs::{AudioCodec, VideoCodec};
+use crate::workflows::steps::test_utils::create_definition;
+use crate::workflows::steps::StepTestContext;
+use crate::{test_utils, VideoTimestamp};
+use anyhow::Result;
+use bytes::Bytes;
+use std::time::Duration;
+
+struct TestContext {
+    step_context: StepTestContext,
+    srt_endpoint: UnboundedReceiver<SrtEndpointRequest>,
+}
+
+impl TestContext {
+    fn new(definition: WorkflowStepDefinition) -> Result<Self> {
+        let (srt_sender, srt_receiver) = unbounded_channel();
+        let generator = SrtReceiverStepGenerator::new(srt_sender);
+        let step_context = StepTestContext::new(Box::new(generator), definition)?;
+
+        Ok(TestContext {
+            step_context,
+            srt_endpoint: srt_receiver,
+        })
+    }
+
+    async fn accept_registration(&mut self) -> UnboundedSender<SrtEndpointPublisherMessage> {
+        let request = test_utils::expect_mpsc_response(&mut self.srt_endpoint).await;
+        let channel = match request {
+            SrtEndpointRequest::ListenForPublishers {
+                message_channel, ..
+            } => {
+                message_channel
+                    .send(SrtEndpointPublisherMessage::PublisherRegistrationSuccessful)
+                    .expect("Failed to send registration response");
+
+                message_channel
+            }
+
+            request => panic!("Unexpected srt request seen: {:?}", request),
+        };
+
+        self.step_context.execute_pending_notifications().await;
+
+        channel
+    }
+}
+
+fn send_publisher_connected(channel: &UnboundedSender<SrtEndpointPublisherMessage>) {
+    channel
+        .send(SrtEndpointPublisherMessage::NewPublisherConnected {
+            connection_id: ConnectionId("connection".to_string()),
+            stream_id: StreamId("test".to_string()),
+            stream_key: "abc".to_string(),
+        })
+        .expect("Failed to send publisher connected message");
+}
+
+#[tokio::test]
+async fn requests_registration_for_publishers() {
+    let definition = create_definition(
+        "srt_receive",
+        &[
+            (PORT_PROPERTY_NAME, "9000"),
+            (STREAM_KEY_PROPERTY_NAME, "some_key"),
+        ],
+    );
+    let mut context = TestContext::new(definition).unwrap();
+
+    let request = test_utils::expect_mpsc_response(&mut context.srt_endpoint).await;
+    match request {
+        SrtEndpointRequest::ListenForPublishers {
+            port,
+            stream_key,
+            ip_restrictions,
+            ..
+        } => {
+            assert_eq!(port, 9000, "Unexpected port");
+            assert_eq!(
+                stream_key,
+                StreamKeyRegistration::Exact("some_key".to_string()),
+                "Unexpected stream key"
+            );
+            assert_eq!(
+                ip_restrictions,
+                IpRestriction::None,
+                "Unexpected ip restrictions"
+            );
+        }
+
+        request => panic!("Unexpected srt request: {:?}", request),
+    }
+}
+
+#[tokio::test]
+async fn asterisk_stream_key_acts_as_wildcard() {
+    let definition = create_definition(
+        "srt_receive",
+        &[
+            (PORT_PROPERTY_NAME, "9000"),
+            (STREAM_KEY_PROPERTY_NAME, "*"),
+        ],
+    );
+    let mut context = TestContext::new(definition).unwrap();
+
+    let request = test_utils::expect_mpsc_response(&mut context.srt_endpoint).await;
+    match request {
+        SrtEndpointRequest::ListenForPublishers { stream_key, .. } => {
+            assert_eq!(
+                stream_key,
+                StreamKeyRegistration::Any,
+                "Unexpected stream key"
+            );
+        }
+
+        request => panic!("Unexpected srt request: {:?}", request),
+    }
+}
+
+#[test]
+fn error_if_no_port_specified() {
+    let definition = create_definition("srt_receive", &[(STREAM_KEY_PROPERTY_NAME, "abc")]);
+
+    assert!(TestContext::new(definition).is_err(), "Expected failure");
+}
+
+#[test]
+fn error_if_no_key_specified() {
+    let definition = create_definition("srt_receive", &[(PORT_PROPERTY_NAME, "9000")]);
+
+    assert!(TestContext::new(definition).is_err(), "Expected failure");
+}
+
+#[test]
+fn error_if_both_allow_and_deny_ips_specified() {
+    let mut definition = create_definition(
+        "srt_receive",
+        &[
+            (PORT_PROPERTY_NAME, "9000"),
+            (STREAM_KEY_PROPERTY_NAME, "abc"),
+        ],
+    );
+    definition.parameters.insert(
+        IP_ALLOW_PROPERTY_NAME.to_string(),
+        Some("127.0.0.1".to_string()),
+    );
+    definition.parameters.insert(
+        IP_DENY_PROPERTY_NAME.to_string(),
+        Some("127.0.0.2".to_string()),
+    );
+
+    assert!(TestContext::new(definition).is_err(), "Expected failure");
+}
+
+#[test]
+fn validation_fails_for_non_numeric_port() {
+    let definition = create_definition(
+        "srt_receive",
+        &[
+            (PORT_PROPERTY_NAME, "abc"),
+            (STREAM_KEY_PROPERTY_NAME, "abc"),
+        ],
+    );
+    let generator = SrtReceiverStepGenerator::new(unbounded_channel().0);
+
+    assert!(generator.validate(&definition).is_err(), "Expected failure");
+}
+
+#[test]
+fn validation_does_not_register_with_srt_endpoint() {
+    let definition = create_definition(
+        "srt_receive",
+        &[
+            (PORT_PROPERTY_NAME, "9000"),
+            (STREAM_KEY_PROPERTY_NAME, "abc"),
+        ],
+    );
+    let (srt_sender, mut srt_receiver) = unbounded_channel();
+    let generator = SrtReceiverStepGenerator::new(srt_sender);
+
+    generator.validate(&definition).unwrap();
+    assert!(
+        srt_receiver.try_recv().is_err(),
+        "Expected no srt endpoint requests"
+    );
+}
+
+#[tokio::test]
+async fn registration_success_sets_status_to_active() {
+    let definition = create_definition(
+        "srt_receive",
+        &[
+            (PORT_PROPERTY_NAME, "9000"),
+            (STREAM_KEY_PROPERTY_NAME, "abc"),
+        ],
+    );
+    let mut context = TestContext::new(definition).unwrap();
+    let _channel = context.accept_registration().await;
+
+    let status = context.step_context.step.get_status();
+    assert_eq!(status, &StepStatus::Active, "Unexpected step status");
+}
+
+#[tokio::test]
+async fn registration_failure_sets_status_to_error() {
+    let definition = create_definition(
+        "srt_receive",
+        &[
+            (PORT_PROPERTY_NAME, "9000"),
+            (STREAM_KEY_PROPERTY_NAME, "abc"),
+        ],
+    );
+    let mut context = TestContext::new(definition).unwrap();
+
+    let request = test_utils::expect_mpsc_response(&mut context.srt_endpoint).await;
+    let _channel = match request {
+        SrtEndpointRequest::ListenForPublishers {
+            message_channel, ..
+        } => {
+            message_channel
+                .send(SrtEndpointPublisherMessage::PublisherRegistrationFailed)
+                .expect("Failed to send registration response");
+
+            message_channel
+        }
+
+        request => panic!("Unexpected srt request seen: {:?}", request),
+    };
+
+    context.step_context.execute_pending_notifications().await;
+
+    let status = context.step_context.step.get_status();
+    match status {
+        StepStatus::Error { .. } => (),
+        status => panic!("Unexpected status: {:?}", status),
+    }
+}
+
+#[tokio::test]
+async fn new_publisher_raises_new_incoming_stream() {
+    let definition = create_definition(
+        "srt_receive",
+        &[
+            (PORT_PROPERTY_NAME, "9000"),
+            (STREAM_KEY_PROPERTY_NAME, "abc"),
+        ],
+    );
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    send_publisher_connected(&channel);
+    context.step_context.execute_pending_notifications().await;
+
+    assert_eq!(
+        context.step_context.media_outputs.len(),
+        1,
+        "Unexpected number of media outputs"
+    );
+
+    let media = &context.step_context.media_outputs[0];
+    assert_eq!(
+        media.stream_id,
+        StreamId("test".to_string()),
+        "Unexpected stream id"
+    );
+    match &media.content {
+        MediaNotificationContent::NewIncomingStream { stream_name } => {
+            assert_eq!(stream_name, "abc", "Unexpected stream name");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[tokio::test]
+async fn publisher_media_passed_as_media_output() {
+    let definition = create_definition(
+        "srt_receive",
+        &[
+            (PORT_PROPERTY_NAME, "9000"),
+            (STREAM_KEY_PROPERTY_NAME, "abc"),
+        ],
+    );
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    send_publisher_connected(&channel);
+    context.step_context.execute_pending_notifications().await;
+
+    channel
+        .send(SrtEndpointPublisherMessage::NewVideoData {
+            publisher: ConnectionId("connection".to_string()),
+            codec: VideoCodec::H264,
+            is_keyframe: true,
+            is_sequence_header: false,
+            data: Bytes::from(vec![1, 2, 3]),
+            timestamp: VideoTimestamp::from_durations(
+                Duration::from_millis(5),
+                Duration::from_millis(10),
+            ),
+        })
+        .expect("Failed to send video");
+
+    context.step_context.execute_pending_notifications().await;
+
+    assert_eq!(
+        context.step_context.media_outputs.len(),
+        1,
+        "Unexpected number of media outputs"
+    );
+
+    match &context.step_context.media_outputs[0].content {
+        MediaNotificationContent::Video {
+            codec,
+            is_keyframe,
+            is_sequence_header,
+            data,
+            timestamp,
+        } => {
+            assert_eq!(codec, &VideoCodec::H264, "Unexpected codec");
+            assert!(is_keyframe, "Expected keyframe");
+            assert!(!is_sequence_header, "Expected non-sequence header");
+            assert_eq!(data, &Bytes::from(vec![1, 2, 3]), "Unexpected data");
+            assert_eq!(timestamp.dts(), Duration::from_millis(5), "Unexpected dts");
+            assert_eq!(timestamp.pts(), Duration::from_millis(10), "Unexpected pts");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+
+    channel
+        .send(SrtEndpointPublisherMessage::NewAudioData {
+            publisher: ConnectionId("connection".to_string()),
+            codec: AudioCodec::Aac,
+            is_sequence_header: false,
+            data: Bytes::from(vec![4, 5, 6]),
+            timestamp: Duration::from_millis(7),
+        })
+        .expect("Failed to send audio");
+
+    context.step_context.execute_pending_notifications().await;
+
+    assert_eq!(
+        context.step_context.media_outputs.len(),
+        1,
+        "Unexpected number of media outputs"
+    );
+
+    match &context.step_context.media_outputs[0].content {
+        MediaNotificationContent::Audio {
+            codec,
+            data,
+            timestamp,
+            ..
+        } => {
+            assert_eq!(codec, &AudioCodec::Aac, "Unexpected codec");
+            assert_eq!(data, &Bytes::from(vec![4, 5, 6]), "Unexpected data");
+            assert_eq!(timestamp, &Duration::from_millis(7), "Unexpected timestamp");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[tokio::test]
+async fn publishing_stopped_raises_stream_disconnected() {
+    let definition = create_definition(
+        "srt_receive",
+        &[
+            (PORT_PROPERTY_NAME, "9000"),
+            (STREAM_KEY_PROPERTY_NAME, "abc"),
+        ],
+    );
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    send_publisher_connected(&channel);
+    context.step_context.execute_pending_notifications().await;
+
+    channel
+        .send(SrtEndpointPublisherMessage::PublishingStopped {
+            connection_id: ConnectionId("connection".to_string()),
+        })
+        .expect("Failed to send publishing stopped");
+
+    context.step_context.execute_pending_notifications().await;
+
+    assert_eq!(
+        context.step_context.media_outputs.len(),
+        1,
+        "Unexpected number of media outputs"
+    );
+
+    match &context.step_context.media_outputs[0].content {
+        MediaNotificationContent::StreamDisconnected => (),
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[tokio::test]
+async fn dropped_registration_sets_status_to_error() {
+    let definition = create_definition(
+        "srt_receive",
+        &[
+            (PORT_PROPERTY_NAME, "9000"),
+            (STREAM_KEY_PROPERTY_NAME, "abc"),
+        ],
+    );
+    let mut context = TestContext::new(definition).unwrap();
+    let channel = context.accept_registration().await;
+
+    drop(channel);
+    context.step_context.execute_pending_notifications().await;
+
+    let status = context.step_context.step.get_status();
+    match status {
+        StepStatus::Error { .. } => (),
+        status => panic!("Unexpected status: {:?}", status),
+    }
+}
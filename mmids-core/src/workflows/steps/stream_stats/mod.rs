@@ -0,0 +1,205 @@
+//! The stream stats step observes media passing through it and tracks the rolling bitrate,
+//! framerate, and keyframe interval of each stream.  All media notifications are passed to
+//! subsequent steps unchanged.
+//!
+//! Statistics are calculated when the step is asked for them by its workflow, which allows them to
+//! be queried through the workflow manager.
+
+#[cfg(test)]
+mod tests;
+
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::{
+    StepCreationResult, StepInputs, StepOutputs, StepStatus, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// How far back media is considered when calculating bitrates and framerates
+const ROLLING_WINDOW: Duration = Duration::from_secs(5);
+
+/// Statistics about a single stream that passed through a stream stats step
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamStatistics {
+    /// The identifier of the stream stats step that's tracking this stream
+    pub step_id: u64,
+
+    pub stream_id: StreamId,
+    pub stream_name: Option<String>,
+    pub video_bitrate_kbps: u64,
+    pub audio_bitrate_kbps: u64,
+    pub video_frames_per_second: f64,
+
+    /// The time between the two most recent video keyframes
+    pub keyframe_interval: Option<Duration>,
+}
+
+/// Generates new stream stats steps
+pub struct StreamStatsStepGenerator {}
+
+struct StreamStatsStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    streams: HashMap<StreamId, StreamTracker>,
+}
+
+#[derive(Default)]
+struct StreamTracker {
+    stream_name: Option<String>,
+    video_packets: VecDeque<(Duration, usize)>,
+    audio_packets: VecDeque<(Duration, usize)>,
+    last_keyframe: Option<Duration>,
+    keyframe_interval: Option<Duration>,
+}
+
+impl StreamStatsStepGenerator {
+    pub fn new() -> Self {
+        StreamStatsStepGenerator {}
+    }
+}
+
+impl StepGenerator for StreamStatsStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let step = StreamStatsStep {
+            definition,
+            status: StepStatus::Active,
+            streams: HashMap::new(),
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+}
+
+impl StreamStatsStep {
+    fn track_media(&mut self, media: &MediaNotification) {
+        match &media.content {
+            MediaNotificationContent::NewIncomingStream { stream_name } => {
+                let tracker = StreamTracker {
+                    stream_name: Some(stream_name.clone()),
+                    ..Default::default()
+                };
+
+                self.streams.insert(media.stream_id.clone(), tracker);
+            }
+
+            MediaNotificationContent::StreamDisconnected => {
+                self.streams.remove(&media.stream_id);
+            }
+
+            MediaNotificationContent::Video {
+                is_sequence_header: false,
+                is_keyframe,
+                data,
+                timestamp,
+                ..
+            } => {
+                let tracker = self.streams.entry(media.stream_id.clone()).or_default();
+                let dts = timestamp.dts();
+                add_packet(&mut tracker.video_packets, dts, data.len());
+
+                if *is_keyframe {
+                    if let Some(last_keyframe) = tracker.last_keyframe {
+                        tracker.keyframe_interval = dts.checked_sub(last_keyframe);
+                    }
+
+                    tracker.last_keyframe = Some(dts);
+                }
+            }
+
+            MediaNotificationContent::Audio {
+                is_sequence_header: false,
+                data,
+                timestamp,
+                ..
+            } => {
+                let tracker = self.streams.entry(media.stream_id.clone()).or_default();
+                add_packet(&mut tracker.audio_packets, *timestamp, data.len());
+            }
+
+            _ => (),
+        }
+    }
+}
+
+impl WorkflowStep for StreamStatsStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for media in inputs.media.drain(..) {
+            self.track_media(&media);
+            outputs.media.push(media);
+        }
+    }
+
+    fn get_stream_statistics(&self) -> Vec<StreamStatistics> {
+        let step_id = self.definition.get_id();
+        self.streams
+            .iter()
+            .map(|(stream_id, tracker)| StreamStatistics {
+                step_id,
+                stream_id: stream_id.clone(),
+                stream_name: tracker.stream_name.clone(),
+                video_bitrate_kbps: get_bitrate_kbps(&tracker.video_packets),
+                audio_bitrate_kbps: get_bitrate_kbps(&tracker.audio_packets),
+                video_frames_per_second: get_packets_per_second(&tracker.video_packets),
+                keyframe_interval: tracker.keyframe_interval,
+            })
+            .collect()
+    }
+
+    fn shutdown(&mut self) {
+        self.streams.clear();
+        self.status = StepStatus::Shutdown;
+    }
+}
+
+fn add_packet(packets: &mut VecDeque<(Duration, usize)>, timestamp: Duration, size: usize) {
+    packets.push_back((timestamp, size));
+    while let Some((oldest, _)) = packets.front() {
+        if timestamp.saturating_sub(*oldest) > ROLLING_WINDOW {
+            packets.pop_front();
+        } else {
+            break;
+        }
+    }
+}
+
+fn get_window_length(packets: &VecDeque<(Duration, usize)>) -> Option<Duration> {
+    match (packets.front(), packets.back()) {
+        (Some((first, _)), Some((last, _))) if last > first => Some(*last - *first),
+        _ => None,
+    }
+}
+
+fn get_bitrate_kbps(packets: &VecDeque<(Duration, usize)>) -> u64 {
+    match get_window_length(packets) {
+        Some(length) => {
+            // The last packet marks the end of the window, so it's not counted as part of it
+            let bits = packets
+                .iter()
+                .take(packets.len() - 1)
+                .map(|(_, size)| *size as u64 * 8)
+                .sum::<u64>();
+
+            (bits as f64 / length.as_secs_f64() / 1000.0) as u64
+        }
+
+        None => 0,
+    }
+}
+
+fn get_packets_per_second(packets: &VecDeque<(Duration, usize)>) -> f64 {
+    match get_window_length(packets) {
+        Some(length) => (packets.len() - 1) as f64 / length.as_secs_f64(),
+        None => 0.0,
+    }
+}
@@ -0,0 +1,176 @@
+use super::*;
+use crate::codecs::{AudioCodec, VideoCodec};
+use crate::workflows::steps::test_utils::create_definition;
+use crate::workflows::steps::StepTestContext;
+use crate::VideoTimestamp;
+use bytes::Bytes;
+
+struct TestContext {
+    step_context: StepTestContext,
+}
+
+impl TestContext {
+    fn new() -> Self {
+        let generator = StreamStatsStepGenerator::new();
+        let definition = create_definition("stream_stats", &[]);
+
+        let step_context = StepTestContext::new(Box::new(generator), definition).unwrap();
+
+        TestContext { step_context }
+    }
+
+    fn get_statistics(&self) -> Vec<StreamStatistics> {
+        self.step_context.step.get_stream_statistics()
+    }
+
+    fn start_stream(&mut self) {
+        self.step_context.execute_with_media(MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: MediaNotificationContent::NewIncomingStream {
+                stream_name: "def".to_string(),
+            },
+            tags: Vec::new(),
+        });
+    }
+
+    fn send_video(&mut self, dts_millis: u64, size: usize, is_keyframe: bool) {
+        self.step_context.execute_with_media(MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: MediaNotificationContent::Video {
+                data: Bytes::from(vec![0; size]),
+                codec: VideoCodec::H264,
+                timestamp: VideoTimestamp::from_durations(
+                    Duration::from_millis(dts_millis),
+                    Duration::from_millis(dts_millis),
+                ),
+                is_keyframe,
+                is_sequence_header: false,
+            },
+            tags: Vec::new(),
+        });
+    }
+
+    fn send_audio(&mut self, timestamp_millis: u64, size: usize) {
+        self.step_context.execute_with_media(MediaNotification {
+            stream_id: StreamId("abc".to_string()),
+            content: MediaNotificationContent::Audio {
+                data: Bytes::from(vec![0; size]),
+                codec: AudioCodec::Aac,
+                timestamp: Duration::from_millis(timestamp_millis),
+                is_sequence_header: false,
+            },
+            tags: Vec::new(),
+        });
+    }
+}
+
+#[test]
+fn media_passed_through_unchanged() {
+    let mut context = TestContext::new();
+    context.send_video(0, 10, true);
+
+    assert_eq!(
+        context.step_context.media_outputs.len(),
+        1,
+        "Unexpected number of media outputs"
+    );
+
+    match &context.step_context.media_outputs[0].content {
+        MediaNotificationContent::Video {
+            data, is_keyframe, ..
+        } => {
+            assert_eq!(data.len(), 10, "Unexpected data length");
+            assert!(is_keyframe, "Expected is_keyframe to be true");
+        }
+
+        content => panic!("Unexpected media content: {:?}", content),
+    }
+}
+
+#[test]
+fn video_bitrate_and_framerate_calculated() {
+    let mut context = TestContext::new();
+    context.start_stream();
+
+    // 1000 bytes every 100ms is 80kbps at 10 frames per second
+    for x in 0..=10 {
+        context.send_video(x * 100, 1000, false);
+    }
+
+    let statistics = context.get_statistics();
+    assert_eq!(statistics.len(), 1, "Unexpected number of streams");
+    assert_eq!(statistics[0].stream_id.0, "abc", "Unexpected stream id");
+    assert_eq!(
+        statistics[0].stream_name,
+        Some("def".to_string()),
+        "Unexpected stream name"
+    );
+    assert_eq!(
+        statistics[0].video_bitrate_kbps, 80,
+        "Unexpected video bitrate"
+    );
+    assert_eq!(
+        statistics[0].video_frames_per_second, 10.0,
+        "Unexpected framerate"
+    );
+}
+
+#[test]
+fn audio_bitrate_calculated() {
+    let mut context = TestContext::new();
+    context.start_stream();
+
+    // 500 bytes every 50ms is 80kbps
+    for x in 0..=10 {
+        context.send_audio(x * 50, 500);
+    }
+
+    let statistics = context.get_statistics();
+    assert_eq!(statistics.len(), 1, "Unexpected number of streams");
+    assert_eq!(
+        statistics[0].audio_bitrate_kbps, 80,
+        "Unexpected audio bitrate"
+    );
+}
+
+#[test]
+fn keyframe_interval_calculated() {
+    let mut context = TestContext::new();
+    context.start_stream();
+    context.send_video(0, 10, true);
+    context.send_video(1000, 10, false);
+    context.send_video(2000, 10, true);
+
+    let statistics = context.get_statistics();
+    assert_eq!(statistics.len(), 1, "Unexpected number of streams");
+    assert_eq!(
+        statistics[0].keyframe_interval,
+        Some(Duration::from_secs(2)),
+        "Unexpected keyframe interval"
+    );
+}
+
+#[test]
+fn statistics_removed_when_stream_disconnects() {
+    let mut context = TestContext::new();
+    context.start_stream();
+    context.send_video(0, 10, true);
+    context.send_video(100, 10, false);
+
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
+    });
+
+    assert!(
+        context.get_statistics().is_empty(),
+        "Expected no stream statistics"
+    );
+
+    assert_eq!(
+        context.step_context.media_outputs.len(),
+        1,
+        "Expected disconnection to be passed through"
+    );
+}
@@ -0,0 +1,105 @@
+//! The tag step labels all media that passes through it with a branch name.  Since workflows are
+//! a linear set of steps, this allows a later `filter` step to only pass along media that was
+//! tagged with a specific branch, which enables simple fan-out within a single workflow.
+//!
+//! All media is passed along to the next step, with the branch added to its tags if it was not
+//! already present.
+
+#[cfg(test)]
+mod tests;
+
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::filter::ANY_BRANCH;
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use thiserror::Error;
+
+const BRANCH: &str = "branch";
+
+/// Generates new instances of the tag workflow step based on specified step definitions.
+pub struct TagStepGenerator {}
+
+struct TagStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    branch: String,
+}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error("No branch specified.  A 'branch' parameter is required")]
+    NoBranchProvided,
+
+    #[error("The branch name '{0}' is reserved and can not be used as a tag")]
+    ReservedBranchName(String),
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl TagStepGenerator {
+    pub fn new() -> Self {
+        TagStepGenerator {}
+    }
+}
+
+impl StepGenerator for TagStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let branch = parse_branch(&definition)?;
+        let step = TagStep {
+            definition,
+            status: StepStatus::Active,
+            branch,
+        };
+
+        Ok((Box::new(step), Vec::new()))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_branch(definition)?;
+        Ok(())
+    }
+}
+
+fn parse_branch(definition: &WorkflowStepDefinition) -> Result<String, StepStartupError> {
+    let branch = match definition.parameters.get(BRANCH) {
+        Some(Some(value)) => value.to_string(),
+        _ => return Err(StepStartupError::NoBranchProvided),
+    };
+
+    if branch == ANY_BRANCH {
+        return Err(StepStartupError::ReservedBranchName(branch));
+    }
+
+    Ok(branch)
+}
+
+impl WorkflowStep for TagStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for mut media in inputs.media.drain(..) {
+            if !media.tags.contains(&self.branch) {
+                media.tags.push(self.branch.clone());
+            }
+
+            outputs.media.push(media);
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+    }
+}
@@ -0,0 +1,76 @@
+use super::*;
+use crate::workflows::steps::test_utils::create_definition;
+use crate::workflows::steps::StepTestContext;
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::StreamId;
+
+fn create_media(tags: Vec<String>) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        tags,
+    }
+}
+
+#[test]
+fn step_fails_to_generate_without_branch() {
+    let generator = TagStepGenerator::new();
+    assert!(generator.generate(create_definition("tag", &[])).is_err());
+}
+
+#[test]
+fn step_fails_to_generate_with_any_branch() {
+    let generator = TagStepGenerator::new();
+    assert!(generator
+        .generate(create_definition("tag", &[(BRANCH, "any")]))
+        .is_err());
+}
+
+#[test]
+fn validation_fails_with_any_branch() {
+    let generator = TagStepGenerator::new();
+    assert!(generator
+        .validate(&create_definition("tag", &[(BRANCH, "any")]))
+        .is_err());
+}
+
+#[test]
+fn validation_passes_with_branch() {
+    let generator = TagStepGenerator::new();
+    assert!(generator
+        .validate(&create_definition("tag", &[(BRANCH, "hls")]))
+        .is_ok());
+}
+
+#[test]
+fn branch_added_to_media_tags() {
+    let generator = TagStepGenerator::new();
+    let mut context = StepTestContext::new(
+        Box::new(generator),
+        create_definition("tag", &[(BRANCH, "hls")]),
+    )
+    .unwrap();
+
+    context.execute_with_media(create_media(vec!["other".to_string()]));
+
+    assert_eq!(context.media_outputs.len(), 1, "Expected one media output");
+    assert_eq!(
+        context.media_outputs[0].tags,
+        vec!["other".to_string(), "hls".to_string()],
+        "Unexpected tags"
+    );
+}
+
+#[test]
+fn branch_not_duplicated_if_media_already_tagged() {
+    let generator = TagStepGenerator::new();
+    let mut context = StepTestContext::new(
+        Box::new(generator),
+        create_definition("tag", &[(BRANCH, "hls")]),
+    )
+    .unwrap();
+
+    context.assert_media_passed_through(create_media(vec!["hls".to_string()]));
+}
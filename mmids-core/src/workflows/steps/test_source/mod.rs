@@ -0,0 +1,348 @@
+//! The test source step synthesizes a single media stream in memory, so workflows and the steps
+//! that consume media can be exercised without a real publisher.  The stream is announced, its
+//! sequence headers are sent, and then a video frame is produced for every frame period with
+//! audio interleaved so it never falls behind the video.  Every `keyframe_interval` video frames
+//! is a keyframe.  When the step is asked for a keyframe of its stream (such as by an
+//! `rtmp_watch` step when a new watcher joins) the next video frame is made a keyframe, and the
+//! keyframe interval restarts from it.  If a duration is given the stream is disconnected once that much media has
+//! been produced.
+//!
+//! Timestamps are derived from the number of frames produced and not the wall clock, so the
+//! media is the same for every run.  Media payloads are placeholders and are not decodable.
+//!
+//! This step is only available in tests, or when the `test-source` feature is enabled.  Any
+//! media passed into the step is passed through unchanged.
+
+#[cfg(test)]
+mod tests;
+
+use crate::codecs::{AudioCodec, VideoCodec};
+use crate::workflows::definitions::WorkflowStepDefinition;
+use crate::workflows::steps::factory::{StepGenerator, StepKind};
+use crate::workflows::steps::{
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
+};
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::{StreamId, VideoTimestamp};
+use bytes::Bytes;
+use futures::FutureExt;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{error, info};
+use uuid::Uuid;
+
+pub const FPS: &str = "fps";
+pub const KEYFRAME_INTERVAL: &str = "keyframe_interval";
+pub const DURATION_SECONDS: &str = "duration_seconds";
+pub const STREAM_NAME: &str = "stream_name";
+
+const DEFAULT_FPS: u32 = 30;
+const DEFAULT_KEYFRAME_INTERVAL: u32 = 60;
+const DEFAULT_STREAM_NAME: &str = "test";
+
+/// Audio is produced as AAC frames of 1024 samples at 48khz
+const AUDIO_SAMPLE_RATE: u64 = 48000;
+const SAMPLES_PER_AUDIO_FRAME: u64 = 1024;
+
+/// An AAC-LC, 48khz, stereo audio specific config
+const AUDIO_SEQUENCE_HEADER: [u8; 2] = [0x11, 0x90];
+const VIDEO_SEQUENCE_HEADER: [u8; 4] = [0x01, 0x64, 0x00, 0x1f];
+
+/// Generates new instances of the test source workflow step
+pub struct TestSourceStepGenerator {}
+
+struct TestSourceStep {
+    definition: WorkflowStepDefinition,
+    status: StepStatus,
+    parameters: StepParameters,
+    stream_id: Option<StreamId>,
+    video_frames_sent: u64,
+    audio_frames_sent: u64,
+    next_keyframe: u64,
+    keyframe_requested: bool,
+    finished: bool,
+}
+
+struct StepParameters {
+    fps: u32,
+    keyframe_interval: u32,
+    duration: Option<Duration>,
+    stream_name: String,
+}
+
+enum FutureResult {
+    NextFrameDue,
+}
+
+impl StepFutureResult for FutureResult {}
+
+#[derive(Error, Debug)]
+enum StepStartupError {
+    #[error("Invalid fps of '{0}'.  A positive whole number is required")]
+    InvalidFps(String),
+
+    #[error("Invalid keyframe interval of '{0}'.  A positive whole number of frames is required")]
+    InvalidKeyframeInterval(String),
+
+    #[error("Invalid duration of '{0}'.  A positive whole number of seconds is required")]
+    InvalidDuration(String),
+}
+
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
+impl TestSourceStepGenerator {
+    pub fn new() -> Self {
+        TestSourceStepGenerator {}
+    }
+}
+
+impl StepGenerator for TestSourceStepGenerator {
+    fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
+        let parameters = parse_parameters(&definition)?;
+        let step = TestSourceStep {
+            definition,
+            status: StepStatus::Active,
+            parameters,
+            stream_id: None,
+            video_frames_sent: 0,
+            audio_frames_sent: 0,
+            next_keyframe: 0,
+            keyframe_requested: false,
+            finished: false,
+        };
+
+        let futures = vec![wait_for_next_frame(Duration::from_secs(0)).boxed()];
+
+        Ok((Box::new(step), futures))
+    }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_parameters(definition)?;
+        Ok(())
+    }
+
+    fn kind(&self) -> StepKind {
+        StepKind::Source
+    }
+}
+
+fn parse_parameters(
+    definition: &WorkflowStepDefinition,
+) -> Result<StepParameters, StepStartupError> {
+    let parse_positive = |name: &str| match definition.parameters.get(name) {
+        Some(Some(value)) => match value.parse::<u32>() {
+            Ok(num) if num > 0 => Ok(Some(num)),
+            _ => Err(value.clone()),
+        },
+
+        _ => Ok(None),
+    };
+
+    let fps = parse_positive(FPS)
+        .map_err(StepStartupError::InvalidFps)?
+        .unwrap_or(DEFAULT_FPS);
+
+    let keyframe_interval = parse_positive(KEYFRAME_INTERVAL)
+        .map_err(StepStartupError::InvalidKeyframeInterval)?
+        .unwrap_or(DEFAULT_KEYFRAME_INTERVAL);
+
+    let duration = parse_positive(DURATION_SECONDS)
+        .map_err(StepStartupError::InvalidDuration)?
+        .map(|seconds| Duration::from_secs(seconds as u64));
+
+    let stream_name = match definition.parameters.get(STREAM_NAME) {
+        Some(Some(name)) => name.clone(),
+        _ => DEFAULT_STREAM_NAME.to_string(),
+    };
+
+    Ok(StepParameters {
+        fps,
+        keyframe_interval,
+        duration,
+        stream_name,
+    })
+}
+
+impl TestSourceStep {
+    fn frame_duration(&self) -> Duration {
+        Duration::from_secs(1) / self.parameters.fps
+    }
+
+    fn video_timestamp(&self, frame: u64) -> Duration {
+        Duration::from_micros(frame * 1_000_000 / self.parameters.fps as u64)
+    }
+
+    fn audio_timestamp(&self, frame: u64) -> Duration {
+        Duration::from_micros(frame * SAMPLES_PER_AUDIO_FRAME * 1_000_000 / AUDIO_SAMPLE_RATE)
+    }
+
+    fn produce_next_frame(&mut self, outputs: &mut StepOutputs) {
+        let stream_id = match &self.stream_id {
+            Some(stream_id) => stream_id.clone(),
+            None => {
+                let stream_id = StreamId(Uuid::new_v4().to_string());
+                info!(
+                    stream_id = ?stream_id,
+                    "Test source starting stream {:?} named '{}'",
+                    stream_id, self.parameters.stream_name
+                );
+
+                self.start_stream(stream_id.clone(), outputs);
+                self.stream_id = Some(stream_id.clone());
+                stream_id
+            }
+        };
+
+        let video_timestamp = self.video_timestamp(self.video_frames_sent);
+        if let Some(duration) = self.parameters.duration {
+            if video_timestamp >= duration {
+                info!(
+                    stream_id = ?stream_id,
+                    "Test source finished stream {:?}", stream_id
+                );
+
+                outputs.media.push(MediaNotification {
+                    stream_id,
+                    content: MediaNotificationContent::StreamDisconnected,
+                    tags: Vec::new(),
+                });
+
+                self.finished = true;
+                return;
+            }
+        }
+
+        // Send all audio up to this video frame, so neither type of media falls behind
+        while self.audio_timestamp(self.audio_frames_sent) <= video_timestamp {
+            outputs.media.push(MediaNotification {
+                stream_id: stream_id.clone(),
+                content: MediaNotificationContent::Audio {
+                    codec: AudioCodec::Aac,
+                    is_sequence_header: false,
+                    data: Bytes::from(vec![0; 100]),
+                    timestamp: self.audio_timestamp(self.audio_frames_sent),
+                },
+                tags: Vec::new(),
+            });
+
+            self.audio_frames_sent += 1;
+        }
+
+        let is_keyframe = self.keyframe_requested || self.video_frames_sent >= self.next_keyframe;
+        if is_keyframe {
+            self.keyframe_requested = false;
+            self.next_keyframe = self.video_frames_sent + self.parameters.keyframe_interval as u64;
+        }
+
+        outputs.media.push(MediaNotification {
+            stream_id,
+            content: MediaNotificationContent::Video {
+                codec: VideoCodec::H264,
+                is_sequence_header: false,
+                is_keyframe,
+                data: Bytes::from(vec![0; if is_keyframe { 1000 } else { 100 }]),
+                timestamp: VideoTimestamp::from_durations(video_timestamp, video_timestamp),
+            },
+            tags: Vec::new(),
+        });
+
+        self.video_frames_sent += 1;
+        outputs
+            .futures
+            .push(wait_for_next_frame(self.frame_duration()).boxed());
+    }
+
+    fn start_stream(&self, stream_id: StreamId, outputs: &mut StepOutputs) {
+        outputs.media.push(MediaNotification {
+            stream_id: stream_id.clone(),
+            content: MediaNotificationContent::NewIncomingStream {
+                stream_name: self.parameters.stream_name.clone(),
+            },
+            tags: Vec::new(),
+        });
+
+        outputs.media.push(MediaNotification {
+            stream_id: stream_id.clone(),
+            content: MediaNotificationContent::Video {
+                codec: VideoCodec::H264,
+                is_sequence_header: true,
+                is_keyframe: true,
+                data: Bytes::from_static(&VIDEO_SEQUENCE_HEADER),
+                timestamp: VideoTimestamp::from_zero(),
+            },
+            tags: Vec::new(),
+        });
+
+        outputs.media.push(MediaNotification {
+            stream_id,
+            content: MediaNotificationContent::Audio {
+                codec: AudioCodec::Aac,
+                is_sequence_header: true,
+                data: Bytes::from_static(&AUDIO_SEQUENCE_HEADER),
+                timestamp: Duration::from_secs(0),
+            },
+            tags: Vec::new(),
+        });
+    }
+}
+
+impl WorkflowStep for TestSourceStep {
+    fn get_status(&self) -> &StepStatus {
+        &self.status
+    }
+
+    fn get_definition(&self) -> &WorkflowStepDefinition {
+        &self.definition
+    }
+
+    fn execute(&mut self, inputs: &mut StepInputs, outputs: &mut StepOutputs) {
+        for notification in inputs.notifications.drain(..) {
+            match notification.downcast::<FutureResult>() {
+                Ok(result) => match *result {
+                    FutureResult::NextFrameDue => {
+                        if !self.finished && self.status == StepStatus::Active {
+                            self.produce_next_frame(outputs);
+                        }
+                    }
+                },
+
+                Err(_) => {
+                    error!("Test source step received a notification that is not a known type");
+                    self.status = StepStatus::Error {
+                        message: "Received future result of unknown type".to_string(),
+                    };
+
+                    return;
+                }
+            }
+        }
+
+        for media in inputs.media.drain(..) {
+            outputs.media.push(media);
+        }
+    }
+
+    fn request_keyframe(&mut self, stream_id: &StreamId) -> bool {
+        if self.finished || self.stream_id.as_ref() != Some(stream_id) {
+            return false;
+        }
+
+        self.keyframe_requested = true;
+        true
+    }
+
+    fn shutdown(&mut self) {
+        self.status = StepStatus::Shutdown;
+    }
+}
+
+async fn wait_for_next_frame(delay: Duration) -> Box<dyn StepFutureResult> {
+    tokio::time::sleep(delay).await;
+
+    Box::new(FutureResult::NextFrameDue)
+}
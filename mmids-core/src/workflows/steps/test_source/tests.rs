@@ -0,0 +1,308 @@
+use super::*;
+use crate::test_utils;
+use crate::workflows::steps::test_utils::create_definition;
+use crate::workflows::steps::StepTestContext;
+
+fn create_context(parameters: &[(&str, &str)]) -> StepTestContext {
+    let generator = TestSourceStepGenerator::new();
+    StepTestContext::new(
+        Box::new(generator),
+        create_definition("test_source", parameters),
+    )
+    .unwrap()
+}
+
+/// Executes the step as if its frame timer had fired, returning the media it output.  This is
+/// used instead of waiting on the step's timer so tests don't depend on wall clock time.
+fn tick(context: &mut StepTestContext) -> Vec<MediaNotification> {
+    let mut inputs = StepInputs::new();
+    let mut outputs = StepOutputs::new();
+    inputs
+        .notifications
+        .push(Box::new(FutureResult::NextFrameDue));
+
+    context.step.execute(&mut inputs, &mut outputs);
+    outputs.media
+}
+
+fn video_frames(media: &[MediaNotification]) -> Vec<(Duration, bool)> {
+    media
+        .iter()
+        .filter_map(|media| match &media.content {
+            MediaNotificationContent::Video {
+                is_sequence_header: false,
+                is_keyframe,
+                timestamp,
+                ..
+            } => Some((timestamp.dts(), *is_keyframe)),
+
+            _ => None,
+        })
+        .collect()
+}
+
+fn audio_timestamps(media: &[MediaNotification]) -> Vec<Duration> {
+    media
+        .iter()
+        .filter_map(|media| match &media.content {
+            MediaNotificationContent::Audio {
+                is_sequence_header: false,
+                timestamp,
+                ..
+            } => Some(*timestamp),
+
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn generator_is_a_source() {
+    let generator = TestSourceStepGenerator::new();
+    assert_eq!(generator.kind(), StepKind::Source, "Unexpected step kind");
+}
+
+#[test]
+fn validation_passes_without_parameters() {
+    let generator = TestSourceStepGenerator::new();
+    assert!(generator
+        .validate(&create_definition("test_source", &[]))
+        .is_ok());
+}
+
+#[test]
+fn validation_fails_for_zero_fps() {
+    let generator = TestSourceStepGenerator::new();
+    assert!(generator
+        .validate(&create_definition("test_source", &[(FPS, "0")]))
+        .is_err());
+}
+
+#[test]
+fn validation_fails_for_non_numeric_keyframe_interval() {
+    let generator = TestSourceStepGenerator::new();
+    assert!(generator
+        .validate(&create_definition(
+            "test_source",
+            &[(KEYFRAME_INTERVAL, "abc")]
+        ))
+        .is_err());
+}
+
+#[test]
+fn validation_fails_for_zero_duration() {
+    let generator = TestSourceStepGenerator::new();
+    assert!(generator
+        .validate(&create_definition(
+            "test_source",
+            &[(DURATION_SECONDS, "0")]
+        ))
+        .is_err());
+}
+
+#[test]
+fn media_passed_through() {
+    let mut context = create_context(&[]);
+    context.assert_media_passed_through(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
+    });
+}
+
+#[tokio::test]
+async fn first_frame_produced_once_step_starts() {
+    let mut context = create_context(&[]);
+
+    let result = test_utils::expect_future_resolved(&mut context.futures).await;
+    context.execute_notification(result).await;
+
+    assert_eq!(
+        video_frames(&context.media_outputs),
+        vec![(Duration::from_secs(0), true)],
+        "Unexpected video frames"
+    );
+}
+
+#[test]
+fn stream_started_with_sequence_headers() {
+    let mut context = create_context(&[(STREAM_NAME, "abc")]);
+    let media = tick(&mut context);
+
+    assert!(media.len() >= 3, "Expected at least 3 media outputs");
+    match &media[0].content {
+        MediaNotificationContent::NewIncomingStream { stream_name } => {
+            assert_eq!(stream_name, "abc", "Unexpected stream name");
+        }
+
+        content => panic!("Expected new incoming stream, instead got {:?}", content),
+    }
+
+    match &media[1].content {
+        MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            is_sequence_header: true,
+            ..
+        } => (),
+
+        content => panic!("Expected video sequence header, instead got {:?}", content),
+    }
+
+    match &media[2].content {
+        MediaNotificationContent::Audio {
+            codec: AudioCodec::Aac,
+            is_sequence_header: true,
+            ..
+        } => (),
+
+        content => panic!("Expected audio sequence header, instead got {:?}", content),
+    }
+
+    assert!(
+        media.iter().all(|m| m.stream_id == media[0].stream_id),
+        "Expected all media to be for the same stream"
+    );
+}
+
+#[test]
+fn keyframe_produced_every_keyframe_interval() {
+    let mut context = create_context(&[(FPS, "10"), (KEYFRAME_INTERVAL, "3")]);
+    let mut media = Vec::new();
+    for _ in 0..7 {
+        media.extend(tick(&mut context));
+    }
+
+    let keyframes = video_frames(&media)
+        .into_iter()
+        .map(|(_, is_keyframe)| is_keyframe)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        keyframes,
+        vec![true, false, false, true, false, false, true],
+        "Unexpected keyframe pattern"
+    );
+}
+
+#[test]
+fn requested_keyframe_produced_on_next_frame_and_restarts_interval() {
+    let mut context = create_context(&[(FPS, "10"), (KEYFRAME_INTERVAL, "3")]);
+    let mut media = tick(&mut context);
+    media.extend(tick(&mut context));
+
+    let stream_id = media[0].stream_id.clone();
+    assert!(
+        context.step.request_keyframe(&stream_id),
+        "Expected keyframe request to be handled"
+    );
+
+    for _ in 0..4 {
+        media.extend(tick(&mut context));
+    }
+
+    let keyframes = video_frames(&media)
+        .into_iter()
+        .map(|(_, is_keyframe)| is_keyframe)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        keyframes,
+        vec![true, false, true, false, false, true],
+        "Unexpected keyframe pattern"
+    );
+}
+
+#[test]
+fn keyframe_request_for_unknown_stream_not_handled() {
+    let mut context = create_context(&[]);
+    tick(&mut context);
+
+    assert!(
+        !context.step.request_keyframe(&StreamId("abc".to_string())),
+        "Expected keyframe request to not be handled"
+    );
+}
+
+#[test]
+fn video_timestamps_advance_by_frame_duration() {
+    let mut context = create_context(&[(FPS, "25")]);
+    let mut media = Vec::new();
+    for _ in 0..3 {
+        media.extend(tick(&mut context));
+    }
+
+    let timestamps = video_frames(&media)
+        .into_iter()
+        .map(|(timestamp, _)| timestamp)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        timestamps,
+        vec![
+            Duration::from_millis(0),
+            Duration::from_millis(40),
+            Duration::from_millis(80)
+        ],
+        "Unexpected video timestamps"
+    );
+}
+
+#[test]
+fn audio_produced_up_to_each_video_frame() {
+    let mut context = create_context(&[(FPS, "10")]);
+    tick(&mut context);
+
+    let media = tick(&mut context);
+    let video_timestamp = video_frames(&media)[0].0;
+    let audio = audio_timestamps(&media);
+
+    assert!(!audio.is_empty(), "Expected audio to be produced");
+    assert!(
+        audio.iter().all(|timestamp| *timestamp <= video_timestamp),
+        "Expected no audio after the video timestamp of {:?}: {:?}",
+        video_timestamp,
+        audio
+    );
+
+    let next_audio = *audio.last().unwrap() + Duration::from_micros(21333);
+    assert!(
+        next_audio > video_timestamp,
+        "Expected all audio up to the video timestamp of {:?}: {:?}",
+        video_timestamp,
+        audio
+    );
+}
+
+#[test]
+fn stream_disconnected_once_duration_reached() {
+    let mut context = create_context(&[(FPS, "10"), (DURATION_SECONDS, "1")]);
+    let mut media = Vec::new();
+    for _ in 0..10 {
+        media.extend(tick(&mut context));
+    }
+
+    assert_eq!(
+        video_frames(&media).len(),
+        10,
+        "Unexpected number of frames"
+    );
+    assert!(
+        !media
+            .iter()
+            .any(|m| m.content == MediaNotificationContent::StreamDisconnected),
+        "Expected stream to not be disconnected yet"
+    );
+
+    let media = tick(&mut context);
+    assert_eq!(media.len(), 1, "Unexpected number of media outputs");
+    assert_eq!(
+        media[0].content,
+        MediaNotificationContent::StreamDisconnected,
+        "Expected stream to be disconnected"
+    );
+
+    assert!(
+        tick(&mut context).is_empty(),
+        "Expected no media after disconnection"
+    );
+}
@@ -0,0 +1,96 @@
+//! Utilities for unit testing workflow steps, used along with `StepTestContext` to drive steps
+//! directly with media and resolved future notifications without needing a workflow runner.
+
+use crate::codecs::{AudioCodec, VideoCodec};
+use crate::workflows::definitions::{WorkflowStepDefinition, WorkflowStepType};
+use crate::workflows::steps::StepFutureResult;
+use crate::workflows::{MediaNotification, MediaNotificationContent};
+use crate::{StreamId, VideoTimestamp};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// The payload used by the video and audio content helpers
+pub const TEST_MEDIA_DATA: &[u8] = &[1, 2, 3, 4];
+
+/// A single input to feed into a workflow step
+pub enum StepInput {
+    Media(MediaNotification),
+    Notification(Box<dyn StepFutureResult>),
+}
+
+impl From<MediaNotification> for StepInput {
+    fn from(media: MediaNotification) -> Self {
+        StepInput::Media(media)
+    }
+}
+
+impl From<Box<dyn StepFutureResult>> for StepInput {
+    fn from(notification: Box<dyn StepFutureResult>) -> Self {
+        StepInput::Notification(notification)
+    }
+}
+
+/// Creates a definition for the specified step type with the specified parameter values
+pub fn create_definition(step_type: &str, parameters: &[(&str, &str)]) -> WorkflowStepDefinition {
+    WorkflowStepDefinition {
+        step_type: WorkflowStepType(step_type.to_string()),
+        parameters: parameters
+            .iter()
+            .map(|(key, value)| (key.to_string(), Some(value.to_string())))
+            .collect(),
+    }
+}
+
+/// Creates a media notification for the specified stream with no tags
+pub fn media(stream_id: &str, content: MediaNotificationContent) -> MediaNotification {
+    MediaNotification {
+        stream_id: StreamId(stream_id.to_string()),
+        content,
+        tags: Vec::new(),
+    }
+}
+
+pub fn new_stream_content(stream_name: &str) -> MediaNotificationContent {
+    MediaNotificationContent::NewIncomingStream {
+        stream_name: stream_name.to_string(),
+    }
+}
+
+pub fn disconnected_content() -> MediaNotificationContent {
+    MediaNotificationContent::StreamDisconnected
+}
+
+/// Creates h264 video content containing `TEST_MEDIA_DATA`, with a pts equal to its dts
+pub fn video_content(
+    is_keyframe: bool,
+    is_sequence_header: bool,
+    dts: Duration,
+) -> MediaNotificationContent {
+    MediaNotificationContent::Video {
+        codec: VideoCodec::H264,
+        is_keyframe,
+        is_sequence_header,
+        data: Bytes::from_static(TEST_MEDIA_DATA),
+        timestamp: VideoTimestamp::from_durations(dts, dts),
+    }
+}
+
+/// Creates AAC audio content containing `TEST_MEDIA_DATA`
+pub fn audio_content(is_sequence_header: bool, timestamp: Duration) -> MediaNotificationContent {
+    MediaNotificationContent::Audio {
+        codec: AudioCodec::Aac,
+        is_sequence_header,
+        data: Bytes::from_static(TEST_MEDIA_DATA),
+        timestamp,
+    }
+}
+
+pub fn metadata_content(values: &[(&str, &str)]) -> MediaNotificationContent {
+    MediaNotificationContent::Metadata {
+        data: values
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>(),
+    }
+}
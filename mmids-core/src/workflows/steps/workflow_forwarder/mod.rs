@@ -1,17 +1,22 @@
 //! The workflow forwarder step takes all media notifications it receives and sends them to the
 //! specified workflow, using the workflow media relay. All media notifications are also passed
 //! to subsequent steps.
+//!
+//! When a reactor is used, the reactor is not queried for a stream until the stream's first
+//! metadata notification is received, so the reactor can pick workflows based on the format of
+//! the stream.  If audio or video arrives before any metadata, the reactor is queried without it.
 
 #[cfg(test)]
 mod tests;
 
 use crate::event_hub::{SubscriptionRequest, WorkflowStartedOrStoppedEvent};
 use crate::reactors::manager::ReactorManagerRequest;
-use crate::reactors::ReactorWorkflowUpdate;
+use crate::reactors::{ReactorWorkflowUpdate, StreamMetadata};
 use crate::workflows::definitions::WorkflowStepDefinition;
-use crate::workflows::steps::factory::StepGenerator;
+use crate::workflows::steps::factory::{StepGenerator, StepKind};
 use crate::workflows::steps::{
-    StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus, WorkflowStep,
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
 };
 use crate::workflows::{
     MediaNotification, MediaNotificationContent, WorkflowRequest, WorkflowRequestOperation,
@@ -33,9 +38,13 @@ pub struct WorkflowForwarderStepGenerator {
 }
 
 struct StreamDetails {
+    stream_name: String,
     target_workflow_names: HashSet<String>,
     required_media: Vec<MediaNotification>,
 
+    // If the reactor still needs to be queried for this stream's workflows
+    awaiting_reactor_query: bool,
+
     // Used to cancel the reactor update future. When a stream disconnects, this cancellation
     // channel will be dropped causing the future waiting for reactor updates to be closed. This
     // will inform the reactor that this step is no longer interested in whatever workflow it was
@@ -90,6 +99,11 @@ enum FutureResult {
 
 impl StepFutureResult for FutureResult {}
 
+struct StepParameters {
+    target_workflow_name: Option<String>,
+    reactor_name: Option<String>,
+}
+
 #[derive(Error, Debug)]
 enum StepStartupError {
     #[error("A {} or {} value must be specified", TARGET_WORKFLOW, REACTOR_NAME)]
@@ -99,6 +113,12 @@ enum StepStartupError {
     ReactorAndTargetWorkflowBothSpecified,
 }
 
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
 impl WorkflowForwarderStepGenerator {
     pub fn new(
         event_hub_subscriber: UnboundedSender<SubscriptionRequest>,
@@ -113,25 +133,10 @@ impl WorkflowForwarderStepGenerator {
 
 impl StepGenerator for WorkflowForwarderStepGenerator {
     fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
-        let target_workflow_name = match definition.parameters.get(TARGET_WORKFLOW) {
-            Some(Some(name)) => Some(name.clone()),
-            _ => None,
-        };
-
-        let reactor_name = match definition.parameters.get(REACTOR_NAME) {
-            Some(Some(reactor)) => Some(reactor.clone()),
-            _ => None,
-        };
-
-        if reactor_name.is_none() && target_workflow_name.is_none() {
-            return Err(Box::new(StepStartupError::NoTargetWorkflowSpecified));
-        }
-
-        if reactor_name.is_some() && target_workflow_name.is_some() {
-            return Err(Box::new(
-                StepStartupError::ReactorAndTargetWorkflowBothSpecified,
-            ));
-        }
+        let StepParameters {
+            target_workflow_name,
+            reactor_name,
+        } = parse_parameters(&definition)?;
 
         let (event_sender, event_receiver) = unbounded_channel();
         let _ = self
@@ -158,6 +163,42 @@ impl StepGenerator for WorkflowForwarderStepGenerator {
 
         Ok((Box::new(step), futures))
     }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_parameters(definition)?;
+        Ok(())
+    }
+
+    fn kind(&self) -> StepKind {
+        StepKind::Sink
+    }
+}
+
+fn parse_parameters(
+    definition: &WorkflowStepDefinition,
+) -> Result<StepParameters, StepStartupError> {
+    let target_workflow_name = match definition.parameters.get(TARGET_WORKFLOW) {
+        Some(Some(name)) => Some(name.clone()),
+        _ => None,
+    };
+
+    let reactor_name = match definition.parameters.get(REACTOR_NAME) {
+        Some(Some(reactor)) => Some(reactor.clone()),
+        _ => None,
+    };
+
+    if reactor_name.is_none() && target_workflow_name.is_none() {
+        return Err(StepStartupError::NoTargetWorkflowSpecified);
+    }
+
+    if reactor_name.is_some() && target_workflow_name.is_some() {
+        return Err(StepStartupError::ReactorAndTargetWorkflowBothSpecified);
+    }
+
+    Ok(StepParameters {
+        target_workflow_name,
+        reactor_name,
+    })
 }
 
 impl WorkflowForwarderStep {
@@ -214,8 +255,10 @@ impl WorkflowForwarderStep {
             MediaNotificationContent::NewIncomingStream { stream_name } => {
                 if !self.active_streams.contains_key(&media.stream_id) {
                     let mut stream_details = StreamDetails {
+                        stream_name: stream_name.clone(),
                         target_workflow_names: HashSet::new(),
                         required_media: vec![media.clone()],
+                        awaiting_reactor_query: self.reactor_name.is_some(),
                         _cancellation_channel: None,
                     };
 
@@ -231,26 +274,6 @@ impl WorkflowForwarderStep {
                         entry.insert(media.stream_id.clone());
                     }
 
-                    if let Some(reactor) = &self.reactor_name {
-                        let (sender, receiver) = unbounded_channel();
-                        let _ = self.reactor_manager.send(
-                            ReactorManagerRequest::CreateWorkflowForStreamName {
-                                reactor_name: reactor.clone(),
-                                stream_name: stream_name.clone(),
-                                response_channel: sender,
-                            },
-                        );
-
-                        outputs.futures.push(
-                            wait_for_reactor_response(
-                                media.stream_id.clone(),
-                                stream_name.clone(),
-                                receiver,
-                            )
-                            .boxed(),
-                        );
-                    }
-
                     self.active_streams
                         .insert(media.stream_id.clone(), stream_details);
                 }
@@ -278,26 +301,32 @@ impl WorkflowForwarderStep {
                 }
             }
 
-            MediaNotificationContent::Metadata { .. } => {
+            MediaNotificationContent::Metadata { data } => {
                 // I don't think this can be considered required, as I think closed captions and
                 // other data will come down as metadata that we don't want to permanently store.
+                let metadata = StreamMetadata::from_metadata(data);
+                self.query_reactor(&media.stream_id, Some(metadata), outputs);
             }
 
             MediaNotificationContent::Video {
-                is_sequence_header: true,
-                ..
+                is_sequence_header, ..
             } => {
-                if let Some(stream) = self.active_streams.get_mut(&media.stream_id) {
-                    stream.required_media.push(media.clone());
+                self.query_reactor(&media.stream_id, None, outputs);
+                if *is_sequence_header {
+                    if let Some(stream) = self.active_streams.get_mut(&media.stream_id) {
+                        stream.required_media.push(media.clone());
+                    }
                 }
             }
 
             MediaNotificationContent::Audio {
-                is_sequence_header: true,
-                ..
+                is_sequence_header, ..
             } => {
-                if let Some(stream) = self.active_streams.get_mut(&media.stream_id) {
-                    stream.required_media.push(media.clone());
+                self.query_reactor(&media.stream_id, None, outputs);
+                if *is_sequence_header {
+                    if let Some(stream) = self.active_streams.get_mut(&media.stream_id) {
+                        stream.required_media.push(media.clone());
+                    }
                 }
             }
 
@@ -320,6 +349,46 @@ impl WorkflowForwarderStep {
         outputs.media.push(media);
     }
 
+    /// Asks the reactor which workflows the stream should be forwarded to, if the reactor hasn't
+    /// been queried for the stream yet
+    fn query_reactor(
+        &mut self,
+        stream_id: &StreamId,
+        metadata: Option<StreamMetadata>,
+        outputs: &mut StepOutputs,
+    ) {
+        let reactor = match &self.reactor_name {
+            Some(reactor) => reactor,
+            None => return,
+        };
+
+        let stream = match self.active_streams.get_mut(stream_id) {
+            Some(stream) => stream,
+            None => return,
+        };
+
+        if !stream.awaiting_reactor_query {
+            return;
+        }
+
+        stream.awaiting_reactor_query = false;
+
+        let (sender, receiver) = unbounded_channel();
+        let _ = self
+            .reactor_manager
+            .send(ReactorManagerRequest::CreateWorkflowForStreamName {
+                reactor_name: reactor.clone(),
+                stream_name: stream.stream_name.clone(),
+                metadata,
+                response_channel: sender,
+            });
+
+        outputs.futures.push(
+            wait_for_reactor_response(stream_id.clone(), stream.stream_name.clone(), receiver)
+                .boxed(),
+        );
+    }
+
     fn handle_reactor_update(
         &mut self,
         stream_id: StreamId,
@@ -365,6 +434,7 @@ impl WorkflowForwarderStep {
                                 media: MediaNotification {
                                     stream_id: stream_id.clone(),
                                     content: MediaNotificationContent::StreamDisconnected,
+                                    tags: Vec::new(),
                                 },
                             },
                         });
@@ -413,6 +483,7 @@ impl WorkflowForwarderStep {
                                 media: MediaNotification {
                                     stream_id: stream_id.clone(),
                                     content: MediaNotificationContent::StreamDisconnected,
+                                    tags: Vec::new(),
                                 },
                             },
                         });
@@ -554,6 +625,7 @@ impl WorkflowStep for WorkflowForwarderStep {
                                         media: MediaNotification {
                                             stream_id: stream_id.clone(),
                                             content: MediaNotificationContent::StreamDisconnected,
+                                            tags: Vec::new(),
                                         },
                                     },
                                 });
@@ -602,6 +674,7 @@ impl WorkflowStep for WorkflowForwarderStep {
                             media: MediaNotification {
                                 stream_id: stream_id.clone(),
                                 content: MediaNotificationContent::StreamDisconnected,
+                                tags: Vec::new(),
                             },
                         },
                     });
@@ -640,6 +713,7 @@ async fn wait_for_reactor_response(
         None => ReactorWorkflowUpdate {
             is_valid: false,
             routable_workflow_names: HashSet::new(),
+            definition_hash: None,
         },
     };
 
@@ -103,6 +103,45 @@ impl TestContext {
     }
 }
 
+#[test]
+fn validation_fails_when_both_workflow_and_reactor_specified() {
+    let generator =
+        WorkflowForwarderStepGenerator::new(unbounded_channel().0, unbounded_channel().0);
+    let mut definition = WorkflowStepDefinition {
+        step_type: WorkflowStepType("".to_string()),
+        parameters: HashMap::new(),
+    };
+
+    definition
+        .parameters
+        .insert(REACTOR_NAME.to_string(), Some("reactor".to_string()));
+    definition
+        .parameters
+        .insert(TARGET_WORKFLOW.to_string(), Some("workflow".to_string()));
+
+    assert!(generator.validate(&definition).is_err());
+}
+
+#[test]
+fn validation_does_not_subscribe_to_event_hub() {
+    let (sub_sender, mut sub_receiver) = unbounded_channel();
+    let generator = WorkflowForwarderStepGenerator::new(sub_sender, unbounded_channel().0);
+    let mut definition = WorkflowStepDefinition {
+        step_type: WorkflowStepType("".to_string()),
+        parameters: HashMap::new(),
+    };
+
+    definition
+        .parameters
+        .insert(TARGET_WORKFLOW.to_string(), Some("workflow".to_string()));
+
+    generator.validate(&definition).unwrap();
+    assert!(
+        sub_receiver.try_recv().is_err(),
+        "Expected no event hub subscriptions"
+    );
+}
+
 #[tokio::test]
 async fn new_stream_message_sent_to_global_workflow() {
     let mut context = TestContext::new(Some("test"), None).await.unwrap();
@@ -113,6 +152,7 @@ async fn new_stream_message_sent_to_global_workflow() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     let response = test_utils::expect_mpsc_response(&mut context.workflow_receiver).await;
@@ -140,6 +180,7 @@ async fn new_stream_message_sent_if_workflow_started_after_message_comes_in() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     test_utils::expect_mpsc_timeout(&mut context.workflow_receiver).await;
@@ -172,6 +213,7 @@ async fn no_message_passed_if_workflow_has_different_name_than_global_name() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     test_utils::expect_mpsc_timeout(&mut context.workflow_receiver).await;
@@ -188,6 +230,7 @@ async fn no_message_passed_if_workflow_stopped_before_media_sent() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     test_utils::expect_mpsc_timeout(&mut context.workflow_receiver).await;
@@ -202,11 +245,13 @@ async fn no_message_passed_if_stream_disconnected_before_workflow_started() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     context.step_context.execute_with_media(MediaNotification {
         stream_id: StreamId("abc".to_string()),
         content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
     });
 
     context.send_workflow_started_event("test", None).await;
@@ -222,6 +267,7 @@ async fn new_stream_media_passed_as_output_immediately() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     assert_eq!(
@@ -249,6 +295,7 @@ async fn stream_disconnected_media_passed_as_output_immediately() {
     context.step_context.execute_with_media(MediaNotification {
         stream_id: StreamId("abc".to_string()),
         content: MediaNotificationContent::StreamDisconnected,
+        tags: Vec::new(),
     });
 
     assert_eq!(
@@ -283,6 +330,7 @@ async fn video_media_passed_as_output_immediately() {
             is_keyframe: true,
             is_sequence_header: true,
         },
+        tags: Vec::new(),
     });
 
     assert_eq!(
@@ -326,6 +374,7 @@ async fn audio_media_passed_as_output_immediately() {
             timestamp: Duration::from_millis(5),
             is_sequence_header: true,
         },
+        tags: Vec::new(),
     });
 
     assert_eq!(
@@ -366,6 +415,7 @@ async fn metadata_media_passed_as_output_immediately() {
         content: MediaNotificationContent::Metadata {
             data: metadata.clone(),
         },
+        tags: Vec::new(),
     });
 
     assert_eq!(
@@ -394,6 +444,7 @@ async fn video_sequence_headers_sent_to_workflow_when_received_before_workflow_s
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     context.step_context.execute_with_media(MediaNotification {
@@ -408,6 +459,7 @@ async fn video_sequence_headers_sent_to_workflow_when_received_before_workflow_s
             is_keyframe: true,
             is_sequence_header: true,
         },
+        tags: Vec::new(),
     });
 
     test_utils::expect_mpsc_timeout(&mut context.workflow_receiver).await;
@@ -442,6 +494,7 @@ async fn non_video_sequence_headers_not_sent_to_workflow_when_received_before_wo
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     context.step_context.execute_with_media(MediaNotification {
@@ -456,6 +509,7 @@ async fn non_video_sequence_headers_not_sent_to_workflow_when_received_before_wo
             is_keyframe: true,
             is_sequence_header: false,
         },
+        tags: Vec::new(),
     });
 
     test_utils::expect_mpsc_timeout(&mut context.workflow_receiver).await;
@@ -482,6 +536,7 @@ async fn audio_sequence_headers_sent_to_workflow_when_received_before_workflow_s
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     context.step_context.execute_with_media(MediaNotification {
@@ -492,6 +547,7 @@ async fn audio_sequence_headers_sent_to_workflow_when_received_before_workflow_s
             timestamp: Duration::from_millis(5),
             is_sequence_header: true,
         },
+        tags: Vec::new(),
     });
 
     test_utils::expect_mpsc_timeout(&mut context.workflow_receiver).await;
@@ -526,6 +582,7 @@ async fn non_audio_sequence_headers_not_sent_to_workflow_when_received_before_wo
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     context.step_context.execute_with_media(MediaNotification {
@@ -536,6 +593,7 @@ async fn non_audio_sequence_headers_not_sent_to_workflow_when_received_before_wo
             timestamp: Duration::from_millis(5),
             is_sequence_header: false,
         },
+        tags: Vec::new(),
     });
 
     test_utils::expect_mpsc_timeout(&mut context.workflow_receiver).await;
@@ -562,6 +620,7 @@ async fn metadata_not_sent_when_received_before_workflow_starts() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
     });
 
     context.step_context.execute_with_media(MediaNotification {
@@ -569,6 +628,7 @@ async fn metadata_not_sent_when_received_before_workflow_starts() {
         content: MediaNotificationContent::Metadata {
             data: HashMap::new(),
         },
+        tags: Vec::new(),
     });
 
     test_utils::expect_mpsc_timeout(&mut context.workflow_receiver).await;
@@ -588,13 +648,39 @@ async fn metadata_not_sent_when_received_before_workflow_starts() {
 }
 
 #[tokio::test]
-async fn new_stream_triggers_reactor_query() {
+async fn new_stream_does_not_trigger_reactor_query() {
+    let mut context = TestContext::new(None, Some("test")).await.unwrap();
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        tags: Vec::new(),
+    });
+
+    test_utils::expect_mpsc_timeout(&mut context.reactor_manager).await;
+}
+
+#[tokio::test]
+async fn metadata_triggers_reactor_query_with_stream_metadata() {
     let mut context = TestContext::new(None, Some("test")).await.unwrap();
     context.step_context.execute_with_media(MediaNotification {
         stream_id: StreamId("abc".to_string()),
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
+    });
+
+    let mut data = HashMap::new();
+    data.insert("width".to_string(), "1920".to_string());
+    data.insert("height".to_string(), "1080".to_string());
+    data.insert("videocodecid".to_string(), "avc1".to_string());
+
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Metadata { data },
+        tags: Vec::new(),
     });
 
     let response = test_utils::expect_mpsc_response(&mut context.reactor_manager).await;
@@ -602,16 +688,97 @@ async fn new_stream_triggers_reactor_query() {
         ReactorManagerRequest::CreateWorkflowForStreamName {
             reactor_name,
             stream_name,
+            metadata,
             ..
         } => {
             assert_eq!(&reactor_name, "test", "Unexpected reactor name");
             assert_eq!(&stream_name, "def", "Unexpected stream name");
+            assert_eq!(
+                metadata,
+                Some(StreamMetadata {
+                    video_codec: Some("avc1".to_string()),
+                    video_width: Some(1920),
+                    video_height: Some(1080),
+                    ..StreamMetadata::default()
+                }),
+                "Unexpected stream metadata"
+            );
+        }
+
+        response => panic!("Unexpected request: {:?}", response),
+    }
+}
+
+#[tokio::test]
+async fn video_before_metadata_triggers_reactor_query_without_metadata() {
+    let mut context = TestContext::new(None, Some("test")).await.unwrap();
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        tags: Vec::new(),
+    });
+
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Video {
+            codec: VideoCodec::H264,
+            is_sequence_header: true,
+            is_keyframe: true,
+            data: Bytes::from(vec![1, 2, 3]),
+            timestamp: VideoTimestamp::from_zero(),
+        },
+        tags: Vec::new(),
+    });
+
+    let response = test_utils::expect_mpsc_response(&mut context.reactor_manager).await;
+    match response {
+        ReactorManagerRequest::CreateWorkflowForStreamName {
+            stream_name,
+            metadata,
+            ..
+        } => {
+            assert_eq!(&stream_name, "def", "Unexpected stream name");
+            assert_eq!(metadata, None, "Expected no stream metadata");
         }
 
         response => panic!("Unexpected request: {:?}", response),
     }
 }
 
+#[tokio::test]
+async fn reactor_only_queried_once_per_stream() {
+    let mut context = TestContext::new(None, Some("test")).await.unwrap();
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::NewIncomingStream {
+            stream_name: "def".to_string(),
+        },
+        tags: Vec::new(),
+    });
+
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Metadata {
+            data: HashMap::new(),
+        },
+        tags: Vec::new(),
+    });
+
+    let _ = test_utils::expect_mpsc_response(&mut context.reactor_manager).await;
+
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Metadata {
+            data: HashMap::new(),
+        },
+        tags: Vec::new(),
+    });
+
+    test_utils::expect_mpsc_timeout(&mut context.reactor_manager).await;
+}
+
 #[tokio::test]
 async fn new_stream_passed_to_all_specified_routable_workflow() {
     let mut context = TestContext::new(None, Some("test")).await.unwrap();
@@ -620,6 +787,15 @@ async fn new_stream_passed_to_all_specified_routable_workflow() {
         content: MediaNotificationContent::NewIncomingStream {
             stream_name: "def".to_string(),
         },
+        tags: Vec::new(),
+    });
+
+    context.step_context.execute_with_media(MediaNotification {
+        stream_id: StreamId("abc".to_string()),
+        content: MediaNotificationContent::Metadata {
+            data: HashMap::new(),
+        },
+        tags: Vec::new(),
     });
 
     let response = test_utils::expect_mpsc_response(&mut context.reactor_manager).await;
@@ -635,6 +811,7 @@ async fn new_stream_passed_to_all_specified_routable_workflow() {
                 .send(ReactorWorkflowUpdate {
                     is_valid: true,
                     routable_workflow_names: workflows,
+                    definition_hash: None,
                 })
                 .expect("Failed to send reactor response");
         }
@@ -10,7 +10,7 @@ mod video_x264;
 
 use anyhow::{Context, Result};
 use bytes::Bytes;
-use gstreamer::{Format, GenericFormattedValue, Pipeline};
+use gstreamer::{BufferFlags, Format, GenericFormattedValue, Pipeline};
 use gstreamer_app::AppSink;
 use mmids_core::codecs::{AudioCodec, VideoCodec};
 use mmids_core::workflows::MediaNotificationContent;
@@ -39,6 +39,18 @@ pub trait VideoEncoder {
         timestamp: VideoTimestamp,
         is_sequence_header: bool,
     ) -> Result<()>;
+
+    /// Returns true if the encoder can be asked to make its next frame a keyframe.  Encoders that
+    /// don't produce their own video (such as ones that copy or drop it) can't.
+    fn supports_keyframe_requests(&self) -> bool {
+        false
+    }
+
+    /// Asks the encoder to make the next frame it produces a keyframe.  Only called on encoders
+    /// that support keyframe requests.
+    fn request_keyframe(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// An encoder that processes audio in its pipeline.  It is expected that each instance of an
@@ -188,6 +200,7 @@ pub struct SampleResult {
     content: Bytes,
     dts: Option<Duration>,
     pts: Option<Duration>,
+    is_keyframe: bool,
 }
 
 impl SampleResult {
@@ -200,6 +213,7 @@ impl SampleResult {
             .map_readable()
             .with_context(|| "Sample's buffer could not be mapped as readable")?;
 
+        let is_keyframe = !buffer.flags().contains(BufferFlags::DELTA_UNIT);
         let mut dts = buffer.dts();
         let mut pts = buffer.pts();
 
@@ -238,6 +252,7 @@ impl SampleResult {
             content: Bytes::copy_from_slice(map.as_slice()),
             dts,
             pts,
+            is_keyframe,
         })
     }
 
@@ -2,8 +2,9 @@ use crate::encoders::{SampleResult, VideoEncoder, VideoEncoderGenerator};
 use crate::utils::{create_gst_element, get_codec_data_from_element};
 use anyhow::{anyhow, Context, Result};
 use bytes::Bytes;
+use gstreamer::event::CustomUpstream;
 use gstreamer::prelude::*;
-use gstreamer::{Caps, Element, FlowError, FlowSuccess, Fraction, Pipeline};
+use gstreamer::{Caps, Element, FlowError, FlowSuccess, Fraction, Pipeline, Structure};
 use gstreamer_app::{AppSink, AppSinkCallbacks, AppSrc};
 use mmids_core::codecs::VideoCodec;
 use mmids_core::workflows::MediaNotificationContent;
@@ -23,6 +24,9 @@ use tracing::{error, warn};
 /// * `preset` - The `speed-preset` value to use in the encoder.  Valid values are: `ultrafast`,
 /// `superfast`, `veryfast`, `faster`, `fast`, `medium`, `slow`, `slower`, `veryslow`.  The default
 /// is `medium`.
+///
+/// The encoder supports keyframe requests, which cause `x264enc` to encode the next frame as an
+/// IDR frame.
 pub struct X264EncoderGenerator {}
 
 impl VideoEncoderGenerator for X264EncoderGenerator {
@@ -42,6 +46,7 @@ impl VideoEncoderGenerator for X264EncoderGenerator {
 
 struct X264Encoder {
     source: AppSrc,
+    encoder: Element,
 }
 
 impl X264Encoder {
@@ -165,7 +170,10 @@ impl X264Encoder {
             .dynamic_cast::<AppSrc>()
             .or_else(|_| Err(anyhow!("source element could not be cast to 'Appsrc'")))?;
 
-        Ok(X264Encoder { source: appsrc })
+        Ok(X264Encoder {
+            source: appsrc,
+            encoder,
+        })
     }
 }
 
@@ -192,6 +200,31 @@ impl VideoEncoder for X264Encoder {
 
         Ok(())
     }
+
+    fn supports_keyframe_requests(&self) -> bool {
+        true
+    }
+
+    fn request_keyframe(&self) -> Result<()> {
+        // This is the structure of the upstream force key unit event that `gstreamer-video`
+        // creates.  It's built by hand so that crate isn't needed just for this one event.
+        let structure = Structure::builder("GstForceKeyUnit")
+            .field("running-time", u64::MAX) // GST_CLOCK_TIME_NONE, meaning as soon as possible
+            .field("all-headers", true)
+            .field("count", 0u32)
+            .build();
+
+        let pad = self
+            .encoder
+            .static_pad("src")
+            .with_context(|| "x264enc had no src pad")?;
+
+        if !pad.send_event(CustomUpstream::new(structure)) {
+            return Err(anyhow!("x264enc did not accept the force key unit event"));
+        }
+
+        Ok(())
+    }
 }
 
 fn get_number(parameters: &HashMap<String, Option<String>>, key: &str) -> Option<u32> {
@@ -234,7 +267,7 @@ fn sample_received(
         codec: VideoCodec::H264,
         timestamp: sample.to_video_timestamp(),
         is_sequence_header: false,
-        is_keyframe: false, // TODO, figure out how to compute this
+        is_keyframe: sample.is_keyframe,
         data: sample.content,
     });
 
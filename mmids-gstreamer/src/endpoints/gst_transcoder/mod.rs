@@ -51,6 +51,13 @@ pub enum GstTranscoderRequest {
         /// The identifier of the transcoding process to stop.
         id: Uuid,
     },
+
+    /// Asks the video encoder of a transcoding process to make its next frame a keyframe.  Only
+    /// acted on if the encoder supports keyframe requests.
+    RequestKeyframe {
+        /// The identifier of the transcoding process to request the keyframe from
+        id: Uuid,
+    },
 }
 
 /// Notifications the transcoding endpoint can raise
@@ -59,6 +66,9 @@ pub enum GstTranscoderNotification {
     TranscodingStarted {
         /// Channel in which resulting audio and video data will be sent to
         output_media: UnboundedReceiver<MediaNotificationContent>,
+
+        /// If the video encoder can be asked to produce a keyframe on demand
+        supports_keyframe_requests: bool,
     },
 
     /// Notification that transcoding stopped
@@ -231,6 +241,14 @@ impl EndpointActor {
                         .send(TranscodeManagerRequest::StopTranscode);
                 }
             }
+
+            GstTranscoderRequest::RequestKeyframe { id } => {
+                if let Some(transcode) = self.active_transcodes.get(&id) {
+                    let _ = transcode
+                        .sender
+                        .send(TranscodeManagerRequest::RequestKeyframe);
+                }
+            }
         }
     }
 
@@ -313,6 +331,7 @@ impl EndpointActor {
             }
         };
 
+        let supports_keyframe_requests = video_encoder.supports_keyframe_requests();
         let parameters = TranscoderParams {
             pipeline,
             video_encoder,
@@ -326,6 +345,7 @@ impl EndpointActor {
 
         let _ = notification_channel.send(GstTranscoderNotification::TranscodingStarted {
             output_media: outbound_media_receiver,
+            supports_keyframe_requests,
         });
 
         self.futures
@@ -7,11 +7,12 @@ use gstreamer::prelude::*;
 use gstreamer::{MessageView, Pipeline, State};
 use mmids_core::workflows::MediaNotificationContent;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
-use tracing::{error, info, instrument};
+use tracing::{error, info, instrument, warn};
 use uuid::Uuid;
 
 pub enum TranscodeManagerRequest {
     StopTranscode,
+    RequestKeyframe,
 }
 
 pub struct TranscoderParams {
@@ -218,6 +219,15 @@ impl TranscodeManager {
             TranscodeManagerRequest::StopTranscode => {
                 self.termination_requested = true;
             }
+
+            TranscodeManagerRequest::RequestKeyframe => {
+                if let Err(error) = self.video_encoder.request_keyframe() {
+                    warn!(
+                        "Failed to request a keyframe from the video encoder: {:?}",
+                        error
+                    );
+                }
+            }
         }
     }
 }
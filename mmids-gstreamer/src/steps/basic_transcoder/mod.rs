@@ -6,6 +6,10 @@
 //! parameter with either `audio_` or `video_`.  These prefixes allow the workflow step to know
 //! which encoder to route the each parameter to.   The prefix is removed from the parameter before
 //! passing it to the encoder, so `video_bitrate` gets passed to the video encoder as `bitrate`.
+//!
+//! Keyframe requests from later steps are acted on by asking the video encoder to make its next
+//! frame a keyframe, as long as the video encoder supports it (e.g. `x264`).  Otherwise (such as
+//! with the `copy` encoder) the request is passed on to the steps before this one.
 
 use crate::endpoints::gst_transcoder::{
     GstTranscoderNotification, GstTranscoderRequest, GstTranscoderStoppedCause,
@@ -14,7 +18,8 @@ use futures::FutureExt;
 use mmids_core::workflows::definitions::WorkflowStepDefinition;
 use mmids_core::workflows::steps::factory::StepGenerator;
 use mmids_core::workflows::steps::{
-    StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus, WorkflowStep,
+    StepCreationError, StepCreationResult, StepFutureResult, StepInputs, StepOutputs, StepStatus,
+    StepValidationResult, WorkflowStep,
 };
 use mmids_core::workflows::{MediaNotification, MediaNotificationContent};
 use mmids_core::StreamId;
@@ -37,6 +42,7 @@ struct ActiveTranscode {
     media_sender: UnboundedSender<MediaNotificationContent>,
     transcode_process_id: Uuid,
     stream_name: String,
+    supports_keyframe_requests: bool,
 }
 
 struct BasicTranscodeStep {
@@ -69,6 +75,13 @@ enum FutureResult {
 
 impl StepFutureResult for FutureResult {}
 
+struct StepParameters {
+    video_encoder_name: String,
+    audio_encoder_name: String,
+    video_params: HashMap<String, Option<String>>,
+    audio_params: HashMap<String, Option<String>>,
+}
+
 #[derive(thiserror::Error, Debug)]
 enum StepStartupError {
     #[error("No video encoder specified")]
@@ -78,6 +91,12 @@ enum StepStartupError {
     NoAudioEncoderSpecified,
 }
 
+impl From<StepStartupError> for StepCreationError {
+    fn from(error: StepStartupError) -> Self {
+        StepCreationError::InvalidConfiguration(Box::new(error))
+    }
+}
+
 impl BasicTranscodeStepGenerator {
     pub fn new(
         transcode_endpoint: UnboundedSender<GstTranscoderRequest>,
@@ -88,28 +107,12 @@ impl BasicTranscodeStepGenerator {
 
 impl StepGenerator for BasicTranscodeStepGenerator {
     fn generate(&self, definition: WorkflowStepDefinition) -> StepCreationResult {
-        let video_encoder_name = match definition.parameters.get(VIDEO_ENCODER) {
-            Some(Some(encoder)) => encoder.clone(),
-            _ => return Err(Box::new(StepStartupError::NoVideoEncoderSpecified)),
-        };
-
-        let audio_encoder_name = match definition.parameters.get(AUDIO_ENCODER) {
-            Some(Some(encoder)) => encoder.clone(),
-            _ => return Err(Box::new(StepStartupError::NoAudioEncoderSpecified)),
-        };
-
-        // Split out audio and video specific parameters based on prefixes.
-        let mut audio_params = HashMap::new();
-        let mut video_params = HashMap::new();
-        for (key, value) in &definition.parameters {
-            if key.starts_with(VIDEO_PARAM_PREFIX) && key.len() > VIDEO_PARAM_PREFIX.len() {
-                video_params.insert(key[VIDEO_PARAM_PREFIX.len()..].to_string(), value.clone());
-            }
-
-            if key.starts_with(AUDIO_PARAM_PREFIX) && key.len() > AUDIO_PARAM_PREFIX.len() {
-                audio_params.insert(key[AUDIO_PARAM_PREFIX.len()..].to_string(), value.clone());
-            }
-        }
+        let StepParameters {
+            video_encoder_name,
+            audio_encoder_name,
+            video_params,
+            audio_params,
+        } = parse_parameters(&definition)?;
 
         let step = BasicTranscodeStep {
             definition: definition.clone(),
@@ -126,6 +129,45 @@ impl StepGenerator for BasicTranscodeStepGenerator {
 
         Ok((Box::new(step), futures))
     }
+
+    fn validate(&self, definition: &WorkflowStepDefinition) -> StepValidationResult {
+        parse_parameters(definition)?;
+        Ok(())
+    }
+}
+
+fn parse_parameters(
+    definition: &WorkflowStepDefinition,
+) -> Result<StepParameters, StepStartupError> {
+    let video_encoder_name = match definition.parameters.get(VIDEO_ENCODER) {
+        Some(Some(encoder)) => encoder.clone(),
+        _ => return Err(StepStartupError::NoVideoEncoderSpecified),
+    };
+
+    let audio_encoder_name = match definition.parameters.get(AUDIO_ENCODER) {
+        Some(Some(encoder)) => encoder.clone(),
+        _ => return Err(StepStartupError::NoAudioEncoderSpecified),
+    };
+
+    // Split out audio and video specific parameters based on prefixes.
+    let mut audio_params = HashMap::new();
+    let mut video_params = HashMap::new();
+    for (key, value) in &definition.parameters {
+        if key.starts_with(VIDEO_PARAM_PREFIX) && key.len() > VIDEO_PARAM_PREFIX.len() {
+            video_params.insert(key[VIDEO_PARAM_PREFIX.len()..].to_string(), value.clone());
+        }
+
+        if key.starts_with(AUDIO_PARAM_PREFIX) && key.len() > AUDIO_PARAM_PREFIX.len() {
+            audio_params.insert(key[AUDIO_PARAM_PREFIX.len()..].to_string(), value.clone());
+        }
+    }
+
+    Ok(StepParameters {
+        video_encoder_name,
+        audio_encoder_name,
+        video_params,
+        audio_params,
+    })
 }
 
 impl BasicTranscodeStep {
@@ -178,6 +220,7 @@ impl BasicTranscodeStep {
                 transcode_process_id: process_id.clone(),
                 media_sender,
                 stream_name: stream_name.clone(),
+                supports_keyframe_requests: false,
             },
         );
 
@@ -228,6 +271,8 @@ impl BasicTranscodeStep {
             }
 
             MediaNotificationContent::Metadata { .. } => (),
+            MediaNotificationContent::Discontinuity => outputs.media.push(media),
+            MediaNotificationContent::Cue { .. } => outputs.media.push(media),
         }
     }
 
@@ -256,7 +301,14 @@ impl BasicTranscodeStep {
                 }
             }
 
-            GstTranscoderNotification::TranscodingStarted { output_media } => {
+            GstTranscoderNotification::TranscodingStarted {
+                output_media,
+                supports_keyframe_requests,
+            } => {
+                if let Some(transcode) = self.active_transcodes.get_mut(&stream_id) {
+                    transcode.supports_keyframe_requests = supports_keyframe_requests;
+                }
+
                 outputs
                     .futures
                     .push(notify_on_transcoder_media(output_media, stream_id).boxed());
@@ -338,15 +390,27 @@ impl WorkflowStep for BasicTranscodeStep {
                         .futures
                         .push(notify_on_transcoder_media(receiver, stream_id.clone()).boxed());
 
-                    outputs.media.push(MediaNotification {
-                        stream_id,
-                        content: media,
-                    });
+                    outputs.media.push(MediaNotification::new(stream_id, media));
                 }
             }
         }
     }
 
+    fn request_keyframe(&mut self, stream_id: &StreamId) -> bool {
+        let transcode = match self.active_transcodes.get(stream_id) {
+            Some(transcode) if transcode.supports_keyframe_requests => transcode,
+            _ => return false,
+        };
+
+        let _ = self
+            .transcoder_endpoint
+            .send(GstTranscoderRequest::RequestKeyframe {
+                id: transcode.transcode_process_id,
+            });
+
+        true
+    }
+
     fn shutdown(&mut self) {
         self.status = StepStatus::Shutdown;
     }
@@ -71,6 +71,7 @@ fn hls_test() -> FfmpegParams {
             path: "c:\\temp\\test\\hlstest.m3u8".to_string(),
             max_entries: None,
             segment_length: 2,
+            delete_old_segments: false,
         },
     }
 }
@@ -2,9 +2,9 @@ use log::{error, info, warn};
 use mmids_core::net::tcp::start_socket_manager;
 
 use mmids_core::endpoints::rtmp_server::{
-    start_rtmp_server_endpoint, IpRestriction, RtmpEndpointMediaData, RtmpEndpointMediaMessage,
-    RtmpEndpointPublisherMessage, RtmpEndpointRequest, RtmpEndpointWatcherNotification,
-    StreamKeyRegistration,
+    start_rtmp_server_endpoint, IpRestriction, RtmpAppMatching, RtmpEndpointMediaData,
+    RtmpEndpointMediaMessage, RtmpEndpointPublisherMessage, RtmpEndpointRequest,
+    RtmpEndpointWatcherNotification, StreamKeyRegistration,
 };
 
 use std::collections::HashMap;
@@ -22,12 +22,14 @@ pub async fn main() {
     let _ = rtmp_server_sender.send(RtmpEndpointRequest::ListenForPublishers {
         port: 1935,
         rtmp_app: "live".to_string(),
+        rtmp_app_matching: RtmpAppMatching::Exact,
         rtmp_stream_key: StreamKeyRegistration::Any,
         message_channel: rtmp_response_sender,
         stream_id: None,
         ip_restrictions: IpRestriction::None,
         use_tls: false,
         requires_registrant_approval: false,
+        max_connects_per_minute: None,
     });
 
     info!("Requesting to listen for publish requests on port 1935 and app 'live'");
@@ -55,10 +57,13 @@ pub async fn main() {
         rtmp_app: "live".to_string(),
         rtmp_stream_key: StreamKeyRegistration::Any,
         media_channel: media_receiver,
+        media_backlog: None,
         notification_channel: notification_sender,
         ip_restrictions: IpRestriction::None,
         use_tls: false,
         requires_registrant_approval: false,
+        max_watchers: None,
+        start_on_keyframe: false,
     });
 
     info!("Requesting to listening for play requests on port 1935 and app 'live'");
@@ -96,6 +101,10 @@ pub async fn main() {
                         info!("Stream key '{}' no longer has any watchers", stream_key);
                     }
 
+                    RtmpEndpointWatcherNotification::WatcherJoined {stream_key} => {
+                        info!("New watcher joined stream key '{}'", stream_key);
+                    }
+
                     event => {
                         info!("Unexpected watcher notification: {:?}", event);
                     }